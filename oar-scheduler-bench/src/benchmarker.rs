@@ -276,13 +276,13 @@ impl BenchmarkConfig {
             let sample_type = self.sample_type;
             tokio::spawn(async move {
                 let waiting_jobs = get_sample_waiting_jobs(res_count, jobs_count, sample_type, new_seed.wrapping_mul(1 + i as u64));
-                let cache_hits = count_cache_hits(&waiting_jobs);
+                let expected_cache_hits = count_cache_hits(&waiting_jobs);
 
                 let platform_config = platform_mock::generate_mock_platform_config(cache, res_count, 24, 4, 64, false);
                 let mut platform = PlatformBenchMock::new(platform_config, vec![], waiting_jobs);
                 let queues = vec!["default".to_string()];
 
-                let (scheduling_time, slot_count) = match target {
+                let (scheduling_time, stats) = match target {
                     BenchmarkTarget::Rust => measure_time(|| schedule_cycle(&mut platform, &queues)),
                     BenchmarkTarget::Python => schedule_cycle_on_oar_python(&mut platform, queues, false),
                     BenchmarkTarget::RustFromPython => schedule_cycle_on_oar_python(&mut platform, queues, true),
@@ -293,29 +293,27 @@ impl BenchmarkConfig {
                 //     println!("{}: {:>width$} -> {:>width$} | {}", j.id, j.begin().unwrap(), j.end().unwrap(), j.assignment.clone().unwrap().proc_set);
                 // });
 
-                let quotas_hits = platform.get_scheduled_jobs().iter().map(|j| j.quotas_hit_count).sum::<u32>();
-                let gantt_width = platform
-                    .get_scheduled_jobs()
-                    .iter()
-                    .map(|j| j.assignment.clone().unwrap().end)
-                    .max()
-                    .unwrap_or(0);
+                // The Python scheduler does not report its cache hits, the expected ones are used instead.
+                let cache_hits = match target {
+                    BenchmarkTarget::Rust => stats.cache_hits,
+                    BenchmarkTarget::Python | BenchmarkTarget::RustFromPython => expected_cache_hits,
+                };
                 let optimal_gantt_width = (platform
                     .get_scheduled_jobs()
                     .iter()
                     .map(|j| j.assignment.clone().unwrap())
                     .map(|sd| sd.resources.core_count() as i64 * (sd.end - sd.begin + 1))
                     .sum::<i64>()
-                    / res_count as i64) as u32;
+                    / (res_count as i64).max(1)) as u32;
 
                 BenchmarkResult::new(
                     jobs_count as u32,
-                    platform.get_scheduled_jobs().len() as u32,
+                    stats.scheduled as u32,
                     scheduling_time,
                     (cache_hits * 100 / jobs_count) as u32,
-                    slot_count as u32,
-                    quotas_hits * 100 / jobs_count as u32,
-                    gantt_width as u32,
+                    stats.final_slot_count as u32,
+                    stats.quotas_hits * 100 / jobs_count as u32,
+                    stats.gantt_width as u32,
                     optimal_gantt_width,
                 )
             })
@@ -2,24 +2,26 @@ mod benchmarker;
 mod grapher;
 mod python_caller;
 mod platform_mock;
+#[cfg(test)]
+mod scenario_test;
 
-use crate::benchmarker::{get_sample_waiting_jobs, BenchmarkConfig, BenchmarkTarget, WaitingJobsSampleType};
+use crate::benchmarker::{BenchmarkConfig, BenchmarkTarget, WaitingJobsSampleType};
 use crate::grapher::graph_benchmark_result;
-use crate::platform_mock::{generate_mock_platform_config, PlatformBenchMock};
+use crate::platform_mock::{MockScenario, PlatformBenchMock};
 use crate::python_caller::schedule_cycle_on_oar_python;
 use indexmap::IndexMap;
 use log::LevelFilter;
 use oar_scheduler_core::auto_bench_fct::{print_bench_fct_hy_results, print_bench_fct_results};
+use oar_scheduler_core::logging;
 use oar_scheduler_core::model::job::Job;
 use oar_scheduler_core::platform::PlatformTrait;
 use oar_scheduler_core::scheduler::kamelot::schedule_cycle;
+use oar_scheduler_core::scheduler::slotset::SlotSet;
+use std::rc::Rc;
 
 #[tokio::main(flavor = "multi_thread", worker_threads = 8)]
 async fn main() {
-    env_logger::Builder::new()
-        .filter(None, LevelFilter::Info)
-        .filter(Some("oar3_rust::scheduler::hierarchy"), LevelFilter::Debug)
-        .init();
+    logging::init(logging::level_from_env(LevelFilter::Info), &[logging::HIERARCHY_DEBUG_FILTER]);
 
     // Run the difference detection
     /*let seed_offset = 2_000_000;
@@ -53,25 +55,33 @@ async fn main() {
 
 #[allow(dead_code)]
 async fn detect_differences(seed: u64) -> bool {
-    let job_count = 20;
-    let res_count = 100;
-    let waiting_jobs = get_sample_waiting_jobs(res_count, job_count, WaitingJobsSampleType::CoreOnly, seed);
+    let scenario = MockScenario::from_failing_seed(seed);
+    let waiting_jobs = scenario.waiting_jobs();
 
     // Run Rust scheduler
-    let platform_config = generate_mock_platform_config(true, res_count, 20, 5, 5, false);
-    let mut rust_platform = PlatformBenchMock::new(platform_config, vec![], waiting_jobs.clone());
+    let mut rust_platform = scenario.platform();
     let queues = vec!["default".to_string()];
 
     schedule_cycle(&mut rust_platform, &queues);
     let rust_scheduled_jobs = rust_platform.get_scheduled_jobs();
 
     // Run Python scheduler
-    let platform_config = generate_mock_platform_config(true, res_count, 20, 5, 5, false);  // Generate again
-    let mut python_platform = PlatformBenchMock::new(platform_config, vec![], waiting_jobs.clone());
+    let mut python_platform = scenario.platform(); // Generate again
 
     schedule_cycle_on_oar_python(&mut python_platform, queues, false);
     let python_scheduled_jobs = python_platform.get_scheduled_jobs();
 
+    // Compare the resulting slot sets, telling when and on which resources the schedules disagree
+    let slot_set_diff = gantt_slot_set(&rust_platform, &rust_scheduled_jobs).diff(&gantt_slot_set(&python_platform, &python_scheduled_jobs));
+    if !slot_set_diff.is_empty() {
+        println!("DIFFERENCE DETECTED: the slot sets differ (self is Rust, other is Python):");
+        for diff in slot_set_diff {
+            println!("  {}", diff);
+        }
+        display_job_comparison(&waiting_jobs, &rust_scheduled_jobs, &python_scheduled_jobs);
+        return true;
+    }
+
     // Compare results
     if rust_scheduled_jobs.len() != python_scheduled_jobs.len() {
         println!("DIFFERENCE DETECTED: Different number of scheduled jobs!");
@@ -116,6 +126,16 @@ async fn detect_differences(seed: u64) -> bool {
     false
 }
 
+/// Builds the slot set of the platform with the `scheduled_jobs` inserted.
+#[allow(dead_code)]
+fn gantt_slot_set(platform: &PlatformBenchMock, scheduled_jobs: &[Job]) -> SlotSet {
+    let mut slot_set = SlotSet::from_platform_config(Rc::clone(platform.get_platform_config()), platform.get_now(), platform.get_max_time());
+    let mut jobs = scheduled_jobs.iter().collect::<Vec<&Job>>();
+    jobs.sort_by_key(|job| job.begin());
+    slot_set.split_slots_for_jobs_and_update_resources(&jobs, false, true, None);
+    slot_set
+}
+
 #[allow(dead_code)]
 fn display_job_comparison(waiting_jobs: &IndexMap<i64, Job>, rust_scheduled: &Vec<Job>, python_scheduled: &Vec<Job>) {
     println!("\n=== JOB COMPARISON ===");
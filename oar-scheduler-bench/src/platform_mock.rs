@@ -1,3 +1,4 @@
+use crate::benchmarker::{get_sample_waiting_jobs, WaitingJobsSampleType};
 use indexmap::IndexMap;
 use oar_scheduler_core::model::configuration::Configuration;
 use oar_scheduler_core::model::job::{Job, ProcSet};
@@ -28,7 +29,7 @@ impl PlatformTrait for PlatformBenchMock {
     fn get_scheduled_jobs(&self) -> Vec<Job> {
         self.scheduled_jobs.clone()
     }
-    fn get_waiting_jobs(&self) -> IndexMap<i64, Job> {
+    fn get_waiting_jobs(&self, _queues: Vec<String>) -> IndexMap<i64, Job> {
         self.waiting_jobs.clone()
     }
 
@@ -61,6 +62,79 @@ impl PlatformBenchMock {
 }
 
 
+/// A reproducible mock scheduling scenario: the platform and the waiting jobs are fully determined by its fields,
+/// the jobs being generated from `seed`. Reporting a scenario is enough to reproduce a scheduling bug.
+#[derive(Debug, Clone, Copy)]
+pub struct MockScenario {
+    pub seed: u64,
+    pub res_count: u32,
+    pub job_count: usize,
+    pub sample_type: WaitingJobsSampleType,
+    pub cache_enabled: bool,
+    /// Sizes of the resource hierarchy, see [`generate_mock_resource_set`].
+    pub switch_size: u32,
+    pub node_size: u32,
+    pub cpu_size: u32,
+    pub quotas_enabled: bool,
+}
+impl MockScenario {
+    /// Creates a scenario of 20 core-only jobs on 100 resources.
+    pub fn new(seed: u64) -> MockScenario {
+        MockScenario {
+            seed,
+            res_count: 100,
+            job_count: 20,
+            sample_type: WaitingJobsSampleType::CoreOnly,
+            cache_enabled: true,
+            switch_size: 20,
+            node_size: 5,
+            cpu_size: 5,
+            quotas_enabled: false,
+        }
+    }
+    /// Creates the scenario compared between the Rust and Python schedulers by `detect_differences` for `seed`,
+    /// to reproduce a reported difference.
+    pub fn from_failing_seed(seed: u64) -> MockScenario {
+        MockScenario::new(seed)
+    }
+    pub fn res_count(mut self, res_count: u32) -> Self {
+        self.res_count = res_count;
+        self
+    }
+    pub fn job_count(mut self, job_count: usize) -> Self {
+        self.job_count = job_count;
+        self
+    }
+    pub fn sample_type(mut self, sample_type: WaitingJobsSampleType) -> Self {
+        self.sample_type = sample_type;
+        self
+    }
+    pub fn cache_enabled(mut self, cache_enabled: bool) -> Self {
+        self.cache_enabled = cache_enabled;
+        self
+    }
+    pub fn hierarchy(mut self, switch_size: u32, node_size: u32, cpu_size: u32) -> Self {
+        self.switch_size = switch_size;
+        self.node_size = node_size;
+        self.cpu_size = cpu_size;
+        self
+    }
+    pub fn quotas_enabled(mut self, quotas_enabled: bool) -> Self {
+        self.quotas_enabled = quotas_enabled;
+        self
+    }
+
+    pub fn platform_config(&self) -> PlatformConfig {
+        generate_mock_platform_config(self.cache_enabled, self.res_count, self.switch_size, self.node_size, self.cpu_size, self.quotas_enabled)
+    }
+    pub fn waiting_jobs(&self) -> IndexMap<i64, Job> {
+        get_sample_waiting_jobs(self.res_count, self.job_count, self.sample_type, self.seed)
+    }
+    /// Builds a new platform without scheduled jobs, and with the waiting jobs of the scenario.
+    pub fn platform(&self) -> PlatformBenchMock {
+        PlatformBenchMock::new(self.platform_config(), vec![], self.waiting_jobs())
+    }
+}
 
 pub fn generate_mock_platform_config(cache_enabled: bool, res_count: u32, switch_size: u32, node_size: u32, cpu_size: u32, quotas_enable: bool) -> PlatformConfig {
     let mut config = Configuration::default();
@@ -70,6 +144,7 @@ pub fn generate_mock_platform_config(cache_enabled: bool, res_count: u32, switch
     PlatformConfig {
         resource_set: generate_mock_resource_set(res_count, switch_size, node_size, cpu_size),
         quotas_config: generate_mock_quotas_config(quotas_enable, res_count),
+        queues_quotas_config: HashMap::new(),
         config,
     }
 }
@@ -111,10 +186,14 @@ pub fn generate_mock_resource_set(res_count: u32, switch_size: u32, node_size: u
     ResourceSet {
         nb_resources_not_dead: res_count,
         nb_resources_default_not_dead: res_count,
+        weighted_resources_not_dead: res_count as u64,
+        weighted_resources_default_not_dead: res_count as u64,
         suspendable_resources: ProcSet::new(),
         default_resources: ProcSet::from_iter([1..=res_count]),
         available_upto: vec![], // All resources available until max_time
+        available_from: vec![], // All resources available from now
         hierarchy,
+        core_weight_prefix_sums: vec![],
     }
 }
 pub fn generate_mock_quotas_config(enabled: bool, res_count: u32) -> QuotasConfig {
@@ -1,6 +1,7 @@
 use crate::benchmarker::measure_time;
-use oar_scheduler_core::model::job::{JobAssignment, ProcSet};
+use oar_scheduler_core::model::job::{average_wait_time, JobAssignment, ProcSet};
 use oar_scheduler_core::platform::PlatformTrait;
+use oar_scheduler_core::scheduler::kamelot::SchedulingStats;
 use pyo3::ffi::c_str;
 use pyo3::prelude::*;
 use pyo3::types::{PyDict, PyList, PyTuple};
@@ -15,9 +16,10 @@ const ADAPTER_MODULE: &CStr = c_str!("adapter");
 const ADAPTER_FILE: &CStr = c_str!("adapter.py");
 const ADAPTER_CODE: &CStr = c_str!(include_str!("adapter.py"));
 
-/// Returns (elapsed ms, slot count)
-pub fn schedule_cycle_on_oar_python<T: PlatformTrait>(platform: &mut T, _queues: Vec<String>, use_rust: bool) -> (u32, usize) {
-    let time = Python::with_gil(|py| {
+/// Returns (elapsed ms, scheduling stats).
+/// The cache hits and the final slot count are not reported by the Python scheduler and are left to 0.
+pub fn schedule_cycle_on_oar_python<T: PlatformTrait>(platform: &mut T, queues: Vec<String>, use_rust: bool) -> (u32, SchedulingStats) {
+    let (time, stats) = Python::with_gil(|py| {
         let sys = py.import("sys").unwrap();
         sys.getattr("path").unwrap().call_method1("append", (PYTHON_MODULE_DIR,)).unwrap();
         sys.getattr("path").unwrap().call_method1("append", (PYTHON_SITE_PACKAGES_DIR,)).unwrap();
@@ -35,13 +37,17 @@ pub fn schedule_cycle_on_oar_python<T: PlatformTrait>(platform: &mut T, _queues:
                     create_config(py, use_rust),
                     platform_py.clone_ref(py),
                     now,
-                    PyList::new(py, ["default"]).unwrap(),
+                    PyList::new(py, &queues).unwrap(),
                 ))
                 .unwrap();
         })
         .0;
 
-        let mut waiting_jobs = platform.get_waiting_jobs();
+        let mut waiting_jobs = platform.get_waiting_jobs(queues.clone());
+        let mut stats = SchedulingStats {
+            rejected: waiting_jobs.len(),
+            ..SchedulingStats::default()
+        };
 
         // Gather scheduled jobs scheduling data to update rust objects
         let scheduled_jobs_py: Vec<Bound<PyDict>> = platform_py
@@ -65,6 +71,11 @@ pub fn schedule_cycle_on_oar_python<T: PlatformTrait>(platform: &mut T, _queues:
                 proc_set = proc_set | ProcSet::from_iter([start..=end]);
             }
 
+            stats.scheduled += 1;
+            stats.rejected -= 1;
+            stats.quotas_hits += quotas_hit_count;
+            stats.gantt_width = stats.gantt_width.max(end - platform.get_now() + 1);
+
             let job = waiting_jobs.get_mut(&id).unwrap();
             job.quotas_hit_count = quotas_hit_count;
             job.assignment = Some(JobAssignment {
@@ -74,14 +85,15 @@ pub fn schedule_cycle_on_oar_python<T: PlatformTrait>(platform: &mut T, _queues:
                 moldable_index,
             });
         }
+        stats.average_wait_time = average_wait_time(waiting_jobs.values(), platform.get_now()).unwrap_or(0);
 
         platform.save_assignments(waiting_jobs);
 
-        Ok::<u32, PyErr>(time)
+        Ok::<(u32, SchedulingStats), PyErr>((time, stats))
     })
     .unwrap();
 
-    (time, 0)
+    (time, stats)
 }
 
 /// Create a fake instance of the Python Configuration class
@@ -130,7 +142,7 @@ pub fn platform_to_dict<'a, P: PlatformTrait>(py: Python<'a>, platform: &P) -> B
 
     // Convert waiting jobs
     let waiting_jobs = PyList::empty(py);
-    for job in platform.get_waiting_jobs() {
+    for job in platform.get_waiting_jobs(vec!["default".to_string()]) {
         waiting_jobs.append(&job).unwrap();
     }
     dict.set_item("waiting_jobs", waiting_jobs).unwrap();
@@ -0,0 +1,19 @@
+use crate::benchmarker::WaitingJobsSampleType;
+use crate::platform_mock::MockScenario;
+use oar_scheduler_core::platform::PlatformTrait;
+
+#[test]
+fn test_scenario_is_reproducible() {
+    for sample_type in [WaitingJobsSampleType::Normal, WaitingJobsSampleType::HighCacheHit, WaitingJobsSampleType::CoreOnly] {
+        let scenario = MockScenario::new(42).res_count(1000).job_count(50).sample_type(sample_type);
+        let jobs = format!("{:?}", scenario.waiting_jobs());
+        assert_eq!(jobs, format!("{:?}", scenario.waiting_jobs()));
+        assert_eq!(jobs, format!("{:?}", scenario.platform().get_waiting_jobs(vec![])));
+        // Another seed gives other jobs
+        assert_ne!(jobs, format!("{:?}", MockScenario { seed: 43, ..scenario }.waiting_jobs()));
+    }
+
+    let scenario = MockScenario::from_failing_seed(2_000_026);
+    assert_eq!(format!("{:?}", scenario.waiting_jobs()), format!("{:?}", MockScenario::from_failing_seed(2_000_026).waiting_jobs()));
+    assert_eq!(scenario.platform().get_waiting_jobs(vec![]).len(), scenario.job_count);
+}
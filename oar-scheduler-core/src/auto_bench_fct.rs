@@ -0,0 +1,53 @@
+pub use ::auto_bench_fct::print_bench_fct_hy_results;
+pub use ::auto_bench_fct::print_bench_fct_results;
+use ::auto_bench_fct::{get_bench_fct_hy_results, get_bench_fct_results, FUNCTION_METRICS, FUNCTION_METRICS_HIERARCHY};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Exports the function benchmark metrics as a JSON string, to be periodically collected by long-running processes.
+/// `functions` lists the metrics grouped by function, and `hierarchy` the metrics grouped by function and call stack,
+/// `stack` being the indices of the calling functions from the root.
+/// Durations are in nanoseconds. Entries are sorted by stack, function name, and index.
+pub fn metrics_as_json() -> String {
+    metrics_to_json(get_bench_fct_results(), get_bench_fct_hy_results()).to_string()
+}
+
+/// Calls count and total duration of each function, keyed by function name and index.
+type FunctionMetrics = HashMap<(String, u32), (u64, Duration)>;
+
+/// Formats the metrics grouped by function and the metrics grouped by call stack as done by [`metrics_as_json`].
+pub(crate) fn metrics_to_json(
+    functions: FunctionMetrics,
+    stacks: HashMap<Vec<u32>, FunctionMetrics>,
+) -> Value {
+    let functions = metrics_entries(functions, None);
+    let mut stacks = stacks.into_iter().collect::<Vec<_>>();
+    stacks.sort_by(|(stack1, _), (stack2, _)| stack1.cmp(stack2));
+    let hierarchy = stacks
+        .into_iter()
+        .flat_map(|(stack, metrics)| metrics_entries(metrics, Some(stack)))
+        .collect::<Vec<Value>>();
+    json!({ "functions": functions, "hierarchy": hierarchy })
+}
+
+/// Clears the function benchmark metrics, e.g., after exporting them with [`metrics_as_json`] to start a new window.
+pub fn reset_metrics() {
+    FUNCTION_METRICS.lock().unwrap().clear();
+    FUNCTION_METRICS_HIERARCHY.lock().unwrap().clear();
+}
+
+fn metrics_entries(metrics: FunctionMetrics, stack: Option<Vec<u32>>) -> Vec<Value> {
+    let mut metrics = metrics.into_iter().collect::<Vec<_>>();
+    metrics.sort_by(|((name1, index1), _), ((name2, index2), _)| name1.cmp(name2).then(index1.cmp(index2)));
+    metrics
+        .into_iter()
+        .map(|((name, index), (calls, duration))| {
+            let mut entry = json!({ "name": name, "index": index, "calls": calls, "duration_ns": duration.as_nanos() as u64 });
+            if let Some(stack) = &stack {
+                entry["stack"] = json!(stack);
+            }
+            entry
+        })
+        .collect()
+}
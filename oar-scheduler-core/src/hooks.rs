@@ -3,6 +3,7 @@ use crate::platform::PlatformConfig;
 use crate::scheduler::slotset::SlotSet;
 use indexmap::IndexMap;
 use std::cell::OnceCell;
+use std::collections::HashMap;
 use std::rc::Rc;
 
 thread_local! {
@@ -13,6 +14,18 @@ pub trait HooksHandler {
     /// Overrides the job sorting process. This hook should sort the `waiting_jobs` in place.
     fn hook_sort(&self, platform_config: &PlatformConfig, queues: &Vec<String>, waiting_jobs: &mut IndexMap<i64, Job>) -> bool;
 
+    /// Reorders the sorted waiting jobs by a fairshare score. This hook should reorder the `waiting_jobs` in place, and return `true` if it did.
+    /// `users_usage` maps the users to their recent resource usage (see [`crate::platform::PlatformTrait::get_users_usage`]).
+    /// It is only called if the `JOB_PRIORITY` is `FAIRSHARE`. By default, the jobs are left in place.
+    fn hook_fairshare(
+        &self,
+        _platform_config: &PlatformConfig,
+        _users_usage: &HashMap<Box<str>, f64>,
+        _waiting_jobs: &mut IndexMap<i64, Job>,
+    ) -> bool {
+        false
+    }
+
     /// Overrides the single job scheduling on a slot set process. This hook should define the `assignment` property of `Job`.
     /// It will override quotas, timesharing, and placeholders, but not container/inner jobs.
     /// The dependencies can be taken into account through the `min_begin` function parameter.
@@ -29,6 +42,11 @@ pub trait HooksHandler {
         min_begin: Option<i64>,
         available_resources: ProcSet,
     ) -> Option<Option<ProcSet>>;
+
+    /// Filters the resources available to a job before requesting them through the hierarchy (or the `hook_find` hook),
+    /// e.g., to remove GPUs reserved for another project, or nodes failing a health check.
+    /// This hook should remove resources from `available` in place, and return `true` if it modified it.
+    fn hook_filter_resources(&self, platform_config: &PlatformConfig, job: &Job, available: &mut ProcSet) -> bool;
 }
 
 pub(crate) struct HooksManager {
@@ -51,6 +69,11 @@ impl HooksManager {
         let _ = self.hooks_handler.set(Box::new(hooks_handler));
     }
 
+    /// Returns true if a hooks handler is registered, in which case the hooks may override the scheduling.
+    pub fn has_hooks_handler(&self) -> bool {
+        self.hooks_handler.get().is_some()
+    }
+
     pub fn hook_sort(&self, platform_config: &PlatformConfig, queues: &Vec<String>, waiting_jobs: &mut IndexMap<i64, Job>) -> bool {
         if self.hooks_handler.get().is_none() {
             return false;
@@ -60,6 +83,20 @@ impl HooksManager {
             .unwrap()
             .hook_sort(platform_config, queues, waiting_jobs)
     }
+    pub fn hook_fairshare(
+        &self,
+        platform_config: &PlatformConfig,
+        users_usage: &HashMap<Box<str>, f64>,
+        waiting_jobs: &mut IndexMap<i64, Job>,
+    ) -> bool {
+        if self.hooks_handler.get().is_none() {
+            return false;
+        }
+        self.hooks_handler
+            .get()
+            .unwrap()
+            .hook_fairshare(platform_config, users_usage, waiting_jobs)
+    }
     pub fn hook_assign(&self, slot_set: &mut SlotSet, job: &mut Job, min_begin: Option<i64>) -> bool {
         if self.hooks_handler.get().is_none() {
             return false;
@@ -82,6 +119,12 @@ impl HooksManager {
             .unwrap()
             .hook_find(slot_set, job, moldable, min_begin, available_resources)
     }
+    pub fn hook_filter_resources(&self, platform_config: &PlatformConfig, job: &Job, available: &mut ProcSet) -> bool {
+        if self.hooks_handler.get().is_none() {
+            return false;
+        }
+        self.hooks_handler.get().unwrap().hook_filter_resources(platform_config, job, available)
+    }
 }
 
 pub fn set_hooks_handler<H>(hooks_handler: H)
@@ -92,6 +135,11 @@ where
         hooks_manager.set_hooks_handler(hooks_handler);
     });
 }
+/// Calls the `hook_filter_resources` hook of the registered hooks handler, if any.
+/// Exposed for the schedulers placing jobs outside of kamelot, like the advance reservations check.
+pub fn hook_filter_resources(platform_config: &PlatformConfig, job: &Job, available: &mut ProcSet) -> bool {
+    get_hooks_manager().hook_filter_resources(platform_config, job, available)
+}
 pub(crate) fn get_hooks_manager() -> Rc<HooksManager> {
     HOOKS_HANDLER.with(|hooks_manager| hooks_manager.clone())
 }
@@ -3,8 +3,6 @@ pub mod scheduler;
 pub mod hooks;
 pub mod model;
 
-pub mod auto_bench_fct {
-    pub use auto_bench_fct::print_bench_fct_hy_results;
-    pub use auto_bench_fct::print_bench_fct_results;
-}
+pub mod auto_bench_fct;
+pub mod logging;
 
@@ -0,0 +1,29 @@
+use log::LevelFilter;
+use std::str::FromStr;
+
+/// Environment variable overriding the default log level of the scheduler binaries and Python module, e.g., `debug`.
+pub const LOG_LEVEL_ENV_VAR: &str = "OAR_SCHEDULER_LOG_LEVEL";
+
+/// Module filter enabling the debug logs of the hierarchy requests resolution.
+pub const HIERARCHY_DEBUG_FILTER: (&str, LevelFilter) = ("oar_scheduler_core::scheduler::hierarchy", LevelFilter::Debug);
+
+/// Initializes the global logger with `level` as the default level, and `module_filters` as the level of specific modules.
+/// If a logger is already initialized, e.g., when the Python module is imported several times, this call does nothing.
+pub fn init(level: LevelFilter, module_filters: &[(&str, LevelFilter)]) {
+    let mut builder = env_logger::Builder::new();
+    builder.filter(None, level);
+    for (module, module_level) in module_filters {
+        builder.filter(Some(module), *module_level);
+    }
+    builder.try_init().ok();
+}
+
+/// Parses a log level name (case-insensitive, e.g., `info`, `DEBUG` or `off`), returning `default` if `level` is `None` or invalid.
+pub fn parse_level(level: Option<&str>, default: LevelFilter) -> LevelFilter {
+    level.and_then(|level| LevelFilter::from_str(level.trim()).ok()).unwrap_or(default)
+}
+
+/// Reads the log level from the [`LOG_LEVEL_ENV_VAR`] environment variable, returning `default` if it is unset or invalid.
+pub fn level_from_env(default: LevelFilter) -> LevelFilter {
+    parse_level(std::env::var(LOG_LEVEL_ENV_VAR).ok().as_deref(), default)
+}
@@ -1,5 +1,6 @@
 pub mod job;
 pub mod configuration;
+pub mod walltime;
 #[cfg(feature = "pyo3")]
 pub mod configuration_python;
 #[cfg(feature = "pyo3")]
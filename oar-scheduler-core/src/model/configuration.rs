@@ -1,13 +1,60 @@
+use crate::model::job::Job;
+use crate::model::walltime::QueueDefaultEstimator;
+use log::warn;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 pub const DEFAULT_CONFIG_FILE: &str = "/etc/oar/oar.conf";
 
+/// OAR configuration, loaded from a .conf file (key=value, keys are case-insensitive).
+/// No key is required: missing keys take their value from [`Configuration::default`].
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct Configuration {
     // --- Global configuration ---
     pub scheduler_job_security_time: i64,
+    /// Per-queue security times overriding `scheduler_job_security_time`, as comma-separated `queue:seconds` pairs,
+    /// e.g. `besteffort:0,long:300`. See [`Configuration::job_security_time`].
+    pub scheduler_queue_job_security_times: Option<String>,
+    /// Walltime in seconds given to the moldables submitted without walltime. See [`Configuration::walltime_estimator`].
+    pub default_job_walltime: i64,
+    /// Per-queue walltimes overriding `default_job_walltime`, as comma-separated `queue:seconds` pairs, e.g. `besteffort:3600`.
+    pub scheduler_queue_default_walltimes: Option<String>,
     pub cache_enabled: bool,
+    /// Maximum number of moldable cache entries kept by each slot set, the least recently used entries being evicted first.
+    pub scheduler_cache_capacity: usize,
     pub scheduler_besteffort_kill_duration_before_reservation: i64,
+    /// If true, the running besteffort jobs are obstacles for the jobs of the other queues, and are only preempted (killed)
+    /// when a job cannot be assigned otherwise. If false, they are transparent, and the resources they use can be assigned to any job.
+    pub scheduler_besteffort_preemption: bool,
+    /// Minimum time in seconds a besteffort job must have been running before being preempted, 0 to disable.
+    /// Avoids killing very young besteffort jobs. See [`Configuration::besteffort_config`].
+    pub scheduler_besteffort_min_runtime_before_kill: i64,
+    /// Minimum remaining walltime in seconds a besteffort job must have to be preempted, 0 to disable.
+    /// Lets the besteffort jobs about to end finish. See [`Configuration::besteffort_config`].
+    pub scheduler_besteffort_min_remaining_walltime_before_kill: i64,
+    /// Per-queue scheduling horizons, as comma-separated `queue:seconds` pairs, e.g. `besteffort:86400,admin:604800`.
+    /// Jobs of these queues are only scheduled if they end within the horizon. See [`Configuration::queue_horizons`].
+    pub scheduler_queue_horizons: Option<String>,
+    /// Placement of the jobs in the slot sets, see [`SchedulingPolicy`].
+    pub scheduler_placement_policy: SchedulingPolicy,
+    /// Backfilling of the jobs left unscheduled by the main scheduling pass, see [`BackfillMode`].
+    pub scheduler_backfill_mode: BackfillMode,
+    /// Scheduling of the members of a job array, see [`ArrayPolicy`].
+    pub scheduler_array_policy: ArrayPolicy,
+    /// Duration in seconds the slot sets begin is rounded down to, e.g., 60 for a minute or 3600 for an hour boundary, 0 to disable.
+    /// Aligning the slot sets begin across cycles avoids single-second slots. Jobs still begin at the earliest at the real `now`.
+    pub scheduler_slotset_begin_rounding: i64,
+    /// Maximum number of slots of a slot set, 0 for no limit. Once reached, the slots are not split anymore,
+    /// and the jobs are inserted in the whole slots they overlap, reserving their resources for longer than needed.
+    /// Protects the scheduler against the unbounded memory growth caused by, e.g., a pathological calendar.
+    pub scheduler_max_slots: usize,
+    /// If true, the windows tried for each waiting job are recorded and returned in the scheduling cycle statistics,
+    /// e.g., to find out why a job was not scheduled. Memory-heavy, only meant for debugging. See [`crate::scheduler::trace::JobTrace`].
+    pub scheduler_job_traces: bool,
+    /// If true, the pending walltime change requests of the running jobs are granted by the meta-scheduler
+    /// when the resources of the jobs are free until their new end.
+    pub walltime_change_enabled: bool,
     // --- Database configuration ---
     pub db_type: String,
     pub db_hostname: String,
@@ -17,15 +64,35 @@ pub struct Configuration {
     pub db_base_passwd: String,
     pub db_base_login_ro: String,
     pub db_base_passwd_ro: String,
+    /// SQLite journal mode set on each connection, e.g. `WAL` to let readers run concurrently with a writer.
+    /// Empty to keep the SQLite default.
+    pub db_sqlite_journal_mode: String,
+    /// Duration in milliseconds a SQLite connection waits for a lock to be released before failing with "database is locked",
+    /// set on each connection. Negative to keep the SQLite default.
+    pub db_sqlite_busy_timeout: i64,
+    /// Number of attempts to connect to the database before giving up, e.g., to survive a database restarting with the scheduler.
+    pub db_connect_attempts: u32,
+    /// Delay in milliseconds before retrying to connect to the database, doubled after each failed attempt.
+    pub db_connect_retry_delay: u64,
     // --- Resources configuration ---
     pub scheduler_resource_order: Option<String>,
     pub scheduler_available_suspended_resource_type: Option<String>,
     pub hierarchy_labels: Option<String>,
+    /// Integer resources column giving the weight of each resource in the quotas `resources` accounting,
+    /// e.g., the cpuset cardinality of the resource. Every resource weighs 1 if unset.
+    pub scheduler_resource_weight_label: Option<String>,
     // --- Quotas configuration ---
     pub quotas: bool,
     pub quotas_conf_file: Option<String>,
+    /// Per-queue quotas configuration files, as comma-separated `queue:path` pairs, e.g. `gpu:/etc/oar/quotas_gpu.json`.
+    /// Their rules are checked in addition to the rules of `quotas_conf_file` for the jobs of the queue.
+    /// See [`Configuration::queues_quotas_conf_files`].
+    pub quotas_queues_conf_files: Option<String>,
     pub quotas_window_time_limit: Option<i64>,
     pub quotas_all_nb_resources_mode: QuotasAllNbResourcesMode,
+    /// If true, the running jobs and resources times limits of the temporal quotas rules are evaluated over the whole
+    /// period in effect (e.g., the current workday), and not only over the job duration: their counters reset at the period boundaries.
+    pub quotas_period_counters: bool,
     // -- Job sorting configuration ---
     pub job_priority: JobPriority,
     pub priority_conf_file: Option<String>,
@@ -49,7 +116,7 @@ impl Configuration {
 
         let contents = std::fs::read_to_string(&path).ok();
         if let Some(contents) = contents {
-            serde_envfile::from_str(&contents).unwrap_or_else(|e| {
+            Configuration::load_from_str(&contents).unwrap_or_else(|e| {
                 eprintln!(
                     "Warning: could not parse configuration file '{}': {}, using default configuration.",
                     path, e
@@ -60,6 +127,118 @@ impl Configuration {
             Configuration::default()
         }
     }
+
+    /// Load configuration from a string, in a .conf format (key=value), e.g. `SCHEDULER_JOB_SECURITY_TIME="60"`.
+    /// Keys are case-insensitive and unknown keys are ignored.
+    /// No key is required: missing keys take their value from [`Configuration::default`].
+    /// Returns an error if the content is malformed or if a value cannot be parsed into its field type.
+    pub fn load_from_str(contents: &str) -> Result<Self, serde_envfile::Error> {
+        serde_envfile::from_str(contents)
+    }
+
+    /// Parses `scheduler_queue_horizons` into a map of queue name to horizon in seconds.
+    /// Malformed entries are ignored with a warning.
+    pub fn queue_horizons(&self) -> HashMap<String, i64> {
+        parse_queue_seconds(self.scheduler_queue_horizons.as_deref(), "SCHEDULER_QUEUE_HORIZONS")
+    }
+
+    /// Parses `scheduler_queue_job_security_times` into a map of queue name to security time in seconds.
+    /// Malformed entries are ignored with a warning.
+    pub fn queue_job_security_times(&self) -> HashMap<String, i64> {
+        parse_queue_seconds(self.scheduler_queue_job_security_times.as_deref(), "SCHEDULER_QUEUE_JOB_SECURITY_TIMES")
+    }
+
+    /// Returns the security time of the jobs of `queue`: its `scheduler_queue_job_security_times` entry,
+    /// or `scheduler_job_security_time` if the queue is not listed.
+    pub fn job_security_time(&self, queue: &str) -> i64 {
+        if self.scheduler_queue_job_security_times.is_none() {
+            return self.scheduler_job_security_time;
+        }
+        self.queue_job_security_times().get(queue).copied().unwrap_or(self.scheduler_job_security_time)
+    }
+
+    /// Returns the distinct security times of all the queues, i.e., `scheduler_job_security_time` and the values of
+    /// `scheduler_queue_job_security_times`, in increasing order.
+    pub fn distinct_job_security_times(&self) -> Vec<i64> {
+        let mut security_times = self.queue_job_security_times().into_values().collect::<Vec<i64>>();
+        security_times.push(self.scheduler_job_security_time);
+        security_times.sort_unstable();
+        security_times.dedup();
+        security_times
+    }
+
+    /// Returns the estimator giving to the moldables without walltime the `scheduler_queue_default_walltimes` entry of their queue,
+    /// or `default_job_walltime` if the queue is not listed.
+    pub fn walltime_estimator(&self) -> QueueDefaultEstimator {
+        parse_queue_seconds(self.scheduler_queue_default_walltimes.as_deref(), "SCHEDULER_QUEUE_DEFAULT_WALLTIMES")
+            .into_iter()
+            .fold(QueueDefaultEstimator::new(self.default_job_walltime), |estimator, (queue, walltime)| {
+                estimator.queue(queue.into_boxed_str(), walltime)
+            })
+    }
+
+    /// Returns the thresholds protecting the besteffort jobs from preemption.
+    pub fn besteffort_config(&self) -> BesteffortConfig {
+        BesteffortConfig {
+            min_runtime_before_kill: self.scheduler_besteffort_min_runtime_before_kill,
+            min_remaining_walltime_before_kill: self.scheduler_besteffort_min_remaining_walltime_before_kill,
+        }
+    }
+
+    /// Parses `quotas_queues_conf_files` into a map of queue name to quotas configuration file path.
+    /// Malformed entries are ignored with a warning.
+    pub fn queues_quotas_conf_files(&self) -> HashMap<String, String> {
+        let mut files = HashMap::new();
+        for entry in self.quotas_queues_conf_files.iter().flat_map(|s| s.split(',')) {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            match entry.split_once(':').map(|(queue, path)| (queue.trim(), path.trim())) {
+                Some((queue, path)) if !queue.is_empty() && !path.is_empty() => {
+                    files.insert(queue.to_string(), path.to_string());
+                }
+                _ => warn!("Ignoring malformed QUOTAS_QUEUES_CONF_FILES entry: '{}'", entry),
+            }
+        }
+        files
+    }
+}
+
+/// Thresholds protecting the besteffort jobs from being preempted by the jobs of the other queues, to avoid thrashing.
+/// A besteffort job is only preempted if it has been running for at least `min_runtime_before_kill` seconds,
+/// and has at least `min_remaining_walltime_before_kill` seconds of walltime left.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BesteffortConfig {
+    pub min_runtime_before_kill: i64,
+    pub min_remaining_walltime_before_kill: i64,
+}
+
+impl BesteffortConfig {
+    /// Returns true if the scheduled besteffort `job` can be preempted at `now`.
+    pub fn can_kill(&self, job: &Job, now: i64) -> bool {
+        job.assignment.as_ref().is_some_and(|assignment| {
+            now - assignment.begin >= self.min_runtime_before_kill && assignment.end - now + 1 >= self.min_remaining_walltime_before_kill
+        })
+    }
+}
+
+/// Parses comma-separated `queue:seconds` pairs, ignoring malformed entries with a warning mentioning the configuration `key`.
+fn parse_queue_seconds(value: Option<&str>, key: &str) -> HashMap<String, i64> {
+    let mut seconds = HashMap::new();
+    for entry in value.iter().flat_map(|s| s.split(',')) {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+        match entry.split_once(':').map(|(queue, value)| (queue.trim(), value.trim().parse::<i64>())) {
+            Some((queue, Ok(value))) if !queue.is_empty() && value >= 0 => {
+                seconds.insert(queue.to_string(), value);
+            }
+            _ => warn!("Ignoring malformed {} entry: '{}'", key, entry),
+        }
+    }
+    seconds
 }
 
 impl Default for Configuration {
@@ -67,8 +246,23 @@ impl Default for Configuration {
         Configuration {
             // --- Global configuration ---
             scheduler_job_security_time: 60, // 1 minute
+            scheduler_queue_job_security_times: None,
+            default_job_walltime: 7200, // 2 hours
+            scheduler_queue_default_walltimes: None,
             cache_enabled: true,
+            scheduler_cache_capacity: 4096,
             scheduler_besteffort_kill_duration_before_reservation: 60, // 1 minute
+            scheduler_besteffort_preemption: false,
+            scheduler_besteffort_min_runtime_before_kill: 0,
+            scheduler_besteffort_min_remaining_walltime_before_kill: 0,
+            scheduler_queue_horizons: None,
+            scheduler_placement_policy: SchedulingPolicy::EarliestFirst,
+            scheduler_backfill_mode: BackfillMode::None,
+            scheduler_array_policy: ArrayPolicy::None,
+            scheduler_slotset_begin_rounding: 0,
+            scheduler_max_slots: 0,
+            scheduler_job_traces: false,
+            walltime_change_enabled: false,
             // --- Database configuration ---
             db_type: "Pg".to_string(),
             db_hostname: "localhost".to_string(),
@@ -78,15 +272,22 @@ impl Default for Configuration {
             db_base_passwd: "oar".to_string(),
             db_base_login_ro: "oar_ro".to_string(),
             db_base_passwd_ro: "oar_ro".to_string(),
+            db_sqlite_journal_mode: "WAL".to_string(),
+            db_sqlite_busy_timeout: 5000, // 5 seconds
+            db_connect_attempts: 5,
+            db_connect_retry_delay: 500,
             // --- Resources configuration ---
             scheduler_resource_order: None,
             scheduler_available_suspended_resource_type: None,
             hierarchy_labels: None,
+            scheduler_resource_weight_label: None,
             // --- Quotas configuration ---
             quotas: false,
             quotas_conf_file: None,
+            quotas_queues_conf_files: None,
             quotas_window_time_limit: Some(60 * 24 * 3600), // 60 days
             quotas_all_nb_resources_mode: QuotasAllNbResourcesMode::DefaultNotDead,
+            quotas_period_counters: false,
             // -- Job sorting configuration ---
             job_priority: JobPriority::Fifo,
             priority_conf_file: None,
@@ -108,6 +309,42 @@ pub enum JobPriority {
     Fairshare,
     Multifactor,
 }
+/// Placement of the jobs in the slot sets.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SchedulingPolicy {
+    /// Jobs are placed in the earliest window where they fit.
+    EarliestFirst,
+    /// Jobs are placed in the latest window where they fit, ending at the latest before the end of the slot set,
+    /// keeping the near term free, e.g., for interactive jobs.
+    LatestFit,
+}
+/// Backfilling pass run after the main scheduling pass on the jobs it left unscheduled,
+/// e.g., jobs depending on a job sorted after them, or jobs that can use the resources freed by preemption.
+/// The backfilled jobs never push back the jobs already scheduled.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum BackfillMode {
+    /// No backfilling pass.
+    None,
+    /// Unscheduled jobs are placed in the earliest window left free by the scheduled jobs, getting a reservation.
+    Conservative,
+    /// EASY backfilling: the first unscheduled job gets a reservation, and the following ones are placed only if they can start now
+    /// without delaying this reservation.
+    Easy,
+}
+/// Scheduling of the members of a job array (jobs sharing the same `array_id`).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ArrayPolicy {
+    /// Array members are scheduled independently, in the order given by the sorting.
+    None,
+    /// Array members are scheduled consecutively, right after the first of them in the sorting order.
+    Consecutive,
+    /// Array members are scheduled consecutively, and all together (all-or-nothing): if they can't all begin at the same time,
+    /// none of them is scheduled during this cycle.
+    Gang,
+}
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum QuotasAllNbResourcesMode {
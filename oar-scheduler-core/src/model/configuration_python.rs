@@ -1,4 +1,4 @@
-use crate::model::configuration::{Configuration, JobPriority, QuotasAllNbResourcesMode};
+use crate::model::configuration::{ArrayPolicy, BackfillMode, Configuration, JobPriority, QuotasAllNbResourcesMode, SchedulingPolicy};
 use pyo3::exceptions::PyValueError;
 use pyo3::types::PyDict;
 use pyo3::{prelude::PyAnyMethods, types::PyString, Bound, FromPyObject, IntoPyObject, PyAny, PyErr, PyResult, Python};
@@ -29,6 +29,82 @@ impl<'a> FromPyObject<'a> for JobPriority {
     }
 }
 
+impl<'p> IntoPyObject<'p> for &SchedulingPolicy {
+    type Target = PyString;
+    type Output = Bound<'p, Self::Target>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'p>) -> Result<Self::Output, Self::Error> {
+        let s = match self {
+            SchedulingPolicy::EarliestFirst => "EARLIEST_FIRST",
+            SchedulingPolicy::LatestFit => "LATEST_FIT",
+        };
+        Ok(PyString::new(py, s))
+    }
+}
+impl<'a> FromPyObject<'a> for SchedulingPolicy {
+    fn extract_bound(obj: &Bound<'a, PyAny>) -> PyResult<Self> {
+        let s: String = obj.extract()?;
+        match s.as_str() {
+            "EARLIEST_FIRST" => Ok(SchedulingPolicy::EarliestFirst),
+            "LATEST_FIT" => Ok(SchedulingPolicy::LatestFit),
+            _ => Err(PyErr::new::<PyValueError, _>(format!("Invalid SchedulingPolicy: {}", s))),
+        }
+    }
+}
+
+impl<'p> IntoPyObject<'p> for &BackfillMode {
+    type Target = PyString;
+    type Output = Bound<'p, Self::Target>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'p>) -> Result<Self::Output, Self::Error> {
+        let s = match self {
+            BackfillMode::None => "NONE",
+            BackfillMode::Conservative => "CONSERVATIVE",
+            BackfillMode::Easy => "EASY",
+        };
+        Ok(PyString::new(py, s))
+    }
+}
+impl<'a> FromPyObject<'a> for BackfillMode {
+    fn extract_bound(obj: &Bound<'a, PyAny>) -> PyResult<Self> {
+        let s: String = obj.extract()?;
+        match s.as_str() {
+            "NONE" => Ok(BackfillMode::None),
+            "CONSERVATIVE" => Ok(BackfillMode::Conservative),
+            "EASY" => Ok(BackfillMode::Easy),
+            _ => Err(PyErr::new::<PyValueError, _>(format!("Invalid BackfillMode: {}", s))),
+        }
+    }
+}
+
+impl<'p> IntoPyObject<'p> for &ArrayPolicy {
+    type Target = PyString;
+    type Output = Bound<'p, Self::Target>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'p>) -> Result<Self::Output, Self::Error> {
+        let s = match self {
+            ArrayPolicy::None => "NONE",
+            ArrayPolicy::Consecutive => "CONSECUTIVE",
+            ArrayPolicy::Gang => "GANG",
+        };
+        Ok(PyString::new(py, s))
+    }
+}
+impl<'a> FromPyObject<'a> for ArrayPolicy {
+    fn extract_bound(obj: &Bound<'a, PyAny>) -> PyResult<Self> {
+        let s: String = obj.extract()?;
+        match s.as_str() {
+            "NONE" => Ok(ArrayPolicy::None),
+            "CONSECUTIVE" => Ok(ArrayPolicy::Consecutive),
+            "GANG" => Ok(ArrayPolicy::Gang),
+            _ => Err(PyErr::new::<PyValueError, _>(format!("Invalid ArrayPolicy: {}", s))),
+        }
+    }
+}
+
 impl<'a> IntoPyObject<'a> for &QuotasAllNbResourcesMode {
     type Target = PyString;
     type Output = Bound<'a, Self::Target>;
@@ -65,11 +141,28 @@ impl<'p> IntoPyObject<'p> for &Configuration {
         dict.set_item("JOB_PRIORITY", (&self.job_priority).into_pyobject(py)?)?;
         dict.set_item("PRIORITY_CONF_FILE", self.priority_conf_file.clone())?;
         dict.set_item("SCHEDULER_JOB_SECURITY_TIME", self.scheduler_job_security_time)?;
+        if let Some(v) = &self.scheduler_queue_job_security_times { dict.set_item("SCHEDULER_QUEUE_JOB_SECURITY_TIMES", v.clone())?; }
+        dict.set_item("DEFAULT_JOB_WALLTIME", self.default_job_walltime)?;
+        if let Some(v) = &self.scheduler_queue_default_walltimes { dict.set_item("SCHEDULER_QUEUE_DEFAULT_WALLTIMES", v.clone())?; }
+        dict.set_item("SCHEDULER_BESTEFFORT_PREEMPTION", PyString::new(py, if self.scheduler_besteffort_preemption { "yes" } else { "no" }))?;
+        dict.set_item("SCHEDULER_BESTEFFORT_MIN_RUNTIME_BEFORE_KILL", self.scheduler_besteffort_min_runtime_before_kill)?;
+        dict.set_item("SCHEDULER_BESTEFFORT_MIN_REMAINING_WALLTIME_BEFORE_KILL", self.scheduler_besteffort_min_remaining_walltime_before_kill)?;
         dict.set_item("QUOTAS", PyString::new(py, if self.quotas { "yes" } else { "no" }))?;
         dict.set_item("QUOTAS_CONF_FILE", self.quotas_conf_file.clone())?;
+        if let Some(v) = &self.quotas_queues_conf_files { dict.set_item("QUOTAS_QUEUES_CONF_FILES", v.clone())?; }
         if let Some(v) = self.quotas_window_time_limit { dict.set_item("QUOTAS_WINDOW_TIME_LIMIT", v)?; }
         dict.set_item("QUOTAS_ALL_NB_RESOURCES_MODE", (&self.quotas_all_nb_resources_mode).into_pyobject(py)?)?;
+        dict.set_item("QUOTAS_PERIOD_COUNTERS", PyString::new(py, if self.quotas_period_counters { "yes" } else { "no" }))?;
         dict.set_item("CACHE_ENABLED", PyString::new(py, if self.cache_enabled { "yes" } else { "no" }))?;
+        dict.set_item("SCHEDULER_CACHE_CAPACITY", self.scheduler_cache_capacity)?;
+        dict.set_item("SCHEDULER_PLACEMENT_POLICY", (&self.scheduler_placement_policy).into_pyobject(py)?)?;
+        dict.set_item("SCHEDULER_BACKFILL_MODE", (&self.scheduler_backfill_mode).into_pyobject(py)?)?;
+        dict.set_item("SCHEDULER_ARRAY_POLICY", (&self.scheduler_array_policy).into_pyobject(py)?)?;
+        dict.set_item("SCHEDULER_SLOTSET_BEGIN_ROUNDING", self.scheduler_slotset_begin_rounding)?;
+        dict.set_item("SCHEDULER_MAX_SLOTS", self.scheduler_max_slots)?;
+        dict.set_item("SCHEDULER_JOB_TRACES", PyString::new(py, if self.scheduler_job_traces { "yes" } else { "no" }))?;
+        dict.set_item("WALLTIME_CHANGE_ENABLED", PyString::new(py, if self.walltime_change_enabled { "yes" } else { "no" }))?;
+        if let Some(v) = &self.scheduler_queue_horizons { dict.set_item("SCHEDULER_QUEUE_HORIZONS", v.clone())?; }
 
         // Optional SCHEDULER_FAIRSHARING_* fields
         if let Some(v) = self.scheduler_fairsharing_window_size { dict.set_item("SCHEDULER_FAIRSHARING_WINDOW_SIZE", v)?; }
@@ -191,11 +284,43 @@ impl<'a> FromPyObject<'a> for Configuration {
         cfg.priority_conf_file = get_opt_str_config(dict, "PRIORITY_CONF_FILE")?;
         cfg.scheduler_resource_order = get_opt_str_config(dict, "SCHEDULER_RESOURCE_ORDER")?;
         cfg.scheduler_job_security_time = get_i64_config(dict, "SCHEDULER_JOB_SECURITY_TIME")?;
+        cfg.scheduler_queue_job_security_times = get_opt_str_config(dict, "SCHEDULER_QUEUE_JOB_SECURITY_TIMES")?;
+        if let Some(walltime) = get_opt_i64_config(dict, "DEFAULT_JOB_WALLTIME")? {
+            cfg.default_job_walltime = walltime;
+        }
+        cfg.scheduler_queue_default_walltimes = get_opt_str_config(dict, "SCHEDULER_QUEUE_DEFAULT_WALLTIMES")?;
         cfg.quotas = get_bool_config(dict, "QUOTAS")?;
         cfg.quotas_conf_file = get_opt_str_config(dict, "QUOTAS_CONF_FILE")?;
+        cfg.quotas_queues_conf_files = get_opt_str_config(dict, "QUOTAS_QUEUES_CONF_FILES")?;
         cfg.quotas_window_time_limit = get_opt_i64_config(dict, "QUOTAS_WINDOW_TIME_LIMIT")?;
         cfg.quotas_all_nb_resources_mode = get_opt_any_config(&dict, "QUOTAS_ALL_NB_RESOURCES_MODE")?.unwrap_or(QuotasAllNbResourcesMode::All);
+        cfg.scheduler_besteffort_preemption = get_opt_bool_config(dict, "SCHEDULER_BESTEFFORT_PREEMPTION")?.unwrap_or(false);
+        cfg.scheduler_besteffort_min_runtime_before_kill = get_opt_i64_config(dict, "SCHEDULER_BESTEFFORT_MIN_RUNTIME_BEFORE_KILL")?.unwrap_or(0);
+        cfg.scheduler_besteffort_min_remaining_walltime_before_kill =
+            get_opt_i64_config(dict, "SCHEDULER_BESTEFFORT_MIN_REMAINING_WALLTIME_BEFORE_KILL")?.unwrap_or(0);
+        cfg.quotas_period_counters = get_opt_bool_config(dict, "QUOTAS_PERIOD_COUNTERS")?.unwrap_or(false);
         cfg.cache_enabled = get_opt_bool_config(dict, "CACHE_ENABLED")?.unwrap_or(true);
+        if let Some(capacity) = get_opt_i64_config(dict, "SCHEDULER_CACHE_CAPACITY")? {
+            cfg.scheduler_cache_capacity = capacity.max(1) as usize;
+        }
+        if dict.contains("SCHEDULER_PLACEMENT_POLICY")? {
+            cfg.scheduler_placement_policy = get_any_config(dict, "SCHEDULER_PLACEMENT_POLICY")?;
+        }
+        if dict.contains("SCHEDULER_BACKFILL_MODE")? {
+            cfg.scheduler_backfill_mode = get_any_config(dict, "SCHEDULER_BACKFILL_MODE")?;
+        }
+        if dict.contains("SCHEDULER_ARRAY_POLICY")? {
+            cfg.scheduler_array_policy = get_any_config(dict, "SCHEDULER_ARRAY_POLICY")?;
+        }
+        if let Some(rounding) = get_opt_i64_config(dict, "SCHEDULER_SLOTSET_BEGIN_ROUNDING")? {
+            cfg.scheduler_slotset_begin_rounding = rounding.max(0);
+        }
+        if let Some(max_slots) = get_opt_i64_config(dict, "SCHEDULER_MAX_SLOTS")? {
+            cfg.scheduler_max_slots = max_slots.max(0) as usize;
+        }
+        cfg.scheduler_job_traces = get_opt_bool_config(dict, "SCHEDULER_JOB_TRACES")?.unwrap_or(false);
+        cfg.walltime_change_enabled = get_opt_bool_config(dict, "WALLTIME_CHANGE_ENABLED")?.unwrap_or(false);
+        cfg.scheduler_queue_horizons = get_opt_str_config(dict, "SCHEDULER_QUEUE_HORIZONS")?;
         cfg.scheduler_fairsharing_window_size = get_opt_i64_config(dict, "SCHEDULER_FAIRSHARING_WINDOW_SIZE")?;
         cfg.scheduler_fairsharing_project_targets = get_opt_str_config(dict, "SCHEDULER_FAIRSHARING_PROJECT_TARGETS")?;
         cfg.scheduler_fairsharing_user_targets = get_opt_str_config(dict, "SCHEDULER_FAIRSHARING_USER_TARGETS")?;
@@ -1,8 +1,13 @@
+use crate::model::walltime::WalltimeEstimator;
+use crate::platform::{proc_set_to_oar_string, ResourceSet};
 use crate::scheduler::hierarchy::HierarchyRequests;
 use auto_bench_fct::auto_bench_fct_hy;
-use log::warn;
+use log::{debug, warn};
+use prettytable::{format, row, Table};
 use range_set_blaze::RangeSetBlaze;
 use std::collections::HashMap;
+use std::io::Write;
+use std::rc::Rc;
 
 pub type ProcSet = RangeSetBlaze<u32>;
 
@@ -15,16 +20,20 @@ pub struct Job {
     pub queue: Box<str>,
     pub types: HashMap<Box<str>, Option<Box<str>>>,
     pub moldables: Vec<Moldable>,
-    /// This attribute is set to true if job has the type key "no_quotas", which means the job is not limited by quotas.
+    /// This attribute is set to true if job has the type key "no_quotas" or "noquotas", which means the job is not limited by quotas:
+    /// it is still placed, but it is neither checked against nor counted in the quotas counters.
     pub no_quotas: bool,
+    /// This attribute is set to true if job has the type `suspend=yes`, which means the job can only be assigned
+    /// the suspendable resources of the platform (see [`crate::platform::ResourceSet::suspendable_resources`]).
+    pub suspendable: bool,
     /// The time interval and resources assigned to the job.
     pub assignment: Option<JobAssignment>,
     /// Used for benchmarking the quotas hit count
     pub quotas_hit_count: u32,
     pub time_sharing: Option<TimeSharingType>,
     pub placeholder: PlaceholderType,
-    /// List of job dependencies, tuples of (job_id, state, exit_code)
-    pub dependencies: Vec<(i64, Box<str>, Option<i32>)>,
+    /// List of job dependencies, see [`JobDependency`].
+    pub dependencies: Vec<JobDependency>,
     /// Attribute used to store the start time of advance reservation jobs before they get an assignment.
     pub advance_reservation_begin: Option<i64>,
     /// Job submission epoch seconds (used for multifactor age)
@@ -34,6 +43,11 @@ pub struct Job {
     /// Job nice value (>=1.0) (used for multifactor nice)
     pub nice: f64,
     pub karma: f64,
+    /// Optional job priority, higher values are scheduled first. Set from the job type `priority=N` if not explicitly set.
+    /// Jobs without priority keep their original (FIFO) order.
+    pub priority: Option<i32>,
+    /// Id of the job array the job belongs to, shared by all the members of the array.
+    pub array_id: Option<i64>,
     pub message: String,
     pub state: String,
 }
@@ -52,6 +66,8 @@ pub struct Moldable {
     pub id: i64,
     pub walltime: i64,
     pub requests: HierarchyRequests,
+    /// Resources that must not be assigned to the job, e.g., nodes excluded by the job properties.
+    pub excluded_resources: ProcSet,
     /// Moldable’s cache key is only calculated at initialization. If fields are changed, the cache key must be recalculated.
     pub cache_key: Box<str>,
 }
@@ -78,27 +94,80 @@ pub enum PlaceholderType {
     None,
 }
 
+/// Dependency of a job on a required job: (job_id, state, exit_code, dependency_type) of the required job.
+pub type JobDependency = (i64, Box<str>, Option<i32>, DependencyType);
+
+/// Condition on the termination of a required job for a dependency to be satisfied.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DependencyType {
+    /// The required job must terminate successfully, i.e., with the exit code 0.
+    /// A terminated job without exit code also counts as successful, as OAR does not record the exit code
+    /// of the jobs whose execution was not followed to the end (e.g., when the connection to the node was lost).
+    #[default]
+    Ok,
+    /// The required job must terminate, whatever its final state and exit code.
+    Anyway,
+    /// Dependency without type, loaded from an OAR 3.0.0 schema without the `job_dependency_type` column
+    /// or from a Python job without dependency type. Same as [`DependencyType::Ok`], except that a required job
+    /// in the Error state is ignored, i.e., the dependency is satisfied, as in OAR Python.
+    Legacy,
+}
+
+impl DependencyType {
+    /// Parses a dependency type, `ok` or `anyway`. Invalid values give [`DependencyType::Ok`] with a warning.
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "ok" => DependencyType::Ok,
+            "anyway" => DependencyType::Anyway,
+            _ => {
+                warn!("Invalid dependency type: {}, using ok", value);
+                DependencyType::Ok
+            }
+        }
+    }
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DependencyType::Ok => "ok",
+            DependencyType::Anyway => "anyway",
+            DependencyType::Legacy => "legacy",
+        }
+    }
+}
+
 impl TimeSharingType {
     pub fn from_str(user: &str, job: &str) -> Self {
+        Self::from_parts(user, job).unwrap_or_else(|| {
+            warn!("Invalid time sharing type: user={}, job={}", user, job);
+            TimeSharingType::AllAll // Default to AllAll if invalid
+        })
+    }
+    fn from_parts(user: &str, job: &str) -> Option<Self> {
         match (user, job) {
-            ("*", "*") => TimeSharingType::AllAll,
-            ("*", "name") => TimeSharingType::AllName,
-            ("name", "*") => TimeSharingType::AllName,
-            ("user", "*") => TimeSharingType::UserAll,
-            ("*", "user") => TimeSharingType::UserAll,
-            ("user", "name") => TimeSharingType::UserName,
-            _ => {
-                warn!("Invalid time sharing type: user={}, job={}", user, job);
-                TimeSharingType::AllAll // Default to AllAll if invalid
-            }
+            ("*", "*") => Some(TimeSharingType::AllAll),
+            ("*", "name") | ("name", "*") => Some(TimeSharingType::AllName),
+            ("user", "*") | ("*", "user") => Some(TimeSharingType::UserAll),
+            ("user", "name") => Some(TimeSharingType::UserName),
+            _ => None,
         }
     }
+    /// Parses an OAR job type string such as `timesharing=*,*` or `timesharing=user,name`.
+    /// Returns `None` if the string is not a time sharing type or if its value is invalid.
+    pub fn from_type_str(type_str: &str) -> Option<Self> {
+        Self::from_type_value(type_str.trim().strip_prefix("timesharing=")?)
+    }
+    /// Parses the value of a `timesharing` job type, e.g. `*,user`.
+    fn from_type_value(value: &str) -> Option<Self> {
+        let (user, job) = value.split_once(',')?;
+        Self::from_parts(user.trim(), job.trim())
+    }
     pub fn from_types(types: &HashMap<Box<str>, Option<Box<str>>>) -> Option<Self> {
         if let Some(value) = types.get(&Box::from("timesharing")) {
             if let Some(value) = value {
-                let parts: Vec<&str> = value.split(',').collect();
-                if parts.len() == 2 {
-                    return Some(TimeSharingType::from_str(parts[0], parts[1]));
+                if value.split(',').count() == 2 {
+                    return Some(TimeSharingType::from_type_value(value).unwrap_or_else(|| {
+                        warn!("Invalid time sharing type: {}", value);
+                        TimeSharingType::AllAll // Default to AllAll if invalid
+                    }));
                 } else {
                     warn!("Invalid time sharing type: {}", value);
                 }
@@ -111,19 +180,25 @@ impl TimeSharingType {
 }
 
 impl PlaceholderType {
-    pub fn from_types(types: &HashMap<Box<str>, Option<Box<str>>>) -> Self {
-        if let Some(value) = types.get(&Box::from("placeholder")) {
-            if let Some(name) = value {
-                return PlaceholderType::Placeholder(name.clone());
-            } else {
-                warn!("Invalid placeholder type: missing name");
-            }
+    /// Parses an OAR job type string `placeholder=NAME` or `allow=NAME`.
+    /// Returns `PlaceholderType::None` for any other job type, or if the name is empty.
+    pub fn from_type_str(type_str: &str) -> Self {
+        match type_str.trim().split_once('=') {
+            Some(("placeholder", name)) if !name.is_empty() => PlaceholderType::Placeholder(name.into()),
+            Some(("allow", name)) if !name.is_empty() => PlaceholderType::Allow(name.into()),
+            _ => PlaceholderType::None,
         }
-        if let Some(value) = types.get(&Box::from("allow")) {
-            if let Some(name) = value {
-                return PlaceholderType::Allow(name.clone());
-            } else {
-                warn!("Invalid allow type: missing name");
+    }
+    /// Finds the placeholder type in the job types, `placeholder` taking precedence over `allow`.
+    pub fn from_types(types: &HashMap<Box<str>, Option<Box<str>>>) -> Self {
+        for key in ["placeholder", "allow"] {
+            if let Some(value) = types.get(key) {
+                let placeholder = PlaceholderType::from_type_str(&format!("{}={}", key, value.as_deref().unwrap_or("")));
+                if placeholder.is_none() {
+                    warn!("Invalid {} type: missing name", key);
+                } else {
+                    return placeholder;
+                }
             }
         }
         PlaceholderType::None
@@ -149,6 +224,13 @@ impl Job {
     pub fn end(&self) -> Option<i64> {
         if let Some(data) = &self.assignment { Some(data.end) } else { None }
     }
+    /// Returns the end of a job of `walltime` seconds beginning at `begin`. Ends are inclusive: a job of walltime 1 ends at its begin.
+    /// A walltime that is not positive is handled as a walltime of 1, the job still occupying its first second:
+    /// such moldables are rejected by the scheduler, but the end of already scheduled jobs must still be computable.
+    pub fn compute_end(begin: i64, walltime: i64) -> i64 {
+        begin + walltime.max(1) - 1
+    }
+    /// Returns the walltime of the job assignment, `end - begin + 1` as the end is inclusive (see [`Job::compute_end`]).
     pub fn walltime(&self) -> Option<i64> {
         if let Some(data) = &self.assignment {
             Some(data.end - data.begin + 1)
@@ -156,6 +238,16 @@ impl Job {
             None
         }
     }
+    /// Returns the begin, end and resources of the job assignment, or `None` if the job is not scheduled.
+    pub fn window(&self) -> Option<(i64, i64, &ProcSet)> {
+        self.assignment
+            .as_ref()
+            .map(|assignment| (assignment.begin, assignment.end, &assignment.resources))
+    }
+    /// Returns the time the job has to wait from `now` before beginning, 0 if it already began, or `None` if the job is not scheduled.
+    pub fn estimated_wait_time(&self, now: i64) -> Option<i64> {
+        self.begin().map(|begin| (begin - now).max(0))
+    }
     pub fn resource_count(&self) -> Option<u32> {
         if let Some(data) = &self.assignment {
             Some(data.resources.core_count())
@@ -163,18 +255,67 @@ impl Job {
             None
         }
     }
+    /// Returns the end of the job assignment without the security time, or `None` if the job is not scheduled.
+    /// See [`Moldable::effective_end`].
+    pub fn effective_end(&self, security_time: i64) -> Option<i64> {
+        self.assignment
+            .as_ref()
+            .map(|assignment| (assignment.end - security_time).max(assignment.begin))
+    }
+    /// Returns the name of the slot set in which the job should be scheduled.
+    /// Inner jobs (type `inner=NAME`) are scheduled in the slot set `NAME` created by their container job(s),
+    /// which is the container job id if the container job has no name (type `container` without value).
     pub fn slot_set_name(&self) -> Box<str> {
         let mut slot_set_name: Box<str> = "default".into();
         // Manage inner jobs
-        if self.types.contains_key::<Box<str>>(&"inner".into()) {
-            slot_set_name = self.types[&Box::from("inner")].clone().unwrap();
+        if let Some(inner) = self.types.get(&Box::from("inner")) {
+            if let Some(name) = inner {
+                slot_set_name = name.clone();
+            } else {
+                warn!("Invalid inner type for job {}: missing container name, using the default slot set", self.id);
+            }
         }
         slot_set_name
     }
 
+    /// Parses the job priority from the job type `priority=N`.
+    pub fn priority_from_types(types: &HashMap<Box<str>, Option<Box<str>>>) -> Option<i32> {
+        match types.get(&Box::from("priority")) {
+            Some(Some(value)) => value.parse::<i32>().map_err(|_| warn!("Invalid priority type: {}", value)).ok(),
+            Some(None) => {
+                warn!("Invalid priority type: missing value");
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Returns the delay in seconds by which the advance reservation of the job may slide later if its requested window is occupied,
+    /// parsed from the job type `max_delay=N`. Returns 0, i.e., the reservation can't slide, if the type is missing or invalid.
+    pub fn reservation_max_delay(&self) -> i64 {
+        match self.types.get("max_delay") {
+            Some(Some(value)) => value.parse::<i64>().map_err(|_| warn!("Invalid max_delay type: {}", value)).unwrap_or(0).max(0),
+            Some(None) => {
+                warn!("Invalid max_delay type: missing value");
+                0
+            }
+            None => 0,
+        }
+    }
+
+    /// Returns true if the job types contain the `no_quotas` key or its `noquotas` alias.
+    pub fn no_quotas_from_types(types: &HashMap<Box<str>, Option<Box<str>>>) -> bool {
+        types.contains_key("no_quotas") || types.contains_key("noquotas")
+    }
+
+    /// Returns true if the job types contain `suspend=yes`.
+    pub fn suspendable_from_types(types: &HashMap<Box<str>, Option<Box<str>>>) -> bool {
+        matches!(types.get("suspend"), Some(Some(value)) if value.as_ref() == "yes")
+    }
+
     /// Returns true if the job can be scheduled using the cache.
     pub fn can_use_cache(&self) -> bool {
-        self.time_sharing.is_none() && self.placeholder.is_none() && !self.no_quotas
+        self.time_sharing.is_none() && self.placeholder.is_none() && !self.no_quotas && !self.suspendable
     }
     /// Returns true if the job assignment can be used to insert a cache entry.
     pub fn can_set_cache(&self) -> bool {
@@ -193,11 +334,14 @@ pub struct JobBuilder {
     assignment: Option<JobAssignment>,
     time_sharing: Option<TimeSharingType>,
     placeholder: Option<PlaceholderType>,
-    dependencies: Vec<(i64, Box<str>, Option<i32>)>,
+    dependencies: Vec<JobDependency>,
     advance_reservation_start_time: Option<i64>,
     submission_time: i64,
+    priority: Option<i32>,
+    array_id: Option<i64>,
     message: String,
     state: String,
+    walltime_estimator: Option<Rc<dyn WalltimeEstimator>>,
 }
 
 impl JobBuilder {
@@ -216,14 +360,29 @@ impl JobBuilder {
             dependencies: Vec::new(),
             advance_reservation_start_time: None,
             submission_time: 0,
+            priority: None,
+            array_id: None,
             message: String::new(),
             state: "Waiting".into(),
+            walltime_estimator: None,
         }
     }
     pub fn moldable_auto(mut self, id: i64, walltime: i64, requests: HierarchyRequests) -> Self {
         self.moldables.push(Moldable::new(id, walltime, requests));
         self
     }
+    /// Adds a moldable with `key` as cache key, instead of the key computed from its walltime and requests.
+    /// Moldables sharing a cache key start their slots search from the same cache entry (see [`SlotSet::get_cache_first_slot`]),
+    /// so forcing a shared key is only correct for moldables of identical walltime and requests, and panics otherwise in debug builds.
+    /// Forcing different keys for identical moldables prevents them from sharing cache entries.
+    ///
+    /// [`SlotSet::get_cache_first_slot`]: crate::scheduler::slotset::SlotSet::get_cache_first_slot
+    pub fn moldable_with_cache_key(mut self, id: i64, walltime: i64, requests: HierarchyRequests, key: Box<str>) -> Self {
+        let mut moldable = Moldable::new(id, walltime, requests);
+        moldable.cache_key = key;
+        self.moldables.push(moldable);
+        self
+    }
     pub fn moldable(mut self, moldable: Moldable) -> Self {
         self.moldables.push(moldable);
         self
@@ -292,16 +451,22 @@ impl JobBuilder {
         self.assignment = assignment;
         self
     }
-    pub fn dependencies(mut self, dependencies: Vec<(i64, Box<str>, Option<i32>)>) -> Self {
+    pub fn dependencies(mut self, dependencies: Vec<JobDependency>) -> Self {
         self.dependencies = dependencies;
         self
     }
-    pub fn add_dependency(mut self, dep_job_id: i64, dep_job_state: Box<str>, dep_job_exit_code: Option<i32>) -> Self {
-        self.dependencies.push((dep_job_id, dep_job_state, dep_job_exit_code));
+    pub fn add_dependency(
+        mut self,
+        dep_job_id: i64,
+        dep_job_state: Box<str>,
+        dep_job_exit_code: Option<i32>,
+        dep_type: DependencyType,
+    ) -> Self {
+        self.dependencies.push((dep_job_id, dep_job_state, dep_job_exit_code, dep_type));
         self
     }
     pub fn add_valid_dependency(self, dep_job_id: i64) -> Self {
-        self.add_dependency(dep_job_id, "Waiting".into(), None)
+        self.add_dependency(dep_job_id, "Waiting".into(), None, DependencyType::Ok)
     }
     pub fn set_advance_reservation_start_time(mut self, start_time: i64) -> Self {
         self.advance_reservation_start_time = Some(start_time);
@@ -311,6 +476,22 @@ impl JobBuilder {
         self.submission_time = submission_time;
         self
     }
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+    pub fn priority_opt(mut self, priority: Option<i32>) -> Self {
+        self.priority = priority;
+        self
+    }
+    pub fn array_id(mut self, array_id: i64) -> Self {
+        self.array_id = Some(array_id);
+        self
+    }
+    pub fn array_id_opt(mut self, array_id: Option<i64>) -> Self {
+        self.array_id = array_id;
+        self
+    }
     pub fn message(mut self, message: String) -> Self {
         self.message = message;
         self
@@ -319,15 +500,24 @@ impl JobBuilder {
         self.state = state;
         self
     }
-    // Computes automatically the no_quotas from the types and TimeSharing and Placeholder if None.
+    /// Sets the estimator supplying the walltime of the moldables without walltime (see [`WalltimeEstimator`]).
+    /// Their cache key is recomputed from the estimated walltime, even if it was forced with [`JobBuilder::moldable_with_cache_key`].
+    pub fn walltime_estimator(mut self, walltime_estimator: Rc<dyn WalltimeEstimator>) -> Self {
+        self.walltime_estimator = Some(walltime_estimator);
+        self
+    }
+    // Computes automatically the no_quotas and suspendable from the types and TimeSharing, Placeholder and priority if None.
+    // Estimates the walltime of the moldables without walltime if a walltime estimator is set.
     pub fn build(self) -> Job {
-        Job {
+        let priority = self.priority.or_else(|| Job::priority_from_types(&self.types));
+        let mut job = Job {
             id: self.id,
             name: self.name,
             user: self.user,
             project: self.project,
             queue: self.queue.unwrap_or_else(|| Box::from("default")),
-            no_quotas: self.types.contains_key(&Box::from("no_quotas")),
+            no_quotas: Job::no_quotas_from_types(&self.types),
+            suspendable: Job::suspendable_from_types(&self.types),
             time_sharing: self.time_sharing.or(TimeSharingType::from_types(&self.types)),
             placeholder: self.placeholder.unwrap_or(PlaceholderType::from_types(&self.types)),
             types: self.types,
@@ -340,10 +530,126 @@ impl JobBuilder {
             qos: 0.0,
             nice: 1.0,
             karma: 0.0,
+            priority,
+            array_id: self.array_id,
             message: self.message,
             state: self.state,
+        };
+        if job.moldables.iter().any(|moldable| !moldable.has_walltime()) {
+            match self.walltime_estimator {
+                Some(walltime_estimator) => {
+                    let walltime = walltime_estimator.estimate_walltime(&job);
+                    for moldable in job.moldables.iter_mut().filter(|moldable| !moldable.has_walltime()) {
+                        moldable.set_walltime(walltime);
+                    }
+                }
+                // Logged once per scheduling cycle for all the jobs, see `kamelot::remove_unsatisfiable_jobs`
+                None => debug!("Job {} has moldables without walltime, and no walltime estimator", job.id),
+            }
+        }
+        job
+    }
+}
+
+/// Builds a `Table` for displaying the assignments of the provided jobs in a human-readable format.
+/// Jobs without assignment are listed with empty assignment columns.
+pub fn assignments_to_table<'a>(jobs: impl IntoIterator<Item = &'a Job>) -> Table {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_CLEAN);
+    table.add_row(row![
+        buFc->"Job id",
+        buFc->"Queue",
+        buFc->"Moldable id",
+        buFc->"Begin (epoch)",
+        buFc->"End (epoch)",
+        buFc->"Walltime",
+        buFc->"Cores",
+        buFc->"ProcSet"
+    ]);
+    for job in jobs {
+        if let Some(assignment) = &job.assignment {
+            table.add_row(row![
+                job.id,
+                job.queue,
+                job.moldables[assignment.moldable_index].id,
+                assignment.begin,
+                assignment.end,
+                assignment.end - assignment.begin + 1,
+                assignment.resources.core_count(),
+                assignment.resources,
+            ]);
+        } else {
+            table.add_row(row![job.id, job.queue, "", "", "", "", "", ""]);
+        }
+    }
+    table
+}
+
+/// Returns the average of the estimated wait times of the scheduled `jobs` (see [`Job::estimated_wait_time`]),
+/// rounded down to the second, or `None` if none of the jobs is scheduled.
+pub fn average_wait_time<'a>(jobs: impl IntoIterator<Item = &'a Job>, now: i64) -> Option<i64> {
+    let (count, total) = jobs
+        .into_iter()
+        .filter_map(|job| job.estimated_wait_time(now))
+        .fold((0, 0), |(count, total), wait_time| (count + 1, total + wait_time));
+    (count > 0).then(|| total / count)
+}
+
+/// Returns the occupation of the resources by the assigned `jobs` during `begin..=end`, e.g., to render a heatmap of the fragmentation.
+/// Row `i` is the time bucket of `bucket_size` seconds beginning at `begin + i * bucket_size`, the last one ending at `end`,
+/// and column `j` is the resource of enumerated ID `j`, up to `resource_count`. Each cell is the percentage of the bucket
+/// during which the resource is assigned, rounded up so that any occupation is visible, and capped to 100 with time-sharing.
+pub fn occupation_matrix<'a>(jobs: impl IntoIterator<Item = &'a Job>, begin: i64, end: i64, resource_count: u32, bucket_size: i64) -> Vec<Vec<u8>> {
+    assert!(bucket_size > 0, "The bucket size must be positive, got {}", bucket_size);
+    let bucket_count = if end < begin { 0 } else { ((end - begin) / bucket_size + 1) as usize };
+    let mut occupied_seconds = vec![vec![0i64; resource_count as usize]; bucket_count];
+    for assignment in jobs.into_iter().filter_map(|job| job.assignment.as_ref()) {
+        let (job_begin, job_end) = (assignment.begin.max(begin), assignment.end.min(end));
+        if job_begin > job_end {
+            continue;
+        }
+        let (first_bucket, last_bucket) = (((job_begin - begin) / bucket_size) as usize, ((job_end - begin) / bucket_size) as usize);
+        for (bucket, row) in occupied_seconds.iter_mut().enumerate().take(last_bucket + 1).skip(first_bucket) {
+            let bucket_begin = begin + bucket as i64 * bucket_size;
+            let seconds = job_end.min(bucket_begin + bucket_size - 1) - job_begin.max(bucket_begin) + 1;
+            for resource in assignment.resources.iter().take_while(|resource| *resource < resource_count) {
+                row[resource as usize] += seconds;
+            }
+        }
+    }
+    occupied_seconds
+        .into_iter()
+        .enumerate()
+        .map(|(bucket, row)| {
+            let bucket_begin = begin + bucket as i64 * bucket_size;
+            let bucket_length = end.min(bucket_begin + bucket_size - 1) - bucket_begin + 1;
+            row.into_iter()
+                .map(|seconds| ((seconds * 100 + bucket_length - 1) / bucket_length).min(100) as u8)
+                .collect()
+        })
+        .collect()
+}
+
+/// Writes the assignments of the assigned `jobs` as CSV to `w`, one row per job after the header
+/// `job_id,moldable_id,begin,end,core_count,resources_oar_string`, the resources being formatted with [`proc_set_to_oar_string`].
+/// Jobs without assignment are skipped. Rows are written as they are iterated, without buffering the whole gantt.
+pub fn export_gantt_csv<'a>(jobs: impl IntoIterator<Item = &'a Job>, w: &mut impl Write) -> std::io::Result<()> {
+    writeln!(w, "job_id,moldable_id,begin,end,core_count,resources_oar_string")?;
+    for job in jobs {
+        if let Some(assignment) = &job.assignment {
+            writeln!(
+                w,
+                "{},{},{},{},{},{}",
+                job.id,
+                job.moldables[assignment.moldable_index].id,
+                assignment.begin,
+                assignment.end,
+                assignment.resources.core_count(),
+                proc_set_to_oar_string(&assignment.resources)
+            )?;
         }
     }
+    Ok(())
 }
 
 impl JobAssignment {
@@ -358,17 +664,89 @@ impl JobAssignment {
     pub fn count_resources(&self) -> u32 {
         self.resources.len() as u32
     }
+    /// Returns the id of the moldable chosen for this assignment among the job `moldables`,
+    /// or `None` if the job has no moldable at `moldable_index`, e.g., for jobs built without moldables.
+    pub fn moldable_id(&self, moldables: &[Moldable]) -> Option<i64> {
+        moldables.get(self.moldable_index).map(|moldable| moldable.id)
+    }
 }
 
 impl Moldable {
     pub fn new(id: i64, walltime: i64, requests: HierarchyRequests) -> Moldable {
         Moldable {
-            cache_key: format!("{}-{}", walltime, requests.get_cache_key()).into(),
+            cache_key: Self::compute_cache_key(walltime, &requests, &ProcSet::new()),
             id,
             walltime,
             requests,
+            excluded_resources: ProcSet::new(),
+        }
+    }
+    /// Returns a description of all the fields of the moldable that the cache key must capture, i.e., all of them but the id.
+    /// Used in debug builds to detect cache keys shared by different moldables, see [`crate::scheduler::slotset::SlotSet::get_cache_first_slot`].
+    #[cfg(debug_assertions)]
+    pub fn fingerprint(&self) -> String {
+        format!("{}-{:?}-{:?}", self.walltime, self.requests, self.excluded_resources)
+    }
+    /// Returns the end of the moldable started at `begin` without the security time, i.e., `begin + walltime - 1 - security_time`.
+    /// The effective end is never before `begin`, even if the walltime is not greater than the security time.
+    pub fn effective_end(&self, begin: i64, security_time: i64) -> i64 {
+        (Job::compute_end(begin, self.walltime) - security_time).max(begin)
+    }
+    /// Sets the resources that must not be assigned to the job, and updates the cache key accordingly.
+    pub fn with_excluded_resources(mut self, excluded_resources: ProcSet) -> Moldable {
+        self.cache_key = Self::compute_cache_key(self.walltime, &self.requests, &excluded_resources);
+        self.excluded_resources = excluded_resources;
+        self
+    }
+    /// Returns false if the moldable has been submitted without walltime, i.e., if its walltime is not greater than 0.
+    pub fn has_walltime(&self) -> bool {
+        self.walltime > 0
+    }
+    /// Sets the walltime of the moldable, and updates the cache key accordingly.
+    pub fn set_walltime(&mut self, walltime: i64) {
+        self.walltime = walltime;
+        self.cache_key = Self::compute_cache_key(walltime, &self.requests, &self.excluded_resources);
+    }
+    fn compute_cache_key(walltime: i64, requests: &HierarchyRequests, excluded_resources: &ProcSet) -> Box<str> {
+        if excluded_resources.is_empty() {
+            format!("{}-{}", walltime, requests.get_cache_key()).into()
+        } else {
+            format!("{}-{}-{}", walltime, requests.get_cache_key(), excluded_resources).into()
         }
     }
+    /// Returns true if the moldable has no request, or a request with no level or a zero (minimum) number of units at a level,
+    /// i.e., if the moldable could be assigned an empty `ProcSet`. Such moldables are misconfigured and their jobs are rejected.
+    pub fn requests_zero_resources(&self) -> bool {
+        self.requests.0.is_empty()
+            || self
+                .requests
+                .0
+                .iter()
+                .any(|request| request.level_nbs.is_empty() || request.level_nbs.iter().any(|(_, nb)| *nb == 0))
+    }
+    /// Returns false if a request of the moldable asks for more units at a level than the platform has,
+    /// e.g., 50 nodes on a 39-node cluster, in which case the moldable can never be placed whatever the other jobs.
+    /// The units requested at a level are multiplied by the units requested at the upper levels,
+    /// and only the default resources matching the request filter and not excluded are counted.
+    /// This is a necessary condition only: a satisfiable moldable may still not fit the hierarchy.
+    /// Levels unknown to the hierarchy are not checked, with a warning: the job is kept waiting rather than rejected.
+    pub fn is_satisfiable(&self, resource_set: &ResourceSet) -> bool {
+        let resources = &resource_set.default_resources - &self.excluded_resources;
+        self.requests.0.iter().all(|request| {
+            let proc_set = &request.filter & &resources;
+            let mut requested_units = 1u32;
+            request.level_nbs.iter().all(|(level, nb)| {
+                requested_units = requested_units.saturating_mul(*nb);
+                match resource_set.hierarchy.count_units(&proc_set, level) {
+                    Some(units) => units >= requested_units,
+                    None => {
+                        warn!("Moldable {} requests the unknown hierarchy level {}, its satisfiability is not checked", self.id, level);
+                        true
+                    }
+                }
+            })
+        })
+    }
 }
 
 pub trait ProcSetCoresOp {
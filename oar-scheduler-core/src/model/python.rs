@@ -1,4 +1,5 @@
 use crate::model::job::{Job, Moldable, ProcSet};
+use crate::scheduler::trace::TraceStep;
 use pyo3::prelude::{PyAnyMethods, PyListMethods, PyModule};
 use pyo3::types::{PyDict, PyList, PyTuple};
 use pyo3::{Bound, IntoPyObject, PyAny, PyErr, Python};
@@ -76,7 +77,29 @@ impl<'a> IntoPyObject<'a> for &Moldable {
         dict.set_item("id", &self.id)?;
         dict.set_item("walltime", &self.walltime)?;
         dict.set_item("requests", &self.requests)?;
+        dict.set_item("excluded_resources", proc_set_to_python(py, &self.excluded_resources))?;
         dict.set_item("cache_key", &self.cache_key.to_string())?;
         Ok(dict)
     }
 }
+
+impl<'a> IntoPyObject<'a> for TraceStep {
+    type Target = PyDict;
+    type Output = Bound<'a, Self::Target>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'a>) -> Result<Self::Output, Self::Error> {
+        let dict = PyDict::new(py);
+        dict.set_item("moldable_id", self.moldable_id)?;
+        dict.set_item("begin", self.begin)?;
+        dict.set_item("end", self.end)?;
+        dict.set_item("available_resources", proc_set_to_python(py, &self.available_resources))?;
+        if let Some(hierarchy_result) = &self.hierarchy_result {
+            dict.set_item("hierarchy_result", proc_set_to_python(py, hierarchy_result))?;
+        } else {
+            dict.set_item("hierarchy_result", py.None())?;
+        }
+        dict.set_item("quotas_exceeded", self.quotas_exceeded.as_deref())?;
+        Ok(dict)
+    }
+}
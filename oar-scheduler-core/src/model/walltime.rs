@@ -0,0 +1,45 @@
+use crate::model::job::Job;
+use std::collections::HashMap;
+
+/// Strategy supplying the walltime of the moldables submitted without one, i.e., with a walltime not greater than 0.
+/// Registered on a [`crate::model::job::JobBuilder`] with [`crate::model::job::JobBuilder::walltime_estimator`],
+/// it is called once per built job having moldables without walltime, all of them getting the estimated walltime.
+pub trait WalltimeEstimator {
+    fn estimate_walltime(&self, job: &Job) -> i64;
+}
+
+/// Estimator giving the same walltime to all the jobs.
+pub struct ConstantEstimator(pub i64);
+
+impl WalltimeEstimator for ConstantEstimator {
+    fn estimate_walltime(&self, _job: &Job) -> i64 {
+        self.0
+    }
+}
+
+/// Estimator giving the default walltime of the queue of the job, or `default_walltime` for the queues without default walltime.
+#[derive(Clone)]
+pub struct QueueDefaultEstimator {
+    queues_walltime: HashMap<Box<str>, i64>,
+    default_walltime: i64,
+}
+
+impl QueueDefaultEstimator {
+    pub fn new(default_walltime: i64) -> Self {
+        QueueDefaultEstimator {
+            queues_walltime: HashMap::new(),
+            default_walltime,
+        }
+    }
+    /// Sets the default walltime of the jobs of `queue`.
+    pub fn queue(mut self, queue: Box<str>, walltime: i64) -> Self {
+        self.queues_walltime.insert(queue, walltime);
+        self
+    }
+}
+
+impl WalltimeEstimator for QueueDefaultEstimator {
+    fn estimate_walltime(&self, job: &Job) -> i64 {
+        self.queues_walltime.get(&job.queue).copied().unwrap_or(self.default_walltime)
+    }
+}
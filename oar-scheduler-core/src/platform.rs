@@ -4,7 +4,10 @@ pub use crate::model::job::{Job, ProcSet, ProcSetCoresOp};
 use crate::model::python::proc_set_to_python;
 use crate::scheduler::calendar::QuotasConfig;
 use crate::scheduler::hierarchy::Hierarchy;
+use crate::scheduler::kamelot::CancellationToken;
+use crate::scheduler::observer::SchedulingObserver;
 use indexmap::IndexMap;
+use log::warn;
 #[cfg(feature = "pyo3")]
 use pyo3::prelude::{PyDictMethods, PyListMethods};
 #[cfg(feature = "pyo3")]
@@ -22,6 +25,16 @@ pub trait PlatformTrait {
     /// Returns already scheduled jobs (in higher priority queues), or advanced reservations.
     fn get_scheduled_jobs(&self) -> Vec<Job>;
 
+    /// Returns the begin, end and resources of the jobs currently running, i.e., in the `Running`, `Launching` or `Finishing` state, keyed by job id.
+    /// `scheduled_jobs` are the jobs returned by [`PlatformTrait::get_scheduled_jobs`]: by default, the running jobs are taken from them.
+    fn running_jobs_windows(&self, scheduled_jobs: &[Job]) -> HashMap<i64, (i64, i64, ProcSet)> {
+        scheduled_jobs
+            .iter()
+            .filter(|job| matches!(job.state.as_ref(), "Running" | "Launching" | "Finishing"))
+            .filter_map(|job| job.window().map(|(begin, end, resources)| (job.id, (begin, end, resources.clone()))))
+            .collect()
+    }
+
     /// Returns the jobs waiting to be scheduled for the provided queues.
     /// Jobs should be sorted according to the meta-scheduler sorting algorithm.
     /// Using `IndexMap` to keep jobs ordered while still allowing O(1) access by job ID.
@@ -32,6 +45,11 @@ pub trait PlatformTrait {
     /// to add them to the scheduled list, and to save them to the database
     fn save_assignments(&mut self, assigned_jobs: IndexMap<i64, Job>);
 
+    /// Marks the jobs that can never be scheduled as rejected, e.g., because they request more resources than the platform has.
+    /// Their `message` tells the reason. The jobs should not be returned by [`PlatformTrait::get_waiting_jobs`] anymore.
+    /// By default, the jobs are left waiting.
+    fn reject_jobs(&mut self, _rejected_jobs: IndexMap<i64, Job>) {}
+
     // --- Accounting DB access ---
     /// Returns summed accounting for all queues in [window_start, window_stop):
     /// (ASKED, USED)
@@ -57,12 +75,35 @@ pub trait PlatformTrait {
         window_start: i64,
         window_stop: i64,
     ) -> (HashMap<String, f64>, HashMap<String, f64>);
+
+    /// Returns the recent resource usage of each user, given to the `hook_fairshare` hook (see [`crate::hooks::HooksHandler`]).
+    /// By default, returns the USED accounting of the users over the last `SCHEDULER_FAIRSHARING_WINDOW_SIZE` seconds (30 days if not set).
+    fn get_users_usage(&self, queues: &[String]) -> HashMap<Box<str>, f64> {
+        let window_size = self.get_platform_config().config.scheduler_fairsharing_window_size.unwrap_or(30 * 24 * 3600);
+        let now = self.get_now();
+        let (_user_asked, user_used) = self.get_sum_accounting_by_user(queues, now - window_size, now);
+        user_used.into_iter().map(|(user, used)| (user.into_boxed_str(), used)).collect()
+    }
+
+    /// Returns the observer notified of each scheduling decision, if any.
+    /// No event is built when `None` is returned.
+    fn get_scheduling_observer(&self) -> Option<&dyn SchedulingObserver> {
+        None
+    }
+
+    /// Returns the token cancelling the scheduling cycles of this platform, if any (see [`CancellationToken`]).
+    fn get_cancellation_token(&self) -> Option<&CancellationToken> {
+        None
+    }
 }
 
 #[cfg_attr(feature = "pyo3", derive(IntoPyObjectRef))]
 pub struct PlatformConfig {
     pub resource_set: ResourceSet,
     pub quotas_config: QuotasConfig,
+    /// Per-queue quotas configurations, whose rules are checked in addition to the `quotas_config` rules for the jobs of the queue.
+    /// Built by [`build_queues_quotas_config`].
+    pub queues_quotas_config: HashMap<String, QuotasConfig>,
     pub config: Configuration
 }
 
@@ -73,13 +114,76 @@ pub struct PlatformConfig {
 pub struct ResourceSet {
     pub nb_resources_not_dead: u32,
     pub nb_resources_default_not_dead: u32,
+    /// Sum of the weights of the resources that are not dead, i.e., `nb_resources_not_dead` if no weight is configured.
+    pub weighted_resources_not_dead: u64,
+    /// Sum of the weights of the resources of type default that are not dead, i.e., `nb_resources_default_not_dead` if no weight is configured.
+    pub weighted_resources_default_not_dead: u64,
     /// Resources that contain a type in the config SCHEDULER_AVAILABLE_SUSPENDED_RESOURCE_TYPE list.
     pub suspendable_resources: ProcSet,
     /// Default available resources for slot initialization.
     pub default_resources: ProcSet,
-    /// For each `ProcSet`, the time until which it is available. Integrated through pseudo jobs.
+    /// For each `ProcSet`, the time until which it is available. Integrated by `SlotSet::from_platform_config`.
     pub available_upto: Vec<(i64, ProcSet)>,
+    /// For each `ProcSet`, the time from which it is available, e.g., for absent resources coming back later.
+    /// Integrated by `SlotSet::from_platform_config`.
+    pub available_from: Vec<(i64, ProcSet)>,
     pub hierarchy: Hierarchy,
+    /// Prefix sums of the resource weights by enumerated ID: the weight of the resources `0..i` is at index `i`.
+    /// Built by [`ResourceSet::core_weight_prefix_sums`] and counted by [`ResourceSet::weighted_core_count`].
+    /// Empty if every resource weighs 1, and resources beyond its length weigh 1.
+    pub core_weight_prefix_sums: Vec<u64>,
+}
+
+impl ResourceSet {
+    /// Creates a `ResourceSet` without any resource, used when no resource can be scheduled on.
+    pub fn empty() -> ResourceSet {
+        ResourceSet {
+            nb_resources_not_dead: 0,
+            nb_resources_default_not_dead: 0,
+            weighted_resources_not_dead: 0,
+            weighted_resources_default_not_dead: 0,
+            suspendable_resources: ProcSet::new(),
+            default_resources: ProcSet::new(),
+            available_upto: vec![],
+            available_from: vec![],
+            hierarchy: Hierarchy::new(),
+            core_weight_prefix_sums: vec![],
+        }
+    }
+    /// Computes the prefix sums of the weights of the resources by enumerated ID, for `core_weight_prefix_sums`.
+    pub fn core_weight_prefix_sums(weights: impl IntoIterator<Item = u32>) -> Vec<u64> {
+        std::iter::once(0)
+            .chain(weights.into_iter().scan(0u64, |sum, weight| {
+                *sum += weight as u64;
+                Some(*sum)
+            }))
+            .collect()
+    }
+    /// Weight of the resources `0..index`, resources beyond the prefix sums weighing 1.
+    fn weight_before(&self, index: u64) -> u64 {
+        let last = self.core_weight_prefix_sums.len() as u64 - 1;
+        if index <= last {
+            self.core_weight_prefix_sums[index as usize]
+        } else {
+            self.core_weight_prefix_sums[last as usize] + index - last
+        }
+    }
+    /// Sum of the weights of the resources of `proc_set`, used for the quotas `resources` accounting.
+    /// Equals `proc_set.core_count()` if no weight is configured.
+    pub fn weighted_core_count(&self, proc_set: &ProcSet) -> u64 {
+        if self.core_weight_prefix_sums.is_empty() {
+            return proc_set.core_count() as u64;
+        }
+        proc_set
+            .ranges()
+            .map(|range| self.weight_before(*range.end() as u64 + 1) - self.weight_before(*range.start() as u64))
+            .sum()
+    }
+    /// Weighted count of the resources of `proc_set` that are of type default,
+    /// used for the quotas `default_resources` accounting.
+    pub fn weighted_default_core_count(&self, proc_set: &ProcSet) -> u64 {
+        self.weighted_core_count(&(proc_set & &self.default_resources))
+    }
 }
 
 #[cfg(feature = "pyo3")]
@@ -101,6 +205,13 @@ impl<'a> IntoPyObject<'a> for &ResourceSet {
         }
         dict.set_item("available_upto", available_upto)?;
 
+        let available_from = PyList::empty(py);
+        for (time, proc_set) in &self.available_from {
+            let tuple = (time, proc_set_to_python(py, proc_set));
+            available_from.append(tuple)?;
+        }
+        dict.set_item("available_from", available_from)?;
+
         dict.set_item("hierarchy", &self.hierarchy)?;
 
         Ok(dict)
@@ -116,12 +227,119 @@ pub fn build_quotas_config(config: &Configuration, res_set: &ResourceSet) -> Quo
         if config.quotas_window_time_limit.is_none() {
             panic!("Quotas are enabled but no quotas window time limit is provided.");
         }
-        let all_value = match &config.quotas_all_nb_resources_mode {
-            QuotasAllNbResourcesMode::DefaultNotDead => res_set.nb_resources_not_dead as i64,
-            QuotasAllNbResourcesMode::All => res_set.default_resources.core_count() as i64,
-        };
-        QuotasConfig::load_from_file(config.quotas_conf_file.clone().unwrap().as_str(), true, all_value, config.quotas_window_time_limit.unwrap())
+        let (all_value, default_all_value) = (quotas_all_value(config, res_set), quotas_default_all_value(config, res_set));
+        let path = config.quotas_conf_file.clone().unwrap();
+        QuotasConfig::load_from_file(path.as_str(), true, all_value, default_all_value, config.quotas_window_time_limit.unwrap())
+            .unwrap_or_else(|e| panic!("Failed to load the quotas configuration: {}", e))
     } else {
         QuotasConfig::new(false, None, Default::default(), Box::new([]))
     }
 }
+
+/// Builds the per-queue QuotasConfig Rust structs from the files of `config.quotas_queues_conf_files`.
+/// Empty if quotas are disabled. Only the `quotas` rules of the queue files are used: their calendar and job types are ignored,
+/// the counters tracking the job types of the global quotas configuration.
+pub fn build_queues_quotas_config(config: &Configuration, res_set: &ResourceSet) -> HashMap<String, QuotasConfig> {
+    if !config.quotas {
+        return HashMap::new();
+    }
+    let (all_value, default_all_value) = (quotas_all_value(config, res_set), quotas_default_all_value(config, res_set));
+    let window_time_limit = config.quotas_window_time_limit.expect("Quotas are enabled but no quotas window time limit is provided.");
+    config
+        .queues_quotas_conf_files()
+        .into_iter()
+        .map(|(queue, path)| {
+            let quotas_config = QuotasConfig::load_from_file(path.as_str(), true, all_value, default_all_value, window_time_limit)
+                .unwrap_or_else(|e| panic!("Failed to load the quotas configuration of the '{}' queue: {}", queue, e));
+            if quotas_config.calendar.is_some() {
+                warn!("Ignoring the temporal quotas of the '{}' queue quotas configuration file '{}'", queue, path);
+            }
+            (queue, quotas_config)
+        })
+        .collect()
+}
+
+/// Returns the value of `ALL` in the quotas rules, depending on `config.quotas_all_nb_resources_mode`.
+/// The resources are weighted as in the quotas counters (see [`ResourceSet::weighted_core_count`]).
+pub(crate) fn quotas_all_value(config: &Configuration, res_set: &ResourceSet) -> i64 {
+    match &config.quotas_all_nb_resources_mode {
+        QuotasAllNbResourcesMode::DefaultNotDead => res_set.weighted_resources_not_dead as i64,
+        QuotasAllNbResourcesMode::All => res_set.weighted_core_count(&res_set.default_resources) as i64,
+    }
+}
+
+/// Returns the value of `ALL` in the default resources limit of the quotas rules (see [`QuotasValue::from_serde_values`]),
+/// counting only the resources of type default, depending on `config.quotas_all_nb_resources_mode`.
+pub(crate) fn quotas_default_all_value(config: &Configuration, res_set: &ResourceSet) -> i64 {
+    match &config.quotas_all_nb_resources_mode {
+        QuotasAllNbResourcesMode::DefaultNotDead => res_set.weighted_resources_default_not_dead as i64,
+        QuotasAllNbResourcesMode::All => res_set.weighted_default_core_count(&res_set.default_resources) as i64,
+    }
+}
+
+/// Formats a `ProcSet` in OAR's compact string format: space-separated ranges, e.g., `1-4 7 10-12`.
+/// An empty set gives an empty string.
+pub fn proc_set_to_oar_string(proc_set: &ProcSet) -> String {
+    proc_set
+        .ranges()
+        .map(|range| {
+            if range.start() == range.end() {
+                range.start().to_string()
+            } else {
+                format!("{}-{}", range.start(), range.end())
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Parses a `ProcSet` from OAR's compact string format (see [`proc_set_to_oar_string`]).
+/// Ranges may overlap or be unordered. Malformed ranges are ignored.
+pub fn proc_set_from_oar_string(value: &str) -> ProcSet {
+    value
+        .split_whitespace()
+        .filter_map(|range| {
+            let parsed = match range.split_once('-') {
+                Some((start, end)) => start.parse::<u32>().ok().zip(end.parse::<u32>().ok()),
+                None => range.parse::<u32>().ok().map(|value| (value, value)),
+            };
+            match parsed {
+                Some((start, end)) if start <= end => Some(start..=end),
+                _ => {
+                    warn!("Ignoring malformed proc set range: '{}'", range);
+                    None
+                }
+            }
+        })
+        .collect::<ProcSet>()
+}
+
+/// Exports a `ProcSet` as a dense bit array of `n_resources` bits, e.g., to cross the Python boundary as `bytes` loadable with
+/// `numpy.unpackbits(..., bitorder="little")`. Bit `i % 8` of byte `i / 8` is set if the resource `i` is in the set.
+/// Resources not lower than `n_resources` are ignored.
+pub fn proc_set_to_bitmap(proc_set: &ProcSet, n_resources: u32) -> Vec<u8> {
+    let mut bitmap = vec![0u8; n_resources.div_ceil(8) as usize];
+    for range in proc_set.ranges() {
+        if *range.start() >= n_resources {
+            warn!("Ignoring the resources {}-{} of the bitmap export: only {} resources", range.start(), range.end(), n_resources);
+            break;
+        }
+        if *range.end() >= n_resources {
+            warn!("Ignoring the resources {}-{} of the bitmap export: only {} resources", n_resources, range.end(), n_resources);
+        }
+        for resource in *range.start()..=(*range.end()).min(n_resources - 1) {
+            bitmap[(resource / 8) as usize] |= 1 << (resource % 8);
+        }
+    }
+    bitmap
+}
+
+/// Parses a `ProcSet` from a dense bit array (see [`proc_set_to_bitmap`]).
+pub fn proc_set_from_bitmap(bitmap: &[u8]) -> ProcSet {
+    bitmap
+        .iter()
+        .enumerate()
+        .filter(|(_, byte)| **byte != 0)
+        .flat_map(|(index, byte)| (0..8u32).filter(move |bit| byte & (1 << bit) != 0).map(move |bit| index as u32 * 8 + bit))
+        .collect::<ProcSet>()
+}
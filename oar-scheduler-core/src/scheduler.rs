@@ -7,3 +7,5 @@ pub mod hierarchy;
 pub mod quotas;
 pub mod calendar;
 pub mod sorting;
+pub mod observer;
+pub mod trace;
@@ -5,15 +5,20 @@ use crate::scheduler::calendar::parsing::{
 };
 use crate::scheduler::quotas;
 use crate::scheduler::quotas::{Quotas, QuotasMap, QuotasTree};
-use crate::scheduler::slotset::SlotSet;
-use chrono::{Datelike, Local, TimeZone, Timelike};
+use crate::scheduler::slotset::{SlotSet, SplitError};
+use chrono::{Datelike, Days, Local, NaiveDate, TimeZone};
 use log::warn;
 #[cfg(feature = "pyo3")]
 use pyo3::{prelude::PyDictMethods, types::PyDict, Bound, IntoPyObject, PyErr, Python};
+use serde::de::DeserializeOwned;
 use serde_json::Value;
 use std::collections::HashMap;
 use std::rc::Rc;
 
+/// Rules id of the default quotas rules, applying where no periodical or oneshot entry of the calendar is in effect.
+/// Negative, as periodicals and oneshots have positive ids.
+pub const DEFAULT_RULES_ID: i32 = -1;
+
 /// Configuration of quotas stored in PlatformConfig.
 #[allow(dead_code)]
 #[derive(Debug)]
@@ -53,33 +58,56 @@ impl QuotasConfig {
         QuotasConfig {
             enabled,
             calendar,
-            default_rules_id: -1,
+            default_rules_id: DEFAULT_RULES_ID,
             default_rules: Rc::new(default_rules),
             default_rules_tree,
             tracked_job_types,
         }
     }
-    pub fn load_from_file(path: &str, enabled: bool, all_value: i64, quotas_window_time_limit: i64) -> Self {
-        let json = std::fs::read_to_string(path).expect("Failed to read quotas config file");
-        Self::load_from_json(json, enabled, all_value, quotas_window_time_limit)
+    /// Loads the quotas configuration from the JSON file at `path` (see [`QuotasConfig::load_from_json`]).
+    /// The parsing errors are wrapped in [`QuotasParseError::File`] to tell the path of the file.
+    pub fn load_from_file(
+        path: &str,
+        enabled: bool,
+        all_value: i64,
+        default_all_value: i64,
+        quotas_window_time_limit: i64,
+    ) -> Result<Self, QuotasParseError> {
+        let json = std::fs::read_to_string(path).map_err(|e| QuotasParseError::Io {
+            path: path.into(),
+            message: e.to_string(),
+        })?;
+        Self::load_from_json(json, enabled, all_value, default_all_value, quotas_window_time_limit).map_err(|error| QuotasParseError::File {
+            path: path.into(),
+            error: Box::new(error),
+        })
     }
-    pub fn load_from_json(json: String, enabled: bool, all_value: i64, quotas_window_time_limit: i64) -> Self {
-        let entries = serde_json::from_str::<HashMap<Box<str>, Value>>(&json).expect("Failed to parse quotas config base JSON");
+    /// Returns an error telling the failing top-level key if the `quotas`, `periodical` or `oneshot` entries,
+    /// or the rules they reference, are malformed.
+    /// Returns [`QuotasParseError::InvalidWindowTimeLimit`] if `quotas_window_time_limit` is not positive, as the periodicals would never be applied.
+    /// `all_value` and `default_all_value` are the values of `ALL` in the rules (see [`quotas::QuotasValue::from_serde_values`]).
+    pub fn load_from_json(
+        json: String,
+        enabled: bool,
+        all_value: i64,
+        default_all_value: i64,
+        quotas_window_time_limit: i64,
+    ) -> Result<Self, QuotasParseError> {
+        if quotas_window_time_limit <= 0 {
+            return Err(QuotasParseError::InvalidWindowTimeLimit(quotas_window_time_limit));
+        }
+        let entries = serde_json::from_str::<HashMap<Box<str>, Value>>(&json).map_err(|e| QuotasParseError::Json(e.to_string()))?;
 
         let job_types = entries
             .get("job_types")
             .and_then(|v| serde_json::from_value::<Box<[Box<str>]>>(v.clone()).ok())
             .unwrap_or_else(|| Box::new(["*".into()]));
-        let quotas = entries
-            .get("quotas")
-            .map(|v| serde_json::from_value::<HashMap<String, Vec<Value>>>(v.clone()).expect("Failed to parse quotas"))
-            .map(|hm| quotas::build_quotas_map(&hm, all_value));
-        let periodical = entries
-            .get("periodical")
-            .map(|v| serde_json::from_value::<PeriodicalsJson>(v.clone()).expect("Failed to parse periodical quotas"));
-        let oneshot = entries
-            .get("oneshot")
-            .map(|v| serde_json::from_value::<OneshotsJson>(v.clone()).expect("Failed to parse periodical quotas"));
+        let quotas = parse_entry::<HashMap<String, Vec<Value>>>(&entries, "quotas")?
+            .map(|hm| quotas::build_quotas_map(&hm, all_value, default_all_value))
+            .transpose()
+            .map_err(|message| QuotasParseError::Key { key: "quotas".into(), message })?;
+        let periodical = parse_entry::<PeriodicalsJson>(&entries, "periodical")?;
+        let oneshot = parse_entry::<OneshotsJson>(&entries, "oneshot")?;
 
         let calendar = if periodical.is_some() || oneshot.is_some() {
             Some(Calendar::from_config(
@@ -87,12 +115,57 @@ impl QuotasConfig {
                 periodical,
                 oneshot,
                 all_value,
+                default_all_value,
                 quotas_window_time_limit,
-            ))
+            )?)
         } else {
             None
         };
-        QuotasConfig::new(enabled, calendar, quotas.unwrap_or_default(), job_types)
+        Ok(QuotasConfig::new(enabled, calendar, quotas.unwrap_or_default(), job_types))
+    }
+}
+
+/// Parses the value of the top-level `key` of the quotas configuration, if present.
+fn parse_entry<T: DeserializeOwned>(entries: &HashMap<Box<str>, Value>, key: &str) -> Result<Option<T>, QuotasParseError> {
+    entries
+        .get(key)
+        .map(|value| T::deserialize(value))
+        .transpose()
+        .map_err(|e| QuotasParseError::Key {
+            key: key.into(),
+            message: e.to_string(),
+        })
+}
+
+/// Error returned by [`QuotasConfig::load_from_json`] and [`QuotasConfig::load_from_file`] when the quotas configuration is malformed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuotasParseError {
+    /// The quotas configuration file at `path` can't be read.
+    Io { path: Box<str>, message: String },
+    /// The configuration is not a valid JSON object. The serde message tells the line and column of the error.
+    Json(String),
+    /// The value of the top-level `key` (`quotas`, `periodical`, `oneshot`, or the name of rules) does not have the expected format.
+    Key { key: Box<str>, message: String },
+    /// The rules `name` referenced by a periodical or oneshot entry are not defined.
+    MissingRules(Box<str>),
+    /// The quotas configuration file at `path` is malformed.
+    File { path: Box<str>, error: Box<QuotasParseError> },
+    /// The quotas window time limit (`QUOTAS_WINDOW_TIME_LIMIT`) is not a positive number of seconds.
+    InvalidWindowTimeLimit(i64),
+}
+
+impl std::fmt::Display for QuotasParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QuotasParseError::Io { path, message } => write!(f, "failed to read the quotas configuration file '{}': {}", path, message),
+            QuotasParseError::Json(message) => write!(f, "invalid quotas configuration JSON: {}", message),
+            QuotasParseError::Key { key, message } => write!(f, "invalid '{}' entry in the quotas configuration: {}", key, message),
+            QuotasParseError::MissingRules(name) => write!(f, "rules '{}' not found in the quotas configuration", name),
+            QuotasParseError::File { path, error } => write!(f, "in the quotas configuration file '{}': {}", path, error),
+            QuotasParseError::InvalidWindowTimeLimit(limit) => {
+                write!(f, "invalid quotas window time limit {}: it must be a positive number of seconds", limit)
+            }
+        }
     }
 }
 
@@ -113,15 +186,19 @@ impl Calendar {
         periodicals: Option<PeriodicalsJson>,
         oneshots: Option<OneshotsJson>,
         all_values: i64,
+        default_all_value: i64,
         quotas_window_time_limit: i64,
-    ) -> Self {
-        let mut config_entries = QuotasConfigEntries::new(json_entries, all_values);
+    ) -> Result<Self, QuotasParseError> {
+        let mut config_entries = QuotasConfigEntries::new(json_entries, all_values, default_all_value);
 
         let ordered_periodicals = if let Some(periodicals) = periodicals {
             let mut entries = periodicals
                 .into_iter()
                 .map(|periodical| PeriodicalJsonEntry::from_tuple(&periodical))
-                .map(|periodical| PeriodicalEntry::from_json_entry(&periodical, &mut config_entries))
+                .enumerate()
+                .map(|(i, periodical)| PeriodicalEntry::from_json_entry(&periodical, i, &mut config_entries))
+                .collect::<Result<Vec<Vec<PeriodicalEntry>>, QuotasParseError>>()?
+                .into_iter()
                 .flatten()
                 .collect::<Vec<PeriodicalEntry>>();
 
@@ -141,6 +218,7 @@ impl Calendar {
                     return false;
                 }
                 b.week_end_time = a.week_end_time;
+                b.declaration_index = b.declaration_index.max(a.declaration_index);
                 b.period_string = format!("{} + {}", b.period_string, a.period_string).into_boxed_str();
                 true
             });
@@ -153,7 +231,7 @@ impl Calendar {
                 .into_iter()
                 .map(|oneshot| OneshotJsonEntry::from_tuple(&oneshot))
                 .map(|oneshot| OneshotEntry::from_json_entry(&oneshot, &mut config_entries))
-                .collect::<Vec<OneshotEntry>>();
+                .collect::<Result<Vec<OneshotEntry>, QuotasParseError>>()?;
 
             // Sort and merge oneshots
             entries.sort_by(|oneshot_a, oneshot_b| oneshot_a.begin_time.cmp(&oneshot_b.begin_time));
@@ -179,139 +257,208 @@ impl Calendar {
         };
         let rules_map = config_entries.to_rules_map();
 
-        Self {
+        Ok(Self {
             quotas_window_time_limit,
             rules_map,
             ordered_periodicals,
             ordered_oneshot,
-        }
+        })
+    }
+
+    /// Returns the active rules_id at a given time with the end time of these rules (inclusive),
+    /// as resolved by [`Calendar::effective_rules_for_interval`].
+    /// If no rule is active at the given time, returns None.
+    pub fn rules_at(&self, time: i64) -> Option<(i32, i64)> {
+        self.rules_at_in(&Local, time)
+    }
+    /// Same as [`Calendar::rules_at`], with the periodicals expressed in the timezone `tz` instead of the local timezone.
+    pub(crate) fn rules_at_in<Tz: TimeZone>(&self, tz: &Tz, time: i64) -> Option<(i32, i64)> {
+        self.effective_rules_for_interval_in(tz, time, time + self.quotas_window_time_limit)
+            .first()
+            .filter(|(begin, _, _)| *begin == time)
+            .map(|(_, end, rules_id)| (*rules_id, *end))
     }
 
-    /// Returns the active rules_id at a given time with the end time of these rules.
-    /// Also returns the indices of the oneshot and periodical rules used.
-    /// The indices can be used to optimize future lookups by starting the search from these indices since rules are ordered by time.
-    /// At the first call, call with oneshots_start_index = 0 and periodicals_start_index = 0.
-    /// If no rule is active at the given time, returns None and the same indices as provided.
-    pub fn rules_at(&self, time: i64, oneshots_start_index: usize, periodicals_start_index: usize) -> (Option<(i32, i64)>, usize, usize) {
-        // Check oneshots first, as they have priority over periodicals
-        for (i, oneshot) in self.ordered_oneshot[oneshots_start_index..].iter().enumerate() {
-            if time >= oneshot.begin_time && time < oneshot.end_time {
-                return (Some((oneshot.rules_id, oneshot.end_time)), i, periodicals_start_index);
+    /// Resolves which rules are in effect over `[begin, end]`, and returns them as `(begin, end, rules_id)` sub-intervals
+    /// (with inclusive ends), ordered by time. Consecutive sub-intervals with the same rules are merged,
+    /// and the times not covered by any entry are omitted: the default rules apply there.
+    ///
+    /// Where entries overlap, the precedence is:
+    /// - a oneshot always overrides the periodicals,
+    /// - among oneshots, the one beginning first wins,
+    /// - among periodicals, the one declared last in the configuration wins,
+    ///   so that a specific period (e.g., `08:00-19:00 mon-fri`) can override a catch-all one (`* * * *`) declared before it.
+    ///
+    /// Periodicals are only expanded over the weeks beginning before `begin + quotas_window_time_limit`.
+    pub fn effective_rules_for_interval(&self, begin: i64, end: i64) -> Vec<(i64, i64, i32)> {
+        self.effective_rules_for_interval_in(&Local, begin, end)
+    }
+    /// Same as [`Calendar::effective_rules_for_interval`], with the periodicals expressed in the timezone `tz` instead of the local timezone.
+    /// The periodicals are placed on each day from the actual midnight of this day, so that they stay aligned on days
+    /// that are not 24 hours long, e.g., on daylight-saving time changes.
+    pub(crate) fn effective_rules_for_interval_in<Tz: TimeZone>(&self, tz: &Tz, begin: i64, end: i64) -> Vec<(i64, i64, i32)> {
+        self.effective_rules_until_in(tz, begin, end, end.min(begin + self.quotas_window_time_limit - 1))
+    }
+    /// Same as [`Calendar::effective_rules_for_interval_in`], with the periodicals only expanded over the weeks beginning at or before `max_time`.
+    fn effective_rules_until_in<Tz: TimeZone>(&self, tz: &Tz, begin: i64, end: i64, max_time: i64) -> Vec<(i64, i64, i32)> {
+        if begin > end {
+            return vec![];
+        }
+        // Occurrences of the entries clipped to [begin, end], as (begin, end, rules_id, precedence).
+        // Oneshots take the highest precedences, so they rank above every periodical.
+        let mut occurrences: Vec<(i64, i64, i32, usize)> = vec![];
+        for (i, oneshot) in self.ordered_oneshot.iter().enumerate() {
+            if oneshot.begin_time <= end && oneshot.end_time >= begin {
+                occurrences.push((oneshot.begin_time.max(begin), oneshot.end_time.min(end), oneshot.rules_id, usize::MAX - i));
             }
         }
-        // Find the time in the week (0 = Monday 00:00:00, 604800 = Sunday 23:59:59)
-        let week_datetime = match Local.timestamp_opt(time, 0) {
-            chrono::LocalResult::Single(dt) => dt,
-            _ => panic!("Failed to convert time to DateTime"),
-        };
-
-        let week_time = (week_datetime.weekday().num_days_from_monday() as i64) * 24 * 3600
-            + (week_datetime.hour() as i64) * 3600
-            + (week_datetime.minute() as i64) * 60
-            + (week_datetime.second() as i64);
-
-        // Iterating over ordered_periodicals, looping back to the start if needed
-        for (i, periodical) in self.ordered_periodicals[periodicals_start_index..]
-            .iter()
-            .chain(self.ordered_periodicals[..periodicals_start_index].iter())
-            .enumerate()
-        {
-            if week_time >= periodical.week_begin_time && week_time < periodical.week_end_time {
-                let periodical_end_time = time + (periodical.week_end_time - week_time);
-                return (Some((periodical.rules_id, periodical_end_time)), oneshots_start_index, i);
+        if !self.ordered_periodicals.is_empty() {
+            let mut week_monday = week_monday_of(tz, begin);
+            while week_time_to_timestamp(tz, week_monday, 0) <= max_time {
+                for periodical in &self.ordered_periodicals {
+                    let periodical_begin = week_time_to_timestamp(tz, week_monday, periodical.week_begin_time);
+                    let periodical_end = week_time_to_timestamp(tz, week_monday, periodical.week_end_time);
+                    if periodical_begin <= end && periodical_end >= begin {
+                        occurrences.push((
+                            periodical_begin.max(begin),
+                            periodical_end.min(end),
+                            periodical.rules_id,
+                            periodical.declaration_index,
+                        ));
+                    }
+                }
+                week_monday = week_monday + Days::new(7);
             }
         }
-        (None, oneshots_start_index, periodicals_start_index)
-    }
 
-    pub fn split_slotset_for_temporal_quotas(&self, slot_set: &mut SlotSet) {
-        if self.ordered_oneshot.is_empty() && self.ordered_periodicals.is_empty() {
-            return;
-        }
-        self.split_slotset_for_oneshots(slot_set);
-        self.split_slotset_for_periodicals(slot_set);
-    }
-
-    /// Splits the slotset according to the oneshot entries in the calendar.
-    /// Sets the correct [`Quotas`] structs to the slots.
-    fn split_slotset_for_oneshots(&self, slot_set: &mut SlotSet) {
-        let slotset_begin = slot_set.begin();
-        let mut starting_id = None;
-        for oneshot in &self.ordered_oneshot {
-            let (begin_slot_id, end_slot_id) = if let Some(slots) = slot_set.split_slots_for_range(oneshot.begin_time, oneshot.end_time, starting_id)
-            {
-                slots
-            } else {
-                // [oneshot.begin_time, oneshot.end_time] is completely before or after the slotset (disjoint ranges)
-                if oneshot.begin_time < slotset_begin {
-                    continue; // Before the slotset
+        // Each elementary interval between two occurrence bounds takes the rules of the highest precedence occurrence covering it.
+        let mut bounds = occurrences.iter().flat_map(|(b, e, _, _)| [*b, *e + 1]).collect::<Vec<i64>>();
+        bounds.sort_unstable();
+        bounds.dedup();
+        let mut effective_rules: Vec<(i64, i64, i32)> = vec![];
+        for bounds in bounds.windows(2) {
+            let (interval_begin, interval_end) = (bounds[0], bounds[1] - 1);
+            let winner = occurrences
+                .iter()
+                .filter(|(b, e, _, _)| *b <= interval_begin && *e >= interval_end)
+                .max_by_key(|(_, _, _, precedence)| *precedence);
+            if let Some(&(_, _, rules_id, _)) = winner {
+                match effective_rules.last_mut() {
+                    Some(last) if last.1 + 1 == interval_begin && last.2 == rules_id => last.1 = interval_end,
+                    _ => effective_rules.push((interval_begin, interval_end, rules_id)),
                 }
-                // After the slotset, we are done
-                break;
-            };
-            starting_id = Some(begin_slot_id);
-            let rules = self.rules_map.get(&oneshot.rules_id).unwrap();
-            let quotas = Quotas::new(
-                Rc::clone(slot_set.get_platform_config()),
-                oneshot.rules_id,
-                Rc::clone(&rules.0),
-                Rc::clone(&rules.1),
-            );
-            for slot_id in slot_set.iter().between(begin_slot_id, end_slot_id).map(|s| s.id).collect::<Vec<i32>>() {
-                slot_set.get_slot_mut(slot_id).unwrap().quotas = quotas.clone();
             }
         }
+        effective_rules
     }
 
-    /// Splits the slotset according to the periodical entries in the calendar.
-    /// Sets the correct [`Quotas`] structs to the slots.
-    fn split_slotset_for_periodicals(&self, slot_set: &mut SlotSet) {
-        let max_time = slot_set.begin() + self.quotas_window_time_limit;
-
-        let slotset_begin = slot_set.begin();
-        let slotset_begin_datetime = match Local.timestamp_opt(slotset_begin, 0) {
-            chrono::LocalResult::Single(dt) => dt,
-            _ => panic!("Failed to convert time to DateTime"),
-        };
-        let mut week_begin = slotset_begin
-            - (slotset_begin_datetime.weekday().num_days_from_monday() as i64) * 24 * 3600
-            - (slotset_begin_datetime.hour() as i64) * 3600
-            - (slotset_begin_datetime.minute() as i64) * 60
-            - (slotset_begin_datetime.second() as i64);
+    /// Returns the next time at or after `after` at which the active rules change, with the id of the rules in effect from this time,
+    /// [`DEFAULT_RULES_ID`] meaning that no entry is in effect anymore. The precedence between entries is the one of
+    /// [`Calendar::effective_rules_for_interval`], and periodicals are only expanded over the weeks beginning before
+    /// `after + quotas_window_time_limit`.
+    /// Returns `None` if the rules never change after `after`.
+    pub fn next_rule_change(&self, after: i64) -> Option<(i64, i32)> {
+        self.next_rule_change_in(&Local, after, after + self.quotas_window_time_limit - 1)
+    }
+    /// Same as [`Calendar::next_rule_change`], with the periodicals expressed in the timezone `tz` instead of the local timezone,
+    /// and only expanded over the weeks beginning at or before `max_time`.
+    pub(crate) fn next_rule_change_in<Tz: TimeZone>(&self, tz: &Tz, after: i64, max_time: i64) -> Option<(i64, i32)> {
+        self.rule_changes_in(tz, after - 1, i64::MAX - 1, max_time).get(1).copied()
+    }
+    /// Returns the rules in effect at `begin`, followed by each change of the rules over `]begin, end]`, as `(time, rules_id)`,
+    /// [`DEFAULT_RULES_ID`] meaning that no entry is in effect. The periodicals are only expanded over the weeks beginning
+    /// at or before `max_time`. The changes are computed at once from the effective rules over the whole range,
+    /// so that walking them does not resolve the rules again for each change.
+    fn rule_changes_in<Tz: TimeZone>(&self, tz: &Tz, begin: i64, end: i64, max_time: i64) -> Vec<(i64, i32)> {
+        // The times not covered by the effective rules are the times where the default rules apply
+        let mut changes = vec![(begin, DEFAULT_RULES_ID)];
+        let mut time = begin - 1;
+        for (rules_begin, rules_end, rules_id) in self.effective_rules_until_in(tz, begin, end, max_time) {
+            if rules_begin > time + 1 && changes.last().unwrap().1 != DEFAULT_RULES_ID {
+                changes.push((time + 1, DEFAULT_RULES_ID));
+            }
+            if rules_begin == begin {
+                changes[0].1 = rules_id;
+            } else if rules_id != changes.last().unwrap().1 {
+                changes.push((rules_begin, rules_id));
+            }
+            time = rules_end;
+        }
+        if changes.last().unwrap().1 != DEFAULT_RULES_ID && time < end {
+            changes.push((time + 1, DEFAULT_RULES_ID));
+        }
+        changes
+    }
 
+    /// Splits the slotset at each change of the rules over the slotset range, found at once as by [`Calendar::next_rule_change`],
+    /// so that the slots are only split where the rules actually change. Sets the correct [`Quotas`] structs to the slots.
+    pub fn split_slotset_for_temporal_quotas(&self, slot_set: &mut SlotSet) {
+        self.split_slotset_for_temporal_quotas_in(&Local, slot_set);
+    }
+    /// Same as [`Calendar::split_slotset_for_temporal_quotas`], with the periodicals expressed in the timezone `tz` instead of the local timezone.
+    pub(crate) fn split_slotset_for_temporal_quotas_in<Tz: TimeZone>(&self, tz: &Tz, slot_set: &mut SlotSet) {
+        if self.ordered_oneshot.is_empty() && self.ordered_periodicals.is_empty() {
+            return;
+        }
+        let (slot_set_begin, slot_set_end) = (slot_set.begin(), slot_set.end());
+        let max_time = slot_set_end.min(slot_set_begin + self.quotas_window_time_limit - 1);
         let mut start_slot_id = None;
-        while week_begin < max_time {
-            for periodical in &self.ordered_periodicals {
-                let periodical_begin = periodical.week_begin_time + week_begin;
-                let periodical_end = periodical.week_end_time + week_begin;
-
-                let (begin_slot_id, end_slot_id) =
-                    if let Some(slots) = slot_set.split_slots_for_range(periodical_begin, periodical_end, start_slot_id) {
-                        slots
-                    } else {
-                        // [periodical_begin, periodical_end] is completely before or after the slotset (disjoint ranges)
-                        if periodical_end < slotset_begin {
-                            continue; // Before the slotset
-                        }
-                        // After the slotset, we are done
-                        break;
-                    };
+        let changes = self.rule_changes_in(tz, slot_set_begin, slot_set_end, max_time);
+        for (i, &(begin, rules_id)) in changes.iter().enumerate() {
+            let next_begin = changes.get(i + 1).map_or(slot_set_end + 1, |(time, _)| *time);
+            // With `quotas_period_counters`, each occurrence of the rules is a separate period, even if it directly follows
+            // another occurrence of the same rules, e.g., each day of `* mon-fri * *`.
+            let mut period_begin = begin;
+            while rules_id != DEFAULT_RULES_ID && period_begin < next_begin {
+                let period_end = if slot_set.get_platform_config().config.quotas_period_counters {
+                    self.occurrence_end_in(tz, period_begin, next_begin - 1)
+                } else {
+                    next_begin - 1
+                };
+                let (begin_slot_id, end_slot_id) = match slot_set.try_split_slots_for_range(period_begin, period_end, start_slot_id) {
+                    Ok(slots) => slots.expect("Rules changes should be within the slotset"),
+                    Err(SplitError::MaxSlotsReached(max_slots)) => {
+                        warn!("Maximum number of slots ({}) reached: periodical quotas are not applied after {}", max_slots, period_begin);
+                        return;
+                    }
+                    Err(e) => panic!("SlotSet::split_slots_for_range: {}", e),
+                };
                 start_slot_id = Some(begin_slot_id);
 
-                let rules = self.rules_map.get(&periodical.rules_id).unwrap();
+                let rules = self.rules_map.get(&rules_id).unwrap();
                 let quotas = Quotas::new(
                     Rc::clone(slot_set.get_platform_config()),
-                    periodical.rules_id,
+                    rules_id,
                     Rc::clone(&rules.0),
                     Rc::clone(&rules.1),
-                );
+                )
+                .with_period(period_begin);
                 for slot_id in slot_set.iter().between(begin_slot_id, end_slot_id).map(|s| s.id).collect::<Vec<i32>>() {
-                    if slot_set.get_slot(slot_id).unwrap().quotas.rules_id() == slot_set.get_platform_config().quotas_config.default_rules_id {
-                        slot_set.get_slot_mut(slot_id).unwrap().quotas = quotas.clone();
-                    }
+                    slot_set.get_slot_mut(slot_id).unwrap().quotas = quotas.clone();
                 }
+                period_begin = period_end + 1;
+            }
+        }
+    }
+
+    /// Returns the end (inclusive) of the occurrence of the rules in effect at `begin`, bounded by `end`.
+    /// The periodical entries are defined per day, so their occurrences end at the latest at midnight,
+    /// unless a oneshot, which overrides the periodicals, is in effect over the midnight.
+    fn occurrence_end_in<Tz: TimeZone>(&self, tz: &Tz, begin: i64, end: i64) -> i64 {
+        let mut day = day_of(tz, begin);
+        loop {
+            day = day + Days::new(1);
+            let midnight = week_time_to_timestamp(tz, day, 0);
+            if midnight > end {
+                return end;
+            }
+            let oneshot_over_midnight = self
+                .ordered_oneshot
+                .iter()
+                .any(|oneshot| oneshot.begin_time < midnight && oneshot.end_time >= midnight);
+            if !oneshot_over_midnight {
+                return midnight - 1;
             }
-            week_begin += 7 * 24 * 3600;
         }
     }
 
@@ -331,10 +478,53 @@ impl Calendar {
     pub fn quotas_window_time_limit(&self) -> i64 {
         self.quotas_window_time_limit
     }
+    /// Returns whether the periodicals are applied over the whole range `[begin, end]`,
+    /// i.e., whether `end` is before `begin + quotas_window_time_limit`. Always true if there is no periodical.
+    pub fn window_covers(&self, begin: i64, end: i64) -> bool {
+        self.ordered_periodicals.is_empty() || end < begin + self.quotas_window_time_limit
+    }
+}
+
+/// Returns the date of the Monday of the week containing `time`, in the timezone `tz`.
+fn week_monday_of<Tz: TimeZone>(tz: &Tz, time: i64) -> NaiveDate {
+    let day = day_of(tz, time);
+    day - Days::new(day.weekday().num_days_from_monday() as u64)
+}
+
+/// Returns the day of `time` in the timezone `tz`.
+fn day_of<Tz: TimeZone>(tz: &Tz, time: i64) -> NaiveDate {
+    match tz.timestamp_opt(time, 0) {
+        chrono::LocalResult::Single(dt) => dt.date_naive(),
+        _ => panic!("Failed to convert time to DateTime"),
+    }
+}
+
+/// Converts a time in the week starting on `week_monday` (0 = Monday 00:00:00) into a timestamp, in the timezone `tz`.
+/// The time of the day is taken from the actual midnight of its day, so a day is not assumed to last 24 hours.
+/// A time skipped by a daylight-saving time change is shifted by the length of the change,
+/// and a repeated time resolves to its first occurrence.
+fn week_time_to_timestamp<Tz: TimeZone>(tz: &Tz, week_monday: NaiveDate, week_time: i64) -> i64 {
+    let day = week_monday + Days::new((week_time / (24 * 3600)) as u64);
+    let day_time = week_time % (24 * 3600);
+    let (hour, minute, second) = ((day_time / 3600) as u32, (day_time % 3600 / 60) as u32, (day_time % 60) as u32);
+    match tz.with_ymd_and_hms(day.year(), day.month(), day.day(), hour, minute, second) {
+        chrono::LocalResult::Single(dt) => dt.timestamp(),
+        chrono::LocalResult::Ambiguous(earliest, _latest) => earliest.timestamp(),
+        chrono::LocalResult::None => {
+            // Skipped time: use the offset in effect before the change (the time one hour earlier is assumed to exist).
+            let before = day.and_hms_opt(hour, minute, second).unwrap() - chrono::Duration::hours(1);
+            tz.from_local_datetime(&before)
+                .earliest()
+                .expect("Failed to convert time to DateTime")
+                .timestamp()
+                + 3600
+        }
+    }
 }
 
 /// Module handling the parsing of temporal quotas from JSON configuration.
 pub mod parsing {
+    use crate::scheduler::calendar::QuotasParseError;
     use crate::scheduler::quotas;
     use crate::scheduler::quotas::{QuotasMap, QuotasTree};
     use chrono::{DateTime, Local, NaiveDateTime};
@@ -355,6 +545,7 @@ pub mod parsing {
     /// and allows to parse entries on demand and to reuse already parsed entries.
     pub struct QuotasConfigEntries {
         all_value: i64,
+        default_all_value: i64,
         id_counter: i32,
         json_entries: HashMap<Box<str>, Value>, // name -> Serde value representing the rules (or other data)
         parsed_entries: HashMap<Box<str>, (i32, QuotasMap)>, // name -> (id, QuotasKey -> QuotasValue)
@@ -378,6 +569,9 @@ pub mod parsing {
         pub(crate) week_begin_time: i64, // Begin time in seconds from week start (0-604800)
         pub(crate) week_end_time: i64,
         pub(crate) rules_id: i32,
+        /// Index of the periodical in the configuration, a later declared periodical overriding an earlier one.
+        /// Merged entries keep the highest index.
+        pub(crate) declaration_index: usize,
         pub(crate) period_string: Box<str>,
         pub(crate) description: Box<str>,
     }
@@ -393,27 +587,30 @@ pub mod parsing {
     }
 
     impl QuotasConfigEntries {
-        pub fn new(json_entries: HashMap<Box<str>, Value>, all_value: i64) -> Self {
+        pub fn new(json_entries: HashMap<Box<str>, Value>, all_value: i64, default_all_value: i64) -> Self {
             QuotasConfigEntries {
                 all_value,
+                default_all_value,
                 id_counter: 0,
                 json_entries,
                 parsed_entries: HashMap::new(),
             }
         }
         /// Get the ID for a given rule name, parsing and storing it if not already done.
-        fn get_rules_id(&mut self, rule_name: &str) -> i32 {
+        fn get_rules_id(&mut self, rule_name: &str) -> Result<i32, QuotasParseError> {
             if let Some((id, _quotas_map)) = self.parsed_entries.get(rule_name) {
-                return *id;
-            }
-            if let Some(value) = self.json_entries.get(rule_name) {
-                self.id_counter += 1;
-                let parsed_value = serde_json::from_value::<HashMap<String, Vec<Value>>>(value.clone()).expect("Failed to parse quotas");
-                let quotas_map = quotas::build_quotas_map(&parsed_value, self.all_value);
-                self.parsed_entries.insert(rule_name.into(), (self.id_counter, quotas_map));
-                return self.id_counter;
+                return Ok(*id);
             }
-            panic!("Rule name '{}' not found in quotas configuration entries", rule_name);
+            let value = self.json_entries.get(rule_name).ok_or_else(|| QuotasParseError::MissingRules(rule_name.into()))?;
+            let invalid_rules = |message: String| QuotasParseError::Key {
+                key: rule_name.into(),
+                message,
+            };
+            let parsed_value = serde_json::from_value::<HashMap<String, Vec<Value>>>(value.clone()).map_err(|e| invalid_rules(e.to_string()))?;
+            let quotas_map = quotas::build_quotas_map(&parsed_value, self.all_value, self.default_all_value).map_err(invalid_rules)?;
+            self.id_counter += 1;
+            self.parsed_entries.insert(rule_name.into(), (self.id_counter, quotas_map));
+            Ok(self.id_counter)
         }
         /// Consumes self and returns a map of rule IDs to their corresponding QuotasMap (the rules).
         pub fn to_rules_map(self) -> HashMap<i32, (Rc<QuotasMap>, Rc<QuotasTree>)> {
@@ -445,10 +642,18 @@ pub mod parsing {
     }
 
     impl PeriodicalEntry {
-        pub(crate) fn from_json_entry(periodical: &PeriodicalJsonEntry, config_entries: &mut QuotasConfigEntries) -> Vec<Self> {
+        pub(crate) fn from_json_entry(
+            periodical: &PeriodicalJsonEntry,
+            declaration_index: usize,
+            config_entries: &mut QuotasConfigEntries,
+        ) -> Result<Vec<Self>, QuotasParseError> {
+            let invalid_period = |message: &str| QuotasParseError::Key {
+                key: "periodical".into(),
+                message: format!("{} in the period '{}'", message, periodical.period),
+            };
             let parts: Vec<&str> = periodical.period.split_whitespace().collect();
             if parts.len() != 4 {
-                panic!("Unable to parse periodical quotas period format. Expected 4 parts: time_range days month day");
+                return Err(invalid_period("expected 4 parts: time_range days month day"));
             }
 
             let time_range = parts[0];
@@ -457,7 +662,7 @@ pub mod parsing {
             let _day = parts[3];
 
             if _month != "*" || _day != "*" {
-                panic!("Can’t parse periodical quotas. Month and day specifications are not yet implemented, please leave them as '*'");
+                return Err(invalid_period("month and day specifications are not yet implemented, please leave them as '*'"));
             }
 
             // Parse time range
@@ -466,25 +671,25 @@ pub mod parsing {
             } else {
                 let time_parts: Vec<&str> = time_range.split('-').collect();
                 if time_parts.len() != 2 {
-                    panic!("Invalid time range format in periodical quotas configuration. Expected 'HH:MM-HH:MM'");
+                    return Err(invalid_period("invalid time range format, expected 'HH:MM-HH:MM'"));
                 }
-                let begin = parse_time_to_seconds(time_parts[0]);
-                let end = parse_time_to_seconds(time_parts[1]);
+                let begin = parse_time_to_seconds(time_parts[0]).map_err(|e| invalid_period(&e))?;
+                let end = parse_time_to_seconds(time_parts[1]).map_err(|e| invalid_period(&e))?;
                 (begin, if end == 0 { 24 * 3600 } else { end })
             };
 
             // Parse days
-            let day_numbers = parse_day_range(days);
+            let day_numbers = parse_day_range(days).map_err(|e| invalid_period(&e))?;
             if day_numbers.is_empty() {
-                panic!(
-                    "Invalid days configuration in periodical quotas configuration. No valid days found.\
-                Use '*' for all days or specify days in an array like 'mon,tue,wed,thu,fri,sat,sun', or as ranges like 'mon-fri'."
-                );
+                return Err(invalid_period(
+                    "no valid days found. Use '*' for all days or specify days in an array like 'mon,tue,wed,thu,fri,sat,sun', \
+                    or as ranges like 'mon-fri'",
+                ));
             }
 
             // Create entries for each day
             let mut entries = Vec::new();
-            let rules_id = config_entries.get_rules_id(&periodical.rule);
+            let rules_id = config_entries.get_rules_id(&periodical.rule)?;
             for day in day_numbers {
                 let day_begin = day as i64 * 24 * 3600;
                 let mut end_time = end_time;
@@ -494,6 +699,7 @@ pub mod parsing {
                         week_begin_time: day_begin,
                         week_end_time: end_time - 1,
                         rules_id,
+                        declaration_index,
                         period_string: periodical.period.clone(),
                         description: periodical.description.clone(),
                     });
@@ -504,6 +710,7 @@ pub mod parsing {
                     week_begin_time: day_begin + begin_time,
                     week_end_time: day_begin + end_time - 1,
                     rules_id,
+                    declaration_index,
                     period_string: periodical.period.clone(),
                     description: periodical.description.clone(),
                 });
@@ -511,87 +718,88 @@ pub mod parsing {
 
             // Sort entries by begin_time
             entries.sort_by(|a, b| a.week_begin_time.cmp(&b.week_begin_time));
-            entries
+            Ok(entries)
         }
     }
 
     impl OneshotEntry {
-        pub(crate) fn from_json_entry(entry: &OneshotJsonEntry, config_entries: &mut QuotasConfigEntries) -> Self {
-            let begin_time = parse_datetime(format!("{}:00", &entry.begin).as_str()).unwrap_or_else(|e| {
-                panic!(
-                    "Invalid begin time format '{}' in oneshot entry. Expected format: YYYY-MM-DD hh:mm. Error: {}",
+        pub(crate) fn from_json_entry(entry: &OneshotJsonEntry, config_entries: &mut QuotasConfigEntries) -> Result<Self, QuotasParseError> {
+            let invalid_oneshot = |message: String| QuotasParseError::Key {
+                key: "oneshot".into(),
+                message,
+            };
+            let begin_time = parse_datetime(format!("{}:00", &entry.begin).as_str()).map_err(|e| {
+                invalid_oneshot(format!(
+                    "invalid begin time format '{}'. Expected format: YYYY-MM-DD hh:mm. Error: {}",
                     entry.begin, e
-                )
-            });
-            let end_time = parse_datetime(format!("{}:00", &entry.end).as_str()).unwrap_or_else(|e| {
-                panic!(
-                    "Invalid end time format '{}' in oneshot entry. Expected format: YYYY-MM-DD hh:mm. Error: {}",
+                ))
+            })?;
+            let end_time = parse_datetime(format!("{}:00", &entry.end).as_str()).map_err(|e| {
+                invalid_oneshot(format!(
+                    "invalid end time format '{}'. Expected format: YYYY-MM-DD hh:mm. Error: {}",
                     entry.end, e
-                )
-            });
+                ))
+            })?;
             if end_time <= begin_time {
-                panic!(
-                    "Invalid time range in oneshot entry: end time '{}' must be after begin time '{}'",
+                return Err(invalid_oneshot(format!(
+                    "invalid time range: end time '{}' must be after begin time '{}'",
                     entry.end, entry.begin
-                );
+                )));
             }
 
-            Self {
+            Ok(Self {
                 begin_time: begin_time.timestamp(),
                 end_time: end_time.timestamp() - 1,
-                rules_id: config_entries.get_rules_id(&entry.rule),
+                rules_id: config_entries.get_rules_id(&entry.rule)?,
                 begin_string: entry.begin.clone(),
                 end_string: entry.end.clone(),
                 description: entry.description.clone(),
-            }
+            })
         }
     }
 
     // Helper function to parse time string in "HH:MM" format to seconds since midnight
-    fn parse_time_to_seconds(time_str: &str) -> i64 {
+    fn parse_time_to_seconds(time_str: &str) -> Result<i64, String> {
         if time_str == "*" {
-            return 0;
+            return Ok(0);
         }
 
-        let time_parts: Vec<&str> = time_str.split(':').collect();
-        if time_parts.len() != 2 {
-            return 0;
+        let invalid_time = || format!("invalid time '{}', expected 'HH:MM'", time_str);
+        let (hours, minutes) = time_str.split_once(':').ok_or_else(invalid_time)?;
+        let hours = hours.parse::<i64>().map_err(|_| invalid_time())?;
+        let minutes = minutes.parse::<i64>().map_err(|_| invalid_time())?;
+        if !(0..=24).contains(&hours) || !(0..60).contains(&minutes) || (hours == 24 && minutes > 0) {
+            return Err(invalid_time());
         }
-
-        let hours = time_parts[0].parse::<i64>().unwrap_or(0);
-        let minutes = time_parts[1].parse::<i64>().unwrap_or(0);
-        hours * 3600 + minutes * 60
+        Ok(hours * 3600 + minutes * 60)
     }
 
     // Parse day range like "mon-fri" to a vector of day numbers
-    fn parse_day_range(day_range: &str) -> Vec<i32> {
+    fn parse_day_range(day_range: &str) -> Result<Vec<i32>, String> {
         if day_range == "*" {
-            return (0..7).collect();
+            return Ok((0..7).collect());
         }
 
+        let day_num = |day: &str| DAYS_TO_NUM.get(day).copied().ok_or_else(|| format!("invalid day '{}'", day));
         let mut result = Vec::new();
         for part in day_range.split(',') {
-            if part.contains('-') {
-                let range_parts: Vec<&str> = part.split('-').collect();
-                if range_parts.len() == 2 {
-                    if let (Some(&start), Some(&end)) = (DAYS_TO_NUM.get(range_parts[0]), DAYS_TO_NUM.get(range_parts[1])) {
-                        if start <= end {
-                            result.extend(start..=end);
-                        } else {
-                            // Handle wrap-around (e.g., sun-mon)
-                            result.extend(start..7);
-                            result.extend(0..=end);
-                        }
-                    }
+            if let Some((start, end)) = part.split_once('-') {
+                let (start, end) = (day_num(start)?, day_num(end)?);
+                if start <= end {
+                    result.extend(start..=end);
+                } else {
+                    // Handle wrap-around (e.g., sun-mon)
+                    result.extend(start..7);
+                    result.extend(0..=end);
                 }
-            } else if let Some(&day_num) = DAYS_TO_NUM.get(part) {
-                result.push(day_num);
+            } else {
+                result.push(day_num(part)?);
             }
         }
 
         result.sort_unstable();
         result.dedup();
-        result
+        Ok(result)
     }
 
     /// Parse a datetime string in the format "YYYY-MM-DD hh:mm" to a DateTime<Utc>
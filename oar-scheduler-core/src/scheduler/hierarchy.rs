@@ -11,6 +11,9 @@ use pyo3::types::{PyDict, PyList, PyTuple};
 use pyo3::{Bound, IntoPyObject, PyAny, PyErr, Python};
 use std::collections::HashMap;
 
+/// Maximum number of placements tried by [`Hierarchy::request_multiple`] before giving up.
+pub const REQUEST_MULTIPLE_MAX_ATTEMPTS: u32 = 1_000;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct HierarchyRequests(pub Box<[HierarchyRequest]>);
 impl HierarchyRequests {
@@ -29,7 +32,14 @@ impl HierarchyRequests {
                     req.filter,
                     req.level_nbs
                         .iter()
-                        .map(|(name, count)| format!("{}:{}", name, count))
+                        .zip(req.level_max_nbs.iter())
+                        .map(|((name, count), max)| {
+                            if count == max {
+                                format!("{}:{}", name, count)
+                            } else {
+                                format!("{}:{}-{}", name, count, max)
+                            }
+                        })
                         .collect::<Vec<_>>()
                         .join(",")
                 )
@@ -52,13 +62,29 @@ impl<'a> IntoPyObject<'a> for &HierarchyRequests {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct HierarchyRequest {
     pub filter: ProcSet,
-    pub level_nbs: Box<[(Box<str>, u32)]>, // Level name, number of resources requested at that level
+    pub level_nbs: Box<[(Box<str>, u32)]>, // Level name, (minimum) number of resources requested at that level
+    pub level_max_nbs: Box<[u32]>, // Maximum number of resources requested at each level, equal to the level_nbs count for exact requests
 }
 impl HierarchyRequest {
     pub fn new(filter: ProcSet, level_nbs: Vec<(Box<str>, u32)>) -> Self {
+        let level_max_nbs = level_nbs.iter().map(|(_, count)| *count).collect();
         HierarchyRequest {
             filter,
             level_nbs: level_nbs.into_boxed_slice(),
+            level_max_nbs,
+        }
+    }
+    /// Creates a request for a range of resources at each level: `(label, min, max)`.
+    /// [`Hierarchy::request`] gives as many resources as available up to `max`, and fails if less than `min` are available.
+    pub fn new_range(filter: ProcSet, level_ranges: Vec<(Box<str>, u32, u32)>) -> Self {
+        if let Some((name, min, max)) = level_ranges.iter().find(|(_, min, max)| min > max) {
+            panic!("Invalid range {}-{} requested for the hierarchy level {}.", min, max, name);
+        }
+        let level_max_nbs = level_ranges.iter().map(|(_, _, max)| *max).collect();
+        HierarchyRequest {
+            filter,
+            level_nbs: level_ranges.into_iter().map(|(name, min, _)| (name, min)).collect(),
+            level_max_nbs,
         }
     }
 }
@@ -86,33 +112,79 @@ impl<'a> IntoPyObject<'a> for &HierarchyRequest {
                     .collect::<Vec<_>>(),
             )
             .unwrap();
+        request_dict.set_item("level_max_nbs", self.level_max_nbs.to_vec()).unwrap();
         Ok(request_dict)
     }
 }
 
+/// Error returned by [`Hierarchy::request_multiple`] when the placements can't be found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RequestMultipleError {
+    /// The placements don't fit the available resources, or no combination was found by the search.
+    NotFound,
+    /// The search gave up after this number of placement attempts: the placements may exist.
+    AttemptsExhausted(u32),
+}
+
+impl std::fmt::Display for RequestMultipleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestMultipleError::NotFound => write!(f, "no placement found"),
+            RequestMultipleError::AttemptsExhausted(attempts) => write!(f, "gave up after {} placement attempts", attempts),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Hierarchy {
     partitions: HashMap<Box<str>, Box<[ProcSet]>>, // Level name, partitions of that level
     unit_partitions: Vec<Box<str>>, // Name of a virtuals unitary partition (correspond to a single u32 in ProcSet), e.g. "core" or "resource_id"
+    partition_values: HashMap<Box<str>, Box<[Box<str>]>>, // Level name, label value of each partition of that level, e.g. host names
 }
 
 impl Hierarchy {
     pub fn new() -> Self {
         Self::new_defined(HashMap::new(), vec![])
     }
+    /// The partitions of each level are sorted by their lowest proc (see [`Self::add_partition`]).
     pub fn new_defined(partitions: HashMap<Box<str>, Box<[ProcSet]>>, unit_partition: Vec<Box<str>>) -> Self {
         Hierarchy {
-            partitions,
+            partitions: partitions.into_iter().map(|(name, partitions)| (name, Self::sort_partitions(partitions))).collect(),
             unit_partitions: unit_partition,
+            partition_values: HashMap::new(),
         }
     }
+    /// Adds a level to the hierarchy. Its partitions are sorted by their lowest proc, empty partitions last,
+    /// so that requests deterministically prefer the lowest-index partitions whatever the order the partitions were built in.
     pub fn add_partition(mut self, name: Box<str>, partitions: Box<[ProcSet]>) -> Self {
+        if self.has_partition(&name) {
+            panic!("A partition with the name {} already exists.", name);
+        }
+        self.partitions.insert(name, Self::sort_partitions(partitions));
+        self
+    }
+    /// Adds a level to the hierarchy, keeping its partitions in the given order, e.g., the order of their label values:
+    /// requests then prefer the first partitions. The order must be deterministic for the requests to be.
+    pub fn add_ordered_partition(mut self, name: Box<str>, partitions: Box<[ProcSet]>) -> Self {
         if self.has_partition(&name) {
             panic!("A partition with the name {} already exists.", name);
         }
         self.partitions.insert(name, partitions);
         self
     }
+    /// Same as [`Self::add_ordered_partition`], also naming each partition by its label value, e.g., its host name,
+    /// for [`Self::format_proc_set_as_labels`]. `values` must have one value per partition, in the same order.
+    pub fn add_valued_partition(mut self, name: Box<str>, partitions: Box<[ProcSet]>, values: Box<[Box<str>]>) -> Self {
+        if partitions.len() != values.len() {
+            panic!("The level {} has {} partitions but {} label values.", name, partitions.len(), values.len());
+        }
+        self.partition_values.insert(name.clone(), values);
+        self.add_ordered_partition(name, partitions)
+    }
+    fn sort_partitions(mut partitions: Box<[ProcSet]>) -> Box<[ProcSet]> {
+        partitions.sort_by_key(|partition| (partition.is_empty(), partition.first()));
+        partitions
+    }
     pub fn add_unit_partition(mut self, name: Box<str>) -> Self {
         if self.has_partition(&name) {
             panic!("A partition with the name {} already exists.", name);
@@ -126,20 +198,213 @@ impl Hierarchy {
     pub fn unit_partitions(&self) -> &Vec<Box<str>> {
         &self.unit_partitions
     }
+    /// Formats a `ProcSet` as the list of partitions of the level `label` it intersects.
+    /// Partitions with a label value (see [`Self::add_valued_partition`]) are identified by it, e.g. `node2,node10`.
+    /// Otherwise, they are identified by their index in the level, consecutive partitions fully included in the proc set being grouped,
+    /// e.g. `nodes[1-4],nodes7`. Partially included partitions are suffixed by their included and total core counts, e.g. `nodes5(12/32)`.
+    /// If `label` is a unit partition, the proc set ranges are formatted directly, e.g. `cores[1-4],cores7`.
+    pub fn format_proc_set_as_labels(&self, proc_set: &ProcSet, label: &str) -> String {
+        let format_group = |begin: u32, end: u32| {
+            if begin == end {
+                format!("{}{}", label, begin)
+            } else {
+                format!("{}[{}-{}]", label, begin, end)
+            }
+        };
+
+        if self.unit_partitions.iter().any(|name| name.as_ref() == label) {
+            return proc_set
+                .ranges()
+                .map(|range| format_group(*range.start(), *range.end()))
+                .collect::<Vec<String>>()
+                .join(",");
+        }
+        let partitions = match self.partitions.get(label) {
+            Some(partitions) => partitions,
+            None => {
+                warn!("No such hierarchy level matching name {}", label);
+                return format!("{}", proc_set);
+            }
+        };
+
+        if let Some(values) = self.partition_values.get(label) {
+            return partitions
+                .iter()
+                .zip(values.iter())
+                .filter_map(|(partition, value)| {
+                    let intersection = partition & proc_set;
+                    if intersection.is_empty() {
+                        None
+                    } else if intersection.len() == partition.len() {
+                        Some(value.to_string())
+                    } else {
+                        Some(format!("{}({}/{})", value, intersection.core_count(), partition.core_count()))
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join(",");
+        }
+
+        let mut groups: Vec<String> = Vec::new();
+        let mut full_group: Option<(u32, u32)> = None;
+        for (index, partition) in partitions.iter().enumerate() {
+            let index = index as u32;
+            let intersection = partition & proc_set;
+            if !intersection.is_empty() && intersection.len() == partition.len() {
+                // Fully included partition: extends or starts a group
+                full_group = match full_group {
+                    Some((begin, end)) if end + 1 == index => Some((begin, index)),
+                    Some((begin, end)) => {
+                        groups.push(format_group(begin, end));
+                        Some((index, index))
+                    }
+                    None => Some((index, index)),
+                };
+                continue;
+            }
+            if let Some((begin, end)) = full_group.take() {
+                groups.push(format_group(begin, end));
+            }
+            if !intersection.is_empty() {
+                groups.push(format!("{}{}({}/{})", label, index, intersection.core_count(), partition.core_count()));
+            }
+        }
+        if let Some((begin, end)) = full_group {
+            groups.push(format_group(begin, end));
+        }
+        groups.join(",")
+    }
+    /// Counts the partitions of the level `label` that intersect with `proc_set`, e.g., the number of nodes it occupies.
+    /// If `label` is a unit partition, returns the core count of the proc set. Returns `None` if there is no such level.
+    pub fn count_units(&self, proc_set: &ProcSet, label: &str) -> Option<u32> {
+        if self.unit_partitions.iter().any(|name| name.as_ref() == label) {
+            return Some(proc_set.core_count());
+        }
+        self.partitions
+            .get(label)
+            .map(|partitions| partitions.iter().filter(|partition| !(*partition & proc_set).is_empty()).count() as u32)
+    }
+    /// Same as [`Self::request`], but the resources of `excluded` are removed from `available_proc_set` beforehand,
+    /// so that they are never part of the result.
+    pub fn request_with_exclusion(&self, available_proc_set: &ProcSet, request: &HierarchyRequests, excluded: &ProcSet) -> Option<ProcSet> {
+        if excluded.is_empty() {
+            return self.request(available_proc_set, request);
+        }
+        self.request(&(available_proc_set - excluded), request)
+    }
+    /// Finds the resources satisfying all the requests in `available_proc_set`.
+    /// When more partitions than requested could satisfy a level, the lowest-index ones are taken.
+    /// For range requests, as many resources as available are taken at each level, up to the requested maximum.
     #[auto_bench_fct_hy]
     pub fn request(&self, available_proc_set: &ProcSet, request: &HierarchyRequests) -> Option<ProcSet> {
         let result = request.0.iter().try_fold(ProcSet::new(), |acc, req| {
-            self.find_resource_hierarchies_scattered(&(available_proc_set & &req.filter), &req.level_nbs)
+            self.find_resource_hierarchies_scattered_range(&(available_proc_set & &req.filter), &req.level_nbs, &req.level_max_nbs)
                 .map(|partition| partition | acc)
         });
         result
     }
-    #[auto_bench_fct_hy]
+    /// Finds `count` disjoint proc sets in `available_proc_set`, each satisfying all the requests, e.g., to place the parts of a gang-scheduled job.
+    /// Placements are chosen as by [`Self::request`], backtracking when the greedy choice leaves no room for the remaining placements:
+    /// a rejected placement is then excluded as a whole, so that the next candidate shares no resource with it.
+    /// As backtracking is exponential on infeasible requests, they are first rejected by a capacity check (see [`Self::can_fit_multiple`]),
+    /// and the search gives up after [`REQUEST_MULTIPLE_MAX_ATTEMPTS`] placement attempts with [`RequestMultipleError::AttemptsExhausted`],
+    /// telling apart a search cut short from placements that were not found.
+    pub fn request_multiple(
+        &self,
+        available_proc_set: &ProcSet,
+        request: &HierarchyRequests,
+        count: usize,
+    ) -> Result<Vec<ProcSet>, RequestMultipleError> {
+        if !self.can_fit_multiple(available_proc_set, request, count) {
+            return Err(RequestMultipleError::NotFound);
+        }
+        let mut attempts_left = REQUEST_MULTIPLE_MAX_ATTEMPTS;
+        match self.request_multiple_backtracking(available_proc_set, request, count, &mut attempts_left) {
+            Some(placements) => Ok(placements),
+            None if attempts_left == 0 => {
+                warn!(
+                    "Gave up placing {} parts of the request {} after {} attempts.",
+                    count,
+                    request.get_cache_key(),
+                    REQUEST_MULTIPLE_MAX_ATTEMPTS
+                );
+                Err(RequestMultipleError::AttemptsExhausted(REQUEST_MULTIPLE_MAX_ATTEMPTS))
+            }
+            None => Err(RequestMultipleError::NotFound),
+        }
+    }
+    fn request_multiple_backtracking(
+        &self,
+        available_proc_set: &ProcSet,
+        request: &HierarchyRequests,
+        count: usize,
+        attempts_left: &mut u32,
+    ) -> Option<Vec<ProcSet>> {
+        if count == 0 {
+            return Some(Vec::new());
+        }
+        let mut excluded = ProcSet::new();
+        while *attempts_left > 0 {
+            *attempts_left -= 1;
+            let placement = self.request_with_exclusion(available_proc_set, request, &excluded)?;
+            if let Some(mut placements) = self.request_multiple_backtracking(&(available_proc_set - &placement), request, count - 1, attempts_left) {
+                placements.insert(0, placement);
+                return Some(placements);
+            }
+            excluded |= &placement;
+        }
+        None
+    }
+    /// Checks that `available_proc_set` has enough capacity at each level of each request for `count` disjoint placements:
+    /// the product of the requested counts down to a level, times `count`, must not exceed the number of partitions of that level
+    /// intersecting the available resources (or included in them for the last level), or the number of cores for unit levels.
+    /// This is a necessary condition only, used to reject infeasible requests before backtracking.
+    pub fn can_fit_multiple(&self, available_proc_set: &ProcSet, request: &HierarchyRequests, count: usize) -> bool {
+        request.0.iter().all(|req| {
+            let available_proc_set = available_proc_set & &req.filter;
+            let mut required = count as u64;
+            req.level_nbs.iter().enumerate().all(|(i, (name, nb))| {
+                required *= *nb as u64;
+                let capacity = if self.unit_partitions.contains(name) {
+                    available_proc_set.core_count() as u64
+                } else if let Some(partitions) = self.partitions.get(name) {
+                    let is_last_level = i == req.level_nbs.len() - 1;
+                    partitions
+                        .iter()
+                        .filter(|partition| {
+                            if is_last_level {
+                                !partition.is_empty() && partition.is_subset(&available_proc_set)
+                            } else {
+                                !partition.is_disjoint(&available_proc_set)
+                            }
+                        })
+                        .count() as u64
+                } else {
+                    // Unknown levels are reported by the request itself
+                    return true;
+                };
+                required <= capacity
+            })
+        })
+    }
     pub fn find_resource_hierarchies_scattered(&self, available_proc_set: &ProcSet, level_requests: &[(Box<str>, u32)]) -> Option<ProcSet> {
+        let level_max_nbs = level_requests.iter().map(|(_, count)| *count).collect::<Vec<u32>>();
+        self.find_resource_hierarchies_scattered_range(available_proc_set, level_requests, &level_max_nbs)
+    }
+    /// Same as [`Self::find_resource_hierarchies_scattered`], taking at each level as many resources as available
+    /// between the count of `level_requests` and the maximum of `level_max_nbs`.
+    #[auto_bench_fct_hy]
+    fn find_resource_hierarchies_scattered_range(
+        &self,
+        available_proc_set: &ProcSet,
+        level_requests: &[(Box<str>, u32)],
+        level_max_nbs: &[u32],
+    ) -> Option<ProcSet> {
         let (name, request) = &level_requests[0];
+        let max = level_max_nbs[0];
         // Optimization for core that should correspond to a single proc.
         if self.unit_partitions.contains(name) {
-            return available_proc_set.sub_proc_set_with_cores(*request);
+            return available_proc_set.sub_proc_set_with_cores(max.min(available_proc_set.core_count()).max(*request));
         }
 
         if let Some(partitions) = self.partitions.get(name) {
@@ -151,7 +416,8 @@ impl Hierarchy {
                         if self.unit_partitions.contains(name) {
                             proc_set.sub_proc_set_with_cores(level_requests[1].1)
                         } else {
-                            self.find_resource_hierarchies_scattered(&(proc_set & available_proc_set), &level_requests[1..])
+                            let available_proc_set = proc_set & available_proc_set;
+                            self.find_resource_hierarchies_scattered_range(&available_proc_set, &level_requests[1..], &level_max_nbs[1..])
                         }
                     } else if proc_set.is_subset(&available_proc_set) {
                         Some(proc_set.clone())
@@ -159,7 +425,7 @@ impl Hierarchy {
                         None
                     }
                 })
-                .take(*request as usize)
+                .take(max as usize)
                 .fold((ProcSet::new(), 0), |(acc, count), proc_set| (acc | proc_set, count + 1));
 
             if count < *request {
@@ -1,24 +1,101 @@
-use crate::model::job::{Job, JobAssignment, JobBuilder, ProcSet};
-use crate::platform::PlatformTrait;
-use crate::scheduler::scheduling::schedule_jobs;
+use crate::model::configuration::{ArrayPolicy, BackfillMode, BesteffortConfig};
+use crate::model::job::{average_wait_time, Job, Moldable, ProcSet};
+use crate::platform::{PlatformTrait, ResourceSet};
+use crate::scheduler::observer::SchedulingEvent;
+use crate::scheduler::scheduling::{schedule_job, schedule_jobs_skipping};
 use crate::scheduler::slotset::SlotSet;
-use crate::scheduler::sorting::sort_jobs;
+use crate::scheduler::sorting::{group_array_jobs, sort_jobs};
+use crate::scheduler::trace::{JobTrace, TraceStep};
 use indexmap::IndexMap;
-use log::debug;
-use std::collections::HashMap;
+use log::{debug, info, warn};
+#[cfg(feature = "pyo3")]
+use pyo3::IntoPyObject;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-pub fn schedule_cycle<T: PlatformTrait>(platform: &mut T, queues: &Vec<String>) -> usize {
-    // Insert the already-scheduled besteffort jobs into the slot sets only if scheduling this queue.
-    let allow_besteffort = queues.len() == 1 && queues[0] == "besteffort";
-    let (mut slot_sets, _besteffort_jobs) = init_slot_sets(platform, allow_besteffort);
+/// Statistics of a scheduling cycle, returned by [`schedule_cycle`] and [`internal_schedule_cycle`].
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "pyo3", derive(IntoPyObject))]
+pub struct SchedulingStats {
+    /// Number of waiting jobs that got an assignment.
+    pub scheduled: usize,
+    /// Number of waiting jobs that could not be assigned.
+    pub rejected: usize,
+    /// Sum of the quotas hit counts of the waiting jobs.
+    pub quotas_hits: u32,
+    /// Number of moldables that started their slots search from a cache entry.
+    pub cache_hits: usize,
+    /// Slot count of the `default` slot set after the scheduling.
+    pub final_slot_count: usize,
+    /// Time from `now` to the end of the last assigned job, or 0 if no job was assigned.
+    pub gantt_width: i64,
+    /// Average time from `now` to the beginning of the assigned jobs, or 0 if no job was assigned (see [`average_wait_time`]).
+    pub average_wait_time: i64,
+    /// Windows tried for the waiting jobs, in the scheduling order. Empty unless `scheduler_job_traces` is enabled.
+    pub job_traces: Vec<JobTrace>,
+}
+
+/// Cooperative cancellation flag of the scheduling cycles, e.g., set when the meta-scheduler receives a shutdown signal.
+/// It is checked before scheduling each job: once cancelled, the remaining jobs are left unscheduled,
+/// the already made assignments being kept and saved. Clones share the same flag, so that it can be set from another thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Requests the cancellation of the scheduling cycles using this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Schedules the waiting jobs of `queues` and saves their assignments in the platform.
+/// Returns the statistics of the cycle, all zeros if there was no job to schedule.
+/// If there is no resource that is not dead, no job is assigned.
+pub fn schedule_cycle<T: PlatformTrait>(platform: &mut T, queues: &Vec<String>) -> SchedulingStats {
+    if platform.get_platform_config().resource_set.nb_resources_not_dead == 0 {
+        warn!("No resource available, skipping the scheduling of queues {:?}", queues);
+        return SchedulingStats::default();
+    }
+    let (mut slot_sets, _besteffort_jobs) = init_slot_sets_for_queues(platform, queues);
 
     internal_schedule_cycle(platform, &mut slot_sets, queues)
 }
 
-pub fn internal_schedule_cycle<T: PlatformTrait>(platform: &mut T, slot_sets: &mut HashMap<Box<str>, SlotSet>, queues: &Vec<String>) -> usize {
+pub fn internal_schedule_cycle<T: PlatformTrait>(
+    platform: &mut T,
+    slot_sets: &mut HashMap<Box<str>, SlotSet>,
+    queues: &Vec<String>,
+) -> SchedulingStats {
+    internal_schedule_cycle_with_preemption(platform, slot_sets, queues, &[]).0
+}
+
+/// Same as [`internal_schedule_cycle`], but with best-effort preemption:
+/// `besteffort_jobs` are the already scheduled besteffort jobs inserted in the slot sets (see [`init_slot_sets`]).
+/// If a non-besteffort job cannot be assigned, the besteffort jobs overlapping with a placement found without them
+/// are marked for preemption and their resources are given back to the slots.
+/// The besteffort jobs protected by the configuration thresholds are never preempted (see [`BesteffortConfig`]).
+/// Returns the statistics of the cycle and the ids of the preempted besteffort jobs.
+/// If the platform cancellation token is cancelled during the cycle (see [`PlatformTrait::get_cancellation_token`]),
+/// the jobs not yet scheduled are left unassigned, and the statistics only count the assignments made before the cancellation.
+pub fn internal_schedule_cycle_with_preemption<T: PlatformTrait>(
+    platform: &mut T,
+    slot_sets: &mut HashMap<Box<str>, SlotSet>,
+    queues: &Vec<String>,
+    besteffort_jobs: &[Job],
+) -> (SchedulingStats, Vec<i64>) {
     let _platform_config = platform.get_platform_config();
+    // Cache entries of a previous cycle may skip slots whose resources were given back since (e.g., by preemption).
+    slot_sets.values_mut().for_each(SlotSet::clear_cache);
     let mut waiting_jobs = platform.get_waiting_jobs(queues.to_vec());
+    let unsatisfiable_count = remove_unsatisfiable_jobs(platform, &mut waiting_jobs);
 
     {
         // info!(
@@ -40,21 +117,372 @@ pub fn internal_schedule_cycle<T: PlatformTrait>(platform: &mut T, slot_sets: &m
     if waiting_jobs.len() > 0 {
         // Sorting
         sort_jobs(platform, queues, &mut waiting_jobs);
+        if platform.get_platform_config().config.scheduler_array_policy != ArrayPolicy::None {
+            group_array_jobs(&mut waiting_jobs);
+        }
 
         // Scheduling
-        schedule_jobs(slot_sets, &mut waiting_jobs);
+        #[cfg(feature = "parallel")]
+        let unfittable_jobs = find_unfittable_jobs(slot_sets, &waiting_jobs);
+        #[cfg(not(feature = "parallel"))]
+        let unfittable_jobs = HashSet::new();
+        let scheduled_jobs_ends = get_scheduled_jobs_ends(platform, &waiting_jobs);
+        let cancellation = platform.get_cancellation_token().cloned();
+        schedule_jobs_skipping(slot_sets, &mut waiting_jobs, &unfittable_jobs, &scheduled_jobs_ends, cancellation.as_ref());
+        let cancelled = cancellation.as_ref().is_some_and(CancellationToken::is_cancelled);
+        if cancelled {
+            info!("Scheduling cycle cancelled, saving the assignments made so far");
+        }
+
+        // Preemption of besteffort jobs for the jobs that could not be scheduled
+        let preempted_jobs = if besteffort_jobs.is_empty() || cancelled {
+            vec![]
+        } else {
+            let besteffort_config = platform.get_platform_config().config.besteffort_config();
+            preempt_besteffort_jobs(slot_sets, &mut waiting_jobs, besteffort_jobs, &besteffort_config, platform.get_now())
+        };
+
+        // Backfilling of the jobs still unscheduled, in the windows left free
+        let backfill_mode = if cancelled { BackfillMode::None } else { platform.get_platform_config().config.scheduler_backfill_mode };
+        backfill_jobs(slot_sets, &mut waiting_jobs, &unfittable_jobs, &scheduled_jobs_ends, backfill_mode, platform.get_now());
+
+        // Notify the observer of the scheduling decisions
+        if let Some(observer) = platform.get_scheduling_observer() {
+            waiting_jobs.values().for_each(|job| observer.on_decision(&SchedulingEvent::from_job(job)));
+        }
+
+        let now = platform.get_now();
+        let quotas_hits = waiting_jobs.values().map(|job| job.quotas_hit_count).sum();
+        let waiting_count = waiting_jobs.len();
+        let job_traces = take_job_traces(slot_sets, &waiting_jobs);
 
         // Save assignments
         let assigned_jobs = waiting_jobs
             .into_iter()
             .filter(|(_id, job)| job.assignment.is_some())
             .collect::<IndexMap<i64, Job>>();
-        debug!("Kamelot internal saving josb: {}", assigned_jobs[0].id);
+        debug!("Kamelot internal saving {} assigned jobs", assigned_jobs.len());
+        let stats = SchedulingStats {
+            scheduled: assigned_jobs.len(),
+            rejected: waiting_count - assigned_jobs.len() + unsatisfiable_count,
+            quotas_hits,
+            cache_hits: slot_sets.values().map(SlotSet::cache_hits).sum(),
+            final_slot_count: slot_sets.get("default").unwrap().slot_count(),
+            gantt_width: assigned_jobs.values().filter_map(Job::end).map(|end| end - now + 1).max().unwrap_or(0),
+            average_wait_time: average_wait_time(assigned_jobs.values(), now).unwrap_or(0),
+            job_traces,
+        };
         platform.save_assignments(assigned_jobs);
 
-        return slot_sets.get("default").unwrap().slot_count();
+        return (stats, preempted_jobs);
+    }
+    let stats = SchedulingStats {
+        rejected: unsatisfiable_count,
+        ..SchedulingStats::default()
+    };
+    (stats, vec![])
+}
+
+/// Takes the traces recorded in the slot sets (see [`SlotSet::take_job_traces`]) for the jobs of `waiting_jobs`, in their order.
+fn take_job_traces(slot_sets: &mut HashMap<Box<str>, SlotSet>, waiting_jobs: &IndexMap<i64, Job>) -> Vec<JobTrace> {
+    let mut traces: HashMap<i64, Vec<TraceStep>> = HashMap::new();
+    for (job_id, steps) in slot_sets.values_mut().flat_map(SlotSet::take_job_traces) {
+        traces.entry(job_id).or_default().extend(steps);
+    }
+    waiting_jobs
+        .keys()
+        .filter_map(|job_id| traces.remove(job_id).map(|steps| JobTrace { job_id: *job_id, steps }))
+        .collect()
+}
+
+/// Removes from `waiting_jobs` the jobs that cannot be scheduled in this cycle, returning their number:
+/// - the moldables requesting zero resources (see [`Moldable::requests_zero_resources`]) or having a non-positive walltime can never run:
+///   they are dropped, and the jobs left without moldable are rejected through [`PlatformTrait::reject_jobs`]
+///   so that they are not retried at every cycle.
+/// - the jobs of which no moldable is satisfiable with the resources of the resource set (see [`Moldable::is_satisfiable`])
+///   are only skipped for this cycle: the resource set leaves out the Dead, Suspected and Absent resources, which may come back.
+///
+/// [`Moldable::requests_zero_resources`]: crate::model::job::Moldable::requests_zero_resources
+/// [`Moldable::is_satisfiable`]: crate::model::job::Moldable::is_satisfiable
+fn remove_unsatisfiable_jobs<T: PlatformTrait>(platform: &mut T, waiting_jobs: &mut IndexMap<i64, Job>) -> usize {
+    let platform_config = Rc::clone(platform.get_platform_config());
+    let without_walltime = waiting_jobs
+        .values()
+        .filter(|job| job.moldables.iter().any(|moldable| !moldable.has_walltime()))
+        .map(|job| job.id)
+        .collect::<Vec<i64>>();
+    if !without_walltime.is_empty() {
+        warn!("Jobs {:?} have moldables without walltime, and no walltime estimator gave them one", without_walltime);
+    }
+    let mut rejected_jobs = IndexMap::new();
+    let mut skipped_count = 0;
+    for (id, mut job) in std::mem::take(waiting_jobs) {
+        let (reason, permanent) = match rejection_reason(&mut job) {
+            Some(reason) => (reason, true),
+            None if !is_satisfiable(&job, &platform_config.resource_set) => ("requests more resources than the platform has".to_string(), false),
+            None => {
+                waiting_jobs.insert(id, job);
+                continue;
+            }
+        };
+        if let Some(observer) = platform.get_scheduling_observer() {
+            let mut event = SchedulingEvent::from_job(&job);
+            event.rejection_reason = Some(reason.as_str().into());
+            observer.on_decision(&event);
+        }
+        if permanent {
+            warn!("Job {} is rejected: it {}", job.id, reason);
+            job.message = format!("This job cannot run: it {}", reason);
+            rejected_jobs.insert(id, job);
+        } else {
+            info!("Job {} is not scheduled in this cycle: it {} available", job.id, reason);
+            skipped_count += 1;
+        }
+    }
+    let count = rejected_jobs.len() + skipped_count;
+    if !rejected_jobs.is_empty() {
+        platform.reject_jobs(rejected_jobs);
+    }
+    count
+}
+
+/// Returns why the job can never be scheduled, i.e., why none of its moldables can run, or None if it may be.
+/// If only some moldables can't run, they are removed from the job, which is scheduled with its other moldables.
+fn rejection_reason(job: &mut Job) -> Option<String> {
+    let reasons = job.moldables.iter().map(invalid_moldable_reason).collect::<Vec<Option<String>>>();
+    if reasons.iter().all(Option::is_none) {
+        return None;
+    }
+    if reasons.iter().all(Option::is_some) {
+        return reasons.into_iter().flatten().next();
+    }
+    for reason in reasons.iter().flatten() {
+        warn!("A moldable of the job {} is ignored: it {}", job.id, reason);
+    }
+    let mut reasons = reasons.into_iter();
+    job.moldables.retain(|_| reasons.next().unwrap().is_none());
+    None
+}
+
+/// Returns why the moldable can never run, or None if it may.
+fn invalid_moldable_reason(moldable: &Moldable) -> Option<String> {
+    if moldable.requests_zero_resources() {
+        Some(format!("requests zero resources in its moldable {}", moldable.id))
+    } else if moldable.walltime <= 0 {
+        Some(format!("has a non-positive walltime of {} in its moldable {}", moldable.walltime, moldable.id))
+    } else {
+        None
+    }
+}
+
+/// Returns false if no moldable of the job is satisfiable with the resources of `resource_set`.
+fn is_satisfiable(job: &Job, resource_set: &ResourceSet) -> bool {
+    job.moldables.is_empty() || job.moldables.iter().any(|moldable| moldable.is_satisfiable(resource_set))
+}
+
+/// Returns the predicted ends of the already scheduled jobs, by job id, so that the waiting jobs depending on them
+/// can't begin before their end. The scheduled jobs are only loaded if a waiting job has dependencies.
+fn get_scheduled_jobs_ends<T: PlatformTrait>(platform: &T, waiting_jobs: &IndexMap<i64, Job>) -> HashMap<i64, i64> {
+    if waiting_jobs.values().all(|job| job.dependencies.is_empty()) {
+        return HashMap::new();
+    }
+    platform
+        .get_scheduled_jobs()
+        .iter()
+        .filter_map(|job| job.end().map(|end| (job.id, end)))
+        .collect()
+}
+
+/// Returns the ids of the jobs that can't fit anywhere in their slot set, whatever the other jobs' assignments.
+/// A job can't fit if none of its moldables is satisfiable with the union of the resources of all the slots,
+/// or longer than the slot set. As scheduling only removes resources from the slot sets, these jobs would be rejected anyway
+/// by the sequential scheduling loop, so pruning them does not change the assignments of the other jobs.
+/// Moldables are evaluated in parallel with rayon. Jobs using time-sharing, placeholders or inner slot sets
+/// (whose resources grow during the scheduling), and all the jobs if hooks are registered, are never pruned.
+#[cfg(feature = "parallel")]
+pub fn find_unfittable_jobs(slot_sets: &HashMap<Box<str>, SlotSet>, waiting_jobs: &IndexMap<i64, Job>) -> HashSet<i64> {
+    use crate::hooks::get_hooks_manager;
+    use crate::model::job::ProcSet;
+    use rayon::prelude::*;
+
+    if get_hooks_manager().has_hooks_handler() {
+        return HashSet::new();
+    }
+    let default_slot_set = match slot_sets.get("default") {
+        Some(slot_set) => slot_set,
+        None => return HashSet::new(),
+    };
+    // Slot sets are not thread-safe, so the data required by the prepass is extracted beforehand.
+    let hierarchy = &default_slot_set.get_platform_config().resource_set.hierarchy;
+    let max_resources = default_slot_set.iter().fold(ProcSet::new(), |acc, slot| acc | slot.proc_set());
+    let max_walltime = default_slot_set.end() - default_slot_set.begin() + 1;
+
+    let candidates = waiting_jobs
+        .values()
+        .filter(|job| job.slot_set_name().as_ref() == "default" && job.time_sharing.is_none() && job.placeholder.is_none())
+        .collect::<Vec<&Job>>();
+    let unfittable_jobs = candidates
+        .par_iter()
+        .filter(|job| {
+            !job.moldables.iter().any(|moldable| {
+                moldable.walltime <= max_walltime
+                    && hierarchy
+                        .request_with_exclusion(&max_resources, &moldable.requests, &moldable.excluded_resources)
+                        .is_some()
+            })
+        })
+        .map(|job| job.id)
+        .collect::<HashSet<i64>>();
+    if !unfittable_jobs.is_empty() {
+        info!("Jobs {:?} can't fit in the slot set and are not scheduled", unfittable_jobs);
+    }
+    unfittable_jobs
+}
+
+/// Tries to assign the unscheduled non-besteffort jobs of `waiting_jobs` by preempting besteffort jobs.
+/// For each such job, a placement is searched in a copy of its slot set where all the remaining besteffort jobs that can be killed at `now`
+/// according to `besteffort_config` are removed.
+/// If found, only the besteffort jobs overlapping (in time and resources) with this placement are preempted:
+/// their resources are added back to the slots, and the job is assigned to the placement.
+/// Returns the ids of the preempted besteffort jobs.
+fn preempt_besteffort_jobs(
+    slot_sets: &mut HashMap<Box<str>, SlotSet>,
+    waiting_jobs: &mut IndexMap<i64, Job>,
+    besteffort_jobs: &[Job],
+    besteffort_config: &BesteffortConfig,
+    now: i64,
+) -> Vec<i64> {
+    let mut preempted_jobs: Vec<i64> = Vec::new();
+    for job in waiting_jobs.values_mut() {
+        // Jobs with dependencies are not considered as their minimum begin time depends on other jobs.
+        if job.assignment.is_some() || job.queue.as_ref() == "besteffort" || !job.dependencies.is_empty() {
+            continue;
+        }
+        let slot_set_name = job.slot_set_name();
+        let candidates = besteffort_jobs
+            .iter()
+            .filter(|be_job| be_job.slot_set_name() == slot_set_name && !preempted_jobs.contains(&be_job.id))
+            // Young besteffort jobs, or those about to end, are protected from preemption
+            .filter(|be_job| besteffort_config.can_kill(be_job, now))
+            .collect::<Vec<&Job>>();
+        if candidates.is_empty() {
+            continue;
+        }
+        let slot_set = match slot_sets.get_mut(&slot_set_name) {
+            Some(slot_set) => slot_set,
+            None => continue,
+        };
+
+        // Search a placement without any of the candidate besteffort jobs
+        let mut freed_slot_set = slot_set.clone();
+        freed_slot_set.split_slots_for_jobs_and_update_resources(&candidates, false, false, None);
+        let mut job_candidate = job.clone();
+        schedule_job(&mut freed_slot_set, &mut job_candidate, None);
+        let assignment = match job_candidate.assignment {
+            Some(assignment) => assignment,
+            None => continue,
+        };
+
+        // Preempt the besteffort jobs overlapping with the placement, giving back their resources to the slots
+        let to_preempt = candidates
+            .into_iter()
+            .filter(|be_job| {
+                let (be_begin, be_end, be_resources) = be_job.window().unwrap();
+                be_begin <= assignment.end && be_end >= assignment.begin && !(be_resources & &assignment.resources).is_empty()
+            })
+            .collect::<Vec<&Job>>();
+        slot_set.split_slots_for_jobs_and_update_resources(&to_preempt, false, false, None);
+        let to_preempt = to_preempt.iter().map(|be_job| be_job.id).collect::<Vec<i64>>();
+        info!("Job {} is scheduled by preempting the besteffort jobs {:?}", job.id, to_preempt);
+        preempted_jobs.extend(to_preempt);
+
+        job.assignment = Some(assignment);
+        job.quotas_hit_count = job_candidate.quotas_hit_count;
+        slot_set.split_slots_for_job_and_update_resources(job, true, true, None);
+    }
+    preempted_jobs
+}
+
+/// Backfilling pass assigning the jobs of `waiting_jobs` left unscheduled by the main pass, according to `mode` (see [`BackfillMode`]).
+/// The jobs are placed in the windows left free by the already assigned jobs, so that none of them is pushed back.
+/// With [`BackfillMode::Easy`], only the first unscheduled job that can be placed (the head job) gets a reservation,
+/// and the following ones are kept only if they begin at `now`. As they are placed after the head job reservation, they never delay it.
+/// Returns the number of backfilled jobs.
+pub(crate) fn backfill_jobs(
+    slot_sets: &mut HashMap<Box<str>, SlotSet>,
+    waiting_jobs: &mut IndexMap<i64, Job>,
+    unfittable_jobs: &HashSet<i64>,
+    scheduled_jobs_ends: &HashMap<i64, i64>,
+    mode: BackfillMode,
+    now: i64,
+) -> usize {
+    if mode == BackfillMode::None {
+        return 0;
+    }
+    let skipped_jobs = waiting_jobs
+        .values()
+        .filter(|job| job.assignment.is_some() || unfittable_jobs.contains(&job.id))
+        .map(|job| job.id)
+        .collect::<HashSet<i64>>();
+    if skipped_jobs.len() == waiting_jobs.len() {
+        return 0;
+    }
+
+    let backfilled_jobs = match mode {
+        BackfillMode::None => vec![],
+        BackfillMode::Conservative => {
+            schedule_jobs_skipping(slot_sets, waiting_jobs, &skipped_jobs, scheduled_jobs_ends, None);
+            waiting_jobs
+                .values()
+                .filter(|job| job.assignment.is_some() && !skipped_jobs.contains(&job.id))
+                .map(|job| job.id)
+                .collect::<Vec<i64>>()
+        }
+        BackfillMode::Easy => {
+            let mut backfilled_jobs = Vec::new();
+            let mut head_reserved = false;
+            for unit in easy_backfill_units(slot_sets, waiting_jobs, &skipped_jobs) {
+                let saved_slot_sets = if head_reserved { Some(slot_sets.clone()) } else { None };
+                let unit_skipped_jobs = waiting_jobs.keys().filter(|id| !unit.contains(id)).copied().collect::<HashSet<i64>>();
+                schedule_jobs_skipping(slot_sets, waiting_jobs, &unit_skipped_jobs, scheduled_jobs_ends, None);
+                if !unit.iter().all(|id| waiting_jobs[id].assignment.is_some()) {
+                    continue;
+                }
+                // The jobs following the head job are unscheduled if they do not begin now
+                if let Some(saved_slot_sets) = saved_slot_sets
+                    && !unit.iter().all(|id| waiting_jobs[id].begin() == Some(now))
+                {
+                    *slot_sets = saved_slot_sets;
+                    unit.iter().for_each(|id| waiting_jobs.get_mut(id).unwrap().assignment = None);
+                    continue;
+                }
+                head_reserved = true;
+                backfilled_jobs.extend(unit);
+            }
+            backfilled_jobs
+        }
+    };
+    if !backfilled_jobs.is_empty() {
+        info!("Backfilled jobs ({:?}): {:?}", mode, backfilled_jobs);
+    }
+    backfilled_jobs.len()
+}
+
+/// Groups the jobs of `waiting_jobs` that are not in `skipped_jobs` into the units backfilled together by [`BackfillMode::Easy`]:
+/// single jobs, or the consecutive members of a job array with the [`ArrayPolicy::Gang`] array policy.
+fn easy_backfill_units(slot_sets: &HashMap<Box<str>, SlotSet>, waiting_jobs: &IndexMap<i64, Job>, skipped_jobs: &HashSet<i64>) -> Vec<Vec<i64>> {
+    let gang = slot_sets
+        .get("default")
+        .is_some_and(|slot_set| slot_set.get_platform_config().config.scheduler_array_policy == ArrayPolicy::Gang);
+    let mut units: Vec<(Option<i64>, Vec<i64>)> = Vec::new();
+    for job in waiting_jobs.values().filter(|job| !skipped_jobs.contains(&job.id)) {
+        let array_id = job.array_id.filter(|_| gang);
+        match units.last_mut() {
+            Some((unit_array_id, unit)) if array_id.is_some() && *unit_array_id == array_id => unit.push(job.id),
+            _ => units.push((array_id, vec![job.id])),
+        }
     }
-    0
+    units.into_iter().map(|(_, unit)| unit).collect()
 }
 
 /// Initialize slot sets map with the `default` SlotSet initialized with resource availability and already scheduled jobs.
@@ -63,43 +491,132 @@ pub fn init_slot_sets<P>(platform: &P, allow_besteffort: bool) -> (HashMap<Box<s
 where
     P: PlatformTrait,
 {
-    let now = platform.get_now();
+    build_slot_sets(platform, allow_besteffort, platform.get_max_time())
+}
+
+/// Same as [`init_slot_sets`], but the slot sets end at the scheduling horizon of `queues`, if any:
+/// jobs that can't end before the horizon are left unscheduled rather than being scheduled far in the future.
+/// `queue_horizons` maps queue names to horizons in seconds (see [`crate::model::configuration::Configuration::queue_horizons`]).
+/// The horizon of several queues scheduled together is the largest of their horizons, and there is none if one of them has no horizon.
+pub fn init_slot_sets_with_horizons<P>(
+    platform: &P,
+    allow_besteffort: bool,
+    queues: &[String],
+    queue_horizons: &HashMap<String, i64>,
+) -> (HashMap<Box<str>, SlotSet>, Vec<Job>)
+where
+    P: PlatformTrait,
+{
     let max_time = platform.get_max_time();
+    let horizon = queues
+        .iter()
+        .map(|queue| queue_horizons.get(queue).copied())
+        .collect::<Option<Vec<i64>>>()
+        .and_then(|horizons| horizons.into_iter().max());
+    let end = horizon.map_or(max_time, |horizon| max_time.min(platform.get_now() + horizon));
+    build_slot_sets(platform, allow_besteffort, end)
+}
+
+/// Initializes the slot sets to schedule the waiting jobs of `queues`, bounded by their scheduling horizon (see [`init_slot_sets_with_horizons`]).
+/// The already scheduled besteffort jobs are obstacles only when scheduling the `besteffort` queue alone (see [`is_besteffort_only`]):
+/// for the other queues, they are transparent as they can be preempted.
+/// Returns the slot sets map and the already scheduled besteffort jobs inserted in the slot sets.
+pub fn init_slot_sets_for_queues<P>(platform: &P, queues: &[String]) -> (HashMap<Box<str>, SlotSet>, Vec<Job>)
+where
+    P: PlatformTrait,
+{
+    let queue_horizons = platform.get_platform_config().config.queue_horizons();
+    init_slot_sets_with_horizons(platform, is_besteffort_only(queues), queues, &queue_horizons)
+}
+
+/// Returns true if `queues` is the `besteffort` queue alone,
+/// in which case the already scheduled besteffort jobs must be inserted in the slot sets.
+pub fn is_besteffort_only(queues: &[String]) -> bool {
+    queues.len() == 1 && queues[0] == "besteffort"
+}
+
+fn build_slot_sets<P>(platform: &P, allow_besteffort: bool, end: i64) -> (HashMap<Box<str>, SlotSet>, Vec<Job>)
+where
+    P: PlatformTrait,
+{
+    let now = platform.get_now();
     let platform_config = platform.get_platform_config();
 
-    let mut initial_slot_set = SlotSet::from_platform_config(Rc::clone(platform_config), now, max_time);
+    // Resource availability (available_upto field) is integrated by SlotSet::from_platform_config
+    let initial_slot_set = SlotSet::from_platform_config(Rc::clone(platform_config), now, end);
 
-    // Resource availability (available_upto field) is integrated through pseudo jobs
-    slot_set_integrate_resource_availability(max_time, &platform_config.resource_set.available_upto, &mut initial_slot_set);
     // Initialize slot sets map
     let mut slot_sets = HashMap::from([("default".into(), initial_slot_set)]);
     // Place already scheduled jobs, advanced reservations and jobs from higher priority queues
-    let besteffort_jobs = add_already_scheduled_jobs_to_slot_set(&mut slot_sets, platform, allow_besteffort, true);
+    let scheduled_jobs = platform.get_scheduled_jobs();
+    let running_jobs = running_jobs_windows(platform, &scheduled_jobs, allow_besteffort);
+    let besteffort_jobs = insert_scheduled_jobs(&mut slot_sets, platform, scheduled_jobs, allow_besteffort, true);
+    reserve_running_jobs_resources(&mut slot_sets, now, running_jobs);
 
     (slot_sets, besteffort_jobs)
 }
 
-/// Create pseudo jobs at the end of the slot_set
-/// allowing to restrict the resource availability until times defined in `available_upto`.
-fn slot_set_integrate_resource_availability(max_time: i64, available_upto: &Vec<(i64, ProcSet)>, slot_set: &mut SlotSet) {
-    let mut pseudo_jobs = available_upto
+/// Returns the windows of the running jobs (see [`PlatformTrait::running_jobs_windows`]) ordered by job id, each ending at `now` at the earliest:
+/// the jobs running beyond their scheduled end (e.g., finishing jobs) are still busy.
+/// If `allow_besteffort` is false, the running besteffort jobs of `scheduled_jobs` are not considered, as they can be killed.
+fn running_jobs_windows<P>(platform: &P, scheduled_jobs: &[Job], allow_besteffort: bool) -> Vec<(i64, i64, ProcSet)>
+where
+    P: PlatformTrait,
+{
+    let now = platform.get_now();
+    let besteffort_job_ids = scheduled_jobs
         .iter()
-        .filter(|(time, _)| time < &max_time)
-        .map(|(time, intervals)| {
-            JobBuilder::new(0)
-                .name("pseudo_job".into())
-                .user("pseudo_job".into())
-                .project("pseudo_job".into())
-                .queue("pseudo_job".into())
-                .assign(JobAssignment::new(*time + 1, max_time, intervals.clone(), 0))
-                .build()
-        })
-        .collect::<Vec<Job>>();
-    pseudo_jobs.sort_by_key(|j| j.begin().unwrap());
-    slot_set.split_slots_for_jobs_and_update_resources(&pseudo_jobs.iter().collect(), false, true, None);
+        .filter(|job| job.queue.as_ref() == "besteffort")
+        .map(|job| job.id)
+        .collect::<HashSet<i64>>();
+    let mut running_jobs = platform
+        .running_jobs_windows(scheduled_jobs)
+        .into_iter()
+        .filter(|(job_id, _)| allow_besteffort || !besteffort_job_ids.contains(job_id))
+        .collect::<Vec<_>>();
+    running_jobs.sort_by_key(|(job_id, _)| *job_id);
+    running_jobs
+        .into_iter()
+        .map(|(_, (begin, end, resources))| (begin, end.max(now), resources))
+        .collect()
+}
+
+/// Removes the resources of the running jobs from the `default` slot set during their windows (see [`running_jobs_windows`]).
+/// The resources of the jobs running beyond their scheduled end, or not scheduled anymore, are not covered by the scheduled jobs assignments,
+/// but are still busy until the end of the jobs.
+fn reserve_running_jobs_resources(slot_sets: &mut HashMap<Box<str>, SlotSet>, now: i64, running_jobs: Vec<(i64, i64, ProcSet)>) {
+    let slot_set = slot_sets.get_mut("default").expect("The default slot set must exist");
+    for (begin, end, resources) in running_jobs {
+        // Only the resources not already removed by the scheduled jobs assignments require a split.
+        let uncovered = match slot_set.available_resources_at(now) {
+            Some(available) => &resources & available,
+            None => return,
+        };
+        if uncovered.is_empty() {
+            continue;
+        }
+        if let Err(e) = slot_set.apply_downtime(&uncovered, begin.max(now), end) {
+            warn!("Unable to reserve the resources {:?} of a running job from {} to {}: {}", uncovered, begin, end, e);
+        }
+    }
+}
+
+/// Removes the suspendable resources from the assignments of the suspended jobs, releasing them back to the slots
+/// so that other jobs can use them while the jobs are suspended. Jobs left without resources are removed.
+fn release_suspended_jobs_resources(scheduled_jobs: &mut Vec<Job>, suspendable_resources: &ProcSet) {
+    if suspendable_resources.is_empty() {
+        return;
+    }
+    for job in scheduled_jobs.iter_mut().filter(|job| job.state == "Suspended") {
+        if let Some(assignment) = job.assignment.as_mut() {
+            assignment.resources = &assignment.resources - suspendable_resources;
+        }
+    }
+    scheduled_jobs.retain(|job| job.assignment.as_ref().is_none_or(|assignment| !assignment.resources.is_empty()));
 }
 
 /// Inserts the scheduled_jobs of the platform into the slot_sets.
+/// The suspendable resources of the suspended jobs are not inserted (see [`release_suspended_jobs_resources`]).
 /// If `allow_besteffort` is true, the besteffort jobs are inserted.
 /// If `allow_other` is true, the non-besteffort jobs are inserted.
 /// Returns a Vec with all the besteffort jobs that were added to the slot sets, ordered by start time.
@@ -107,7 +624,20 @@ pub fn add_already_scheduled_jobs_to_slot_set<T>(slot_sets: &mut HashMap<Box<str
 where
     T: PlatformTrait,
 {
-    let mut scheduled_jobs = platform.get_scheduled_jobs();
+    insert_scheduled_jobs(slot_sets, platform, platform.get_scheduled_jobs(), allow_besteffort, allow_other)
+}
+
+/// Same as [`add_already_scheduled_jobs_to_slot_set`], with the already loaded `scheduled_jobs` of the platform.
+fn insert_scheduled_jobs<T>(
+    slot_sets: &mut HashMap<Box<str>, SlotSet>,
+    platform: &T,
+    mut scheduled_jobs: Vec<Job>,
+    allow_besteffort: bool,
+    allow_other: bool,
+) -> Vec<Job>
+where
+    T: PlatformTrait,
+{
     // let mut scheduled_jobs = scheduled_jobs.iter().collect::<Vec<&Job>>();
     scheduled_jobs.sort_by_key(|j| j.begin().unwrap());
     if allow_besteffort && !allow_other {
@@ -119,6 +649,7 @@ where
     } else if !allow_besteffort && !allow_other {
         return vec![];
     }
+    release_suspended_jobs_resources(&mut scheduled_jobs, &platform.get_platform_config().resource_set.suspendable_resources);
     let mut slot_set_jobs: HashMap<Box<str>, Vec<&Job>> = HashMap::new();
     scheduled_jobs.iter().for_each(|job| {
         let slot_set_name = job.slot_set_name();
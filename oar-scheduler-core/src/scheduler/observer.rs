@@ -0,0 +1,82 @@
+use crate::model::job::{Job, ProcSetCoresOp};
+use log::warn;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::io::Write;
+
+/// Machine-readable record of the scheduling decision taken for a job during a scheduling cycle.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SchedulingEvent {
+    pub job_id: i64,
+    /// Index of the chosen moldable in `job.moldables`, if the job has been scheduled.
+    pub moldable_index: Option<usize>,
+    /// Id of the chosen moldable, if the job has been scheduled.
+    pub moldable_id: Option<i64>,
+    pub begin: Option<i64>,
+    pub end: Option<i64>,
+    /// Core count of the assigned proc set, if the job has been scheduled.
+    pub core_count: Option<u32>,
+    /// Reason why the job could not be scheduled, if unscheduled.
+    pub rejection_reason: Option<Box<str>>,
+}
+
+impl SchedulingEvent {
+    /// Builds the event from a job after its assignment attempt.
+    pub fn from_job(job: &Job) -> SchedulingEvent {
+        match &job.assignment {
+            Some(assignment) => SchedulingEvent {
+                job_id: job.id,
+                moldable_index: Some(assignment.moldable_index),
+                moldable_id: assignment.moldable_id(&job.moldables),
+                begin: Some(assignment.begin),
+                end: Some(assignment.end),
+                core_count: Some(assignment.resources.core_count()),
+                rejection_reason: None,
+            },
+            None => SchedulingEvent {
+                job_id: job.id,
+                moldable_index: None,
+                moldable_id: None,
+                begin: None,
+                end: None,
+                core_count: None,
+                rejection_reason: Some(if job.quotas_hit_count > 0 {
+                    "quotas exceeded".into()
+                } else {
+                    "no resources available".into()
+                }),
+            },
+        }
+    }
+}
+
+/// Observer of the scheduling decisions, registered by the platform with [`crate::platform::PlatformTrait::get_scheduling_observer`].
+/// `on_decision` is called by `kamelot` once per waiting job after its assignment attempt.
+pub trait SchedulingObserver {
+    fn on_decision(&self, event: &SchedulingEvent);
+}
+
+/// Observer writing each event as a JSON object on its own line (JSON Lines format).
+pub struct JsonlObserver<W: Write> {
+    writer: RefCell<W>,
+}
+
+impl<W: Write> JsonlObserver<W> {
+    pub fn new(writer: W) -> JsonlObserver<W> {
+        JsonlObserver { writer: RefCell::new(writer) }
+    }
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+}
+
+impl<W: Write> SchedulingObserver for JsonlObserver<W> {
+    fn on_decision(&self, event: &SchedulingEvent) {
+        let mut writer = self.writer.borrow_mut();
+        let result = serde_json::to_writer(&mut *writer, event).map_err(std::io::Error::from).and_then(|_| writer.write_all(b"\n"));
+        if let Err(e) = result {
+            warn!("Failed to write the scheduling event of job {}: {}", event.job_id, e);
+        }
+    }
+}
@@ -1,9 +1,12 @@
 use crate::model::job::Job;
 use crate::platform::PlatformConfig;
+use crate::scheduler::calendar::DEFAULT_RULES_ID;
+use crate::scheduler::slot::Slot;
 use crate::scheduler::slotset::SlotIterator;
 use auto_bench_fct::auto_bench_fct_hy;
 use serde::Deserialize;
 use serde_json::Value;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::rc::Rc;
@@ -11,12 +14,26 @@ use std::rc::Rc;
 /// key: (queue, project, job_type, user)
 pub type QuotasKey = (Box<str>, Box<str>, Box<str>, Box<str>);
 
-/// Used to store the quotas maximum values for a certain rule, and to track a slot current quota usage
+/// Used to store the quotas maximum values for a certain rule, and to track a slot current quota usage.
+/// The resources and default resources are counted with the resource weights (see [`crate::platform::ResourceSet::weighted_core_count`]).
 #[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct QuotasValue {
     resources: Option<u32>,       // Number of busy resources
     running_jobs: Option<u32>,    // Number of running jobs
     resources_times: Option<i64>, // Resource time in use (nb_resources * walltime)
+    /// Number of busy resources of type default, i.e., in [`crate::platform::ResourceSet::default_resources`],
+    /// e.g., to limit the CPU nodes without limiting the accelerators.
+    /// Its limit is parsed from the fifth element of the rule values, and not the fourth, which is the resources times weight
+    /// (see [`QuotasValue::from_serde_values`]).
+    #[serde(default)]
+    default_resources: Option<u32>,
+    /// Multiplier applied to the resources times of the jobs this rule applies to, e.g., 2.0 to make GPU jobs count double
+    /// against a time budget. Only meaningful for the rules, counters keep the default value of 1.0.
+    #[serde(default = "default_resources_times_weight")]
+    resources_times_weight: f64,
+}
+fn default_resources_times_weight() -> f64 {
+    1.0
 }
 impl QuotasValue {
     pub fn new(resources: Option<u32>, running_jobs: Option<u32>, resources_times: Option<i64>) -> Self {
@@ -24,14 +41,32 @@ impl QuotasValue {
             resources,
             running_jobs,
             resources_times,
+            default_resources: None,
+            resources_times_weight: default_resources_times_weight(),
         }
     }
+    /// Sets the number of busy resources of type default, unlimited if `None`.
+    pub fn with_default_resources(mut self, default_resources: Option<u32>) -> Self {
+        self.default_resources = default_resources;
+        self
+    }
+    /// Sets the multiplier applied to the resources times of the jobs this rule applies to.
+    pub fn with_resources_times_weight(mut self, resources_times_weight: f64) -> Self {
+        self.resources_times_weight = resources_times_weight;
+        self
+    }
+    pub fn resources_times_weight(&self) -> f64 {
+        self.resources_times_weight
+    }
     /// Increments the values of `self` by the given amounts.
     /// Used by the counters to track the current usage of quotas.
-    pub fn increment(&mut self, resources: u32, running_jobs: u32, resources_times: i64) {
+    pub fn increment(&mut self, resources: u32, default_resources: u32, running_jobs: u32, resources_times: i64) {
         if let Some(r) = &mut self.resources {
             *r += resources;
         }
+        if let Some(dr) = &mut self.default_resources {
+            *dr += default_resources;
+        }
         if let Some(rj) = &mut self.running_jobs {
             *rj += running_jobs;
         }
@@ -39,7 +74,7 @@ impl QuotasValue {
             *rt += resources_times;
         }
     }
-    /// Combines the values of `self` and `other` by taking the maximum for resources and running_jobs,
+    /// Combines the values of `self` and `other` by taking the maximum for resources, default resources and running_jobs,
     /// and summing resources_times (as resources_times depend on the time).
     /// Used to combine slot quotas and make checks against larger time windows.
     pub fn combine(&mut self, other: &QuotasValue) {
@@ -48,6 +83,11 @@ impl QuotasValue {
                 *r = (*r).max(other_r);
             }
         }
+        if let Some(dr) = &mut self.default_resources
+            && let Some(other_dr) = other.default_resources
+        {
+            *dr = (*dr).max(other_dr);
+        }
         if let Some(rj) = &mut self.running_jobs {
             if let Some(other_rj) = other.running_jobs {
                 *rj = (*rj).max(other_rj);
@@ -69,6 +109,12 @@ impl QuotasValue {
                 }
             }
         }
+        if let Some(default_resources) = self.default_resources
+            && let Some(counted_default_resources) = counts.default_resources
+            && counted_default_resources > default_resources
+        {
+            return Some(("Default resources exceeded".into(), default_resources as i64));
+        }
         if let Some(running_jobs) = self.running_jobs {
             if let Some(counted_running_jobs) = counts.running_jobs {
                 if counted_running_jobs > running_jobs {
@@ -87,47 +133,47 @@ impl QuotasValue {
     }
     /// Converts an array of serde values integer Number or String to a QuotasValue.
     /// Values "ALL" will be replaced by the `all_value` parameter, and values "x*ALL" will multiply the `all_value` by the float `x`.
-    /// Examples: `[100, "ALL", "0.5*ALL"]`, `["34.5", "ALL", "2*ALL"]` are valid inputs.
+    /// An optional fourth value is the resources times weight (see [`QuotasValue::with_resources_times_weight`]), 1.0 by default,
+    /// and an optional fifth value is the default resources limit (see [`QuotasValue::with_default_resources`]), unlimited by default.
+    /// In the fifth value, "ALL" is replaced by `default_all_value`, the count of the resources of type default.
+    /// Examples: `[100, "ALL", "0.5*ALL"]`, `["34.5", "ALL", "2*ALL"]`, `[-1, -1, 100, 2.0]`, `[-1, -1, -1, 1.0, 64]` are valid inputs.
+    /// Returns an error message if a value is not a number, a numeric string, `"ALL"` or `"x*ALL"`, or if there are less than three values.
     #[allow(dead_code)]
-    pub fn from_serde_values(values: &[Value], all_value: i64) -> QuotasValue {
+    pub fn from_serde_values(values: &[Value], all_value: i64, default_all_value: i64) -> Result<QuotasValue, String> {
+        let parse_number = |s: &str| s.parse::<f64>().map_err(|_| format!("invalid quotas value number: expected a number, got '{}'", s));
         let parsed = values
             .iter()
-            .map(|v| match v {
+            .enumerate()
+            .map(|(index, v)| match v {
                 Value::Number(n) => {
-                    let n = n
-                        .as_f64()
-                        .expect(format!("Invalid quotas value number: expected f64, got {}", n).as_str());
-                    if n < 0f64 { None } else { Some(n) }
+                    let n = n.as_f64().ok_or_else(|| format!("invalid quotas value number: expected f64, got {}", n))?;
+                    Ok(if n < 0f64 { None } else { Some(n) })
                 }
                 Value::String(s) => {
+                    let all_value = if index == 4 { default_all_value } else { all_value };
                     if s == "ALL" {
-                        Some(all_value as f64)
-                    } else if s.ends_with("*ALL") {
-                        Some(
-                            s[..s.len() - 4].parse::<f64>().expect(
-                                format!(
-                                    "Invalid quotas value number: excepted f64 multiplicator, got {}",
-                                    s[..s.len() - 4].to_string()
-                                )
-                                .as_str(),
-                            ) * all_value as f64,
-                        )
+                        Ok(Some(all_value as f64))
+                    } else if let Some(multiplicator) = s.strip_suffix("*ALL") {
+                        Ok(Some(parse_number(multiplicator)? * all_value as f64))
                     } else {
-                        let n = s
-                            .parse::<f64>()
-                            .expect(format!("Invalid quotas value number: excepted i64, got {}", s).as_str());
-                        if n < 0f64 { None } else { Some(n) }
+                        let n = parse_number(s)?;
+                        Ok(if n < 0f64 { None } else { Some(n) })
                     }
                 }
-                _ => None,
+                v => Err(format!("invalid quotas value: expected a number or a string, got {}", v)),
             })
-            .collect::<Vec<Option<f64>>>();
+            .collect::<Result<Vec<Option<f64>>, String>>()?;
+        if parsed.len() < 3 {
+            return Err(format!("expected at least 3 quotas values (resources, running jobs, resources times), got {}", parsed.len()));
+        }
 
-        QuotasValue {
+        Ok(QuotasValue {
             resources: parsed[0].map(|i| i as u32),
             running_jobs: parsed[1].map(|i| i as u32),
             resources_times: parsed[2].map(|i| (i * 3600.0) as i64), // Converting hours to seconds
-        }
+            default_resources: parsed.get(4).copied().flatten().map(|i| i as u32),
+            resources_times_weight: parsed.get(3).copied().flatten().unwrap_or_else(default_resources_times_weight),
+        })
     }
 }
 impl Default for QuotasValue {
@@ -136,6 +182,8 @@ impl Default for QuotasValue {
             resources: None,
             running_jobs: None,
             resources_times: None,
+            default_resources: None,
+            resources_times_weight: default_resources_times_weight(),
         }
     }
 }
@@ -147,26 +195,27 @@ pub type QuotasMap = HashMap<QuotasKey, QuotasValue>;
 /// Parses a JSON string representing quotas into a QuotasMap.
 /// The JSON must be a mapping between a string key (formatted as `queue,project,job_type,user` with names or `*` or `/`)
 ///     and an array of values (see `QuotasValue::from_serde_values`).
+/// Returns an error message telling the failing key if a key or its values are malformed.
 #[allow(dead_code)]
-pub fn build_quotas_map(quotas_map: &HashMap<String, Vec<Value>>, all_value: i64) -> QuotasMap {
+pub fn build_quotas_map(quotas_map: &HashMap<String, Vec<Value>>, all_value: i64, default_all_value: i64) -> Result<QuotasMap, String> {
     quotas_map
         .iter()
         .map(|(key, value)| {
             let key_parts: Vec<&str> = key.split(',').collect();
             if key_parts.len() != 4 {
-                panic!(
-                    "Invalid quotas key format: expected 4 parts, got {} parts in {}",
+                return Err(format!(
+                    "invalid quotas key format: expected 4 parts, got {} parts in {}",
                     key_parts.len(),
                     key.as_str()
-                );
+                ));
             }
             let queue = key_parts[0].into();
             let project = key_parts[1].into();
             let job_type = key_parts[2].into();
             let user = key_parts[3].into();
 
-            let quotas_value = QuotasValue::from_serde_values(value, all_value);
-            ((queue, project, job_type, user), quotas_value)
+            let quotas_value = QuotasValue::from_serde_values(value, all_value, default_all_value).map_err(|e| format!("{} in {}", e, key.as_str()))?;
+            Ok(((queue, project, job_type, user), quotas_value))
         })
         .collect()
 }
@@ -261,11 +310,44 @@ impl<T> QuotasTreeNodeTrait for HashMap<Box<str>, T> {
     }
 }
 
+/// Explanation of the quotas rule applied to a job, returned by [`Quotas::explain`] and [`explain_slots_quotas`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotasExplanation {
+    /// Key of the matched rule, which might contain wildcards `*` or `/`.
+    pub rule_key: QuotasKey,
+    /// Key of the counter checked against the rule, i.e., `rule_key` with the `/` replaced by the job values.
+    pub counter_key: QuotasKey,
+    pub counters: QuotasValue,
+    pub limits: QuotasValue,
+    /// Description and value of the exceeded limit, if any (see [`QuotasValue::check`]).
+    pub exceeded: Option<(Box<str>, i64)>,
+}
+impl QuotasExplanation {
+    fn new(rule_key: QuotasKey, counter_key: QuotasKey, counters: QuotasValue, limits: QuotasValue) -> QuotasExplanation {
+        let exceeded = limits.check(&counters);
+        QuotasExplanation {
+            rule_key,
+            counter_key,
+            counters,
+            limits,
+            exceeded,
+        }
+    }
+}
+fn zero_counters() -> QuotasValue {
+    QuotasValue::new(Some(0), Some(0), Some(0)).with_default_resources(Some(0))
+}
+
 /// Tracks quotas for a single slot
 #[derive(Clone)]
 pub struct Quotas {
     counters: QuotasMap,
+    /// Number of jobs entering the slot for each counter key, i.e., beginning in it, or running since a slot with other rules.
+    /// Summed over the slots of a period, it gives the number of jobs running during the period. Only tracked with `quotas_period_counters`.
+    period_jobs: HashMap<QuotasKey, u32>,
     rules_id: i32, // Used to differentiate Quotas instances with different rules.
+    /// Begin time of the occurrence of the rules the slot belongs to, differentiating consecutive periods with the same rules.
+    period: i64,
     rules: Rc<QuotasMap>,
     rules_tree: Rc<QuotasTree>,
     platform_config: Rc<PlatformConfig>,
@@ -274,7 +356,9 @@ impl Debug for Quotas {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Quotas")
             .field("counters", &self.counters)
+            .field("period_jobs", &self.period_jobs)
             .field("rules_id", &self.rules_id)
+            .field("period", &self.period)
             .field("rules", &self.rules)
             .field("rules_tree", &self.rules_tree)
             .finish()
@@ -291,7 +375,9 @@ impl Quotas {
     pub fn new(platform_config: Rc<PlatformConfig>, rules_id: i32, rules: Rc<QuotasMap>, rules_tree: Rc<QuotasTree>) -> Quotas {
         Quotas {
             counters: QuotasMap::default(),
+            period_jobs: HashMap::new(),
             rules_id,
+            period: 0,
             rules,
             rules_tree,
             platform_config,
@@ -302,24 +388,62 @@ impl Quotas {
     pub fn from_platform_config(platform_config: Rc<PlatformConfig>) -> Quotas {
         Quotas {
             counters: QuotasMap::default(),
+            period_jobs: HashMap::new(),
             rules_id: platform_config.quotas_config.default_rules_id,
+            period: 0,
             rules: Rc::clone(&platform_config.quotas_config.default_rules),
             rules_tree: Rc::clone(&platform_config.quotas_config.default_rules_tree),
             platform_config,
         }
     }
 
+    /// Sets the begin time of the occurrence of the rules the slot belongs to (see [`Quotas::is_same_period`]).
+    pub fn with_period(mut self, period: i64) -> Quotas {
+        self.period = period;
+        self
+    }
+    /// Returns whether `self` and `other` belong to the same occurrence of the same rules.
+    pub fn is_same_period(&self, other: &Quotas) -> bool {
+        self.rules_id == other.rules_id && self.period == other.period
+    }
+
     /// Increment the Quotas counters for a job.
-    /// The job does not need to be scheduled yet, hence the slot width (end - begin + 1) and resource_count are provided.
-    pub fn increment_for_job(&mut self, job: &Job, slot_width: i64, resource_count: u32) {
+    /// The job does not need to be scheduled yet, hence the slot width (end - begin + 1), resource_count,
+    /// and default_resource_count (the count of its resources of type default) are provided.
+    /// The resources times are scaled by the weight of the rule applicable to the job, if any.
+    pub fn increment_for_job(&mut self, job: &Job, slot_width: i64, resource_count: u32, default_resource_count: u32) {
         if job.types.contains_key("container") {
             // Job container does not increment quotas counters but do are subject to quotas limits.
             return;
         }
         let resources = resource_count;
         let running_jobs = 1;
-        let resources_times = slot_width * resources as i64;
+        let weight = self
+            .find_applicable_rule(job)
+            .map_or(default_resources_times_weight(), |(_, _, rule_value)| rule_value.resources_times_weight);
+        let resources_times = weighted_resources_times(slot_width, resources, weight);
+
+        for key in self.counter_keys_for_job(job) {
+            self.counters
+                .entry(key)
+                .or_insert_with(zero_counters)
+                .increment(resources, default_resource_count, running_jobs, resources_times);
+        }
+    }
+
+    /// Increment the number of jobs entering the slot for a job (see `Quotas::period_jobs`),
+    /// used with `quotas_period_counters` to count the jobs running during a period.
+    pub fn increment_period_jobs_for_job(&mut self, job: &Job) {
+        if job.types.contains_key("container") {
+            return;
+        }
+        for key in self.counter_keys_for_job(job) {
+            *self.period_jobs.entry(key).or_insert(0) += 1;
+        }
+    }
 
+    /// Returns the keys of the counters incremented for a job, i.e., all the combinations of `*` and the job queue, project, tracked types and user.
+    fn counter_keys_for_job(&self, job: &Job) -> Vec<QuotasKey> {
         let matched_queues = ["*", &job.queue];
         let mut matched_projects = vec!["*"];
         if let Some(project) = job.project.as_ref() {
@@ -339,19 +463,17 @@ impl Quotas {
             matched_users.push(user);
         }
 
+        let mut keys = Vec::with_capacity(matched_queues.len() * matched_projects.len() * matched_job_types.len() * matched_users.len());
         matched_queues.iter().for_each(|queue| {
             matched_projects.iter().for_each(|project| {
                 matched_job_types.iter().for_each(|job_type| {
                     matched_users.iter().for_each(|user| {
-                        let value = self
-                            .counters
-                            .entry(((*queue).into(), (*project).into(), (*job_type).clone(), (*user).into()))
-                            .or_insert(QuotasValue::new(Some(0), Some(0), Some(0)));
-                        value.increment(resources, running_jobs, resources_times);
+                        keys.push(((*queue).into(), (*project).into(), (*job_type).clone(), (*user).into()));
                     });
                 });
             });
         });
+        keys
     }
 
     /// Combines the counters of `self` and `quotas` by taking the maximum for resources and running_jobs,
@@ -361,6 +483,58 @@ impl Quotas {
         for (key, value) in &quotas.counters {
             self.counters.entry(key.clone()).and_modify(|v| v.combine(value)).or_insert(value.clone());
         }
+        for (key, count) in &quotas.period_jobs {
+            *self.period_jobs.entry(key.clone()).or_insert(0) += count;
+        }
+    }
+
+    /// Splits the counters of a slot being split in two consecutive parts of `width_before` and `width_after` seconds:
+    /// `self` keeps the counters of the first part, and the counters of the second part are returned.
+    /// The counters are duplicated in both parts, as in the Python scheduler, except with `quotas_period_counters`,
+    /// where the resources times are shared proportionally to the widths so that their sum over a period stays exact.
+    /// The jobs entering the slot stay counted in the first part only.
+    pub fn split_off(&mut self, width_before: i64, width_after: i64) -> Quotas {
+        let mut after = Quotas {
+            counters: self.counters.clone(),
+            period_jobs: HashMap::new(),
+            rules_id: self.rules_id,
+            period: self.period,
+            rules: Rc::clone(&self.rules),
+            rules_tree: Rc::clone(&self.rules_tree),
+            platform_config: Rc::clone(&self.platform_config),
+        };
+        if !self.platform_config.config.quotas_period_counters {
+            return after;
+        }
+        for (key, value) in self.counters.iter_mut() {
+            if let Some(resources_times) = value.resources_times.as_mut() {
+                let resources_times_after = (*resources_times as i128 * width_after as i128 / (width_before + width_after) as i128) as i64;
+                *resources_times -= resources_times_after;
+                after.counters.get_mut(key).unwrap().resources_times = Some(resources_times_after);
+            }
+        }
+        after
+    }
+
+    /// Adds the running jobs of the period (the jobs entering the slot) and the resources times of `self` to `period_counters`.
+    /// Summed over the slots of a period, `period_counters` gives the counters of the whole period.
+    fn add_period_counters(&self, period_counters: &mut QuotasMap) {
+        for (key, value) in &self.counters {
+            let period_jobs = self.period_jobs.get(key).copied().unwrap_or(0);
+            period_counters
+                .entry(key.clone())
+                .or_insert_with(zero_counters)
+                .increment(0, 0, period_jobs, value.resources_times.unwrap_or(0));
+        }
+    }
+    /// Replaces the running jobs and resources times counters of `self` by the ones of `period_counters`,
+    /// the resources counters being kept.
+    fn set_period_counters(&mut self, period_counters: QuotasMap) {
+        for (key, value) in period_counters {
+            let counters = self.counters.entry(key).or_insert_with(zero_counters);
+            counters.running_jobs = value.running_jobs;
+            counters.resources_times = value.resources_times;
+        }
     }
 
     /// Finds the rule key that should be applied to `job` (i.e., the QuotasMapKey).
@@ -369,49 +543,7 @@ impl Quotas {
     /// it will lead to undefined behavior if there are quotas rules for different job types and jobs that have these job types at the same time.
     /// It returns two keys, the first one being the same as the second one, but with the "/" replaced by the actual name, and the value QuotasValue (the limits).
     pub fn find_applicable_rule(&self, job: &Job) -> Option<(QuotasKey, QuotasKey, &QuotasValue)> {
-        let key_queue = Some(job.queue.as_ref());
-        let key_project = job.project.as_ref().map(|s| s.as_ref());
-        let key_job_types = job.types.iter().map(|(k, _v)| k).collect::<Box<[&Box<str>]>>();
-        let key_user = job.user.as_ref().map(|s| s.as_ref());
-
-        let mut rule_key = None;
-        let mut rule_value = None;
-
-        if let Some(key_queue) = self.rules_tree.0.first_valid_key(key_queue) {
-            let map = self.rules_tree.0.get(&key_queue).unwrap();
-            if let Some(key_project) = map.first_valid_key(key_project) {
-                let map = map.get(&key_project).unwrap();
-                if let Some(key_job_type) = map.first_valid_key_multiple(key_job_types.as_ref()) {
-                    let map = map.get(&key_job_type).unwrap();
-                    if let Some(key_user) = map.first_valid_key(key_user) {
-                        rule_value = map.get(&key_user);
-                        rule_key = Some((key_queue, key_project, key_job_type, key_user));
-                    }
-                }
-            }
-        }
-
-        let mut rule_key_counter = rule_key.clone();
-        // If the key is "/", replace by the queue name, project name, job type, or user.
-        if let Some((key_queue, key_project, _key_job_type, key_user)) = &mut rule_key_counter {
-            if key_queue.as_ref() == "/" {
-                *key_queue = job.queue.clone().into();
-            }
-            if let Some(project) = &job.project {
-                if key_project.as_ref() == "/" {
-                    *key_project = project.clone().into();
-                }
-            }
-            // "/" is not available for job types
-            if let Some(user) = &job.user {
-                if key_user.as_ref() == "/" {
-                    *key_user = user.clone().into();
-                }
-            }
-
-            return Some((rule_key_counter.unwrap(), rule_key.unwrap(), rule_value.unwrap()));
-        }
-        None
+        find_applicable_rule_in(&self.rules_tree, job)
     }
     /// Checks if the quotas counters for the job `job` exceeds the limits.
     /// WARNING: This function assumes that the counters have already been updated with `Quotas::update_for_job`.
@@ -423,40 +555,259 @@ impl Quotas {
         rule_value.check(counts).map(|(description, limit)| (description, rule_key, limit))
     }
 
+    /// Checks if the quotas counters would exceed the limits after incrementing them for `job` with `Quotas::increment_for_job`,
+    /// using `extra_width` as slot width, and `extra_resources` and `extra_default_resources` as resource counts.
+    /// Gives the same result as incrementing a clone of `self` and calling `Quotas::check`, but without mutating or cloning the counters:
+    /// only the counter of the applicable rule is computed.
+    pub fn check_with_additional(
+        &self,
+        job: &Job,
+        extra_resources: u32,
+        extra_default_resources: u32,
+        extra_width: i64,
+    ) -> Option<(Box<str>, QuotasKey, i64)> {
+        self.check_with_additional_rules(&self.rules_tree, job, extra_resources, extra_default_resources, extra_width)
+    }
+    /// Same as [`Quotas::check_with_additional`], but checks the counters against the rules of `rules_tree` instead of the rules of `self`,
+    /// e.g., against the rules of the job queue quotas configuration.
+    pub fn check_with_additional_rules(
+        &self,
+        rules_tree: &QuotasTree,
+        job: &Job,
+        extra_resources: u32,
+        extra_default_resources: u32,
+        extra_width: i64,
+    ) -> Option<(Box<str>, QuotasKey, i64)> {
+        let (rule_key_counter, rule_key, rule_value) = find_applicable_rule_in(rules_tree, job)?;
+        let counts = self.counts_with_additional(&rule_key_counter, rule_value, job, (extra_resources, extra_default_resources), extra_width)?;
+        rule_value.check(&counts).map(|(description, limit)| (description, rule_key, limit))
+    }
+
+    /// Explains which rule of `self` applies to `job`, with its current counters and limits, even if they are not exceeded.
+    /// Returns None if no rule applies to the job.
+    pub fn explain(&self, job: &Job) -> Option<QuotasExplanation> {
+        let (counter_key, rule_key, rule_value) = self.find_applicable_rule(job)?;
+        let counters = self.counters.get(&counter_key).cloned().unwrap_or_else(zero_counters);
+        Some(QuotasExplanation::new(rule_key, counter_key, counters, rule_value.clone()))
+    }
+    /// Same as [`Quotas::explain`], but against the rules of `rules_tree`,
+    /// and with the counters incremented as in [`Quotas::check_with_additional_rules`].
+    pub fn explain_with_additional_rules(
+        &self,
+        rules_tree: &QuotasTree,
+        job: &Job,
+        extra_resources: u32,
+        extra_default_resources: u32,
+        extra_width: i64,
+    ) -> Option<QuotasExplanation> {
+        let (counter_key, rule_key, rule_value) = find_applicable_rule_in(rules_tree, job)?;
+        let counters = self
+            .counts_with_additional(&counter_key, rule_value, job, (extra_resources, extra_default_resources), extra_width)
+            .unwrap_or_else(zero_counters);
+        Some(QuotasExplanation::new(rule_key, counter_key, counters, rule_value.clone()))
+    }
+
+    /// Returns the counter of key `key` as if incremented for `job` with `Quotas::increment_for_job`,
+    /// or None if the counter does not exist and is not incremented by the job.
+    /// `extra_resources` are the counts of resources and of default resources of the job.
+    fn counts_with_additional(
+        &self,
+        key: &QuotasKey,
+        rule_value: &QuotasValue,
+        job: &Job,
+        (extra_resources, extra_default_resources): (u32, u32),
+        extra_width: i64,
+    ) -> Option<QuotasValue> {
+        let extra_resources_times = weighted_resources_times(extra_width, extra_resources, rule_value.resources_times_weight);
+        match (self.counters.get(key), self.is_counter_incremented_by_job(key, job)) {
+            (Some(counts), false) => Some(counts.clone()),
+            (Some(counts), true) => {
+                let mut counts = counts.clone();
+                counts.increment(extra_resources, extra_default_resources, 1, extra_resources_times);
+                Some(counts)
+            }
+            (None, true) => Some(
+                QuotasValue::new(Some(extra_resources), Some(1), Some(extra_resources_times)).with_default_resources(Some(extra_default_resources)),
+            ),
+            (None, false) => None,
+        }
+    }
+
+    /// Returns true if the counter of key `key` is incremented by `Quotas::increment_for_job` for the job `job`.
+    fn is_counter_incremented_by_job(&self, key: &QuotasKey, job: &Job) -> bool {
+        if job.types.contains_key("container") {
+            return false;
+        }
+        let (queue, project, job_type, user) = key;
+        (queue.as_ref() == "*" || queue == &job.queue)
+            && (project.as_ref() == "*" || Some(project) == job.project.as_ref())
+            && self.platform_config.quotas_config.tracked_job_types.contains(job_type)
+            && (job_type.as_ref() == "*" || job.types.contains_key(job_type))
+            && (user.as_ref() == "*" || Some(user) == job.user.as_ref())
+    }
+
     pub fn rules_id(&self) -> i32 {
         self.rules_id
     }
+    pub fn counters(&self) -> &QuotasMap {
+        &self.counters
+    }
+}
+
+/// Finds the rule of `rules_tree` that should be applied to `job`, see [`Quotas::find_applicable_rule`].
+fn find_applicable_rule_in<'a>(rules_tree: &'a QuotasTree, job: &Job) -> Option<(QuotasKey, QuotasKey, &'a QuotasValue)> {
+    let key_queue = Some(job.queue.as_ref());
+    let key_project = job.project.as_ref().map(|s| s.as_ref());
+    let key_job_types = job.types.keys().collect::<Box<[&Box<str>]>>();
+    let key_user = job.user.as_ref().map(|s| s.as_ref());
+
+    let mut rule_key = None;
+    let mut rule_value = None;
+
+    if let Some(key_queue) = rules_tree.0.first_valid_key(key_queue) {
+        let map = rules_tree.0.get(&key_queue).unwrap();
+        if let Some(key_project) = map.first_valid_key(key_project) {
+            let map = map.get(&key_project).unwrap();
+            if let Some(key_job_type) = map.first_valid_key_multiple(key_job_types.as_ref()) {
+                let map = map.get(&key_job_type).unwrap();
+                if let Some(key_user) = map.first_valid_key(key_user) {
+                    rule_value = map.get(&key_user);
+                    rule_key = Some((key_queue, key_project, key_job_type, key_user));
+                }
+            }
+        }
+    }
+
+    let mut rule_key_counter = rule_key.clone();
+    // If the key is "/", replace by the queue name, project name, job type, or user.
+    if let Some((key_queue, key_project, _key_job_type, key_user)) = &mut rule_key_counter {
+        if key_queue.as_ref() == "/" {
+            *key_queue = job.queue.clone();
+        }
+        if let Some(project) = &job.project
+            && key_project.as_ref() == "/"
+        {
+            *key_project = project.clone();
+        }
+        // "/" is not available for job types
+        if let Some(user) = &job.user
+            && key_user.as_ref() == "/"
+        {
+            *key_user = user.clone();
+        }
+
+        return Some((rule_key_counter.unwrap(), rule_key.unwrap(), rule_value.unwrap()));
+    }
+    None
+}
+
+/// Returns the resources times of `resources` resources used during `width` seconds, scaled by `weight`.
+fn weighted_resources_times(width: i64, resources: u32, weight: f64) -> i64 {
+    let resources_times = width * resources as i64;
+    if weight == 1.0 { resources_times } else { (resources_times as f64 * weight).round() as i64 }
 }
 
-/// The job does not need to be scheduled yet; hence the start time, end time and resource_count are provided.
+/// The job does not need to be scheduled yet; hence the start time, end time, resource_count and default_resource_count are provided.
 /// `slots` are the encompassing slots for the job.
 /// Returns Some if quotas are exceeded, with a description, the rule key, and the limit value.
-pub fn check_slots_quotas<'s>(slots: SlotIterator, job: &Job, start: i64, end: i64, resource_count: u32) -> Option<(Box<str>, QuotasKey, i64)> {
-    let mut slots_quotas: HashMap<i32, (Quotas, i64)> = HashMap::new();
+pub fn check_slots_quotas(
+    slots: SlotIterator,
+    job: &Job,
+    start: i64,
+    end: i64,
+    resource_count: u32,
+    default_resource_count: u32,
+) -> Option<(Box<str>, QuotasKey, i64)> {
+    check_quotas(&combine_slots_quotas(slots, start, end), job, resource_count, default_resource_count)
+}
+/// Verbose version of [`check_slots_quotas`]: explains the rules applied to the job for each combined slots quotas,
+/// including the rules of the job queue quotas configuration, whether they are exceeded or not.
+pub fn explain_slots_quotas(
+    slots: SlotIterator,
+    job: &Job,
+    start: i64,
+    end: i64,
+    resource_count: u32,
+    default_resource_count: u32,
+) -> Vec<QuotasExplanation> {
+    let slots_quotas = combine_slots_quotas(slots, start, end);
+    let queue_rules_tree = queue_rules_tree(&slots_quotas, job);
+    slots_quotas
+        .values()
+        .flat_map(|(quotas, duration)| {
+            [
+                Some(quotas.explain_with_additional_rules(&quotas.rules_tree, job, resource_count, default_resource_count, *duration)),
+                queue_rules_tree
+                    .map(|rules_tree| quotas.explain_with_additional_rules(rules_tree, job, resource_count, default_resource_count, *duration)),
+            ]
+        })
+        .flatten()
+        .flatten()
+        .collect()
+}
+/// Combines the quotas of `slots` with the total duration they cover within `[start, end]`, grouped by rules_id.
+fn combine_slots_quotas<'a>(slots: SlotIterator<'a>, start: i64, end: i64) -> HashMap<i32, (Cow<'a, Quotas>, i64)> {
+    let mut slots_quotas: HashMap<i32, (Cow<Quotas>, i64)> = HashMap::new();
 
     // Combine in slot_quotas all quotas with the total duration they cover, grouped by rules_id.
-    for slot in slots {
+    // Quotas are only cloned if several slots with the same rules_id need to be combined.
+    for slot in slots.clone() {
         let quotas = slot.quotas();
         let used_width = slot.end().min(end) - slot.begin().max(start) + 1;
         slots_quotas
             .entry(quotas.rules_id)
             .and_modify(|(q, duration)| {
-                q.combine(&quotas);
+                q.to_mut().combine(quotas);
                 *duration += used_width;
             })
-            .or_insert((quotas.clone(), used_width));
+            .or_insert((Cow::Borrowed(quotas), used_width));
+    }
+
+    // With `quotas_period_counters`, the running jobs and resources times of the temporal rules are counted over the whole periods
+    // overlapped by `[start, end]`, i.e., extending the slots to the neighbor slots with the same rules.
+    if let Some(first_slot) = slots.peek()
+        && first_slot.platform_config.config.quotas_period_counters
+    {
+        let mut periods_counters: HashMap<i32, QuotasMap> = HashMap::new();
+        let same_period = |slot: &Slot, neighbor: &Slot| slot.quotas.rules_id != DEFAULT_RULES_ID && slot.quotas.is_same_period(&neighbor.quotas);
+        for slot in slots.extend_while(same_period).filter(|slot| slot.quotas.rules_id != DEFAULT_RULES_ID) {
+            slot.quotas.add_period_counters(periods_counters.entry(slot.quotas.rules_id).or_default());
+        }
+        for (rules_id, period_counters) in periods_counters {
+            if let Some((quotas, _)) = slots_quotas.get_mut(&rules_id) {
+                quotas.to_mut().set_period_counters(period_counters);
+            }
+        }
     }
-    check_quotas(slots_quotas, job, resource_count)
+    slots_quotas
+}
+/// Returns the rules of the job queue quotas configuration, if any.
+fn queue_rules_tree<'a>(slots_quotas: &'a HashMap<i32, (Cow<Quotas>, i64)>, job: &Job) -> Option<&'a QuotasTree> {
+    slots_quotas
+        .values()
+        .next()
+        .and_then(|(quotas, _)| quotas.platform_config.queues_quotas_config.get(job.queue.as_ref()))
+        .map(|queue_quotas_config| queue_quotas_config.default_rules_tree.as_ref())
 }
-/// The job does not need to be scheduled yet, hence the resource_count is provided.
+/// The job does not need to be scheduled yet, hence the resource_count and default_resource_count are provided.
 /// Returns Some if quotas are exceeded, with a description, the rule key, and the limit value.
 #[auto_bench_fct_hy]
-pub fn check_quotas<'s>(mut slots_quotas: HashMap<i32, (Quotas, i64)>, job: &Job, resource_count: u32) -> Option<(Box<str>, QuotasKey, i64)> {
-    // Check each combined quotas against the job.
-    for (_, (quotas, duration)) in slots_quotas.iter_mut() {
-        // Checking if after updating, it exceeds the rules.
-        quotas.increment_for_job(job, *duration, resource_count); // Doing it on a clone of quotas to avoid modifying the original.
-        let res = quotas.check(job);
+pub fn check_quotas(
+    slots_quotas: &HashMap<i32, (Cow<Quotas>, i64)>,
+    job: &Job,
+    resource_count: u32,
+    default_resource_count: u32,
+) -> Option<(Box<str>, QuotasKey, i64)> {
+    // The rules of the job queue quotas configuration, if any, are checked in addition to the slots rules.
+    let queue_rules_tree = queue_rules_tree(slots_quotas, job);
+    // Check each combined quotas against the job, as if the counters were incremented for the job.
+    for (quotas, duration) in slots_quotas.values() {
+        let res = quotas
+            .check_with_additional(job, resource_count, default_resource_count, *duration)
+            .or_else(|| {
+                queue_rules_tree
+                    .and_then(|rules_tree| quotas.check_with_additional_rules(rules_tree, job, resource_count, default_resource_count, *duration))
+            });
         if res.is_some() {
             return res;
         }
@@ -1,28 +1,81 @@
 use crate::hooks::get_hooks_manager;
-use crate::model::job::{Job, JobAssignment, JobBuilder, Moldable, ProcSet, ProcSetCoresOp};
+use crate::model::configuration::{ArrayPolicy, SchedulingPolicy};
+use crate::model::job::{DependencyType, Job, JobAssignment, JobBuilder, Moldable, ProcSet, ProcSetCoresOp};
+use crate::scheduler::kamelot::CancellationToken;
 use crate::scheduler::quotas;
 use crate::scheduler::slot::Slot;
 use crate::scheduler::slotset::SlotSet;
+use crate::scheduler::trace::TraceStep;
 use auto_bench_fct::auto_bench_fct_hy;
 use indexmap::IndexMap;
-use log::{error, info, warn};
+use log::{debug, error, info, log_enabled, warn, Level};
 use std::cmp::max;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 /// Schedule loop with support for jobs container - can be recursive
 pub fn schedule_jobs(slot_sets: &mut HashMap<Box<str>, SlotSet>, waiting_jobs: &mut IndexMap<i64, Job>) {
-    let job_ids = waiting_jobs.keys().into_iter().cloned().collect::<Box<[i64]>>();
+    schedule_jobs_skipping(slot_sets, waiting_jobs, &HashSet::new(), &HashMap::new(), None);
+}
+
+/// Same as [`schedule_jobs`], but the jobs of `skipped_jobs` are not scheduled, e.g., because they are known not to fit.
+/// `scheduled_jobs_ends` maps the ids of the already scheduled jobs (e.g., running jobs) to their predicted end:
+/// a job depending on one of them can't begin before this end. Other non-terminated dependencies prevent the job from being scheduled.
+/// A terminated dependency is satisfied according to its [`DependencyType`]: on any terminal state with `anyway`,
+/// and only on a successful termination (exit code 0, or unknown) with `ok`.
+/// With the [`ArrayPolicy::Gang`] array policy, the consecutive members of a job array are unscheduled
+/// if they do not all begin at the same time (see [`crate::scheduler::sorting::group_array_jobs`]).
+/// If `cancellation` is cancelled, the jobs following the one being scheduled are left unscheduled.
+pub fn schedule_jobs_skipping(
+    slot_sets: &mut HashMap<Box<str>, SlotSet>,
+    waiting_jobs: &mut IndexMap<i64, Job>,
+    skipped_jobs: &HashSet<i64>,
+    scheduled_jobs_ends: &HashMap<i64, i64>,
+    cancellation: Option<&CancellationToken>,
+) {
+    let job_ids = waiting_jobs.keys().cloned().collect::<Box<[i64]>>();
+    let gang = slot_sets
+        .get("default")
+        .is_some_and(|slot_set| slot_set.get_platform_config().config.scheduler_array_policy == ArrayPolicy::Gang);
+    let mut array_sizes: HashMap<i64, usize> = HashMap::new();
+    if gang {
+        waiting_jobs.values().filter_map(|job| job.array_id).for_each(|array_id| *array_sizes.entry(array_id).or_default() += 1);
+    }
+    let mut gang_array: Option<GangArray> = None;
     for job_id in job_ids {
+        if cancellation.is_some_and(CancellationToken::is_cancelled) {
+            break;
+        }
+        if gang {
+            let array_id = waiting_jobs.get(&job_id).unwrap().array_id.filter(|array_id| array_sizes[array_id] > 1);
+            if gang_array.as_ref().is_some_and(|gang_array| Some(gang_array.array_id) != array_id) {
+                end_gang_array(slot_sets, waiting_jobs, gang_array.take().unwrap());
+            }
+            if let Some(array_id) = array_id {
+                gang_array
+                    .get_or_insert_with(|| GangArray { array_id, saved_slot_sets: slot_sets.clone(), members: Vec::new() })
+                    .members
+                    .push(job_id);
+            }
+        }
+        if skipped_jobs.contains(&job_id) {
+            continue;
+        }
         // Check job dependencies
         let dependencies = waiting_jobs.get(&job_id).unwrap().dependencies.clone();
         let mut min_begin: Option<i64> = None;
-        if !dependencies.iter().all(|(dep_job_id, dep_state, dep_exit_code)| {
+        if !dependencies.iter().all(|(dep_job_id, dep_state, dep_exit_code, dep_type)| {
             if dep_state.as_ref() == "Error" {
-                info!(
-                    "Job {} has a dependency on job {} which is in error state, ignoring dependency.",
-                    job_id, dep_job_id
-                );
-                return true;
+                match dep_type {
+                    DependencyType::Anyway => return true,
+                    DependencyType::Legacy => {
+                        info!("Job {} has a dependency on job {} which is in error state, ignoring dependency.", job_id, dep_job_id);
+                        return true;
+                    }
+                    DependencyType::Ok => {
+                        info!("Job {} has an ok dependency on job {} which is in error state.", job_id, dep_job_id);
+                        return false;
+                    }
+                }
             }
             if dep_state.as_ref() == "Waiting" {
                 if let Some(dep_job) = waiting_jobs.get(dep_job_id) {
@@ -38,7 +91,12 @@ pub fn schedule_jobs(slot_sets: &mut HashMap<Box<str>, SlotSet>, waiting_jobs: &
                 }
                 return false;
             }
-            if dep_state.as_ref() == "Terminated" && (*dep_exit_code == Some(0) || *dep_exit_code == None) {
+            if dep_state.as_ref() == "Terminated" {
+                // A missing exit code counts as a success (see [`DependencyType::Ok`])
+                return *dep_type == DependencyType::Anyway || matches!(dep_exit_code, Some(0) | None);
+            }
+            if let Some(dep_end) = scheduled_jobs_ends.get(dep_job_id) {
+                min_begin = Some(min_begin.map_or(dep_end + 1, |min| min.max(dep_end + 1)));
                 return true;
             }
             false
@@ -60,11 +118,39 @@ pub fn schedule_jobs(slot_sets: &mut HashMap<Box<str>, SlotSet>, waiting_jobs: &
             }
         }
     }
+    if let Some(gang_array) = gang_array {
+        end_gang_array(slot_sets, waiting_jobs, gang_array);
+    }
+}
+
+/// Job array being gang-scheduled by [`schedule_jobs_skipping`].
+struct GangArray {
+    array_id: i64,
+    /// Slot sets before the scheduling of the first member.
+    saved_slot_sets: HashMap<Box<str>, SlotSet>,
+    /// Ids of the members scheduled so far.
+    members: Vec<i64>,
+}
+
+/// Checks that all the members of a gang-scheduled job array begin at the same time.
+/// Otherwise, unschedules them and restores the slot sets captured before the scheduling of the first member.
+fn end_gang_array(slot_sets: &mut HashMap<Box<str>, SlotSet>, waiting_jobs: &mut IndexMap<i64, Job>, gang_array: GangArray) {
+    let GangArray { array_id, saved_slot_sets, members } = gang_array;
+    let begins = members.iter().map(|id| waiting_jobs[id].begin()).collect::<Vec<Option<i64>>>();
+    if begins[0].is_some() && begins.iter().all(|begin| *begin == begins[0]) {
+        return;
+    }
+    info!("Job array {} can't be scheduled all together, its jobs {:?} are deferred", array_id, members);
+    *slot_sets = saved_slot_sets;
+    for id in members {
+        waiting_jobs.get_mut(&id).unwrap().assignment = None;
+    }
 }
 
 /// According to a Job’s resources and a `SlotSet`, find the time and the resources to launch a job.
 /// This function supports the moldable jobs. In case of multiple moldable jobs corresponding to the request,
-/// it selects the first to finish.
+/// it selects the first to finish, and among those finishing at the same time, the one using the fewest resources.
+/// With the [`SchedulingPolicy::LatestFit`] policy, it selects the last to begin instead.
 ///
 /// This function has two side effects.
 ///   - Assign the results directly to the `job` (such as start_time, resources, etc.)
@@ -75,17 +161,30 @@ pub fn schedule_job(slotset: &mut SlotSet, job: &mut Job, min_begin: Option<i64>
     let mut chosen_begin = None;
     let mut chosen_end = None;
     let mut chosen_moldable_index = None;
-    let mut chosen_proc_set = None;
+    let mut chosen_proc_set: Option<ProcSet> = None;
 
     let mut total_quotas_hit_count = 0;
+    let policy = slotset.get_platform_config().config.scheduler_placement_policy;
 
     job.moldables.iter().enumerate().for_each(|(i, moldable)| {
-        if let Some((slot_id_left, _slot_id_right, proc_set, quotas_hit_count)) = find_slots_for_moldable(slotset, job, moldable, min_begin) {
+        if let Some((slot_id_left, slot_id_right, proc_set, quotas_hit_count)) = find_slots_for_moldable(slotset, job, moldable, min_begin) {
             total_quotas_hit_count += quotas_hit_count;
-            let begin = slotset.get_slot(slot_id_left).unwrap().begin();
-            let end = begin + max(0, moldable.walltime - 1);
+            let begin = moldable_begin(slotset, policy, moldable, slot_id_left, slot_id_right);
+            let end = Job::compute_end(begin, moldable.walltime);
 
-            if chosen_end.is_none() || end < chosen_end.unwrap() {
+            // Keep the moldable finishing first (or beginning last with LatestFit), and on equal times, the one using the fewest resources.
+            let is_better = match (chosen_begin, chosen_end, &chosen_proc_set) {
+                (Some(chosen_begin), Some(chosen_end), Some(chosen_proc_set)) => match policy {
+                    SchedulingPolicy::EarliestFirst => {
+                        end < chosen_end || (end == chosen_end && proc_set.core_count() < chosen_proc_set.core_count())
+                    }
+                    SchedulingPolicy::LatestFit => {
+                        begin > chosen_begin || (begin == chosen_begin && proc_set.core_count() < chosen_proc_set.core_count())
+                    }
+                },
+                _ => true,
+            };
+            if is_better {
                 chosen_slot_id_left = Some(slot_id_left);
                 chosen_begin = Some(begin);
                 chosen_end = Some(end);
@@ -110,55 +209,107 @@ pub fn schedule_job(slotset: &mut SlotSet, job: &mut Job, min_begin: Option<i64>
     }
 }
 
+/// Returns the begin time of a moldable placed between the slots `slot_id_left` and `slot_id_right` returned by [`find_slots_for_moldable`]:
+/// the beginning of the left slot, or with [`SchedulingPolicy::LatestFit`], the time making the moldable end with the right slot.
+fn moldable_begin(slotset: &SlotSet, policy: SchedulingPolicy, moldable: &Moldable, slot_id_left: i32, slot_id_right: i32) -> i64 {
+    match policy {
+        SchedulingPolicy::EarliestFirst => slotset.get_slot(slot_id_left).unwrap().begin(),
+        SchedulingPolicy::LatestFit => slotset.get_slot(slot_id_right).unwrap().end() - max(0, moldable.walltime - 1),
+    }
+}
+
 /// Returns left slot id, right slot id, proc_set and quotas hit count.
+/// Slots are searched from the beginning of the slot set, or from its end with the [`SchedulingPolicy::LatestFit`] policy.
 #[auto_bench_fct_hy]
 pub fn find_slots_for_moldable(slotset: &mut SlotSet, job: &Job, moldable: &Moldable, min_begin: Option<i64>) -> Option<(i32, i32, ProcSet, u32)> {
-    let mut iter = slotset.iter();
-    // Start at cache if available
-    if job.can_use_cache() {
-        if let Some(cache_first_slot) = slotset.get_cache_first_slot(moldable) {
-            iter = iter.start_at(cache_first_slot);
-        }
-    }
-    // Start at the minimum begin time if specified
-    let cache_begin = iter.peek().map(|s| s.begin()).unwrap_or(slotset.begin());
-    if let Some(min_begin) = min_begin {
-        if min_begin > cache_begin {
-            if let Some(start_slot) = slotset.slot_at(min_begin, iter.peek().map(|s| s.id())) {
-                // If min_begin is not the beginning of a slot, we need to split the current slot at min_begin
-                // (can occur if the job is not in the same slot set as its dependencies).
-                if start_slot.begin() < min_begin {
-                    let (_left_slot_id, right_slot_id) = slotset.find_and_split_at(min_begin, true);
-                    iter = slotset.iter().start_at(right_slot_id);
-                } else {
-                    iter = iter.start_at(start_slot.id());
+    let policy = slotset.get_platform_config().config.scheduler_placement_policy;
+    // Jobs never begin before the real now, even if the slot set begins earlier
+    let min_begin = min_begin.max(slotset.jobs_min_begin());
+    // The cache entries are shared by the jobs with the same moldable requests, so they are neither used nor set
+    // for the jobs whose available resources are filtered by `hook_filter_resources`.
+    let mut filtered_by_hook = {
+        let mut probe = slotset.get_platform_config().resource_set.default_resources.clone();
+        get_hooks_manager().hook_filter_resources(slotset.get_platform_config(), job, &mut probe)
+    };
+    let windows: Box<dyn Iterator<Item = (&Slot, &Slot)>> = match policy {
+        SchedulingPolicy::EarliestFirst => {
+            // Start at cache if available
+            let cache_first_slot = if job.can_use_cache() && !filtered_by_hook { slotset.get_cache_first_slot(moldable) } else { None };
+            let mut iter = slotset.iter();
+            if let Some(cache_first_slot) = cache_first_slot {
+                iter = iter.start_at(cache_first_slot);
+            }
+            // Start at the minimum begin time if specified
+            let cache_begin = iter.peek().map(|s| s.begin()).unwrap_or(slotset.begin());
+            if let Some(min_begin) = min_begin
+                && min_begin > cache_begin
+            {
+                if let Some(start_slot) = slotset.slot_at(min_begin, iter.peek().map(|s| s.id())) {
+                    // If min_begin is not the beginning of a slot, we need to split the current slot at min_begin
+                    // (can occur if the job is not in the same slot set as its dependencies).
+                    if start_slot.begin() < min_begin {
+                        let start_slot_id = start_slot.id();
+                        match slotset.try_split_at(start_slot_id, min_begin, true) {
+                            Ok((_left_slot_id, right_slot_id)) => iter = slotset.iter().start_at(right_slot_id),
+                            // Without splitting, the search starts at the next slot
+                            Err(e) => match slotset.get_slot(start_slot_id).and_then(|slot| slot.next()) {
+                                Some(next_slot_id) => {
+                                    warn!("Job {} search starts after {}: {}", job.id, min_begin, e);
+                                    iter = slotset.iter().start_at(next_slot_id);
+                                }
+                                None => return None,
+                            },
+                        }
+                    } else {
+                        iter = iter.start_at(start_slot.id());
+                    }
+                } else if min_begin > slotset.end() {
+                    return None; // No slots available after the minimum begin time
                 }
-            } else if min_begin > slotset.end() {
-                return None; // No slots available after the minimum begin time
             }
+            Box::new(iter.with_width(moldable.walltime))
         }
-    }
+        SchedulingPolicy::LatestFit => {
+            // The windows begin earlier and earlier, stop at the first one beginning before the minimum begin time.
+            let min_begin = min_begin.unwrap_or(i64::MIN);
+            let walltime = moldable.walltime;
+            Box::new(
+                slotset
+                    .iter()
+                    .rev_with_width(walltime)
+                    .take_while(move |(_left_slot, right_slot)| right_slot.end() - max(0, walltime - 1) >= min_begin),
+            )
+        }
+    };
 
     // A cache entry is set to the first slot available before the quotas check, so the cache key does not include the job user, project, types or queue.
     let mut cache_first_slot = None;
 
     let mut quotas_hit_count = 0;
 
+    // Windows tried, only recorded with `scheduler_job_traces`
+    let tracing = slotset.get_platform_config().config.scheduler_job_traces;
+    let mut trace_steps: Vec<TraceStep> = Vec::new();
+
     let mut count = 0;
-    let res = iter.with_width(moldable.walltime).find_map(|(left_slot, right_slot)| {
+    let res = windows.into_iter().find_map(|(left_slot, right_slot)| {
         count += 1;
         let left_slot_id = left_slot.id();
         let right_slot_id = right_slot.id();
-        let left_slot_begin = left_slot.begin();
+        let begin = moldable_begin(slotset, policy, moldable, left_slot_id, right_slot_id);
 
         let empty: Box<str> = "".into();
         let (ts_user_name, ts_job_name) = job.time_sharing.as_ref().map_or((None, None), |_| {
             (Some(job.user.as_ref().unwrap_or(&empty)), Some(job.name.as_ref().unwrap_or(&empty)))
         });
-        let available_resources = slotset.intersect_slots_intervals(left_slot_id, right_slot_id, ts_user_name, ts_job_name, &job.placeholder);
+        let mut available_resources = slotset.intersect_slots_intervals(left_slot_id, right_slot_id, ts_user_name, ts_job_name, &job.placeholder);
+        if job.suspendable {
+            available_resources = available_resources & &slotset.get_platform_config().resource_set.suspendable_resources;
+        }
+        filtered_by_hook |= get_hooks_manager().hook_filter_resources(slotset.get_platform_config(), job, &mut available_resources);
 
         // Finding resources according to hook or hierarchy request
-        {
+        let found = {
             if let Some(res) = get_hooks_manager().hook_find(slotset, job, moldable, min_begin, available_resources.clone()) {
                 res
             } else {
@@ -166,9 +317,18 @@ pub fn find_slots_for_moldable(slotset: &mut SlotSet, job: &Job, moldable: &Mold
                     .get_platform_config()
                     .resource_set
                     .hierarchy
-                    .request(&available_resources, &moldable.requests)
+                    .request_with_exclusion(&available_resources, &moldable.requests, &moldable.excluded_resources)
             }
-        }
+        };
+        let mut trace_step = tracing.then(|| TraceStep {
+            moldable_id: moldable.id,
+            begin,
+            end: Job::compute_end(begin, moldable.walltime),
+            available_resources,
+            hierarchy_result: found.clone(),
+            quotas_exceeded: None,
+        });
+        let res = found
             .and_then(|proc_set| {
                 if cache_first_slot.is_none() {
                     cache_first_slot = Some(left_slot.id());
@@ -177,47 +337,80 @@ pub fn find_slots_for_moldable(slotset: &mut SlotSet, job: &Job, moldable: &Mold
                 // Checking quotas
             if slotset.get_platform_config().quotas_config.enabled && !job.no_quotas {
                 if let Some(calendar) = &slotset.get_platform_config().quotas_config.calendar {
-                    if left_slot_begin + moldable.walltime - 1 > slotset.begin() + calendar.quotas_window_time_limit() {
+                    if Job::compute_end(begin, moldable.walltime) > slotset.begin() + calendar.quotas_window_time_limit() {
                         warn!(
                             "Job {} cannot be scheduled: no slots available within the quotas time limit ({} seconds).",
                             job.id,
                             calendar.quotas_window_time_limit()
                         );
+                        if let Some(step) = &mut trace_step {
+                            step.quotas_exceeded = Some("Beyond the quotas time limit".into());
+                        }
                         return None;
                     }
                 }
                 let slots = slotset.iter().between(left_slot_id, right_slot_id);
-                let end = left_slot_begin + moldable.walltime - 1;
-                if let Some((msg, rule, limit)) = quotas::check_slots_quotas(slots, job, left_slot_begin, end, proc_set.core_count()) {
+                let end = Job::compute_end(begin, moldable.walltime);
+                let resource_set = &slotset.get_platform_config().resource_set;
+                let resources_count = resource_set.weighted_core_count(&proc_set) as u32;
+                let default_resources_count = resource_set.weighted_default_core_count(&proc_set) as u32;
+                if let Some((msg, rule, limit)) = quotas::check_slots_quotas(slots, job, begin, end, resources_count, default_resources_count) {
                     info!(
                         "Quotas limitation reached for job {}: {}, rule: {:?}, limit: {}",
                         job.id, msg, rule, limit
                     );
+                    if log_enabled!(Level::Debug) {
+                        let slots = slotset.iter().between(left_slot_id, right_slot_id);
+                        for explanation in quotas::explain_slots_quotas(slots, job, begin, end, resources_count, default_resources_count) {
+                            debug!("Quotas rule applied to job {}: {:?}", job.id, explanation);
+                        }
+                    }
+                    if let Some(step) = &mut trace_step {
+                        step.quotas_exceeded = Some(format!("{}, rule: {:?}, limit: {}", msg, rule, limit).into());
+                    }
                     quotas_hit_count += 1;
                     return None; // Skip this slot if quotas check fails
                 }
             }
                 Some((left_slot_id, right_slot_id, proc_set, quotas_hit_count))
-            })
+            });
+        trace_steps.extend(trace_step);
+        res
     });
+    if tracing {
+        slotset.record_trace_steps(job.id, trace_steps);
+    }
 
-    if job.can_set_cache() && slotset.get_platform_config().config.cache_enabled {
-        if let Some(cache_first_slot_id) = cache_first_slot {
-            slotset.insert_cache_entry(moldable.cache_key.clone(), cache_first_slot_id);
-        }
+    // The cache entries are the first slots of the forward search, so they are only set and used with EarliestFirst.
+    if policy == SchedulingPolicy::EarliestFirst
+        && job.can_set_cache()
+        && !filtered_by_hook
+        && slotset.get_platform_config().config.cache_enabled
+        && let Some(cache_first_slot_id) = cache_first_slot
+    {
+        slotset.insert_cache_entry(moldable.cache_key.clone(), cache_first_slot_id);
     }
 
     res
 }
 
-/// Returns the slot set for a job using get_job_slot_set_name.
+/// Returns the slot set for a job using [`Job::slot_set_name`].
+/// Returns `None` if the slot set does not exist, which is the case for inner jobs whose container job has not been scheduled.
 pub fn get_job_slot_set<'s>(slotsets: &'s mut HashMap<Box<str>, SlotSet>, job: &Job) -> Option<&'s mut SlotSet> {
     let slot_set_name = job.slot_set_name();
     if !slotsets.contains_key(&slot_set_name) {
-        error!(
-            "Job {} can't be scheduled, slot set {} is missing. Skip it for this round.",
-            job.id, slot_set_name
-        );
+        if job.types.contains_key("inner") {
+            // The container job has not been scheduled (rejected, or not yet processed), so the inner job is rejected too.
+            info!(
+                "Job {} can't be scheduled, its container slot set {} does not exist (container job not scheduled). Skip it for this round.",
+                job.id, slot_set_name
+            );
+        } else {
+            error!(
+                "Job {} can't be scheduled, slot set {} is missing. Skip it for this round.",
+                job.id, slot_set_name
+            );
+        }
         return None;
     }
     Some(slotsets.get_mut(&slot_set_name).unwrap())
@@ -262,7 +455,8 @@ pub fn update_container_job_slot_set(slotsets: &mut HashMap<Box<str>, SlotSet>,
             // .placeholder(job.placeholder.clone()) Do not apply the placeholder to the available slots of the children slot set
             .assign(JobAssignment::new(
                 assignment.begin,
-                assignment.end - platform_config.config.scheduler_job_security_time, // Removing the security time added by get_data_jobs.
+                // Removing the security time added by get_data_jobs.
+                job.effective_end(platform_config.config.job_security_time(&job.queue)).unwrap(),
                 assignment.resources.clone(),
                 0,
             ))
@@ -273,3 +467,93 @@ pub fn update_container_job_slot_set(slotsets: &mut HashMap<Box<str>, SlotSet>,
             .split_slots_for_job_and_update_resources(&pseudo_job, false, false, None);
     }
 }
+
+/// Window found by [`find_reservation_window`] for an advance reservation, or the reason why no window is found.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReservationWindow {
+    /// The reservation fits on `resources`, `delay` seconds after its requested start time.
+    Found { delay: i64, resources: ProcSet },
+    /// No window fits before the slot set end, i.e., the scheduling horizon, and the requested window or a later window allowed
+    /// by the max delay ends after it: the reservation should be checked again by the next cycles, instead of being rejected.
+    AfterHorizon { horizon: i64 },
+    /// The requested resources are not available in any window.
+    InsufficientResources,
+    /// The requested resources are available, but the quotas are exceeded in every window having them.
+    /// The quotas exceeded by the first of these windows are returned.
+    QuotasExceeded { reason: Box<str>, rule: quotas::QuotasKey, limit: i64 },
+}
+
+/// Finds the window of the advance reservation `job` (only its first moldable is considered) requested from `start_time` to `end_time`,
+/// `effective_end` being the end of the window without the security time.
+/// If the requested resources are not available or the quotas are exceeded in the requested window, a job with the type `max_delay=N`
+/// slides to the first slot beginning at most `N` seconds later in which they are (see [`Job::reservation_max_delay`]).
+/// Only the windows ending before the scheduling horizon, i.e., the slot set end, are checked as the resources are unknown after it:
+/// if none of them fits and later windows are allowed by the max delay, the reservation should be checked again by the next cycles.
+pub fn find_reservation_window(slot_set: &SlotSet, job: &Job, start_time: i64, end_time: i64, effective_end: i64) -> ReservationWindow {
+    // The requested window ends after the horizon: it can't be checked in this cycle
+    let horizon_delay = slot_set.end() - effective_end;
+    if horizon_delay < 0 {
+        return ReservationWindow::AfterHorizon { horizon: slot_set.end() };
+    }
+    // Delays to try: the requested window first, then the windows starting at the next slots, up to the max delay clamped to the horizon
+    let max_delay = job.reservation_max_delay();
+    let max_checked_delay = max_delay.min(horizon_delay);
+    let mut delays = vec![0];
+    if max_checked_delay > 0 {
+        delays.extend(
+            slot_set
+                .iter()
+                .map(|slot| slot.begin() - start_time)
+                .filter(|delay| *delay > 0 && *delay <= max_checked_delay),
+        );
+    }
+
+    let platform_config = slot_set.get_platform_config();
+    let mut quotas_exceeded = None;
+    for delay in delays {
+        let Some((left_slot, right_slot)) = slot_set.get_encompassing_range(start_time + delay, effective_end + delay, None) else {
+            continue;
+        };
+        let (left_slot_id, right_slot_id) = (left_slot.id(), right_slot.id());
+        let Some(proc_set) = find_reservation_resources(slot_set, job, left_slot_id, right_slot_id) else {
+            continue;
+        };
+
+        if platform_config.quotas_config.enabled && !job.no_quotas {
+            let slots = slot_set.iter().between(left_slot_id, right_slot_id);
+            let resources_count = platform_config.resource_set.weighted_core_count(&proc_set) as u32;
+            let default_resources_count = platform_config.resource_set.weighted_default_core_count(&proc_set) as u32;
+            if let Some((reason, rule, limit)) =
+                quotas::check_slots_quotas(slots, job, start_time + delay, end_time + delay, resources_count, default_resources_count)
+            {
+                debug!("Job {}: quotas exceeded with a delay of {}s: {}", job.id, delay, reason);
+                quotas_exceeded.get_or_insert(ReservationWindow::QuotasExceeded { reason, rule, limit });
+                continue;
+            }
+        }
+        return ReservationWindow::Found { delay, resources: proc_set };
+    }
+    if max_delay > horizon_delay {
+        return ReservationWindow::AfterHorizon { horizon: slot_set.end() };
+    }
+    quotas_exceeded.unwrap_or(ReservationWindow::InsufficientResources)
+}
+
+/// Returns the resources of the first moldable of the advance reservation `job` that are available from the slot `left_slot_id`
+/// to the slot `right_slot_id`, if any.
+fn find_reservation_resources(slot_set: &SlotSet, job: &Job, left_slot_id: i32, right_slot_id: i32) -> Option<ProcSet> {
+    let moldable = &job.moldables[0];
+    // Time-sharing and placeholder
+    let empty: Box<str> = "".into();
+    let (ts_user_name, ts_job_name) = job.time_sharing.as_ref().map_or((None, None), |_| {
+        (Some(job.user.as_ref().unwrap_or(&empty)), Some(job.name.as_ref().unwrap_or(&empty)))
+    });
+    let mut available_resources = slot_set.intersect_slots_intervals(left_slot_id, right_slot_id, ts_user_name, ts_job_name, &job.placeholder);
+    get_hooks_manager().hook_filter_resources(slot_set.get_platform_config(), job, &mut available_resources);
+
+    slot_set
+        .get_platform_config()
+        .resource_set
+        .hierarchy
+        .request_with_exclusion(&available_resources, &moldable.requests, &moldable.excluded_resources)
+}
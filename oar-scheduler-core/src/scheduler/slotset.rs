@@ -1,10 +1,15 @@
 use crate::model::job::{Job, Moldable, PlaceholderType, ProcSet, ProcSetCoresOp, TimeSharingType};
-use crate::platform::PlatformConfig;
+use crate::platform::{proc_set_to_oar_string, PlatformConfig};
 use crate::scheduler::slot::Slot;
+use crate::scheduler::trace::TraceStep;
 use auto_bench_fct::auto_bench_fct_hy;
-use prettytable::{format, row, Table};
-use std::collections::HashMap;
+use indexmap::IndexMap;
+use log::{info, warn};
+use lru::LruCache;
+use prettytable::{cell, format, row, Table};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fmt::{Debug, Formatter};
+use std::num::NonZeroUsize;
 use std::rc::Rc;
 
 /// A SlotSet is a collection of Slots ordered by time.
@@ -20,11 +25,32 @@ pub struct SlotSet {
     last_id: i32,  // id of the last slot in the list
     next_id: i32,  // next available id
     slots: HashMap<i32, Slot>,
-    /// Stores a slot id for a given moldable cache key, allowing to start again at this slot if multiple moldable have the same cache key, i.e., are identical.
-    cache: HashMap<Box<str>, i32>,
+    /// Id of the slot beginning at each slot begin time, for [`Self::slot_at`] lookups in O(log n). Updated by [`Self::try_split_at`].
+    begin_index: BTreeMap<i64, i32>,
+    /// Stores a slot id and its begin time for a given moldable cache key,
+    /// allowing to start again at this slot if multiple moldable have the same cache key, i.e., are identical.
+    /// Bounded by `scheduler_cache_capacity`, the least recently used entries being evicted first.
+    cache: LruCache<Box<str>, (i32, i64)>,
+    /// Number of cache entries returned by [`Self::get_cache_first_slot`] since the last [`Self::clear_cache`].
+    cache_hits: usize,
+    /// Fingerprint of the moldable that first used each cache key, see [`Moldable::fingerprint`].
+    /// Only kept in debug builds, to panic if two different moldables share a cache key.
+    #[cfg(debug_assertions)]
+    cache_fingerprints: HashMap<Box<str>, String>,
+    /// Earliest begin time of the jobs placed in the slot set, when the slot set begins before, see [`Self::from_platform_config`].
+    jobs_min_begin: Option<i64>,
+    /// Windows tried for each job since the last [`Self::take_job_traces`], only recorded with `scheduler_job_traces`.
+    job_traces: IndexMap<i64, Vec<TraceStep>>,
     platform_config: Rc<PlatformConfig>,
 }
 
+/// Number of slots walked by [`SlotSet::slot_at`] from its starting slot before looking up the begin index.
+const SLOT_AT_LINEAR_WALK_LIMIT: usize = 8;
+
+fn new_cache(platform_config: &PlatformConfig) -> LruCache<Box<str>, (i32, i64)> {
+    LruCache::new(NonZeroUsize::new(platform_config.config.scheduler_cache_capacity).unwrap_or(NonZeroUsize::MIN))
+}
+
 impl Debug for SlotSet {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -71,6 +97,7 @@ impl SlotSet {
             }
             last_slot = next_slot;
         }
+        let begin_index = slots.values().map(|slot| (slot.begin, slot.id)).collect();
         SlotSet {
             begin: first_slot.begin,
             end: last_slot.end,
@@ -78,10 +105,71 @@ impl SlotSet {
             last_id: last_slot.id,
             next_id,
             slots,
-            cache: HashMap::new(),
+            begin_index,
+            cache: new_cache(&platform_config),
+            cache_hits: 0,
+            #[cfg(debug_assertions)]
+            cache_fingerprints: HashMap::new(),
+            jobs_min_begin: None,
+            job_traces: IndexMap::new(),
             platform_config,
         }
     }
+    /// Panics if the slot set is inconsistent, i.e., if walking the doubly linked list from the first slot does not give
+    /// contiguous slots with matching `prev`/`next` ids, ending at the last slot, covering `[begin, end]`, and going through all the slots.
+    /// Only available in debug builds and tests, to catch regressions of the splitting code early, e.g., in tests after splitting slots.
+    #[cfg(any(test, debug_assertions))]
+    pub fn assert_consistent(&self) {
+        let mut slot = self
+            .slots
+            .get(&self.first_id)
+            .unwrap_or_else(|| panic!("SlotSet::assert_consistent: first slot of id {} not found.", self.first_id));
+        assert_eq!(slot.prev, None, "SlotSet::assert_consistent: first slot of id {} has a prev slot.", slot.id);
+        assert_eq!(slot.begin, self.begin, "SlotSet::assert_consistent: first slot of id {} does not begin with the slot set.", slot.id);
+        let mut walk_length = 1;
+        while let Some(next_slot_id) = slot.next {
+            let next_slot = self
+                .slots
+                .get(&next_slot_id)
+                .unwrap_or_else(|| panic!("SlotSet::assert_consistent: next slot of id {} of slot {} not found.", next_slot_id, slot.id));
+            assert_eq!(
+                next_slot.id, next_slot_id,
+                "SlotSet::assert_consistent: the key {} is associated with the slot of id {}.",
+                next_slot_id, next_slot.id
+            );
+            assert_eq!(
+                next_slot.prev,
+                Some(slot.id),
+                "SlotSet::assert_consistent: slot {} has a next slot {}, but this next slot has a prev slot {:?}.",
+                slot.id,
+                next_slot_id,
+                next_slot.prev
+            );
+            assert_eq!(
+                next_slot.begin,
+                slot.end + 1,
+                "SlotSet::assert_consistent: slots {} and {} are not contiguous.",
+                slot.id,
+                next_slot_id
+            );
+            assert!(next_slot.id < self.next_id, "SlotSet::assert_consistent: slot id {} is not below the next id {}.", next_slot.id, self.next_id);
+            walk_length += 1;
+            slot = next_slot;
+        }
+        assert_eq!(slot.id, self.last_id, "SlotSet::assert_consistent: the walk ends at slot {} instead of the last slot {}.", slot.id, self.last_id);
+        assert_eq!(slot.end, self.end, "SlotSet::assert_consistent: last slot of id {} does not end with the slot set.", slot.id);
+        assert_eq!(walk_length, self.slots.len(), "SlotSet::assert_consistent: some slots are not reachable from the first slot.");
+        assert_eq!(self.begin_index.len(), self.slots.len(), "SlotSet::assert_consistent: the begin index does not have one entry per slot.");
+        for (begin, slot_id) in &self.begin_index {
+            assert_eq!(
+                self.slots.get(slot_id).map(|slot| slot.begin),
+                Some(*begin),
+                "SlotSet::assert_consistent: the begin index maps {} to the slot {} not beginning at this time.",
+                begin,
+                slot_id
+            );
+        }
+    }
     /// Create a `SlotSet` with a single slot.
     pub fn from_slot(slot: Slot) -> SlotSet {
         SlotSet {
@@ -91,31 +179,106 @@ impl SlotSet {
             first_id: slot.id,
             last_id: slot.id,
             next_id: slot.id + 1,
+            cache: new_cache(&slot.platform_config),
+            cache_hits: 0,
+            #[cfg(debug_assertions)]
+            cache_fingerprints: HashMap::new(),
+            jobs_min_begin: None,
+            job_traces: IndexMap::new(),
+            begin_index: BTreeMap::from([(slot.begin, slot.id)]),
             slots: HashMap::from([(slot.id, slot)]),
-            cache: HashMap::new(),
         }
     }
     /// Create a `SlotSet` with slots covering the entire range from `begin` to `end` with a `ProcSet = platform_config.resource_set.default_intervals`.
+    /// The slots are splitted after each time of `platform_config.resource_set.available_upto`, the corresponding resources being removed
+    /// from the following slots, and before each time of `platform_config.resource_set.available_from`, the corresponding resources being removed
+    /// from the preceding slots.
     /// The procset will be splitted into multiple slots according to the temporal quotas defined in the `platform_config`.
+    /// If `scheduler_slotset_begin_rounding` is set, `begin` is rounded down to a multiple of it, but jobs are not placed before `begin`.
     pub fn from_platform_config(platform_config: Rc<PlatformConfig>, begin: i64, end: i64) -> SlotSet {
+        let rounding = platform_config.config.scheduler_slotset_begin_rounding;
+        let (begin, jobs_min_begin) = if rounding > 0 && begin.rem_euclid(rounding) != 0 {
+            (begin - begin.rem_euclid(rounding), Some(begin))
+        } else {
+            (begin, None)
+        };
         let proc_set = platform_config.resource_set.default_resources.clone();
         let slot = Slot::new(Rc::clone(&platform_config), 1, None, None, begin, end, proc_set, None);
         let mut slotset = SlotSet::from_slot(slot);
+        slotset.jobs_min_begin = jobs_min_begin;
+        slotset.integrate_resource_availability(&platform_config.resource_set.available_upto, &platform_config.resource_set.available_from);
         if let Some(calendar) = &platform_config.quotas_config.calendar {
             calendar.split_slotset_for_temporal_quotas(&mut slotset);
+            if !calendar.window_covers(begin, end) {
+                info!(
+                    "Periodical quotas are only applied until {}, before the end of the slot set at {} (quotas window time limit of {}s)",
+                    begin + calendar.quotas_window_time_limit(),
+                    end,
+                    calendar.quotas_window_time_limit()
+                );
+            }
         }
         slotset
     }
 
+    /// Removes the resources of `available_upto` from the slots beginning after their availability time,
+    /// splitting the slot containing `time + 1` for each `(time, proc_set)` entry.
+    /// Removes the resources of `available_from` from the slots ending before their availability time,
+    /// splitting the slot containing `time` for each `(time, proc_set)` entry.
+    fn integrate_resource_availability(&mut self, available_upto: &[(i64, ProcSet)], available_from: &[(i64, ProcSet)]) {
+        let end = self.end;
+        for (time, proc_set) in available_upto.iter().filter(|(time, _)| *time < end) {
+            let first_unavailable_id = if *time < self.begin {
+                self.first_id
+            } else {
+                let slot = self.slot_at(time + 1, None).unwrap();
+                if slot.begin == time + 1 {
+                    slot.id
+                } else {
+                    // Without splitting, the resources are also removed before their availability time
+                    let slot_id = slot.id;
+                    self.try_split_at(slot_id, time + 1, false).map_or(slot_id, |(new_slot_id, _)| new_slot_id)
+                }
+            };
+            let slot_ids = self.iter().start_at(first_unavailable_id).map(|slot| slot.id).collect::<Vec<i32>>();
+            for slot_id in slot_ids {
+                self.slots.get_mut(&slot_id).unwrap().sub_proc_set(proc_set);
+            }
+        }
+        let begin = self.begin;
+        for (time, proc_set) in available_from.iter().filter(|(time, _)| *time > begin) {
+            if *time <= end {
+                let slot = self.slot_at(*time, None).unwrap();
+                if slot.begin != *time {
+                    let slot_id = slot.id;
+                    // Without splitting, the resources are also removed after their availability time
+                    let _ = self.try_split_at(slot_id, *time, false);
+                }
+            }
+            let slot_ids = self.iter().take_while(|slot| slot.begin < *time).map(|slot| slot.id).collect::<Vec<i32>>();
+            for slot_id in slot_ids {
+                self.slots.get_mut(&slot_id).unwrap().sub_proc_set(proc_set);
+            }
+        }
+    }
+
     pub fn get_platform_config(&self) -> &Rc<PlatformConfig> {
         &self.platform_config
     }
 
     /// Builds a `Table` for displaying the slots in a human-readable format.
     pub fn to_table(&self) -> Table {
+        self.build_table(None)
+    }
+    /// Same as [`Self::to_table`], with an additional column displaying the slots’ proc sets as partitions of the hierarchy level `label`.
+    /// See [`crate::scheduler::hierarchy::Hierarchy::format_proc_set_as_labels`].
+    pub fn to_table_with_label(&self, label: &str) -> Table {
+        self.build_table(Some(label))
+    }
+    fn build_table(&self, label: Option<&str>) -> Table {
         let mut table = Table::new();
         table.set_format(*format::consts::FORMAT_CLEAN);
-        table.add_row(row![
+        let mut titles = row![
             buFc->"Id",
             buFc->"Prev",
             buFc->"Next",
@@ -125,10 +288,14 @@ impl SlotSet {
             buFc->"ProcSet",
             buFc->"Quotas r_id"
             //buFc->"Placeholders ProcSets"
-        ]);
+        ];
+        if let Some(label) = label {
+            titles.add_cell(cell!(buFc->label));
+        }
+        table.add_row(titles);
         let mut slot = self.first_slot();
         while let Some(s) = slot {
-            table.add_row(row![
+            let mut row = row![
                 s.id,
                 s.prev.map(|prev| format!("Some({})", prev)).unwrap_or("None".to_string()),
                 s.next.map(|next| format!("Some({})", next)).unwrap_or("None".to_string()),
@@ -138,7 +305,11 @@ impl SlotSet {
                 s.proc_set,
                 s.quotas.rules_id(),
                 //s.placeholder_proc_set,
-            ]);
+            ];
+            if let Some(label) = label {
+                row.add_cell(cell!(self.platform_config.resource_set.hierarchy.format_proc_set_as_labels(&s.proc_set, label)));
+            }
+            table.add_row(row);
 
             slot = if let Some(next_id) = s.next { self.slots.get(&next_id) } else { None };
         }
@@ -166,11 +337,71 @@ impl SlotSet {
 
     /// If there is a cache hit with this moldable, returns the slot id of the last slot iterated over for this cache key.
     /// If there is no cache hit, returns None.
-    pub fn get_cache_first_slot(&self, moldable: &Moldable) -> Option<i32> {
-        self.cache.get(&moldable.cache_key).cloned()
+    /// If the cached slot has since been split with a new slot inserted before it (see [`Self::split_at`]),
+    /// it no longer begins at the cached time, and the slot containing the cached time is returned instead.
+    /// In debug builds, panics if another moldable with the same cache key but a different fingerprint has been looked up since
+    /// the last [`Self::clear_cache`], as this would reuse a slot start computed for a different moldable.
+    pub fn get_cache_first_slot(&mut self, moldable: &Moldable) -> Option<i32> {
+        #[cfg(debug_assertions)]
+        self.assert_cache_key_unique(moldable);
+        let (slot_id, begin) = *self.cache.get(&moldable.cache_key)?;
+        match self.slots.get(&slot_id) {
+            Some(slot) if slot.begin == begin => {
+                self.cache_hits += 1;
+                Some(slot_id)
+            }
+            Some(slot) if slot.begin > begin => {
+                let mut slot = slot;
+                while slot.begin > begin {
+                    slot = self.slots.get(&slot.prev?)?;
+                }
+                self.cache.put(moldable.cache_key.clone(), (slot.id, begin));
+                self.cache_hits += 1;
+                Some(slot.id)
+            }
+            _ => {
+                self.cache.pop(&moldable.cache_key);
+                None
+            }
+        }
     }
     pub fn insert_cache_entry(&mut self, key: Box<str>, slot_id: i32) {
-        self.cache.insert(key, slot_id);
+        if let Some(begin) = self.slots.get(&slot_id).map(|slot| slot.begin) {
+            self.cache.put(key, (slot_id, begin));
+        }
+    }
+    /// Removes all the cache entries. Called between scheduling cycles, as the cache is only valid while resources are only removed from the slots.
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+        self.cache_hits = 0;
+        #[cfg(debug_assertions)]
+        self.cache_fingerprints.clear();
+    }
+    #[cfg(debug_assertions)]
+    fn assert_cache_key_unique(&mut self, moldable: &Moldable) {
+        let fingerprint = moldable.fingerprint();
+        let known = self.cache_fingerprints.entry(moldable.cache_key.clone()).or_insert_with(|| fingerprint.clone());
+        assert_eq!(
+            *known, fingerprint,
+            "SlotSet::get_cache_first_slot: cache key '{}' is shared by two different moldables.",
+            moldable.cache_key
+        );
+    }
+    /// Returns the number of cache hits since the last [`Self::clear_cache`].
+    pub fn cache_hits(&self) -> usize {
+        self.cache_hits
+    }
+    /// Returns the number of entries in the cache.
+    pub fn cache_len(&self) -> usize {
+        self.cache.len()
+    }
+    /// Appends `steps` to the trace of the job `job_id`.
+    pub fn record_trace_steps(&mut self, job_id: i64, steps: Vec<TraceStep>) {
+        self.job_traces.entry(job_id).or_default().extend(steps);
+    }
+    /// Returns the traces recorded by [`Self::record_trace_steps`] by job id, and clears them.
+    pub fn take_job_traces(&mut self) -> IndexMap<i64, Vec<TraceStep>> {
+        std::mem::take(&mut self.job_traces)
     }
 
     /// Returns the id of the slot from [`Self::slot_at`].
@@ -179,22 +410,49 @@ impl SlotSet {
         self.slot_at(time, starting_id).map(|slot| slot.id)
     }
     /// Returns the slot containing the given time, or None if no such slot exists.
+    /// If `starting_id` is set, only this slot and the following ones are considered: the slots following it are walked first,
+    /// as the searched slot is usually close, before looking up the begin index in O(log n).
     pub fn slot_at(&self, time: i64, starting_id: Option<i32>) -> Option<&Slot> {
-        let mut slot = if let Some(starting_id) = starting_id {
-            self.slots.get(&starting_id)
-        } else {
-            self.first_slot()
-        };
-        while let Some(s) = slot {
-            if time < s.begin {
-                return None;
-            }
-            if time <= s.end {
-                return Some(s);
+        if let Some(starting_id) = starting_id {
+            let mut slot = self.slots.get(&starting_id);
+            for _ in 0..SLOT_AT_LINEAR_WALK_LIMIT {
+                let s = slot?;
+                if time < s.begin {
+                    return None;
+                }
+                if time <= s.end {
+                    return Some(s);
+                }
+                slot = s.next.and_then(|next_id| self.slots.get(&next_id));
             }
-            slot = if let Some(next_id) = s.next { self.slots.get(&next_id) } else { None };
+            slot?;
         }
-        None
+        let (_begin, slot_id) = self.begin_index.range(..=time).next_back()?;
+        self.slots.get(slot_id).filter(|slot| time <= slot.end)
+    }
+    /// Returns the resources available at the given time, i.e., the proc set of the slot containing `time`,
+    /// or None if `time` is outside the SlotSet.
+    pub fn available_resources_at(&self, time: i64) -> Option<&ProcSet> {
+        self.slot_at(time, None).map(|slot| slot.proc_set())
+    }
+    /// Returns the number of cores available at the given time, or 0 if `time` is outside the SlotSet.
+    pub fn free_core_count_at(&self, time: i64) -> u32 {
+        self.available_resources_at(time).map_or(0, |proc_set| proc_set.core_count())
+    }
+    /// Returns the integral of the free cores over the time range `[begin, end]`, in core-seconds,
+    /// i.e., the sum of the free core count of each slot times its width within the range.
+    /// The parts of the range outside the SlotSet are not counted.
+    pub fn free_resource_seconds(&self, begin: i64, end: i64) -> i64 {
+        self.slots_overlapping(begin, end)
+            .map(|(slot, begin, end)| slot.proc_set().core_count() as i64 * (end - begin + 1))
+            .sum()
+    }
+    /// Returns the integral of the busy default resources over the time range `[begin, end]`, in core-seconds,
+    /// i.e., the default resources core count times the width of the range within the SlotSet, minus [`Self::free_resource_seconds`].
+    pub fn busy_resource_seconds(&self, begin: i64, end: i64) -> i64 {
+        let total_cores = self.platform_config.resource_set.default_resources.core_count() as i64;
+        let width = self.slots_overlapping(begin, end).map(|(_slot, begin, end)| end - begin + 1).sum::<i64>();
+        total_cores * width - self.free_resource_seconds(begin, end)
     }
     /// Returns an iterator over the slots in the SlotSet, starting from the first slot and going to the last slot.
     /// It is a double-ended iterator, so you can also iterate backwards.
@@ -244,26 +502,39 @@ impl SlotSet {
     ///           |0                  10|       |0   time-1|time    10|
     /// ```
     /// If trying to split with `time-1` and `time` already in two different slots, it will panic (i.e., splitting with time = the beginning of a slot).
-    /// Returns the two slots, starting with the new one.
+    /// Returns the two slots, starting with the new one. See [`Self::try_split_at`] for a non-panicking version.
     pub(crate) fn split_at(&mut self, slot_id: i32, time: i64, before: bool) -> (i32, i32) {
+        self.try_split_at(slot_id, time, before).unwrap_or_else(|e| panic!("SlotSet::split_at: {}", e))
+    }
+    /// Same as [`Self::split_at`], but returns an error instead of panicking if the slot does not exist,
+    /// if `time` is not in `]slot.begin, slot.end]`, e.g., when splitting at untrusted reservation times,
+    /// or if the SlotSet already has `scheduler_max_slots` slots.
+    /// The SlotSet is left unchanged on error.
+    pub fn try_split_at(&mut self, slot_id: i32, time: i64, before: bool) -> Result<(i32, i32), SplitError> {
         // Sanity checks
-        let slot = self
-            .slots
-            .get_mut(&slot_id)
-            .expect(format!("SlotSet::split_at_before: slot of id {} not found", slot_id).as_str());
-        assert!(
-            time > slot.begin && time <= slot.end,
-            "SlotSet::split_at_before: split time {} not in the slot time range: must be > {} and <={}",
-            time,
-            slot.begin,
-            slot.end
-        );
+        let max_slots = self.platform_config.config.scheduler_max_slots;
+        let slot_count = self.slots.len();
+        let slot = self.slots.get_mut(&slot_id).ok_or(SplitError::SlotNotFound(slot_id))?;
+        if time <= slot.begin || time > slot.end {
+            return Err(SplitError::TimeOutOfSlot {
+                slot_id,
+                time,
+                begin: slot.begin,
+                end: slot.end,
+            });
+        }
+        if max_slots > 0 && slot_count >= max_slots {
+            return Err(SplitError::MaxSlotsReached(max_slots));
+        }
         let new_begin = time;
 
         // Create new slot
         let new_slot_id = self.next_id;
         let new_slot = if before {
-            let new_slot = slot.duplicate(new_slot_id, slot.prev, Some(slot.id), slot.begin, new_begin - 1);
+            let mut new_slot = slot.duplicate(new_slot_id, slot.prev, Some(slot.id), slot.begin, new_begin - 1);
+            slot.quotas = new_slot.quotas.split_off(new_begin - slot.begin, slot.end - new_begin + 1);
+            self.begin_index.insert(slot.begin, new_slot_id);
+            self.begin_index.insert(new_begin, slot_id);
             // Update original slot
             slot.begin = new_begin;
             slot.prev = Some(new_slot_id);
@@ -271,7 +542,9 @@ impl SlotSet {
             self.set_prev_slot_correct_next_id(&new_slot);
             new_slot
         } else {
-            let new_slot = slot.duplicate(new_slot_id, Some(slot.id), slot.next, new_begin, slot.end);
+            let mut new_slot = slot.duplicate(new_slot_id, Some(slot.id), slot.next, new_begin, slot.end);
+            new_slot.quotas = slot.quotas.split_off(new_begin - slot.begin, slot.end - new_begin + 1);
+            self.begin_index.insert(new_begin, new_slot_id);
             // Update original slot
             slot.end = new_begin - 1;
             slot.next = Some(new_slot_id);
@@ -282,7 +555,7 @@ impl SlotSet {
 
         self.slots.insert(new_slot_id, new_slot);
         self.increment_next_id();
-        (new_slot_id, slot_id)
+        Ok((new_slot_id, slot_id))
     }
     /// Find the slot containing the given time and split it right before `time`,
     /// creating a new slot before or after the time depending on `before`. See `Self::split_at`.
@@ -315,6 +588,21 @@ impl SlotSet {
         begin_slot_opt.zip(end_slot_opt)
     }
 
+    /// Returns an iterator over the slots intersecting the range `[begin, end]`, without splitting any slot.
+    /// Each slot is returned with its time range clamped to `[begin, end]`, i.e., `(slot, max(slot.begin, begin), min(slot.end, end))`.
+    /// The iterator is empty if the range is disjoint from the SlotSet.
+    pub fn slots_overlapping(&self, begin: i64, end: i64) -> impl Iterator<Item = (&Slot, i64, i64)> {
+        let range = if begin <= end {
+            self.get_encompassing_range(begin, end, None).map(|(begin_slot, end_slot)| (begin_slot.id, end_slot.id))
+        } else {
+            None
+        };
+        range
+            .into_iter()
+            .flat_map(move |(begin_slot_id, end_slot_id)| self.iter().between(begin_slot_id, end_slot_id))
+            .map(move |slot| (slot, slot.begin.max(begin), slot.end.min(end)))
+    }
+
     /// Find the slot right before begin, and the slot right after end. Returns their ids.
     /// If start_slot_id is not None, it will be used to find faster the slot of `begin` and end by not looping through all the slots.
     /// Equivalent to calling two times [`Self::slot_id_at`], and getting the previous/next ids.
@@ -333,23 +621,50 @@ impl SlotSet {
     /// If start_slot_id is not None, it will be used to find faster the slots of the range by not looping through all the slots.
     /// Returns the first and last slot ids in which the range can fit, and then in which the job can be scheduled.
     pub fn split_slots_for_range(&mut self, begin: i64, end: i64, start_slot_id: Option<i32>) -> Option<(i32, i32)> {
+        self.try_split_slots_for_range(begin, end, start_slot_id).unwrap_or_else(|e| panic!("SlotSet::split_slots_for_range: {}", e))
+    }
+    /// Same as [`Self::split_slots_for_range`], but returns an error instead of panicking if a split is invalid (see [`Self::try_split_at`]),
+    /// e.g., if `end` is before `begin`. The slots may have been split at `begin` when the split at `end + 1` fails.
+    pub fn try_split_slots_for_range(&mut self, begin: i64, end: i64, start_slot_id: Option<i32>) -> Result<Option<(i32, i32)>, SplitError> {
         let (begin_slot, end_slot) = if let Some(slots) = self.get_encompassing_range(begin, end, start_slot_id) {
             slots
         } else {
             // Nothing to split as the [begin, end] range is disjoint from the slotset.
-            return None;
+            return Ok(None);
         };
         let begin_slot_id = begin_slot.id;
         let end_slot_id = end_slot.id;
         let end_slot_end = end_slot.end;
 
         if begin_slot.begin < begin {
-            self.split_at(begin_slot_id, begin, true);
+            self.try_split_at(begin_slot_id, begin, true)?;
         }
         if end_slot_end > end {
-            self.split_at(end_slot_id, end + 1, false);
+            self.try_split_at(end_slot_id, end + 1, false)?;
         }
-        Some((begin_slot_id, end_slot_id))
+        Ok(Some((begin_slot_id, end_slot_id)))
+    }
+    /// Removes `resources` from the slots of `begin..=end`, e.g., for a maintenance window, splitting the slots at its boundaries.
+    /// Unlike dead resources, the resources stay available before and after the downtime. The part of the window outside the slotset is ignored.
+    /// Returns an error without removing the resources if the window can't be split, e.g., if `end` is before `begin` (see [`Self::try_split_at`]).
+    pub fn apply_downtime(&mut self, resources: &ProcSet, begin: i64, end: i64) -> Result<(), SplitError> {
+        let (begin_slot_id, end_slot_id) = match self.try_split_slots_for_range(begin, end, None) {
+            Ok(Some(slots)) => slots,
+            Ok(None) => return Ok(()),
+            Err(SplitError::MaxSlotsReached(max_slots)) => {
+                warn!("Maximum number of slots ({}) reached: the downtime is applied to the whole overlapping slots", max_slots);
+                let Some((begin_slot, end_slot)) = self.get_encompassing_range(begin, end, None) else {
+                    return Ok(());
+                };
+                (begin_slot.id, end_slot.id)
+            }
+            Err(e) => return Err(e),
+        };
+        let slot_ids = self.iter().between(begin_slot_id, end_slot_id).map(|slot| slot.id).collect::<Vec<i32>>();
+        for slot_id in slot_ids {
+            self.slots.get_mut(&slot_id).unwrap().sub_proc_set(resources);
+        }
+        Ok(())
     }
     /// See [`SlotSet::split_slots_for_jobs_and_update_resources`].
     /// Returns None if the job is outside of the slotset.
@@ -360,30 +675,45 @@ impl SlotSet {
         sub_resources: bool,
         start_slot_id: Option<i32>,
     ) -> Option<(i32, i32)> {
-        let assignment = job
-            .assignment
-            .as_ref()
+        let (begin, end, proc_set) = job
+            .window()
             .expect("Job must be scheduled to split slots and update resources for it");
 
-        let (begin_slot_id, end_slot_id) = match self.split_slots_for_range(assignment.begin, assignment.end, start_slot_id) {
-            Some(slots) => slots,
-            None => {
+        let (begin_slot_id, end_slot_id) = match self.try_split_slots_for_range(begin, end, start_slot_id) {
+            Ok(Some(slots)) => slots,
+            Ok(None) => {
                 return None;
             }
+            Err(SplitError::MaxSlotsReached(max_slots)) => {
+                warn!("Maximum number of slots ({}) reached: job {} is inserted without splitting the slots", max_slots, job.id);
+                let (begin_slot, end_slot) = self.get_encompassing_range(begin, end, start_slot_id)?;
+                (begin_slot.id, end_slot.id)
+            }
+            Err(e) => panic!("SlotSet::split_slots_for_range: {}", e),
         };
+        let resources_count = self.platform_config.resource_set.weighted_core_count(proc_set) as u32;
+        let default_resources_count = self.platform_config.resource_set.weighted_default_core_count(proc_set) as u32;
         self.iter()
             .between(begin_slot_id, end_slot_id)
-            .map(|slot| slot.id)
-            .collect::<Vec<i32>>()
+            // Without splitting, resources are subtracted from the whole overlapping slots, but only added to the slots within the job.
+            .filter(|slot| sub_resources || (slot.begin >= begin && slot.end <= end))
+            .map(|slot| {
+                // The job enters a period of the temporal quotas in its first slot, or when the period changes.
+                let enters_period = slot.begin <= begin
+                    || slot.prev.and_then(|id| self.slots.get(&id)).is_none_or(|prev| !prev.quotas.is_same_period(&slot.quotas));
+                (slot.id, enters_period)
+            })
+            .collect::<Vec<(i32, bool)>>()
             .iter()
-            .for_each(|slot_id| {
+            .for_each(|(slot_id, enters_period)| {
                 let slot = self.slots.get_mut(&slot_id).unwrap();
-                let proc_set = &assignment.resources;
                 if sub_resources {
                     slot.sub_proc_set(proc_set);
                     if self.platform_config.quotas_config.enabled && !job.no_quotas && do_update_quotas {
-                        slot.quotas
-                            .increment_for_job(job, slot.end - slot.begin + 1, assignment.resources.core_count());
+                        slot.quotas.increment_for_job(job, slot.end - slot.begin + 1, resources_count, default_resources_count);
+                        if *enters_period && self.platform_config.config.quotas_period_counters {
+                            slot.quotas.increment_period_jobs_for_job(job);
+                        }
                     }
                 } else {
                     slot.add_proc_set(proc_set);
@@ -441,6 +771,42 @@ impl SlotSet {
         }
     }
 
+    /// Changes the end of a job already inserted in the SlotSet to `new_end`, e.g., to honor a walltime change request.
+    /// Extending the job requires its resources to be free in the slots between its current and new end,
+    /// otherwise an error is returned and the SlotSet is left unchanged. Shrinking the job frees its resources after `new_end`,
+    /// without decrementing the quotas counters, and clears the cache.
+    /// The job assignment is not modified: its end should be updated by the caller.
+    pub fn resize_job(&mut self, job: &Job, new_end: i64) -> Result<(), String> {
+        let (begin, end, proc_set) = job.window().ok_or_else(|| format!("Job {} is not scheduled and can't be resized", job.id))?;
+        if new_end < begin {
+            return Err(format!("Job {} can't end at {}, before its begin at {}", job.id, new_end, begin));
+        }
+        if new_end > end
+            && let Some((slot, _, _)) = self.slots_overlapping(end + 1, new_end).find(|(slot, _, _)| !proc_set.is_subset(slot.proc_set()))
+        {
+            return Err(format!(
+                "Job {} can't be extended to {}: resources {} are assigned to another job at {}",
+                job.id,
+                new_end,
+                proc_set_to_oar_string(&(proc_set - slot.proc_set())),
+                slot.begin().max(end + 1)
+            ));
+        }
+
+        let mut resized_part = job.clone();
+        let assignment = resized_part.assignment.as_mut().unwrap();
+        if new_end > end {
+            (assignment.begin, assignment.end) = (end + 1, new_end);
+            self.split_slots_for_job_and_update_resources(&resized_part, true, true, None);
+        } else if new_end < end {
+            (assignment.begin, assignment.end) = (new_end + 1, end);
+            self.split_slots_for_job_and_update_resources(&resized_part, false, false, None);
+            // The cache is only valid while resources are only removed from the slots.
+            self.clear_cache();
+        }
+        Ok(())
+    }
+
     /// Returns the intersection of all the slots’ intervals between begin_slot_id and end_slot_id (inclusive)
     /// Take into account the time-shared procsets if `ts_user_name` and `ts_job_name` are [`Some`].
     /// Take into account the placeholder procsets if ph is [`PlaceholderType::Allow`].
@@ -476,12 +842,170 @@ impl SlotSet {
     pub fn end(&self) -> i64 {
         self.end
     }
+    /// Returns the earliest begin time of the jobs placed in the slot set, if it begins before, e.g., when its begin is rounded down.
+    pub fn jobs_min_begin(&self) -> Option<i64> {
+        self.jobs_min_begin
+    }
     /// Returns the number of slots in the SlotSet.
     pub fn slot_count(&self) -> usize {
         self.slots.len()
     }
+
+    /// Captures the slots, the ids and the cache of the SlotSet, so that it can be rolled back with [`Self::restore`],
+    /// e.g., after trying a speculative assignment.
+    pub fn snapshot(&self) -> SlotSetSnapshot {
+        SlotSetSnapshot(self.clone())
+    }
+    /// Rolls the SlotSet back to the state captured by [`Self::snapshot`].
+    pub fn restore(&mut self, snapshot: SlotSetSnapshot) {
+        *self = snapshot.0;
+    }
+
+    /// Returns a standalone copy of the SlotSet covering only `[begin, end]`, clamped to the SlotSet range,
+    /// e.g., to try a speculative assignment on a small copy instead of cloning the whole SlotSet.
+    /// The overlapping slots are cloned with their ids, the first and last ones being shortened to `begin` and `end`
+    /// (their quotas counters being split as when splitting a slot, see [`Quotas::split_off`]). The cache is not copied.
+    /// Returns `None` if `[begin, end]` does not overlap the SlotSet.
+    ///
+    /// [`Quotas::split_off`]: crate::scheduler::quotas::Quotas::split_off
+    pub fn clone_range(&self, begin: i64, end: i64) -> Option<SlotSet> {
+        let (begin, end) = (begin.max(self.begin), end.min(self.end));
+        if begin > end {
+            return None;
+        }
+        let (first_slot, last_slot) = self.get_encompassing_range(begin, end, None)?;
+        let (first_id, last_id) = (first_slot.id, last_slot.id);
+        let mut slots = HashMap::new();
+        for slot in self.iter().between(first_id, last_id) {
+            let mut slot = slot.clone();
+            if slot.id == first_id {
+                if slot.begin < begin {
+                    slot.quotas = slot.quotas.split_off(begin - slot.begin, slot.end - begin + 1);
+                }
+                slot.prev = None;
+                slot.begin = begin;
+            }
+            if slot.id == last_id {
+                if slot.end > end {
+                    slot.quotas.split_off(end - slot.begin + 1, slot.end - end);
+                }
+                slot.next = None;
+                slot.end = end;
+            }
+            slots.insert(slot.id, slot);
+        }
+        let mut slot_set = SlotSet::from_map(Rc::clone(&self.platform_config), slots, first_id);
+        // Keeping the ids of the parent, so that the new slots never reuse the id of a parent slot
+        slot_set.next_id = self.next_id;
+        slot_set.jobs_min_begin = self.jobs_min_begin.filter(|jobs_min_begin| *jobs_min_begin > begin);
+        Some(slot_set)
+    }
+
+    /// Reports where `self` and `other` disagree, e.g., to compare the schedules of two schedulers:
+    /// the time ranges they cover, the slot boundaries only one of them has, and the time ranges where their resources differ.
+    /// Only the time range covered by both slot sets is compared. The differences are ordered by time.
+    pub fn diff(&self, other: &SlotSet) -> Vec<SlotDiff> {
+        let mut diffs = Vec::new();
+        if (self.begin, self.end) != (other.begin, other.end) {
+            diffs.push(SlotDiff::Range { self_range: (self.begin, self.end), other_range: (other.begin, other.end) });
+        }
+        let (begin, end) = (self.begin.max(other.begin), self.end.min(other.end));
+        if begin > end {
+            return diffs;
+        }
+        let boundaries = |slot_set: &SlotSet| {
+            slot_set.iter().map(|slot| slot.begin).filter(|time| *time > begin && *time <= end).collect::<BTreeSet<i64>>()
+        };
+        let (self_boundaries, other_boundaries) = (boundaries(self), boundaries(other));
+        let times = std::iter::once(begin).chain(self_boundaries.union(&other_boundaries).copied()).collect::<Vec<i64>>();
+
+        let (mut self_slot_id, mut other_slot_id) = (None, None);
+        for (i, &time) in times.iter().enumerate() {
+            if i > 0 && self_boundaries.contains(&time) != other_boundaries.contains(&time) {
+                diffs.push(SlotDiff::Boundary { time, in_self: self_boundaries.contains(&time) });
+            }
+            let self_slot = self.slot_at(time, self_slot_id).unwrap();
+            let other_slot = other.slot_at(time, other_slot_id).unwrap();
+            (self_slot_id, other_slot_id) = (Some(self_slot.id), Some(other_slot.id));
+            if self_slot.proc_set == other_slot.proc_set {
+                continue;
+            }
+            let range_end = times.get(i + 1).map_or(end, |next| next - 1);
+            let only_self = &self_slot.proc_set - &other_slot.proc_set;
+            let only_other = &other_slot.proc_set - &self_slot.proc_set;
+            // Consecutive ranges with the same difference are merged, e.g., across a boundary of a single slot set
+            if let Some(SlotDiff::ProcSet { end, only_self: last_only_self, only_other: last_only_other, .. }) = diffs.last_mut()
+                && *end == time - 1
+                && *last_only_self == only_self
+                && *last_only_other == only_other
+            {
+                *end = range_end;
+                continue;
+            }
+            diffs.push(SlotDiff::ProcSet { begin: time, end: range_end, only_self, only_other });
+        }
+        diffs
+    }
+}
+
+/// Error returned by [`SlotSet::try_split_at`] when a slot can't be split.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SplitError {
+    /// No slot has this id in the SlotSet.
+    SlotNotFound(i32),
+    /// The split time is not in `]begin, end]`, e.g., it is the beginning of the slot.
+    TimeOutOfSlot { slot_id: i32, time: i64, begin: i64, end: i64 },
+    /// The SlotSet already has the maximum number of slots (`scheduler_max_slots`).
+    MaxSlotsReached(usize),
+}
+
+impl std::fmt::Display for SplitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SplitError::SlotNotFound(slot_id) => write!(f, "slot of id {} not found", slot_id),
+            SplitError::TimeOutOfSlot { slot_id, time, begin, end } => write!(
+                f,
+                "split time {} not in the time range of slot {}: must be > {} and <= {}",
+                time, slot_id, begin, end
+            ),
+            SplitError::MaxSlotsReached(max_slots) => write!(f, "maximum number of slots ({}) reached", max_slots),
+        }
+    }
+}
+
+/// Difference between two slot sets, see [`SlotSet::diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlotDiff {
+    /// The slot sets do not cover the same time range, as `(begin, end)`.
+    Range { self_range: (i64, i64), other_range: (i64, i64) },
+    /// A slot begins at `time` in only one of the slot sets: `self` if `in_self` is true, `other` otherwise.
+    Boundary { time: i64, in_self: bool },
+    /// Between `begin` and `end`, the resources `only_self` are only available in `self`, and `only_other` only in `other`.
+    ProcSet { begin: i64, end: i64, only_self: ProcSet, only_other: ProcSet },
+}
+
+impl std::fmt::Display for SlotDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SlotDiff::Range { self_range, other_range } => {
+                write!(f, "time range [{}, {}] != [{}, {}]", self_range.0, self_range.1, other_range.0, other_range.1)
+            }
+            SlotDiff::Boundary { time, in_self } => write!(f, "slot boundary at {} only in {}", time, if *in_self { "self" } else { "other" }),
+            SlotDiff::ProcSet { begin, end, only_self, only_other } => write!(
+                f,
+                "[{}, {}]: resources '{}' only in self, '{}' only in other",
+                begin,
+                end,
+                proc_set_to_oar_string(only_self),
+                proc_set_to_oar_string(only_other)
+            ),
+        }
+    }
 }
 
+/// State of a [`SlotSet`] captured by [`SlotSet::snapshot`].
+pub struct SlotSetSnapshot(SlotSet);
+
 /// double-ended iterator over Slots in a SlotSet, with the ability to iterate within a beginning and end slot id.
 #[derive(Clone)]
 pub struct SlotIterator<'a> {
@@ -539,6 +1063,23 @@ impl<'a> SlotIterator<'a> {
         self.end = Some(end_id);
         self
     }
+    /// Extends the iterator to the previous slots as long as `predicate(first_slot, previous_slot)` holds,
+    /// and to the next slots as long as `predicate(last_slot, next_slot)` holds.
+    pub fn extend_while<P: Fn(&Slot, &Slot) -> bool>(mut self, predicate: P) -> SlotIterator<'a> {
+        while let Some(slot) = self.begin.and_then(|id| self.slots.get(&id))
+            && let Some(prev) = slot.prev.and_then(|id| self.slots.get(&id))
+            && predicate(slot, prev)
+        {
+            self.begin = Some(prev.id);
+        }
+        while let Some(slot) = self.end.and_then(|id| self.slots.get(&id))
+            && let Some(next) = slot.next.and_then(|id| self.slots.get(&id))
+            && predicate(slot, next)
+        {
+            self.end = Some(next.id);
+        }
+        self
+    }
     /// Peek at the next slot without moving the iterator
     pub fn peek(&self) -> Option<&'a Slot> {
         Some(self.slots.get(&self.begin?)?)
@@ -548,11 +1089,17 @@ impl<'a> SlotIterator<'a> {
     pub fn with_width(self, min_width: i64) -> SlotWidthIterator<'a> {
         SlotWidthIterator::from_iterator(self, min_width)
     }
+    /// Create an iterator that iterates backward with a minimum slot width.
+    /// See [`SlotRevWidthIterator`].
+    pub fn rev_with_width(self, min_width: i64) -> SlotRevWidthIterator<'a> {
+        SlotRevWidthIterator::from_iterator(self, min_width)
+    }
 }
 
 /// Iterates over Slots, finding each time a following slot with a width `slot2.end - slot1.begin >= width`.
 /// It is possible to iterate over a specific range in the linked list by using the [`SlotIterator`] methods like
 /// [`SlotIterator::between`], [`SlotIterator::start_at`], and [`SlotIterator::end_at`] before calling [`SlotIterator::with_width`] or [`SlotWidthIterator::from_iterator`].
+/// See [`SlotRevWidthIterator`] for the backward scan.
 pub struct SlotWidthIterator<'a> {
     begin_iterator: SlotIterator<'a>,
     end_iterator: SlotIterator<'a>,
@@ -593,3 +1140,43 @@ impl<'a> Iterator for SlotWidthIterator<'a> {
         Some((start_slot, end_slot))
     }
 }
+
+/// Iterates backward over Slots, finding each time a preceding slot with a width `slot2.end - slot1.begin >= width`.
+/// Same as [`SlotWidthIterator`], but starting from the end slot of the [`SlotIterator`] and going toward its beginning.
+pub struct SlotRevWidthIterator<'a> {
+    begin_iterator: SlotIterator<'a>,
+    end_iterator: SlotIterator<'a>,
+    begin_slot: Option<&'a Slot>,
+    min_width: i64,
+}
+
+impl<'a> SlotRevWidthIterator<'a> {
+    /// Builds a new SlotRevWidthIterator from a SlotIterator and a minimum width.
+    pub fn from_iterator(iter: SlotIterator<'a>, min_width: i64) -> SlotRevWidthIterator<'a> {
+        SlotRevWidthIterator {
+            begin_iterator: iter.clone(),
+            end_iterator: iter,
+            begin_slot: None,
+            min_width,
+        }
+    }
+}
+
+impl<'a> Iterator for SlotRevWidthIterator<'a> {
+    type Item = (&'a Slot, &'a Slot);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let end_slot = self.end_iterator.next_back()?;
+
+        // Continue until we reach a width of at least min_width
+        let mut begin_slot = match self.begin_slot {
+            Some(slot) => slot,
+            None => self.begin_iterator.next_back()?,
+        };
+        while end_slot.end - begin_slot.begin + 1 < self.min_width {
+            begin_slot = self.begin_iterator.next_back()?;
+        }
+        self.begin_slot = Some(begin_slot);
+        Some((begin_slot, end_slot))
+    }
+}
@@ -220,17 +220,65 @@ fn multifactor_sort<P: PlatformTrait>(platform: &P, queues: &Vec<String>, waitin
     waiting_jobs.reverse(); // descending
 }
 
+/// Stable sort of the waiting jobs by descending `job.priority` (jobs without priority have a priority of 0).
+/// If `by_submission_time` is true, jobs with the same priority are sorted by submission time.
+/// Does nothing if no job has a priority, preserving the original order.
+fn priority_sort(waiting_jobs: &mut IndexMap<i64, Job>, by_submission_time: bool) {
+    if waiting_jobs.values().all(|job| job.priority.is_none()) {
+        return;
+    }
+    waiting_jobs.sort_by(|_id1, job1, _id2, job2| {
+        let ordering = job2.priority.unwrap_or(0).cmp(&job1.priority.unwrap_or(0));
+        if by_submission_time {
+            ordering.then(job1.submission_time.cmp(&job2.submission_time))
+        } else {
+            ordering
+        }
+    });
+}
+
+/// Moves the members of each job array (jobs sharing the same `array_id`) right after the first of them,
+/// keeping their relative order, so that they are scheduled consecutively.
+pub fn group_array_jobs(waiting_jobs: &mut IndexMap<i64, Job>) {
+    // Rank of each array: the position of its first member
+    let mut array_ranks: HashMap<i64, usize> = HashMap::new();
+    let ranks = waiting_jobs
+        .values()
+        .enumerate()
+        .map(|(index, job)| job.array_id.map_or(index, |array_id| *array_ranks.entry(array_id).or_insert(index)))
+        .collect::<Vec<usize>>();
+    let mut ranked_jobs = waiting_jobs.drain(..).zip(ranks).collect::<Vec<((i64, Job), usize)>>();
+    ranked_jobs.sort_by_key(|(_job, rank)| *rank);
+    waiting_jobs.extend(ranked_jobs.into_iter().map(|(job, _rank)| job));
+}
+
+/// Sorts the waiting jobs using the `hook_sort` hook if it returns true, or according to the `JOB_PRIORITY` configuration otherwise.
+/// Jobs are sorted by descending `job.priority` first, the `JOB_PRIORITY` sorting being applied to jobs with the same priority.
+/// If the `JOB_PRIORITY` is `FAIRSHARE` and a hooks handler is registered, the sorted jobs are then given to the `hook_fairshare` hook
+/// with the users recent usage (see [`PlatformTrait::get_users_usage`]), which is not computed otherwise.
 pub fn sort_jobs<P>(platform: &P, queues: &Vec<String>, waiting_jobs: &mut IndexMap<i64, Job>)
 where
     P: PlatformTrait,
 {
-    if get_hooks_manager().hook_sort(platform.get_platform_config(), queues, waiting_jobs) {
-        return;
+    if !get_hooks_manager().hook_sort(platform.get_platform_config(), queues, waiting_jobs) {
+        sort_jobs_by_priority(platform, queues, waiting_jobs);
     }
+    if matches!(platform.get_platform_config().config.job_priority, JobPriority::Fairshare) && get_hooks_manager().has_hooks_handler() {
+        let users_usage = platform.get_users_usage(queues);
+        get_hooks_manager().hook_fairshare(platform.get_platform_config(), &users_usage, waiting_jobs);
+    }
+}
 
+/// Sorts the waiting jobs according to the `JOB_PRIORITY` configuration (see [`sort_jobs`]).
+fn sort_jobs_by_priority<P>(platform: &P, queues: &Vec<String>, waiting_jobs: &mut IndexMap<i64, Job>)
+where
+    P: PlatformTrait,
+{
     match &platform.get_platform_config().config.job_priority {
         JobPriority::Fifo => {
-            // No sorting required.
+            // Jobs are already in FIFO order, only sort by priority if some jobs have one.
+            priority_sort(waiting_jobs, true);
+            return;
         },
         JobPriority::Fairshare => {
             evaluate_jobs_karma(platform, queues, waiting_jobs);
@@ -242,4 +290,5 @@ where
             multifactor_sort(platform, queues, waiting_jobs);
         },
     }
+    priority_sort(waiting_jobs, false);
 }
@@ -18,3 +18,45 @@ mod placeholder_test;
 mod quotas_parsing_test;
 #[cfg(test)]
 mod temporal_quotas_test;
+#[cfg(test)]
+mod configuration_test;
+#[cfg(test)]
+mod sorting_test;
+#[cfg(test)]
+mod preemption_test;
+#[cfg(test)]
+mod moldable_test;
+#[cfg(test)]
+mod observer_test;
+#[cfg(test)]
+mod horizon_test;
+#[cfg(all(test, feature = "parallel"))]
+mod parallel_test;
+#[cfg(test)]
+mod stats_test;
+#[cfg(test)]
+mod policy_test;
+#[cfg(test)]
+mod hooks_test;
+#[cfg(test)]
+mod backfill_test;
+#[cfg(test)]
+mod array_test;
+#[cfg(test)]
+mod proc_set_test;
+#[cfg(test)]
+mod bench_metrics_test;
+#[cfg(test)]
+mod suspend_test;
+#[cfg(test)]
+mod logging_test;
+#[cfg(test)]
+mod gantt_export_test;
+#[cfg(test)]
+mod cancellation_test;
+#[cfg(test)]
+mod running_test;
+#[cfg(test)]
+mod trace_test;
+#[cfg(test)]
+mod reservation_test;
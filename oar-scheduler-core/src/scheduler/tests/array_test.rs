@@ -0,0 +1,38 @@
+use crate::model::configuration::ArrayPolicy;
+use crate::model::job::{JobBuilder, Moldable};
+use crate::scheduler::hierarchy::{HierarchyRequest, HierarchyRequests};
+use crate::scheduler::kamelot;
+use crate::scheduler::tests::platform_mock::{generate_mock_platform_config, PlatformBenchMock};
+use indexmap::indexmap;
+
+#[test]
+fn test_array_policies() {
+    // 8 nodes: only two jobs of 4 nodes fit at the same time
+    for (policy, expected) in [
+        (ArrayPolicy::None, [(1, Some(0)), (2, Some(100)), (3, Some(100)), (4, Some(0))]),
+        (ArrayPolicy::Consecutive, [(1, Some(0)), (2, Some(0)), (3, Some(100)), (4, Some(100))]),
+        (ArrayPolicy::Gang, [(1, None), (2, None), (3, None), (4, Some(0))]),
+    ] {
+        let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+        platform_config.config.scheduler_array_policy = policy;
+        let available = platform_config.resource_set.default_resources.clone();
+        let moldable = |id: i64| {
+            Moldable::new(id, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 4)])]))
+        };
+        // Jobs 1, 2 and 3 are the members of the array 1, job 4 is submitted between them
+        let waiting_jobs = indexmap![
+            1 => JobBuilder::new(1).moldable(moldable(1)).array_id(1).build(),
+            4 => JobBuilder::new(4).moldable(moldable(4)).array_id(4).build(),
+            2 => JobBuilder::new(2).moldable(moldable(2)).array_id(1).build(),
+            3 => JobBuilder::new(3).moldable(moldable(3)).array_id(1).build(),
+        ];
+
+        let mut platform = PlatformBenchMock::new(platform_config, vec![], waiting_jobs);
+        kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+
+        let scheduled = platform.get_scheduled_jobs_ref();
+        for (id, begin) in expected {
+            assert_eq!(scheduled.iter().find(|job| job.id == id).and_then(|job| job.begin()), begin, "job {} with {:?}", id, policy);
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use crate::model::configuration::BackfillMode;
+use crate::model::job::{JobAssignment, JobBuilder, Moldable, ProcSet};
+use crate::scheduler::hierarchy::{HierarchyRequest, HierarchyRequests};
+use crate::scheduler::kamelot;
+use crate::scheduler::slotset::SlotSet;
+use crate::scheduler::tests::platform_mock::{generate_mock_platform_config, PlatformBenchMock};
+use indexmap::indexmap;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+#[test]
+fn test_backfill_fills_gap() {
+    for (mode, expected_small_job) in [
+        (BackfillMode::None, None),
+        (BackfillMode::Conservative, Some((10, 19))),
+        // The small job is the head job of the EASY backfilling: it gets a reservation
+        (BackfillMode::Easy, Some((10, 19))),
+    ] {
+        let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+        platform_config.config.scheduler_backfill_mode = mode;
+        let available = platform_config.resource_set.default_resources.clone();
+        let moldable = |id: i64, walltime: i64, nodes: u32| {
+            Moldable::new(id, walltime, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), nodes)])]))
+        };
+
+        // Running job on nodes 5 to 8 until 49: the large job can't begin before 50, leaving nodes 1 to 4 free until then
+        let running_job = JobBuilder::new(10).assign(JobAssignment::new(0, 49, ProcSet::from_iter([129..=256]), 0)).build();
+        // The small job 1 depends on job 2, sorted after it: it is left unscheduled by the main pass
+        let small_job = JobBuilder::new(1).moldable(moldable(1, 10, 1)).add_valid_dependency(2).build();
+        let dependency_job = JobBuilder::new(2).moldable(moldable(2, 10, 1)).build();
+        let large_job = JobBuilder::new(3).moldable(moldable(3, 100, 8)).build();
+        let waiting_jobs = indexmap![1 => small_job, 2 => dependency_job, 3 => large_job];
+
+        let mut platform = PlatformBenchMock::new(platform_config, vec![running_job], waiting_jobs);
+        kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+
+        let scheduled = platform.get_scheduled_jobs_ref();
+        let window = |id: i64| scheduled.iter().find(|job| job.id == id).map(|job| (job.begin().unwrap(), job.end().unwrap()));
+        assert_eq!(window(2), Some((0, 9)), "{:?}", mode);
+        assert_eq!(window(3), Some((50, 149)), "{:?}", mode);
+        assert_eq!(window(1), expected_small_job, "{:?}", mode);
+    }
+}
+
+#[test]
+fn test_easy_backfill_head_reservation() {
+    for (mode, expected) in [
+        (BackfillMode::Conservative, [Some((50, 59)), Some((60, 119)), Some((0, 49)), Some((0, 19))]),
+        // Only the head job 1 gets a reservation, job 2 can't begin now without delaying it
+        (BackfillMode::Easy, [Some((50, 59)), None, Some((0, 49)), Some((0, 19))]),
+    ] {
+        let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+        platform_config.config.scheduler_backfill_mode = mode;
+        let platform_config = Rc::new(platform_config);
+        let available = platform_config.resource_set.default_resources.clone();
+        let moldable = |id: i64, walltime: i64, nodes: u32| {
+            let request = HierarchyRequest::new(available.clone(), vec![("nodes".into(), nodes)]);
+            Moldable::new(id, walltime, HierarchyRequests::from_requests(vec![request]))
+        };
+
+        // Running job on nodes 5 to 8 until 49
+        let running_job = JobBuilder::new(10).assign(JobAssignment::new(0, 49, ProcSet::from_iter([129..=256]), 0)).build();
+        let mut slot_set = SlotSet::from_platform_config(Rc::clone(&platform_config), 0, 1000);
+        slot_set.split_slots_for_job_and_update_resources(&running_job, true, true, None);
+        let mut slot_sets = HashMap::from([("default".into(), slot_set)]);
+
+        // Jobs left unscheduled by the main pass, in priority order
+        let mut waiting_jobs = indexmap![
+            1 => JobBuilder::new(1).moldable(moldable(1, 10, 8)).build(),
+            2 => JobBuilder::new(2).moldable(moldable(2, 60, 4)).build(),
+            3 => JobBuilder::new(3).moldable(moldable(3, 50, 2)).build(),
+            4 => JobBuilder::new(4).moldable(moldable(4, 20, 2)).build(),
+        ];
+        let count = kamelot::backfill_jobs(&mut slot_sets, &mut waiting_jobs, &HashSet::new(), &HashMap::new(), mode, 0);
+
+        let windows = waiting_jobs.values().map(|job| job.begin().zip(job.end())).collect::<Vec<_>>();
+        assert_eq!(windows, expected, "{:?}", mode);
+        assert_eq!(count, expected.iter().flatten().count(), "{:?}", mode);
+    }
+}
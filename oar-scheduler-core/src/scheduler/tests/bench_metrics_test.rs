@@ -0,0 +1,32 @@
+use crate::auto_bench_fct::metrics_to_json;
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::Duration;
+
+// The metrics are formatted from local maps: the global metrics are updated by the other tests running concurrently.
+#[test]
+fn test_metrics_to_json() {
+    let functions = HashMap::from([
+        (("find_slots".to_string(), 1), (3, Duration::from_micros(30))),
+        (("schedule_job".to_string(), 0), (2, Duration::from_micros(50))),
+    ]);
+    let stacks = HashMap::from([
+        (vec![0], HashMap::from([(("find_slots".to_string(), 1), (3, Duration::from_micros(30)))])),
+        (vec![], HashMap::from([(("schedule_job".to_string(), 0), (2, Duration::from_micros(50)))])),
+    ]);
+
+    assert_eq!(
+        metrics_to_json(functions, stacks),
+        json!({
+            "functions": [
+                { "name": "find_slots", "index": 1, "calls": 3, "duration_ns": 30_000 },
+                { "name": "schedule_job", "index": 0, "calls": 2, "duration_ns": 50_000 },
+            ],
+            "hierarchy": [
+                { "name": "schedule_job", "index": 0, "calls": 2, "duration_ns": 50_000, "stack": [] },
+                { "name": "find_slots", "index": 1, "calls": 3, "duration_ns": 30_000, "stack": [0] },
+            ],
+        })
+    );
+    assert_eq!(metrics_to_json(HashMap::new(), HashMap::new()), json!({ "functions": [], "hierarchy": [] }));
+}
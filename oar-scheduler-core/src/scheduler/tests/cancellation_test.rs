@@ -0,0 +1,73 @@
+use crate::hooks::{set_hooks_handler, HooksHandler};
+use crate::model::job::{Job, JobBuilder, Moldable, ProcSet};
+use crate::platform::PlatformConfig;
+use crate::scheduler::hierarchy::HierarchyRequests;
+use crate::scheduler::kamelot::{self, CancellationToken};
+use crate::scheduler::slotset::SlotSet;
+use crate::scheduler::tests::platform_mock::{generate_mock_platform_config, PlatformBenchMock};
+use indexmap::IndexMap;
+use std::cell::Cell;
+
+/// Hooks handler cancelling the token when the job number `cancel_at` is about to be scheduled, e.g., on a shutdown signal.
+struct CancellingHooks {
+    token: CancellationToken,
+    cancel_at: usize,
+    assigned: Cell<usize>,
+}
+impl HooksHandler for CancellingHooks {
+    fn hook_sort(&self, _platform_config: &PlatformConfig, _queues: &Vec<String>, _waiting_jobs: &mut IndexMap<i64, Job>) -> bool {
+        false
+    }
+    fn hook_assign(&self, _slot_set: &mut SlotSet, _job: &mut Job, _min_begin: Option<i64>) -> bool {
+        self.assigned.set(self.assigned.get() + 1);
+        if self.assigned.get() == self.cancel_at {
+            self.token.cancel();
+        }
+        false
+    }
+    fn hook_find(&self, _slot_set: &SlotSet, _job: &Job, _moldable: &Moldable, _min_begin: Option<i64>, _available: ProcSet) -> Option<Option<ProcSet>> {
+        None
+    }
+    fn hook_filter_resources(&self, _platform_config: &PlatformConfig, _job: &Job, _available: &mut ProcSet) -> bool {
+        false
+    }
+}
+
+fn waiting_jobs(platform_config: &PlatformConfig, count: i64) -> IndexMap<i64, Job> {
+    let available = platform_config.resource_set.default_resources.clone();
+    (1..=count)
+        .map(|id| {
+            let requests = HierarchyRequests::new_single(available.clone(), vec![("nodes".into(), 1)]);
+            (id, JobBuilder::new(id).moldable(Moldable::new(id, 100, requests)).build())
+        })
+        .collect()
+}
+
+#[test]
+fn test_cancelled_cycle_keeps_first_assignments() {
+    // The hooks handler is thread local, and each test runs in its own thread.
+    let token = CancellationToken::new();
+    set_hooks_handler(CancellingHooks { token: token.clone(), cancel_at: 3, assigned: Cell::new(0) });
+
+    let platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    let waiting_jobs = waiting_jobs(&platform_config, 10);
+    let mut platform = PlatformBenchMock::new(platform_config, vec![], waiting_jobs).with_cancellation_token(token.clone());
+    let stats = kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+
+    // The token is cancelled while scheduling the third job, which is the last one scheduled
+    assert!(token.is_cancelled());
+    assert_eq!((stats.scheduled, stats.rejected), (3, 7));
+    assert_eq!(platform.get_scheduled_jobs_ref().iter().map(|job| job.id).collect::<Vec<i64>>(), vec![1, 2, 3]);
+}
+
+#[test]
+fn test_cancelled_token_schedules_nothing() {
+    let token = CancellationToken::new();
+    token.cancel();
+    let platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    let waiting_jobs = waiting_jobs(&platform_config, 4);
+    let mut platform = PlatformBenchMock::new(platform_config, vec![], waiting_jobs).with_cancellation_token(token);
+    let stats = kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+    assert_eq!((stats.scheduled, stats.rejected), (0, 4));
+    assert!(platform.get_scheduled_jobs_ref().is_empty());
+}
@@ -0,0 +1,71 @@
+use crate::model::configuration::{Configuration, JobPriority};
+
+#[test]
+fn test_load_from_str() {
+    let config = Configuration::load_from_str(
+        r#"
+SCHEDULER_JOB_SECURITY_TIME="30"
+HIERARCHY_LABELS="resource_id,network_address,cpu,core"
+SCHEDULER_RESOURCE_ORDER="resource_id ASC"
+SCHEDULER_AVAILABLE_SUSPENDED_RESOURCE_TYPE="default"
+JOB_PRIORITY="FAIRSHARE"
+UNKNOWN_KEY="ignored"
+"#,
+    )
+    .expect("Failed to parse configuration");
+
+    assert_eq!(config.scheduler_job_security_time, 30);
+    assert_eq!(config.hierarchy_labels.as_deref(), Some("resource_id,network_address,cpu,core"));
+    assert_eq!(config.scheduler_resource_order.as_deref(), Some("resource_id ASC"));
+    assert_eq!(config.scheduler_available_suspended_resource_type.as_deref(), Some("default"));
+    assert!(matches!(config.job_priority, JobPriority::Fairshare));
+}
+
+#[test]
+fn test_load_from_str_defaults() {
+    let config = Configuration::load_from_str("").expect("Failed to parse empty configuration");
+    let default = Configuration::default();
+
+    assert_eq!(config.scheduler_job_security_time, default.scheduler_job_security_time);
+    assert_eq!(config.db_type, default.db_type);
+    assert_eq!(config.quotas_window_time_limit, default.quotas_window_time_limit);
+    assert!(config.hierarchy_labels.is_none());
+}
+
+#[test]
+fn test_load_from_str_malformed() {
+    assert!(Configuration::load_from_str(r#"SCHEDULER_JOB_SECURITY_TIME="one minute""#).is_err());
+}
+
+#[test]
+fn test_queue_horizons() {
+    let config = Configuration::load_from_str(r#"SCHEDULER_QUEUE_HORIZONS="besteffort:3600, admin:86400,malformed,default:-1""#)
+        .expect("Failed to parse configuration");
+    let horizons = config.queue_horizons();
+    assert_eq!(horizons.len(), 2);
+    assert_eq!(horizons.get("besteffort"), Some(&3600));
+    assert_eq!(horizons.get("admin"), Some(&86400));
+    assert!(Configuration::default().queue_horizons().is_empty());
+}
+
+#[test]
+fn test_queue_job_security_times() {
+    let contents = "SCHEDULER_JOB_SECURITY_TIME=60\nSCHEDULER_QUEUE_JOB_SECURITY_TIMES=\"besteffort:0, long:300,malformed\"";
+    let config = Configuration::load_from_str(contents).expect("Failed to parse configuration");
+    assert_eq!(config.queue_job_security_times().len(), 2);
+    assert_eq!(config.job_security_time("besteffort"), 0);
+    assert_eq!(config.job_security_time("long"), 300);
+    assert_eq!(config.job_security_time("default"), 60);
+    assert_eq!(Configuration::default().job_security_time("long"), 60);
+}
+
+#[test]
+fn test_queues_quotas_conf_files() {
+    let config = Configuration::load_from_str(r#"QUOTAS_QUEUES_CONF_FILES="gpu:/etc/oar/quotas_gpu.json, besteffort : /tmp/be.json,malformed,:x""#)
+        .expect("Failed to parse configuration");
+    let files = config.queues_quotas_conf_files();
+    assert_eq!(files.len(), 2);
+    assert_eq!(files.get("gpu").map(String::as_str), Some("/etc/oar/quotas_gpu.json"));
+    assert_eq!(files.get("besteffort").map(String::as_str), Some("/tmp/be.json"));
+    assert!(Configuration::default().queues_quotas_conf_files().is_empty());
+}
@@ -138,3 +138,82 @@ fn test_inner_job_in_two_overlapping_containers_same_slotset_name() {
     assert_eq!(sched_inner.end, 169, "Inner job should end at time 169, which is 70 after it started");
     assert!(sched_inner.resources.is_subset(&(&sched_c2.resources | &sched_c1.resources)), "Inner job should use a subset of the c1 and c2 container jobs' resources");
 }
+
+#[test]
+fn test_inner_job_of_rejected_container_is_rejected() {
+    let platform_config = container_platform_config();
+    let available = platform_config.resource_set.default_resources.clone();
+    let mut all_ss = HashMap::from([("default".into(), SlotSet::from_platform_config(Rc::clone(&platform_config), 0, 1000))]);
+
+    // Container job requesting more nodes than available: rejected
+    let moldable_container = Moldable::new(1, 200, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 10)])]));
+    let job_container = JobBuilder::new(1)
+        .add_type("container".into(), "sub4".into())
+        .moldable(moldable_container)
+        .build();
+    // Inner job that would fit in the platform, but not in the missing container
+    let moldable_inner = Moldable::new(2, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 1)])]));
+    let job_inner = JobBuilder::new(2)
+        .add_type("inner".into(), "sub4".into())
+        .moldable(moldable_inner)
+        .build();
+
+    let mut jobs = indexmap![1 => job_container, 2 => job_inner];
+    scheduling::schedule_jobs(&mut all_ss, &mut jobs);
+    assert!(jobs[0].assignment.is_none(), "Container job should be rejected");
+    assert!(jobs[1].assignment.is_none(), "Inner job of a rejected container should be rejected");
+    assert!(!all_ss.contains_key("sub4"), "No slot set should be created for a rejected container");
+}
+
+#[test]
+fn test_inner_job_in_container_named_by_job_id() {
+    let platform_config = container_platform_config();
+    let available = platform_config.resource_set.default_resources.clone();
+    let mut all_ss = HashMap::from([("default".into(), SlotSet::from_platform_config(Rc::clone(&platform_config), 0, 1000))]);
+
+    // Container job without name: the inner slot set is keyed by the container job id
+    let moldable_container = Moldable::new(1, 200, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 1)])]));
+    let job_container = JobBuilder::new(30)
+        .add_type_key("container".into())
+        .moldable(moldable_container)
+        .build();
+    let moldable_inner = Moldable::new(2, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("cpus".into(), 2)])]));
+    let job_inner = JobBuilder::new(31)
+        .add_type("inner".into(), "30".into())
+        .moldable(moldable_inner)
+        .build();
+
+    let mut jobs = indexmap![30 => job_container, 31 => job_inner];
+    scheduling::schedule_jobs(&mut all_ss, &mut jobs);
+    assert!(all_ss.contains_key("30"));
+    let sched_container = jobs[0].assignment.as_ref().expect("Container job should be scheduled");
+    let sched_inner = jobs[1].assignment.as_ref().expect("Inner job should be scheduled");
+    assert!(sched_inner.resources.is_subset(&sched_container.resources));
+    assert!(sched_inner.end <= sched_container.end);
+}
+
+#[test]
+fn test_container_queue_security_time() {
+    let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    platform_config.config.scheduler_job_security_time = 60;
+    platform_config.config.scheduler_queue_job_security_times = Some("long:120".to_string());
+    let platform_config = Rc::new(platform_config);
+    let available = platform_config.resource_set.default_resources.clone();
+    let mut all_ss = HashMap::from([("default".into(), SlotSet::from_platform_config(Rc::clone(&platform_config), 0, 1000))]);
+
+    // Two containers from 0 to 199, the inner jobs can't use the last security time seconds of their queue
+    let container = |id: i64, queue: &str, name: &str| {
+        let requests = HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 1)])]);
+        let moldable = Moldable::new(id, 200, requests);
+        JobBuilder::new(id).queue(queue.into()).add_type("container".into(), name.into()).moldable(moldable).build()
+    };
+    let mut jobs = indexmap![1 => container(1, "long", "long_sub"), 2 => container(2, "default", "default_sub")];
+    scheduling::schedule_jobs(&mut all_ss, &mut jobs);
+
+    for (job, sub_slot_set, effective_end) in [(&jobs[0], "long_sub", 79), (&jobs[1], "default_sub", 139)] {
+        let resources = &job.assignment.as_ref().unwrap().resources;
+        let sub_slot_set = &all_ss[sub_slot_set];
+        assert_eq!(sub_slot_set.available_resources_at(effective_end), Some(resources), "{}", job.queue);
+        assert!(sub_slot_set.available_resources_at(effective_end + 1).unwrap().is_empty(), "{}", job.queue);
+    }
+}
@@ -1,9 +1,10 @@
-use crate::model::job::{JobBuilder, Moldable};
+use crate::model::job::{DependencyType, JobAssignment, JobBuilder, Moldable, ProcSet};
 use crate::platform::PlatformConfig;
 use crate::scheduler::hierarchy::{HierarchyRequest, HierarchyRequests};
+use crate::scheduler::kamelot;
 use crate::scheduler::scheduling;
 use crate::scheduler::slotset::SlotSet;
-use crate::scheduler::tests::platform_mock::generate_mock_platform_config;
+use crate::scheduler::tests::platform_mock::{generate_mock_platform_config, PlatformBenchMock};
 use indexmap::indexmap;
 use log::LevelFilter;
 use std::collections::HashMap;
@@ -110,3 +111,54 @@ fn test_find_slots_for_moldable_with_container_and_inner_jobs() {
     assert_eq!(sched_normal.begin, 100, "Normal job should start right after the inner job, at begin = 100");
 }
 
+
+#[test]
+fn test_dependency_on_scheduled_job() {
+    let platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    let available = platform_config.resource_set.default_resources.clone();
+    let moldable = |id: i64| Moldable::new(id, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 1)])]));
+
+    // Job 1 is running until 499 on the first node
+    let job1 = JobBuilder::new(1)
+        .moldable(moldable(1))
+        .assign(JobAssignment::new(0, 499, ProcSet::from_iter([1..=32]), 0))
+        .build();
+    // Job 2 depends on the running job 1, job 3 on a job that is neither scheduled nor terminated
+    let job2 = JobBuilder::new(2).moldable(moldable(2)).add_dependency(1, "Running".into(), None, DependencyType::Ok).build();
+    let job3 = JobBuilder::new(3).moldable(moldable(3)).add_dependency(10, "Hold".into(), None, DependencyType::Ok).build();
+    let mut platform = PlatformBenchMock::new(platform_config, vec![job1], indexmap![2 => job2, 3 => job3]);
+
+    let stats = kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+    assert_eq!((stats.scheduled, stats.rejected), (1, 1));
+    let job2 = platform.get_scheduled_jobs_ref().iter().find(|job| job.id == 2).expect("Job 2 is not scheduled");
+    assert_eq!(job2.begin(), Some(500));
+}
+
+#[test]
+fn test_dependency_types() {
+    let platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    let available = platform_config.resource_set.default_resources.clone();
+    let job = |id: i64, dep_job_id: i64, dep_state: &str, dep_exit_code: Option<i32>, dep_type: DependencyType| {
+        let requests = HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 1)])]);
+        JobBuilder::new(id).moldable(Moldable::new(id, 100, requests)).add_dependency(dep_job_id, dep_state.into(), dep_exit_code, dep_type).build()
+    };
+    // Job 1 errored, job 2 succeeded, and job 3 terminated with a non-zero exit code
+    let waiting_jobs = indexmap![
+        10 => job(10, 1, "Error", None, DependencyType::Ok),
+        11 => job(11, 1, "Error", None, DependencyType::Anyway),
+        12 => job(12, 2, "Terminated", Some(0), DependencyType::Ok),
+        13 => job(13, 2, "Terminated", Some(0), DependencyType::Anyway),
+        14 => job(14, 3, "Terminated", Some(1), DependencyType::Ok),
+        15 => job(15, 3, "Terminated", Some(1), DependencyType::Anyway),
+        // Dependencies without type ignore the errored jobs
+        16 => job(16, 1, "Error", None, DependencyType::Legacy),
+        17 => job(17, 3, "Terminated", Some(1), DependencyType::Legacy),
+    ];
+    let mut platform = PlatformBenchMock::new(platform_config, vec![], waiting_jobs);
+
+    let stats = kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+    assert_eq!((stats.scheduled, stats.rejected), (5, 3));
+    let mut scheduled = platform.get_scheduled_jobs_ref().iter().map(|job| job.id).collect::<Vec<i64>>();
+    scheduled.sort();
+    assert_eq!(scheduled, vec![11, 12, 13, 15, 16]);
+}
@@ -0,0 +1,60 @@
+use crate::model::job::{export_gantt_csv, occupation_matrix, JobAssignment, JobBuilder, Moldable, ProcSet};
+use crate::scheduler::hierarchy::HierarchyRequests;
+
+#[test]
+fn test_export_gantt_csv() {
+    let requests = HierarchyRequests::new_single(ProcSet::from_iter([1..=16]), vec![("cores".into(), 7)]);
+    let assigned_job = JobBuilder::new(3)
+        .moldable(Moldable::new(30, 100, requests.clone()))
+        .moldable(Moldable::new(31, 50, requests.clone()))
+        .assign(JobAssignment::new(10, 59, ProcSet::from_iter([1..=4, 7..=7, 10..=11]), 1))
+        .build();
+    // Waiting jobs without assignment are not exported
+    let waiting_job = JobBuilder::new(4).moldable(Moldable::new(40, 100, requests)).build();
+
+    let mut csv = Vec::new();
+    export_gantt_csv(&[assigned_job, waiting_job], &mut csv).unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "job_id,moldable_id,begin,end,core_count,resources_oar_string\n3,31,10,59,7,1-4 7 10-11\n"
+    );
+}
+
+#[test]
+fn test_occupation_matrix() {
+    let requests = HierarchyRequests::new_single(ProcSet::from_iter([0..=7]), vec![("cores".into(), 1)]);
+    // (begin, end, resources): resource 7 and the seconds after 19 are outside the matrix
+    let windows = [(0, 9, ProcSet::from_iter([0..=1])), (5, 14, ProcSet::from_iter([2])), (18, 30, ProcSet::from_iter([3, 7]))];
+    let mut jobs = windows
+        .iter()
+        .enumerate()
+        .map(|(i, (begin, end, resources))| {
+            JobBuilder::new(i as i64)
+                .moldable(Moldable::new(i as i64, end - begin + 1, requests.clone()))
+                .assign(JobAssignment::new(*begin, *end, resources.clone(), 0))
+                .build()
+        })
+        .collect::<Vec<_>>();
+    jobs.push(JobBuilder::new(3).moldable(Moldable::new(3, 10, requests)).build());
+
+    // With buckets of one second, exactly the occupied cells are marked
+    let matrix = occupation_matrix(&jobs, 0, 19, 4, 1);
+    assert_eq!(matrix.len(), 20);
+    for (time, row) in matrix.iter().enumerate() {
+        assert_eq!(row.len(), 4);
+        for (resource, cell) in row.iter().enumerate() {
+            let occupied = windows
+                .iter()
+                .any(|(begin, end, resources)| (*begin..=*end).contains(&(time as i64)) && resources.contains(resource as u32));
+            assert_eq!(*cell, if occupied { 100 } else { 0 }, "time {}, resource {}", time, resource);
+        }
+    }
+
+    assert_eq!(
+        occupation_matrix(&jobs, 0, 19, 4, 5),
+        vec![vec![100, 100, 0, 0], vec![100, 100, 100, 0], vec![0, 0, 100, 0], vec![0, 0, 0, 40]]
+    );
+    assert_eq!(occupation_matrix(&jobs, 0, 19, 4, 10), vec![vec![100, 100, 50, 0], vec![0, 0, 50, 20]]);
+    // The last bucket is shorter when the range is not a multiple of the bucket size
+    assert_eq!(occupation_matrix(&jobs, 0, 11, 3, 8), vec![vec![100, 100, 38], vec![50, 50, 100]]);
+}
@@ -1,6 +1,10 @@
 use crate::model::job::ProcSet;
-use crate::scheduler::hierarchy::{Hierarchy, HierarchyRequest, HierarchyRequests};
+use crate::scheduler::hierarchy::{Hierarchy, HierarchyRequest, HierarchyRequests, RequestMultipleError, REQUEST_MULTIPLE_MAX_ATTEMPTS};
 use crate::scheduler::tests::platform_mock::generate_mock_platform_config;
+use rand::prelude::SliceRandom;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::HashMap;
 use std::ops::RangeInclusive;
 
 #[allow(dead_code)]
@@ -151,3 +155,161 @@ fn test_hierarchy_from_platform() {
     assert_eq!(proc_set_2, ProcSet::from_iter([1..=64]));
     assert_eq!(proc_set, ProcSet::from_iter([1..=64]));
 }
+
+#[test]
+fn test_format_proc_set_as_labels() {
+    // 8 nodes of 32 cores
+    let platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    let h = &platform_config.resource_set.hierarchy;
+
+    let proc_set = procset(1..=128) | procset(140..=150) | procset(225..=256);
+    assert_eq!(h.format_proc_set_as_labels(&proc_set, "nodes"), "nodes[0-3],nodes4(11/32),nodes7");
+    assert_eq!(h.format_proc_set_as_labels(&proc_set, "switches"), "switches0(171/256)");
+    assert_eq!(h.format_proc_set_as_labels(&proc_set, "cores"), "cores[1-128],cores[140-150],cores[225-256]");
+    assert_eq!(h.format_proc_set_as_labels(&procset(33..=33), "cores"), "cores33");
+    assert_eq!(h.format_proc_set_as_labels(&ProcSet::new(), "nodes"), "");
+
+    // Partitions with label values are identified by them
+    let h = Hierarchy::new().add_valued_partition(
+        "host".into(),
+        Box::new([procset(1..=4), procset(5..=8), procset(9..=12)]),
+        Box::new(["node2".into(), "node10".into(), "node11".into()]),
+    );
+    assert_eq!(h.format_proc_set_as_labels(&procset(1..=10), "host"), "node2,node10,node11(2/4)");
+}
+
+#[test]
+fn test_count_units() {
+    let h = Hierarchy::new()
+        .add_partition("switch".into(), procsets([1..=24].into()))
+        .add_partition("node".into(), procsets([1..=8, 9..=16, 17..=24].into()))
+        .add_unit_partition("core".into());
+
+    // Spanning two of the three nodes
+    let proc_set = procset(3..=8) | procset(17..=18);
+    assert_eq!(h.count_units(&proc_set, "node"), Some(2));
+    assert_eq!(h.count_units(&proc_set, "switch"), Some(1));
+    assert_eq!(h.count_units(&proc_set, "core"), Some(8));
+    assert_eq!(h.count_units(&ProcSet::new(), "node"), Some(0));
+    assert_eq!(h.count_units(&proc_set, "rack"), None);
+}
+
+#[test]
+fn test_request_with_exclusion() {
+    let h = Hierarchy::new()
+        .add_partition("switch".into(), procsets([1..=16, 17..=32].into()))
+        .add_partition("node".into(), procsets([1..=8, 9..=16, 17..=24, 25..=32].into()));
+    let available = procset(1..=32);
+    let request = HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("node".into(), 2)])]);
+
+    assert_eq!(h.request_with_exclusion(&available, &request, &ProcSet::new()), Some(procset(1..=16)));
+    // A single excluded core makes its whole node unavailable
+    assert_eq!(h.request_with_exclusion(&available, &request, &procset(3..=3)), Some(procset(9..=24)));
+    assert_eq!(h.request_with_exclusion(&available, &request, &procset(1..=24)), None);
+}
+
+#[test]
+fn test_request_range() {
+    // 8 nodes of 32 cores
+    let platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    let h = &platform_config.resource_set.hierarchy;
+    let filter = ProcSet::from_iter([1..=256]);
+    let request = |min: u32, max: u32| {
+        HierarchyRequests::from_requests(vec![HierarchyRequest::new_range(filter.clone(), vec![("nodes".into(), min, max)])])
+    };
+
+    // 5 free nodes: as many as available up to the maximum
+    let available = ProcSet::from_iter([1..=160]);
+    assert_eq!(h.request(&available, &request(2, 8)), Some(ProcSet::from_iter([1..=160])));
+    assert_eq!(h.request(&available, &request(2, 3)), Some(ProcSet::from_iter([1..=96])));
+    assert_eq!(h.request(&available, &request(6, 8)), None);
+
+    // Range of cores in each node
+    let available = ProcSet::from_iter([1..=20, 33..=64]);
+    let request = HierarchyRequests::from_requests(vec![HierarchyRequest::new_range(
+        filter.clone(),
+        vec![("nodes".into(), 1, 2), ("cores".into(), 16, 24)],
+    )]);
+    assert_eq!(h.request(&available, &request), Some(ProcSet::from_iter([1..=20, 33..=56])));
+}
+
+#[test]
+fn test_request_deterministic_partition_order() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let nodes = procsets(Box::new([1..=4, 5..=8, 9..=12, 13..=16, 17..=20, 21..=24, 25..=28, 29..=32]));
+    let switches = procsets(Box::new([1..=16, 17..=32]));
+    let available = ProcSet::from_iter([3..=32]);
+    let request = HierarchyRequests::new_single(available.clone(), vec![("switch".into(), 1), ("node".into(), 2)]);
+
+    for _ in 0..20 {
+        let mut shuffled_nodes = nodes.clone();
+        shuffled_nodes.shuffle(&mut rng);
+        let mut shuffled_switches = switches.clone();
+        shuffled_switches.shuffle(&mut rng);
+
+        let h = Hierarchy::new()
+            .add_partition("switch".into(), shuffled_switches.clone())
+            .add_partition("node".into(), shuffled_nodes.clone())
+            .add_unit_partition("core".into());
+        let h_defined = Hierarchy::new_defined(
+            HashMap::from([("switch".into(), shuffled_switches), ("node".into(), shuffled_nodes)]),
+            vec!["core".into()],
+        );
+        // Node 1 is not fully available: the lowest-index satisfying nodes are 2 and 3
+        assert_eq!(h.request(&available, &request), Some(ProcSet::from_iter([5..=12])));
+        assert_eq!(h_defined.request(&available, &request), Some(ProcSet::from_iter([5..=12])));
+        assert_eq!(h.format_proc_set_as_labels(&ProcSet::from_iter([5..=12]), "node"), "node[1-2]");
+    }
+}
+
+#[test]
+fn test_request_multiple() {
+    let h = Hierarchy::new()
+        .add_partition("nodes".into(), procsets([0..=3, 4..=7].into()))
+        .add_unit_partition("cores".into());
+
+    // Three disjoint 2-core sets on an 8-core free window
+    let request = HierarchyRequests::new_single(procset(0..=7), vec![("cores".into(), 2)]);
+    let placements = h.request_multiple(&procset(0..=7), &request, 3).unwrap();
+    assert_eq!(placements, vec![procset(0..=1), procset(2..=3), procset(4..=5)]);
+    assert_eq!(h.request_multiple(&procset(0..=7), &request, 5), Err(RequestMultipleError::NotFound));
+    assert_eq!(h.request_multiple(&procset(0..=7), &request, 0), Ok(vec![]));
+
+    // Two 2-core sets on a single node each: the free cores 0, 1 and 4 to 6 leave room for one set per node
+    let request = HierarchyRequests::new_single(procset(0..=7), vec![("nodes".into(), 1), ("cores".into(), 2)]);
+    let available = procset(0..=1) | procset(4..=6);
+    assert_eq!(h.request_multiple(&available, &request, 2), Ok(vec![procset(0..=1), procset(4..=5)]));
+
+    // The greedy placement {0, 1} leaves no core of the filter {0, 1} for the second placement,
+    // and the next candidate must not share any resource with the rejected placement: there is none
+    let request = HierarchyRequests::from_requests(vec![
+        HierarchyRequest::new(procset(0..=1), vec![("cores".into(), 1)]),
+        HierarchyRequest::new(procset(1..=2), vec![("cores".into(), 1)]),
+    ]);
+    assert_eq!(h.request_multiple(&procset(0..=2), &request, 2), Err(RequestMultipleError::NotFound));
+}
+
+#[test]
+fn test_request_multiple_infeasible() {
+    // 26 nodes of 2 cpus of 2 cores, the 24 first nodes are free, and only a cpu of each of the 2 last nodes
+    let h = Hierarchy::new()
+        .add_partition("nodes".into(), (0..26).map(|i| procset(i * 4..=i * 4 + 3)).collect())
+        .add_partition("cpus".into(), (0..52).map(|i| procset(i * 2..=i * 2 + 1)).collect())
+        .add_unit_partition("cores".into());
+    let available = procset(0..=95) | procset(96..=97) | procset(100..=101);
+    let request = HierarchyRequests::new_single(procset(0..=103), vec![("nodes".into(), 1), ("cpus".into(), 2)]);
+
+    // Rejected by the capacity check: 27 nodes, or 54 cpus
+    assert!(!h.can_fit_multiple(&available, &request, 27));
+    let request_cpus = HierarchyRequests::new_single(procset(0..=103), vec![("cpus".into(), 2)]);
+    assert!(!h.can_fit_multiple(&available, &request_cpus, 26));
+    assert_eq!(h.request_multiple(&available, &request_cpus, 26), Err(RequestMultipleError::NotFound));
+
+    // 25 nodes with 2 free cpus pass the capacity check, but only 24 exist: the backtracking gives up, telling it was cut short
+    assert!(h.can_fit_multiple(&available, &request, 25));
+    assert_eq!(
+        h.request_multiple(&available, &request, 25),
+        Err(RequestMultipleError::AttemptsExhausted(REQUEST_MULTIPLE_MAX_ATTEMPTS))
+    );
+    assert_eq!(h.request_multiple(&available, &request, 24).map(|placements| placements.len()), Ok(24));
+}
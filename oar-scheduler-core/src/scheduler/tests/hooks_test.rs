@@ -0,0 +1,162 @@
+use crate::hooks::{set_hooks_handler, HooksHandler};
+use crate::model::configuration::JobPriority;
+use crate::model::job::{Job, JobBuilder, Moldable, ProcSet};
+use crate::platform::PlatformConfig;
+use crate::scheduler::hierarchy::{HierarchyRequest, HierarchyRequests};
+use crate::scheduler::kamelot;
+use crate::scheduler::slotset::SlotSet;
+use crate::scheduler::tests::platform_mock::{generate_mock_platform_config, PlatformBenchMock};
+use indexmap::{indexmap, IndexMap};
+use std::collections::HashMap;
+
+/// Hooks handler removing a blacklisted node from the resources available to the jobs of a given project.
+struct BlacklistHooks {
+    project: Box<str>,
+    blacklisted_node: ProcSet,
+}
+impl HooksHandler for BlacklistHooks {
+    fn hook_sort(&self, _platform_config: &PlatformConfig, _queues: &Vec<String>, _waiting_jobs: &mut IndexMap<i64, Job>) -> bool {
+        false
+    }
+    fn hook_assign(&self, _slot_set: &mut SlotSet, _job: &mut Job, _min_begin: Option<i64>) -> bool {
+        false
+    }
+    fn hook_find(&self, _slot_set: &SlotSet, _job: &Job, _moldable: &Moldable, _min_begin: Option<i64>, _available: ProcSet) -> Option<Option<ProcSet>> {
+        None
+    }
+    fn hook_filter_resources(&self, _platform_config: &PlatformConfig, job: &Job, available: &mut ProcSet) -> bool {
+        if job.project.as_ref() != Some(&self.project) {
+            return false;
+        }
+        *available = &*available - &self.blacklisted_node;
+        true
+    }
+}
+
+#[test]
+fn test_hook_filter_resources() {
+    // The hooks handler is thread local, and each test runs in its own thread.
+    let blacklisted_node = ProcSet::from_iter([1..=32]);
+    set_hooks_handler(BlacklistHooks {
+        project: "restricted".into(),
+        blacklisted_node: blacklisted_node.clone(),
+    });
+
+    // 8 nodes of 32 cores
+    let platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    let available = platform_config.resource_set.default_resources.clone();
+    let moldable = |id: i64, nodes: u32| Moldable::new(id, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), nodes)])]));
+
+    let waiting_jobs = indexmap![
+        1 => JobBuilder::new(1).project("restricted".into()).moldable(moldable(1, 1)).build(),
+        2 => JobBuilder::new(2).project("restricted".into()).moldable(moldable(2, 8)).build(),
+        3 => JobBuilder::new(3).moldable(moldable(3, 8)).build(),
+    ];
+    let mut platform = PlatformBenchMock::new(platform_config, vec![], waiting_jobs);
+    kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+
+    let scheduled_jobs = platform.get_scheduled_jobs_ref();
+    let assignment = |id: i64| scheduled_jobs.iter().find(|job| job.id == id).and_then(|job| job.assignment.as_ref());
+    // Job 1 is placed on another node than the blacklisted one
+    assert_eq!(assignment(1).map(|a| a.begin), Some(0));
+    assert!((&assignment(1).unwrap().resources & &blacklisted_node).is_empty());
+    // Job 2 would need the blacklisted node
+    assert!(assignment(2).is_none());
+    // Job 3 is not filtered, and uses all the nodes after job 1
+    assert_eq!(assignment(3).map(|a| (a.begin, a.resources.clone())), Some((100, ProcSet::from_iter([1..=256]))));
+}
+
+#[test]
+fn test_hook_filter_resources_without_cache() {
+    let blacklisted_node = ProcSet::from_iter([1..=32]);
+    set_hooks_handler(BlacklistHooks {
+        project: "restricted".into(),
+        blacklisted_node: blacklisted_node.clone(),
+    });
+
+    // 8 nodes of 32 cores, with the cache enabled
+    let platform_config = generate_mock_platform_config(true, 256, 8, 4, 8, false);
+    let available = platform_config.resource_set.default_resources.clone();
+    let moldable = |id: i64, nodes: u32| {
+        Moldable::new(id, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), nodes)])]))
+    };
+
+    let waiting_jobs = indexmap![
+        1 => JobBuilder::new(1).moldable(Moldable::new(1, 100, HierarchyRequests::from_requests(vec![
+            HierarchyRequest::new(ProcSet::from_iter([33..=256]), vec![("nodes".into(), 7)])
+        ]))).build(),
+        2 => JobBuilder::new(2).project("restricted".into()).moldable(moldable(2, 1)).build(),
+        3 => JobBuilder::new(3).moldable(moldable(3, 1)).build(),
+    ];
+    let mut platform = PlatformBenchMock::new(platform_config, vec![], waiting_jobs);
+    kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+
+    let scheduled_jobs = platform.get_scheduled_jobs_ref();
+    let assignment = |id: i64| scheduled_jobs.iter().find(|job| job.id == id).and_then(|job| job.assignment.as_ref());
+    // Job 2 waits for job 1 as the only free node is blacklisted for it
+    assert_eq!(assignment(2).map(|a| a.begin), Some(100));
+    // Job 3 has the same request, but does not start from the slot found for job 2
+    assert_eq!(assignment(3).map(|a| (a.begin, a.resources.clone())), Some((0, blacklisted_node)));
+}
+
+/// Hooks handler ordering the waiting jobs by increasing usage of their user.
+struct FairshareHooks;
+impl HooksHandler for FairshareHooks {
+    fn hook_sort(&self, _platform_config: &PlatformConfig, _queues: &Vec<String>, _waiting_jobs: &mut IndexMap<i64, Job>) -> bool {
+        false
+    }
+    fn hook_fairshare(&self, _platform_config: &PlatformConfig, users_usage: &HashMap<Box<str>, f64>, waiting_jobs: &mut IndexMap<i64, Job>) -> bool {
+        let usage = |job: &Job| job.user.as_ref().and_then(|user| users_usage.get(user)).copied().unwrap_or(0.0);
+        waiting_jobs.sort_by(|_id1, job1, _id2, job2| usage(job1).total_cmp(&usage(job2)));
+        true
+    }
+    fn hook_assign(&self, _slot_set: &mut SlotSet, _job: &mut Job, _min_begin: Option<i64>) -> bool {
+        false
+    }
+    fn hook_find(&self, _slot_set: &SlotSet, _job: &Job, _moldable: &Moldable, _begin: Option<i64>, _available: ProcSet) -> Option<Option<ProcSet>> {
+        None
+    }
+    fn hook_filter_resources(&self, _platform_config: &PlatformConfig, _job: &Job, _available: &mut ProcSet) -> bool {
+        false
+    }
+}
+
+#[test]
+fn test_hook_fairshare() {
+    set_hooks_handler(FairshareHooks);
+
+    let schedule = |job_priority: JobPriority| {
+        // 8 nodes of 32 cores
+        let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+        platform_config.config.job_priority = job_priority;
+        // Null coefficients: the karma leaves the jobs in FIFO order, the hook reorders them
+        platform_config.config.scheduler_fairsharing_window_size = Some(30 * 24 * 3600);
+        platform_config.config.scheduler_fairsharing_project_targets = Some("{}".to_string());
+        platform_config.config.scheduler_fairsharing_user_targets = Some("{}".to_string());
+        platform_config.config.scheduler_fairsharing_coef_project = Some(0.0);
+        platform_config.config.scheduler_fairsharing_coef_user = Some(0.0);
+        platform_config.config.scheduler_fairsharing_coef_user_ask = Some(0.0);
+        let available = platform_config.resource_set.default_resources.clone();
+        let request = HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 8)])]);
+        let moldable = |id: i64| Moldable::new(id, 100, request.clone());
+
+        let waiting_jobs = indexmap![
+            1 => JobBuilder::new(1).user("heavy".into()).moldable(moldable(1)).build(),
+            2 => JobBuilder::new(2).user("heavy".into()).moldable(moldable(2)).build(),
+            3 => JobBuilder::new(3).user("light".into()).moldable(moldable(3)).build(),
+        ];
+        let users_used = HashMap::from([("heavy".to_string(), 1000.0), ("light".to_string(), 10.0)]);
+        let mut platform = PlatformBenchMock::new(platform_config, vec![], waiting_jobs).with_users_used(users_used);
+        kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+        platform
+            .get_scheduled_jobs_ref()
+            .iter()
+            .map(|job| (job.id, job.begin().unwrap()))
+            .collect::<HashMap<i64, i64>>()
+    };
+
+    // The heavy user's jobs are demoted below the light user's job, keeping their order
+    assert_eq!(schedule(JobPriority::Fairshare), HashMap::from([(3, 0), (1, 100), (2, 200)]));
+    // Without fairshare, the hook is not called and the jobs stay in FIFO order
+    assert_eq!(schedule(JobPriority::Fifo), HashMap::from([(1, 0), (2, 100), (3, 200)]));
+}
@@ -0,0 +1,28 @@
+use crate::model::job::{JobAssignment, JobBuilder, Moldable};
+use crate::scheduler::hierarchy::{HierarchyRequest, HierarchyRequests};
+use crate::scheduler::kamelot;
+use crate::scheduler::tests::platform_mock::{generate_mock_platform_config, PlatformBenchMock};
+use indexmap::indexmap;
+
+#[test]
+fn test_queue_horizon() {
+    for (queue, scheduled) in [("besteffort", false), ("default", true)] {
+        let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+        platform_config.config.scheduler_queue_horizons = Some("besteffort:1000".to_string());
+        let available = platform_config.resource_set.default_resources.clone();
+
+        // The whole cluster is used until time 499, so a job of walltime 600 can't end before the besteffort horizon.
+        let blocking_job = JobBuilder::new(1).assign(JobAssignment::new(0, 499, available.clone(), 0)).build();
+        let moldable = Moldable::new(2, 600, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available, vec![("nodes".into(), 1)])]));
+        let job = JobBuilder::new(2).queue(queue.into()).moldable(moldable).build();
+
+        let mut platform = PlatformBenchMock::new(platform_config, vec![blocking_job], indexmap![2 => job]);
+        kamelot::schedule_cycle(&mut platform, &vec![queue.to_string()]);
+
+        let job = platform.get_scheduled_jobs_ref().iter().find(|job| job.id == 2);
+        assert_eq!(job.is_some(), scheduled, "queue {}", queue);
+        if let Some(job) = job {
+            assert_eq!(job.assignment.as_ref().unwrap().begin, 500);
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use crate::logging;
+use log::LevelFilter;
+
+#[test]
+fn test_parse_level() {
+    assert_eq!(logging::parse_level(Some("debug"), LevelFilter::Info), LevelFilter::Debug);
+    assert_eq!(logging::parse_level(Some(" WARN "), LevelFilter::Info), LevelFilter::Warn);
+    assert_eq!(logging::parse_level(Some("off"), LevelFilter::Info), LevelFilter::Off);
+    assert_eq!(logging::parse_level(Some("verbose"), LevelFilter::Info), LevelFilter::Info);
+    assert_eq!(logging::parse_level(None, LevelFilter::Trace), LevelFilter::Trace);
+}
+
+#[test]
+fn test_init_twice() {
+    // A logger may already be set by another test: initializing again must not panic
+    logging::init(LevelFilter::Info, &[logging::HIERARCHY_DEBUG_FILTER]);
+    logging::init(LevelFilter::Debug, &[]);
+}
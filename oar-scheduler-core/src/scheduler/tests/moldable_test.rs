@@ -0,0 +1,258 @@
+use crate::model::job::{Job, JobAssignment, JobBuilder, Moldable, ProcSet, ProcSetCoresOp};
+use crate::model::walltime::{ConstantEstimator, QueueDefaultEstimator};
+use crate::scheduler::hierarchy::{HierarchyRequest, HierarchyRequests};
+use crate::platform::PlatformTrait;
+use crate::scheduler::{kamelot, scheduling};
+use crate::scheduler::slotset::SlotSet;
+use crate::scheduler::tests::platform_mock::{generate_mock_platform_config, PlatformBenchMock};
+use indexmap::indexmap;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[test]
+fn test_moldable_earliest_end_is_chosen() {
+    let platform_config = Rc::new(generate_mock_platform_config(false, 256, 8, 4, 8, false));
+    let available = platform_config.resource_set.default_resources.clone();
+    let mut ss = SlotSet::from_platform_config(Rc::clone(&platform_config), 0, 10_000);
+
+    // Running job on nodes 1 to 4 until 999
+    let running_job = JobBuilder::new(1).assign(JobAssignment::new(0, 999, ProcSet::from_iter([1..=128]), 0)).build();
+    ss.split_slots_for_job_and_update_resources(&running_job, true, true, None);
+    ss.assert_consistent();
+    let mut all_ss = HashMap::from([("default".into(), ss)]);
+
+    // Small-but-slow moldable: can start now on the free nodes, but finishes at 2999.
+    let small_moldable = Moldable::new(2, 3000, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 2)])]));
+    // Large-but-fast moldable: must wait for the running job to finish, but finishes at 1499.
+    let large_moldable = Moldable::new(3, 500, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 8)])]));
+    let job = JobBuilder::new(2).moldables(vec![small_moldable, large_moldable]).build();
+
+    let mut jobs = indexmap![2 => job];
+    scheduling::schedule_jobs(&mut all_ss, &mut jobs);
+
+    let assignment = jobs[0].assignment.as_ref().expect("Job 2 should be scheduled");
+    assert_eq!(assignment.moldable_index, 1);
+    assert_eq!(assignment.begin, 1000);
+    assert_eq!(assignment.end, 1499);
+    assert_eq!(assignment.resources, available);
+}
+
+#[test]
+fn test_moldable_fewest_resources_on_same_end() {
+    let platform_config = Rc::new(generate_mock_platform_config(false, 256, 8, 4, 8, false));
+    let available = platform_config.resource_set.default_resources.clone();
+    let ss = SlotSet::from_platform_config(Rc::clone(&platform_config), 0, 10_000);
+    let mut all_ss = HashMap::from([("default".into(), ss)]);
+
+    let large_moldable = Moldable::new(1, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 4)])]));
+    let small_moldable = Moldable::new(2, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 2)])]));
+    let job = JobBuilder::new(1).moldables(vec![large_moldable, small_moldable]).build();
+
+    let mut jobs = indexmap![1 => job];
+    scheduling::schedule_jobs(&mut all_ss, &mut jobs);
+
+    let assignment = jobs[0].assignment.as_ref().expect("Job 1 should be scheduled");
+    assert_eq!(assignment.moldable_index, 1);
+    assert_eq!(assignment.begin, 0);
+    assert_eq!(assignment.resources.core_count(), 64);
+}
+
+#[test]
+fn test_moldable_excluded_resources() {
+    let platform_config = Rc::new(generate_mock_platform_config(false, 256, 8, 4, 8, false));
+    let available = platform_config.resource_set.default_resources.clone();
+    let ss = SlotSet::from_platform_config(Rc::clone(&platform_config), 0, 10_000);
+    let mut all_ss = HashMap::from([("default".into(), ss)]);
+
+    // Excluding the first node and a core of the second node, making the whole second node unavailable
+    let moldable = Moldable::new(1, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 2)])]))
+        .with_excluded_resources(ProcSet::from_iter([1..=33]));
+    let same_moldable = Moldable::new(2, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 2)])]));
+    assert_ne!(moldable.cache_key, same_moldable.cache_key);
+    let job = JobBuilder::new(1).moldable(moldable).build();
+
+    let mut jobs = indexmap![1 => job];
+    scheduling::schedule_jobs(&mut all_ss, &mut jobs);
+
+    let assignment = jobs[0].assignment.as_ref().expect("Job 1 should be scheduled");
+    assert_eq!(assignment.begin, 0);
+    assert_eq!(assignment.resources, ProcSet::from_iter([65..=128]));
+}
+
+#[test]
+fn test_effective_end() {
+    let moldable = Moldable::new(1, 100, HierarchyRequests::from_requests(vec![]));
+    assert_eq!(moldable.effective_end(1000, 0), 1099);
+    assert_eq!(moldable.effective_end(1000, 60), 1039);
+    // The walltime is not greater than the security time: the effective end is clamped to the beginning.
+    assert_eq!(moldable.effective_end(1000, 99), 1000);
+    assert_eq!(moldable.effective_end(1000, 100), 1000);
+    assert_eq!(moldable.effective_end(1000, 500), 1000);
+
+    let job = JobBuilder::new(1).moldable(moldable.clone()).build();
+    assert_eq!(job.effective_end(60), None);
+    let job = JobBuilder::new(1).moldable(moldable).assign(JobAssignment::new(1000, 1099, ProcSet::from_iter([1..=4]), 0)).build();
+    assert_eq!(job.effective_end(60), Some(1039));
+    assert_eq!(job.effective_end(500), Some(1000));
+}
+
+#[test]
+fn test_job_window() {
+    let moldable = Moldable::new(1, 100, HierarchyRequests::from_requests(vec![]));
+    let job = JobBuilder::new(1).moldable(moldable.clone()).build();
+    assert_eq!(job.window(), None);
+
+    let job = JobBuilder::new(1)
+        .moldable(moldable)
+        .assign(JobAssignment::new(1000, 1099, ProcSet::from_iter([1..=4]), 0))
+        .build();
+    assert_eq!(job.window(), Some((1000, 1099, &ProcSet::from_iter([1..=4]))));
+}
+
+#[test]
+fn test_unsatisfiable_moldable_is_skipped() {
+    // 8 nodes of 32 cores
+    let platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    let available = platform_config.resource_set.default_resources.clone();
+    let nodes = |id: i64, nodes: u32| Moldable::new(id, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), nodes)])]));
+
+    assert!(nodes(1, 8).is_satisfiable(&platform_config.resource_set));
+    assert!(!nodes(2, 9).is_satisfiable(&platform_config.resource_set));
+    assert!(!nodes(3, 8).with_excluded_resources(ProcSet::from_iter([1..=32])).is_satisfiable(&platform_config.resource_set));
+
+    // Job 1 only has unsatisfiable moldables, job 2 has a satisfiable one
+    let job1 = JobBuilder::new(1).moldable(nodes(4, 9)).build();
+    let job2 = JobBuilder::new(2).moldable(nodes(5, 10)).moldable(nodes(6, 2)).build();
+    let mut platform = PlatformBenchMock::new(platform_config, vec![], indexmap![1 => job1, 2 => job2]);
+    let stats = kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+    assert_eq!((stats.scheduled, stats.rejected), (1, 1));
+
+    // The unsatisfiable job is not rejected, as the missing resources may come back: it is only skipped at each cycle
+    assert!(platform.get_rejected_jobs_ref().is_empty());
+    let stats = kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+    assert_eq!((stats.scheduled, stats.rejected), (0, 1));
+    assert_eq!(platform.get_waiting_jobs(vec!["default".to_string()]).keys().copied().collect::<Vec<i64>>(), vec![1]);
+}
+
+#[test]
+fn test_unknown_level_moldable_is_kept_waiting() {
+    let platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    let available = platform_config.resource_set.default_resources.clone();
+    let moldable = Moldable::new(1, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available, vec![("racks".into(), 1)])]));
+    assert!(moldable.is_satisfiable(&platform_config.resource_set));
+
+    // The job cannot be scheduled, but is not moved out of the waiting jobs either
+    let job = JobBuilder::new(1).moldable(moldable).build();
+    let mut platform = PlatformBenchMock::new(platform_config, vec![], indexmap![1 => job]);
+    let stats = kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+    assert_eq!(stats.scheduled, 0);
+    assert!(platform.get_rejected_jobs_ref().is_empty());
+    assert_eq!(platform.get_waiting_jobs(vec!["default".to_string()]).keys().copied().collect::<Vec<i64>>(), vec![1]);
+}
+
+#[test]
+fn test_zero_resources_moldable_is_rejected() {
+    let platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    let available = platform_config.resource_set.default_resources.clone();
+    let request = |level_nbs: Vec<(Box<str>, u32)>| HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), level_nbs)]);
+
+    assert!(!Moldable::new(1, 100, request(vec![("nodes".into(), 1)])).requests_zero_resources());
+    assert!(Moldable::new(2, 100, request(vec![("nodes".into(), 0)])).requests_zero_resources());
+    assert!(Moldable::new(3, 100, request(vec![("nodes".into(), 1), ("cores".into(), 0)])).requests_zero_resources());
+    assert!(Moldable::new(4, 100, request(vec![])).requests_zero_resources());
+    assert!(Moldable::new(5, 100, HierarchyRequests::from_requests(vec![])).requests_zero_resources());
+
+    // Job 1 requests zero cores in its moldable 6 only: it is scheduled with its valid moldable 7
+    let job1 = JobBuilder::new(1)
+        .moldable(Moldable::new(6, 100, request(vec![("nodes".into(), 1), ("cores".into(), 0)])))
+        .moldable(Moldable::new(7, 100, request(vec![("nodes".into(), 1)])))
+        .build();
+    let job2 = JobBuilder::new(2).moldable(Moldable::new(8, 100, request(vec![("cores".into(), 4)]))).build();
+    // Job 3 has no valid moldable
+    let job3 = JobBuilder::new(3)
+        .moldable(Moldable::new(9, 100, request(vec![("nodes".into(), 0)])))
+        .moldable(Moldable::new(10, -10, request(vec![("nodes".into(), 1)])))
+        .build();
+    let mut platform = PlatformBenchMock::new(platform_config, vec![], indexmap![1 => job1, 2 => job2, 3 => job3]);
+    let stats = kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+    assert_eq!((stats.scheduled, stats.rejected), (2, 1));
+    let scheduled_jobs = platform.get_scheduled_jobs_ref();
+    assert_eq!(scheduled_jobs.iter().map(|job| job.id).collect::<Vec<i64>>(), vec![1, 2]);
+    let job1 = &scheduled_jobs[0];
+    assert_eq!(job1.moldables.iter().map(|moldable| moldable.id).collect::<Vec<i64>>(), vec![7]);
+    assert_eq!(job1.moldables[job1.assignment.as_ref().unwrap().moldable_index].id, 7);
+    assert_eq!(platform.get_rejected_jobs_ref()[0].id, 3);
+    assert!(platform.get_rejected_jobs_ref()[0].message.contains("requests zero resources in its moldable 9"));
+}
+
+#[test]
+fn test_non_positive_walltime_moldable_is_rejected() {
+    let platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    let available = platform_config.resource_set.default_resources.clone();
+    let request = HierarchyRequests::from_requests(vec![HierarchyRequest::new(available, vec![("nodes".into(), 1)])]);
+
+    let job1 = JobBuilder::new(1).moldable(Moldable::new(1, -10, request.clone())).build();
+    let job2 = JobBuilder::new(2).moldable(Moldable::new(2, 100, request)).build();
+    let mut platform = PlatformBenchMock::new(platform_config, vec![], indexmap![1 => job1, 2 => job2]);
+    let stats = kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+    assert_eq!((stats.scheduled, stats.rejected), (1, 1));
+    assert_eq!(platform.get_scheduled_jobs_ref().iter().map(|job| job.id).collect::<Vec<i64>>(), vec![2]);
+    assert!(platform.get_rejected_jobs_ref()[0].message.contains("has a non-positive walltime of -10 in its moldable 1"));
+}
+
+#[test]
+fn test_walltime_estimator() {
+    let requests = HierarchyRequests::from_requests(vec![HierarchyRequest::new(ProcSet::from_iter([1..=32]), vec![("cores".into(), 1)])]);
+    let estimator = Rc::new(QueueDefaultEstimator::new(3600).queue("short".into(), 600));
+    let build_job = |id: i64, queue: &str, walltime: i64| {
+        JobBuilder::new(id)
+            .queue(queue.into())
+            .moldable(Moldable::new(id, walltime, requests.clone()))
+            .walltime_estimator(estimator.clone())
+            .build()
+    };
+
+    // Jobs without walltime get the default walltime of their queue
+    let job = build_job(1, "short", 0);
+    assert_eq!(job.moldables[0].walltime, 600);
+    assert_eq!(job.moldables[0].cache_key, Moldable::new(1, 600, requests.clone()).cache_key);
+    assert_eq!(build_job(2, "default", 0).moldables[0].walltime, 3600);
+    // Explicit walltimes are kept
+    assert_eq!(build_job(3, "short", 120).moldables[0].walltime, 120);
+
+    let job = JobBuilder::new(4)
+        .moldable(Moldable::new(4, 0, requests.clone()))
+        .moldable(Moldable::new(5, 300, requests.clone()))
+        .walltime_estimator(Rc::new(ConstantEstimator(60)))
+        .build();
+    assert_eq!(job.moldables.iter().map(|moldable| moldable.walltime).collect::<Vec<_>>(), vec![60, 300]);
+}
+
+#[test]
+fn test_compute_end_inclusive() {
+    assert_eq!(Job::compute_end(10, 1), 10);
+    assert_eq!(Job::compute_end(10, 100), 109);
+    assert_eq!(Job::compute_end(10, 0), 10);
+
+    let platform_config = Rc::new(generate_mock_platform_config(false, 256, 8, 4, 8, false));
+    let available = platform_config.resource_set.default_resources.clone();
+    let all_nodes_moldable = |id: i64, walltime: i64| {
+        Moldable::new(id, walltime, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 8)])]))
+    };
+    let mut jobs = indexmap![
+        1 => JobBuilder::new(1).moldable(all_nodes_moldable(1, 1)).build(),
+        2 => JobBuilder::new(2).moldable(all_nodes_moldable(2, 100)).build(),
+    ];
+    let slot_set = SlotSet::from_platform_config(Rc::clone(&platform_config), 0, 10_000);
+    let mut slot_sets = HashMap::from([("default".into(), slot_set)]);
+    scheduling::schedule_jobs(&mut slot_sets, &mut jobs);
+
+    // Each job occupies exactly its walltime seconds, the next one beginning right after.
+    assert_eq!((jobs[0].begin(), jobs[0].end(), jobs[0].walltime()), (Some(0), Some(0), Some(1)));
+    assert_eq!((jobs[1].begin(), jobs[1].end(), jobs[1].walltime()), (Some(1), Some(100), Some(100)));
+    let slot_set = &slot_sets["default"];
+    assert_eq!(slot_set.available_resources_at(0), Some(&ProcSet::new()));
+    assert_eq!(slot_set.available_resources_at(100), Some(&ProcSet::new()));
+    assert_eq!(slot_set.available_resources_at(101), Some(&available));
+    slot_set.assert_consistent();
+}
@@ -0,0 +1,76 @@
+use crate::model::job::{JobAssignment, JobBuilder, Moldable, ProcSet};
+use crate::scheduler::hierarchy::{HierarchyRequest, HierarchyRequests};
+use crate::scheduler::kamelot;
+use crate::scheduler::observer::{JsonlObserver, SchedulingEvent, SchedulingObserver};
+use crate::scheduler::tests::platform_mock::{generate_mock_platform_config, PlatformBenchMock};
+use indexmap::indexmap;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Default)]
+struct RecordingObserver {
+    events: RefCell<Vec<SchedulingEvent>>,
+}
+impl SchedulingObserver for RecordingObserver {
+    fn on_decision(&self, event: &SchedulingEvent) {
+        self.events.borrow_mut().push(event.clone());
+    }
+}
+
+#[test]
+fn test_scheduling_observer_events() {
+    let platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    let available = platform_config.resource_set.default_resources.clone();
+
+    let moldable_1 = Moldable::new(11, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 2)])]));
+    let job_1 = JobBuilder::new(1).moldable(moldable_1).build();
+    // Requesting more nodes than available
+    let moldable_2 = Moldable::new(12, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 9)])]));
+    let job_2 = JobBuilder::new(2).moldable(moldable_2).build();
+
+    let observer = Rc::new(RecordingObserver::default());
+    let mut platform = PlatformBenchMock::new(platform_config, vec![], indexmap![1 => job_1, 2 => job_2]).with_scheduling_observer(observer.clone());
+    kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+
+    let events = observer.events.borrow();
+    assert_eq!(events.len(), 2);
+    // Unsatisfiable jobs are rejected before scheduling
+    assert_eq!(events[0].job_id, 2);
+    assert_eq!(events[0].begin, None);
+    assert_eq!(events[0].rejection_reason.as_deref(), Some("requests more resources than the platform has"));
+    assert_eq!(
+        events[1],
+        SchedulingEvent {
+            job_id: 1,
+            moldable_index: Some(0),
+            moldable_id: Some(11),
+            begin: Some(0),
+            end: Some(99),
+            core_count: Some(64),
+            rejection_reason: None,
+        }
+    );
+}
+
+#[test]
+fn test_jsonl_observer() {
+    let job = JobBuilder::new(3)
+        .moldable(Moldable::new(4, 10, HierarchyRequests::from_requests(vec![])))
+        .assign(JobAssignment::new(5, 14, ProcSet::from_iter([1..=8]), 0))
+        .build();
+    let observer = JsonlObserver::new(Vec::new());
+    observer.on_decision(&SchedulingEvent::from_job(&job));
+    observer.on_decision(&SchedulingEvent::from_job(&JobBuilder::new(4).build()));
+
+    let output = String::from_utf8(observer.into_inner()).unwrap();
+    let lines = output.lines().collect::<Vec<&str>>();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(
+        lines[0],
+        r#"{"job_id":3,"moldable_index":0,"moldable_id":4,"begin":5,"end":14,"core_count":8,"rejection_reason":null}"#
+    );
+    assert_eq!(
+        lines[1],
+        r#"{"job_id":4,"moldable_index":null,"moldable_id":null,"begin":null,"end":null,"core_count":null,"rejection_reason":"no resources available"}"#
+    );
+}
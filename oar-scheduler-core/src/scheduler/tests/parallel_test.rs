@@ -0,0 +1,58 @@
+use crate::model::job::{Job, JobBuilder, Moldable};
+use crate::scheduler::hierarchy::{HierarchyRequest, HierarchyRequests};
+use crate::scheduler::kamelot;
+use crate::scheduler::scheduling;
+use crate::scheduler::tests::platform_mock::{generate_mock_platform_config, PlatformBenchMock};
+use indexmap::{indexmap, IndexMap};
+
+fn waiting_jobs(available: &crate::model::job::ProcSet) -> IndexMap<i64, Job> {
+    let job = |id: i64, walltime: i64, nodes: u32| {
+        let moldable = Moldable::new(id, walltime, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), nodes)])]));
+        JobBuilder::new(id).moldable(moldable).build()
+    };
+    indexmap![
+        1 => job(1, 100, 9), // More nodes than available
+        2 => job(2, 100, 6),
+        3 => job(3, 2_000_000_000, 1), // Longer than the slot set
+        4 => job(4, 50, 4), // Contends with job 2
+        5 => job(5, 50, 2),
+    ]
+}
+
+#[test]
+fn test_find_unfittable_jobs() {
+    let platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    let available = platform_config.resource_set.default_resources.clone();
+    let platform = PlatformBenchMock::new(platform_config, vec![], indexmap![]);
+    let (slot_sets, _) = kamelot::init_slot_sets(&platform, false);
+
+    let unfittable = kamelot::find_unfittable_jobs(&slot_sets, &waiting_jobs(&available));
+    let mut unfittable = unfittable.into_iter().collect::<Vec<i64>>();
+    unfittable.sort();
+    assert_eq!(unfittable, vec![1, 3]);
+}
+
+#[test]
+fn test_parallel_prepass_keeps_assignments() {
+    let platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    let available = platform_config.resource_set.default_resources.clone();
+
+    // Sequential scheduling without prepass
+    let platform = PlatformBenchMock::new(generate_mock_platform_config(false, 256, 8, 4, 8, false), vec![], indexmap![]);
+    let (mut slot_sets, _) = kamelot::init_slot_sets(&platform, false);
+    let mut expected = waiting_jobs(&available);
+    scheduling::schedule_jobs(&mut slot_sets, &mut expected);
+
+    // Scheduling cycle with the parallel prepass
+    let mut platform = PlatformBenchMock::new(platform_config, vec![], waiting_jobs(&available));
+    kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+
+    let scheduled = platform.get_scheduled_jobs_ref();
+    assert_eq!(scheduled.len(), 3);
+    for job in scheduled {
+        let expected_assignment = expected[&job.id].assignment.as_ref().unwrap();
+        let assignment = job.assignment.as_ref().unwrap();
+        assert_eq!((assignment.begin, &assignment.resources), (expected_assignment.begin, &expected_assignment.resources));
+    }
+    assert!(expected[&1].assignment.is_none() && expected[&3].assignment.is_none());
+}
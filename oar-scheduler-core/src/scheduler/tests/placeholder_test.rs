@@ -146,3 +146,38 @@ fn allow_job_outside_placeholder() {
     assert_eq!(allow2_assignment.end, 59, "Allow job 2 should end at 61");
     assert_eq!(allow2_assignment.resources, ProcSet::from_iter(1..=96), "Allow job 2 should have proc_set [1..=96]");
 }
+
+#[test]
+fn placeholder_from_type_str() {
+    assert_eq!(PlaceholderType::from_type_str("placeholder=foo"), PlaceholderType::Placeholder("foo".into()));
+    assert_eq!(PlaceholderType::from_type_str("allow=foo"), PlaceholderType::Allow("foo".into()));
+    assert_eq!(PlaceholderType::from_type_str("allow="), PlaceholderType::None);
+    assert_eq!(PlaceholderType::from_type_str("timesharing=*,*"), PlaceholderType::None);
+    assert_eq!(PlaceholderType::from_type_str("besteffort"), PlaceholderType::None);
+}
+
+#[test]
+fn allow_job_from_types_accesses_placeholder() {
+    // Placeholder and allow are set through the job types, as done when loading jobs from the database.
+    let platform_config = setup_platform();
+    let available = platform_config.resource_set.default_resources.clone();
+    let mut all_ss = HashMap::from([("default".into(), SlotSet::from_platform_config(Rc::clone(&platform_config), 0, 1000))]);
+
+    let placeholder_job = JobBuilder::new(0)
+        .add_type("placeholder".into(), "foo".into())
+        .moldable_auto(0, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 8)])]))
+        .build();
+    assert_eq!(placeholder_job.placeholder, PlaceholderType::Placeholder("foo".into()));
+    let mut jobs = indexmap![0 => placeholder_job];
+    schedule_jobs(&mut all_ss, &mut jobs);
+    assert!(jobs[0].assignment.is_some(), "Placeholder job should be scheduled");
+
+    let allow_job = JobBuilder::new(1).add_type("allow".into(), "foo".into()).build();
+    let other_job = JobBuilder::new(2).add_type("allow".into(), "bar".into()).build();
+    assert_eq!(allow_job.placeholder, PlaceholderType::Allow("foo".into()));
+
+    let ss = all_ss.get("default").unwrap();
+    let slot_id = ss.slot_at(0, None).unwrap().id();
+    assert_eq!(ss.intersect_slots_intervals(slot_id, slot_id, None, None, &allow_job.placeholder), available);
+    assert_eq!(ss.intersect_slots_intervals(slot_id, slot_id, None, None, &other_job.placeholder), ProcSet::new());
+}
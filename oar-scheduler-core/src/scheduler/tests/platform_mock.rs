@@ -3,6 +3,8 @@ use crate::model::job::{Job, ProcSet};
 use crate::platform::{PlatformConfig, PlatformTrait, ResourceSet};
 use crate::scheduler::calendar::QuotasConfig;
 use crate::scheduler::hierarchy::Hierarchy;
+use crate::scheduler::kamelot::CancellationToken;
+use crate::scheduler::observer::SchedulingObserver;
 use crate::scheduler::quotas::QuotasValue;
 use indexmap::IndexMap;
 use std::collections::HashMap;
@@ -13,6 +15,42 @@ pub struct PlatformBenchMock {
     platform_config: Rc<PlatformConfig>,
     scheduled_jobs: Vec<Job>,
     waiting_jobs: IndexMap<i64, Job>,
+    rejected_jobs: Vec<Job>,
+    scheduling_observer: Option<Rc<dyn SchedulingObserver>>,
+    /// USED accounting by user, returned by `get_sum_accounting_by_user` whatever the window.
+    users_used: HashMap<String, f64>,
+    cancellation_token: Option<CancellationToken>,
+}
+impl PlatformBenchMock {
+    pub fn new(platform_config: PlatformConfig, scheduled_jobs: Vec<Job>, waiting_jobs: IndexMap<i64, Job>) -> Self {
+        PlatformBenchMock {
+            platform_config: Rc::new(platform_config),
+            scheduled_jobs,
+            waiting_jobs,
+            rejected_jobs: vec![],
+            scheduling_observer: None,
+            users_used: HashMap::new(),
+            cancellation_token: None,
+        }
+    }
+    pub fn with_scheduling_observer(mut self, observer: Rc<dyn SchedulingObserver>) -> Self {
+        self.scheduling_observer = Some(observer);
+        self
+    }
+    pub fn with_users_used(mut self, users_used: HashMap<String, f64>) -> Self {
+        self.users_used = users_used;
+        self
+    }
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = Some(cancellation_token);
+        self
+    }
+    pub fn get_scheduled_jobs_ref(&self) -> &Vec<Job> {
+        &self.scheduled_jobs
+    }
+    pub fn get_rejected_jobs_ref(&self) -> &Vec<Job> {
+        &self.rejected_jobs
+    }
 }
 impl PlatformTrait for PlatformBenchMock {
     fn get_now(&self) -> i64 {
@@ -29,7 +67,7 @@ impl PlatformTrait for PlatformBenchMock {
     fn get_scheduled_jobs(&self) -> Vec<Job> {
         self.scheduled_jobs.clone()
     }
-    fn get_waiting_jobs(&self) -> IndexMap<i64, Job> {
+    fn get_waiting_jobs(&self, _queues: Vec<String>) -> IndexMap<i64, Job> {
         self.waiting_jobs.clone()
     }
 
@@ -38,6 +76,10 @@ impl PlatformTrait for PlatformBenchMock {
         self.waiting_jobs.retain(|id, _job| !assigned_jobs.contains_key(id));
         self.scheduled_jobs.extend(assigned_jobs.into_values());
     }
+    fn reject_jobs(&mut self, rejected_jobs: IndexMap<i64, Job>) {
+        self.waiting_jobs.retain(|id, _job| !rejected_jobs.contains_key(id));
+        self.rejected_jobs.extend(rejected_jobs.into_values());
+    }
 
     fn get_sum_accounting_window(&self, queues: &[String], window_start: i64, window_stop: i64) -> (f64, f64) {
         (0f64, 0f64)
@@ -48,7 +90,14 @@ impl PlatformTrait for PlatformBenchMock {
     }
 
     fn get_sum_accounting_by_user(&self, queues: &[String], window_start: i64, window_stop: i64) -> (HashMap<String, f64>, HashMap<String, f64>) {
-        (HashMap::new(), HashMap::new())
+        (HashMap::new(), self.users_used.clone())
+    }
+
+    fn get_scheduling_observer(&self) -> Option<&dyn SchedulingObserver> {
+        self.scheduling_observer.as_deref()
+    }
+    fn get_cancellation_token(&self) -> Option<&CancellationToken> {
+        self.cancellation_token.as_ref()
     }
 }
 
@@ -61,6 +110,7 @@ pub fn generate_mock_platform_config(cache_enabled: bool, res_count: u32, switch
     PlatformConfig {
         resource_set: generate_mock_resource_set(res_count, switch_size, node_size, cpu_size),
         quotas_config: generate_mock_quotas_config(quotas_enable, res_count),
+        queues_quotas_config: HashMap::new(),
         config,
     }
 }
@@ -102,10 +152,14 @@ pub fn generate_mock_resource_set(res_count: u32, switch_size: u32, node_size: u
     ResourceSet {
         nb_resources_not_dead: res_count,
         nb_resources_default_not_dead: res_count,
+        weighted_resources_not_dead: res_count as u64,
+        weighted_resources_default_not_dead: res_count as u64,
         suspendable_resources: ProcSet::new(),
         default_resources: ProcSet::from_iter([1..=res_count]),
         available_upto: vec![], // All resources available until max_time
+        available_from: vec![], // All resources available from now
         hierarchy,
+        core_weight_prefix_sums: vec![],
     }
 }
 pub fn generate_mock_quotas_config(enabled: bool, res_count: u32) -> QuotasConfig {
@@ -0,0 +1,35 @@
+use crate::model::configuration::SchedulingPolicy;
+use crate::model::job::{JobBuilder, Moldable};
+use crate::scheduler::hierarchy::{HierarchyRequest, HierarchyRequests};
+use crate::scheduler::kamelot;
+use crate::scheduler::tests::platform_mock::{generate_mock_platform_config, PlatformBenchMock};
+use indexmap::indexmap;
+
+#[test]
+fn test_scheduling_policies() {
+    // Slot set from 0 to 10000, with 8 nodes of 32 cores
+    for (policy, expected) in [
+        (SchedulingPolicy::EarliestFirst, [(1, 0, 999), (2, 1000, 1499)]),
+        (SchedulingPolicy::LatestFit, [(1, 9001, 10000), (2, 8501, 9000)]),
+    ] {
+        let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+        platform_config.config.scheduler_queue_horizons = Some("default:10000".to_string());
+        platform_config.config.scheduler_placement_policy = policy;
+        let available = platform_config.resource_set.default_resources.clone();
+        let job = |id: i64, walltime: i64, nodes: u32| {
+            let moldable = Moldable::new(id, walltime, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), nodes)])]));
+            JobBuilder::new(id).moldable(moldable).build()
+        };
+        let waiting_jobs = indexmap![1 => job(1, 1000, 4), 2 => job(2, 500, 8)];
+
+        let mut platform = PlatformBenchMock::new(platform_config, vec![], waiting_jobs);
+        kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+
+        let scheduled = platform.get_scheduled_jobs_ref();
+        assert_eq!(scheduled.len(), 2);
+        for (id, begin, end) in expected {
+            let assignment = scheduled.iter().find(|job| job.id == id).unwrap().assignment.as_ref().unwrap();
+            assert_eq!((assignment.begin, assignment.end), (begin, end), "job {} with {:?}", id, policy);
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use crate::model::job::{JobAssignment, JobBuilder, Moldable, ProcSet};
+use crate::platform::PlatformTrait;
+use crate::scheduler::hierarchy::{HierarchyRequest, HierarchyRequests};
+use crate::scheduler::{kamelot, scheduling};
+use crate::scheduler::tests::platform_mock::{generate_mock_platform_config, PlatformBenchMock};
+use indexmap::indexmap;
+
+#[test]
+fn test_besteffort_preemption() {
+    let platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    let available = platform_config.resource_set.default_resources.clone();
+
+    // Besteffort job running on nodes 1 and 2 until the end of the scheduling horizon
+    let besteffort_job = JobBuilder::new(1)
+        .queue("besteffort".into())
+        .add_type_key("besteffort".into())
+        .assign(JobAssignment::new(0, 1_000_000_000, ProcSet::from_iter([1..=64]), 0))
+        .build();
+    // Besteffort job running on node 8, not blocking the normal job
+    let other_besteffort_job = JobBuilder::new(2)
+        .queue("besteffort".into())
+        .add_type_key("besteffort".into())
+        .assign(JobAssignment::new(0, 1_000_000_000, ProcSet::from_iter([225..=256]), 0))
+        .build();
+    // Normal job requesting 7 nodes: can only be scheduled if the first besteffort job is preempted
+    let moldable = Moldable::new(3, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available, vec![("nodes".into(), 7)])]));
+    let job = JobBuilder::new(3).queue("default".into()).moldable(moldable).build();
+
+    let mut platform = PlatformBenchMock::new(platform_config, vec![besteffort_job, other_besteffort_job], indexmap![3 => job]);
+    let queues = vec!["default".to_string()];
+
+    // Without preemption, the job can't be scheduled
+    let (mut slot_sets, _besteffort_jobs) = kamelot::init_slot_sets(&platform, true);
+    let (_stats, preempted) = kamelot::internal_schedule_cycle_with_preemption(&mut platform, &mut slot_sets, &queues, &[]);
+    assert!(preempted.is_empty());
+    assert!(platform.get_scheduled_jobs_ref().iter().all(|job| job.id != 3));
+
+    // With preemption
+    let (mut slot_sets, besteffort_jobs) = kamelot::init_slot_sets(&platform, true);
+    assert_eq!(besteffort_jobs.len(), 2);
+    let (_stats, preempted) = kamelot::internal_schedule_cycle_with_preemption(&mut platform, &mut slot_sets, &queues, &besteffort_jobs);
+    assert_eq!(preempted, vec![1]);
+
+    let job = platform.get_scheduled_jobs_ref().iter().find(|job| job.id == 3).expect("Job 3 should be scheduled");
+    let assignment = job.assignment.as_ref().unwrap();
+    assert_eq!(assignment.begin, platform.get_now());
+    assert!((&assignment.resources & &ProcSet::from_iter([225..=256])).is_empty());
+}
+
+#[test]
+fn test_besteffort_min_runtime_before_kill() {
+    // Besteffort jobs running on node 1 for 10 seconds, and on node 2 for an hour
+    let besteffort_job = |id: i64, begin: i64, resources: ProcSet| {
+        JobBuilder::new(id)
+            .queue("besteffort".into())
+            .add_type_key("besteffort".into())
+            .assign(JobAssignment::new(begin, 1_000_000_000, resources, 0))
+            .build()
+    };
+    let queues = vec!["default".to_string()];
+
+    for (min_runtime_before_kill, expected_preempted) in [(0, vec![1, 2]), (60, vec![2])] {
+        let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+        platform_config.config.scheduler_besteffort_min_runtime_before_kill = min_runtime_before_kill;
+        let available = platform_config.resource_set.default_resources.clone();
+        // Normal job requesting 7 nodes: one of the besteffort jobs must be preempted
+        let moldable = Moldable::new(3, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available, vec![("nodes".into(), 7)])]));
+        let job = JobBuilder::new(3).queue("default".into()).moldable(moldable).build();
+        let scheduled_jobs = vec![besteffort_job(1, -10, ProcSet::from_iter([1..=32])), besteffort_job(2, -3600, ProcSet::from_iter([33..=64]))];
+        let mut platform = PlatformBenchMock::new(platform_config, scheduled_jobs, indexmap![3 => job]);
+
+        let (mut slot_sets, besteffort_jobs) = kamelot::init_slot_sets(&platform, true);
+        let (_stats, mut preempted) = kamelot::internal_schedule_cycle_with_preemption(&mut platform, &mut slot_sets, &queues, &besteffort_jobs);
+        preempted.sort();
+        assert_eq!(preempted, expected_preempted);
+
+        // The job is scheduled without the resources of the spared besteffort job
+        let job = platform.get_scheduled_jobs_ref().iter().find(|job| job.id == 3).expect("Job 3 should be scheduled");
+        let assignment = job.assignment.as_ref().unwrap();
+        assert_eq!(assignment.begin, platform.get_now());
+        if min_runtime_before_kill > 0 {
+            assert!((&assignment.resources & &ProcSet::from_iter([1..=32])).is_empty());
+        }
+    }
+}
+
+#[test]
+fn test_init_slot_sets_for_queues() {
+    let platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    let available = platform_config.resource_set.default_resources.clone();
+
+    // Besteffort job running on nodes 1 to 7 until the end of the scheduling horizon
+    let besteffort_job = JobBuilder::new(1)
+        .queue("besteffort".into())
+        .add_type_key("besteffort".into())
+        .assign(JobAssignment::new(0, 1_000_000_000, ProcSet::from_iter([1..=224]), 0))
+        .build();
+    let platform = PlatformBenchMock::new(platform_config, vec![besteffort_job], indexmap![]);
+    let job = |id: i64, queue: &str| {
+        let moldable = Moldable::new(id, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 2)])]));
+        JobBuilder::new(id).queue(queue.into()).moldable(moldable).build()
+    };
+
+    // A normal job ignores the besteffort occupant
+    let (mut slot_sets, besteffort_jobs) = kamelot::init_slot_sets_for_queues(&platform, &["default".to_string()]);
+    assert!(besteffort_jobs.is_empty());
+    assert_eq!(slot_sets["default"].free_core_count_at(0), 256);
+    let mut jobs = indexmap![2 => job(2, "default")];
+    scheduling::schedule_jobs(&mut slot_sets, &mut jobs);
+    assert_eq!(jobs[0].begin(), Some(0));
+
+    // A besteffort job can't use the resources of the besteffort occupant
+    let (mut slot_sets, besteffort_jobs) = kamelot::init_slot_sets_for_queues(&platform, &["besteffort".to_string()]);
+    assert_eq!(besteffort_jobs.len(), 1);
+    assert_eq!(slot_sets["default"].free_core_count_at(0), 32);
+    let mut jobs = indexmap![3 => job(3, "besteffort")];
+    scheduling::schedule_jobs(&mut slot_sets, &mut jobs);
+    assert!(jobs[0].assignment.is_none());
+}
@@ -0,0 +1,50 @@
+use crate::platform::{proc_set_from_bitmap, proc_set_from_oar_string, proc_set_to_bitmap, proc_set_to_oar_string, ProcSet, ProcSetCoresOp};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+#[test]
+fn test_proc_set_oar_string() {
+    assert_eq!(proc_set_to_oar_string(&ProcSet::new()), "");
+    assert_eq!(proc_set_to_oar_string(&ProcSet::from_iter([7])), "7");
+    assert_eq!(proc_set_to_oar_string(&ProcSet::from_iter([1..=4, 7..=7, 10..=12])), "1-4 7 10-12");
+
+    assert_eq!(proc_set_from_oar_string(""), ProcSet::new());
+    assert_eq!(proc_set_from_oar_string("  "), ProcSet::new());
+    assert_eq!(proc_set_from_oar_string("7"), ProcSet::from_iter([7]));
+    assert_eq!(proc_set_from_oar_string("1-4 7 10-12"), ProcSet::from_iter([1..=4, 7..=7, 10..=12]));
+    // Unordered and overlapping ranges are merged
+    assert_eq!(proc_set_from_oar_string("10-12 3-5 1-4"), ProcSet::from_iter([1..=5, 10..=12]));
+    // Malformed ranges are ignored
+    assert_eq!(proc_set_from_oar_string("1-2 a 5-3 4- 8"), ProcSet::from_iter([1..=2, 8..=8]));
+}
+
+#[test]
+fn test_proc_set_oar_string_round_trip() {
+    let mut rng = StdRng::seed_from_u64(42);
+    for _ in 0..1000 {
+        let mut proc_set = ProcSet::new();
+        for _ in 0..rng.random_range(0..10) {
+            let start = rng.random_range(0..1000);
+            proc_set.ranges_insert(start..=start + rng.random_range(0..20));
+        }
+        let string = proc_set_to_oar_string(&proc_set);
+        assert_eq!(proc_set_from_oar_string(&string), proc_set, "'{}'", string);
+        assert_eq!(proc_set_to_oar_string(&proc_set_from_oar_string(&string)), string);
+    }
+}
+
+#[test]
+fn test_proc_set_bitmap_round_trip() {
+    let sparse = ProcSet::from_iter([0..=0, 9..=9, 17..=19, 1000..=1000]);
+    let dense = ProcSet::from_iter([0..=511, 520..=1023]);
+    for proc_set in [sparse, dense, ProcSet::new()] {
+        let bitmap = proc_set_to_bitmap(&proc_set, 1024);
+        assert_eq!(bitmap.len(), 128);
+        assert_eq!(bitmap.iter().map(|byte| byte.count_ones()).sum::<u32>(), proc_set.core_count());
+        assert_eq!(proc_set_from_bitmap(&bitmap), proc_set);
+    }
+
+    assert_eq!(proc_set_to_bitmap(&ProcSet::from_iter([0..=0, 9..=10]), 11), vec![0b0000_0001, 0b0000_0110]);
+    // Resources beyond the bitmap size are ignored
+    assert_eq!(proc_set_to_bitmap(&ProcSet::from_iter([4..=12, 20..=30]), 10), vec![0b1111_0000, 0b0000_0011]);
+}
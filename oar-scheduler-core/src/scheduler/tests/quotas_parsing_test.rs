@@ -1,5 +1,5 @@
 use crate::scheduler::calendar::parsing::{PeriodicalEntry, PeriodicalJsonEntry, QuotasConfigEntries};
-use crate::scheduler::calendar::QuotasConfig;
+use crate::scheduler::calendar::{QuotasConfig, QuotasParseError};
 use serde_json::Value;
 use std::collections::HashMap;
 
@@ -18,9 +18,9 @@ fn test_parse_periodical_entry() {
             }
         }"#.to_string();
     let entries = serde_json::from_str::<HashMap<Box<str>, Value>>(&rules_json).expect("Failed to parse quotas config base JSON");
-    let mut config_entries = QuotasConfigEntries::new(entries, 100);
+    let mut config_entries = QuotasConfigEntries::new(entries, 100, 100);
 
-    let result = PeriodicalEntry::from_json_entry(&entry, &mut config_entries);
+    let result = PeriodicalEntry::from_json_entry(&entry, 0, &mut config_entries).unwrap();
     assert_eq!(result.len(), 5); // 5 weekdays
 
     // Verify first entry (Monday)
@@ -44,9 +44,9 @@ fn test_overnight_period() {
             }
         }"#.to_string();
     let entries = serde_json::from_str::<HashMap<Box<str>, Value>>(&rules_json).expect("Failed to parse quotas config base JSON");
-    let mut config_entries = QuotasConfigEntries::new(entries, 100);
+    let mut config_entries = QuotasConfigEntries::new(entries, 100, 100);
 
-    let result = PeriodicalEntry::from_json_entry(&entry, &mut config_entries);
+    let result = PeriodicalEntry::from_json_entry(&entry, 0, &mut config_entries).unwrap();
 
     // Should have entries for each day, with proper overflow handling
     assert_eq!(result.len(), 7 * 2); // 7 days * 2 entries per day (split at midnight)
@@ -60,7 +60,7 @@ fn test_quotas_config() {
             "quotas_2": {"*,*,*,/": [-1, -1, -1]},
             "oneshot": [["2025-08-27 15:47", "2025-08-28 15:47", "quotas_2", ""]]
         }"#.to_string();
-    let quotas_config = QuotasConfig::load_from_json(rules_json, true, 0, 2 * 7 * 24 * 3600);
+    let quotas_config = QuotasConfig::load_from_json(rules_json, true, 0, 0, 2 * 7 * 24 * 3600).unwrap();
 
 
     let calendar = quotas_config.calendar.unwrap();
@@ -72,3 +72,93 @@ fn test_quotas_config() {
     assert_eq!(periodical.week_begin_time, 0);
     assert_eq!(periodical.week_end_time, 7 * 24 * 3600 - 1);
 }
+
+#[test]
+fn test_quotas_config_parse_errors() {
+    let load = |json: &str| QuotasConfig::load_from_json(json.to_string(), true, 100, 100, 2 * 7 * 24 * 3600).unwrap_err();
+
+    // Rule value not being an array
+    let error = load(r#"{"quotas": {"*,*,*,*": 10}}"#);
+    assert!(matches!(&error, QuotasParseError::Key { key, message } if key.as_ref() == "quotas" && message.contains("expected a sequence")));
+    // Periodical tuple without description
+    let error = load(r#"{"periodical": [["* * * *", "quotas_1"]], "quotas_1": {"*,*,*,*": [1, -1, -1]}}"#);
+    assert!(matches!(&error, QuotasParseError::Key { key, message } if key.as_ref() == "periodical" && message.contains("invalid length 2")));
+    assert!(error.to_string().starts_with("invalid 'periodical' entry in the quotas configuration"));
+    // Malformed JSON, with the line of the error
+    let error = load("{\n\"quotas\": {\n}\n,}");
+    assert!(matches!(&error, QuotasParseError::Json(message) if message.contains("line 4")));
+
+    // The file path is included in the errors of load_from_file
+    let path = std::env::temp_dir().join(format!("oar_quotas_parse_error_{}.json", std::process::id()));
+    std::fs::write(&path, r#"{"oneshot": "not a list"}"#).unwrap();
+    let path = path.to_str().unwrap();
+    let error = QuotasConfig::load_from_file(path, true, 100, 100, 3600).unwrap_err();
+    std::fs::remove_file(path).unwrap();
+    let QuotasParseError::File { error: file_error, .. } = &error else {
+        panic!("Expected a file error, got {:?}", error);
+    };
+    assert!(matches!(file_error.as_ref(), QuotasParseError::Key { key, .. } if key.as_ref() == "oneshot"));
+    assert!(error.to_string().contains(path));
+    assert!(matches!(QuotasConfig::load_from_file(path, true, 100, 100, 3600), Err(QuotasParseError::Io { .. })));
+}
+
+#[test]
+fn test_quotas_config_rules_errors() {
+    let load = |json: &str| QuotasConfig::load_from_json(json.to_string(), true, 100, 100, 2 * 7 * 24 * 3600).unwrap_err();
+
+    // Key without 4 parts
+    let error = load(r#"{"quotas": {"*,*,*": [1, -1, -1]}}"#);
+    assert!(matches!(&error, QuotasParseError::Key { key, message } if key.as_ref() == "quotas" && message.contains("expected 4 parts")));
+    // Non-numeric values, and missing values
+    let error = load(r#"{"quotas": {"*,*,*,*": ["many", -1, -1]}}"#);
+    assert!(matches!(&error, QuotasParseError::Key { key, message } if key.as_ref() == "quotas" && message.contains("'many'")));
+    let error = load(r#"{"quotas": {"*,*,*,*": ["x*ALL", -1, -1]}}"#);
+    assert!(matches!(&error, QuotasParseError::Key { message, .. } if message.contains("'x'")));
+    let error = load(r#"{"quotas": {"*,*,*,*": [true, -1, -1]}}"#);
+    assert!(matches!(&error, QuotasParseError::Key { message, .. } if message.contains("expected a number or a string")));
+    let error = load(r#"{"quotas": {"*,*,*,*": [1, -1]}}"#);
+    assert!(matches!(&error, QuotasParseError::Key { message, .. } if message.contains("at least 3 quotas values")));
+    // Malformed rules referenced by a periodical, and missing rules
+    let error = load(r#"{"periodical": [["* * * *", "quotas_1", ""]], "quotas_1": {"*,*,*,*": ["1.5x", -1, -1]}}"#);
+    assert!(matches!(&error, QuotasParseError::Key { key, .. } if key.as_ref() == "quotas_1"));
+    let error = load(r#"{"oneshot": [["2025-08-27 15:47", "2025-08-28 15:47", "quotas_2", ""]]}"#);
+    assert_eq!(error, QuotasParseError::MissingRules("quotas_2".into()));
+}
+
+#[test]
+fn test_quotas_config_period_errors() {
+    let load_period = |period: &str| {
+        let json = format!(r#"{{"periodical": [["{}", "quotas_1", ""]], "quotas_1": {{"*,*,*,*": [1, -1, -1]}}}}"#, period);
+        QuotasConfig::load_from_json(json, true, 100, 100, 2 * 7 * 24 * 3600)
+    };
+    assert!(load_period("08:00-19:00 mon-fri * *").is_ok());
+
+    for (period, message) in [
+        ("08:00-19:00 mon-fri *", "expected 4 parts"),
+        ("08:00-19:00 mon-fri 1 *", "month and day"),
+        ("08:00 mon-fri * *", "invalid time range"),
+        ("8h-19:00 mon-fri * *", "invalid time '8h'"),
+        ("08:00-25:00 mon-fri * *", "invalid time '25:00'"),
+        ("08:00-19:00 monday * *", "invalid day 'monday'"),
+        ("08:00-19:00 mon-xyz * *", "invalid day 'xyz'"),
+    ] {
+        let error = load_period(period).unwrap_err();
+        assert!(
+            matches!(&error, QuotasParseError::Key { key, message: m } if key.as_ref() == "periodical" && m.contains(message) && m.contains(period)),
+            "period '{}' gave {:?}",
+            period,
+            error
+        );
+    }
+
+    // Oneshot with an invalid date, and ending before it begins
+    let load_oneshot = |begin: &str, end: &str| {
+        let json = format!(r#"{{"oneshot": [["{}", "{}", "quotas_1", ""]], "quotas_1": {{"*,*,*,*": [1, -1, -1]}}}}"#, begin, end);
+        QuotasConfig::load_from_json(json, true, 100, 100, 2 * 7 * 24 * 3600)
+    };
+    assert!(load_oneshot("2025-08-27 15:47", "2025-08-28 15:47").is_ok());
+    let error = load_oneshot("2025-08-27", "2025-08-28 15:47").unwrap_err();
+    assert!(matches!(&error, QuotasParseError::Key { key, message } if key.as_ref() == "oneshot" && message.contains("begin time")));
+    let error = load_oneshot("2025-08-28 15:47", "2025-08-27 15:47").unwrap_err();
+    assert!(matches!(&error, QuotasParseError::Key { key, message } if key.as_ref() == "oneshot" && message.contains("must be after")));
+}
@@ -1,5 +1,6 @@
-use crate::model::job::{JobAssignment, JobBuilder, Moldable, ProcSet, ProcSetCoresOp};
-use crate::platform::PlatformConfig;
+use crate::model::job::{Job, JobAssignment, JobBuilder, Moldable, ProcSet, ProcSetCoresOp};
+use crate::model::configuration::QuotasAllNbResourcesMode;
+use crate::platform::{quotas_all_value, quotas_default_all_value, PlatformConfig, ResourceSet};
 use crate::scheduler::calendar::QuotasConfig;
 use crate::scheduler::hierarchy::{HierarchyRequest, HierarchyRequests};
 use crate::scheduler::quotas::*;
@@ -21,13 +22,15 @@ fn test_quotas_rules_from_json() {
     let quotas_rules_json = r#"{
             "quotas": {
                 "*,*,*,john": [100, "ALL", "0.5*ALL"],
-                "*,projA,*,*": ["34", "ALL", "2*ALL"]
+                "*,projA,*,*": ["34", "ALL", "2*ALL"],
+                "*,*,*,jane": ["ALL", -1, -1, 1.0, "0.5*ALL"]
             }
         }"#.to_string();
 
-    let quotas = QuotasConfig::load_from_json(quotas_rules_json, true, 100, 2 * 7 * 24 * 3600).default_rules;
+    // The default resources limit, i.e., the fifth value, counts ALL as the number of default resources
+    let quotas = QuotasConfig::load_from_json(quotas_rules_json, true, 100, 64, 2 * 7 * 24 * 3600).unwrap().default_rules;
 
-    assert_eq!(quotas.len(), 2);
+    assert_eq!(quotas.len(), 3);
     assert!(quotas.contains_key(&("*".into(), "*".into(), "*".into(), "john".into())));
     assert!(quotas.contains_key(&("*".into(), "projA".into(), "*".into(), "*".into())));
     assert_eq!(
@@ -38,6 +41,10 @@ fn test_quotas_rules_from_json() {
         quotas[&("*".into(), "projA".into(), "*".into(), "*".into())],
         QuotasValue::new(Some(34), Some(100), Some(200*3600))
     );
+    assert_eq!(
+        quotas[&("*".into(), "*".into(), "*".into(), "jane".into())],
+        QuotasValue::new(Some(100), None, None).with_default_resources(Some(32))
+    );
 }
 
 #[test]
@@ -155,6 +162,46 @@ fn test_quotas_one_job_rule_nb_res_2() {
     assert_eq!(sched.resources.core_count(), 64);
 }
 
+#[test]
+fn test_quotas_no_quotas_job_types() {
+    for no_quotas_type in ["noquotas", "no_quotas"] {
+        let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, true);
+        platform_config.quotas_config = QuotasConfig::new(
+            true,
+            None,
+            HashMap::from([(("*".into(), "*".into(), "*".into(), "/".into()), QuotasValue::new(Some(63), None, None))]),
+            Box::new(["*".into()]),
+        );
+        let platform_config = Rc::new(platform_config);
+
+        let available = platform_config.resource_set.default_resources.clone();
+        let ss = SlotSet::from_platform_config(Rc::clone(&platform_config), 0, 1000);
+        let mut all_ss = HashMap::from([("default".into(), ss)]);
+
+        let requests = HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 2)])]);
+        let job_1 = JobBuilder::new(1)
+            .user("user".into())
+            .add_type_key(no_quotas_type.into())
+            .moldable(Moldable::new(1, 60, requests.clone()))
+            .build();
+        let job_2 = JobBuilder::new(2)
+            .user("user".into())
+            .moldable(Moldable::new(2, 60, requests))
+            .build();
+        assert!(job_1.no_quotas);
+        assert!(!job_2.no_quotas);
+
+        let mut jobs = indexmap![1 => job_1, 2 => job_2];
+        scheduling::schedule_jobs(&mut all_ss, &mut jobs);
+
+        // The rule of 63 resources is exceeded by both jobs, but only the normal job is limited by it
+        let assignment = jobs[0].assignment.as_ref().expect("noquotas job should be scheduled");
+        assert_eq!(assignment.begin, 0);
+        assert_eq!(assignment.resources.core_count(), 64);
+        assert!(jobs[1].assignment.is_none());
+    }
+}
+
 #[test]
 fn test_quotas_four_jobs_rule_1() {
     // Quotas: 16 procs max, except project "yop" (max 1 running job)
@@ -189,6 +236,7 @@ fn test_quotas_four_jobs_rule_1() {
     // Insert scheduled jobs into slots
     let ss = all_ss.get_mut("default").unwrap();
     ss.split_slots_for_jobs_and_update_resources(&jobs, true, true, None);
+    ss.assert_consistent();
 
     // Now schedule two more jobs
     let moldable_j3 = Moldable::new(
@@ -247,6 +295,7 @@ fn test_quotas_three_jobs_rule_1() {
     let jobs = vec![&job];
     let ss = all_ss.get_mut("default").unwrap();
     ss.split_slots_for_jobs_and_update_resources(&jobs, true, true, None);
+    ss.assert_consistent();
 
     // Now schedule two more jobs
     let moldable_j2 = Moldable::new(
@@ -365,3 +414,295 @@ fn test_quotas_two_jobs_job_type_proc() {
     assert_eq!(sched1.begin, 0);
     assert_eq!(sched2.begin, 50);
 }
+
+#[test]
+fn test_quotas_check_with_additional_matches_increment_and_check() {
+    let quotas_config = QuotasConfig::new(
+        true,
+        None,
+        HashMap::from([
+            (("*".into(), "*".into(), "*".into(), "/".into()), QuotasValue::new(Some(64), Some(2), None)),
+            (("*".into(), "projA".into(), "*".into(), "*".into()), QuotasValue::new(Some(100), None, Some(3000))),
+            (("default".into(), "*".into(), "yop".into(), "*".into()), QuotasValue::new(None, Some(1), None)),
+        ]),
+        Box::new(["*".into(), "yop".into()]),
+    );
+    let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, true);
+    platform_config.quotas_config = quotas_config;
+    let platform_config = Rc::new(platform_config);
+
+    let mut quotas = Quotas::from_platform_config(Rc::clone(&platform_config));
+    let running = JobBuilder::new(1).user("toto".into()).project("projA".into()).queue("default".into()).build();
+    quotas.increment_for_job(&running, 20, 32, 32);
+    let counters = quotas.counters().clone();
+
+    let jobs = [
+        JobBuilder::new(2).user("toto".into()).queue("default".into()).build(),
+        JobBuilder::new(3).user("john".into()).project("projA".into()).queue("default".into()).build(),
+        JobBuilder::new(4).user("john".into()).queue("default".into()).add_type_key("yop".into()).build(),
+        JobBuilder::new(5).user("toto".into()).queue("default".into()).add_type_key("container".into()).build(),
+        JobBuilder::new(6).queue("besteffort".into()).build(),
+    ];
+    for job in jobs.iter() {
+        for (width, resource_count) in [(10, 16), (20, 32), (100, 64)] {
+            let mut incremented = quotas.clone();
+            incremented.increment_for_job(job, width, resource_count, resource_count);
+            assert_eq!(
+                quotas.check_with_additional(job, resource_count, resource_count, width),
+                incremented.check(job),
+                "job {} with width {} and {} resources",
+                job.id,
+                width,
+                resource_count
+            );
+        }
+    }
+    // Counters are left untouched
+    assert_eq!(quotas.counters(), &counters);
+}
+
+#[test]
+fn test_quotas_resources_times_weight() {
+    // GPU jobs count double against the resources times budget of 1 hour
+    let quotas_rules_json = r#"{
+            "quotas": {
+                "*,*,*,*": [-1, -1, 1],
+                "*,*,gpu,*": [-1, -1, 1, 2.0]
+            },
+            "job_types": ["*", "gpu"]
+        }"#
+    .to_string();
+    let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, true);
+    platform_config.quotas_config = QuotasConfig::load_from_json(quotas_rules_json, true, 100, 100, 2 * 7 * 24 * 3600).unwrap();
+    let platform_config = Rc::new(platform_config);
+    let default_rules = &platform_config.quotas_config.default_rules;
+    assert_eq!(default_rules[&("*".into(), "*".into(), "*".into(), "*".into())].resources_times_weight(), 1.0);
+    assert_eq!(default_rules[&("*".into(), "*".into(), "gpu".into(), "*".into())].resources_times_weight(), 2.0);
+
+    let all_key: QuotasKey = ("*".into(), "*".into(), "*".into(), "*".into());
+    let counted_resources_times = |job: &Job| {
+        let mut quotas = Quotas::from_platform_config(Rc::clone(&platform_config));
+        quotas.increment_for_job(job, 100, 16, 16);
+        (quotas.counters()[&all_key].clone(), quotas.check(job))
+    };
+
+    let job = JobBuilder::new(1).queue("default".into()).build();
+    let (counts, exceeded) = counted_resources_times(&job);
+    assert_eq!(counts, QuotasValue::new(Some(16), Some(1), Some(1600)).with_default_resources(Some(16)));
+    assert_eq!(exceeded, None);
+
+    let gpu_job = JobBuilder::new(2).queue("default".into()).add_type_key("gpu".into()).build();
+    let (counts, exceeded) = counted_resources_times(&gpu_job);
+    assert_eq!(counts, QuotasValue::new(Some(16), Some(1), Some(3200)).with_default_resources(Some(16)));
+    assert_eq!(exceeded, None);
+
+    // 16 resources during 150 seconds (2400 resource-seconds) only fit in the 3600 budget when not doubled
+    let quotas = Quotas::from_platform_config(Rc::clone(&platform_config));
+    assert!(quotas.check_with_additional(&job, 16, 16, 150).is_none());
+    assert!(quotas.check_with_additional(&gpu_job, 16, 16, 150).is_some());
+}
+
+#[test]
+fn test_quotas_queue_specific_rules() {
+    let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, true);
+    // Globally, each user can use 4 nodes, but only 1 node in the gpu queue
+    platform_config.quotas_config = QuotasConfig::new(
+        true,
+        None,
+        HashMap::from([(("*".into(), "*".into(), "*".into(), "/".into()), QuotasValue::new(Some(128), None, None))]),
+        Box::new(["*".into()]),
+    );
+    let gpu_quotas_json = r#"{ "quotas": { "*,*,*,/": [32, -1, -1] } }"#.to_string();
+    let gpu_quotas_config = QuotasConfig::load_from_json(gpu_quotas_json, true, 256, 256, 3600).unwrap();
+    platform_config.queues_quotas_config = HashMap::from([("gpu".to_string(), gpu_quotas_config)]);
+    let platform_config = Rc::new(platform_config);
+
+    let available = platform_config.resource_set.default_resources.clone();
+    let ss = SlotSet::from_platform_config(Rc::clone(&platform_config), 0, 1000);
+    let mut all_ss = HashMap::from([("default".into(), ss)]);
+
+    let job = |id: i64, queue: &str| {
+        JobBuilder::new(id)
+            .user("user".into())
+            .queue(queue.into())
+            .moldable(Moldable::new(id, 60, HierarchyRequests::new_single(available.clone(), vec![("nodes".into(), 1)])))
+            .build()
+    };
+    let mut jobs = indexmap![1 => job(1, "gpu"), 2 => job(2, "gpu"), 3 => job(3, "default"), 4 => job(4, "default")];
+    scheduling::schedule_jobs(&mut all_ss, &mut jobs);
+
+    // The second gpu job hits the gpu queue limit, not present in the global rules
+    let begins = jobs.values().map(|job| job.begin().unwrap()).collect::<Vec<i64>>();
+    assert_eq!(begins, vec![0, 60, 0, 0]);
+}
+
+#[test]
+fn test_quotas_explain() {
+    let quotas_config = QuotasConfig::new(
+        true,
+        None,
+        HashMap::from([
+            (("*".into(), "*".into(), "*".into(), "*".into()), QuotasValue::new(Some(128), None, None)),
+            (("*".into(), "*".into(), "*".into(), "john".into()), QuotasValue::new(Some(32), Some(1), None)),
+            (("*".into(), "projA".into(), "*".into(), "/".into()), QuotasValue::new(None, Some(4), None)),
+        ]),
+        Box::new(["*".into()]),
+    );
+    let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, true);
+    platform_config.quotas_config = quotas_config;
+    let platform_config = Rc::new(platform_config);
+
+    let john = JobBuilder::new(1).user("john".into()).queue("default".into()).build();
+    let toto = JobBuilder::new(2).user("toto".into()).queue("default".into()).build();
+    let toto_proj = JobBuilder::new(3).user("toto".into()).project("projA".into()).queue("default".into()).build();
+
+    let mut quotas = Quotas::from_platform_config(Rc::clone(&platform_config));
+    quotas.increment_for_job(&john, 10, 64, 64);
+
+    // Named rule, exceeded
+    let explanation = quotas.explain(&john).unwrap();
+    assert_eq!(explanation.rule_key, ("*".into(), "*".into(), "*".into(), "john".into()));
+    assert_eq!(explanation.counter_key, explanation.rule_key);
+    assert_eq!(explanation.counters, QuotasValue::new(Some(64), Some(1), Some(640)).with_default_resources(Some(64)));
+    assert_eq!(explanation.limits, QuotasValue::new(Some(32), Some(1), None));
+    assert_eq!(explanation.exceeded, Some(("Resources exceeded".into(), 32)));
+    assert_eq!(quotas.check(&john).map(|(_, rule, limit)| (rule, limit)), Some((explanation.rule_key.clone(), 32)));
+
+    // Wildcard rule, not exceeded
+    let explanation = quotas.explain(&toto).unwrap();
+    assert_eq!(explanation.rule_key, ("*".into(), "*".into(), "*".into(), "*".into()));
+    assert_eq!(explanation.counters, QuotasValue::new(Some(64), Some(1), Some(640)).with_default_resources(Some(64)));
+    assert_eq!(explanation.exceeded, None);
+
+    // "For each" rule, with a counter not incremented yet
+    let explanation = quotas.explain(&toto_proj).unwrap();
+    assert_eq!(explanation.rule_key, ("*".into(), "projA".into(), "*".into(), "/".into()));
+    assert_eq!(explanation.counter_key, ("*".into(), "projA".into(), "*".into(), "toto".into()));
+    assert_eq!(explanation.counters, QuotasValue::new(Some(0), Some(0), Some(0)).with_default_resources(Some(0)));
+    assert_eq!(explanation.exceeded, None);
+
+    // Verbose path of the slots quotas check, counting the job itself
+    let ss = SlotSet::from_platform_config(Rc::clone(&platform_config), 0, 100);
+    let slot_id = ss.first_slot().unwrap().id();
+    assert_eq!(check_slots_quotas(ss.iter().between(slot_id, slot_id), &john, 0, 9, 48, 48).map(|(_, _, limit)| limit), Some(32));
+    let explanations = explain_slots_quotas(ss.iter().between(slot_id, slot_id), &john, 0, 9, 48, 48);
+    assert_eq!(explanations.len(), 1);
+    assert_eq!(explanations[0].rule_key, ("*".into(), "*".into(), "*".into(), "john".into()));
+    assert_eq!(explanations[0].counters, QuotasValue::new(Some(48), Some(1), Some(480)).with_default_resources(Some(48)));
+    assert_eq!(explanations[0].exceeded, Some(("Resources exceeded".into(), 32)));
+}
+
+#[test]
+fn test_quotas_weighted_core_count() {
+    let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, true);
+    // Resource 0 weighs 1 and resource 1 weighs 4, the others weigh 2.
+    platform_config.resource_set.core_weight_prefix_sums =
+        ResourceSet::core_weight_prefix_sums([1, 4].into_iter().chain(std::iter::repeat_n(2, 254)));
+    assert_eq!(platform_config.resource_set.weighted_core_count(&ProcSet::from_iter([0, 1])), 5);
+    assert_eq!(platform_config.resource_set.weighted_core_count(&ProcSet::from_iter([1, 300])), 5);
+    assert_eq!(platform_config.resource_set.weighted_core_count(&ProcSet::from_iter(64..=127)), 128);
+    // The mock default resources are 1..=256: resource 1 weighs 4, resources 2..=255 weigh 2 and resource 256 weighs 1.
+    platform_config.config.quotas_all_nb_resources_mode = QuotasAllNbResourcesMode::All;
+    assert_eq!(quotas_all_value(&platform_config.config, &platform_config.resource_set), 4 + 254 * 2 + 1);
+    assert_eq!(quotas_default_all_value(&platform_config.config, &platform_config.resource_set), 4 + 254 * 2 + 1);
+    platform_config.resource_set.weighted_resources_not_dead = 300;
+    platform_config.resource_set.weighted_resources_default_not_dead = 200;
+    platform_config.config.quotas_all_nb_resources_mode = QuotasAllNbResourcesMode::DefaultNotDead;
+    assert_eq!(quotas_all_value(&platform_config.config, &platform_config.resource_set), 300);
+    assert_eq!(quotas_default_all_value(&platform_config.config, &platform_config.resource_set), 200);
+
+    // Two nodes (64 resources) weigh 128, exceeding the limit of 100 resources.
+    platform_config.quotas_config = QuotasConfig::new(
+        true,
+        None,
+        HashMap::from([(("*".into(), "*".into(), "*".into(), "/".into()), QuotasValue::new(Some(100), None, None))]),
+        Box::new(["*".into()]),
+    );
+    let platform_config = Rc::new(platform_config);
+    let available = platform_config.resource_set.default_resources.clone();
+    let ss = SlotSet::from_platform_config(Rc::clone(&platform_config), 0, 1000);
+    let mut all_ss = HashMap::from([("default".into(), ss)]);
+
+    let jobs = [(1, 1), (2, 2)].map(|(id, nodes)| {
+        let requests = HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), nodes)])]);
+        (id, JobBuilder::new(id).user("user".into()).moldable(Moldable::new(id, 60, requests)).build())
+    });
+    let mut jobs = jobs.into_iter().collect();
+    scheduling::schedule_jobs(&mut all_ss, &mut jobs);
+
+    assert!(jobs[0].assignment.is_some());
+    assert!(jobs[1].assignment.is_none());
+}
+
+#[test]
+fn test_quotas_default_resources_limit() {
+    let quotas_rules_json = r#"{
+            "quotas": {
+                "*,*,*,*": [100, -1, -1, 1.0, 32]
+            }
+        }"#
+    .to_string();
+    let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, true);
+    platform_config.quotas_config = QuotasConfig::load_from_json(quotas_rules_json, true, 100, 100, 2 * 7 * 24 * 3600).unwrap();
+    // Only the first half of the resources are of type default.
+    platform_config.resource_set.default_resources = ProcSet::from_iter(0..=127);
+    let platform_config = Rc::new(platform_config);
+    let all_key: QuotasKey = ("*".into(), "*".into(), "*".into(), "*".into());
+    assert_eq!(
+        platform_config.quotas_config.default_rules[&all_key],
+        QuotasValue::new(Some(100), None, None).with_default_resources(Some(32))
+    );
+
+    let resource_set = &platform_config.resource_set;
+    let mostly_default = ProcSet::from_iter(80..=143);
+    let mostly_other = ProcSet::from_iter(112..=175);
+    assert_eq!(resource_set.weighted_default_core_count(&mostly_default), 48);
+    assert_eq!(resource_set.weighted_default_core_count(&mostly_other), 16);
+
+    // 64 resources are within the resources limit, but 48 default resources exceed the default resources limit.
+    let job = JobBuilder::new(1).queue("default".into()).build();
+    let quotas = Quotas::from_platform_config(Rc::clone(&platform_config));
+    assert_eq!(
+        quotas.check_with_additional(&job, 64, 48, 100).map(|(msg, _, limit)| (msg, limit)),
+        Some(("Default resources exceeded".into(), 32))
+    );
+    assert!(quotas.check_with_additional(&job, 64, 16, 100).is_none());
+
+    // Counters are incremented with the default resources of the job.
+    let mut quotas = Quotas::from_platform_config(Rc::clone(&platform_config));
+    quotas.increment_for_job(&job, 100, 64, 16);
+    assert_eq!(quotas.counters()[&all_key], QuotasValue::new(Some(64), Some(1), Some(6400)).with_default_resources(Some(16)));
+    assert!(quotas.check_with_additional(&JobBuilder::new(2).queue("default".into()).build(), 16, 16, 100).is_none());
+    assert!(quotas.check_with_additional(&JobBuilder::new(3).queue("default".into()).build(), 16, 17, 100).is_some());
+}
+
+#[test]
+fn test_quotas_split_off() {
+    let all_key: QuotasKey = ("*".into(), "*".into(), "*".into(), "*".into());
+    let job = JobBuilder::new(1).queue("default".into()).build();
+    for period_counters in [false, true] {
+        let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, true);
+        platform_config.config.quotas_period_counters = period_counters;
+        platform_config.quotas_config = QuotasConfig::new(
+            true,
+            None,
+            HashMap::from([(all_key.clone(), QuotasValue::new(Some(100), None, None))]),
+            Box::new(["*".into()]),
+        );
+        let mut quotas = Quotas::from_platform_config(Rc::new(platform_config));
+        quotas.increment_for_job(&job, 100, 64, 64);
+
+        // A slot of 100 seconds split into 40 and 60 seconds
+        let after = quotas.split_off(40, 60);
+        let counters = |quotas: &Quotas| quotas.counters()[&all_key].clone();
+        if period_counters {
+            // The resources times are shared between both parts
+            assert_eq!(counters(&quotas), QuotasValue::new(Some(64), Some(1), Some(2560)).with_default_resources(Some(64)));
+            assert_eq!(counters(&after), QuotasValue::new(Some(64), Some(1), Some(3840)).with_default_resources(Some(64)));
+        } else {
+            // The counters are duplicated in both parts, as in the Python scheduler
+            assert_eq!(counters(&quotas), QuotasValue::new(Some(64), Some(1), Some(6400)).with_default_resources(Some(64)));
+            assert_eq!(counters(&after), counters(&quotas));
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use crate::model::job::{Job, JobAssignment, JobBuilder, Moldable, ProcSet};
+use crate::scheduler::calendar::QuotasConfig;
+use crate::scheduler::hierarchy::{HierarchyRequest, HierarchyRequests};
+use crate::scheduler::quotas::QuotasValue;
+use crate::scheduler::scheduling::{find_reservation_window, ReservationWindow};
+use crate::scheduler::slotset::SlotSet;
+use crate::scheduler::tests::platform_mock::generate_mock_platform_config;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Finds the window of a reservation of two nodes at 100 for 60 seconds, with the job type `max_delay=<max_delay>`,
+/// in a slot set ending at 1000 in which a job uses two other nodes until `busy_end`.
+/// The quotas limit the platform to 100 resources, so that the reservation can't run alongside the busy job.
+fn find_window_with_busy_quotas(max_delay: i64, busy_end: i64) -> ReservationWindow {
+    let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, true);
+    platform_config.quotas_config = QuotasConfig::new(
+        true,
+        None,
+        HashMap::from([(("*".into(), "*".into(), "*".into(), "*".into()), QuotasValue::new(Some(100), None, None))]),
+        Box::new(["*".into()]),
+    );
+    let platform_config = Rc::new(platform_config);
+    let available = platform_config.resource_set.default_resources.clone();
+    let two_nodes = |id: i64| {
+        let request = HierarchyRequest::new(available.clone(), vec![("nodes".into(), 2)]);
+        Moldable::new(id, 60, HierarchyRequests::from_requests(vec![request]))
+    };
+
+    let mut slot_set = SlotSet::from_platform_config(Rc::clone(&platform_config), 0, 1000);
+    let busy_job = JobBuilder::new(1)
+        .user("user".into())
+        .moldable(two_nodes(1))
+        .assign(JobAssignment::new(0, busy_end, ProcSet::from_iter([1..=64]), 0))
+        .build();
+    slot_set.split_slots_for_job_and_update_resources(&busy_job, true, true, None);
+
+    let reservation = JobBuilder::new(2)
+        .user("user".into())
+        .add_type("max_delay".into(), max_delay.to_string().into())
+        .moldable(two_nodes(2))
+        .build();
+    find_reservation_window(&slot_set, &reservation, 100, Job::compute_end(100, 60), Job::compute_end(100, 60))
+}
+
+#[test]
+fn test_reservation_window_quotas_exceeded() {
+    let window = find_window_with_busy_quotas(0, 499);
+    assert!(matches!(window, ReservationWindow::QuotasExceeded { limit: 100, .. }), "{:?}", window);
+}
+
+#[test]
+fn test_reservation_window_slides_after_quotas_exceeded() {
+    // The requested resources are available at 100, but the quotas are only respected once the busy job ends
+    let window = find_window_with_busy_quotas(600, 499);
+    assert_eq!(window, ReservationWindow::Found { delay: 400, resources: ProcSet::from_iter([1..=64]) });
+}
+
+#[test]
+fn test_reservation_window_sliding_after_horizon() {
+    // Sliding after the busy job reaches a window ending after the horizon: the reservation waits for the next cycles
+    let window = find_window_with_busy_quotas(900, 949);
+    assert_eq!(window, ReservationWindow::AfterHorizon { horizon: 1000 });
+}
+
+#[test]
+fn test_reservation_window_slides_within_horizon() {
+    // The max delay allows windows ending after the horizon, but a window ending before it is found first
+    let window = find_window_with_busy_quotas(900, 499);
+    assert_eq!(window, ReservationWindow::Found { delay: 400, resources: ProcSet::from_iter([1..=64]) });
+}
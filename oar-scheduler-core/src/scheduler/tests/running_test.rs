@@ -0,0 +1,48 @@
+use crate::model::job::{JobAssignment, JobBuilder, ProcSet};
+use crate::platform::PlatformTrait;
+use crate::scheduler::kamelot;
+use crate::scheduler::tests::platform_mock::{generate_mock_platform_config, PlatformBenchMock};
+use indexmap::IndexMap;
+use std::collections::HashSet;
+
+#[test]
+fn test_running_jobs_reduce_initial_free_resources() {
+    let platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+
+    // Finishing job on node 1, beyond its scheduled end
+    let finishing_job = JobBuilder::new(1)
+        .assign(JobAssignment::new(-100, -1, ProcSet::from_iter([1..=32]), 0))
+        .state("Finishing".into())
+        .build();
+    // Running job on node 2 until 99
+    let running_job = JobBuilder::new(2)
+        .assign(JobAssignment::new(-10, 99, ProcSet::from_iter([33..=64]), 0))
+        .state("Running".into())
+        .build();
+    // Running besteffort job on node 3, beyond its scheduled end
+    let besteffort_job = JobBuilder::new(3)
+        .assign(JobAssignment::new(-100, -1, ProcSet::from_iter([65..=96]), 0))
+        .queue("besteffort".into())
+        .state("Running".into())
+        .build();
+    // Terminated job on node 4: its resources are free
+    let terminated_job = JobBuilder::new(4)
+        .assign(JobAssignment::new(-100, -1, ProcSet::from_iter([97..=128]), 0))
+        .state("Terminated".into())
+        .build();
+
+    let platform = PlatformBenchMock::new(platform_config, vec![finishing_job, running_job, besteffort_job, terminated_job], IndexMap::new());
+    let running_jobs = platform.running_jobs_windows(&platform.get_scheduled_jobs());
+    assert_eq!(running_jobs.keys().copied().collect::<HashSet<i64>>(), HashSet::from([1, 2, 3]));
+    assert_eq!(running_jobs[&2], (-10, 99, ProcSet::from_iter([33..=64])));
+
+    let (slot_sets, _) = kamelot::init_slot_sets(&platform, false);
+    let slot_set = slot_sets.get("default").unwrap();
+    assert_eq!(slot_set.available_resources_at(0), Some(&ProcSet::from_iter([65..=256])));
+    // The finishing job is considered busy at now only
+    assert_eq!(slot_set.available_resources_at(1), Some(&ProcSet::from_iter([1..=32, 65..=256])));
+    assert_eq!(slot_set.available_resources_at(100), Some(&ProcSet::from_iter([1..=256])));
+
+    let (slot_sets, _) = kamelot::init_slot_sets(&platform, true);
+    assert_eq!(slot_sets.get("default").unwrap().available_resources_at(0), Some(&ProcSet::from_iter([97..=256])));
+}
@@ -1,7 +1,10 @@
-use crate::model::job::{JobAssignment, JobBuilder, PlaceholderType, ProcSet};
+use crate::model::job::{JobAssignment, JobBuilder, Moldable, PlaceholderType, ProcSet, ProcSetCoresOp};
+use crate::scheduler::hierarchy::{HierarchyRequest, HierarchyRequests};
 use crate::scheduler::slot::Slot;
-use crate::scheduler::slotset::SlotSet;
+use crate::scheduler::slotset::{SlotDiff, SlotSet, SplitError};
 use crate::scheduler::tests::platform_mock::generate_mock_platform_config;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::collections::HashMap;
 use std::rc::Rc;
 
@@ -149,12 +152,63 @@ pub fn test_iter_between_with_width() {
     assert_eq!(it.next().map(|(s1, s2)| (s1.id(), s2.id())), None);
 }
 
+#[test]
+pub fn test_iter_forward_and_backward_with_width() {
+    // Slots of uneven widths: [0, 4], [5, 11], [12, 29], [30, 30], [31, 59], [60, 99]
+    let platform_config = Rc::new(generate_mock_platform_config(false, 100, 48, 4, 64, false));
+    let bounds = [(0, 4), (5, 11), (12, 29), (30, 30), (31, 59), (60, 99)];
+    let slots = bounds
+        .iter()
+        .enumerate()
+        .map(|(i, &(begin, end))| {
+            let id = i as i32 + 1;
+            let prev = if id > 1 { Some(id - 1) } else { None };
+            let next = if (id as usize) < bounds.len() { Some(id + 1) } else { None };
+            (id, Slot::new(Rc::clone(&platform_config), id, prev, next, begin, end, ProcSet::from_iter([1..=32]), None))
+        })
+        .collect::<HashMap<i32, Slot>>();
+    let ss = SlotSet::from_map(Rc::clone(&platform_config), slots, 1);
+    ss.assert_consistent();
+
+    for min_width in [1, 8, 20, 30, 50, 100, 101] {
+        let forward = ss.iter().with_width(min_width).map(|(s1, s2)| (s1.begin(), s2.end())).collect::<Vec<_>>();
+        let backward = ss.iter().rev_with_width(min_width).map(|(s1, s2)| (s1.begin(), s2.end())).collect::<Vec<_>>();
+
+        // Forward: the first end slot reaching the width from each begin slot
+        let expected_forward = bounds
+            .iter()
+            .filter_map(|&(begin, _)| bounds.iter().find(|&&(_, end)| end - begin + 1 >= min_width).map(|&(_, end)| (begin, end)))
+            .collect::<Vec<_>>();
+        // Backward: the last begin slot reaching the width from each end slot, from the last end slot
+        let expected_backward = bounds
+            .iter()
+            .rev()
+            .filter_map(|&(_, end)| bounds.iter().rev().find(|&&(begin, _)| end - begin + 1 >= min_width).map(|&(begin, _)| (begin, end)))
+            .collect::<Vec<_>>();
+        assert_eq!(forward, expected_forward, "forward scan with width {}", min_width);
+        assert_eq!(backward, expected_backward, "backward scan with width {}", min_width);
+
+        // Both scans find the same windows that can't be shrunk from either side
+        let is_tight = |&&(begin, end): &&(i64, i64)| {
+            let shrinkable_begin = bounds.iter().any(|&(b, _)| b > begin && b <= end && end - b + 1 >= min_width);
+            let shrinkable_end = bounds.iter().any(|&(_, e)| e < end && e >= begin && e - begin + 1 >= min_width);
+            !shrinkable_begin && !shrinkable_end
+        };
+        let mut tight_forward = forward.iter().filter(is_tight).collect::<Vec<_>>();
+        let mut tight_backward = backward.iter().filter(is_tight).collect::<Vec<_>>();
+        tight_forward.sort();
+        tight_backward.sort();
+        assert_eq!(tight_forward, tight_backward, "tight windows with width {}", min_width);
+    }
+}
+
 #[test]
 pub fn test_split_slots() {
     let mut ss = get_test_slot_set();
     let scheduled_job_data = JobAssignment::new(5, 14, ProcSet::from_iter([4..=6]), 0);
     let job = JobBuilder::new(1).assign(scheduled_job_data).build();
     ss.split_slots_for_job_and_update_resources(&job, true, true, None);
+    ss.assert_consistent();
 
     assert_eq!(ss.slot_at(4, None).unwrap().proc_set().clone(), ProcSet::from_iter([1..=32]));
     assert_eq!(ss.slot_at(5, None).unwrap().proc_set().clone(), ProcSet::from_iter([1..=3, 7..=32]));
@@ -176,6 +230,7 @@ pub fn test_split_slots_outside() {
     let scheduled_job_data = JobAssignment::new(-5, 14, ProcSet::from_iter([4..=6]), 0);
     let job = JobBuilder::new(1).assign(scheduled_job_data).build();
     ss.split_slots_for_job_and_update_resources(&job, true, true, None);
+    ss.assert_consistent();
 
     assert_eq!(ss.slot_at(4, None).unwrap().proc_set().clone(), ProcSet::from_iter([1..=3, 7..=32]));
     assert_eq!(ss.slot_at(5, None).unwrap().proc_set().clone(), ProcSet::from_iter([1..=3, 7..=32]));
@@ -198,3 +253,482 @@ pub fn test_intersect_slots_intervals() {
     assert_eq!(ss.intersect_slots_intervals(2, 2, None, None, &PlaceholderType::None), ProcSet::from_iter([1..=16, 28..=32]));
     assert_eq!(ss.intersect_slots_intervals(1, 3, None, None, &PlaceholderType::None), ProcSet::from_iter([1..=8, 30..=32]));
 }
+
+#[test]
+pub fn test_slots_overlapping() {
+    let ss = get_test_slot_set();
+
+    let overlapping = ss.slots_overlapping(5, 14).map(|(s, b, e)| (s.id(), b, e)).collect::<Vec<_>>();
+    assert_eq!(overlapping, vec![(1, 5, 9), (2, 10, 14)]);
+
+    let overlapping = ss.slots_overlapping(-5, 3).map(|(s, b, e)| (s.id(), b, e)).collect::<Vec<_>>();
+    assert_eq!(overlapping, vec![(1, 0, 3)]);
+
+    let overlapping = ss.slots_overlapping(25, 100).map(|(s, b, e)| (s.id(), b, e)).collect::<Vec<_>>();
+    assert_eq!(overlapping, vec![(3, 25, 29)]);
+
+    assert_eq!(ss.slots_overlapping(30, 40).count(), 0);
+    assert_eq!(ss.slots_overlapping(-10, -1).count(), 0);
+
+    // Slots are not split
+    assert_eq!(ss.slot_count(), 3);
+
+    // Core-seconds over the window
+    let core_seconds: u32 = ss
+        .slots_overlapping(5, 14)
+        .map(|(s, b, e)| s.proc_set().core_count() * (e - b + 1) as u32)
+        .sum();
+    assert_eq!(core_seconds, 32 * 5 + 21 * 5);
+}
+
+#[test]
+pub fn test_from_platform_config_available_upto() {
+    let mut platform_config = generate_mock_platform_config(false, 100, 48, 4, 64, false);
+    platform_config.resource_set.available_upto = vec![(49, ProcSet::from_iter([81..=100])), (19, ProcSet::from_iter([1..=10]))];
+    let ss = SlotSet::from_platform_config(Rc::new(platform_config), 0, 100);
+
+    assert_eq!(ss.slot_count(), 3);
+    let slots = ss.iter().map(|s| (s.begin(), s.end(), s.proc_set().clone())).collect::<Vec<_>>();
+    assert_eq!(
+        slots,
+        vec![
+            (0, 19, ProcSet::from_iter([1..=100])),
+            (20, 49, ProcSet::from_iter([11..=100])),
+            (50, 100, ProcSet::from_iter([11..=80])),
+        ]
+    );
+}
+
+#[test]
+pub fn test_available_resources_at() {
+    let mut ss = get_test_slot_set();
+    assert_eq!(ss.available_resources_at(5), Some(&ProcSet::from_iter([1..=32])));
+    assert_eq!(ss.free_core_count_at(5), 32);
+    assert_eq!(ss.available_resources_at(-1), None);
+    assert_eq!(ss.available_resources_at(30), None);
+    assert_eq!(ss.free_core_count_at(30), 0);
+
+    let job = JobBuilder::new(1).assign(JobAssignment::new(5, 14, ProcSet::from_iter([4..=6]), 0)).build();
+    ss.split_slots_for_job_and_update_resources(&job, true, true, None);
+    ss.assert_consistent();
+
+    assert_eq!(ss.available_resources_at(4), Some(&ProcSet::from_iter([1..=32])));
+    assert_eq!(ss.available_resources_at(5), Some(&ProcSet::from_iter([1..=3, 7..=32])));
+    assert_eq!(ss.free_core_count_at(5), 29);
+    assert_eq!(ss.free_core_count_at(14), 18);
+    assert_eq!(ss.free_core_count_at(15), 21);
+}
+
+#[test]
+pub fn test_cache_lru_capacity() {
+    let mut platform_config = generate_mock_platform_config(true, 100, 48, 4, 64, false);
+    platform_config.config.scheduler_cache_capacity = 2;
+    let mut ss = SlotSet::from_platform_config(Rc::new(platform_config), 0, 100);
+    let requests = HierarchyRequests::from_requests(vec![HierarchyRequest::new(ProcSet::from_iter([1..=100]), vec![("cores".into(), 1)])]);
+    let moldables = (1..=3)
+        .map(|walltime| Moldable::new(walltime, walltime, requests.clone()))
+        .collect::<Vec<Moldable>>();
+
+    ss.insert_cache_entry(moldables[0].cache_key.clone(), 1);
+    ss.insert_cache_entry(moldables[1].cache_key.clone(), 1);
+    // Using the first entry makes the second one the least recently used
+    assert_eq!(ss.get_cache_first_slot(&moldables[0]), Some(1));
+    ss.insert_cache_entry(moldables[2].cache_key.clone(), 1);
+    assert_eq!(ss.cache_len(), 2);
+    assert_eq!(ss.get_cache_first_slot(&moldables[1]), None);
+    assert_eq!(ss.get_cache_first_slot(&moldables[0]), Some(1));
+    assert_eq!(ss.get_cache_first_slot(&moldables[2]), Some(1));
+
+    ss.clear_cache();
+    assert_eq!(ss.cache_len(), 0);
+    assert_eq!(ss.get_cache_first_slot(&moldables[0]), None);
+}
+
+#[test]
+pub fn test_cache_keys_distinct() {
+    let mut ss = get_test_slot_set();
+    let filter = ProcSet::from_iter([1..=32]);
+    let excluded = ProcSet::from_iter([1..=2]);
+    let moldables = [
+        Moldable::new(1, 5, HierarchyRequests::new_single(filter.clone(), vec![("cores".into(), 1)])),
+        Moldable::new(2, 5, HierarchyRequests::new_single(filter.clone(), vec![("cores".into(), 2)])),
+        Moldable::new(3, 5, HierarchyRequests::from_requests(vec![HierarchyRequest::new_range(filter.clone(), vec![("cores".into(), 1, 2)])])),
+        Moldable::new(4, 5, HierarchyRequests::new_single(filter.clone(), vec![("cores".into(), 1)])).with_excluded_resources(excluded),
+        Moldable::new(5, 6, HierarchyRequests::new_single(filter, vec![("cores".into(), 1)])),
+    ];
+    for (i, moldable) in moldables.iter().enumerate() {
+        assert!(moldables[..i].iter().all(|other| other.cache_key != moldable.cache_key), "{}", moldable.cache_key);
+        assert_eq!(ss.get_cache_first_slot(moldable), None);
+    }
+    // The same moldable with another id shares the cache key
+    let same = Moldable::new(6, 5, HierarchyRequests::new_single(ProcSet::from_iter([1..=32]), vec![("cores".into(), 1)]));
+    assert_eq!(same.cache_key, moldables[0].cache_key);
+    assert_eq!(ss.get_cache_first_slot(&same), None);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "is shared by two different moldables")]
+pub fn test_cache_key_collision_panics() {
+    let mut ss = get_test_slot_set();
+    let filter = ProcSet::from_iter([1..=32]);
+    let moldable = Moldable::new(1, 5, HierarchyRequests::new_single(filter.clone(), vec![("cores".into(), 1)]));
+    let mut colliding = Moldable::new(2, 5, HierarchyRequests::new_single(filter, vec![("cores".into(), 2)]));
+    colliding.cache_key = moldable.cache_key.clone();
+
+    ss.get_cache_first_slot(&moldable);
+    ss.get_cache_first_slot(&colliding);
+}
+
+#[test]
+pub fn test_cache_invalidated_by_split() {
+    let mut ss = get_test_slot_set();
+    let requests = HierarchyRequests::from_requests(vec![HierarchyRequest::new(ProcSet::from_iter([1..=32]), vec![("cores".into(), 1)])]);
+    let moldable = Moldable::new(1, 5, requests);
+
+    ss.insert_cache_entry(moldable.cache_key.clone(), 2);
+    // Splitting after the cached time keeps the cached slot
+    ss.find_and_split_at(15, false);
+    assert_eq!(ss.get_cache_first_slot(&moldable), Some(2));
+    // Splitting inserts the new slot 5 before slot 2, so slot 2 no longer begins at the cached time
+    let (new_slot_id, _) = ss.find_and_split_at(12, true);
+    assert_eq!(new_slot_id, 5);
+    assert_eq!(ss.get_cache_first_slot(&moldable), Some(5));
+    assert_eq!(ss.get_slot(5).unwrap().begin(), 10);
+}
+
+#[test]
+pub fn test_snapshot_restore() {
+    let mut ss = get_test_slot_set();
+    let slots_state = |ss: &SlotSet| {
+        ss.iter()
+            .map(|s| (s.id(), s.prev(), s.next(), s.begin(), s.end(), s.proc_set().clone()))
+            .collect::<Vec<_>>()
+    };
+    let initial_state = slots_state(&ss);
+    let snapshot = ss.snapshot();
+
+    let scheduled_job_data = JobAssignment::new(5, 14, ProcSet::from_iter([4..=6]), 0);
+    let job = JobBuilder::new(1).assign(scheduled_job_data).build();
+    ss.split_slots_for_job_and_update_resources(&job, true, true, None);
+    ss.assert_consistent();
+    assert_ne!(slots_state(&ss), initial_state);
+    assert_eq!(ss.slot_count(), initial_state.len() + 2);
+
+    ss.restore(snapshot);
+    assert_eq!(slots_state(&ss), initial_state);
+    assert_eq!(ss.slot_count(), initial_state.len());
+    assert_eq!(
+        (ss.first_slot().unwrap().id(), ss.last_slot().unwrap().id()),
+        (1, initial_state.len() as i32)
+    );
+}
+
+#[test]
+pub fn test_clone_range() {
+    let ss = get_test_slot_set();
+
+    let sub = ss.clone_range(5, 14).unwrap();
+    sub.assert_consistent();
+    assert_eq!((sub.begin(), sub.end()), (5, 14));
+    assert_eq!(sub.slot_count(), 2);
+    let (first, last) = (sub.first_slot().unwrap(), sub.last_slot().unwrap());
+    assert_eq!((first.id(), first.prev(), first.begin()), (1, None, 5));
+    assert_eq!((last.id(), last.next(), last.end()), (2, None, 14));
+    assert_eq!(last.proc_set(), &ProcSet::from_iter([1..=16, 28..=32]));
+
+    // Clamped to the range of the SlotSet
+    let sub = ss.clone_range(-10, 100).unwrap();
+    sub.assert_consistent();
+    assert_eq!((sub.begin(), sub.end(), sub.slot_count()), (0, 29, 3));
+    let sub = ss.clone_range(25, 100).unwrap();
+    assert_eq!((sub.begin(), sub.end(), sub.slot_count()), (25, 29, 1));
+    assert!(ss.clone_range(30, 40).is_none());
+
+    // The parent is left unchanged
+    ss.assert_consistent();
+    assert_eq!((ss.begin(), ss.end(), ss.slot_count()), (0, 29, 3));
+    assert_eq!(ss.first_slot().unwrap().begin(), 0);
+}
+
+#[test]
+#[should_panic(expected = "slots 1 and 2 are not contiguous")]
+pub fn test_assert_consistent_detects_gap() {
+    let mut ss = get_test_slot_set();
+    ss.assert_consistent();
+    ss.get_slot_mut(2).unwrap().begin += 1;
+    ss.assert_consistent();
+}
+
+#[test]
+pub fn test_from_platform_config_available_from() {
+    let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    platform_config.resource_set.available_from = vec![
+        (50, ProcSet::from_iter([1..=32])),
+        (-10, ProcSet::from_iter([33..=64])), // Already available
+        (200, ProcSet::from_iter([65..=96])), // Available after the end of the slot set
+    ];
+    let ss = SlotSet::from_platform_config(Rc::new(platform_config), 0, 99);
+    ss.assert_consistent();
+    let slots = ss.iter().map(|s| (s.begin(), s.end(), s.proc_set().clone())).collect::<Vec<_>>();
+    assert_eq!(
+        slots,
+        vec![
+            (0, 49, ProcSet::from_iter([33..=64, 97..=256])),
+            (50, 99, ProcSet::from_iter([1..=64, 97..=256])),
+        ]
+    );
+}
+
+#[test]
+fn test_resize_job() {
+    let mut ss = get_test_slot_set();
+    let mut job = JobBuilder::new(1).assign(JobAssignment::new(5, 12, ProcSet::from_iter([1..=4]), 0)).build();
+    ss.split_slots_for_job_and_update_resources(&job, true, true, None);
+
+    // Extension over free resources
+    assert_eq!(ss.resize_job(&job, 24), Ok(()));
+    job.assignment.as_mut().unwrap().end = 24;
+    assert_eq!(ss.available_resources_at(13), Some(&ProcSet::from_iter([5..=16, 28..=32])));
+    assert_eq!(ss.available_resources_at(24), Some(&ProcSet::from_iter([5..=8, 30..=32])));
+    assert_eq!(ss.available_resources_at(25), Some(&ProcSet::from_iter([1..=8, 30..=32])));
+
+    // Shrink frees the resources
+    assert_eq!(ss.resize_job(&job, 9), Ok(()));
+    job.assignment.as_mut().unwrap().end = 9;
+    assert_eq!(ss.available_resources_at(9), Some(&ProcSet::from_iter([5..=32])));
+    assert_eq!(ss.available_resources_at(10), Some(&ProcSet::from_iter([1..=16, 28..=32])));
+    assert_eq!(ss.available_resources_at(24), Some(&ProcSet::from_iter([1..=8, 30..=32])));
+    ss.assert_consistent();
+}
+
+#[test]
+fn test_resize_job_collision() {
+    let mut ss = get_test_slot_set();
+    let job = JobBuilder::new(1).assign(JobAssignment::new(0, 9, ProcSet::from_iter([20..=30]), 0)).build();
+    ss.split_slots_for_job_and_update_resources(&job, true, true, None);
+
+    // Resources 20 to 27 are not available from 10
+    let slots_before = ss.iter().map(|slot| (slot.begin(), slot.end(), slot.proc_set().clone())).collect::<Vec<_>>();
+    let result = ss.resize_job(&job, 15);
+    assert!(result.is_err());
+    assert!(result.unwrap_err().contains("20-27"));
+    assert_eq!(ss.iter().map(|slot| (slot.begin(), slot.end(), slot.proc_set().clone())).collect::<Vec<_>>(), slots_before);
+
+    // Shrinking before the begin is an error
+    assert!(ss.resize_job(&job, -1).is_err());
+    // Unscheduled jobs can't be resized
+    assert!(ss.resize_job(&JobBuilder::new(2).build(), 15).is_err());
+}
+
+#[test]
+fn test_free_and_busy_resource_seconds() {
+    // 100 default resources
+    let platform_config = Rc::new(generate_mock_platform_config(false, 100, 48, 4, 64, false));
+    let s1 = Slot::new(Rc::clone(&platform_config), 1, None, Some(2), 0, 9, ProcSet::from_iter([1..=32]), None);
+    let s2 = Slot::new(Rc::clone(&platform_config), 2, Some(1), None, 10, 19, ProcSet::from_iter([1..=16]), None);
+    let ss = SlotSet::from_map(Rc::clone(&platform_config), HashMap::from([(1, s1), (2, s2)]), 1);
+
+    assert_eq!(ss.free_resource_seconds(0, 19), 32 * 10 + 16 * 10);
+    assert_eq!(ss.busy_resource_seconds(0, 19), 68 * 10 + 84 * 10);
+    assert_eq!(ss.free_resource_seconds(5, 14), 32 * 5 + 16 * 5);
+    assert_eq!(ss.busy_resource_seconds(5, 14), 68 * 5 + 84 * 5);
+    // Only the part of the range within the slot set is counted
+    assert_eq!(ss.free_resource_seconds(15, 100), 16 * 5);
+    assert_eq!(ss.busy_resource_seconds(15, 100), 84 * 5);
+    assert_eq!(ss.free_resource_seconds(30, 40), 0);
+    assert_eq!(ss.busy_resource_seconds(30, 40), 0);
+    // Slots are not split
+    assert_eq!(ss.slot_count(), 2);
+}
+
+#[test]
+pub fn test_try_split_at() {
+    let mut ss = get_test_slot_set();
+    // Splitting at the beginning of a slot is an error, leaving the SlotSet unchanged
+    assert_eq!(ss.try_split_at(2, 10, true), Err(SplitError::TimeOutOfSlot { slot_id: 2, time: 10, begin: 10, end: 19 }));
+    assert_eq!(ss.try_split_at(2, 20, false), Err(SplitError::TimeOutOfSlot { slot_id: 2, time: 20, begin: 10, end: 19 }));
+    assert_eq!(ss.try_split_at(9, 15, true), Err(SplitError::SlotNotFound(9)));
+    assert_eq!(ss.slot_count(), 3);
+
+    // Splitting mid-slot
+    assert_eq!(ss.try_split_at(2, 15, true), Ok((4, 2)));
+    ss.assert_consistent();
+    assert_eq!((ss.get_slot(4).unwrap().begin(), ss.get_slot(4).unwrap().end()), (10, 14));
+    assert_eq!((ss.get_slot(2).unwrap().begin(), ss.get_slot(2).unwrap().end()), (15, 19));
+    assert_eq!(ss.get_slot(4).unwrap().proc_set(), &ProcSet::from_iter([1..=16, 28..=32]));
+
+    // A range ending before its begin can't be split
+    assert!(ss.try_split_slots_for_range(17, 16, None).is_err());
+    ss.assert_consistent();
+}
+
+#[test]
+fn test_slot_set_begin_rounding() {
+    let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    platform_config.config.scheduler_slotset_begin_rounding = 60;
+    let platform_config = Rc::new(platform_config);
+    let now = 3625;
+
+    // The slot set begins at the previous minute boundary, and now still falls inside the first slot
+    let slot_set = SlotSet::from_platform_config(Rc::clone(&platform_config), now, 10000);
+    assert_eq!(slot_set.begin(), 3600);
+    assert_eq!(slot_set.jobs_min_begin(), Some(now));
+    let first_slot = slot_set.slot_at(now, None).unwrap();
+    assert_eq!(first_slot.prev(), None);
+    assert_eq!((first_slot.begin(), first_slot.end()), (3600, 10000));
+    // Already aligned begins are unchanged
+    let aligned_slot_set = SlotSet::from_platform_config(Rc::clone(&platform_config), 3600, 10000);
+    assert_eq!((aligned_slot_set.begin(), aligned_slot_set.jobs_min_begin()), (3600, None));
+
+    // Jobs still begin at the real now
+    let available = platform_config.resource_set.default_resources.clone();
+    let moldable = Moldable::new(1, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available, vec![("nodes".into(), 1)])]));
+    let mut jobs = indexmap::indexmap![1 => JobBuilder::new(1).moldable(moldable).build()];
+    let mut slot_sets = HashMap::from([("default".into(), slot_set)]);
+    crate::scheduler::scheduling::schedule_jobs(&mut slot_sets, &mut jobs);
+    assert_eq!(jobs[0].begin(), Some(now));
+    assert_eq!(jobs[0].end(), Some(now + 99));
+}
+
+#[test]
+fn test_slot_set_diff() {
+    let slot_set = get_test_slot_set();
+    assert!(slot_set.diff(&get_test_slot_set()).is_empty());
+
+    // A job on the resources 1 to 4 from 12 to 24 only in the other slot set
+    let mut other = get_test_slot_set();
+    let job = JobBuilder::new(1).assign(JobAssignment::new(12, 24, ProcSet::from_iter([1..=4]), 0)).build();
+    other.split_slots_for_job_and_update_resources(&job, false, true, None);
+    assert_eq!(
+        slot_set.diff(&other),
+        vec![
+            SlotDiff::Boundary { time: 12, in_self: false },
+            SlotDiff::ProcSet { begin: 12, end: 24, only_self: ProcSet::from_iter([1..=4]), only_other: ProcSet::new() },
+            SlotDiff::Boundary { time: 25, in_self: false },
+        ]
+    );
+    assert_eq!(other.diff(&slot_set)[1].to_string(), "[12, 24]: resources '' only in self, '1-4' only in other");
+
+    // Only the common time range is compared
+    let platform_config = Rc::clone(slot_set.get_platform_config());
+    let slot = Slot::new(platform_config, 1, None, None, 0, 19, ProcSet::from_iter([1..=32]), None);
+    let shorter = SlotSet::from_slot(slot);
+    assert_eq!(
+        slot_set.diff(&shorter),
+        vec![
+            SlotDiff::Range { self_range: (0, 29), other_range: (0, 19) },
+            SlotDiff::Boundary { time: 10, in_self: true },
+            SlotDiff::ProcSet { begin: 10, end: 19, only_self: ProcSet::new(), only_other: ProcSet::from_iter([17..=27]) },
+        ]
+    );
+}
+
+#[test]
+fn test_slot_at_indexed_lookup() {
+    let platform_config = Rc::new(generate_mock_platform_config(false, 256, 8, 4, 8, false));
+    let mut slot_set = SlotSet::from_platform_config(platform_config, 0, 10_000);
+    let mut rng = StdRng::seed_from_u64(42);
+    for _ in 0..500 {
+        let time = rng.random_range(1..=10_000);
+        if slot_set.slot_at(time, None).unwrap().begin() != time {
+            // Splitting both before and after the time to update the index in both ways
+            slot_set.find_and_split_at(time, rng.random_bool(0.5));
+        }
+    }
+    #[cfg(debug_assertions)]
+    slot_set.assert_consistent();
+
+    // The indexed lookup agrees with a linear walk over the slots, also when starting at a given slot
+    let linear_slot_at = |time: i64, starting_id: Option<i32>| {
+        let slots = slot_set.iter().skip_while(|slot| starting_id.is_some_and(|id| slot.id() != id)).collect::<Vec<_>>();
+        slots.first().filter(|slot| slot.begin() <= time)?;
+        slots.into_iter().find(|slot| slot.begin() <= time && time <= slot.end()).map(|slot| slot.id())
+    };
+    for _ in 0..1000 {
+        let time = rng.random_range(-10..=10_010);
+        let starting_id = rng.random_bool(0.5).then(|| slot_set.slot_at(rng.random_range(0..=10_000), None).unwrap().id());
+        assert_eq!(slot_set.slot_id_at(time, starting_id), linear_slot_at(time, starting_id), "time {}, starting slot {:?}", time, starting_id);
+    }
+}
+
+#[test]
+pub fn test_max_slots() {
+    let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    platform_config.config.scheduler_max_slots = 4;
+    let platform_config = Rc::new(platform_config);
+    let mut ss = SlotSet::from_platform_config(Rc::clone(&platform_config), 0, 999);
+
+    // Jobs on node 1, then node 2, ... each 100 seconds later
+    let jobs = (0..8)
+        .map(|i| {
+            let node = ProcSet::from_iter([i * 32 + 1..=i * 32 + 32]);
+            JobBuilder::new(i as i64).assign(JobAssignment::new(i as i64 * 100 + 50, i as i64 * 100 + 99, node, 0)).build()
+        })
+        .collect::<Vec<_>>();
+    ss.split_slots_for_jobs_and_update_resources(&jobs.iter().collect(), true, true, None);
+    ss.assert_consistent();
+    assert_eq!(ss.slot_count(), 4);
+    let (slot_id, slot_end) = ss.last_slot().map(|slot| (slot.id(), slot.end())).unwrap();
+    assert_eq!(ss.try_split_at(slot_id, slot_end, true), Err(SplitError::MaxSlotsReached(4)));
+
+    // The jobs inserted once the maximum is reached have their resources removed from the whole slots they overlap
+    for job in jobs.iter() {
+        let (begin, end, proc_set) = job.window().unwrap();
+        for time in [begin, end] {
+            assert!(ss.available_resources_at(time).unwrap().is_disjoint(proc_set), "job {} at {}", job.id, time);
+        }
+    }
+    // Slots: [0, 49], [50, 99] for job 0, [100, 149], and [150, 999] shared by the next jobs
+    assert_eq!(ss.available_resources_at(120), Some(&ProcSet::from_iter([1..=256])));
+    assert_eq!(ss.available_resources_at(999), Some(&ProcSet::from_iter([1..=32])));
+
+    // Scheduling more jobs does not split the slots anymore
+    let available = platform_config.resource_set.default_resources.clone();
+    let request = HierarchyRequests::from_requests(vec![HierarchyRequest::new(available, vec![("nodes".into(), 1)])]);
+    let moldable = |id: i64| Moldable::new(id, 10, request.clone());
+    let mut waiting_jobs = (10..20).map(|id| (id, JobBuilder::new(id).moldable(moldable(id)).build())).collect::<indexmap::IndexMap<_, _>>();
+    let mut slot_sets = HashMap::from([("default".into(), ss)]);
+    crate::scheduler::scheduling::schedule_jobs(&mut slot_sets, &mut waiting_jobs);
+    assert!(waiting_jobs.values().all(|job| job.assignment.is_some()));
+    assert_eq!(slot_sets["default"].slot_count(), 4);
+}
+
+#[test]
+fn test_apply_downtime() {
+    let platform_config = Rc::new(generate_mock_platform_config(false, 256, 8, 4, 8, false));
+    let available = platform_config.resource_set.default_resources.clone();
+    let mut slot_set = SlotSet::from_platform_config(Rc::clone(&platform_config), 0, 10000);
+
+    // The first seven nodes are down for maintenance in [100, 499], only the last node stays up.
+    slot_set.apply_downtime(&ProcSet::from_iter([1..=224]), 100, 499).unwrap();
+    assert_eq!(slot_set.slot_count(), 3);
+    assert_eq!(slot_set.available_resources_at(99), Some(&available));
+    assert_eq!(slot_set.available_resources_at(100), Some(&ProcSet::from_iter([225..=256])));
+    assert_eq!(slot_set.available_resources_at(499), Some(&ProcSet::from_iter([225..=256])));
+    assert_eq!(slot_set.available_resources_at(500), Some(&available));
+    slot_set.assert_consistent();
+
+    // An invalid window is an error, and no resource is removed
+    assert!(slot_set.apply_downtime(&ProcSet::from_iter([1..=224]), 700, 600).is_err());
+    assert_eq!(slot_set.available_resources_at(600), Some(&available));
+    assert_eq!(slot_set.available_resources_at(700), Some(&available));
+    slot_set.assert_consistent();
+
+    let nodes_moldable = |id: i64, walltime: i64, nodes: u32| {
+        Moldable::new(id, walltime, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), nodes)])]))
+    };
+    let mut jobs = indexmap::indexmap![
+        1 => JobBuilder::new(1).moldable(nodes_moldable(1, 100, 2)).build(),
+        2 => JobBuilder::new(2).moldable(nodes_moldable(2, 200, 2)).build(),
+        3 => JobBuilder::new(3).moldable(nodes_moldable(3, 300, 1)).build(),
+    ];
+    let mut slot_sets = HashMap::from([("default".into(), slot_set)]);
+    crate::scheduler::scheduling::schedule_jobs(&mut slot_sets, &mut jobs);
+
+    // Job 1 ends before the downtime, job 2 can't fit in it and starts after, and job 3 runs through it on the last node.
+    assert_eq!((jobs[0].begin(), jobs[0].end()), (Some(0), Some(99)));
+    assert_eq!(jobs[1].begin(), Some(500));
+    assert_eq!(jobs[2].begin(), Some(0));
+    assert_eq!(jobs[2].assignment.as_ref().unwrap().resources, ProcSet::from_iter([225..=256]));
+}
@@ -0,0 +1,58 @@
+use crate::model::job::{Job, JobBuilder, Moldable, ProcSet};
+use crate::scheduler::hierarchy::{HierarchyRequest, HierarchyRequests};
+use crate::scheduler::kamelot;
+use crate::scheduler::tests::platform_mock::{generate_mock_platform_config, PlatformBenchMock};
+use indexmap::IndexMap;
+
+/// Job requesting the whole mock cluster (8 nodes of 32 cores) for 100 seconds.
+fn whole_cluster_job(id: i64, submission_time: i64) -> JobBuilder {
+    let available = ProcSet::from_iter([1..=256]);
+    let moldable = Moldable::new(id, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available, vec![("nodes".into(), 8)])]));
+    JobBuilder::new(id).queue("default".into()).submission_time(submission_time).moldable(moldable)
+}
+
+/// Schedules the jobs on a mock platform and returns the begin time of each job.
+fn schedule_and_get_begins(jobs: Vec<Job>) -> IndexMap<i64, i64> {
+    let mut platform = PlatformBenchMock::new(
+        generate_mock_platform_config(false, 256, 8, 4, 8, false),
+        vec![],
+        jobs.into_iter().map(|job| (job.id, job)).collect(),
+    );
+    kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+    platform
+        .get_scheduled_jobs_ref()
+        .iter()
+        .map(|job| (job.id, job.begin().unwrap()))
+        .collect()
+}
+
+#[test]
+fn test_priority_sort() {
+    let jobs = vec![
+        whole_cluster_job(1, 10).build(),
+        whole_cluster_job(2, 20).add_type("priority".into(), "5".into()).build(),
+        whole_cluster_job(3, 30).priority(10).build(),
+    ];
+    assert_eq!(jobs[0].priority, None);
+    assert_eq!(jobs[1].priority, Some(5));
+
+    let begins = schedule_and_get_begins(jobs);
+    assert_eq!(begins[&3], 0);
+    assert_eq!(begins[&2], 100);
+    assert_eq!(begins[&1], 200);
+}
+
+#[test]
+fn test_priority_sort_keeps_fifo_without_priority() {
+    // Submission times are not consistent with the job order: the original order must be kept.
+    let jobs = vec![
+        whole_cluster_job(1, 30).build(),
+        whole_cluster_job(2, 20).build(),
+        whole_cluster_job(3, 10).build(),
+    ];
+
+    let begins = schedule_and_get_begins(jobs);
+    assert_eq!(begins[&1], 0);
+    assert_eq!(begins[&2], 100);
+    assert_eq!(begins[&3], 200);
+}
@@ -0,0 +1,81 @@
+use crate::model::job::{average_wait_time, JobAssignment, JobBuilder, Moldable, ProcSet};
+use crate::scheduler::hierarchy::{HierarchyRequest, HierarchyRequests};
+use crate::scheduler::kamelot::{self, SchedulingStats};
+use crate::scheduler::tests::platform_mock::{generate_mock_platform_config, PlatformBenchMock};
+use indexmap::indexmap;
+
+#[test]
+fn test_scheduling_stats() {
+    // 8 nodes of 32 cores
+    let platform_config = generate_mock_platform_config(true, 256, 8, 4, 8, false);
+    let available = platform_config.resource_set.default_resources.clone();
+    let job = |id: i64, nodes: u32| {
+        let moldable = Moldable::new(id, 100, HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), nodes)])]));
+        JobBuilder::new(id).moldable(moldable).build()
+    };
+    let waiting_jobs = indexmap![
+        1 => job(1, 8),
+        2 => job(2, 8), // Cache hit
+        3 => job(3, 8), // Cache hit
+        4 => job(4, 9), // More nodes than available
+    ];
+    let mut platform = PlatformBenchMock::new(platform_config, vec![], waiting_jobs);
+
+    let stats = kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+    assert_eq!(
+        stats,
+        SchedulingStats {
+            scheduled: 3,
+            rejected: 1,
+            quotas_hits: 0,
+            cache_hits: 2,
+            final_slot_count: 4,
+            gantt_width: 300,
+            average_wait_time: 100,
+            job_traces: vec![],
+        }
+    );
+
+    // The unsatisfiable job is kept waiting, and skipped again
+    let stats = kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+    assert_eq!(stats, SchedulingStats { rejected: 1, ..SchedulingStats::default() });
+}
+
+#[test]
+fn test_forced_cache_keys() {
+    for (keys, expected_cache_hits) in [(["shared", "shared"], 1), (["first", "second"], 0)] {
+        let platform_config = generate_mock_platform_config(true, 256, 8, 4, 8, false);
+        let available = platform_config.resource_set.default_resources.clone();
+        let job = |id: i64, key: &str| {
+            let requests = HierarchyRequests::new_single(available.clone(), vec![("nodes".into(), 8)]);
+            JobBuilder::new(id).moldable_with_cache_key(id, 100, requests, key.into()).build()
+        };
+        let waiting_jobs = indexmap![1 => job(1, keys[0]), 2 => job(2, keys[1])];
+        let mut platform = PlatformBenchMock::new(platform_config, vec![], waiting_jobs);
+
+        let stats = kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+        assert_eq!((stats.scheduled, stats.cache_hits), (2, expected_cache_hits), "{:?}", keys);
+        assert!(platform.get_scheduled_jobs_ref().iter().all(|job| job.moldables[0].cache_key.as_ref() == keys[job.id as usize - 1]));
+    }
+}
+
+#[test]
+fn test_estimated_wait_time() {
+    let job = |id: i64, begin: Option<i64>| {
+        let builder = JobBuilder::new(id).moldable(Moldable::new(id, 100, HierarchyRequests::from_requests(vec![])));
+        match begin {
+            Some(begin) => builder.assign(JobAssignment::new(begin, begin + 99, ProcSet::from_iter(1..=1), 0)).build(),
+            None => builder.build(),
+        }
+    };
+    let now = 1000;
+    let jobs = [job(1, Some(now + 3600)), job(2, Some(now - 50)), job(3, None)];
+    assert_eq!(jobs[0].estimated_wait_time(now), Some(3600));
+    // A job that already began does not wait
+    assert_eq!(jobs[1].estimated_wait_time(now), Some(0));
+    assert_eq!(jobs[2].estimated_wait_time(now), None);
+
+    // Unscheduled jobs are ignored
+    assert_eq!(average_wait_time(&jobs, now), Some(1800));
+    assert_eq!(average_wait_time(&jobs[2..], now), None);
+}
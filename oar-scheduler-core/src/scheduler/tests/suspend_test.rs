@@ -0,0 +1,35 @@
+use crate::model::job::{JobAssignment, JobBuilder, ProcSet};
+use crate::scheduler::hierarchy::{HierarchyRequest, HierarchyRequests};
+use crate::scheduler::kamelot;
+use crate::scheduler::tests::platform_mock::{generate_mock_platform_config, PlatformBenchMock};
+use indexmap::indexmap;
+
+#[test]
+fn test_suspendable_job_confined_to_suspendable_resources() {
+    let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, false);
+    // Nodes 5 to 8 are suspendable
+    platform_config.resource_set.suspendable_resources = ProcSet::from_iter([129..=256]);
+    let available = platform_config.resource_set.default_resources.clone();
+    let request = || HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), 1)])]);
+
+    // Suspended job on nodes 4 to 8 until 99: only node 4 is kept
+    let suspended_job = JobBuilder::new(10)
+        .assign(JobAssignment::new(0, 99, ProcSet::from_iter([97..=256]), 0))
+        .state("Suspended".into())
+        .build();
+
+    let suspendable_job = JobBuilder::new(1).moldable_auto(1, 10, request()).add_type("suspend".into(), "yes".into()).build();
+    let other_job = JobBuilder::new(2).moldable_auto(2, 10, request()).build();
+    let waiting_jobs = indexmap![1 => suspendable_job, 2 => other_job];
+
+    let mut platform = PlatformBenchMock::new(platform_config, vec![suspended_job], waiting_jobs);
+    kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+
+    let scheduled = platform.get_scheduled_jobs_ref();
+    let assignment = |id: i64| scheduled.iter().find(|job| job.id == id).and_then(|job| job.assignment.clone()).unwrap();
+    // The suspendable job skips the free nodes 1 to 3 for the first suspendable node, released by the suspended job
+    assert_eq!(assignment(1).begin, 0);
+    assert_eq!(assignment(1).resources, ProcSet::from_iter([129..=160]));
+    assert_eq!(assignment(2).begin, 0);
+    assert_eq!(assignment(2).resources, ProcSet::from_iter([1..=32]));
+}
@@ -1,11 +1,13 @@
-use crate::model::job::{JobBuilder, Moldable};
+use crate::model::job::{Job, JobAssignment, JobBuilder, Moldable, ProcSet, ProcSetCoresOp};
 use crate::platform::PlatformConfig;
-use crate::scheduler::calendar::QuotasConfig;
+use crate::scheduler::calendar::{QuotasConfig, QuotasParseError, DEFAULT_RULES_ID};
 use crate::scheduler::hierarchy::HierarchyRequests;
 use crate::scheduler::quotas;
+use crate::scheduler::slot::Slot;
 use crate::scheduler::slotset::SlotSet;
 use crate::scheduler::tests::platform_mock::generate_mock_platform_config;
 use chrono::{Datelike, Local, TimeZone};
+use chrono_tz::Europe::Paris;
 use std::rc::Rc;
 
 fn period_weekstart(now_epoch: i64) -> i64 {
@@ -124,7 +126,7 @@ fn add_oneshots_to_rules(rules: &mut String, _oneshots: &[&str]) {
 fn test_quota_limits_periodical_segments() {
     let json = rules_example_simple_json();
     let mut pc: PlatformConfig = generate_mock_platform_config(false, 256, 8, 4, 8, true);
-    pc.quotas_config = QuotasConfig::load_from_json(json, true, 100, 3 * 7 * 24 * 3600);
+    pc.quotas_config = QuotasConfig::load_from_json(json, true, 100, 100, 3 * 7 * 24 * 3600).unwrap();
     let pc = Rc::new(pc);
 
     let t0 = period_weekstart(Local::now().timestamp());
@@ -141,14 +143,14 @@ fn test_quota_limits_periodical_segments() {
     // Monday: resource limit is 20: 16 allowed, 24 rejected
     let b_mon = t0 + 1; // avoid boundary
     let sid_mon = ss.slot_at(b_mon, None).unwrap().id();
-    assert!(quotas::check_slots_quotas(ss.iter().between(sid_mon, sid_mon), &job, b_mon, b_mon + 3600, 16).is_none());
-    assert!(quotas::check_slots_quotas(ss.iter().between(sid_mon, sid_mon), &job, b_mon, b_mon + 3600, 24).is_some());
+    assert!(quotas::check_slots_quotas(ss.iter().between(sid_mon, sid_mon), &job, b_mon, b_mon + 3600, 16, 16).is_none());
+    assert!(quotas::check_slots_quotas(ss.iter().between(sid_mon, sid_mon), &job, b_mon, b_mon + 3600, 24, 24).is_some());
 
     // Thursday: resource limit is 24: 24 allowed, 30 rejected
     let b_thu = t0 + 3 * 86400 + 1;
     let sid_thu = ss.slot_at(b_thu, None).unwrap().id();
-    assert!(quotas::check_slots_quotas(ss.iter().between(sid_thu, sid_thu), &job, b_thu, b_thu + 3600, 24).is_none());
-    assert!(quotas::check_slots_quotas(ss.iter().between(sid_thu, sid_thu), &job, b_thu, b_thu + 3600, 30).is_some());
+    assert!(quotas::check_slots_quotas(ss.iter().between(sid_thu, sid_thu), &job, b_thu, b_thu + 3600, 24, 24).is_none());
+    assert!(quotas::check_slots_quotas(ss.iter().between(sid_thu, sid_thu), &job, b_thu, b_thu + 3600, 30, 30).is_some());
 }
 
 #[test]
@@ -158,7 +160,7 @@ fn test_quota_limits_in_oneshot_window() {
     let json = rules_example_with_oneshot_json(tw);
 
     let mut pc: PlatformConfig = generate_mock_platform_config(false, 256, 8, 4, 8, true);
-    pc.quotas_config = QuotasConfig::load_from_json(json, true, 100, 3 * 7 * 24 * 3600);
+    pc.quotas_config = QuotasConfig::load_from_json(json, true, 100, 100, 3 * 7 * 24 * 3600).unwrap();
     let pc = Rc::new(pc);
 
     let t0 = tw;
@@ -175,36 +177,36 @@ fn test_quota_limits_in_oneshot_window() {
     // Monday 13:00 within oneshot window: 32 allowed
     let b = tw + 13 * 3600;
     let sid = ss.slot_at(b, None).unwrap().id();
-    assert!(quotas::check_slots_quotas(ss.iter().between(sid, sid), &job, b, b + 3600, 32).is_none());
-    assert!(quotas::check_slots_quotas(ss.iter().between(sid, sid), &job, b, b + 3600, 33).is_some());
+    assert!(quotas::check_slots_quotas(ss.iter().between(sid, sid), &job, b, b + 3600, 32, 32).is_none());
+    assert!(quotas::check_slots_quotas(ss.iter().between(sid, sid), &job, b, b + 3600, 33, 33).is_some());
 
     // Thursday 23:00: periodical quotas_1 (15) 14 allowed, 16 rejected (for projB)
     let b = tw + 5 * 86400 - 3600;
     let sid = ss.slot_at(b, None).unwrap().id();
-    assert!(quotas::check_slots_quotas(ss.iter().between(sid, sid), &job, b, b + 3600, 14).is_none());
-    assert!(quotas::check_slots_quotas(ss.iter().between(sid, sid), &job, b, b + 3600, 16).is_some());
+    assert!(quotas::check_slots_quotas(ss.iter().between(sid, sid), &job, b, b + 3600, 14, 14).is_none());
+    assert!(quotas::check_slots_quotas(ss.iter().between(sid, sid), &job, b, b + 3600, 16, 16).is_some());
 }
 
 #[test]
 fn test_calendar_periodical_from_json() {
-    let qc = QuotasConfig::load_from_json(rules_example_full(), true, 100, 3 * 7 * 24 * 3600);
+    let qc = QuotasConfig::load_from_json(rules_example_full(), true, 100, 100, 3 * 7 * 24 * 3600).unwrap();
     assert!(qc.calendar.is_some());
     let cal = qc.calendar.unwrap();
     assert!(!cal.ordered_periodicals().is_empty());
 
-    let qc = QuotasConfig::load_from_json(rules_default_example_json(), true, 100, 3 * 7 * 24 * 3600);
+    let qc = QuotasConfig::load_from_json(rules_default_example_json(), true, 100, 100, 3 * 7 * 24 * 3600).unwrap();
     assert!(qc.calendar.is_some());
     let cal = qc.calendar.unwrap();
     assert!(!cal.ordered_periodicals().is_empty());
 
-    let qc = QuotasConfig::load_from_json(rules_only_default_example_json(), true, 100, 3 * 7 * 24 * 3600);
+    let qc = QuotasConfig::load_from_json(rules_only_default_example_json(), true, 100, 100, 3 * 7 * 24 * 3600).unwrap();
     assert!(qc.calendar.is_some());
     let cal = qc.calendar.unwrap();
     assert!(!cal.ordered_periodicals().is_empty());
 
     let mut json = rules_example_simple_json();
     add_oneshots_to_rules(&mut json, &["''"]);
-    let qc = QuotasConfig::load_from_json(json, true, 100, 3 * 7 * 24 * 3600);
+    let qc = QuotasConfig::load_from_json(json, true, 100, 100, 3 * 7 * 24 * 3600).unwrap();
     assert!(qc.calendar.is_some());
     let cal = qc.calendar.unwrap();
     assert!(!cal.ordered_periodicals().is_empty());
@@ -213,12 +215,12 @@ fn test_calendar_periodical_from_json() {
 #[test]
 fn test_rules_at_periodical_segment() {
     let mut pc: PlatformConfig = generate_mock_platform_config(false, 256, 8, 4, 8, true);
-    pc.quotas_config = QuotasConfig::load_from_json(rules_example_simple_json(), true, 100, 3 * 7 * 24 * 3600);
+    pc.quotas_config = QuotasConfig::load_from_json(rules_example_simple_json(), true, 100, 100, 3 * 7 * 24 * 3600).unwrap();
 
     let cal = pc.quotas_config.calendar.unwrap();
     let t0 = period_weekstart(Local::now().timestamp());
 
-    let (res, _i1, _i2) = cal.rules_at(t0, 0, 0);
+    let res = cal.rules_at(t0);
     assert!(res.is_some());
     let (_rid, end) = res.unwrap();
 
@@ -234,10 +236,10 @@ fn test_rules_at_oneshot_priority() {
     let json = rules_example_with_oneshot_json(tw);
     let t = tw + (1 * 86400) + 12 * 3600; // Tuesday 12:00
 
-    pc.quotas_config = QuotasConfig::load_from_json(json, true, 100, 3 * 7 * 24 * 3600);
+    pc.quotas_config = QuotasConfig::load_from_json(json, true, 100, 100, 3 * 7 * 24 * 3600).unwrap();
     let cal = pc.quotas_config.calendar.unwrap();
 
-    let (res, _i1, _i2) = cal.rules_at(t, 0, 0);
+    let res = cal.rules_at(t);
     assert!(res.is_some());
 
     let (_rules_id, end) = res.unwrap();
@@ -251,7 +253,7 @@ fn test_rules_at_oneshot_priority() {
 fn test_calendar_simple_slotset_ids_and_lengths() {
     let json = rules_example_simple_json();
     let mut pc: PlatformConfig = generate_mock_platform_config(false, 256, 8, 4, 8, true);
-    pc.quotas_config = QuotasConfig::load_from_json(json, true, 100, 3 * 7 * 24 * 3600);
+    pc.quotas_config = QuotasConfig::load_from_json(json, true, 100, 100, 3 * 7 * 24 * 3600).unwrap();
     let pc = Rc::new(pc);
 
     let t0 = period_weekstart(Local::now().timestamp());
@@ -290,7 +292,7 @@ fn test_calendar_simple_slotset_splitting() {
     // Build a SlotSet over 2 weeks and ensure splitting alternates quotas_1 and quotas_2 as expected
     let json = rules_example_simple_json();
     let mut platform_config: PlatformConfig = generate_mock_platform_config(false, 256, 8, 4, 8, true);
-    platform_config.quotas_config = QuotasConfig::load_from_json(json, true, 100, 3 * 7 * 24 * 3600);
+    platform_config.quotas_config = QuotasConfig::load_from_json(json, true, 100, 100, 3 * 7 * 24 * 3600).unwrap();
     let platform_config = Rc::new(platform_config);
 
     let now = Local::now().timestamp();
@@ -312,9 +314,9 @@ fn test_calendar_simple_slotset_splitting() {
         let e = (b + 3600).min(s.end());
         let id = s.id();
         // try 30 -> 32, else 24, else 16
-        let lim = if quotas::check_slots_quotas(ss.iter().between(id, id), &job, b, e, 30).is_none() {
+        let lim = if quotas::check_slots_quotas(ss.iter().between(id, id), &job, b, e, 30, 30).is_none() {
             32
-        } else if quotas::check_slots_quotas(ss.iter().between(id, id), &job, b, e, 24).is_none() {
+        } else if quotas::check_slots_quotas(ss.iter().between(id, id), &job, b, e, 24, 24).is_none() {
             24
         } else {
             16
@@ -339,7 +341,7 @@ fn test_temporal_slotset_oneshot() {
     let json = rules_example_with_oneshot_json(tw);
 
     let mut platform_config: PlatformConfig = generate_mock_platform_config(false, 256, 8, 4, 8, true);
-    platform_config.quotas_config = QuotasConfig::load_from_json(json, true, 100, 3 * 7 * 24 * 3600);
+    platform_config.quotas_config = QuotasConfig::load_from_json(json, true, 100, 100, 3 * 7 * 24 * 3600).unwrap();
     let platform_config = Rc::new(platform_config);
 
     let t0 = tw;
@@ -358,9 +360,9 @@ fn test_temporal_slotset_oneshot() {
         let b = s.begin();
         let e = (b + 3600).min(s.end());
         let id = s.id();
-        let lim = if quotas::check_slots_quotas(ss.iter().between(id, id), &job, b, e, 30).is_none() {
+        let lim = if quotas::check_slots_quotas(ss.iter().between(id, id), &job, b, e, 30, 30).is_none() {
             32
-        } else if quotas::check_slots_quotas(ss.iter().between(id, id), &job, b, e, 24).is_none() {
+        } else if quotas::check_slots_quotas(ss.iter().between(id, id), &job, b, e, 24, 24).is_none() {
             24
         } else {
             16
@@ -379,7 +381,7 @@ fn test_check_slots_quotas_against_limits() {
     // Build 2 weeks SlotSet with the simple rules and check quotas limits for a job
     let json = rules_example_simple_json();
     let mut platform_config: PlatformConfig = generate_mock_platform_config(false, 256, 8, 4, 8, true);
-    platform_config.quotas_config = QuotasConfig::load_from_json(json, true, 100, 3 * 7 * 24 * 3600);
+    platform_config.quotas_config = QuotasConfig::load_from_json(json, true, 100, 100, 3 * 7 * 24 * 3600).unwrap();
     let platform_config = Rc::new(platform_config);
 
     let now = Local::now().timestamp();
@@ -397,10 +399,302 @@ fn test_check_slots_quotas_against_limits() {
     let right = ss.slot_at(t0 + 7 * 86400 - 1, None).unwrap().id();
 
     // 10 resources within first period (limit 16) -> Ok
-    let res = quotas::check_slots_quotas(ss.iter().between(left, right), &job, t0, t0 + 86400 - 1, 10);
+    let res = quotas::check_slots_quotas(ss.iter().between(left, right), &job, t0, t0 + 86400 - 1, 10, 10);
     assert!(res.is_none());
 
     // 20 resources entirely inside first 3 days period (limit 16) -> should fail
-    let res = quotas::check_slots_quotas(ss.iter().between(left, left), &job, t0, t0 + 3600, 20);
+    let res = quotas::check_slots_quotas(ss.iter().between(left, left), &job, t0, t0 + 3600, 20, 20);
     assert!(res.is_some());
 }
+
+#[test]
+fn test_periodicals_across_dst_change() {
+    // Clocks go from 02:00 to 03:00 on Sunday 2025-03-30 in Paris, so this day lasts 23 hours.
+    let json = r#"{
+        "periodical": [
+            ["08:00-20:00 mon-fri * *", "quotas_workday", "workdays"],
+            ["* sun * *", "quotas_sunday", "sundays"]
+        ],
+        "quotas_workday": {"*,*,*,/": [16, -1, -1]},
+        "quotas_sunday": {"*,*,*,/": [24, -1, -1]}
+    }"#;
+    let mut platform_config: PlatformConfig = generate_mock_platform_config(false, 256, 8, 4, 8, true);
+    platform_config.quotas_config = QuotasConfig::load_from_json(json.to_string(), true, 100, 100, 3 * 7 * 24 * 3600).unwrap();
+    let platform_config = Rc::new(platform_config);
+    let calendar = platform_config.quotas_config.calendar.as_ref().unwrap();
+    let paris = |d: u32, h: u32, m: u32, s: u32| Paris.with_ymd_and_hms(2025, 3, d, h, m, s).unwrap().timestamp();
+
+    // The sunday rule ends at local midnight, 23 hours after the beginning of the day
+    let rules = calendar.rules_at_in(&Paris, paris(30, 1, 0, 0));
+    let (sunday_rules_id, end) = rules.unwrap();
+    assert_eq!(end, paris(30, 23, 59, 59));
+    assert_eq!(end - paris(30, 0, 0, 0) + 1, 23 * 3600);
+
+    // Two weeks from Monday 2025-03-24, the second one starting after the change
+    let begin = paris(24, 0, 0, 0);
+    let available = platform_config.resource_set.default_resources.clone();
+    let slot = Slot::new(Rc::clone(&platform_config), 1, None, None, begin, begin + 14 * 86400, available, None);
+    let mut slot_set = SlotSet::from_slot(slot);
+    calendar.split_slotset_for_temporal_quotas_in(&Paris, &mut slot_set);
+
+    let workday = slot_set.slot_at(paris(31, 8, 0, 0), None).unwrap();
+    assert_eq!((workday.begin(), workday.end()), (paris(31, 8, 0, 0), paris(31, 19, 59, 59)));
+    assert_ne!(workday.quotas().rules_id(), platform_config.quotas_config.default_rules_id);
+    let sunday = slot_set.slot_at(paris(30, 12, 0, 0), None).unwrap();
+    assert_eq!((sunday.begin(), sunday.end()), (paris(30, 0, 0, 0), paris(30, 23, 59, 59)));
+    assert_eq!(sunday.quotas().rules_id(), sunday_rules_id);
+    let night = slot_set.slot_at(paris(31, 7, 59, 59), None).unwrap();
+    assert_eq!(night.quotas().rules_id(), platform_config.quotas_config.default_rules_id);
+}
+
+#[test]
+fn test_effective_rules_precedence() {
+    // The workday periodical is declared after the catch-all one, and the oneshot overlaps a workday.
+    let json = r#"{
+        "periodical": [
+            ["* * * *", "quotas_all", "all times"],
+            ["08:00-19:00 mon-fri * *", "quotas_workday", "workdays"]
+        ],
+        "oneshot": [
+            ["2025-01-07 12:00", "2025-01-07 14:00", "quotas_holiday", "long lunch"]
+        ],
+        "quotas_all": {"*,*,*,/": [32, -1, -1]},
+        "quotas_workday": {"*,*,*,/": [16, -1, -1]},
+        "quotas_holiday": {"*,*,*,/": [8, -1, -1]}
+    }"#;
+    let mut platform_config: PlatformConfig = generate_mock_platform_config(false, 256, 8, 4, 8, true);
+    platform_config.quotas_config = QuotasConfig::load_from_json(json.to_string(), true, 100, 100, 3 * 7 * 24 * 3600).unwrap();
+    let platform_config = Rc::new(platform_config);
+    let calendar = platform_config.quotas_config.calendar.as_ref().unwrap();
+    let local = |d: u32, h: u32| Local.with_ymd_and_hms(2025, 1, d, h, 0, 0).unwrap().timestamp();
+    // Rules ids follow the parsing order: periodicals first, then oneshots.
+    let (all, workday, holiday) = (1, 2, 3);
+
+    // Monday 2025-01-06 and Tuesday 2025-01-07
+    let effective = calendar.effective_rules_for_interval(local(6, 0), local(8, 0) - 1);
+    assert_eq!(
+        effective,
+        vec![
+            (local(6, 0), local(6, 8) - 1, all),
+            (local(6, 8), local(6, 19) - 1, workday),
+            (local(6, 19), local(7, 8) - 1, all),
+            (local(7, 8), local(7, 12) - 1, workday),
+            (local(7, 12), local(7, 14) - 1, holiday),
+            (local(7, 14), local(7, 19) - 1, workday),
+            (local(7, 19), local(8, 0) - 1, all),
+        ]
+    );
+    assert_eq!(calendar.rules_at(local(7, 13)), Some((holiday, local(7, 14) - 1)));
+    assert_eq!(calendar.rules_at(local(6, 10)), Some((workday, local(6, 19) - 1)));
+
+    // The slotset is split along the same intervals
+    let available = platform_config.resource_set.default_resources.clone();
+    let slot = Slot::new(Rc::clone(&platform_config), 1, None, None, local(6, 0), local(8, 0) - 1, available, None);
+    let mut slot_set = SlotSet::from_slot(slot);
+    calendar.split_slotset_for_temporal_quotas(&mut slot_set);
+    let slots = slot_set.iter().map(|s| (s.begin(), s.end(), s.quotas().rules_id())).collect::<Vec<_>>();
+    assert_eq!(slots, effective);
+}
+
+#[test]
+fn test_zero_quotas_window_time_limit() {
+    let error = QuotasConfig::load_from_json(rules_example_simple_json(), true, 100, 100, 0).unwrap_err();
+    assert_eq!(error, QuotasParseError::InvalidWindowTimeLimit(0));
+    assert!(error.to_string().starts_with("invalid quotas window time limit 0"));
+}
+
+#[test]
+fn test_quotas_window_shorter_than_slotset() {
+    let qc = QuotasConfig::load_from_json(rules_example_simple_json(), true, 100, 100, 7 * 24 * 3600).unwrap();
+    let calendar = qc.calendar.unwrap();
+    let t0 = period_weekstart(Local::now().timestamp());
+    assert!(calendar.window_covers(t0, t0 + 7 * 24 * 3600 - 1));
+    assert!(!calendar.window_covers(t0, t0 + 7 * 24 * 3600));
+}
+
+#[test]
+fn test_split_by_rule_changes_matches_effective_rules() {
+    // The quotas window ends in the middle of the slot set, and the slot set crosses a daylight-saving time change (2020-03-29 in Paris)
+    let mut platform_config: PlatformConfig = generate_mock_platform_config(false, 256, 8, 4, 8, true);
+    platform_config.quotas_config = QuotasConfig::load_from_json(rules_example_full(), true, 100, 100, 6 * 7 * 24 * 3600 + 3600).unwrap();
+    let platform_config = Rc::new(platform_config);
+    let calendar = platform_config.quotas_config.calendar.as_ref().unwrap();
+    let default_rules_id = platform_config.quotas_config.default_rules_id;
+    let begin = Paris.with_ymd_and_hms(2020, 3, 4, 10, 30, 0).unwrap().timestamp();
+    let end = begin + 10 * 7 * 24 * 3600;
+
+    // Slots computed from the effective rules intervals, the gaps between them taking the default rules
+    let max_time = end.min(begin + calendar.quotas_window_time_limit() - 1);
+    let mut expected = vec![];
+    let mut time = begin;
+    for (interval_begin, interval_end, rules_id) in calendar.effective_rules_for_interval_in(&Paris, begin, end) {
+        if interval_begin > time {
+            expected.push((time, interval_begin - 1, default_rules_id));
+        }
+        expected.push((interval_begin, interval_end, rules_id));
+        time = interval_end + 1;
+    }
+    if time <= end {
+        expected.push((time, end, default_rules_id));
+    }
+
+    let available = platform_config.resource_set.default_resources.clone();
+    let slot = Slot::new(Rc::clone(&platform_config), 1, None, None, begin, end, available, None);
+    let mut slot_set = SlotSet::from_slot(slot);
+    calendar.split_slotset_for_temporal_quotas_in(&Paris, &mut slot_set);
+    let slots = slot_set.iter().map(|s| (s.begin(), s.end(), s.quotas().rules_id())).collect::<Vec<_>>();
+    assert_eq!(slots, expected);
+
+    // Each slot begins at a rule change found by next_rule_change
+    for window in slots.windows(2) {
+        assert_eq!(calendar.next_rule_change_in(&Paris, window[0].0 + 1, max_time), Some((window[1].0, window[1].2)));
+    }
+    assert_eq!(calendar.next_rule_change_in(&Paris, slots.last().unwrap().0 + 1, max_time).filter(|(t, _)| *t <= end), None);
+}
+
+#[test]
+fn test_next_rule_change() {
+    let qc = QuotasConfig::load_from_json(rules_example_simple_json(), true, 100, 100, 2 * 7 * 24 * 3600).unwrap();
+    let calendar = qc.calendar.unwrap();
+    // Monday 00:00, when the sunday rules switch to the monday ones
+    let t0 = period_weekstart(Local::now().timestamp());
+    let (monday_rules_id, monday_rules_end) = calendar.rules_at(t0).unwrap();
+
+    // A change right at `after` is returned
+    assert_eq!(calendar.next_rule_change(t0), Some((t0, monday_rules_id)));
+    // The next change is the end of the mon-wed rules
+    let (thursday, thursday_rules_id) = calendar.next_rule_change(t0 + 1).unwrap();
+    assert_eq!(thursday, monday_rules_end + 1);
+    assert_ne!(thursday_rules_id, monday_rules_id);
+    assert_eq!(calendar.rules_at(thursday).map(|(id, _)| id), Some(thursday_rules_id));
+}
+
+#[test]
+fn test_next_rule_change_matches_effective_rules() {
+    // Overlapping periodicals, a oneshot overriding them, and times without any entry where the default rules apply
+    let t0 = period_weekstart(Local::now().timestamp());
+    let json = format!(
+        r#"{{
+        "periodical": [
+            ["08:00-19:00 mon-fri * *", "quotas_workday", "workdays"],
+            ["10:00-12:00 wed * *", "quotas_meeting", "meeting"]
+        ],
+        "oneshot": [["{}", "{}", "quotas_holiday", "holiday"]],
+        "quotas_workday": {{"*,*,*,*": [100, -1, -1]}},
+        "quotas_meeting": {{"*,*,*,*": [50, -1, -1]}},
+        "quotas_holiday": {{"*,*,*,*": [10, -1, -1]}}
+    }}"#,
+        local_to_sql_minutes(t0 + 86400 + 12 * 3600),
+        local_to_sql_minutes(t0 + 2 * 86400 + 11 * 3600)
+    );
+    let calendar = QuotasConfig::load_from_json(json, true, 100, 100, 2 * 7 * 24 * 3600).unwrap().calendar.unwrap();
+
+    // Between two successive changes, the effective rules are the ones of the first change, or none for the default rules
+    let week_end = t0 + 7 * 86400 - 1;
+    let mut changes = vec![];
+    let mut after = t0;
+    while let Some((time, rules_id)) = calendar.next_rule_change(after)
+        && time <= week_end
+    {
+        changes.push((time, rules_id));
+        after = time + 1;
+    }
+    // Two changes per workday, the holiday beginning on Tuesday at 12:00 and ending on Wednesday at 11:00 during the meeting
+    assert_eq!(changes.len(), 2 + 2 + 3 + 2 + 2);
+    for (i, (time, rules_id)) in changes.iter().enumerate() {
+        let next_time = changes.get(i + 1).map_or(week_end + 1, |(next_time, _)| *next_time);
+        let effective_rules = calendar.effective_rules_for_interval(*time, next_time - 1);
+        if *rules_id == DEFAULT_RULES_ID {
+            assert!(effective_rules.is_empty(), "{:?}", effective_rules);
+        } else {
+            assert_eq!(effective_rules, vec![(*time, next_time - 1, *rules_id)]);
+        }
+    }
+}
+
+#[test]
+fn test_period_counters() {
+    // At most 2 jobs and 25 resource hours per user and per workday
+    let json = r#"{
+        "periodical": [["08:00-19:00 mon-fri * *", "quotas_workday", "workdays"]],
+        "quotas_workday": {"*,*,*,/": [-1, 2, 25]}
+    }"#;
+    let t0 = period_weekstart(Local::now().timestamp());
+    let build_slot_set = |period_counters: bool| {
+        let mut platform_config: PlatformConfig = generate_mock_platform_config(false, 256, 8, 4, 8, true);
+        platform_config.config.quotas_period_counters = period_counters;
+        platform_config.quotas_config = QuotasConfig::load_from_json(json.to_string(), true, 100, 100, 3 * 7 * 24 * 3600).unwrap();
+        SlotSet::from_platform_config(Rc::new(platform_config), t0, t0 + 7 * 86400 - 1)
+    };
+    let job = |id: i64, user: &str, begin: i64, walltime: i64, resources: u32| {
+        JobBuilder::new(id)
+            .user(user.into())
+            .queue("default".into())
+            .moldable(Moldable::new(id, walltime, HierarchyRequests::from_requests(Vec::new())))
+            .assign(JobAssignment::new(begin, begin + walltime - 1, ProcSet::from_iter(1..=resources), 0))
+            .build()
+    };
+    let check = |slot_set: &SlotSet, job: &Job| {
+        let (begin, end, proc_set) = job.window().unwrap();
+        let (left, right) = (slot_set.slot_id_at(begin, None).unwrap(), slot_set.slot_id_at(end, None).unwrap());
+        let count = proc_set.core_count();
+        quotas::check_slots_quotas(slot_set.iter().between(left, right), job, begin, end, count, count).map(|(msg, _, _)| msg)
+    };
+
+    let monday = t0 + 8 * 3600;
+    let tuesday = monday + 86400;
+    for period_counters in [false, true] {
+        let mut slot_set = build_slot_set(period_counters);
+        // On Monday morning, john runs two jobs, and bob uses 20 resource hours
+        for job in [job(1, "john", monday + 3600, 3600, 4), job(2, "john", monday + 3 * 3600, 3600, 4), job(3, "bob", monday + 3600, 5 * 3600, 4)] {
+            slot_set.split_slots_for_job_and_update_resources(&job, true, true, None);
+        }
+
+        // A third john job in the afternoon does not overlap the others, but is the third one of the workday
+        let expected = period_counters.then(|| "Running jobs exceeded".into());
+        assert_eq!(check(&slot_set, &job(4, "john", monday + 7 * 3600, 3600, 4)), expected);
+        // Bob can still use 4 resource hours on Monday, but not 8
+        assert_eq!(check(&slot_set, &job(5, "bob", monday + 7 * 3600, 3600, 4)), None);
+        let expected = period_counters.then(|| "Resources times exceeded".into());
+        assert_eq!(check(&slot_set, &job(6, "bob", monday + 7 * 3600, 3600, 8)), expected);
+
+        // The counters are reset on Tuesday
+        assert_eq!(check(&slot_set, &job(7, "john", tuesday + 7 * 3600, 3600, 4)), None);
+        assert_eq!(check(&slot_set, &job(8, "bob", tuesday + 7 * 3600, 3600, 8)), None);
+    }
+}
+
+#[test]
+fn test_period_counters_all_day_periodical() {
+    // At most 1 job per user and per workday, with a rule covering the whole workdays
+    let json = r#"{
+        "periodical": [["* mon-fri * *", "quotas_workday", "workdays"]],
+        "quotas_workday": {"*,*,*,/": [-1, 1, -1]}
+    }"#;
+    let t0 = period_weekstart(Local::now().timestamp());
+    let mut platform_config: PlatformConfig = generate_mock_platform_config(false, 256, 8, 4, 8, true);
+    platform_config.config.quotas_period_counters = true;
+    platform_config.quotas_config = QuotasConfig::load_from_json(json.to_string(), true, 100, 100, 3 * 7 * 24 * 3600).unwrap();
+    let mut slot_set = SlotSet::from_platform_config(Rc::new(platform_config), t0, t0 + 7 * 86400 - 1);
+    let job = |id: i64, begin: i64| {
+        JobBuilder::new(id)
+            .user("john".into())
+            .queue("default".into())
+            .moldable(Moldable::new(id, 3600, HierarchyRequests::from_requests(Vec::new())))
+            .assign(JobAssignment::new(begin, begin + 3600 - 1, ProcSet::from_iter(1..=4), 0))
+            .build()
+    };
+    let check = |slot_set: &SlotSet, job: &Job| {
+        let (begin, end, _) = job.window().unwrap();
+        let (left, right) = (slot_set.slot_id_at(begin, None).unwrap(), slot_set.slot_id_at(end, None).unwrap());
+        quotas::check_slots_quotas(slot_set.iter().between(left, right), job, begin, end, 4, 4).map(|(msg, _, _)| msg)
+    };
+
+    // Each workday is a separate period, although the occurrences of the rule follow each other
+    let monday = t0 + 10 * 3600;
+    let tuesday = monday + 86400;
+    assert_eq!(slot_set.slot_at(tuesday, None).unwrap().begin(), t0 + 86400);
+    slot_set.split_slots_for_job_and_update_resources(&job(1, monday), true, true, None);
+    assert_eq!(check(&slot_set, &job(2, monday + 5 * 3600)), Some("Running jobs exceeded".into()));
+    assert_eq!(check(&slot_set, &job(3, tuesday)), None);
+}
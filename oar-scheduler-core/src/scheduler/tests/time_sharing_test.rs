@@ -68,3 +68,17 @@ fn test_quotas_two_job_rules_nb_res_quotas_file() {
     assert_eq!(j5.resources, ProcSet::from_iter(1..=56));
     assert_eq!(j5.begin, 120);
 }
+
+#[test]
+fn test_time_sharing_from_type_str() {
+    assert_eq!(TimeSharingType::from_type_str("timesharing=*,*"), Some(TimeSharingType::AllAll));
+    assert_eq!(TimeSharingType::from_type_str("timesharing=user,*"), Some(TimeSharingType::UserAll));
+    assert_eq!(TimeSharingType::from_type_str("timesharing=*,user"), Some(TimeSharingType::UserAll));
+    assert_eq!(TimeSharingType::from_type_str("timesharing=*,name"), Some(TimeSharingType::AllName));
+    assert_eq!(TimeSharingType::from_type_str("timesharing=name,*"), Some(TimeSharingType::AllName));
+    assert_eq!(TimeSharingType::from_type_str("timesharing=user,name"), Some(TimeSharingType::UserName));
+
+    assert_eq!(TimeSharingType::from_type_str("timesharing=foo,*"), None);
+    assert_eq!(TimeSharingType::from_type_str("timesharing=*"), None);
+    assert_eq!(TimeSharingType::from_type_str("placeholder=*,*"), None);
+}
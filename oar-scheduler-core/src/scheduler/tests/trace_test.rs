@@ -0,0 +1,59 @@
+use crate::model::job::{JobBuilder, Moldable, ProcSetCoresOp};
+use crate::scheduler::calendar::QuotasConfig;
+use crate::scheduler::hierarchy::{HierarchyRequest, HierarchyRequests};
+use crate::scheduler::kamelot;
+use crate::scheduler::quotas::QuotasValue;
+use crate::scheduler::tests::platform_mock::{generate_mock_platform_config, PlatformBenchMock};
+use indexmap::indexmap;
+use std::collections::HashMap;
+
+#[test]
+fn test_job_traces() {
+    // 8 nodes of 32 cores, john being limited to 64 cores
+    let mut platform_config = generate_mock_platform_config(false, 256, 8, 4, 8, true);
+    platform_config.config.scheduler_job_traces = true;
+    platform_config.quotas_config = QuotasConfig::new(
+        true,
+        None,
+        HashMap::from([(("*".into(), "*".into(), "*".into(), "john".into()), QuotasValue::new(Some(64), None, None))]),
+        Box::new(["*".into()]),
+    );
+    let available = platform_config.resource_set.default_resources.clone();
+    let job = |id: i64, user: &str, nodes: u32| {
+        let requests = HierarchyRequests::from_requests(vec![HierarchyRequest::new(available.clone(), vec![("nodes".into(), nodes)])]);
+        JobBuilder::new(id).user(user.into()).moldable(Moldable::new(10 + id, 100, requests)).build()
+    };
+    let waiting_jobs = indexmap![
+        1 => job(1, "john", 2),
+        2 => job(2, "john", 4), // Exceeds the quotas
+        3 => job(3, "toto", 8), // Waits for job 1
+    ];
+    let mut platform = PlatformBenchMock::new(platform_config, vec![], waiting_jobs);
+
+    let stats = kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+    assert_eq!((stats.scheduled, stats.rejected), (2, 1));
+    assert_eq!(stats.job_traces.iter().map(|trace| trace.job_id).collect::<Vec<i64>>(), vec![1, 2, 3]);
+
+    // Placed in the first window tried
+    let trace_1 = &stats.job_traces[0];
+    assert_eq!(trace_1.steps.len(), 1);
+    assert!(trace_1.steps[0].is_success());
+    assert_eq!((trace_1.steps[0].moldable_id, trace_1.steps[0].begin, trace_1.steps[0].end), (11, 0, 99));
+    assert_eq!(trace_1.last_failure(), None);
+
+    // Rejected: the trace ends with the quotas failure of the last window tried
+    let trace_2 = &stats.job_traces[1];
+    let last_step = trace_2.steps.last().unwrap();
+    assert_eq!(trace_2.last_failure(), Some(last_step));
+    assert_eq!(last_step.hierarchy_result.as_ref().map(|proc_set| proc_set.core_count()), Some(128));
+    assert!(last_step.quotas_exceeded.as_deref().unwrap().starts_with("Resources exceeded"));
+    assert!(trace_2.steps.iter().all(|step| step.quotas_exceeded.is_some()));
+
+    // Not enough free nodes alongside job 1, then placed after it
+    let trace_3 = &stats.job_traces[2];
+    assert_eq!(trace_3.steps.len(), 2);
+    assert_eq!(trace_3.steps[0].hierarchy_result, None);
+    assert_eq!(trace_3.steps[0].available_resources.core_count(), 192);
+    assert!(trace_3.steps[1].is_success());
+    assert_eq!(trace_3.steps[1].begin, 100);
+}
@@ -0,0 +1,41 @@
+use crate::model::job::ProcSet;
+#[cfg(feature = "pyo3")]
+use pyo3::IntoPyObject;
+
+/// Placements tried for a waiting job during a scheduling cycle, in the order they were tried.
+/// Only recorded with `scheduler_job_traces`, and returned in [`crate::scheduler::kamelot::SchedulingStats::job_traces`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "pyo3", derive(IntoPyObject))]
+pub struct JobTrace {
+    pub job_id: i64,
+    pub steps: Vec<TraceStep>,
+}
+
+impl JobTrace {
+    /// Returns the last step that failed, e.g., the reason why a rejected job did not get its last window.
+    pub fn last_failure(&self) -> Option<&TraceStep> {
+        self.steps.iter().rev().find(|step| !step.is_success())
+    }
+}
+
+/// Window of the slot set tried for a moldable of a job by [`crate::scheduler::scheduling::find_slots_for_moldable`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceStep {
+    pub moldable_id: i64,
+    /// Begin and end times of the window tried.
+    pub begin: i64,
+    pub end: i64,
+    /// Resources available in all the slots of the window.
+    pub available_resources: ProcSet,
+    /// Resources found by the hierarchy request (or the `find` hook) among the available resources, `None` if the request failed.
+    pub hierarchy_result: Option<ProcSet>,
+    /// Reason why the quotas rejected the resources found, `None` if they were not checked or were not exceeded.
+    pub quotas_exceeded: Option<Box<str>>,
+}
+
+impl TraceStep {
+    /// Whether the job could be placed in this window.
+    pub fn is_success(&self) -> bool {
+        self.hierarchy_result.is_some() && self.quotas_exceeded.is_none()
+    }
+}
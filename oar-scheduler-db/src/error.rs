@@ -0,0 +1,66 @@
+use std::fmt::{Display, Formatter};
+
+/// Error returned by the database requests, hiding the sqlx backend error behind a few categories
+/// so that callers can tell a missing row from a schema mismatch or from a lost connection.
+#[derive(Debug)]
+pub enum DbError {
+    /// The request expected a row, but none was returned.
+    NotFound,
+    /// The database schema does not match the expected one, e.g., a table, a column or a type is missing.
+    Schema(String),
+    /// A value could not be decoded into the expected Rust type.
+    Decode(String),
+    /// Any other error of the database backend, e.g., a connection or I/O error.
+    Backend(sqlx::Error),
+    /// The resource index does not come from the loaded resource set, so it has no database resource id.
+    UnknownResourceIndex(u32),
+    /// The resource id is not in the loaded resource set, e.g., a resource added to the database since it was loaded.
+    UnknownResourceId(i32),
+}
+
+impl Display for DbError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DbError::NotFound => write!(f, "no row returned by the database"),
+            DbError::Schema(msg) => write!(f, "database schema error: {}", msg),
+            DbError::Decode(msg) => write!(f, "database decode error: {}", msg),
+            DbError::Backend(err) => write!(f, "database backend error: {}", err),
+            DbError::UnknownResourceIndex(index) => write!(f, "unknown resource index {}", index),
+            DbError::UnknownResourceId(id) => write!(f, "unknown resource id {}", id),
+        }
+    }
+}
+
+impl std::error::Error for DbError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DbError::Backend(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<sqlx::Error> for DbError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => DbError::NotFound,
+            sqlx::Error::ColumnNotFound(_) | sqlx::Error::ColumnIndexOutOfBounds { .. } | sqlx::Error::TypeNotFound { .. } => {
+                DbError::Schema(err.to_string())
+            }
+            sqlx::Error::ColumnDecode { .. } | sqlx::Error::Decode(_) => DbError::Decode(err.to_string()),
+            sqlx::Error::Database(ref db_err) if is_schema_error(db_err.as_ref()) => DbError::Schema(err.to_string()),
+            err => DbError::Backend(err),
+        }
+    }
+}
+
+/// Returns true if the database rejected the request because a column or a table does not exist:
+/// SQLSTATE `42703` (undefined column) or `42P01` (undefined table) with PostgreSQL,
+/// and the `no such column` or `no such table` messages with SQLite, which only reports a generic error code.
+fn is_schema_error(db_err: &dyn sqlx::error::DatabaseError) -> bool {
+    if db_err.code().is_some_and(|code| code == "42703" || code == "42P01") {
+        return true;
+    }
+    let message = db_err.message();
+    message.starts_with("no such column") || message.starts_with("no such table")
+}
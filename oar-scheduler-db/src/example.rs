@@ -1,3 +1,4 @@
+use crate::DbError;
 use dotenvy::dotenv;
 use rand::Rng;
 use sea_query::{Alias, ExprTrait, Iden, PostgresQueryBuilder, Query};
@@ -14,17 +15,15 @@ pub enum Users {
 }
 
 #[allow(dead_code)]
-pub async fn sqlx_sea_query_example() {
+pub async fn sqlx_sea_query_example() -> Result<(), DbError> {
     dotenv().ok();
 
-    let db_url = env::var("DATABASE_URL");
-    if db_url.is_err() {
+    let Ok(db_url) = env::var("DATABASE_URL") else {
         println!("WARNING: can’t run sqlx_sea_query_example() without a DATABASE_URL");
-        return;
-    }
-    let db_url = db_url.unwrap();
+        return Ok(());
+    };
 
-    let pool = PgPoolOptions::new().max_connections(5).connect(db_url.as_str()).await.unwrap();
+    let pool = PgPoolOptions::new().max_connections(5).connect(db_url.as_str()).await?;
 
     let column_name: String = rand::rng()
         .sample_iter(&rand::distr::Alphanumeric)
@@ -37,16 +36,15 @@ pub async fn sqlx_sea_query_example() {
     // create table if not exists
     sqlx::query("CREATE TABLE IF NOT EXISTS users (id serial PRIMARY KEY, name varchar NOT NULL)")
         .execute(&pool)
-        .await
-        .unwrap();
+        .await?;
 
     let sql = format!(
         "ALTER TABLE users ADD COLUMN IF NOT EXISTS \"{}\" varchar DEFAULT '{}'",
         column_name, "value"
     );
-    sqlx::query(sql.as_str()).execute(&pool).await.unwrap();
+    sqlx::query(sql.as_str()).execute(&pool).await?;
 
-    let tx = pool.begin().await.unwrap();
+    let tx = pool.begin().await?;
 
     let (sql, values) = Query::insert()
         .into_table(Users::Table)
@@ -54,9 +52,9 @@ pub async fn sqlx_sea_query_example() {
         .values_panic(vec!["test".into(), column_name.clone().into()])
         .build_sqlx(PostgresQueryBuilder);
     println!("Insert SQL: {}", sql);
-    sqlx::query_with(sql.as_str(), values).execute(&pool).await.unwrap();
+    sqlx::query_with(sql.as_str(), values).execute(&pool).await?;
 
-    tx.commit().await.unwrap();
+    tx.commit().await?;
 
     let (sql, values) = Query::select()
         .column(Users::Id)
@@ -66,8 +64,9 @@ pub async fn sqlx_sea_query_example() {
         .and_where(sea_query::Expr::col(Alias::new(&column_name)).eq(&column_name))
         .build_sqlx(PostgresQueryBuilder);
     println!("Select SQL: {}", sql);
-    let row: (i32, String, String) = sqlx::query_as_with(sql.as_str(), values).fetch_one(&pool).await.unwrap();
+    let row: (i32, String, String) = sqlx::query_as_with(sql.as_str(), values).fetch_one(&pool).await?;
 
     assert_eq!(row.1, "test".to_string());
     assert_eq!(row.2, column_name);
+    Ok(())
 }
@@ -1,6 +1,7 @@
-use crate::model::resources::{Resource, ResourceLabelValue};
-use log::{debug, info};
+use crate::model::resources::{Resource, ResourceLabelValue, ResourcesSignature};
+use log::{debug, info, warn};
 use oar_scheduler_core::model::configuration::Configuration;
+use oar_scheduler_core::model::walltime::QueueDefaultEstimator;
 use oar_scheduler_core::platform::{ProcSet, ResourceSet};
 use oar_scheduler_core::scheduler::hierarchy::Hierarchy;
 use sea_query::{DeleteStatement, Iden, InsertStatement, PostgresQueryBuilder, QueryBuilder, SelectStatement, SqliteQueryBuilder, UpdateStatement};
@@ -8,12 +9,19 @@ use sea_query_sqlx::{SqlxBinder, SqlxValues};
 use sqlx::any::{install_default_drivers, AnyRow};
 use sqlx::pool::PoolOptions;
 use sqlx::AnyPool;
-use sqlx::{Any, Error};
+use sqlx::{Any, Error, Transaction};
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::time::Duration;
 use tokio::runtime::Runtime;
 
+pub mod error;
 pub mod example;
 pub mod model;
+pub mod properties;
+
+pub use error::DbError;
 
 enum Backend {
     Postgres,
@@ -21,6 +29,32 @@ enum Backend {
 }
 
 
+/// Weight of `resource` read from its `weight_label` column. Defaults to 1 if the value is missing or not a non-negative integer.
+fn resource_weight(resource: &Resource, weight_label: &str) -> u32 {
+    let weight = match resource.labels.get(weight_label) {
+        Some(ResourceLabelValue::Integer(value)) => u32::try_from(*value).ok(),
+        Some(ResourceLabelValue::Varchar(value)) => value.trim().parse::<u32>().ok(),
+        None => None,
+    };
+    weight.unwrap_or_else(|| {
+        warn!("Resource {} has no valid weight in column {}: it weighs 1", resource.id, weight_label);
+        1
+    })
+}
+
+/// Returns true if `error` means that the database can't be reached, rather than a failure of the statement itself.
+/// PostgreSQL reports a server shutting down or restarting as database errors with the SQLSTATE `57P01` (admin shutdown),
+/// `57P02` (crash shutdown) or `57P03` (cannot connect now).
+fn is_connection_error(error: &Error) -> bool {
+    match error {
+        Error::Database(db_err) => db_err.code().is_some_and(|code| matches!(code.as_ref(), "57P01" | "57P02" | "57P03")),
+        _ => matches!(error, Error::Io(_) | Error::Tls(_) | Error::Protocol(_) | Error::PoolTimedOut | Error::PoolClosed | Error::WorkerCrashed),
+    }
+}
+
+/// Maximum delay between two connection attempts, the delay doubling after each failed attempt.
+const MAX_CONNECT_RETRY_DELAY: Duration = Duration::from_secs(60);
+
 impl From<&str> for Backend {
     fn from(s: &str) -> Self {
         match s.to_lowercase().as_str() {
@@ -57,40 +91,159 @@ impl Backend {
     }
 }
 
+/// Settings used to (re)connect to the database, see [`Session::new`] and [`Session::ensure_connected`].
+struct ConnectSettings {
+    url: String,
+    /// `PRAGMA` statements run on each new SQLite connection.
+    sqlite_pragmas: Vec<String>,
+    attempts: u32,
+    retry_delay: Duration,
+}
+
 pub struct Session {
-    /// sqlx connection pool.
-    pool: AnyPool,
+    /// sqlx connection pool, replaced by [`Session::ensure_connected`] when the connection is lost.
+    pool: RefCell<AnyPool>,
+    connect_settings: ConnectSettings,
     /// Database backend type (Postgres or Sqlite).
     backend: Backend,
     /// Tokio runtime used to run async database operations in a sync context.
     runtime: Runtime,
     /// Maps the database resource ID to the enumerated ID used in the ProcSet.
     resource_id_to_resource_index: HashMap<i32, u32>,
+    /// Maps the enumerated ID used in the ProcSet back to the database resource ID.
     resource_index_to_resource_id: HashMap<u32, i32>,
+    /// When set, returned by [`Session::get_now`] instead of querying the database clock. See [`Session::set_reference_now`].
+    reference_now: Option<i64>,
+    /// Supplies the walltime of the loaded moldables without walltime, built from the configuration by [`Session::new`]
+    /// until replaced with [`Session::set_walltime_estimator`], e.g., by the platform using a newer configuration.
+    walltime_estimator: QueueDefaultEstimator,
+    /// Signature of the resources table when the last resource set was built by [`Session::get_resource_set`],
+    /// with the default resources of this resource set. See [`Session::reload_resource_set_if_changed`].
+    resource_set_signature: Option<(ResourcesSignature, ProcSet)>,
+    /// Number of statements run by the session. See [`Session::query_count`].
+    query_count: Cell<u64>,
 }
 
 impl Session {
+    /// Connects to the database. A failed connection is retried up to `db_connect_attempts` times in total,
+    /// waiting `db_connect_retry_delay` milliseconds before the first retry, and twice longer before each next one,
+    /// up to a minute (or `db_connect_retry_delay` if longer).
+    /// Panics if all the attempts fail.
     pub fn new(config: &Configuration) -> Session {
-        let max_connections = 1; // Only one connection is needed since we are using a single-threaded runtime.
         let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
-
-        let (pool, backend) = runtime.block_on(async {
-            install_default_drivers();
-
-            let pool = PoolOptions::<Any>::new()
-                .max_connections(max_connections)
-                .connect(Self::get_database_url(config).as_str())
-                .await
-                .expect("Failed to create connection pool");
-
-            let conn = pool.acquire().await.expect("Failed to acquire connection");
-            let backend = conn.backend_name().into();
-            conn.close().await.unwrap();
-            (pool, backend)
-        });
+        install_default_drivers();
+        let connect_settings = ConnectSettings {
+            url: Self::get_database_url(config),
+            sqlite_pragmas: Self::get_sqlite_pragmas(config),
+            attempts: config.db_connect_attempts.max(1),
+            retry_delay: Duration::from_millis(config.db_connect_retry_delay),
+        };
+        let (pool, backend) = Self::connect_with_retry(&runtime, &connect_settings)
+            .unwrap_or_else(|e| panic!("Failed to connect to the database after {} attempt(s): {}", connect_settings.attempts, e));
         let resource_id_to_resource_index = HashMap::new();
         let resource_index_to_resource_id = HashMap::new();
-        Session { pool, backend, runtime, resource_id_to_resource_index, resource_index_to_resource_id }
+        Session {
+            pool: RefCell::new(pool),
+            connect_settings,
+            backend,
+            runtime,
+            resource_id_to_resource_index,
+            resource_index_to_resource_id,
+            reference_now: None,
+            walltime_estimator: config.walltime_estimator(),
+            resource_set_signature: None,
+            query_count: Cell::new(0),
+        }
+    }
+    /// Creates the connection pool, retrying as configured in `settings`. Returns the error of the last attempt if they all fail.
+    fn connect_with_retry(runtime: &Runtime, settings: &ConnectSettings) -> Result<(AnyPool, Backend), Error> {
+        let mut retry_delay = settings.retry_delay;
+        let mut attempt = 1;
+        loop {
+            match runtime.block_on(Self::connect(settings)) {
+                Ok(connected) => return Ok(connected),
+                Err(e) if attempt < settings.attempts => {
+                    warn!("Database connection attempt {}/{} failed: {}. Retrying in {:?}", attempt, settings.attempts, e, retry_delay);
+                    std::thread::sleep(retry_delay);
+                    retry_delay = retry_delay.saturating_mul(2).min(MAX_CONNECT_RETRY_DELAY.max(settings.retry_delay));
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    async fn connect(settings: &ConnectSettings) -> Result<(AnyPool, Backend), Error> {
+        let max_connections = 1; // Only one connection is needed since we are using a single-threaded runtime.
+        let pragmas = settings.sqlite_pragmas.clone();
+        let pool = PoolOptions::<Any>::new()
+            .max_connections(max_connections)
+            .after_connect(move |conn, _meta| {
+                let pragmas = pragmas.clone();
+                Box::pin(async move {
+                    if conn.backend_name() == "SQLite" {
+                        for pragma in pragmas {
+                            sqlx::query(&pragma).execute(&mut *conn).await?;
+                        }
+                    }
+                    Ok(())
+                })
+            })
+            .connect(settings.url.as_str())
+            .await?;
+
+        let conn = pool.acquire().await?;
+        let backend = conn.backend_name().into();
+        conn.close().await?;
+        Ok((pool, backend))
+    }
+    /// Checks that the database is reachable, and re-establishes the connection pool (retrying as in [`Session::new`])
+    /// if the check fails with a connection error, e.g., after a database restart.
+    /// Should be called at the beginning of each scheduling round, and after a request failed with a [`DbError::Backend`] error,
+    /// before trying again.
+    /// Note that reconnecting to an in-memory SQLite database gives a new, empty database.
+    pub fn ensure_connected(&self) -> Result<(), DbError> {
+        let pool = self.pool();
+        match self.runtime.block_on(sqlx::query("SELECT 1").execute(&pool)) {
+            Ok(_) => Ok(()),
+            Err(e) if is_connection_error(&e) => {
+                warn!("Database connection lost: {}. Reconnecting", e);
+                let (pool, _backend) = Self::connect_with_retry(&self.runtime, &self.connect_settings)?;
+                self.runtime.block_on(self.pool.replace(pool).close());
+                info!("Database connection re-established");
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+    /// Returns a handle to the connection pool.
+    fn pool(&self) -> AnyPool {
+        self.pool.borrow().clone()
+    }
+    /// Returns the number of statements built by the models and run by the session since its creation,
+    /// e.g., to check that loading the jobs does not issue one query per job.
+    pub fn query_count(&self) -> u64 {
+        self.query_count.get()
+    }
+    /// Logs a statement about to be run, and counts it in [`Session::query_count`].
+    fn log_query(&self, sql: &str, values: &SqlxValues) {
+        self.query_count.set(self.query_count.get() + 1);
+        debug!("SQL: {}   VALUES: {:?}", sql, values);
+    }
+    /// Returns the `PRAGMA` statements run on each new SQLite connection,
+    /// from the `db_sqlite_journal_mode` and `db_sqlite_busy_timeout` configuration.
+    fn get_sqlite_pragmas(config: &Configuration) -> Vec<String> {
+        let mut pragmas = Vec::new();
+        if config.db_sqlite_busy_timeout >= 0 {
+            pragmas.push(format!("PRAGMA busy_timeout = {}", config.db_sqlite_busy_timeout));
+        }
+        let journal_mode = config.db_sqlite_journal_mode.trim();
+        if !journal_mode.is_empty() {
+            if !journal_mode.chars().all(|c| c.is_ascii_alphabetic()) {
+                panic!("Invalid DB_SQLITE_JOURNAL_MODE {}", journal_mode);
+            }
+            pragmas.push(format!("PRAGMA journal_mode = {}", journal_mode));
+        }
+        pragmas
     }
     pub fn get_database_url(config: &Configuration) -> String {
         match config.db_type.to_lowercase().as_str() {
@@ -109,81 +262,139 @@ impl Session {
             _ => panic!("Unsupported database type {}", config.db_type),
         }
     }
-    pub fn get_now(&self) -> i64 {
-        match self.backend {
-            Backend::Postgres => {
-                let row: (i64,) = self.runtime.block_on(async {
-                    sqlx::query_as("SELECT EXTRACT(EPOCH FROM current_timestamp)::BIGINT")
-                        .fetch_one(&self.pool)
-                        .await
-                        .expect("Failed to fetch current time")
-                });
-                row.0
-            }
-            Backend::Sqlite => {
-                let row: (i64,) = self.runtime.block_on(async {
-                    sqlx::query_as("SELECT CAST(strftime('%s','now') AS INTEGER)")
-                        .fetch_one(&self.pool)
-                        .await
-                        .expect("Failed to fetch current time")
-                });
-                row.0
-            }
+    /// Returns the current time of the database clock, in seconds since the epoch.
+    /// If a reference time is set with [`Session::set_reference_now`], it is returned instead, without any database round-trip.
+    pub fn get_now(&self) -> Result<i64, DbError> {
+        if let Some(now) = self.reference_now {
+            return Ok(now);
         }
+        let sql = match self.backend {
+            Backend::Postgres => "SELECT EXTRACT(EPOCH FROM current_timestamp)::BIGINT",
+            Backend::Sqlite => "SELECT CAST(strftime('%s','now') AS INTEGER)",
+        };
+        let row: (i64,) = self.runtime.block_on(async { sqlx::query_as(sql).fetch_one(&self.pool()).await })?;
+        Ok(row.0)
+    }
+    /// Returns the current time of the local clock, in seconds since the epoch.
+    /// Unlike [`Session::get_now`], it never queries the database, for environments where the database clock is not trusted or not needed.
+    pub fn get_now_local(&self) -> i64 {
+        chrono::Local::now().timestamp()
+    }
+    /// Freezes the time returned by [`Session::get_now`] to `now`, e.g., for the duration of a scheduling cycle,
+    /// so that all the database writes of the cycle share the same time and the database clock is not queried again.
+    pub fn set_reference_now(&mut self, now: i64) {
+        self.reference_now = Some(now);
     }
-    pub(crate) async fn begin(&self) -> sqlx::Transaction<'_, Any> {
-        self.pool.begin().await.expect("Failed to begin transaction")
+    /// Makes [`Session::get_now`] query the database clock again.
+    pub fn clear_reference_now(&mut self) {
+        self.reference_now = None;
     }
-    pub fn create_schema(&self) {
+    /// Replaces the estimator supplying the walltime of the loaded moldables without walltime,
+    /// by default the [`Configuration::walltime_estimator`] of the configuration the session was created with.
+    pub fn set_walltime_estimator(&mut self, walltime_estimator: QueueDefaultEstimator) {
+        self.walltime_estimator = walltime_estimator;
+    }
+    pub(crate) async fn begin(&self) -> Result<sqlx::Transaction<'_, Any>, DbError> {
+        Ok(self.pool().begin().await?)
+    }
+    pub fn create_schema(&self) -> Result<(), DbError> {
         let sql = match self.backend {
             Backend::Postgres => include_str!("sql/up-postgres.sql"),
             Backend::Sqlite => include_str!("sql/up-sqlite.sql"),
         };
-        self.runtime.block_on(async {
-            sqlx::raw_sql(sql).execute(&self.pool).await.expect("Failed to create schema");
-        });
+        self.runtime.block_on(async { sqlx::raw_sql(sql).execute(&self.pool()).await })?;
+        Ok(())
     }
     /// From test with DB: empty all tables
-    pub fn empty_all(&self) {
+    pub fn empty_all(&self) -> Result<(), DbError> {
         let sql = match self.backend {
             Backend::Postgres => include_str!("sql/truncate-postgres.sql"),
             Backend::Sqlite => include_str!("sql/delete-sqlite.sql"),
         };
-        self.runtime.block_on(async {
-            sqlx::raw_sql(sql).execute(&self.pool).await.expect("Failed to create schema");
-        });
+        self.runtime.block_on(async { sqlx::raw_sql(sql).execute(&self.pool()).await })?;
+        Ok(())
     }
     /**
     From test with DB: reset resources by drop it (if exists) and recreate it. Needed
     because field can be added by some tests (adding properties to caracterise ressources)
     */
-    pub fn reset_resources(&self) {
+    pub fn reset_resources(&self) -> Result<(), DbError> {
         let sql = match self.backend {
             Backend::Postgres => include_str!("sql/reset-resources-postgres.sql"),
             Backend::Sqlite => include_str!("sql/reset-resources-sqlite.sql"),
         };
-        self.runtime.block_on(async {
-            sqlx::raw_sql(sql).execute(&self.pool).await.expect("Failed to create schema");
-        });
+        self.runtime.block_on(async { sqlx::raw_sql(sql).execute(&self.pool()).await })?;
+        Ok(())
     }
 
-    pub fn reset(&self) {
-        self.empty_all();
-        self.reset_resources();
+    pub fn reset(&self) -> Result<(), DbError> {
+        self.empty_all()?;
+        self.reset_resources()
     }
 
-    pub fn get_resource_set(&mut self, config: &Configuration) -> ResourceSet {
-        let mut resource_id_to_resource_index = HashMap::new();
-        let mut resource_index_to_resource_id = HashMap::new();
+    /// Loads the resources from the database, ordered by the SQL `ORDER BY` clause `scheduler_resource_order`,
+    /// and builds the resource set, the resources being enumerated in this order.
+    pub fn get_resource_set(&mut self, config: &Configuration) -> Result<ResourceSet, DbError> {
+        let labels = Self::get_hierarchy_labels(config);
+        let order_by = config.scheduler_resource_order.clone().unwrap_or("type, network_address".to_string());
+        let signature = Resource::get_signature(self)?;
+        let resources = Resource::get_all_sorted(self, order_by.as_str(), &Self::get_loaded_labels(config, &labels))?;
+        let resource_set = self.build_resource_set(config, &labels, resources)?;
+        self.resource_set_signature = Some((signature, resource_set.default_resources.clone()));
+        Ok(resource_set)
+    }
+    /// Same as [`Session::get_resource_set`], but returns `None` if `prev` is still up to date, skipping the loading of the resources
+    /// and the construction of the resource set and hierarchy. `prev` is up to date if it is the resource set built by the last call
+    /// to [`Session::get_resource_set`] or to this function, and if the [`ResourcesSignature`] of the resources table did not change since.
+    /// Only a single aggregate query is run to compute the signature.
+    pub fn reload_resource_set_if_changed(&mut self, prev: &ResourceSet, config: &Configuration) -> Result<Option<ResourceSet>, DbError> {
+        let signature = Resource::get_signature(self)?;
+        if let Some((last_signature, last_default_resources)) = &self.resource_set_signature
+            && *last_signature == signature
+            && *last_default_resources == prev.default_resources
+        {
+            debug!("Resources unchanged: the resource set is not rebuilt");
+            return Ok(None);
+        }
+        let resource_set = self.get_resource_set(config)?;
+        Ok(Some(resource_set))
+    }
+    /// Same as [`Session::get_resource_set`], but the resources are ordered in Rust by `comparator` instead of by `scheduler_resource_order`,
+    /// e.g., to sort the nodes by the numeric suffix of their `network_address`. Resources comparing equal are kept ordered by id.
+    pub fn get_resource_set_with_comparator<F>(&mut self, config: &Configuration, comparator: F) -> Result<ResourceSet, DbError>
+    where
+        F: Fn(&Resource, &Resource) -> Ordering,
+    {
+        let labels = Self::get_hierarchy_labels(config);
+        let mut resources = Resource::get_all_sorted(self, "resource_id", &Self::get_loaded_labels(config, &labels))?;
+        resources.sort_by(comparator);
+        // Not ordered as by `scheduler_resource_order`: the next reload always rebuilds the resource set
+        self.resource_set_signature = None;
+        self.build_resource_set(config, &labels, resources)
+    }
+    fn get_hierarchy_labels(config: &Configuration) -> Vec<Box<str>> {
         let labels = config
             .hierarchy_labels
             .clone()
             .map(|s| s.split(',').map(|s| s.trim().to_string().into_boxed_str()).collect())
             .unwrap_or(vec![Box::from("resource_id"), Box::from("network_address")]);
         info!("Resource labels configured for hierarchy: {:?}", labels);
-
-        let order_by = config.scheduler_resource_order.clone().unwrap_or("type, network_address".to_string());
-        let resources = Resource::get_all_sorted(&self, order_by.as_str(), &labels).unwrap();
+        labels
+    }
+    /// Labels loaded with the resources: the hierarchy `labels`, and the `scheduler_resource_weight_label` column if set.
+    fn get_loaded_labels(config: &Configuration, labels: &[Box<str>]) -> Vec<Box<str>> {
+        let mut loaded_labels = labels.to_vec();
+        if let Some(weight_label) = &config.scheduler_resource_weight_label
+            && !loaded_labels.iter().any(|label| label.as_ref() == weight_label)
+        {
+            loaded_labels.push(Box::from(weight_label.as_str()));
+        }
+        loaded_labels
+    }
+    /// Builds the resource set from the ordered `resources`, and maps their ids to their index in this order.
+    fn build_resource_set(&mut self, config: &Configuration, labels: &Vec<Box<str>>, resources: Vec<Resource>) -> Result<ResourceSet, DbError> {
+        let mut resource_id_to_resource_index = HashMap::new();
+        let mut resource_index_to_resource_id = HashMap::new();
         info!("Loaded {} resources from database", resources.len());
         info!("Resource labels considered: {:?}", labels);
 
@@ -197,31 +408,54 @@ impl Session {
 
         let mut nb_resources_not_dead = 0;
         let mut nb_resources_default_not_dead = 0;
+        let mut weighted_resources_not_dead = 0;
+        let mut weighted_resources_default_not_dead = 0;
         let mut suspendable_resources = Vec::new();
         let mut default_resources = Vec::new();
         let mut available_upto_map: HashMap<i64, Vec<u32>> = HashMap::new();
+        let mut available_from_map: HashMap<i64, Vec<u32>> = HashMap::new();
+        let now = self.get_now()?;
         // Mapping: resource label name -> (resource label value -> [enumerated id])
         let mut hierarchy_resources: HashMap<Box<str>, HashMap<ResourceLabelValue, Vec<u32>>> = HashMap::new();
+        let mut core_weights = Vec::new();
 
         for (enumerated_id, resource) in resources.iter().enumerate() {
             resource_id_to_resource_index.insert(resource.id, enumerated_id as u32);
             resource_index_to_resource_id.insert(enumerated_id as u32, resource.id);
             info!("Resource {}: id={} type={}, state={} map={:?}", enumerated_id, resource.id, resource.r#type, resource.state, resource.labels);
+            let weight = match &config.scheduler_resource_weight_label {
+                Some(weight_label) => {
+                    let weight = resource_weight(resource, weight_label);
+                    core_weights.push(weight);
+                    weight
+                }
+                None => 1,
+            };
             if resource.r#state.to_lowercase() != "dead" {
                 nb_resources_not_dead += 1;
+                weighted_resources_not_dead += weight as u64;
                 if resource.r#type.to_lowercase() == "default" {
                     nb_resources_default_not_dead += 1;
+                    weighted_resources_default_not_dead += weight as u64;
                 }
             }
-            if resource.state.to_lowercase() == "alive" || resource.state.to_lowercase() == "absent" {
+            // Absent resources are only schedulable from their return time (`available_upto`) if it is in the future, and excluded otherwise.
+            let is_absent = resource.state.to_lowercase() == "absent";
+            let absent_until = resource.available_upto.filter(|time| is_absent && *time > now);
+            if is_absent && absent_until.is_none() {
+                info!("Resource {} is absent without a future return time: it is excluded", resource.id);
+            }
+            if resource.state.to_lowercase() == "alive" || absent_until.is_some() {
                 if resource.r#type.to_lowercase() == "default" {
                     default_resources.push(enumerated_id as u32);
                 }
-                for (label, value) in resource.labels.iter() {
+                for (label, value) in resource.labels.iter().filter(|(label, _)| labels.contains(label)) {
                     let entry = hierarchy_resources.entry(label.clone()).or_insert_with(HashMap::new);
                     entry.entry(value.clone()).or_insert_with(Vec::new).push(enumerated_id as u32);
                 }
-                if let Some(time) = resource.available_upto {
+                if let Some(time) = absent_until {
+                    available_from_map.entry(time).or_default().push(enumerated_id as u32);
+                } else if let Some(time) = resource.available_upto {
                     available_upto_map.entry(time).or_insert_with(Vec::new).push(enumerated_id as u32);
                 }
                 if suspended_types.contains(&resource.r#type) {
@@ -234,34 +468,61 @@ impl Session {
         info!("Hierarchy resources: {:?}", hierarchy_resources);
         for (label, map) in hierarchy_resources.into_iter() {
             let mut partitions = Vec::new();
+            let mut partition_values = Vec::new();
             let mut is_unit = true;
-            for (_value, ids) in map.into_iter() {
+            // Partitions are kept in the label values order, e.g., `node2` before `node10`, whatever the hash map order
+            let mut values = map.into_iter().collect::<Vec<_>>();
+            values.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+            for (value, ids) in values.into_iter() {
                 if ids.len() > 1 {
                     is_unit = false;
                 }
                 partitions.push(ProcSet::from_iter(ids.iter()));
+                partition_values.push(match value {
+                    ResourceLabelValue::Integer(i) => i.to_string().into_boxed_str(),
+                    ResourceLabelValue::Varchar(s) => s.into_boxed_str(),
+                });
             }
             info!("Resource label {}: is_unit={} partitions={:?}", label, is_unit, partitions);
             hierarchy = if is_unit {
                 hierarchy.add_unit_partition(label)
             } else {
-                hierarchy.add_partition(label, partitions.into_boxed_slice())
+                hierarchy.add_valued_partition(label, partitions.into_boxed_slice(), partition_values.into_boxed_slice())
             };
         }
 
         self.resource_id_to_resource_index = resource_id_to_resource_index;
         self.resource_index_to_resource_id = resource_index_to_resource_id;
-        ResourceSet {
+        if nb_resources_not_dead == 0 {
+            warn!("No resource that is not dead found in the database ({} resources loaded): no job can be scheduled", resources.len());
+            return Ok(ResourceSet::empty());
+        }
+        Ok(ResourceSet {
             nb_resources_not_dead,
             nb_resources_default_not_dead,
+            weighted_resources_not_dead,
+            weighted_resources_default_not_dead,
             suspendable_resources: ProcSet::from_iter(suspendable_resources.iter()),
             default_resources: ProcSet::from_iter(default_resources.iter()),
             available_upto: available_upto_map
                 .into_iter()
                 .map(|(time, ids)| (time, ProcSet::from_iter(ids.iter())))
                 .collect(),
+            available_from: available_from_map
+                .into_iter()
+                .map(|(time, ids)| (time, ProcSet::from_iter(ids.iter())))
+                .collect(),
             hierarchy,
-        }
+            core_weight_prefix_sums: if core_weights.is_empty() { vec![] } else { ResourceSet::core_weight_prefix_sums(core_weights) },
+        })
+    }
+    /// Returns whether a resource set has been loaded, i.e., whether the resource ids can be mapped to enumerated ids.
+    pub(crate) fn has_resource_set(&self) -> bool {
+        !self.resource_id_to_resource_index.is_empty()
+    }
+    /// Returns the enumerated ids of all the resources of the loaded resource set, whatever their state.
+    pub(crate) fn resource_indexes(&self) -> ProcSet {
+        ProcSet::from_iter(self.resource_id_to_resource_index.values().copied())
     }
     pub fn resource_id_to_resource_index(&self, resource_id: i32) -> Option<u32> {
         self.resource_id_to_resource_index.get(&resource_id).cloned()
@@ -269,22 +530,39 @@ impl Session {
     pub fn resource_index_to_resource_id(&self, resource_index: u32) -> Option<i32> {
         self.resource_index_to_resource_id.get(&resource_index).cloned()
     }
+    /// Translates a `ProcSet` of enumerated IDs into the database resource IDs, in the `ProcSet` order.
+    /// Returns [`DbError::UnknownResourceIndex`] if an enumerated ID is unknown,
+    /// i.e., if it does not come from the resource set loaded by [`Session::get_resource_set`].
+    pub fn proc_set_to_resource_ids(&self, proc_set: &ProcSet) -> Result<Vec<i32>, DbError> {
+        proc_set
+            .iter()
+            .map(|resource_index| self.resource_index_to_resource_id(resource_index).ok_or(DbError::UnknownResourceIndex(resource_index)))
+            .collect()
+    }
 }
 
 trait SessionInsertStatement {
     async fn fetch_one<'q>(&'q self, session: &Session) -> Result<AnyRow, Error>;
     async fn execute<'q>(&'q self, session: &Session) -> Result<u64, Error>;
+    /// Same as `execute`, but runs the statement in the transaction `tx` instead of acquiring a connection from the pool.
+    async fn execute_in(&self, session: &Session, tx: &mut Transaction<'_, Any>) -> Result<u64, Error>;
 }
 impl SessionInsertStatement for InsertStatement {
     async fn fetch_one<'q>(&'q self, session: &Session) -> Result<AnyRow, Error> {
         let (sql, values) = session.backend.build_insert(&self);
-        debug!("SQL: {}   VALUES: {:?}", sql, values);
-        sqlx::query_with(sql.as_str(), values).fetch_one(&session.pool).await
+        session.log_query(&sql, &values);
+        sqlx::query_with(sql.as_str(), values).fetch_one(&session.pool()).await
     }
     async fn execute<'q>(&'q self, session: &Session) -> Result<u64, Error> {
         let (sql, values) = session.backend.build_insert(&self);
-        debug!("SQL: {}   VALUES: {:?}", sql, values);
-        let result = sqlx::query_with(sql.as_str(), values).execute(&session.pool).await?;
+        session.log_query(&sql, &values);
+        let result = sqlx::query_with(sql.as_str(), values).execute(&session.pool()).await?;
+        Ok(result.rows_affected())
+    }
+    async fn execute_in(&self, session: &Session, tx: &mut Transaction<'_, Any>) -> Result<u64, Error> {
+        let (sql, values) = session.backend.build_insert(self);
+        session.log_query(&sql, &values);
+        let result = sqlx::query_with(sql.as_str(), values).execute(&mut **tx).await?;
         Ok(result.rows_affected())
     }
 }
@@ -295,34 +573,50 @@ trait SessionSelectStatement {
 impl SessionSelectStatement for SelectStatement {
     async fn fetch_one<'q>(&'q self, session: &Session) -> Result<AnyRow, Error> {
         let (sql, values) = session.backend.build_select(&self);
-        debug!("SQL: {}   VALUES: {:?}", sql, values);
-        sqlx::query_with(sql.as_str(), values).fetch_one(&session.pool).await
+        session.log_query(&sql, &values);
+        sqlx::query_with(sql.as_str(), values).fetch_one(&session.pool()).await
     }
     async fn fetch_all<'q>(&'q self, session: &Session) -> Result<Vec<AnyRow>, Error> {
         let (sql, values) = session.backend.build_select(&self);
-        debug!("SQL: {}   VALUES: {:?}", sql, values);
-        sqlx::query_with(sql.as_str(), values).fetch_all(&session.pool).await
+        session.log_query(&sql, &values);
+        sqlx::query_with(sql.as_str(), values).fetch_all(&session.pool()).await
     }
 }
 trait SessionUpdateStatement {
     async fn execute<'q>(&'q self, session: &Session) -> Result<u64, Error>;
+    /// Same as `execute`, but runs the statement in the transaction `tx` instead of acquiring a connection from the pool.
+    async fn execute_in(&self, session: &Session, tx: &mut Transaction<'_, Any>) -> Result<u64, Error>;
 }
 impl SessionUpdateStatement for sea_query::UpdateStatement {
     async fn execute<'q>(&'q self, session: &Session) -> Result<u64, Error> {
         let (sql, values) = session.backend.build_update(&self);
-        debug!("SQL: {}   VALUES: {:?}", sql, values);
-        let result = sqlx::query_with(sql.as_str(), values).execute(&session.pool).await?;
+        session.log_query(&sql, &values);
+        let result = sqlx::query_with(sql.as_str(), values).execute(&session.pool()).await?;
+        Ok(result.rows_affected())
+    }
+    async fn execute_in(&self, session: &Session, tx: &mut Transaction<'_, Any>) -> Result<u64, Error> {
+        let (sql, values) = session.backend.build_update(self);
+        session.log_query(&sql, &values);
+        let result = sqlx::query_with(sql.as_str(), values).execute(&mut **tx).await?;
         Ok(result.rows_affected())
     }
 }
 trait SessionDeleteStatement {
     async fn execute<'q>(&'q self, session: &Session) -> Result<u64, Error>;
+    /// Same as `execute`, but runs the statement in the transaction `tx` instead of acquiring a connection from the pool.
+    async fn execute_in(&self, session: &Session, tx: &mut Transaction<'_, Any>) -> Result<u64, Error>;
 }
 impl SessionDeleteStatement for DeleteStatement {
     async fn execute<'q>(&'q self, session: &Session) -> Result<u64, Error> {
         let (sql, values) = session.backend.build_delete(&self);
-        debug!("SQL: {}   VALUES: {:?}", sql, values);
-        let result = sqlx::query_with(sql.as_str(), values).execute(&session.pool).await?;
+        session.log_query(&sql, &values);
+        let result = sqlx::query_with(sql.as_str(), values).execute(&session.pool()).await?;
+        Ok(result.rows_affected())
+    }
+    async fn execute_in(&self, session: &Session, tx: &mut Transaction<'_, Any>) -> Result<u64, Error> {
+        let (sql, values) = session.backend.build_delete(self);
+        session.log_query(&sql, &values);
+        let result = sqlx::query_with(sql.as_str(), values).execute(&mut **tx).await?;
         Ok(result.rows_affected())
     }
 }
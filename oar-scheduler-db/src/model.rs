@@ -7,6 +7,8 @@ pub mod queues;
 pub mod job_types;
 pub mod job_dependencies;
 pub mod moldable;
+pub mod accounting;
+pub mod walltime_change;
 
 pub trait SqlEnum {
     fn as_str(&self) -> &str;
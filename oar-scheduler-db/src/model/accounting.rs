@@ -0,0 +1,99 @@
+use crate::{DbError, Session, SessionInsertStatement, SessionSelectStatement};
+use sea_query::{Expr, ExprTrait, Iden, Query};
+use sqlx::Row;
+use std::collections::HashMap;
+
+#[derive(Iden)]
+enum Accountings {
+    #[iden = "accounting"]
+    Table,
+    #[iden = "window_start"]
+    WindowStart,
+    #[iden = "window_stop"]
+    WindowStop,
+    #[iden = "accounting_user"]
+    AccountingUser,
+    #[iden = "accounting_project"]
+    AccountingProject,
+    #[iden = "queue_name"]
+    QueueName,
+    #[iden = "consumption_type"]
+    ConsumptionType,
+    #[iden = "consumption"]
+    Consumption,
+}
+
+/// Consumptions summed by key, e.g., by user.
+pub type ConsumptionSums = HashMap<String, f64>;
+
+/// Resource consumption of a user and project in a queue over a time window, either `ASKED` or `USED`.
+#[derive(Debug, Clone)]
+pub struct Accounting {
+    pub window_start: i64,
+    pub window_stop: i64,
+    pub user: String,
+    pub project: String,
+    pub queue_name: String,
+    pub consumption_type: String,
+    pub consumption: i64,
+}
+impl Accounting {
+    pub fn insert(&self, session: &Session) -> Result<(), DbError> {
+        session.runtime.block_on(async {
+            Query::insert()
+                .into_table(Accountings::Table)
+                .columns(vec![
+                    Accountings::WindowStart,
+                    Accountings::WindowStop,
+                    Accountings::AccountingUser,
+                    Accountings::AccountingProject,
+                    Accountings::QueueName,
+                    Accountings::ConsumptionType,
+                    Accountings::Consumption,
+                ])
+                .values_panic(vec![
+                    Expr::val(self.window_start),
+                    Expr::val(self.window_stop),
+                    Expr::val(&self.user),
+                    Expr::val(&self.project),
+                    Expr::val(&self.queue_name),
+                    Expr::val(&self.consumption_type),
+                    Expr::val(self.consumption),
+                ])
+                .execute(session)
+                .await
+        })?;
+        Ok(())
+    }
+
+    /// Returns the `ASKED` and `USED` consumptions summed by user, for the accounting windows of `queues` within `[window_start, window_stop)`.
+    pub fn get_sum_by_user(
+        session: &Session,
+        queues: &[String],
+        window_start: i64,
+        window_stop: i64,
+    ) -> Result<(ConsumptionSums, ConsumptionSums), DbError> {
+        let rows = session.runtime.block_on(async {
+            Query::select()
+                .columns(vec![Accountings::AccountingUser, Accountings::ConsumptionType, Accountings::Consumption])
+                .from(Accountings::Table)
+                .and_where(Expr::col(Accountings::QueueName).is_in(queues))
+                .and_where(Expr::col(Accountings::WindowStart).gte(window_start))
+                .and_where(Expr::col(Accountings::WindowStop).lt(window_stop))
+                .to_owned()
+                .fetch_all(session)
+                .await
+        })?;
+
+        // Summed here rather than in SQL, as the type of the sum depends on the database backend
+        let mut asked = HashMap::new();
+        let mut used = HashMap::new();
+        for row in rows {
+            let consumption_type: String = row.try_get(Accountings::ConsumptionType.unquoted())?;
+            let sums = if consumption_type == "ASKED" { &mut asked } else { &mut used };
+            *sums.entry(row.try_get(Accountings::AccountingUser.unquoted())?).or_insert(0.0) +=
+                row.try_get::<i64, &str>(Accountings::Consumption.unquoted())? as f64;
+        }
+        Ok((asked, used))
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{Session, SessionInsertStatement};
+use crate::{DbError, Session, SessionInsertStatement};
 use sea_query::Iden;
 
 #[derive(Iden)]
@@ -29,8 +29,8 @@ pub enum EventLogHostnames {
     Hostname,
 }
 
-pub fn add_new_event(session: &Session, ev_type: &str, job_id: i64, description: &str) {
-    let now = session.get_now();
+pub fn add_new_event(session: &Session, ev_type: &str, job_id: i64, description: &str) -> Result<(), DbError> {
+    let now = session.get_now()?;
     session.runtime.block_on(async {
         sea_query::Query::insert()
             .into_table(EventLogs::Table)
@@ -44,6 +44,6 @@ pub fn add_new_event(session: &Session, ev_type: &str, job_id: i64, description:
             ])
             .execute(session)
             .await
-            .expect("Failed to insert new event log");
-    });
+    })?;
+    Ok(())
 }
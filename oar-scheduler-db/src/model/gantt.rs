@@ -5,7 +5,7 @@ use indexmap::IndexMap;
 use log::debug;
 use oar_scheduler_core::platform::Job;
 use sea_query::{Expr, ExprTrait, Iden, Query};
-use sqlx::Error;
+use crate::DbError;
 
 #[derive(Iden)]
 pub enum GanttJobsResources {
@@ -29,7 +29,7 @@ pub enum GanttJobsPredictions {
 
 /// Removes all entries in the tables GanttJobsResources and GanttJobsPredictions except
 /// the moldable ids of AR jobs with the reservation state `Scheduled` and the job state `Waiting` or `toAckReservation`.
-pub fn gantt_flush_tables(session: &Session) {
+pub fn gantt_flush_tables(session: &Session) -> Result<(), DbError> {
     session.runtime.block_on(async {
         let to_keep_moldables_ids_req = Query::select()
             .column(MoldableJobDescriptions::Id)
@@ -57,19 +57,24 @@ pub fn gantt_flush_tables(session: &Session) {
             .from_table(GanttJobsResources::Table)
             .cond_where(Expr::col(GanttJobsResources::MoldableId).not_in_subquery(to_keep_moldables_ids_req.clone()))
             .execute(session)
-            .await
-            .expect("Failed to flush gantt_jobs_resources table");
+            .await?;
 
         Query::delete()
             .from_table(GanttJobsPredictions::Table)
             .cond_where(Expr::col(GanttJobsPredictions::MoldableId).not_in_subquery(to_keep_moldables_ids_req.clone()))
             .execute(session)
-            .await
-            .expect("Failed to flush gantt_jobs_resources table");
-    });
+            .await?;
+        Ok(())
+    })
 }
 
-pub fn save_jobs_assignments_in_gantt(session: &Session, jobs: IndexMap<i64, Job>) -> Result<(), Error> {
+/// Saves the jobs assignments in the gantt tables, in a single transaction:
+/// the stale `gantt_jobs_predictions` and `gantt_jobs_resources` rows of all the moldables of the jobs are deleted,
+/// then the predicted start time and the resources of the chosen moldable of each job are inserted.
+/// The `assigned_moldable_job` of the jobs is left untouched: it is only set when the jobs are launched,
+/// the gantt moldable of a job being found through its gantt prediction (see [`crate::model::jobs::JobDatabaseRequests::get_gantt_jobs`]).
+/// Resources are converted to database resource ids with [`Session::proc_set_to_resource_ids`].
+pub fn save_jobs_assignments_in_gantt(session: &Session, jobs: IndexMap<i64, Job>) -> Result<(), DbError> {
     debug!("Saving {} assignments in gantt tables", jobs.len());
     if jobs.values().any(|job| job.assignment.is_none()) {
         panic!("Trying to save jobs assignments in gantt tables but some jobs have no assignment");
@@ -79,6 +84,7 @@ pub fn save_jobs_assignments_in_gantt(session: &Session, jobs: IndexMap<i64, Job
         return Ok(());
     }
     session.runtime.block_on(async {
+        let mut tx = session.begin().await?;
         let mut res_query = Query::insert()
             .into_table(GanttJobsResources::Table)
             .columns(vec![GanttJobsResources::MoldableId, GanttJobsResources::ResourceId])
@@ -87,19 +93,45 @@ pub fn save_jobs_assignments_in_gantt(session: &Session, jobs: IndexMap<i64, Job
             .into_table(GanttJobsPredictions::Table)
             .columns(vec![GanttJobsPredictions::MoldableId, GanttJobsPredictions::StartTime])
             .take();
+        let mut has_resources = false;
 
-        for job in jobs.values() {
+        // The chosen moldable of each job, which is not necessarily the first one of multi-moldable jobs
+        let moldable_ids = jobs
+            .values()
+            .map(|job| {
+                job.assignment
+                    .as_ref()
+                    .unwrap()
+                    .moldable_id(&job.moldables)
+                    .unwrap_or_else(|| panic!("Job {} has no moldable for its assignment", job.id))
+            })
+            .collect::<Vec<i64>>();
+        // A job has at most one gantt prediction: the rows of the moldables that are no longer chosen are deleted too
+        let all_moldable_ids = jobs.values().flat_map(|job| job.moldables.iter().map(|m| m.id)).collect::<Vec<i64>>();
+        Query::delete()
+            .from_table(GanttJobsResources::Table)
+            .and_where(Expr::col(GanttJobsResources::MoldableId).is_in(all_moldable_ids.clone()))
+            .execute_in(session, &mut tx)
+            .await?;
+        Query::delete()
+            .from_table(GanttJobsPredictions::Table)
+            .and_where(Expr::col(GanttJobsPredictions::MoldableId).is_in(all_moldable_ids))
+            .execute_in(session, &mut tx)
+            .await?;
+
+        for (job, moldable_id) in jobs.values().zip(moldable_ids) {
             let assignment = job.assignment.as_ref().unwrap();
-            let moldable_id = &job.moldables[assignment.moldable_index].id;
-            let begin = assignment.begin;
 
-            pred_query.values_panic(vec![Expr::val(*moldable_id), Expr::val(begin)]);
-            for res_id in &assignment.resources {
-                res_query.values_panic(vec![Expr::val(*moldable_id), Expr::val(session.resource_index_to_resource_id(res_id))]);
+            pred_query.values_panic(vec![Expr::val(moldable_id), Expr::val(assignment.begin)]);
+            for resource_id in session.proc_set_to_resource_ids(&assignment.resources)? {
+                res_query.values_panic(vec![Expr::val(moldable_id), Expr::val(resource_id)]);
+                has_resources = true;
             }
         }
-        res_query.execute(session).await?;
-        pred_query.execute(session).await?;
-        Ok(())
+        if has_resources {
+            res_query.execute_in(session, &mut tx).await?;
+        }
+        pred_query.execute_in(session, &mut tx).await?;
+        Ok(tx.commit().await?)
     })
 }
@@ -1,7 +1,10 @@
 use crate::model::jobs::Jobs;
-use crate::{Session, SessionSelectStatement};
-use sea_query::{Expr, ExprTrait, Iden, Query};
-use sqlx::{Error, Row};
+use crate::{Session, SessionInsertStatement, SessionSelectStatement};
+use sea_query::{Asterisk, Expr, ExprTrait, Iden, Query};
+use crate::DbError;
+use oar_scheduler_core::model::job::{DependencyType, JobDependency};
+use log::debug;
+use sqlx::Row;
 use std::collections::HashMap;
 
 #[derive(Iden)]
@@ -14,13 +17,17 @@ pub enum JobDependencies {
     RequiredJobId,
     #[iden = "job_dependency_index"]
     Index,
+    /// Condition on the termination of the required job, `ok` or `anyway` (see [`DependencyType`]).
+    /// This column is not part of the OAR 3.0.0 schema: when it is missing, dependencies are of type [`DependencyType::Legacy`].
+    #[iden = "job_dependency_type"]
+    Type,
 }
 
 pub struct AllJobDependencies {
-    dependencies: HashMap<i64, Vec<(i64, Box<str>, Option<i32>)>>,
+    dependencies: HashMap<i64, Vec<JobDependency>>,
 }
 impl AllJobDependencies {
-    pub(crate) async fn load_dependencies_for_jobs(session: &Session, jobs: Vec<i64>) -> Result<Self, Error> {
+    pub(crate) async fn load_dependencies_for_jobs(session: &Session, jobs: Vec<i64>) -> Result<Self, DbError> {
         if jobs.is_empty() {
             return Ok(Self {
                 dependencies: HashMap::new(),
@@ -28,14 +35,9 @@ impl AllJobDependencies {
         }
 
         let dependencies = Query::select()
-            .columns(vec![
-                JobDependencies::RequiredJobId.to_string(),
-                Jobs::State.to_string(),
-                Jobs::ExitCode.to_string(),
-            ])
-            .columns(vec![
-                (JobDependencies::Table, JobDependencies::JobId)
-            ])
+            .columns(vec![Jobs::State.to_string(), Jobs::ExitCode.to_string()])
+            // All the columns of the table, so that the optional type column is read when it exists
+            .column((JobDependencies::Table, Asterisk))
             .from(JobDependencies::Table)
             .inner_join(Jobs::Table, Expr::col((JobDependencies::Table, JobDependencies::RequiredJobId)).equals((Jobs::Table, Jobs::Id)))
             .and_where(Expr::col((JobDependencies::Table, JobDependencies::JobId)).is_in(jobs))
@@ -50,20 +52,62 @@ impl AllJobDependencies {
                     r.get::<i64, &str>(JobDependencies::RequiredJobId.unquoted()),
                     r.get::<String, &str>(Jobs::State.unquoted()).into_boxed_str(),
                     r.try_get::<i32, &str>(Jobs::ExitCode.unquoted()).ok(),
+                    r.try_get::<String, &str>(JobDependencies::Type.unquoted())
+                        .map(|dependency_type| DependencyType::parse(&dependency_type))
+                        .unwrap_or(DependencyType::Legacy),
                 )
             })
             .fold(
-                HashMap::<i64, Vec<(i64, Box<str>, Option<i32>)>>::new(),
-                |mut acc, (job_id, job_required_id, job_required_state, job_required_exit_code)| {
+                HashMap::<i64, Vec<JobDependency>>::new(),
+                |mut acc, (job_id, job_required_id, job_required_state, job_required_exit_code, dependency_type)| {
                     acc.entry(job_id)
                         .or_insert_with(Vec::new)
-                        .push((job_required_id, job_required_state, job_required_exit_code));
+                        .push((job_required_id, job_required_state, job_required_exit_code, dependency_type));
                     acc
                 },
             );
         Ok(Self { dependencies })
     }
-    pub fn get_job_dependencies(&self, job_id: i64) -> Vec<(i64, Box<str>, Option<i32>)> {
+    pub fn get_job_dependencies(&self, job_id: i64) -> Vec<JobDependency> {
         self.dependencies.get(&job_id).cloned().unwrap_or_default()
     }
 }
+
+/// Dependency of the job `job_id` on the job `job_id_required`.
+pub struct NewJobDependency {
+    pub job_id: i64,
+    pub job_id_required: i64,
+    pub dependency_type: DependencyType,
+}
+
+impl NewJobDependency {
+    /// Only meant to be used by tests, as dependencies are inserted by OAR on submission.
+    /// The type is only inserted if it is `anyway`, which requires the column added by [`NewJobDependency::add_type_column`].
+    /// The `ok` and `legacy` types are not inserted: they are loaded as `ok` if the column exists, and as `legacy` otherwise.
+    pub fn insert(&self, session: &Session) -> Result<(), DbError> {
+        session.runtime.block_on(async {
+            let mut columns = vec![JobDependencies::JobId, JobDependencies::RequiredJobId];
+            let mut values = vec![Expr::val(self.job_id), Expr::val(self.job_id_required)];
+            if self.dependency_type == DependencyType::Anyway {
+                columns.push(JobDependencies::Type);
+                values.push(Expr::val(self.dependency_type.as_str()));
+            }
+            Query::insert()
+                .into_table(JobDependencies::Table)
+                .columns(columns)
+                .values_panic(values)
+                .execute(session)
+                .await?;
+            Ok(())
+        })
+    }
+    /// Adds the optional `job_dependency_type` column to the `job_dependencies` table, defaulting to `ok`.
+    pub fn add_type_column(session: &Session) -> Result<(), DbError> {
+        session.runtime.block_on(async {
+            let sql = "ALTER TABLE job_dependencies ADD COLUMN job_dependency_type VARCHAR(6) NOT NULL DEFAULT 'ok';";
+            debug!("New Job Dependency Column SQL: {}", sql);
+            sqlx::query(sql).execute(&session.pool()).await?;
+            Ok(())
+        })
+    }
+}
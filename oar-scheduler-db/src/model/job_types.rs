@@ -1,6 +1,7 @@
 use crate::{Session, SessionSelectStatement};
 use sea_query::{Expr, ExprTrait, Iden, Query};
-use sqlx::{Error, Row};
+use crate::DbError;
+use sqlx::Row;
 use std::collections::HashMap;
 
 #[derive(Iden)]
@@ -19,7 +20,7 @@ pub struct AllJobTypes {
     types: HashMap<i64, HashMap<Box<str>, Option<Box<str>>>>,
 }
 impl AllJobTypes {
-    pub(crate) async fn load_type_for_jobs(session: &Session, jobs: Vec<i64>) -> Result<Self, Error> {
+    pub(crate) async fn load_type_for_jobs(session: &Session, jobs: Vec<i64>) -> Result<Self, DbError> {
         if jobs.is_empty() {
             return Ok(Self { types: HashMap::new() });
         }
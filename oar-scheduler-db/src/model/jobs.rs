@@ -1,16 +1,20 @@
 use crate::model::gantt::GanttJobsPredictions;
 use crate::model::job_dependencies::AllJobDependencies;
 use crate::model::job_types::{AllJobTypes, JobTypes};
-use crate::model::moldable::{AllJobMoldables, JobResourceDescriptions, JobResourceGroups, MoldableJobDescriptions};
+use crate::model::moldable::{AllJobMoldables, AssignedResources, JobResourceDescriptions, JobResourceGroups, MoldableJobDescriptions};
 use crate::model::SqlEnum;
 use crate::{Session, SessionInsertStatement, SessionSelectStatement, SessionUpdateStatement};
 use indexmap::IndexMap;
 use log::{debug, info, trace, warn};
 use oar_scheduler_core::model::job::JobBuilder;
-use oar_scheduler_core::platform::Job;
+use oar_scheduler_core::model::walltime::WalltimeEstimator;
+use oar_scheduler_core::platform::{Job, ProcSet};
 use sea_query::{Alias, Expr, Func, Query};
 use sea_query::{ExprTrait, Iden};
-use sqlx::{Error, Row};
+use crate::DbError;
+use sqlx::Row;
+use std::collections::HashMap;
+use std::rc::Rc;
 use std::io::{stdout, Write};
 
 // jobs and related tables
@@ -91,6 +95,7 @@ pub enum JobState {
     ToError,
     ToAckReservation,
     Error,
+    Terminated,
     Launching,
     Running,
     Finishing,
@@ -106,6 +111,7 @@ impl SqlEnum for JobState {
             JobState::ToError => "toError",
             JobState::ToAckReservation => "toAckReservation",
             JobState::Error => "Error",
+            JobState::Terminated => "Terminated",
             JobState::Launching => "Launching",
             JobState::Running => "Running",
             JobState::Finishing => "Finishing",
@@ -125,6 +131,7 @@ impl SqlEnum for JobState {
             "toError" => Some(JobState::ToError),
             "toAckReservation" => Some(JobState::ToAckReservation),
             "Error" => Some(JobState::Error),
+            "Terminated" => Some(JobState::Terminated),
             "Launching" => Some(JobState::Launching),
             "Running" => Some(JobState::Running),
             "Finishing" => Some(JobState::Finishing),
@@ -182,6 +189,14 @@ pub enum JobStateLogs {
     DateStop,
 }
 
+/// Entry of the `job_state_logs` table. `date_stop` is 0 while the job is still in this state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobStateLog {
+    pub job_state: String,
+    pub date_start: i64,
+    pub date_stop: i64,
+}
+
 #[derive(Iden)]
 pub enum FragJobs {
     #[iden = "frag_jobs"]
@@ -214,18 +229,37 @@ pub trait JobDatabaseRequests {
         queues: Option<Vec<String>>,
         reservation: Option<JobReservation>,
         states: Option<Vec<JobState>>,
-    ) -> Result<IndexMap<i64, Job>, Error>;
+    ) -> Result<IndexMap<i64, Job>, DbError>;
+    fn get_jobs_paged(
+        session: &Session,
+        queues: Option<Vec<String>>,
+        reservation: Option<JobReservation>,
+        states: Option<Vec<JobState>>,
+        limit: u64,
+        offset: u64,
+    ) -> Result<IndexMap<i64, Job>, DbError>;
+    fn get_jobs_submitted_between(
+        session: &Session,
+        queues: Option<Vec<String>>,
+        reservation: Option<JobReservation>,
+        states: Option<Vec<JobState>>,
+        submitted_after: Option<i64>,
+        submitted_before: Option<i64>,
+    ) -> Result<IndexMap<i64, Job>, DbError>;
     fn get_gantt_jobs(
         session: &Session,
         queues: Option<Vec<String>>,
         reservation: Option<JobReservation>,
         states: Option<Vec<JobState>>,
         max_start_time: Option<i64>,
-    ) -> Result<Vec<Job>, Error>;
-    fn set_state(&self, session: &Session, new_state: JobState) -> Result<(), Error>;
-    fn set_message(&self, session: &Session, message: &str) -> Result<(), Error>;
-    fn set_resa_state(&self, session: &Session, new_resa_state: &str) -> Result<(), Error>;
-    fn assign_moldable_and_set_start_time(&self, session: &Session, moldable_id: i64, start_time: i64) -> Result<(), Error>;
+    ) -> Result<Vec<Job>, DbError>;
+    fn get_running_jobs_windows(session: &Session) -> Result<HashMap<i64, (i64, i64, ProcSet)>, DbError>;
+    fn set_state(&self, session: &Session, new_state: JobState) -> Result<(), DbError>;
+    fn get_state_logs(&self, session: &Session) -> Result<Vec<JobStateLog>, DbError>;
+    fn set_message(&self, session: &Session, message: &str) -> Result<(), DbError>;
+    fn set_resa_state(&self, session: &Session, new_resa_state: &str) -> Result<(), DbError>;
+    fn assign_moldable_and_set_start_time(&self, session: &Session, moldable_id: i64, start_time: i64) -> Result<(), DbError>;
+    fn frag(&self, session: &Session) -> Result<bool, DbError>;
 }
 
 impl JobDatabaseRequests for Job {
@@ -242,71 +276,38 @@ impl JobDatabaseRequests for Job {
         queues: Option<Vec<String>>,
         reservation: Option<JobReservation>,
         states: Option<Vec<JobState>>,
-    ) -> Result<IndexMap<i64, Job>, Error> {
-        let jobs = session.runtime.block_on(async {
-            let rows = Query::select()
-                .columns(vec![
-                    Jobs::Id,
-                    Jobs::Name,
-                    Jobs::User,
-                    Jobs::Project,
-                    Jobs::QueueName,
-                    Jobs::SubmissionTime,
-                    Jobs::StartTime,
-                    Jobs::StopTime,
-                    // Jobs::State,
-                    Jobs::Message,
-                    Jobs::Reservation,
-                    Jobs::AssignedMoldableId,
-                ])
-                .from(Jobs::Table)
-                .apply_if(queues, |req, queues| {
-                    req.and_where(Expr::col(Jobs::QueueName).is_in(queues));
-                })
-                .apply_if(reservation, |req, reservation| {
-                    req.and_where(Expr::col(Jobs::Reservation).eq(reservation.as_str()));
-                })
-                .apply_if(states, |req, states| {
-                    req.and_where(Expr::col(Jobs::State).is_in(states.iter().map(|s| s.as_str().as_enum("job_state"))));
-                })
-                .order_by(Jobs::StartTime, sea_query::Order::Asc)
-                .order_by(Jobs::Id, sea_query::Order::Asc)
-                .to_owned()
-                .fetch_all(session)
-                .await.unwrap();
-
-            let job_ids = rows.iter().map(|r| r.get::<i64, &str>(Jobs::Id.unquoted())).collect::<Vec<i64>>();
-
-            let jobs_types = AllJobTypes::load_type_for_jobs(session, job_ids.clone()).await.unwrap();
-            let jobs_dependencies = AllJobDependencies::load_dependencies_for_jobs(session, job_ids.clone()).await.unwrap();
-            let jobs_moldables = AllJobMoldables::load_moldables_for_jobs(session, job_ids).await.unwrap();
+    ) -> Result<IndexMap<i64, Job>, DbError> {
+        session.runtime.block_on(load_jobs(session, queues, reservation, states, (None, None), None))
+    }
 
-            let mut jobs = IndexMap::new();
-            for row in rows {
-                let id: i64 = row.get(Jobs::Id.unquoted());
-                let moldables = jobs_moldables.get_job_moldables(id);
+    /// Same as [`JobDatabaseRequests::get_jobs`], but only returns a page of at most `limit` jobs, skipping the first `offset` matching jobs.
+    /// Types, dependencies and moldables are only loaded for the jobs of the page.
+    /// The ordering by start time and then by job id is kept across pages, allowing to iterate over all jobs without loading them all at once.
+    fn get_jobs_paged(
+        session: &Session,
+        queues: Option<Vec<String>>,
+        reservation: Option<JobReservation>,
+        states: Option<Vec<JobState>>,
+        limit: u64,
+        offset: u64,
+    ) -> Result<IndexMap<i64, Job>, DbError> {
+        session.runtime.block_on(load_jobs(session, queues, reservation, states, (None, None), Some((limit, offset))))
+    }
 
-                let mut job_builder = JobBuilder::new(id)
-                    .types(jobs_types.get_job_types(id))
-                    .name_opt(row.try_get(Jobs::Name.unquoted()).map(|s: String| s.into_boxed_str()).ok())
-                    .user_opt(row.try_get(Jobs::User.unquoted()).map(|s: String| s.into_boxed_str()).ok())
-                    .project_opt(row.try_get(Jobs::Project.unquoted()).map(|s: String| s.into_boxed_str()).ok())
-                    .queue(row.get::<String, &str>(Jobs::QueueName.unquoted()).into_boxed_str())
-                    .dependencies(jobs_dependencies.get_job_dependencies(id))
-                    .submission_time(row.get::<i64, &str>(Jobs::SubmissionTime.unquoted()))
-                    .assign_opt(jobs_moldables.get_job_assignment(session, &row, false).await)
-                    .state(row.try_get(Jobs::State.unquoted()).unwrap_or("Waiting").into())
-                    .message(row.try_get(Jobs::Message.unquoted()).unwrap_or("".to_string()))
-                    .moldables(moldables);
-                // Reservation jobs
-                if JobReservation::ToSchedule.as_str() == row.get::<String, &str>(Jobs::Reservation.unquoted()) {
-                    job_builder = job_builder.set_advance_reservation_start_time(row.get::<i64, &str>(Jobs::StartTime.unquoted()));
-                };
-                jobs.insert(id, job_builder.build());
-            }
-            Ok::<IndexMap<i64, Job>, Error>(jobs)
-        })?;
-        Ok(jobs)
+    /// Same as [`JobDatabaseRequests::get_jobs`], but only returns the jobs submitted in the provided time window.
+    /// If `submitted_after` is `Some`, only jobs submitted at or after this time are returned.
+    /// If `submitted_before` is `Some`, only jobs submitted at or before this time are returned.
+    fn get_jobs_submitted_between(
+        session: &Session,
+        queues: Option<Vec<String>>,
+        reservation: Option<JobReservation>,
+        states: Option<Vec<JobState>>,
+        submitted_after: Option<i64>,
+        submitted_before: Option<i64>,
+    ) -> Result<IndexMap<i64, Job>, DbError> {
+        session
+            .runtime
+            .block_on(load_jobs(session, queues, reservation, states, (submitted_after, submitted_before), None))
     }
 
     /// Get jobs from the database, taking their assignments data from the gantt tables `gantt_jobs_resources` and `gantt_jobs_prediction`.
@@ -320,7 +321,7 @@ impl JobDatabaseRequests for Job {
         reservation: Option<JobReservation>,
         states: Option<Vec<JobState>>,
         max_start_time: Option<i64>,
-    ) -> Result<Vec<Job>, Error> {
+    ) -> Result<Vec<Job>, DbError> {
         session.runtime.block_on(async {
             let rows = Query::select()
                 .columns(vec![
@@ -333,13 +334,19 @@ impl JobDatabaseRequests for Job {
                     (Jobs::Table, Jobs::State),
                     (Jobs::Table, Jobs::Message),
                     (Jobs::Table, Jobs::Reservation),
-                    (Jobs::Table, Jobs::AssignedMoldableId),
                 ])
                 .columns(vec![(GanttJobsPredictions::Table, GanttJobsPredictions::StartTime)])
+                // The moldable chosen by the scheduler is the one with a gantt prediction, exposed as the assigned moldable of the job.
+                .expr_as(Expr::col((GanttJobsPredictions::Table, GanttJobsPredictions::MoldableId)), Jobs::AssignedMoldableId)
                 .from(Jobs::Table)
+                .inner_join(
+                    MoldableJobDescriptions::Table,
+                    Expr::col((MoldableJobDescriptions::Table, MoldableJobDescriptions::JobId)).equals((Jobs::Table, Jobs::Id)),
+                )
                 .inner_join(
                     GanttJobsPredictions::Table,
-                    Expr::col(Jobs::AssignedMoldableId).equals(GanttJobsPredictions::MoldableId),
+                    Expr::col((GanttJobsPredictions::Table, GanttJobsPredictions::MoldableId))
+                        .equals((MoldableJobDescriptions::Table, MoldableJobDescriptions::Id)),
                 )
                 .apply_if(reservation, |req, reservation| {
                     req.and_where(Expr::col(Jobs::Reservation).eq(reservation.as_str()));
@@ -364,7 +371,9 @@ impl JobDatabaseRequests for Job {
             let jobs_types = AllJobTypes::load_type_for_jobs(session, job_ids.clone()).await?;
             let jobs_dependencies = AllJobDependencies::load_dependencies_for_jobs(session, job_ids.clone()).await?;
             let jobs_moldables = AllJobMoldables::load_moldables_for_jobs(session, job_ids).await?;
+            let mut jobs_assignments = jobs_moldables.load_job_assignments(session, &rows, true).await?;
 
+            let walltime_estimator: Rc<dyn WalltimeEstimator> = Rc::new(session.walltime_estimator.clone());
             let mut jobs: Vec<Job> = Vec::with_capacity(rows.len());
             for row in rows {
                 let id: i64 = row.get(Jobs::Id.unquoted());
@@ -378,23 +387,87 @@ impl JobDatabaseRequests for Job {
                     .queue(row.get::<String, &str>(Jobs::QueueName.unquoted()).into_boxed_str())
                     .dependencies(jobs_dependencies.get_job_dependencies(id))
                     .submission_time(row.get::<i64, &str>(Jobs::SubmissionTime.unquoted()))
-                    .assign_opt(jobs_moldables.get_job_assignment(session, &row, true).await)
+                    .assign_opt(jobs_assignments.remove(&row.get::<i64, &str>(Jobs::AssignedMoldableId.unquoted())))
                     .state(row.try_get(Jobs::State.unquoted()).unwrap_or("Waiting").into())
                     .message(row.try_get(Jobs::Message.unquoted()).unwrap_or("".to_string()))
-                    .moldables(moldables);
+                    .moldables(moldables)
+                    .walltime_estimator(walltime_estimator.clone());
                 // Reservation jobs
                 if JobReservation::ToSchedule.as_str() == row.get::<String, &str>(Jobs::Reservation.unquoted()) {
                     job_builder = job_builder.set_advance_reservation_start_time(row.get::<i64, &str>(Jobs::StartTime.unquoted()));
                 };
-                jobs.push(job_builder.build());
+                let mut job = job_builder.build();
+                // The end is computed from the walltime of the assigned moldable, which might only be known once estimated by the builder
+                if let Some(assignment) = job.assignment.as_mut() {
+                    assignment.end = Job::compute_end(assignment.begin, job.moldables[assignment.moldable_index].walltime);
+                }
+                jobs.push(job);
             }
             Ok(jobs)
         })
     }
 
-    fn set_state(&self, session: &Session, new_state: JobState) -> Result<(), Error> {
+    /// Returns the begin, end and resources of the running jobs, i.e., in the `Running`, `Launching` or `Finishing` state, keyed by job id.
+    /// The resources are read from the `assigned_resources` table, joined with `moldable_job_descriptions` to only keep the assigned moldable,
+    /// and not from the gantt tables, which only hold the predictions.
+    /// The jobs run from their `start_time` for the walltime of their assigned moldable.
+    /// Resources not in the resource set loaded by [`Session::get_resource_set`] (e.g., dead resources) are ignored.
+    fn get_running_jobs_windows(session: &Session) -> Result<HashMap<i64, (i64, i64, ProcSet)>, DbError> {
         session.runtime.block_on(async {
-            let tx = session.begin().await;
+            let states = [JobState::Running, JobState::Launching, JobState::Finishing];
+            let rows = Query::select()
+                .columns(vec![(Jobs::Table, Jobs::Id), (Jobs::Table, Jobs::StartTime)])
+                .columns(vec![(MoldableJobDescriptions::Table, MoldableJobDescriptions::Walltime)])
+                .columns(vec![(AssignedResources::Table, AssignedResources::ResourceId)])
+                .from(AssignedResources::Table)
+                .inner_join(
+                    MoldableJobDescriptions::Table,
+                    Expr::col((AssignedResources::Table, AssignedResources::MoldableId))
+                        .equals((MoldableJobDescriptions::Table, MoldableJobDescriptions::Id)),
+                )
+                .inner_join(
+                    Jobs::Table,
+                    Expr::col((Jobs::Table, Jobs::Id))
+                        .equals((MoldableJobDescriptions::Table, MoldableJobDescriptions::JobId))
+                        .and(
+                            Expr::col((Jobs::Table, Jobs::AssignedMoldableId))
+                                .equals((MoldableJobDescriptions::Table, MoldableJobDescriptions::Id)),
+                        ),
+                )
+                .and_where(Expr::col((Jobs::Table, Jobs::State)).is_in(states.iter().map(|s| s.as_str().as_enum("job_state"))))
+                .and_where(Expr::col((AssignedResources::Table, AssignedResources::Index)).eq("CURRENT"))
+                .to_owned()
+                .fetch_all(session)
+                .await?;
+
+            let mut jobs_windows = HashMap::<i64, (i64, i64, ProcSet)>::new();
+            for row in rows {
+                let job_id: i64 = row.get(Jobs::Id.unquoted());
+                let resource_id: i32 = row.get(AssignedResources::ResourceId.unquoted());
+                match session.resource_id_to_resource_index(resource_id) {
+                    Some(resource_index) => {
+                        let start_time: i64 = row.get(Jobs::StartTime.unquoted());
+                        let walltime: i64 = row.get(MoldableJobDescriptions::Walltime.unquoted());
+                        let (_, _, resources) = jobs_windows
+                            .entry(job_id)
+                            .or_insert_with(|| (start_time, start_time + walltime - 1, ProcSet::new()));
+                        resources.insert(resource_index);
+                    }
+                    None => debug!("Ignoring the resource {} of the running job {}: not in the resource set", resource_id, job_id),
+                }
+            }
+            Ok(jobs_windows)
+        })
+    }
+
+    /// Sets the state of the job if it is not already terminated, in error, or in the wanted state.
+    /// The change is logged in the `job_state_logs` table: the current log entry of the job is closed (`date_stop` set to now),
+    /// and a new entry is opened with `date_start` set to now and `date_stop` set to 0, as done by OAR.
+    /// As jobs never leave the final states `Terminated` and `Error`, their entry is closed right away with `date_stop` set to now.
+    fn set_state(&self, session: &Session, new_state: JobState) -> Result<(), DbError> {
+        let now = session.get_now()?;
+        session.runtime.block_on(async {
+            let mut tx = session.begin().await?;
             let mut states = vec![
                 "toLaunch",
                 "toError",
@@ -407,17 +480,18 @@ impl JobDatabaseRequests for Job {
                 "Suspended",
                 "Resuming",
             ];
-            states.remove(states.iter().position(|s| *s == new_state.as_str()).expect("Invalid state"));
+            // Jobs can be set to any state from the non-final ones, but the wanted one
+            states.retain(|s| *s != new_state.as_str());
             let states = states.into_iter().map(|s| s.as_enum("job_state"));
             let res = Query::update()
                 .table(Jobs::Table)
                 .and_where(Expr::col(Jobs::Id).eq(self.id))
                 .and_where(Expr::col(Jobs::State).is_in(states))
                 .value(Jobs::State, new_state.as_str().as_enum("job_state"))
-                .execute(session)
+                .execute_in(session, &mut tx)
                 .await?;
-            tx.commit().await.unwrap();
             if res == 0 {
+                tx.commit().await?;
                 warn!(
                     "Job is already terminated or in error or wanted state, job_id: {}, wanted state: {}",
                     self.id, new_state.as_str()
@@ -425,16 +499,56 @@ impl JobDatabaseRequests for Job {
                 return Ok(());
             }
 
+            // Close the current state log entry and open a new one
+            let date_stop = if matches!(new_state, JobState::Terminated | JobState::Error) { now } else { 0 };
+            Query::update()
+                .table(JobStateLogs::Table)
+                .and_where(Expr::col(JobStateLogs::JobId).eq(self.id))
+                .and_where(Expr::col(JobStateLogs::DateStop).eq(0))
+                .value(JobStateLogs::DateStop, now)
+                .execute_in(session, &mut tx)
+                .await?;
+            Query::insert()
+                .into_table(JobStateLogs::Table)
+                .columns(vec![JobStateLogs::JobId, JobStateLogs::JobState, JobStateLogs::DateStart, JobStateLogs::DateStop])
+                .values_panic(vec![Expr::val(self.id), Expr::val(new_state.as_str()), Expr::val(now), Expr::val(date_stop)])
+                .execute_in(session, &mut tx)
+                .await?;
+            tx.commit().await?;
+
             debug!("Job {} state changed to {}", self.id, new_state.as_str());
 
-            // TODO: update the JobStateLog table and notify user as done here:
+            // TODO: notify user as done here:
             //   https://github.com/oar-team/oar3/blob/e6b6e7e59eb751cc2e7388d6c2fb7f94a3ac8c6e/oar/lib/job_handling.py#L1714-L1800
 
             Ok(())
         })
     }
 
-    fn set_message(&self, session: &Session, message: &str) -> Result<(), Error> {
+    /// Returns the `job_state_logs` entries of the job, ordered by start date.
+    fn get_state_logs(&self, session: &Session) -> Result<Vec<JobStateLog>, DbError> {
+        session.runtime.block_on(async {
+            let rows = Query::select()
+                .columns(vec![JobStateLogs::JobState, JobStateLogs::DateStart, JobStateLogs::DateStop])
+                .from(JobStateLogs::Table)
+                .and_where(Expr::col(JobStateLogs::JobId).eq(self.id))
+                .order_by(JobStateLogs::DateStart, sea_query::Order::Asc)
+                .order_by(JobStateLogs::Id, sea_query::Order::Asc)
+                .to_owned()
+                .fetch_all(session)
+                .await?;
+            Ok(rows
+                .iter()
+                .map(|row| JobStateLog {
+                    job_state: row.get(JobStateLogs::JobState.unquoted()),
+                    date_start: row.get(JobStateLogs::DateStart.unquoted()),
+                    date_stop: row.get(JobStateLogs::DateStop.unquoted()),
+                })
+                .collect())
+        })
+    }
+
+    fn set_message(&self, session: &Session, message: &str) -> Result<(), DbError> {
         session.runtime.block_on(async {
             let res = Query::update()
                 .table(Jobs::Table)
@@ -449,7 +563,7 @@ impl JobDatabaseRequests for Job {
         })
     }
 
-    fn set_resa_state(&self, session: &Session, new_resa_state: &str) -> Result<(), Error> {
+    fn set_resa_state(&self, session: &Session, new_resa_state: &str) -> Result<(), DbError> {
         session.runtime.block_on(async {
             let res = Query::update()
                 .table(Jobs::Table)
@@ -467,7 +581,7 @@ impl JobDatabaseRequests for Job {
         })
     }
 
-    fn assign_moldable_and_set_start_time(&self, session: &Session, moldable_id: i64, start_time: i64) -> Result<(), Error> {
+    fn assign_moldable_and_set_start_time(&self, session: &Session, moldable_id: i64, start_time: i64) -> Result<(), DbError> {
         session.runtime.block_on(async {
             let res = Query::update()
                 .table(Jobs::Table)
@@ -487,6 +601,119 @@ impl JobDatabaseRequests for Job {
             Ok(())
         })
     }
+
+    /// Marks the job to be killed by inserting it in the `frag_jobs` table, as `frag_job` in the Python scheduler.
+    /// Returns false if the job was already marked.
+    fn frag(&self, session: &Session) -> Result<bool, DbError> {
+        let now = session.get_now()?;
+        session.runtime.block_on(async {
+            let already_fragged = !Query::select()
+                .column(FragJobs::FragIdJob)
+                .from(FragJobs::Table)
+                .and_where(Expr::col(FragJobs::FragIdJob).eq(self.id))
+                .fetch_all(session)
+                .await?
+                .is_empty();
+            if already_fragged {
+                return Ok(false);
+            }
+            Query::insert()
+                .into_table(FragJobs::Table)
+                .columns(vec![FragJobs::FragIdJob, FragJobs::FragDate])
+                .values_panic(vec![self.id.into(), now.into()])
+                .execute(session)
+                .await?;
+            Ok(true)
+        })
+    }
+}
+
+/// Loads the jobs matching the filters of [`JobDatabaseRequests::get_jobs`], with their types, dependencies and moldables.
+/// `submission_window` bounds the submission time of the jobs (see [`JobDatabaseRequests::get_jobs_submitted_between`]).
+/// If `page` is `Some((limit, offset))`, only `limit` jobs are loaded, skipping the first `offset` jobs.
+async fn load_jobs(
+    session: &Session,
+    queues: Option<Vec<String>>,
+    reservation: Option<JobReservation>,
+    states: Option<Vec<JobState>>,
+    submission_window: (Option<i64>, Option<i64>),
+    page: Option<(u64, u64)>,
+) -> Result<IndexMap<i64, Job>, DbError> {
+    let rows = Query::select()
+        .columns(vec![
+            Jobs::Id,
+            Jobs::Name,
+            Jobs::User,
+            Jobs::Project,
+            Jobs::QueueName,
+            Jobs::SubmissionTime,
+            Jobs::StartTime,
+            Jobs::StopTime,
+            Jobs::ArrayId,
+            // Jobs::State,
+            Jobs::Message,
+            Jobs::Reservation,
+            Jobs::AssignedMoldableId,
+        ])
+        .from(Jobs::Table)
+        .apply_if(queues, |req, queues| {
+            req.and_where(Expr::col(Jobs::QueueName).is_in(queues));
+        })
+        .apply_if(reservation, |req, reservation| {
+            req.and_where(Expr::col(Jobs::Reservation).eq(reservation.as_str()));
+        })
+        .apply_if(states, |req, states| {
+            req.and_where(Expr::col(Jobs::State).is_in(states.iter().map(|s| s.as_str().as_enum("job_state"))));
+        })
+        .apply_if(submission_window.0, |req, submitted_after| {
+            req.and_where(Expr::col(Jobs::SubmissionTime).gte(submitted_after));
+        })
+        .apply_if(submission_window.1, |req, submitted_before| {
+            req.and_where(Expr::col(Jobs::SubmissionTime).lte(submitted_before));
+        })
+        .order_by(Jobs::StartTime, sea_query::Order::Asc)
+        .order_by(Jobs::Id, sea_query::Order::Asc)
+        .apply_if(page, |req, (limit, offset)| {
+            req.limit(limit).offset(offset);
+        })
+        .to_owned()
+        .fetch_all(session)
+        .await?;
+
+    let job_ids = rows.iter().map(|r| r.get::<i64, &str>(Jobs::Id.unquoted())).collect::<Vec<i64>>();
+
+    let jobs_types = AllJobTypes::load_type_for_jobs(session, job_ids.clone()).await?;
+    let jobs_dependencies = AllJobDependencies::load_dependencies_for_jobs(session, job_ids.clone()).await?;
+    let jobs_moldables = AllJobMoldables::load_moldables_for_jobs(session, job_ids).await?;
+    let mut jobs_assignments = jobs_moldables.load_job_assignments(session, &rows, false).await?;
+
+    let walltime_estimator: Rc<dyn WalltimeEstimator> = Rc::new(session.walltime_estimator.clone());
+    let mut jobs = IndexMap::new();
+    for row in rows {
+        let id: i64 = row.get(Jobs::Id.unquoted());
+        let moldables = jobs_moldables.get_job_moldables(id);
+
+        let mut job_builder = JobBuilder::new(id)
+            .types(jobs_types.get_job_types(id))
+            .name_opt(row.try_get(Jobs::Name.unquoted()).map(|s: String| s.into_boxed_str()).ok())
+            .user_opt(row.try_get(Jobs::User.unquoted()).map(|s: String| s.into_boxed_str()).ok())
+            .project_opt(row.try_get(Jobs::Project.unquoted()).map(|s: String| s.into_boxed_str()).ok())
+            .queue(row.get::<String, &str>(Jobs::QueueName.unquoted()).into_boxed_str())
+            .dependencies(jobs_dependencies.get_job_dependencies(id))
+            .submission_time(row.get::<i64, &str>(Jobs::SubmissionTime.unquoted()))
+            .array_id_opt(row.try_get(Jobs::ArrayId.unquoted()).ok())
+            .assign_opt(jobs_assignments.remove(&row.get::<i64, &str>(Jobs::AssignedMoldableId.unquoted())))
+            .state(row.try_get(Jobs::State.unquoted()).unwrap_or("Waiting").into())
+            .message(row.try_get(Jobs::Message.unquoted()).unwrap_or("".to_string()))
+            .moldables(moldables)
+            .walltime_estimator(walltime_estimator.clone());
+        // Reservation jobs
+        if JobReservation::ToSchedule.as_str() == row.get::<String, &str>(Jobs::Reservation.unquoted()) {
+            job_builder = job_builder.set_advance_reservation_start_time(row.get::<i64, &str>(Jobs::StartTime.unquoted()));
+        };
+        jobs.insert(id, job_builder.build());
+    }
+    Ok(jobs)
 }
 
 pub struct NewJob {
@@ -498,14 +725,22 @@ pub struct NewJob {
 }
 
 impl NewJob {
-    pub fn insert(&self, session: &Session) -> Result<i64, Error> {
-        session.runtime.block_on(async { self.insert_async(session).await })
+    pub fn insert(&self, session: &Session) -> Result<i64, DbError> {
+        session.runtime.block_on(async { self.insert_async(session, 0, "").await })
+    }
+    /// Same as [`NewJob::insert`], but with the provided submission time instead of `0`.
+    pub fn insert_submitted_at(&self, session: &Session, submission_time: i64) -> Result<i64, DbError> {
+        session.runtime.block_on(async { self.insert_async(session, submission_time, "").await })
+    }
+    /// Same as [`NewJob::insert`], but with the provided job properties (`jobs.properties`) instead of an empty string.
+    pub fn insert_with_properties(&self, session: &Session, properties: &str) -> Result<i64, DbError> {
+        session.runtime.block_on(async { self.insert_async(session, 0, properties).await })
     }
     /// Big unstructured piece of code since it should only be used by tests.
-    async fn insert_async(&self, session: &Session) -> Result<i64, Error> {
+    async fn insert_async(&self, session: &Session, submission_time: i64, properties: &str) -> Result<i64, DbError> {
         let launching_directory = "".to_string();
         let checkpoint_signal: i64 = 0;
-        let properties = "".to_string();
+        let properties = properties.to_string();
         let queue_name = self.queue_name.clone();
         let job_user = self.user.clone().unwrap_or_else(|| "".to_string());
 
@@ -520,6 +755,7 @@ impl NewJob {
                 Alias::new(Jobs::Properties.to_string()),
                 Alias::new(Jobs::QueueName.to_string()),
                 Alias::new(Jobs::User.to_string()),
+                Alias::new(Jobs::SubmissionTime.to_string()),
             ])
             .values_panic(vec![
                 Expr::val(&launching_directory),
@@ -527,6 +763,7 @@ impl NewJob {
                 Expr::val(&properties),
                 Expr::val(&queue_name),
                 Expr::val(&job_user),
+                Expr::val(submission_time),
             ])
             .returning_col(Jobs::Id)
             .fetch_one(session)
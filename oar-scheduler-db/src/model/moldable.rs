@@ -1,12 +1,15 @@
 use crate::model::gantt::{GanttJobsPredictions, GanttJobsResources};
 use crate::model::jobs::Jobs;
+use crate::properties::{self, PropertiesExpr};
 use crate::{Session, SessionInsertStatement, SessionSelectStatement, SessionUpdateStatement};
+use log::{debug, warn};
 use oar_scheduler_core::model::job::ProcSet;
-use oar_scheduler_core::model::job::{JobAssignment, Moldable};
+use oar_scheduler_core::model::job::{Job, JobAssignment, Moldable};
 use oar_scheduler_core::scheduler::hierarchy::{HierarchyRequest, HierarchyRequests};
 use sea_query::{Expr, ExprTrait, Iden, Query};
 use sqlx::any::AnyRow;
-use sqlx::{Error, Row};
+use crate::DbError;
+use sqlx::Row;
 use std::collections::HashMap;
 
 #[derive(Iden)]
@@ -64,13 +67,13 @@ pub enum AssignedResources {
 
 pub trait MoldableDatabaseRequests {
     /// Saves the moldables assigned resources in the table `assigned_resources`.
-    fn save_resources_as_assigned_resources(&self, session: &Session, resources: &ProcSet) -> Result<(), Error>;
-    fn set_gantt_job_start_time(&self, session: &Session, start_time: i64) -> Result<(), Error>;
-    fn set_walltime(&self, session: &Session, walltime: i64) -> Result<(), Error>;
+    fn save_resources_as_assigned_resources(&self, session: &Session, resources: &ProcSet) -> Result<(), DbError>;
+    fn set_gantt_job_start_time(&self, session: &Session, start_time: i64) -> Result<(), DbError>;
+    fn set_walltime(&self, session: &Session, walltime: i64) -> Result<(), DbError>;
 }
 
 impl MoldableDatabaseRequests for Moldable {
-    fn save_resources_as_assigned_resources(&self, session: &Session, resources: &ProcSet) -> Result<(), Error> {
+    fn save_resources_as_assigned_resources(&self, session: &Session, resources: &ProcSet) -> Result<(), DbError> {
         if resources.is_empty() {
             return Ok(());
         }
@@ -78,8 +81,8 @@ impl MoldableDatabaseRequests for Moldable {
         query
             .into_table(AssignedResources::Table)
             .columns(vec![AssignedResources::MoldableId, AssignedResources::ResourceId]);
-        for res in resources.iter() {
-            query.values_panic(vec![Expr::val(self.id), Expr::val(session.resource_index_to_resource_id(res).unwrap())]);
+        for resource_id in session.proc_set_to_resource_ids(resources)? {
+            query.values_panic(vec![Expr::val(self.id), Expr::val(resource_id)]);
         }
         session.runtime.block_on(async {
             query.execute(session).await?;
@@ -87,7 +90,7 @@ impl MoldableDatabaseRequests for Moldable {
         })
     }
 
-    fn set_gantt_job_start_time(&self, session: &Session, start_time: i64) -> Result<(), Error> {
+    fn set_gantt_job_start_time(&self, session: &Session, start_time: i64) -> Result<(), DbError> {
         session.runtime.block_on(async {
             Query::update()
                 .table(GanttJobsPredictions::Table)
@@ -99,7 +102,7 @@ impl MoldableDatabaseRequests for Moldable {
         })
     }
 
-    fn set_walltime(&self, session: &Session, walltime: i64) -> Result<(), Error> {
+    fn set_walltime(&self, session: &Session, walltime: i64) -> Result<(), DbError> {
         session.runtime.block_on(async {
             Query::update()
                 .table(MoldableJobDescriptions::Table)
@@ -116,12 +119,13 @@ pub struct AllJobMoldables {
     moldables: HashMap<i64, Vec<Moldable>>,
 }
 impl AllJobMoldables {
-    pub(crate) async fn load_moldables_for_jobs(session: &Session, jobs: Vec<i64>) -> Result<Self, Error> {
+    pub(crate) async fn load_moldables_for_jobs(session: &Session, jobs: Vec<i64>) -> Result<Self, DbError> {
         if jobs.is_empty() {
             return Ok(Self { moldables: HashMap::new() });
         }
-        let moldables = Query::select()
+        let rows = Query::select()
             .columns(vec![
+                Jobs::Properties.to_string(),
                 MoldableJobDescriptions::Id.to_string(),
                 MoldableJobDescriptions::JobId.to_string(),
                 MoldableJobDescriptions::Walltime.to_string(),
@@ -133,6 +137,10 @@ impl AllJobMoldables {
                 JobResourceDescriptions::Value.to_string(),
             ])
             .from(MoldableJobDescriptions::Table)
+            .inner_join(
+                Jobs::Table,
+                Expr::col((Jobs::Table, Jobs::Id)).equals((MoldableJobDescriptions::Table, MoldableJobDescriptions::JobId)),
+            )
             .left_join(
                 JobResourceGroups::Table,
                 Expr::col(MoldableJobDescriptions::Id).equals(JobResourceGroups::MoldableId),
@@ -149,32 +157,70 @@ impl AllJobMoldables {
             .order_by(JobResourceGroups::Id, sea_query::Order::Asc)
             .order_by(JobResourceDescriptions::Order, sea_query::Order::Asc)
             .fetch_all(session)
-            .await?
+            .await?;
+
+        // The job properties (`jobs.properties`) apply to all the moldables of the job
+        let job_properties = rows
+            .iter()
+            .map(|row| {
+                let job_id: i64 = row.get(MoldableJobDescriptions::JobId.unquoted());
+                let properties: Option<String> = row.get(Jobs::Properties.unquoted());
+                (job_id, properties.unwrap_or_default())
+            })
+            .collect::<HashMap<i64, String>>();
+        let moldables = rows
             .iter()
             .fold(
-                // job_id -> moldable_id -> (walltime, group_id -> level_nbs)
-                HashMap::<i64, HashMap<i64, (i64, HashMap<i64, Vec<(Box<str>, u32)>>)>>::new(),
+                // job_id -> moldable_id -> (walltime, group_id -> (property, level_nbs))
+                HashMap::<i64, HashMap<i64, (i64, HashMap<i64, (String, Vec<(Box<str>, u32)>)>)>>::new(),
                 |mut acc, row| {
                     let job_id: i64 = row.get(MoldableJobDescriptions::JobId.unquoted());
                     let mld_id: i64 = row.get(MoldableJobDescriptions::Id.unquoted());
                     let walltime: i64 = row.get(MoldableJobDescriptions::Walltime.unquoted());
                     let group_id: i64 = row.get(JobResourceGroups::Id.unquoted());
+                    let property: Option<String> = row.get(JobResourceGroups::Property.unquoted());
                     let rtype: String = row.get(JobResourceDescriptions::ResourceType.unquoted());
                     let rvalue: i64 = row.get(JobResourceDescriptions::Value.unquoted());
 
                     acc.entry(job_id)
                         .or_insert_with(HashMap::new)
                         .entry(mld_id)
-                        .or_insert_with(|| (walltime, HashMap::<i64, Vec<(Box<str>, u32)>>::new()))
+                        .or_insert_with(|| (walltime, HashMap::<i64, (String, Vec<(Box<str>, u32)>)>::new()))
                         .1
                         .entry(group_id)
-                        .or_insert_with(Vec::new)
+                        .or_insert_with(|| (property.unwrap_or_default(), Vec::new()))
+                        .1
                         .push((rtype.into_boxed_str(), rvalue as u32));
                     acc
                 },
-            )
+            );
+
+        // Resources allowed by each distinct resource group or job property
+        let mut filters = HashMap::<String, ProcSet>::new();
+        for property in job_properties.values() {
+            if !filters.contains_key(property) {
+                filters.insert(property.clone(), Self::property_filter(session, property).await);
+            }
+        }
+        for mlds in moldables.values() {
+            for (_walltime, groups_map) in mlds.values() {
+                for (property, _levels) in groups_map.values() {
+                    if !filters.contains_key(property) {
+                        filters.insert(property.clone(), Self::property_filter(session, property).await);
+                    }
+                }
+            }
+        }
+
+        let resource_indexes = session.resource_indexes();
+        let moldables = moldables
             .into_iter()
             .map(|(job_id, mlds)| {
+                // The resources not satisfying the job properties are excluded from all its moldables
+                let excluded_resources = &resource_indexes - &filters[&job_properties[&job_id]];
+                // Moldables are ordered by id, i.e., in submission order, for their index to be stable
+                let mut mlds = mlds.into_iter().collect::<Vec<_>>();
+                mlds.sort_unstable_by_key(|(mld_id, _)| *mld_id);
                 let molds = mlds
                     .into_iter()
                     .map(|(mld_id, (walltime, groups_map))| {
@@ -184,9 +230,9 @@ impl AllJobMoldables {
                         let reqs: Vec<HierarchyRequest> = group_ids
                             .into_iter()
                             .filter_map(|gid| groups_map.get(&gid).cloned())
-                            .map(|levels| HierarchyRequest::new(!ProcSet::new(), levels))
+                            .map(|(property, levels)| HierarchyRequest::new(filters[&property].clone(), levels))
                             .collect();
-                        Moldable::new(mld_id, walltime, HierarchyRequests::from_requests(reqs))
+                        Moldable::new(mld_id, walltime, HierarchyRequests::from_requests(reqs)).with_excluded_resources(excluded_resources.clone())
                     })
                     .collect::<Vec<Moldable>>();
                 (job_id, molds)
@@ -196,69 +242,120 @@ impl AllJobMoldables {
         Ok(Self { moldables })
     }
 
+    /// Returns the resources satisfying the resource group `property` (see [`PropertiesExpr`]), all the resources if it is empty.
+    /// Resources are filtered only once the resource set is loaded.
+    /// If `property` uses operators [`PropertiesExpr`] does not support (e.g., `IS NULL`), it is evaluated by the database instead.
+    /// If the filtering fails, e.g., because `property` is invalid SQL, no resource is allowed, with a warning:
+    /// the constraint of the job is never widened to all the resources.
+    async fn property_filter(session: &Session, property: &str) -> ProcSet {
+        let properties = match PropertiesExpr::parse(property) {
+            Ok(PropertiesExpr::True) => return !ProcSet::new(),
+            Ok(properties) => Some(properties),
+            Err(e) => {
+                debug!("Resource group properties not supported, evaluating them in SQL: {}", e);
+                None
+            }
+        };
+        if !session.has_resource_set() {
+            debug!("Resource set not loaded, resources are not filtered by the properties '{}'", property);
+            return !ProcSet::new();
+        }
+        let filtered = match properties {
+            Some(properties) => properties::filter_resources(session, &properties).await,
+            None => properties::filter_resources_sql(session, property).await,
+        };
+        filtered.unwrap_or_else(|e| {
+            warn!("Failed to filter the resources by the properties '{}': {}", property, e);
+            ProcSet::new()
+        })
+    }
+
     pub fn get_job_moldables(&self, job_id: i64) -> Vec<Moldable> {
         self.moldables.get(&job_id).unwrap_or(&Vec::new()).clone()
     }
 
-    /// Get the moldable assignment for a job.
+    /// Loads the assignments of the jobs of `job_rows`, keyed by assigned moldable id, with a single query for all the jobs.
     /// If `properties_from_gantt` is true, the resources are fetched from the gantt table `gantt_jobs_resources`,
     /// and the start time from the `gantt_jobs_prediction` table.
     /// Otherwise, they are fetched from the table `assigned_resources` and the job `start_time` column.
-    /// The `job_row` parameter is the row of the job in the jobs table. It should contain at least the columns `Jobs::Id`, `Jobs::AssignedMoldableJob`, and:
+    /// The `job_rows` are rows of the jobs table. They should contain at least the columns `Jobs::Id`, `Jobs::AssignedMoldableJob`, and:
     /// - if `properties_from_gantt` is false, `Jobs::StartTime` and `Jobs::StopTime`.
     /// - if `properties_from_gantt` is true, `GanttJobsPredictions::StartTime` (in this case the end time is computed from the start time and the moldable walltime).
-    pub(crate) async fn get_job_assignment(&self, session: &Session, job_row: &AnyRow, properties_from_gantt: bool) -> Option<JobAssignment> {
-        let job_id: i64 = job_row.get(Jobs::Id.unquoted());
-        let assigned_moldable_id: i64 = job_row.get(Jobs::AssignedMoldableId.unquoted());
-        if assigned_moldable_id == 0 {
-            return None;
+    pub(crate) async fn load_job_assignments(
+        &self,
+        session: &Session,
+        job_rows: &[AnyRow],
+        properties_from_gantt: bool,
+    ) -> Result<HashMap<i64, JobAssignment>, DbError> {
+        let moldable_ids = job_rows
+            .iter()
+            .map(|row| row.get::<i64, &str>(Jobs::AssignedMoldableId.unquoted()))
+            .filter(|moldable_id| *moldable_id != 0)
+            .collect::<Vec<i64>>();
+        if moldable_ids.is_empty() {
+            return Ok(HashMap::new());
         }
-        let job_moldables = self.get_job_moldables(job_id);
-        let moldable_index = job_moldables.iter().position(|m| m.id == assigned_moldable_id)?;
-        let moldable = &job_moldables[moldable_index];
 
         // Get assigned resources
-        let resources = if properties_from_gantt {
+        let rows = if properties_from_gantt {
             Query::select()
-                .columns(vec![GanttJobsResources::ResourceId])
+                .columns(vec![GanttJobsResources::MoldableId, GanttJobsResources::ResourceId])
                 .from(GanttJobsResources::Table)
-                .and_where(Expr::col(GanttJobsResources::MoldableId).eq(moldable.id))
+                .and_where(Expr::col(GanttJobsResources::MoldableId).is_in(moldable_ids))
                 .fetch_all(session)
-                .await
-                .unwrap()
+                .await?
         } else {
             Query::select()
-                .columns(vec![AssignedResources::ResourceId])
+                .columns(vec![AssignedResources::MoldableId, AssignedResources::ResourceId])
                 .from(AssignedResources::Table)
-                .and_where(Expr::col(AssignedResources::MoldableId).eq(moldable.id))
+                .and_where(Expr::col(AssignedResources::MoldableId).is_in(moldable_ids))
                 .and_where(Expr::col(AssignedResources::Index).eq("CURRENT"))
                 .fetch_all(session)
-                .await
-                .unwrap()
+                .await?
         };
-        let resources: ProcSet = ProcSet::from_iter(resources.iter().map(|row| {
+        let mut moldables_resources = HashMap::<i64, ProcSet>::new();
+        for row in rows {
+            let moldable_id: i64 = row.get(AssignedResources::MoldableId.unquoted());
             let res_id: i32 = row.get(AssignedResources::ResourceId.unquoted());
-            session
-                .resource_id_to_resource_index(res_id)
-                .expect("Resource not found. There might be a database concurrency issue.")
-        }));
+            let resource_index = session.resource_id_to_resource_index(res_id).ok_or(DbError::UnknownResourceId(res_id))?;
+            moldables_resources.entry(moldable_id).or_default().insert(resource_index);
+        }
 
-        // Get assigned start time
-        let (begin, end) = if properties_from_gantt {
-            let start_time: i64 = job_row.get(GanttJobsPredictions::StartTime.unquoted());
-            let stop_time = start_time + moldable.walltime - 1;
-            (start_time, stop_time)
-        } else {
-            let start_time: i64 = job_row.get(Jobs::StartTime.unquoted());
-            let stop_time: i64 = job_row.get(Jobs::StopTime.unquoted());
-            (start_time, stop_time)
-        };
+        let mut assignments = HashMap::new();
+        for job_row in job_rows {
+            let job_id: i64 = job_row.get(Jobs::Id.unquoted());
+            let assigned_moldable_id: i64 = job_row.get(Jobs::AssignedMoldableId.unquoted());
+            if assigned_moldable_id == 0 {
+                continue;
+            }
+            let job_moldables = self.moldables.get(&job_id).map(Vec::as_slice).unwrap_or_default();
+            let Some(moldable_index) = job_moldables.iter().position(|m| m.id == assigned_moldable_id) else {
+                continue;
+            };
+            let moldable = &job_moldables[moldable_index];
+            let resources = moldables_resources.remove(&assigned_moldable_id).unwrap_or_default();
 
-        Some(JobAssignment {
-            begin,
-            end,
-            resources,
-            moldable_index,
-        })
+            // Get assigned start time
+            let (begin, end) = if properties_from_gantt {
+                let start_time: i64 = job_row.get(GanttJobsPredictions::StartTime.unquoted());
+                let stop_time = Job::compute_end(start_time, moldable.walltime);
+                (start_time, stop_time)
+            } else {
+                let start_time: i64 = job_row.get(Jobs::StartTime.unquoted());
+                let stop_time: i64 = job_row.get(Jobs::StopTime.unquoted());
+                (start_time, stop_time)
+            };
+
+            assignments.insert(
+                assigned_moldable_id,
+                JobAssignment {
+                    begin,
+                    end,
+                    resources,
+                    moldable_index,
+                },
+            );
+        }
+        Ok(assignments)
     }
 }
@@ -1,6 +1,7 @@
 use crate::{Session, SessionInsertStatement, SessionSelectStatement};
 use sea_query::{Expr, Iden, Query};
-use sqlx::{Error, Row};
+use crate::DbError;
+use sqlx::Row;
 use std::collections::BTreeMap;
 
 #[derive(Iden)]
@@ -25,7 +26,7 @@ pub struct Queue {
     pub state: String,
 }
 impl Queue {
-    pub fn insert(&self, session: &Session) -> Result<(), Error> {
+    pub fn insert(&self, session: &Session) -> Result<(), DbError> {
         session.runtime.block_on(async {
             Query::insert()
                 .into_table(Queues::Table)
@@ -43,7 +44,7 @@ impl Queue {
     }
 
     /// Gets all queues ordered by priority (highest priority first).
-    pub fn get_all_ordered_by_priority(session: &Session) -> Result<Vec<Queue>, Error> {
+    pub fn get_all_ordered_by_priority(session: &Session) -> Result<Vec<Queue>, DbError> {
         let rows = session.runtime.block_on(async {
             Query::select()
                 .columns(vec![Queues::QueueName, Queues::Priority, Queues::SchedulerPolicy, Queues::State])
@@ -67,7 +68,7 @@ impl Queue {
     }
 
     /// Gets all queues grouped by priority (highest priority first).
-    pub fn get_all_grouped_by_priority(session: &Session) -> Result<Vec<Vec<Queue>>, Error> {
+    pub fn get_all_grouped_by_priority(session: &Session) -> Result<Vec<Vec<Queue>>, DbError> {
         let rows = session.runtime.block_on(async {
             Query::select()
                 .columns(vec![Queues::QueueName, Queues::Priority, Queues::SchedulerPolicy, Queues::State])
@@ -1,12 +1,14 @@
-use crate::{Session, SessionInsertStatement, SessionSelectStatement};
+use crate::{Session, SessionInsertStatement, SessionSelectStatement, SessionUpdateStatement};
 use indexmap::IndexMap;
 use log::debug;
-use sea_query::{Alias, Expr, Iden, Query};
-use sqlx::{Error, Row};
+use sea_query::{Alias, Expr, ExprTrait, Func, Iden, Query};
+use crate::DbError;
+use sqlx::Row;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 
 #[derive(Iden)]
-enum Resources {
+pub(crate) enum Resources {
     #[iden = "resources"]
     Table,
     #[iden = "resource_id"]
@@ -99,6 +101,20 @@ enum Accounting {
     Consumption,
 }
 
+/// Cheap summary of the resources table, computed by a single aggregate query (see [`Resource::get_signature`]).
+/// Detects the added and removed resources, the resources becoming Alive or leaving the Alive state,
+/// the `available_upto` changes, and any change logged by OAR in the `resource_logs` table, e.g., a property change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourcesSignature {
+    pub count: i64,
+    pub max_id: i64,
+    /// Sum of the ids of the Alive resources.
+    pub alive_ids_sum: i64,
+    pub available_upto_sum: i64,
+    /// Maximum id of the `resource_logs` table.
+    pub max_log_id: i64,
+}
+
 pub struct NewResource {
     pub network_address: String,
     pub r#type: String,
@@ -106,7 +122,7 @@ pub struct NewResource {
     pub labels: IndexMap<String, ResourceLabelValue>,
 }
 impl NewResource {
-    pub fn insert(&self, session: &Session) -> Result<i64, Error> {
+    pub fn insert(&self, session: &Session) -> Result<i64, DbError> {
         let columns = vec![
             Alias::new(Resources::NetworkAddress.to_string()),
             Alias::new(Resources::Type.to_string()),
@@ -141,18 +157,18 @@ pub struct NewResourceColumn {
     pub r#type: String,
 }
 impl NewResourceColumn {
-    pub fn insert(&self, session: &Session) -> Result<(), Error> {
+    pub fn insert(&self, session: &Session) -> Result<(), DbError> {
         session.runtime.block_on(async {
             match session.backend {
                 crate::Backend::Postgres => {
                     let sql = format!("ALTER TABLE resources ADD COLUMN {} {};", self.name, self.r#type);
                     debug!("New Resource Column SQL: {}", sql);
-                    sqlx::query(&sql).execute(&session.pool).await?;
+                    sqlx::query(&sql).execute(&session.pool()).await?;
                 }
                 crate::Backend::Sqlite => {
                     let sql = format!("ALTER TABLE resources ADD COLUMN {} {};", self.name, self.r#type);
                     debug!("New Resource Column SQL: {}", sql);
-                    sqlx::query(&sql).execute(&session.pool).await?;
+                    sqlx::query(&sql).execute(&session.pool()).await?;
                 }
             }
             Ok(())
@@ -166,6 +182,62 @@ pub enum ResourceLabelValue {
     Varchar(String),
 }
 
+impl Ord for ResourceLabelValue {
+    /// Integers are compared numerically, and text values with a natural ordering where digit runs are compared as numbers,
+    /// e.g., `node2 < node10`. An integer and a text value are compared as text, the integer first if both are equivalent.
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (ResourceLabelValue::Integer(a), ResourceLabelValue::Integer(b)) => a.cmp(b),
+            (ResourceLabelValue::Varchar(a), ResourceLabelValue::Varchar(b)) => natural_cmp(a, b),
+            (ResourceLabelValue::Integer(a), ResourceLabelValue::Varchar(b)) => natural_cmp(&a.to_string(), b).then(Ordering::Less),
+            (ResourceLabelValue::Varchar(a), ResourceLabelValue::Integer(b)) => natural_cmp(a, &b.to_string()).then(Ordering::Greater),
+        }
+    }
+}
+impl PartialOrd for ResourceLabelValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Compares two strings chunk by chunk, digit runs being compared by their numeric value and other runs lexically.
+/// Strings with the same natural value (e.g., `node02` and `node2`) are then compared lexically to keep the ordering total.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a_chunks, mut b_chunks) = (natural_chunks(a), natural_chunks(b));
+    loop {
+        let ordering = match (a_chunks.next(), b_chunks.next()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (Some(a_chunk), Some(b_chunk)) => {
+                let a_digits = a_chunk.starts_with(|c: char| c.is_ascii_digit());
+                let b_digits = b_chunk.starts_with(|c: char| c.is_ascii_digit());
+                if a_digits && b_digits {
+                    // Without leading zeros, a longer digit run is a larger number
+                    let (a_number, b_number) = (a_chunk.trim_start_matches('0'), b_chunk.trim_start_matches('0'));
+                    a_number.len().cmp(&b_number.len()).then_with(|| a_number.cmp(b_number))
+                } else {
+                    a_chunk.cmp(b_chunk)
+                }
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+/// Splits a string into its maximal runs of ASCII digits and of other characters.
+fn natural_chunks(s: &str) -> impl Iterator<Item = &str> {
+    let mut rest = s;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let end = rest.find(|c: char| c.is_ascii_digit() != first.is_ascii_digit()).unwrap_or(rest.len());
+        let (chunk, remaining) = rest.split_at(end);
+        rest = remaining;
+        Some(chunk)
+    })
+}
+
 pub struct Resource {
     pub id: i32,
     pub r#type: String,
@@ -174,37 +246,88 @@ pub struct Resource {
     pub labels: HashMap<Box<str>, ResourceLabelValue>,
 }
 impl Resource {
+    /// Returns a cheap summary of the resources table, telling whether resources were added, removed or changed (see [`ResourcesSignature`]).
+    pub fn get_signature(session: &Session) -> Result<ResourcesSignature, DbError> {
+        session.runtime.block_on(async {
+            let row = Query::select()
+                .expr_as(Func::count(Expr::col(Resources::ResourceId)), Alias::new("count"))
+                .expr_as(Expr::cust("CAST(COALESCE(MAX(resource_id), 0) AS BIGINT)"), Alias::new("max_id"))
+                .expr_as(
+                    Expr::cust("CAST(COALESCE(SUM(CASE WHEN LOWER(state) = 'alive' THEN resource_id ELSE 0 END), 0) AS BIGINT)"),
+                    Alias::new("alive_ids_sum"),
+                )
+                .expr_as(Expr::cust("CAST(COALESCE(SUM(available_upto), 0) AS BIGINT)"), Alias::new("available_upto_sum"))
+                .expr_as(
+                    Expr::cust("(SELECT CAST(COALESCE(MAX(resource_log_id), 0) AS BIGINT) FROM resource_logs)"),
+                    Alias::new("max_log_id"),
+                )
+                .from(Resources::Table)
+                .fetch_one(session)
+                .await?;
+            Ok(ResourcesSignature {
+                count: row.try_get("count")?,
+                max_id: row.try_get("max_id")?,
+                alive_ids_sum: row.try_get("alive_ids_sum")?,
+                available_upto_sum: row.try_get("available_upto_sum")?,
+                max_log_id: row.try_get("max_log_id")?,
+            })
+        })
+    }
+    /// Sets the state of the resource `resource_id`, and logs the change in the `resource_logs` table as OAR does.
+    /// Only meant to be used by tests, as resource states are changed by OAR.
+    pub fn set_state(session: &Session, resource_id: i64, state: &str) -> Result<(), DbError> {
+        let now = session.get_now()?;
+        session.runtime.block_on(async {
+            Query::update()
+                .table(Resources::Table)
+                .value(Resources::State, state)
+                .and_where(Expr::col(Resources::ResourceId).eq(resource_id))
+                .execute(session)
+                .await?;
+            Query::insert()
+                .into_table(ResourceLogs::Table)
+                .columns(vec![ResourceLogs::ResourceId, ResourceLogs::Attribute, ResourceLogs::Value, ResourceLogs::DateStart])
+                .values_panic(vec![Expr::val(resource_id), Expr::val("state"), Expr::val(state), Expr::val(now)])
+                .execute(session)
+                .await?;
+            Ok(())
+        })
+    }
     /// Get all resources, sorted by the given order_by_clause (e.g., "type, network_address").
     pub fn get_all_sorted(
         session: &Session,
         order_by_clause: &str,
         labels: &Vec<Box<str>>,
-    ) -> Result<Vec<Resource>, Error> {
+    ) -> Result<Vec<Resource>, DbError> {
+        session.runtime.block_on(async { Self::get_all_sorted_async(session, order_by_clause, labels).await })
+    }
+    /// Async version of [`Resource::get_all_sorted`], to be used from within the session runtime.
+    pub(crate) async fn get_all_sorted_async(
+        session: &Session,
+        order_by_clause: &str,
+        labels: &[Box<str>],
+    ) -> Result<Vec<Resource>, DbError> {
         let customs = parse_order_clause(order_by_clause);
-        let rows = session.runtime.block_on(async {
-            Query::select()
-                .columns(vec![Resources::Type, Resources::State, Resources::AvailableUpto])
-                .columns(labels.iter().map(|s| Alias::new(s.as_ref())).collect::<Vec<Alias>>())
-                .from(Resources::Table)
-                .order_by_customs(customs)
-                .fetch_all(session)
-                .await
-        })?;
+        let rows = Query::select()
+            .columns(vec![Resources::Type, Resources::State, Resources::AvailableUpto])
+            .columns(labels.iter().map(|s| Alias::new(s.as_ref())).collect::<Vec<Alias>>())
+            .from(Resources::Table)
+            .order_by_customs(customs)
+            .fetch_all(session)
+            .await?;
 
         let mut results = Vec::new();
         for row in rows {
             let mut map = HashMap::new();
-            labels.iter().for_each(|label| {
+            for label in labels.iter() {
                 let value: Result<i64, _> = row.try_get(label.as_ref());
                 if let Ok(v) = value {
                     map.insert(label.clone(), ResourceLabelValue::Integer(v));
                 } else {
-                    let v: String = row
-                        .try_get(label.as_ref())
-                        .expect(format!("Failed to get resource label value for label {}", label).as_str());
+                    let v: String = row.try_get(label.as_ref())?;
                     map.insert(label.clone(), ResourceLabelValue::Varchar(v));
                 }
-            });
+            }
             results.push(Resource {
                 id: row.get("resource_id"),
                 r#type: row.get("type"),
@@ -0,0 +1,76 @@
+use crate::{DbError, Session, SessionInsertStatement, SessionSelectStatement, SessionUpdateStatement};
+use sea_query::{Expr, ExprTrait, Iden, Query};
+use sqlx::Row;
+
+#[derive(Iden)]
+enum WalltimeChanges {
+    #[iden = "walltime_change"]
+    Table,
+    #[iden = "job_id"]
+    JobId,
+    #[iden = "pending"]
+    Pending,
+    #[iden = "granted"]
+    Granted,
+}
+
+/// Walltime change request of a job, e.g., submitted with `oarwalltime`.
+/// `pending` is the walltime increase (or decrease if negative) in seconds not granted yet,
+/// and `granted` the sum of the changes already granted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalltimeChange {
+    pub job_id: i64,
+    pub pending: i64,
+    pub granted: i64,
+}
+impl WalltimeChange {
+    pub fn insert(&self, session: &Session) -> Result<(), DbError> {
+        session.runtime.block_on(async {
+            Query::insert()
+                .into_table(WalltimeChanges::Table)
+                .columns(vec![WalltimeChanges::JobId, WalltimeChanges::Pending, WalltimeChanges::Granted])
+                .values_panic(vec![Expr::val(self.job_id), Expr::val(self.pending), Expr::val(self.granted)])
+                .execute(session)
+                .await
+        })?;
+        Ok(())
+    }
+
+    /// Returns the walltime change requests with a pending change, ordered by job id.
+    pub fn get_pending(session: &Session) -> Result<Vec<WalltimeChange>, DbError> {
+        let rows = session.runtime.block_on(async {
+            Query::select()
+                .columns(vec![WalltimeChanges::JobId, WalltimeChanges::Pending, WalltimeChanges::Granted])
+                .from(WalltimeChanges::Table)
+                .and_where(Expr::col(WalltimeChanges::Pending).ne(0))
+                .order_by(WalltimeChanges::JobId, sea_query::Order::Asc)
+                .to_owned()
+                .fetch_all(session)
+                .await
+        })?;
+        rows.iter()
+            .map(|row| {
+                Ok(WalltimeChange {
+                    job_id: row.try_get(WalltimeChanges::JobId.unquoted())?,
+                    pending: row.try_get(WalltimeChanges::Pending.unquoted())?,
+                    granted: row.try_get(WalltimeChanges::Granted.unquoted())?,
+                })
+            })
+            .collect()
+    }
+
+    /// Grants the pending change: it is added to the granted changes, and the request is not pending anymore.
+    /// The walltime of the job moldable should be updated by the caller.
+    pub fn grant(&self, session: &Session) -> Result<(), DbError> {
+        session.runtime.block_on(async {
+            Query::update()
+                .table(WalltimeChanges::Table)
+                .value(WalltimeChanges::Pending, 0)
+                .value(WalltimeChanges::Granted, self.granted + self.pending)
+                .and_where(Expr::col(WalltimeChanges::JobId).eq(self.job_id))
+                .execute(session)
+                .await
+        })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,392 @@
+use crate::model::resources::{Resource, ResourceLabelValue, Resources};
+use crate::{DbError, Session, SessionSelectStatement};
+use log::warn;
+use oar_scheduler_core::platform::ProcSet;
+use sea_query::{Expr, Iden, Query};
+use sqlx::Row;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Parsed resource properties expression of a job resource group (`res_group_property`), e.g., `host = 'node1' AND cpu IN (1, 2)`.
+/// Supports the `=`, `!=` (or `<>`), `<`, `<=`, `>`, `>=`, `IN (...)` and `LIKE` comparisons of a resource label with a value,
+/// combined with `AND`, `OR`, `NOT` and parentheses. An empty expression matches all the resources.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertiesExpr {
+    True,
+    Eq(Box<str>, PropertyValue),
+    NotEq(Box<str>, PropertyValue),
+    Lt(Box<str>, PropertyValue),
+    LtEq(Box<str>, PropertyValue),
+    Gt(Box<str>, PropertyValue),
+    GtEq(Box<str>, PropertyValue),
+    In(Box<str>, Vec<PropertyValue>),
+    /// SQL pattern, `%` matching any sequence of characters and `_` any single character.
+    Like(Box<str>, String),
+    Not(Box<PropertiesExpr>),
+    And(Box<PropertiesExpr>, Box<PropertiesExpr>),
+    Or(Box<PropertiesExpr>, Box<PropertiesExpr>),
+}
+
+/// Literal value of a properties expression. Unquoted values that are not integers are read as text, e.g., `lowpower = true`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyValue {
+    Integer(i64),
+    Text(String),
+}
+
+impl PropertyValue {
+    /// Integers are compared with integer labels, anything else is compared as text.
+    fn matches(&self, label: &ResourceLabelValue) -> bool {
+        match (label, self) {
+            (ResourceLabelValue::Integer(label), PropertyValue::Integer(value)) => label == value,
+            (ResourceLabelValue::Varchar(label), PropertyValue::Text(value)) => label == value,
+            (ResourceLabelValue::Integer(label), PropertyValue::Text(value)) => label.to_string() == *value,
+            (ResourceLabelValue::Varchar(label), PropertyValue::Integer(value)) => *label == value.to_string(),
+        }
+    }
+    /// Compares a label with the value. Integers are compared numerically, as well as text parsing as an integer
+    /// compared with an integer. Anything else is compared as text.
+    fn compare(&self, label: &ResourceLabelValue) -> Ordering {
+        match (label, self) {
+            (ResourceLabelValue::Integer(label), PropertyValue::Integer(value)) => label.cmp(value),
+            (ResourceLabelValue::Integer(label), PropertyValue::Text(value)) => match value.parse::<i64>() {
+                Ok(value) => label.cmp(&value),
+                Err(_) => label.to_string().as_str().cmp(value.as_str()),
+            },
+            (ResourceLabelValue::Varchar(label), PropertyValue::Integer(value)) => match label.parse::<i64>() {
+                Ok(label) => label.cmp(value),
+                Err(_) => label.as_str().cmp(value.to_string().as_str()),
+            },
+            (ResourceLabelValue::Varchar(label), PropertyValue::Text(value)) => label.as_str().cmp(value.as_str()),
+        }
+    }
+}
+
+/// Returns whether `text` matches the SQL `LIKE` pattern, `%` matching any sequence of characters and `_` any single character.
+fn like_matches(text: &str, pattern: &str) -> bool {
+    let (text, pattern) = (text.chars().collect::<Vec<char>>(), pattern.chars().collect::<Vec<char>>());
+    // matches[j]: whether the text read so far matches the first j characters of the pattern
+    let mut matches = vec![false; pattern.len() + 1];
+    matches[0] = true;
+    for j in 1..=pattern.len() {
+        matches[j] = matches[j - 1] && pattern[j - 1] == '%';
+    }
+    for c in text {
+        let mut next = vec![false; pattern.len() + 1];
+        for j in 1..=pattern.len() {
+            next[j] = match pattern[j - 1] {
+                '%' => next[j - 1] || matches[j],
+                '_' => matches[j - 1],
+                p => matches[j - 1] && p == c,
+            };
+        }
+        matches = next;
+    }
+    matches[pattern.len()]
+}
+
+impl PropertiesExpr {
+    /// Parses a properties expression, returning an error message if it is malformed.
+    pub fn parse(expr: &str) -> Result<PropertiesExpr, String> {
+        let tokens = tokenize(expr)?;
+        if tokens.is_empty() {
+            return Ok(PropertiesExpr::True);
+        }
+        let mut parser = Parser { tokens, pos: 0 };
+        let parsed = parser.parse_or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(parsed),
+            Some(token) => Err(format!("unexpected token {:?} in properties '{}'", token, expr)),
+        }
+    }
+
+    /// Returns whether a resource with these `labels` satisfies the expression.
+    /// A comparison on a label the resource does not have is false.
+    pub fn matches(&self, labels: &HashMap<Box<str>, ResourceLabelValue>) -> bool {
+        match self {
+            PropertiesExpr::True => true,
+            PropertiesExpr::Eq(label, value) => labels.get(label).is_some_and(|label| value.matches(label)),
+            PropertiesExpr::NotEq(label, value) => labels.get(label).is_some_and(|label| !value.matches(label)),
+            PropertiesExpr::Lt(label, value) => labels.get(label).is_some_and(|label| value.compare(label).is_lt()),
+            PropertiesExpr::LtEq(label, value) => labels.get(label).is_some_and(|label| value.compare(label).is_le()),
+            PropertiesExpr::Gt(label, value) => labels.get(label).is_some_and(|label| value.compare(label).is_gt()),
+            PropertiesExpr::GtEq(label, value) => labels.get(label).is_some_and(|label| value.compare(label).is_ge()),
+            PropertiesExpr::In(label, values) => labels.get(label).is_some_and(|label| values.iter().any(|value| value.matches(label))),
+            PropertiesExpr::Like(label, pattern) => labels.get(label).is_some_and(|label| match label {
+                ResourceLabelValue::Integer(label) => like_matches(&label.to_string(), pattern),
+                ResourceLabelValue::Varchar(label) => like_matches(label, pattern),
+            }),
+            PropertiesExpr::Not(expr) => !expr.matches(labels),
+            PropertiesExpr::And(left, right) => left.matches(labels) && right.matches(labels),
+            PropertiesExpr::Or(left, right) => left.matches(labels) || right.matches(labels),
+        }
+    }
+
+    /// Returns the resource labels referenced by the expression, without duplicates.
+    pub fn labels(&self) -> Vec<Box<str>> {
+        let mut labels = Vec::new();
+        self.collect_labels(&mut labels);
+        labels
+    }
+    fn collect_labels(&self, labels: &mut Vec<Box<str>>) {
+        match self {
+            PropertiesExpr::True => {}
+            PropertiesExpr::Eq(label, _)
+            | PropertiesExpr::NotEq(label, _)
+            | PropertiesExpr::Lt(label, _)
+            | PropertiesExpr::LtEq(label, _)
+            | PropertiesExpr::Gt(label, _)
+            | PropertiesExpr::GtEq(label, _)
+            | PropertiesExpr::In(label, _)
+            | PropertiesExpr::Like(label, _) => {
+                if !labels.contains(label) {
+                    labels.push(label.clone());
+                }
+            }
+            PropertiesExpr::Not(expr) => expr.collect_labels(labels),
+            PropertiesExpr::And(left, right) | PropertiesExpr::Or(left, right) => {
+                left.collect_labels(labels);
+                right.collect_labels(labels);
+            }
+        }
+    }
+}
+
+/// Returns whether a resource with these `labels` satisfies the properties expression `expr`.
+/// A malformed or unsupported expression matches all the resources, with a warning.
+pub fn matches(labels: &HashMap<Box<str>, ResourceLabelValue>, expr: &str) -> bool {
+    match PropertiesExpr::parse(expr) {
+        Ok(properties) => properties.matches(labels),
+        Err(e) => {
+            warn!("Invalid resource properties, ignoring them: {}", e);
+            true
+        }
+    }
+}
+
+/// Returns the resources of the loaded resource set (see [`Session::get_resource_set`]) satisfying `properties`, as a `ProcSet`.
+/// The labels referenced by `properties` are loaded from the `resources` table, failing if one of them is not a column.
+pub(crate) async fn filter_resources(session: &Session, properties: &PropertiesExpr) -> Result<ProcSet, DbError> {
+    let mut labels = properties.labels();
+    if !labels.iter().any(|label| label.as_ref() == "resource_id") {
+        labels.push("resource_id".into());
+    }
+    let resources = Resource::get_all_sorted_async(session, "resource_id", &labels).await?;
+    Ok(resources
+        .iter()
+        .filter(|resource| properties.matches(&resource.labels))
+        .filter_map(|resource| session.resource_id_to_resource_index(resource.id))
+        .collect::<ProcSet>())
+}
+
+/// Returns the resources of the loaded resource set (see [`Session::get_resource_set`]) satisfying the SQL condition `properties`,
+/// evaluated by the database with `SELECT resource_id FROM resources WHERE <properties>`.
+/// Used for the expressions [`PropertiesExpr`] cannot parse, e.g., `IS NULL` conditions or SQL functions.
+pub(crate) async fn filter_resources_sql(session: &Session, properties: &str) -> Result<ProcSet, DbError> {
+    let rows = Query::select()
+        .column(Resources::ResourceId)
+        .from(Resources::Table)
+        .and_where(Expr::cust(format!("({})", properties)))
+        .to_owned()
+        .fetch_all(session)
+        .await?;
+    Ok(rows
+        .iter()
+        .filter_map(|row| session.resource_id_to_resource_index(row.get::<i32, &str>(Resources::ResourceId.unquoted())))
+        .collect::<ProcSet>())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LeftParen,
+    RightParen,
+    Comma,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    And,
+    Or,
+    Not,
+    In,
+    Like,
+    Word(String),
+    Quoted(String),
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = expr.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | ',' | '=' => {
+                chars.next();
+                tokens.push(match c {
+                    '(' => Token::LeftParen,
+                    ')' => Token::RightParen,
+                    ',' => Token::Comma,
+                    _ => Token::Eq,
+                });
+            }
+            '!' => {
+                chars.next();
+                match chars.next() {
+                    Some('=') => tokens.push(Token::NotEq),
+                    _ => return Err(format!("unsupported operator in properties '{}'", expr)),
+                }
+            }
+            '<' | '>' => {
+                chars.next();
+                let token = match (c, chars.peek()) {
+                    ('<', Some('>')) => Token::NotEq,
+                    ('<', Some('=')) => Token::LtEq,
+                    ('>', Some('=')) => Token::GtEq,
+                    ('<', _) => Token::Lt,
+                    _ => Token::Gt,
+                };
+                // Consume the second character of two-character operators
+                if matches!(token, Token::NotEq | Token::LtEq | Token::GtEq) {
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+            '\'' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        // A doubled quote is an escaped quote
+                        Some('\'') if chars.peek() == Some(&'\'') => {
+                            chars.next();
+                            value.push('\'');
+                        }
+                        Some('\'') => break,
+                        Some(c) => value.push(c),
+                        None => return Err(format!("unterminated string in properties '{}'", expr)),
+                    }
+                }
+                tokens.push(Token::Quoted(value));
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '-' || c == '.' => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_' || c == '-' || c == '.') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.to_uppercase().as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    "IN" => Token::In,
+                    "LIKE" => Token::Like,
+                    _ => Token::Word(word),
+                });
+            }
+            c => return Err(format!("unexpected character '{}' in properties '{}'", c, expr)),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser, `NOT` taking precedence over `AND`, itself taking precedence over `OR`.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            token => Err(format!("expected {:?}, found {:?}", expected, token)),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<PropertiesExpr, String> {
+        let mut expr = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            expr = PropertiesExpr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+    fn parse_and(&mut self) -> Result<PropertiesExpr, String> {
+        let mut expr = self.parse_primary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            expr = PropertiesExpr::And(Box::new(expr), Box::new(self.parse_primary()?));
+        }
+        Ok(expr)
+    }
+    fn parse_primary(&mut self) -> Result<PropertiesExpr, String> {
+        match self.next() {
+            Some(Token::LeftParen) => {
+                let expr = self.parse_or()?;
+                self.expect(Token::RightParen)?;
+                Ok(expr)
+            }
+            Some(Token::Not) => Ok(PropertiesExpr::Not(Box::new(self.parse_primary()?))),
+            Some(Token::Word(label)) => {
+                let label = label.into_boxed_str();
+                // `label NOT IN (...)` and `label NOT LIKE ...`
+                if self.peek() == Some(&Token::Not) {
+                    self.pos += 1;
+                    return match self.peek() {
+                        Some(Token::In) | Some(Token::Like) => Ok(PropertiesExpr::Not(Box::new(self.parse_comparison(label)?))),
+                        token => Err(format!("expected IN or LIKE after '{} NOT', found {:?}", label, token)),
+                    };
+                }
+                self.parse_comparison(label)
+            }
+            token => Err(format!("expected a label or '(', found {:?}", token)),
+        }
+    }
+    fn parse_comparison(&mut self, label: Box<str>) -> Result<PropertiesExpr, String> {
+        match self.next() {
+            Some(Token::Eq) => Ok(PropertiesExpr::Eq(label, self.parse_value()?)),
+            Some(Token::NotEq) => Ok(PropertiesExpr::NotEq(label, self.parse_value()?)),
+            Some(Token::Lt) => Ok(PropertiesExpr::Lt(label, self.parse_value()?)),
+            Some(Token::LtEq) => Ok(PropertiesExpr::LtEq(label, self.parse_value()?)),
+            Some(Token::Gt) => Ok(PropertiesExpr::Gt(label, self.parse_value()?)),
+            Some(Token::GtEq) => Ok(PropertiesExpr::GtEq(label, self.parse_value()?)),
+            Some(Token::Like) => match self.parse_value()? {
+                PropertyValue::Text(pattern) => Ok(PropertiesExpr::Like(label, pattern)),
+                PropertyValue::Integer(pattern) => Ok(PropertiesExpr::Like(label, pattern.to_string())),
+            },
+            Some(Token::In) => {
+                self.expect(Token::LeftParen)?;
+                let mut values = vec![self.parse_value()?];
+                while self.peek() == Some(&Token::Comma) {
+                    self.pos += 1;
+                    values.push(self.parse_value()?);
+                }
+                self.expect(Token::RightParen)?;
+                Ok(PropertiesExpr::In(label, values))
+            }
+            token => Err(format!("expected a comparison after '{}', found {:?}", label, token)),
+        }
+    }
+    fn parse_value(&mut self) -> Result<PropertyValue, String> {
+        match self.next() {
+            Some(Token::Quoted(value)) => Ok(PropertyValue::Text(value)),
+            Some(Token::Word(value)) => Ok(value.parse::<i64>().map(PropertyValue::Integer).unwrap_or(PropertyValue::Text(value))),
+            token => Err(format!("expected a value, found {:?}", token)),
+        }
+    }
+}
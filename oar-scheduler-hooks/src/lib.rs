@@ -4,6 +4,7 @@ use oar_scheduler_core::hooks::HooksHandler;
 use oar_scheduler_core::model::job::{Job, Moldable, ProcSet};
 use oar_scheduler_core::platform::PlatformConfig;
 use oar_scheduler_core::scheduler::slotset::SlotSet;
+use std::collections::HashMap;
 
 pub struct Hooks {
 
@@ -22,6 +23,10 @@ impl HooksHandler for Hooks {
         debug!("Sort hook called");
         false
     }
+    fn hook_fairshare(&self, platform_config: &PlatformConfig, users_usage: &HashMap<Box<str>, f64>, waiting_jobs: &mut IndexMap<i64, Job>) -> bool {
+        debug!("Fairshare hook called");
+        false
+    }
     fn hook_assign(&self, slot_set: &mut SlotSet, job: &mut Job, min_begin: Option<i64>) -> bool {
         debug!("Assign hook called");
         false
@@ -30,4 +35,8 @@ impl HooksHandler for Hooks {
         debug!("Find hook called");
         None
     }
+    fn hook_filter_resources(&self, platform_config: &PlatformConfig, job: &Job, available: &mut ProcSet) -> bool {
+        debug!("Filter resources hook called");
+        false
+    }
 }
@@ -1,13 +1,18 @@
 mod platform;
 mod queues_schedule;
 mod meta_schedule;
+mod session;
+#[cfg(test)]
+mod mock_session;
 #[cfg(test)]
 mod test;
 
 use dotenvy::dotenv;
-use log::LevelFilter;
+use log::{warn, LevelFilter};
+use oar_scheduler_core::logging;
 use oar_scheduler_core::model::configuration::Configuration;
 use oar_scheduler_core::platform::PlatformTrait;
+use oar_scheduler_core::scheduler::kamelot::CancellationToken;
 use oar_scheduler_db::Session;
 use platform::Platform;
 
@@ -16,9 +21,7 @@ fn main() {
     dotenv().ok();
 
     // Initialize logging
-    env_logger::Builder::new()
-        .filter(None, LevelFilter::Debug)
-        .init();
+    logging::init(logging::level_from_env(LevelFilter::Debug), &[]);
 
     // Load configuration
     let config = Configuration::load();
@@ -26,8 +29,17 @@ fn main() {
     // Initialize database connection
     let session = Session::new(&config);
 
-    // Create the platform instance
-    let mut platform = Platform::from_database(session, config);
+    // Create the platform instance, never modifying the database if `--dry-run` is passed
+    let dry_run = std::env::args().any(|arg| arg == "--dry-run");
+    // Cancel the scheduling on SIGINT or SIGTERM, keeping the assignments already made
+    let cancellation_token = CancellationToken::new();
+    let handler_token = cancellation_token.clone();
+    if let Err(e) = ctrlc::set_handler(move || handler_token.cancel()) {
+        warn!("Unable to set the shutdown signal handler: {}", e);
+    }
+    let mut platform = Platform::from_database(session, config)
+        .with_dry_run(dry_run)
+        .with_cancellation_token(cancellation_token);
 
     // Meta scheduling
     meta_schedule::meta_schedule(&mut platform);
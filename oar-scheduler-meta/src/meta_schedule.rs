@@ -1,35 +1,52 @@
 use crate::platform::Platform;
 use crate::queues_schedule::queues_schedule;
-use log::{debug, warn};
+use log::{debug, error, info, warn};
+use oar_scheduler_core::model::job::{assignments_to_table, ProcSet};
 use oar_scheduler_core::platform::{Job, PlatformTrait};
 use oar_scheduler_db::model::jobs::{JobDatabaseRequests, JobState};
 use oar_scheduler_db::model::moldable::MoldableDatabaseRequests;
+use oar_scheduler_db::model::walltime_change::WalltimeChange;
 use oar_scheduler_db::model::{events, gantt, SqlEnum};
-use std::collections::HashSet;
+use oar_scheduler_core::scheduler::kamelot;
+use std::collections::{HashMap, HashSet};
 use std::process::{exit, Command};
 
+/// Runs a meta-scheduling round.
+/// If the platform is in dry-run mode (see [`Platform::with_dry_run`]), the database is left untouched,
+/// and the would-be assignments are printed and can be retrieved with [`Platform::get_dry_run_assignments`].
+/// If the platform cancellation token is cancelled (see [`Platform::with_cancellation_token`]), the jobs and queues not yet scheduled
+/// are left for the next round, while the assignments made so far are saved and handled as usual.
 pub fn meta_schedule(platform: &mut Platform) -> i64 {
     let mut exit_code = 0;
     let now = platform.get_now();
 
-    // TODO: Implement `process_walltime_change_requests` with config values WALLTIME_CHANGE_ENABLED, WALLTIME_CHANGE_APPLY_TIME, WALLTIME_INCREMENT
+    // The connection may have been lost since the previous round
+    if let Err(e) = platform.ensure_connected() {
+        error!("Unable to reach the database: {}", e);
+        return 1;
+    }
 
     // Initialize gantt tables with running/already scheduled jobs so they are accessible from `platform.get_scheduled_jobs()`
     gantt_init_with_running_jobs(platform);
 
+    // TODO: Handle the config values WALLTIME_CHANGE_APPLY_TIME and WALLTIME_INCREMENT
+    if platform.get_platform_config().config.walltime_change_enabled {
+        process_walltime_change_requests(platform);
+    }
+
     // Schedule queues
-    let besteffort_scheduled_jobs = queues_schedule(platform);
+    let (besteffort_scheduled_jobs, preempted_jobs) = queues_schedule(platform);
 
-    // Getting waiting gantt jobs with a start time before now + min(security_time, kill_duration_before_reservation)
+    // Getting waiting gantt jobs with a start time before now + max(security_time, kill_duration_before_reservation)
     let jobs_to_launch_with_security_time = platform.get_gantt_jobs_to_launch_with_security_time();
 
     let jobs_to_launch = jobs_to_launch_with_security_time
         .iter()
-        .filter(|job| job.assignment.as_ref().unwrap().begin <= now)
+        .filter(|job| job.window().expect("Gantt jobs to launch should be scheduled").0 <= now)
         .collect::<Vec<_>>();
 
     // Killing besteffort jobs on which new jobs have been scheduled.
-    let no_killed_job = check_besteffort_jobs_to_kill(platform, &besteffort_scheduled_jobs, &jobs_to_launch);
+    let no_killed_job = check_besteffort_jobs_to_kill(platform, &besteffort_scheduled_jobs, &preempted_jobs, &jobs_to_launch);
     if no_killed_job {
         if handle_jobs_to_launch(platform, &jobs_to_launch) == 1 {
             exit_code = 0; // Exit code was already at 0 anyway... Following the Python code.
@@ -48,6 +65,13 @@ pub fn meta_schedule(platform: &mut Platform) -> i64 {
 
     // TODO: Process toAckReservation jobs
 
+    if platform.is_dry_run() {
+        info!("Dry run: the database has not been modified, would-be assignments:");
+        assignments_to_table(platform.get_dry_run_assignments()).printstd();
+        debug!("End of Meta Scheduler");
+        return exit_code;
+    }
+
     let jobs_by_state = platform.get_current_non_waiting_jobs_by_state();
 
     if let Some(jobs) = jobs_by_state.get(&JobState::Resuming.as_str().to_string()) {
@@ -74,7 +98,9 @@ pub fn meta_schedule(platform: &mut Platform) -> i64 {
 
 /// Initialize gantt tables with scheduled reservation jobs, Running jobs, toLaunch jobs and Launching jobs.
 fn gantt_init_with_running_jobs(platform: &mut Platform) {
-    gantt::gantt_flush_tables(&platform.session());
+    if !platform.is_dry_run() {
+        gantt::gantt_flush_tables(platform.session()).expect("Unable to flush the gantt tables");
+    }
     let current_jobs = platform.get_fully_scheduled_jobs();
     debug!("(gantt_init with running jobs: save assignement with current");
     platform.save_assignments(current_jobs);
@@ -82,14 +108,98 @@ fn gantt_init_with_running_jobs(platform: &mut Platform) {
     // `kamelot::init_slot_sets` function to avoid code duplication.
 }
 
-/// Detect if there are besteffort jobs to kill
+/// Grants the pending walltime change requests of the running jobs in the gantt tables (see [`gantt_init_with_running_jobs`]).
+/// A job is resized in a slot set holding the running jobs and advance reservations
+/// (see [`oar_scheduler_core::scheduler::slotset::SlotSet::resize_job`]): shrinking is always granted,
+/// while extending is only granted if the resources of the job are free until its new end,
+/// the waiting jobs being scheduled after the extended jobs. Requests that can't be granted stay pending for the next rounds.
+/// A job can't be shrunk to end before now.
+fn process_walltime_change_requests(platform: &mut Platform) {
+    let requests = match WalltimeChange::get_pending(platform.session()) {
+        Ok(requests) => requests,
+        Err(e) => {
+            error!("Unable to get the walltime change requests: {}", e);
+            return;
+        }
+    };
+    if requests.is_empty() {
+        return;
+    }
+    let now = platform.get_now();
+    let (mut slot_sets, _) = kamelot::init_slot_sets(platform, false);
+    let slot_set = slot_sets.get_mut("default").expect("The default slot set must exist");
+    let jobs = platform
+        .get_scheduled_jobs()
+        .into_iter()
+        .map(|job| (job.id, job))
+        .collect::<HashMap<i64, Job>>();
+    for request in requests {
+        let running_job = jobs.get(&request.job_id).filter(|job| job.state == JobState::Running.as_str());
+        let Some((job, assignment)) = running_job.and_then(|job| job.assignment.as_ref().map(|assignment| (job, assignment))) else {
+            debug!("Walltime change of the job {} is left pending: the job is not running", request.job_id);
+            continue;
+        };
+        let moldable = &job.moldables[assignment.moldable_index];
+        let new_walltime = (moldable.walltime + request.pending).max(now - assignment.begin + 1);
+        if let Err(e) = slot_set.resize_job(job, Job::compute_end(assignment.begin, new_walltime)) {
+            info!("Walltime change of the job {} is left pending: {}", job.id, e);
+            continue;
+        }
+        if platform.is_dry_run() {
+            info!("Dry run: the walltime of the job {} would be changed from {} to {}", job.id, moldable.walltime, new_walltime);
+            continue;
+        }
+        info!("Changing the walltime of the job {} from {} to {}", job.id, moldable.walltime, new_walltime);
+        let result = moldable
+            .set_walltime(platform.session(), new_walltime)
+            .and_then(|_| WalltimeChange { pending: new_walltime - moldable.walltime, ..request }.grant(platform.session()))
+            .and_then(|_| {
+                let description = format!("Change walltime from {} to {}", moldable.walltime, new_walltime);
+                events::add_new_event(platform.session(), "WALLTIME", job.id, description.as_str())
+            });
+        if let Err(e) = result {
+            error!("Unable to change the walltime of the job {}: {}", job.id, e);
+        }
+    }
+}
+
+/// Detect if there are besteffort jobs to kill: the besteffort jobs preempted by the scheduling (see [`queues_schedule`]),
+/// and the ones using resources of the jobs to launch. They are marked to be killed, with a `BESTEFFORT_KILL` event.
+/// `besteffort_scheduled_jobs` are only the besteffort jobs inserted in the slot sets, i.e., none without `scheduler_besteffort_preemption`.
 /// return true if there is no frag job (no job marked as to be killed), false otherwise
-fn check_besteffort_jobs_to_kill(platform: &mut Platform, besteffort_scheduled_jobs: &Vec<Job>, jobs_to_launch: &Vec<&Job>) -> bool {
-    // TODO: Review and implement the logic of that function:
-    //   https://github.com/oar-team/oar3/blob/e6b6e7e59eb751cc2e7388d6c2fb7f94a3ac8c6e/oar/kao/meta_sched.py#L148-L227
-    //   The algorithm might need adaptations as we don’t have the resources_id to job_id map (maybe build them here),
-    //   and our jobs in `besteffort_scheduled_jobs` are only present if we scheduled the besteffort queue in this cycle.
-    true
+fn check_besteffort_jobs_to_kill(
+    platform: &mut Platform,
+    besteffort_scheduled_jobs: &Vec<Job>,
+    preempted_jobs: &[i64],
+    jobs_to_launch: &Vec<&Job>,
+) -> bool {
+    let launched_resources = jobs_to_launch
+        .iter()
+        .filter_map(|job| job.assignment.as_ref())
+        .fold(ProcSet::new(), |resources, assignment| resources | &assignment.resources);
+    let jobs_to_kill = besteffort_scheduled_jobs
+        .iter()
+        .filter(|job| {
+            preempted_jobs.contains(&job.id)
+                || job.assignment.as_ref().is_some_and(|assignment| !(&assignment.resources & &launched_resources).is_empty())
+        })
+        .collect::<Vec<&Job>>();
+    for job in &jobs_to_kill {
+        if platform.is_dry_run() {
+            info!("Dry run: besteffort job {} would be killed", job.id);
+            continue;
+        }
+        match job.frag(&platform.session()) {
+            Ok(true) => {
+                info!("Killing the besteffort job {}", job.id);
+                events::add_new_event(&platform.session(), "BESTEFFORT_KILL", job.id, format!("kill the besteffort job {}", job.id).as_str())
+                    .expect("Unable to add the besteffort kill event");
+            }
+            Ok(false) => debug!("Besteffort job {} is already being killed", job.id),
+            Err(e) => error!("Unable to kill the besteffort job {}: {}", job.id, e),
+        }
+    }
+    jobs_to_kill.is_empty()
 }
 
 fn handle_jobs_to_launch(platform: &mut Platform, jobs_to_launch: &Vec<&Job>) -> i32 {
@@ -99,6 +209,11 @@ fn handle_jobs_to_launch(platform: &mut Platform, jobs_to_launch: &Vec<&Job>) ->
     for job in jobs_to_launch {
         return_code = 1;
 
+        if platform.is_dry_run() {
+            info!("Dry run: job {} would be set to toLaunch", job.id);
+            continue;
+        }
+
         if job.assignment.is_none() {
             panic!("Can’t mark job {} as toLaunch as it has no assignment", job.id);
         }
@@ -112,7 +227,7 @@ fn handle_jobs_to_launch(platform: &mut Platform, jobs_to_launch: &Vec<&Job>) ->
                 start_time = now;
                 // The job should start now, so we update its assignment to start now
                 let mut new_job = job.clone();
-                let walltime = assignment.end - assignment.begin + 1;
+                let walltime = moldable.walltime;
                 let new_walltime = assignment.end - now + 1;
                 warn!("Reducing the walltime of the job {} from {} to {}", job.id, walltime, new_walltime);
 
@@ -127,7 +242,8 @@ fn handle_jobs_to_launch(platform: &mut Platform, jobs_to_launch: &Vec<&Job>) ->
                     "REDUCE_RESERVATION_WALLTIME",
                     job.id,
                     format!("Change walltime from {} to {}", walltime, new_walltime).as_str(),
-                );
+                )
+                .expect("Unable to add the AR walltime reduction event");
 
                 // updating job’s message
                 let old_walltime_str = format!("{:02}:{:02}:{:02}", walltime / 3600, (walltime % 3600) / 60, walltime % 60);
@@ -159,9 +275,7 @@ fn notify_to_run_job(_platform: &Platform, job_id: i64) {
     debug!("Notify to run job {}", job_id);
 
     // Testing with a temporary script
-    Command::new("oar-notify-to-run-job")
-        .arg(job_id.to_string())
-        .output()
-        .expect("failed to run oar-notify-to-run-job");
-
+    if let Err(e) = Command::new("oar-notify-to-run-job").arg(job_id.to_string()).output() {
+        error!("Failed to run oar-notify-to-run-job for job {}: {}", job_id, e);
+    }
 }
@@ -0,0 +1,115 @@
+use crate::session::MetaSession;
+use indexmap::IndexMap;
+use oar_scheduler_core::model::configuration::Configuration;
+use oar_scheduler_core::model::job::{Job, ProcSet};
+use oar_scheduler_core::platform::ResourceSet;
+use oar_scheduler_db::model::jobs::{JobReservation, JobState};
+use oar_scheduler_db::model::queues::Queue;
+use oar_scheduler_db::model::SqlEnum;
+use oar_scheduler_db::Session;
+use std::collections::{BTreeMap, HashMap};
+
+/// In-memory [`MetaSession`] serving canned resources, queues, and jobs, to test the meta scheduler without any database.
+/// The jobs having an assignment are the gantt jobs. Having no database, it only supports dry runs (see [`crate::platform::Platform::from_mock`]).
+pub struct MockSession {
+    now: i64,
+    resource_set: ResourceSet,
+    queues: Vec<Queue>,
+    jobs: IndexMap<i64, Job>,
+    /// Reservation state of the advance reservation jobs, the other jobs having the reservation state `None`.
+    reservations: HashMap<i64, JobReservation>,
+}
+
+impl MockSession {
+    pub fn new(now: i64, resource_set: ResourceSet) -> Self {
+        MockSession {
+            now,
+            resource_set,
+            queues: Vec::new(),
+            jobs: IndexMap::new(),
+            reservations: HashMap::new(),
+        }
+    }
+    /// Adds an active queue.
+    pub fn queue(mut self, name: &str, priority: i32, scheduler_policy: &str) -> Self {
+        self.queues.push(Queue {
+            queue_name: name.to_string(),
+            priority,
+            scheduler_policy: scheduler_policy.to_string(),
+            state: "Active".to_string(),
+        });
+        self
+    }
+    pub fn job(mut self, job: Job) -> Self {
+        self.jobs.insert(job.id, job);
+        self
+    }
+    /// Adds an advance reservation job with the reservation state `reservation`.
+    pub fn reservation_job(mut self, job: Job, reservation: JobReservation) -> Self {
+        self.reservations.insert(job.id, reservation);
+        self.job(job)
+    }
+
+    fn reservation_of(&self, job: &Job) -> &str {
+        self.reservations.get(&job.id).map_or(JobReservation::None.as_str(), |reservation| reservation.as_str())
+    }
+    fn filter_jobs<'a>(
+        &'a self,
+        queues: Option<Vec<String>>,
+        reservation: Option<JobReservation>,
+        states: Option<Vec<JobState>>,
+    ) -> impl Iterator<Item = &'a Job> {
+        self.jobs.values().filter(move |job| {
+            queues.as_ref().is_none_or(|queues| queues.iter().any(|queue| queue.as_str() == job.queue.as_ref()))
+                && reservation.as_ref().is_none_or(|reservation| reservation.as_str() == self.reservation_of(job))
+                && states.as_ref().is_none_or(|states| states.iter().any(|state| state.as_str() == job.state))
+        })
+    }
+}
+
+impl MetaSession for MockSession {
+    fn get_now(&self) -> i64 {
+        self.now
+    }
+    fn get_resource_set(&mut self, _config: &Configuration) -> ResourceSet {
+        self.resource_set.clone()
+    }
+    fn get_queues_grouped_by_priority(&self) -> Vec<Vec<Queue>> {
+        let mut priority_map: BTreeMap<i32, Vec<Queue>> = BTreeMap::new();
+        for queue in &self.queues {
+            priority_map.entry(queue.priority).or_default().push(queue.clone());
+        }
+        priority_map.into_values().rev().collect()
+    }
+    fn get_jobs(&self, queues: Option<Vec<String>>, reservation: Option<JobReservation>, states: Option<Vec<JobState>>) -> IndexMap<i64, Job> {
+        self.filter_jobs(queues, reservation, states)
+            .map(|job| (job.id, job.clone()))
+            .collect()
+    }
+    fn get_gantt_jobs(
+        &self,
+        queues: Option<Vec<String>>,
+        reservation: Option<JobReservation>,
+        states: Option<Vec<JobState>>,
+        max_start_time: Option<i64>,
+    ) -> Vec<Job> {
+        self.filter_jobs(queues, reservation, states)
+            .filter(|job| job.begin().is_some_and(|begin| max_start_time.is_none_or(|max_start_time| begin <= max_start_time)))
+            .cloned()
+            .collect()
+    }
+    fn get_running_jobs_windows(&self) -> HashMap<i64, (i64, i64, ProcSet)> {
+        self.filter_jobs(None, None, Some(vec![JobState::Running, JobState::Launching, JobState::Finishing]))
+            .filter_map(|job| job.window().map(|(begin, end, resources)| (job.id, (begin, end, resources.clone()))))
+            .collect()
+    }
+    fn get_sum_accounting_by_user(&self, _queues: &[String], _window_start: i64, _window_stop: i64) -> (HashMap<String, f64>, HashMap<String, f64>) {
+        (HashMap::new(), HashMap::new())
+    }
+    fn database(&self) -> Option<&Session> {
+        None
+    }
+    fn database_mut(&mut self) -> Option<&mut Session> {
+        None
+    }
+}
@@ -1,29 +1,56 @@
 use indexmap::IndexMap;
+use log::info;
 use oar_scheduler_core::model::configuration::Configuration;
-use oar_scheduler_core::model::job::Job;
+use oar_scheduler_core::model::job::{Job, ProcSet};
 use oar_scheduler_core::platform::{PlatformConfig, PlatformTrait};
-use oar_scheduler_db::model::gantt;
+use oar_scheduler_core::scheduler::kamelot::CancellationToken;
+#[cfg(test)]
+use crate::mock_session::MockSession;
+use crate::session::MetaSession;
+use oar_scheduler_db::model::{gantt, SqlEnum};
 use oar_scheduler_db::model::jobs::{JobDatabaseRequests, JobReservation, JobState};
-use oar_scheduler_db::Session;
+use oar_scheduler_db::model::queues::Queue;
+use oar_scheduler_db::{DbError, Session};
 use std::collections::HashMap;
 use std::hash::Hash;
 use std::rc::Rc;
 
 pub struct Platform {
     now: i64,
-    session: Session,
+    session: Box<dyn MetaSession>,
     platform_config: Rc<PlatformConfig>,
+    /// When true, the database is never modified: assignments are kept in `dry_run_scheduled_jobs` instead of the gantt tables.
+    dry_run: bool,
+    /// In dry-run mode, the jobs that would have been saved in the gantt tables, in saving order.
+    dry_run_scheduled_jobs: IndexMap<i64, Job>,
+    /// Token cancelling the scheduling cycles, e.g., on a shutdown signal.
+    cancellation_token: Option<CancellationToken>,
 }
 
 impl Platform {
     pub fn from_database(mut session: Session, config: Configuration) -> Self {
+        // The whole scheduling cycle uses the same time
+        session.set_reference_now(session.get_now().expect("Failed to fetch the current time from the database"));
+        // The loaded moldables without walltime get the walltime of the platform configuration
+        session.set_walltime_estimator(config.walltime_estimator());
+        Self::from_session(Box::new(session), config)
+    }
+    /// Creates a platform reading its resources, queues, and jobs from `session` instead of a database.
+    /// The platform is in dry-run mode, as the database can't be modified.
+    #[cfg(test)]
+    pub fn from_mock(session: MockSession, config: Configuration) -> Self {
+        Self::from_session(Box::new(session), config).with_dry_run(true)
+    }
+    fn from_session(mut session: Box<dyn MetaSession>, config: Configuration) -> Self {
         let now = session.get_now();
         let resource_set = session.get_resource_set(&config);
         let quotas_config = oar_scheduler_core::platform::build_quotas_config(&config, &resource_set);
+        let queues_quotas_config = oar_scheduler_core::platform::build_queues_quotas_config(&config, &resource_set);
 
         let platform_config = Rc::new(PlatformConfig {
             resource_set,
             quotas_config,
+            queues_quotas_config,
             config,
         });
 
@@ -31,46 +58,81 @@ impl Platform {
             now,
             session,
             platform_config,
+            dry_run: false,
+            dry_run_scheduled_jobs: IndexMap::new(),
+            cancellation_token: None,
         }
     }
+    /// Enables or disables the dry-run mode, in which scheduling never modifies the database.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+    /// Sets the token cancelling the scheduling: once cancelled, the jobs not yet scheduled are left waiting for the next round,
+    /// the assignments made so far being saved.
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = Some(cancellation_token);
+        self
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation_token.as_ref().is_some_and(CancellationToken::is_cancelled)
+    }
+    /// Returns the jobs scheduled during a dry run that are still waiting, i.e., the assignments that would have been saved.
+    pub fn get_dry_run_assignments(&self) -> Vec<&Job> {
+        self.dry_run_scheduled_jobs
+            .values()
+            .filter(|job| job.state == JobState::Waiting.as_str())
+            .collect()
+    }
+    /// The database session, used to modify the database.
+    /// Panics if the platform does not read from a database, which only happens in dry-run mode.
     pub fn session(&self) -> &Session {
-        &self.session
+        self.session.database().expect("The platform has no database session")
+    }
+    /// Checks that the database connection is still alive, reconnecting if it was lost (see [`Session::ensure_connected`]).
+    /// Does nothing if the platform does not read from a database.
+    pub fn ensure_connected(&self) -> Result<(), DbError> {
+        self.session.database().map_or(Ok(()), Session::ensure_connected)
+    }
+    pub fn get_queues_grouped_by_priority(&self) -> Vec<Vec<Queue>> {
+        self.session.get_queues_grouped_by_priority()
     }
 
-    // Waiting jobs in the Gantt that should be launched before now + min(security_time, kill_duration_before_reservation)
+    // Waiting jobs in the Gantt that should be launched before now + max(security_time, kill_duration_before_reservation),
+    // with the security time of the queue of each job.
     pub fn get_gantt_jobs_to_launch_with_security_time(&self) -> Vec<Job> {
-        let mut interval = self.platform_config.config.scheduler_besteffort_kill_duration_before_reservation;
-        if interval < self.platform_config.config.scheduler_job_security_time {
-            interval = self.platform_config.config.scheduler_job_security_time;
-        }
-        let max_start_time = self.now + interval;
-        Job::get_gantt_jobs(&self.session, None, None, Some(vec![JobState::Waiting]), Some(max_start_time)).unwrap()
+        let config = &self.platform_config.config;
+        let kill_duration = config.scheduler_besteffort_kill_duration_before_reservation;
+        let max_start_time = |security_time: i64| self.now + kill_duration.max(security_time);
+        // Fetch the jobs with the largest security time, then keep those beginning before the limit of their queue
+        let largest_max_start_time = max_start_time(config.distinct_job_security_times().into_iter().max().unwrap_or(0));
+        let jobs = if self.dry_run {
+            self.get_dry_run_assignments()
+                .into_iter()
+                .filter(|job| job.assignment.as_ref().is_some_and(|a| a.begin <= largest_max_start_time))
+                .cloned()
+                .collect()
+        } else {
+            self.session.get_gantt_jobs(None, None, Some(vec![JobState::Waiting]), Some(largest_max_start_time))
+        };
+        jobs.into_iter()
+            .filter(|job| job.begin().is_some_and(|begin| begin <= max_start_time(config.job_security_time(&job.queue))))
+            .collect()
     }
     // AR jobs that are scheduled still on waiting state in the Gantt
     pub fn get_gantt_waiting_scheduled_ar_jobs(&self, queue_name: String) -> Vec<Job> {
-        Job::get_gantt_jobs(
-            &self.session,
-            Some(vec![queue_name]),
-            Some(JobReservation::Scheduled),
-            Some(vec![JobState::Waiting]),
-            None,
-        )
-            .unwrap()
+        self.session.get_gantt_jobs(Some(vec![queue_name]), Some(JobReservation::Scheduled), Some(vec![JobState::Waiting]), None)
     }
     // AR jobs that are not yet scheduled
     pub fn get_waiting_to_schedule_ar_jobs(&self, queue_name: String) -> IndexMap<i64, Job> {
-        Job::get_jobs(
-            &self.session,
-            Some(vec![queue_name]),
-            Some(JobReservation::ToSchedule),
-            Some(vec![JobState::Waiting]),
-        )
-            .unwrap()
+        self.session.get_jobs(Some(vec![queue_name]), Some(JobReservation::ToSchedule), Some(vec![JobState::Waiting]))
     }
     // Scheduled and at least toLaunch state jobs
     pub fn get_fully_scheduled_jobs(&self) -> IndexMap<i64, Job> {
-        Job::get_jobs(
-            &self.session,
+        self.session.get_jobs(
             None,
             None,
             Some(vec![
@@ -82,11 +144,9 @@ impl Platform {
                 JobState::Resuming,
             ]),
         )
-            .unwrap()
     }
     pub fn get_current_non_waiting_jobs_by_state(&self) -> HashMap<String, Vec<Job>> {
-        let jobs = Job::get_jobs(
-            &self.session,
+        let jobs = self.session.get_jobs(
             None,
             None,
             Some(vec![
@@ -101,8 +161,7 @@ impl Platform {
                 JobState::Suspended,
                 JobState::Resuming,
             ]),
-        )
-            .unwrap();
+        );
         jobs.values().fold(HashMap::new(), |mut map, job| {
             map.entry(job.state.to_string()).or_insert_with(Vec::new).push(job.clone());
             map
@@ -122,14 +181,37 @@ impl PlatformTrait for Platform {
     }
 
     fn get_scheduled_jobs(&self) -> Vec<Job> {
-        Job::get_gantt_jobs(&self.session, None, None, None, None).unwrap()
+        if self.dry_run {
+            return self.dry_run_scheduled_jobs.values().cloned().collect();
+        }
+        self.session.get_gantt_jobs(None, None, None, None)
+    }
+    /// Reads the running jobs from the `assigned_resources` table (see [`JobDatabaseRequests::get_running_jobs_windows`]),
+    /// covering the running jobs that are not in the gantt tables anymore.
+    fn running_jobs_windows(&self, _scheduled_jobs: &[Job]) -> HashMap<i64, (i64, i64, ProcSet)> {
+        self.session.get_running_jobs_windows()
     }
     fn get_waiting_jobs(&self, queues: Vec<String>) -> IndexMap<i64, Job> {
-        Job::get_jobs(&self.session, Some(queues), Some(JobReservation::None), Some(vec![JobState::Waiting])).unwrap()
+        self.session.get_jobs(Some(queues), Some(JobReservation::None), Some(vec![JobState::Waiting]))
     }
 
     fn save_assignments(&mut self, assigned_jobs: IndexMap<i64, Job>) {
-        gantt::save_jobs_assignments_in_gantt(&mut self.session, assigned_jobs).unwrap()
+        if self.dry_run {
+            self.dry_run_scheduled_jobs.extend(assigned_jobs);
+            return;
+        }
+        let session = self.session.database_mut().expect("The platform has no database session");
+        gantt::save_jobs_assignments_in_gantt(session, assigned_jobs).unwrap()
+    }
+    fn reject_jobs(&mut self, rejected_jobs: IndexMap<i64, Job>) {
+        if self.dry_run {
+            info!("Dry run: jobs {:?} would be set to toError", rejected_jobs.keys().collect::<Vec<_>>());
+            return;
+        }
+        for job in rejected_jobs.values() {
+            job.set_message(self.session(), &job.message).expect("Unable to set job message");
+            job.set_state(self.session(), JobState::ToError).expect("Unable to set job state");
+        }
     }
 
     fn get_sum_accounting_window(&self, queues: &[String], window_start: i64, window_stop: i64) -> (f64, f64) {
@@ -139,6 +221,9 @@ impl PlatformTrait for Platform {
         todo!()
     }
     fn get_sum_accounting_by_user(&self, queues: &[String], window_start: i64, window_stop: i64) -> (HashMap<String, f64>, HashMap<String, f64>) {
-        todo!()
+        self.session.get_sum_accounting_by_user(queues, window_start, window_stop)
+    }
+    fn get_cancellation_token(&self) -> Option<&CancellationToken> {
+        self.cancellation_token.as_ref()
     }
 }
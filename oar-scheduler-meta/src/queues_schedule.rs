@@ -2,22 +2,34 @@ use crate::platform::Platform;
 use indexmap::IndexMap;
 use log::{debug, info, warn};
 use oar_scheduler_core::model::job::JobAssignment;
-use oar_scheduler_core::platform::{Job, PlatformTrait, ProcSetCoresOp};
-use oar_scheduler_core::scheduler::slotset::SlotSet;
-use oar_scheduler_core::scheduler::{kamelot, quotas};
+use oar_scheduler_core::platform::{Job, PlatformTrait};
+use oar_scheduler_core::scheduler::slotset::{SlotSet, SplitError};
+use oar_scheduler_core::scheduler::quotas::QuotasKey;
+use oar_scheduler_core::scheduler::kamelot;
+use oar_scheduler_core::scheduler::scheduling::{self, ReservationWindow};
 use oar_scheduler_db::model::jobs::{JobDatabaseRequests, JobState};
 use oar_scheduler_db::model::queues::Queue;
+use oar_scheduler_db::model::SqlEnum;
 use std::collections::HashMap;
 
-///
-pub fn queues_schedule(platform: &mut Platform) -> Vec<Job> {
+/// Schedules the waiting jobs of the active queues, by decreasing queue priority.
+/// With `scheduler_besteffort_preemption`, the already scheduled besteffort jobs are obstacles for all the queues,
+/// and are preempted for the jobs that cannot be assigned otherwise (see [`kamelot::internal_schedule_cycle_with_preemption`]).
+/// Returns the already scheduled besteffort jobs inserted in the slot sets, and the ids of the preempted ones.
+pub fn queues_schedule(platform: &mut Platform) -> (Vec<Job>, Vec<i64>) {
     // Init slotset
-    let (mut slot_sets, besteffort_scheduled_jobs) = kamelot::init_slot_sets(platform, false);
+    let preemption = platform.get_platform_config().config.scheduler_besteffort_preemption;
+    let (mut slot_sets, besteffort_scheduled_jobs) = kamelot::init_slot_sets(platform, preemption);
+    let mut preempted_jobs: Vec<i64> = Vec::new();
     info!("Slotset map: {:?}", slot_sets.keys().collect::<Vec<&Box<str>>>());
 
     // Schedule each queue
-    let grouped_queues: Vec<Vec<Queue>> = Queue::get_all_grouped_by_priority(&platform.session()).expect("Failed to get queues from database");
+    let grouped_queues: Vec<Vec<Queue>> = platform.get_queues_grouped_by_priority();
     for queues in grouped_queues {
+        if platform.is_cancelled() {
+            info!("Scheduling cancelled, the remaining queues are not scheduled");
+            break;
+        }
         let active_queues = queues
             .iter()
             .filter(|q| q.state.to_lowercase() == "active")
@@ -31,33 +43,103 @@ pub fn queues_schedule(platform: &mut Platform) -> Vec<Job> {
         info!("Slotset map: {:?}", slot_sets.keys().collect::<Vec<&Box<str>>>());
 
 
-        // Insert scheduled besteffort jobs if queues = ['besteffort'].
-        if active_queues.len() == 1 && active_queues[0] == "besteffort" {
+        // Insert scheduled besteffort jobs if queues = ['besteffort'], unless they are already inserted for the preemption.
+        if kamelot::is_besteffort_only(&active_queues) && !preemption {
             kamelot::add_already_scheduled_jobs_to_slot_set(&mut slot_sets, &mut *platform, true, false);
         }
 
-        // Schedule jobs
-        kamelot::internal_schedule_cycle(&mut *platform, &mut slot_sets, &active_queues);
+        // Schedule jobs, preempting the besteffort jobs not already preempted by a higher priority queue
+        let besteffort_jobs = besteffort_scheduled_jobs
+            .iter()
+            .filter(|job| !preempted_jobs.contains(&job.id))
+            .cloned()
+            .collect::<Vec<Job>>();
+        let (stats, preempted) = kamelot::internal_schedule_cycle_with_preemption(&mut *platform, &mut slot_sets, &active_queues, &besteffort_jobs);
+        debug!("Scheduling stats of queue(s) {:?}: {:?}", active_queues, stats);
+        preempted_jobs.extend(preempted);
 
         for queue in active_queues {
             // TODO: Manage waiting reservation jobs with the `handle_waiting_reservation_jobs` behavior:
             //   https://github.com/oar-team/oar3/blob/e6b6e7e59eb751cc2e7388d6c2fb7f94a3ac8c6e/oar/kao/queues_sched.py#L421-L512
 
             // Check new AR jobs
-            check_reservation_jobs(platform, &mut slot_sets, &queue)
+            for outcome in check_reservation_jobs(platform, &mut slot_sets, &queue) {
+                match (&outcome, outcome.message()) {
+                    (ReservationOutcome::Delayed { delay, .. }, _) => info!("Reservation job {} scheduled {}s later", outcome.job_id(), delay),
+                    (_, Some(message)) => info!("Reservation job {} not scheduled: {}", outcome.job_id(), message),
+                    (_, None) => info!("Reservation job {} scheduled", outcome.job_id()),
+                }
+            }
+        }
+    }
+    (besteffort_scheduled_jobs, preempted_jobs)
+}
+
+/// Outcome of the scheduling of a waiting advance reservation job by [`check_reservation_jobs`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReservationOutcome {
+    /// The reservation is scheduled, and its assignment saved.
+    Scheduled { job_id: i64 },
+    /// The requested window being occupied, the reservation is scheduled `delay` seconds later, within its `max_delay` job type.
+    Delayed { job_id: i64, delay: i64 },
+    /// The reservation ended before now: the job is set to error.
+    Expired { job_id: i64 },
+    /// The reservation ends after the scheduling horizon: the job is left waiting, to be checked again by the next cycles.
+    AfterHorizon { job_id: i64, horizon: i64 },
+    /// The requested resources are not available from the reservation start time: the job is set to error.
+    InsufficientResources { job_id: i64, start_time: i64 },
+    /// The reservation exceeds a quotas rule: the job is set to error.
+    QuotasExceeded { job_id: i64, reason: Box<str>, rule: QuotasKey, limit: i64 },
+    /// The reservation time range can't be split into the slot set: the job is set to error.
+    InvalidTimeRange { job_id: i64 },
+}
+
+impl ReservationOutcome {
+    pub fn job_id(&self) -> i64 {
+        match self {
+            ReservationOutcome::Scheduled { job_id }
+            | ReservationOutcome::Delayed { job_id, .. }
+            | ReservationOutcome::Expired { job_id }
+            | ReservationOutcome::AfterHorizon { job_id, .. }
+            | ReservationOutcome::InsufficientResources { job_id, .. }
+            | ReservationOutcome::QuotasExceeded { job_id, .. }
+            | ReservationOutcome::InvalidTimeRange { job_id } => *job_id,
+        }
+    }
+    /// Message saved as the job message, explaining why the reservation is not scheduled, or telling the delay of a delayed reservation.
+    /// `None` if it is scheduled at its requested start time.
+    pub fn message(&self) -> Option<String> {
+        match self {
+            ReservationOutcome::Scheduled { .. } => None,
+            ReservationOutcome::Delayed { delay, .. } => Some(format!("This AR is delayed by {}s: the requested window is occupied", delay)),
+            ReservationOutcome::Expired { .. } => Some("Reservation expired and couldn't be started.".to_string()),
+            ReservationOutcome::AfterHorizon { horizon, .. } => {
+                Some(format!("This AR cannot be scheduled yet: requested window after scheduling horizon {}", horizon))
+            }
+            ReservationOutcome::InsufficientResources { start_time, .. } => {
+                Some(format!("This AR cannot run: insufficient resources at {}", start_time))
+            }
+            ReservationOutcome::QuotasExceeded { reason, rule, limit, .. } => Some(format!(
+                "This AR cannot run: quotas exceeded ({} for rule ({}, {}, {}, {}), limit {})",
+                reason, rule.0, rule.1, rule.2, rule.3, limit
+            )),
+            ReservationOutcome::InvalidTimeRange { .. } => Some("This AR cannot run: invalid reservation time range".to_string()),
         }
     }
-    besteffort_scheduled_jobs
 }
 
-fn check_reservation_jobs(platform: &mut Platform, slot_sets: &mut HashMap<Box<str>, SlotSet>, queue: &String) {
+/// Schedules the waiting advance reservation jobs of `queue` at their requested start time.
+/// If the requested resources are not available or the quotas are exceeded, a job with the type `max_delay=N` slides to a later window
+/// (see [`scheduling::find_reservation_window`]), without sliding beyond the horizon.
+/// Returns the outcome of each job, the reason of a job not being scheduled being also saved as its message.
+pub(crate) fn check_reservation_jobs(platform: &mut Platform, slot_sets: &mut HashMap<Box<str>, SlotSet>, queue: &String) -> Vec<ReservationOutcome> {
     let platform_config = platform.get_platform_config();
-    let job_security_time = platform_config.config.scheduler_job_security_time;
     let now = platform.get_now();
 
     let jobs: IndexMap<i64, Job> = platform.get_waiting_to_schedule_ar_jobs(queue.clone());
+    let mut outcomes = Vec::with_capacity(jobs.len());
     if jobs.is_empty() {
-        return;
+        return outcomes;
     }
 
     // Process each job for reservation
@@ -68,9 +150,11 @@ fn check_reservation_jobs(platform: &mut Platform, slot_sets: &mut HashMap<Box<s
 
         // Check if reservation is too old
         let mut start_time = job.advance_reservation_begin.unwrap();
-        let end_time = start_time + moldable.walltime - 1;
-        if now > start_time + moldable.walltime {
-            set_job_resa_not_scheduled(&platform, &job, "Reservation expired and couldn't be started.");
+        let end_time = Job::compute_end(start_time, moldable.walltime);
+        if now > end_time {
+            let outcome = ReservationOutcome::Expired { job_id: job.id };
+            set_job_resa_not_scheduled(&platform, &job, &outcome.message().unwrap());
+            outcomes.push(outcome);
             continue;
         } else if start_time < now {
             start_time = now;
@@ -79,54 +163,71 @@ fn check_reservation_jobs(platform: &mut Platform, slot_sets: &mut HashMap<Box<s
         let ss_name = job.slot_set_name();
         let slot_set = slot_sets.get_mut(&*ss_name).expect("SlotSet not found");
 
-        let effective_end = end_time - job_security_time;
-        let (left_slot_id, right_slot_id) = match slot_set.get_encompassing_range(start_time, effective_end, None) {
-            Some((s1, s2)) => (s1.id(), s2.id()),
-            None => {
-                // Skipping, reservation might be after max_time.
+        let job_security_time = slot_set.get_platform_config().config.job_security_time(&job.queue);
+        let effective_end = moldable.effective_end(job.advance_reservation_begin.unwrap(), job_security_time);
+        let (delay, proc_set) = match scheduling::find_reservation_window(slot_set, &job, start_time, end_time, effective_end) {
+            ReservationWindow::Found { delay, resources } => (delay, resources),
+            ReservationWindow::AfterHorizon { horizon } => {
+                // The reservation ends after the scheduling horizon: it is checked again by the next cycles
+                let outcome = ReservationOutcome::AfterHorizon { job_id: job.id, horizon };
                 warn!("Job {} cannot be scheduled: no slots available for the requested time range.", job.id);
+                set_job_resa_message(&platform, &job, &outcome.message().unwrap());
+                outcomes.push(outcome);
                 continue;
             }
-        };
-
-        // Time-sharing and placeholder
-        let empty: Box<str> = "".into();
-        let (ts_user_name, ts_job_name) = job.time_sharing.as_ref().map_or((None, None), |_| {
-            (Some(job.user.as_ref().unwrap_or(&empty)), Some(job.name.as_ref().unwrap_or(&empty)))
-        });
-        let available_resources = slot_set.intersect_slots_intervals(left_slot_id, right_slot_id, ts_user_name, ts_job_name, &job.placeholder);
-
-        let res = slot_set
-            .get_platform_config()
-            .resource_set
-            .hierarchy
-            .request(&available_resources, &moldable.requests);
-
-        if let Some(proc_set) = res {
-            if slot_set.get_platform_config().quotas_config.enabled && !job.no_quotas {
-                let slots = slot_set.iter().between(left_slot_id, right_slot_id);
-                if let Some((_msg, _rule, _limit)) = quotas::check_slots_quotas(slots, &job, start_time, end_time, proc_set.core_count()) {
-                    set_job_resa_scheduled(&platform, &job, Some("This AR cannot run: quotas exceeded"));
-                    continue;
-                }
+            ReservationWindow::InsufficientResources => {
+                let outcome = ReservationOutcome::InsufficientResources { job_id: job.id, start_time };
+                set_job_resa_scheduled(&platform, &job, outcome.message().as_deref());
+                outcomes.push(outcome);
+                continue;
+            }
+            ReservationWindow::QuotasExceeded { reason, rule, limit } => {
+                let outcome = ReservationOutcome::QuotasExceeded { job_id: job.id, reason, rule, limit };
+                set_job_resa_scheduled(&platform, &job, outcome.message().as_deref());
+                outcomes.push(outcome);
+                continue;
             }
+        };
+        let (start_time, end_time) = (start_time + delay, end_time + delay);
 
-            job.assignment = Some(JobAssignment::new(start_time, end_time, proc_set, 0));
-            slot_set.split_slots_for_job_and_update_resources(&job, true, true, None);
-            set_job_resa_scheduled(&platform, &job, None);
-            assigned_jobs.insert(job.id, job);
-        } else {
-            set_job_resa_scheduled(&platform, &job, Some("This AR cannot run: not enough resources"));
+        // Reservation times are untrusted: an invalid split rejects the reservation instead of crashing the cycle
+        // Once the maximum number of slots is reached, the reservation is inserted without splitting the slots
+        if let Err(e) = slot_set.try_split_slots_for_range(start_time, end_time, None)
+            && !matches!(e, SplitError::MaxSlotsReached(_))
+        {
+            warn!("Job {} cannot be scheduled: {}", job.id, e);
+            let outcome = ReservationOutcome::InvalidTimeRange { job_id: job.id };
+            set_job_resa_scheduled(&platform, &job, outcome.message().as_deref());
+            outcomes.push(outcome);
             continue;
         }
+        job.assignment = Some(JobAssignment::new(start_time, end_time, proc_set, 0));
+        slot_set.split_slots_for_job_and_update_resources(&job, true, true, None);
+        if delay > 0 {
+            let outcome = ReservationOutcome::Delayed { job_id: job.id, delay };
+            set_job_resa_start_time(&platform, &job, start_time);
+            set_job_resa_state(&platform, &job, JobState::ToAckReservation, outcome.message().as_deref(), true);
+            outcomes.push(outcome);
+        } else {
+            set_job_resa_scheduled(&platform, &job, None);
+            outcomes.push(ReservationOutcome::Scheduled { job_id: job.id });
+        }
+        assigned_jobs.insert(job.id, job);
     }
     if !assigned_jobs.is_empty() {
         debug!("Check reservations: save assignments");
         platform.save_assignments(assigned_jobs);
     }
+    outcomes
 }
 
+/// Returns the resources requested by the first moldable of the reservation `job` that are available
+/// in all the slots from `left_slot_id` to `right_slot_id`, or `None` if they are not available.
 fn set_job_resa_state(platform: &Platform, job: &Job, state: JobState, message: Option<&str>, scheduled: bool) {
+    if platform.is_dry_run() {
+        info!("Dry run: reservation job {} would be set to {} ({})", job.id, state.as_str(), message.unwrap_or("no message"));
+        return;
+    }
     job.set_state(&platform.session(), state).expect("Unable to set job state");
     if let Some(message) = message {
         job.set_message(&platform.session(), message).expect("Unable to set job message");
@@ -136,6 +237,23 @@ fn set_job_resa_state(platform: &Platform, job: &Job, state: JobState, message:
             .expect("Unable to set job reservation state");
     }
 }
+/// Moves the start time of the delayed reservation `job` to its new `start_time`.
+fn set_job_resa_start_time(platform: &Platform, job: &Job, start_time: i64) {
+    if platform.is_dry_run() {
+        info!("Dry run: reservation job {} would start at {}", job.id, start_time);
+        return;
+    }
+    job.assign_moldable_and_set_start_time(&platform.session(), job.moldables[0].id, start_time)
+        .expect("Unable to set job start time");
+}
+/// Only sets the job message, the job being left waiting.
+fn set_job_resa_message(platform: &Platform, job: &Job, message: &str) {
+    if platform.is_dry_run() {
+        info!("Dry run: reservation job {} message would be set to {}", job.id, message);
+        return;
+    }
+    job.set_message(&platform.session(), message).expect("Unable to set job message");
+}
 fn set_job_resa_scheduled(platform: &Platform, job: &Job, error: Option<&str>) {
     if let Some(error) = error {
         set_job_resa_state(platform, job, JobState::ToError, Some(error), true);
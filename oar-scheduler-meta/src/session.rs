@@ -0,0 +1,69 @@
+use indexmap::IndexMap;
+use oar_scheduler_core::model::configuration::Configuration;
+use oar_scheduler_core::model::job::{Job, ProcSet};
+use oar_scheduler_core::platform::ResourceSet;
+use oar_scheduler_db::model::accounting::Accounting;
+use oar_scheduler_db::model::jobs::{JobDatabaseRequests, JobReservation, JobState};
+use oar_scheduler_db::model::queues::Queue;
+use oar_scheduler_db::Session;
+use std::collections::HashMap;
+
+/// Data read by the meta scheduler: the database [`Session`], or an in-memory session in tests.
+/// Writes always go through the database session returned by [`MetaSession::database`],
+/// so a session without database can only be used by a [`crate::platform::Platform`] in dry-run mode.
+pub trait MetaSession {
+    fn get_now(&self) -> i64;
+    fn get_resource_set(&mut self, config: &Configuration) -> ResourceSet;
+    /// Queues grouped by priority, highest priority first.
+    fn get_queues_grouped_by_priority(&self) -> Vec<Vec<Queue>>;
+    fn get_jobs(&self, queues: Option<Vec<String>>, reservation: Option<JobReservation>, states: Option<Vec<JobState>>) -> IndexMap<i64, Job>;
+    fn get_gantt_jobs(
+        &self,
+        queues: Option<Vec<String>>,
+        reservation: Option<JobReservation>,
+        states: Option<Vec<JobState>>,
+        max_start_time: Option<i64>,
+    ) -> Vec<Job>;
+    /// Begin, end and resources of the running jobs, by job id.
+    fn get_running_jobs_windows(&self) -> HashMap<i64, (i64, i64, ProcSet)>;
+    fn get_sum_accounting_by_user(&self, queues: &[String], window_start: i64, window_stop: i64) -> (HashMap<String, f64>, HashMap<String, f64>);
+    /// The database session used to modify the database, `None` if the session is not backed by a database.
+    fn database(&self) -> Option<&Session>;
+    fn database_mut(&mut self) -> Option<&mut Session>;
+}
+
+impl MetaSession for Session {
+    fn get_now(&self) -> i64 {
+        Session::get_now(self).expect("Failed to fetch the current time from the database")
+    }
+    fn get_resource_set(&mut self, config: &Configuration) -> ResourceSet {
+        Session::get_resource_set(self, config).expect("Failed to get the resources from the database")
+    }
+    fn get_queues_grouped_by_priority(&self) -> Vec<Vec<Queue>> {
+        Queue::get_all_grouped_by_priority(self).expect("Failed to get queues from database")
+    }
+    fn get_jobs(&self, queues: Option<Vec<String>>, reservation: Option<JobReservation>, states: Option<Vec<JobState>>) -> IndexMap<i64, Job> {
+        Job::get_jobs(self, queues, reservation, states).unwrap()
+    }
+    fn get_gantt_jobs(
+        &self,
+        queues: Option<Vec<String>>,
+        reservation: Option<JobReservation>,
+        states: Option<Vec<JobState>>,
+        max_start_time: Option<i64>,
+    ) -> Vec<Job> {
+        Job::get_gantt_jobs(self, queues, reservation, states, max_start_time).unwrap()
+    }
+    fn get_running_jobs_windows(&self) -> HashMap<i64, (i64, i64, ProcSet)> {
+        Job::get_running_jobs_windows(self).unwrap()
+    }
+    fn get_sum_accounting_by_user(&self, queues: &[String], window_start: i64, window_stop: i64) -> (HashMap<String, f64>, HashMap<String, f64>) {
+        Accounting::get_sum_by_user(self, queues, window_start, window_stop).expect("Unable to get the accounting by user")
+    }
+    fn database(&self) -> Option<&Session> {
+        Some(self)
+    }
+    fn database_mut(&mut self) -> Option<&mut Session> {
+        Some(self)
+    }
+}
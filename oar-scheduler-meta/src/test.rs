@@ -11,6 +11,16 @@ mod queues_test;
 mod quotas_test;
 #[cfg(test)]
 mod resources_test;
+#[cfg(test)]
+mod properties_test;
+#[cfg(test)]
+mod accounting_test;
+#[cfg(test)]
+mod reservations_test;
+#[cfg(test)]
+mod session_test;
+#[cfg(test)]
+mod mock_session_test;
 
 #[cfg(test)]
 fn setup_for_tests(use_sqlite_memory: bool) -> (Session, Configuration) {
@@ -36,7 +46,7 @@ fn setup_for_tests(use_sqlite_memory: bool) -> (Session, Configuration) {
 
     // Create schema
     if use_sqlite_memory {
-        session.create_schema();
+        session.create_schema().unwrap();
     }
 
     (session, config)
@@ -0,0 +1,42 @@
+use crate::platform::Platform;
+use crate::test::setup_for_tests;
+use oar_scheduler_core::platform::PlatformTrait;
+use oar_scheduler_db::model::accounting::Accounting;
+use std::collections::HashMap;
+
+#[test]
+fn test_users_usage_from_accounting() {
+    let (session, mut config) = setup_for_tests(true);
+    config.scheduler_fairsharing_window_size = Some(1000);
+    let platform = Platform::from_database(session, config);
+    let now = platform.get_now();
+
+    let accounting = |window_start: i64, user: &str, queue_name: &str, consumption_type: &str, consumption: i64| Accounting {
+        window_start,
+        window_stop: window_start + 99,
+        user: user.to_string(),
+        project: "project1".to_string(),
+        queue_name: queue_name.to_string(),
+        consumption_type: consumption_type.to_string(),
+        consumption,
+    };
+    for entry in [
+        accounting(now - 500, "user1", "default", "USED", 100),
+        accounting(now - 300, "user1", "default", "USED", 50),
+        accounting(now - 300, "user1", "default", "ASKED", 200),
+        accounting(now - 300, "user2", "default", "USED", 10),
+        // Other queue
+        accounting(now - 300, "user2", "besteffort", "USED", 1000),
+        // Out of the fairsharing window
+        accounting(now - 2000, "user2", "default", "USED", 1000),
+    ] {
+        entry.insert(platform.session()).unwrap();
+    }
+
+    let (asked, used) = Accounting::get_sum_by_user(platform.session(), &["default".to_string()], now - 1000, now).unwrap();
+    assert_eq!(asked, HashMap::from([("user1".to_string(), 200.0)]));
+    assert_eq!(used, HashMap::from([("user1".to_string(), 150.0), ("user2".to_string(), 10.0)]));
+
+    let users_usage = platform.get_users_usage(&["default".to_string()]);
+    assert_eq!(users_usage, HashMap::from([("user1".into(), 150.0), ("user2".into(), 10.0)]));
+}
@@ -2,10 +2,18 @@ use crate::meta_schedule::meta_schedule;
 use crate::platform::Platform;
 use crate::test::setup_for_tests;
 use log::info;
-use oar_scheduler_core::model::job::{PlaceholderType, TimeSharingType};
-use oar_scheduler_core::platform::{Job, PlatformTrait};
-use oar_scheduler_db::model::jobs::{JobDatabaseRequests, JobReservation, NewJob};
+use oar_scheduler_core::model::job::{DependencyType, JobAssignment, PlaceholderType, TimeSharingType};
+use oar_scheduler_core::model::configuration::Configuration;
+use oar_scheduler_core::platform::{Job, PlatformTrait, ProcSet, ProcSetCoresOp};
+use oar_scheduler_core::scheduler::kamelot;
+use oar_scheduler_core::scheduler::kamelot::CancellationToken;
+use oar_scheduler_db::model::job_dependencies::NewJobDependency;
+use oar_scheduler_db::model::jobs::{JobDatabaseRequests, JobReservation, JobState, NewJob};
+use oar_scheduler_db::model::moldable::MoldableDatabaseRequests;
 use oar_scheduler_db::model::queues::Queue;
+use oar_scheduler_db::model::walltime_change::WalltimeChange;
+use oar_scheduler_db::model::{gantt, SqlEnum};
+use oar_scheduler_db::Session;
 use oar_scheduler_db::model::resources::{NewResource, NewResourceColumn, ResourceLabelValue};
 use std::collections::HashMap;
 
@@ -73,6 +81,29 @@ fn insert_jobs_for_tests(platform: &Platform) {
     assert!(j1 > 0 && j2 > 0 && j3 > 0 && j4 > 0 && j5 > 0);
 }
 
+/// Inserts two resources without hierarchy labels and the `default` queue.
+fn insert_resources_and_queue_for_tests(session: &Session, config: &mut Configuration) {
+    config.hierarchy_labels = Some("resource_id,network_address".to_string());
+    for network_address in ["100.64.0.1", "100.64.0.2"] {
+        NewResource {
+            network_address: network_address.to_string(),
+            r#type: "default".to_string(),
+            state: "Alive".to_string(),
+            labels: indexmap::indexmap! {},
+        }
+            .insert(session)
+            .expect("Failed to insert test resource");
+    }
+    Queue {
+        queue_name: "default".to_string(),
+        priority: 2,
+        scheduler_policy: "kamelot".to_string(),
+        state: "Active".to_string(),
+    }
+        .insert(session)
+        .unwrap();
+}
+
 /// Test to try a complete integration with postgres.
 #[test]
 // #[ignore]
@@ -80,7 +111,7 @@ fn test_insert_job_and_queues() {
     let (session, mut config) = setup_for_tests(true); // Sqlite
     //let (session, mut config) = setup_for_tests(false); // Pg
 
-    session.reset();
+    session.reset().unwrap();
 
     config.hierarchy_labels = Some("resource_id,network_address,switch,core,cpu,host,mem".to_string());
 
@@ -209,7 +240,7 @@ fn test_insert_job_and_queues() {
 fn test_insert_and_retrieve_job() {
     let (session, config) = setup_for_tests(true); // Sqlite
     //let (session, config) = setup_for_tests(false); // Pg
-    session.reset();
+    session.reset().unwrap();
     let platform = Platform::from_database(session, config);
     insert_jobs_for_tests(&platform);
 
@@ -306,3 +337,533 @@ fn test_insert_and_retrieve_job() {
     assert_eq!(req_4_2.level_nbs, Box::from([(Box::from("licence"), 20)]));
     assert_eq!(req_5.level_nbs, Box::from([(Box::from("nodes"), 3)]));
 }
+
+#[test]
+fn test_get_jobs_paged() {
+    let (session, config) = setup_for_tests(true); // Sqlite
+    session.reset().unwrap();
+    let platform = Platform::from_database(session, config);
+    insert_jobs_for_tests(&platform);
+
+    let all_jobs = Job::get_jobs(platform.session(), None, None, None).unwrap();
+    assert_eq!(all_jobs.len(), 5);
+
+    let mut paged_job_ids = Vec::new();
+    for offset in (0..6).step_by(2) {
+        let page = Job::get_jobs_paged(platform.session(), None, None, None, 2, offset).unwrap();
+        assert!(page.len() <= 2);
+        for (id, job) in page.iter() {
+            assert_eq!(job.moldables.len(), all_jobs[id].moldables.len());
+            assert_eq!(job.types, all_jobs[id].types);
+        }
+        paged_job_ids.extend(page.keys().cloned());
+    }
+    assert_eq!(paged_job_ids, all_jobs.keys().cloned().collect::<Vec<i64>>());
+
+    // Filters are applied before paging
+    let page = Job::get_jobs_paged(platform.session(), Some(vec!["default".to_string()]), None, None, 2, 2).unwrap();
+    let default_jobs = Job::get_jobs(platform.session(), Some(vec!["default".to_string()]), None, None).unwrap();
+    assert_eq!(page.keys().collect::<Vec<_>>(), default_jobs.keys().skip(2).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_set_state_job_state_logs() {
+    let (session, config) = setup_for_tests(true); // Sqlite
+    session.reset().unwrap();
+    let platform = Platform::from_database(session, config);
+    insert_jobs_for_tests(&platform);
+
+    let job = Job::get_jobs(platform.session(), Some(vec!["default".to_string()]), None, None).unwrap()[0].clone();
+    assert!(job.get_state_logs(platform.session()).unwrap().is_empty());
+
+    let before = platform.session().get_now().unwrap();
+    job.set_state(platform.session(), JobState::ToLaunch).unwrap();
+    job.set_state(platform.session(), JobState::Launching).unwrap();
+    let after = platform.session().get_now().unwrap();
+
+    let logs = job.get_state_logs(platform.session()).unwrap();
+    assert_eq!(logs.len(), 2);
+    assert_eq!(logs[0].job_state, "toLaunch");
+    assert_eq!(logs[1].job_state, "Launching");
+    assert!(logs[0].date_start >= before && logs[0].date_start <= after);
+    assert_eq!(logs[0].date_stop, logs[1].date_start);
+    assert!(logs[1].date_start <= after);
+    assert_eq!(logs[1].date_stop, 0);
+
+    // Setting the same state again is ignored and does not log anything
+    job.set_state(platform.session(), JobState::Launching).unwrap();
+    assert_eq!(job.get_state_logs(platform.session()).unwrap().len(), 2);
+
+    // The entry of a final state is closed right away
+    job.set_state(platform.session(), JobState::Error).unwrap();
+    let logs = job.get_state_logs(platform.session()).unwrap();
+    assert_eq!(logs.len(), 3);
+    assert_eq!(logs[2].job_state, "Error");
+    assert_eq!(logs[1].date_stop, logs[2].date_start);
+    assert_eq!(logs[2].date_stop, logs[2].date_start);
+}
+
+#[test]
+fn test_meta_schedule_dry_run() {
+    let (session, mut config) = setup_for_tests(true); // Sqlite
+    session.reset().unwrap();
+    insert_resources_and_queue_for_tests(&session, &mut config);
+    let mut platform = Platform::from_database(session, config).with_dry_run(true);
+    let job_id = NewJob {
+        user: Some("user1".to_string()),
+        queue_name: "default".to_string(),
+        res: vec![(60, vec![("resource_id=1".to_string(), "".to_string())])],
+        types: vec![],
+    }
+        .insert(platform.session())
+        .expect("insert job 1");
+
+    assert_eq!(meta_schedule(&mut platform), 0);
+
+    // The assignment is computed but not saved
+    let assignments = platform.get_dry_run_assignments();
+    assert_eq!(assignments.len(), 1);
+    assert_eq!(assignments[0].id, job_id);
+    assert_eq!(assignments[0].assignment.as_ref().unwrap().resources.core_count(), 1);
+
+    assert!(Job::get_gantt_jobs(platform.session(), None, None, None, None).unwrap().is_empty());
+    let job = &Job::get_jobs(platform.session(), None, None, None).unwrap()[&job_id];
+    assert_eq!(job.state, JobState::Waiting.as_str());
+    assert!(job.get_state_logs(platform.session()).unwrap().is_empty());
+}
+
+#[test]
+fn test_meta_schedule_cancelled() {
+    let (session, mut config) = setup_for_tests(true); // Sqlite
+    session.reset().unwrap();
+    insert_resources_and_queue_for_tests(&session, &mut config);
+    let cancellation_token = CancellationToken::new();
+    let mut platform = Platform::from_database(session, config).with_cancellation_token(cancellation_token.clone());
+    let job_id = NewJob {
+        user: Some("user1".to_string()),
+        queue_name: "default".to_string(),
+        res: vec![(60, vec![("resource_id=1".to_string(), "".to_string())])],
+        types: vec![],
+    }
+        .insert(platform.session())
+        .expect("insert job 1");
+
+    // Cancelled before the round: the job is left waiting for the next one
+    cancellation_token.cancel();
+    assert_eq!(meta_schedule(&mut platform), 0);
+    assert!(Job::get_gantt_jobs(platform.session(), None, None, None, None).unwrap().is_empty());
+    let job = &Job::get_jobs(platform.session(), None, None, None).unwrap()[&job_id];
+    assert_eq!(job.state, JobState::Waiting.as_str());
+}
+
+#[test]
+fn test_save_assignments_in_gantt() {
+    let (session, mut config) = setup_for_tests(true); // Sqlite
+    session.reset().unwrap();
+    insert_resources_and_queue_for_tests(&session, &mut config);
+    let mut platform = Platform::from_database(session, config);
+    let job_id = NewJob {
+        user: Some("user1".to_string()),
+        queue_name: "default".to_string(),
+        res: vec![(60, vec![("resource_id=2".to_string(), "".to_string())])],
+        types: vec![],
+    }
+        .insert(platform.session())
+        .expect("insert job 1");
+
+    kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+
+    let gantt_jobs = Job::get_gantt_jobs(platform.session(), None, None, None, None).unwrap();
+    assert_eq!(gantt_jobs.len(), 1);
+    let job = &gantt_jobs[0];
+    assert_eq!(job.id, job_id);
+    let assignment = job.assignment.as_ref().unwrap();
+    assert_eq!(assignment.begin, platform.get_now());
+    assert_eq!(assignment.end, platform.get_now() + 59);
+    assert_eq!(assignment.resources, ProcSet::from_iter(0..=1));
+    assert_eq!(platform.session().proc_set_to_resource_ids(&assignment.resources).unwrap(), vec![1, 2]);
+
+    // Saving again replaces the stale gantt rows of the moldable
+    let mut job = job.clone();
+    job.assignment.as_mut().unwrap().begin += 100;
+    job.assignment.as_mut().unwrap().resources = ProcSet::from_iter(1..=1);
+    platform.save_assignments(indexmap::indexmap! { job_id => job });
+    let gantt_jobs = Job::get_gantt_jobs(platform.session(), None, None, None, None).unwrap();
+    assert_eq!(gantt_jobs.len(), 1);
+    let assignment = gantt_jobs[0].assignment.as_ref().unwrap();
+    assert_eq!(assignment.begin, platform.get_now() + 100);
+    assert_eq!(assignment.resources, ProcSet::from_iter(1..=1));
+
+    // The assigned moldable of the waiting job is not set by the gantt: it has no assignment outside the gantt
+    assert!(Job::get_jobs(platform.session(), None, None, None).unwrap()[&job_id].assignment.is_none());
+}
+
+#[test]
+fn test_save_chosen_moldable() {
+    let (session, mut config) = setup_for_tests(true); // Sqlite
+    insert_resources_and_queue_for_tests(&session, &mut config);
+    let mut platform = Platform::from_database(session, config);
+    // The first moldable requests more resources than available: the second one is chosen
+    let job_id = NewJob {
+        user: Some("user1".to_string()),
+        queue_name: "default".to_string(),
+        res: vec![
+            (60, vec![("resource_id=3".to_string(), "".to_string())]),
+            (120, vec![("resource_id=1".to_string(), "".to_string())]),
+        ],
+        types: vec![],
+    }
+        .insert(platform.session())
+        .expect("insert job");
+    let moldables = Job::get_jobs(platform.session(), None, None, None).unwrap()[&job_id].moldables.clone();
+    assert_eq!(moldables.len(), 2);
+
+    kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+
+    // The gantt assignment is loaded from the moldable having a gantt prediction
+    let gantt_jobs = Job::get_gantt_jobs(platform.session(), None, None, None, None).unwrap();
+    assert_eq!(gantt_jobs.len(), 1);
+    let assignment = gantt_jobs[0].assignment.as_ref().unwrap();
+    assert_eq!(assignment.moldable_index, 1);
+    assert_eq!(assignment.moldable_id(&gantt_jobs[0].moldables), Some(moldables[1].id));
+    assert_eq!(assignment.end - assignment.begin + 1, 120);
+
+    // Saving the job with another moldable replaces the prediction of the previous one
+    let mut job = gantt_jobs[0].clone();
+    job.assignment.as_mut().unwrap().moldable_index = 0;
+    platform.save_assignments(indexmap::indexmap! { job_id => job });
+    let gantt_jobs = Job::get_gantt_jobs(platform.session(), None, None, None, None).unwrap();
+    assert_eq!(gantt_jobs.len(), 1);
+    assert_eq!(gantt_jobs[0].assignment.as_ref().unwrap().moldable_index, 0);
+}
+
+#[test]
+fn test_job_properties_excluded_resources() {
+    let (session, mut config) = setup_for_tests(true); // Sqlite
+    insert_resources_and_queue_for_tests(&session, &mut config);
+    let mut platform = Platform::from_database(session, config);
+    let new_job = NewJob {
+        user: Some("user1".to_string()),
+        queue_name: "default".to_string(),
+        res: vec![(60, vec![("resource_id=1".to_string(), "".to_string())])],
+        types: vec![],
+    };
+    let job_id = new_job.insert_with_properties(platform.session(), "network_address = '100.64.0.2'").unwrap();
+    let free_job_id = new_job.insert(platform.session()).unwrap();
+
+    // The resources not satisfying the job properties are excluded from its moldables
+    let jobs = Job::get_jobs(platform.session(), None, None, None).unwrap();
+    assert_eq!(jobs[&job_id].moldables[0].excluded_resources, ProcSet::from_iter(0..=0));
+    assert!(jobs[&free_job_id].moldables[0].excluded_resources.is_empty());
+
+    kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+    let gantt_jobs = Job::get_gantt_jobs(platform.session(), None, None, None, None).unwrap();
+    let job = gantt_jobs.iter().find(|job| job.id == job_id).unwrap();
+    assert_eq!(job.assignment.as_ref().unwrap().resources, ProcSet::from_iter(1..=1));
+}
+
+#[test]
+fn test_load_jobs_query_count() {
+    let (session, mut config) = setup_for_tests(true); // Sqlite
+    insert_resources_and_queue_for_tests(&session, &mut config);
+    let mut platform = Platform::from_database(session, config);
+    let insert_job = |platform: &Platform| {
+        NewJob {
+            user: Some("user1".to_string()),
+            queue_name: "default".to_string(),
+            res: vec![(60, vec![("resource_id=1".to_string(), "".to_string())])],
+            types: vec![],
+        }
+            .insert(platform.session())
+            .expect("insert job");
+    };
+    // Returns the number of jobs loaded from the gantt, and the number of queries it took
+    let load_gantt_jobs = |platform: &Platform| {
+        let query_count = platform.session().query_count();
+        let jobs = Job::get_gantt_jobs(platform.session(), None, None, None, None).unwrap();
+        assert!(jobs.iter().all(|job| job.assignment.as_ref().is_some_and(|assignment| assignment.resources.core_count() == 1)));
+        (jobs.len(), platform.session().query_count() - query_count)
+    };
+
+    insert_job(&platform);
+    kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+    let (job_count, single_job_query_count) = load_gantt_jobs(&platform);
+    assert_eq!(job_count, 1);
+
+    for _ in 0..5 {
+        insert_job(&platform);
+    }
+    kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+    // The assignments are loaded for all the jobs at once
+    assert_eq!(load_gantt_jobs(&platform), (6, single_job_query_count));
+}
+
+#[test]
+fn test_get_jobs_submitted_between() {
+    let (session, mut config) = setup_for_tests(true); // Sqlite
+    insert_resources_and_queue_for_tests(&session, &mut config);
+    let job_ids = [100, 200, 300, 400]
+        .map(|submission_time| {
+            NewJob {
+                user: Some("user1".to_string()),
+                queue_name: "default".to_string(),
+                res: vec![(60, vec![("resource_id=1".to_string(), "".to_string())])],
+                types: vec![],
+            }
+                .insert_submitted_at(&session, submission_time)
+                .expect("insert job")
+        });
+
+    let jobs = Job::get_jobs_submitted_between(&session, None, None, None, Some(200), Some(300)).unwrap();
+    assert_eq!(jobs.keys().copied().collect::<Vec<i64>>(), vec![job_ids[1], job_ids[2]]);
+    assert!(jobs.values().all(|job| (200..=300).contains(&job.submission_time)));
+
+    let jobs = Job::get_jobs_submitted_between(&session, None, None, None, Some(250), None).unwrap();
+    assert_eq!(jobs.keys().copied().collect::<Vec<i64>>(), vec![job_ids[2], job_ids[3]]);
+    let jobs = Job::get_jobs_submitted_between(&session, None, None, None, None, Some(100)).unwrap();
+    assert_eq!(jobs.keys().copied().collect::<Vec<i64>>(), vec![job_ids[0]]);
+    // Without bounds, all the jobs are returned
+    assert_eq!(Job::get_jobs_submitted_between(&session, None, None, None, None, None).unwrap().len(), 4);
+}
+
+#[test]
+fn test_running_jobs_assigned_resources() {
+    let (session, mut config) = setup_for_tests(true); // Sqlite
+    insert_resources_and_queue_for_tests(&session, &mut config);
+    let platform = Platform::from_database(session, config);
+    let insert_job = |platform: &Platform| {
+        NewJob {
+            user: Some("user1".to_string()),
+            queue_name: "default".to_string(),
+            res: vec![(60, vec![("resource_id=1".to_string(), "".to_string())])],
+            types: vec![],
+        }
+            .insert(platform.session())
+            .expect("insert job")
+    };
+    // Running job on the second resource, only known from the assigned_resources table, and suspended job on the first one
+    let running_job_id = insert_job(&platform);
+    let suspended_job_id = insert_job(&platform);
+    let jobs = Job::get_jobs(platform.session(), None, None, None).unwrap();
+    for (job_id, state, resource_index) in [(running_job_id, JobState::Running, 1), (suspended_job_id, JobState::Suspended, 0)] {
+        let job = &jobs[&job_id];
+        let moldable = &job.moldables[0];
+        job.assign_moldable_and_set_start_time(platform.session(), moldable.id, platform.get_now() - 10).unwrap();
+        moldable
+            .save_resources_as_assigned_resources(platform.session(), &ProcSet::from_iter([resource_index]))
+            .unwrap();
+        job.set_state(platform.session(), state).unwrap();
+    }
+
+    let now = platform.get_now();
+    let running_jobs = Job::get_running_jobs_windows(platform.session()).unwrap();
+    assert_eq!(running_jobs, HashMap::from([(running_job_id, (now - 10, now + 49, ProcSet::from_iter([1])))]));
+    assert_eq!(platform.running_jobs_windows(&[]), running_jobs);
+
+    // The running job is busy until the end of its walltime
+    let (slot_sets, _) = kamelot::init_slot_sets(&platform, true);
+    let slot_set = slot_sets.get("default").unwrap();
+    assert_eq!(slot_set.available_resources_at(now), Some(&ProcSet::from_iter([0])));
+    assert_eq!(slot_set.available_resources_at(now + 49), Some(&ProcSet::from_iter([0])));
+    assert_eq!(slot_set.available_resources_at(now + 50), Some(&ProcSet::from_iter([0..=1])));
+}
+
+#[test]
+fn test_walltime_change_requests() {
+    let (session, mut config) = setup_for_tests(true); // Sqlite
+    insert_resources_and_queue_for_tests(&session, &mut config);
+    config.walltime_change_enabled = true;
+    let mut platform = Platform::from_database(session, config);
+    let now = platform.get_now();
+    let session = platform.session();
+    let insert_job = || {
+        NewJob {
+            user: Some("user1".to_string()),
+            queue_name: "default".to_string(),
+            res: vec![(60, vec![("resource_id=1".to_string(), "".to_string())])],
+            types: vec![],
+        }
+            .insert(session)
+            .expect("insert job")
+    };
+    // Jobs running until now + 49 on each resource, and a reservation beginning at now + 100 on the first resource
+    let (blocked_job_id, extended_job_id, reservation_id) = (insert_job(), insert_job(), insert_job());
+    let jobs = Job::get_jobs(session, None, None, None).unwrap();
+    for (job_id, resource_index) in [(blocked_job_id, 0), (extended_job_id, 1)] {
+        let job = &jobs[&job_id];
+        let moldable = &job.moldables[0];
+        job.assign_moldable_and_set_start_time(session, moldable.id, now - 10).unwrap();
+        moldable.save_resources_as_assigned_resources(session, &ProcSet::from_iter([resource_index])).unwrap();
+        job.set_state(session, JobState::Running).unwrap();
+    }
+    let mut reservation = jobs[&reservation_id].clone();
+    reservation.set_resa_state(session, JobReservation::Scheduled.as_str()).unwrap();
+    reservation.assignment = Some(JobAssignment::new(now + 100, now + 159, ProcSet::from_iter([0]), 0));
+    gantt::save_jobs_assignments_in_gantt(session, indexmap::indexmap! { reservation_id => reservation }).unwrap();
+    for job_id in [blocked_job_id, extended_job_id] {
+        WalltimeChange { job_id, pending: 100, granted: 0 }.insert(session).unwrap();
+    }
+
+    meta_schedule(&mut platform);
+
+    // The extension colliding with the reservation is left pending
+    let pending = WalltimeChange::get_pending(platform.session()).unwrap();
+    assert_eq!(pending, vec![WalltimeChange { job_id: blocked_job_id, pending: 100, granted: 0 }]);
+    let jobs = Job::get_jobs(platform.session(), None, None, None).unwrap();
+    assert_eq!(jobs[&blocked_job_id].moldables[0].walltime, 60);
+    assert_eq!(jobs[&extended_job_id].moldables[0].walltime, 160);
+    let gantt_jobs = Job::get_gantt_jobs(platform.session(), None, None, None, None).unwrap();
+    let extended_job = gantt_jobs.iter().find(|job| job.id == extended_job_id).unwrap();
+    assert_eq!(extended_job.end(), Some(now + 149));
+}
+
+#[test]
+fn test_session_reference_now() {
+    let (mut session, _config) = setup_for_tests(true); // Sqlite
+    let local_now = session.get_now_local();
+    assert!((session.get_now().unwrap() - local_now).abs() <= 2);
+
+    session.set_reference_now(1_000);
+    assert_eq!(session.get_now().unwrap(), 1_000);
+    assert_eq!(session.get_now().unwrap(), 1_000);
+    // The local clock is not affected
+    assert!(session.get_now_local() >= local_now);
+
+    session.clear_reference_now();
+    assert!((session.get_now().unwrap() - session.get_now_local()).abs() <= 2);
+}
+
+#[test]
+fn test_dependency_types() {
+    let (session, mut config) = setup_for_tests(true); // Sqlite
+    session.reset().unwrap();
+    insert_resources_and_queue_for_tests(&session, &mut config);
+    NewJobDependency::add_type_column(&session).expect("add the dependency type column");
+    let mut platform = Platform::from_database(session, config);
+    let new_job = |platform: &Platform| {
+        NewJob {
+            user: Some("user1".to_string()),
+            queue_name: "default".to_string(),
+            res: vec![(60, vec![("resource_id=1".to_string(), "".to_string())])],
+            types: vec![],
+        }
+        .insert(platform.session())
+        .expect("insert job")
+    };
+    let errored = new_job(&platform);
+    let succeeded = new_job(&platform);
+    let jobs = Job::get_jobs(platform.session(), None, None, None).unwrap();
+    jobs[&errored].set_state(platform.session(), JobState::Error).unwrap();
+    jobs[&succeeded].set_state(platform.session(), JobState::Terminated).unwrap();
+
+    // Jobs depending on the errored job, then on the succeeded job, with the ok and anyway dependency types
+    let dependencies = [
+        (errored, DependencyType::Ok),
+        (errored, DependencyType::Anyway),
+        (succeeded, DependencyType::Ok),
+        (succeeded, DependencyType::Anyway),
+    ];
+    let dependent_jobs = dependencies
+        .iter()
+        .map(|(job_id_required, dependency_type)| {
+            let job_id = new_job(&platform);
+            NewJobDependency { job_id, job_id_required: *job_id_required, dependency_type: *dependency_type }
+                .insert(platform.session())
+                .expect("insert dependency");
+            job_id
+        })
+        .collect::<Vec<i64>>();
+
+    let jobs = Job::get_jobs(platform.session(), None, None, None).unwrap();
+    assert_eq!(jobs[&dependent_jobs[0]].dependencies, vec![(errored, "Error".into(), None, DependencyType::Ok)]);
+    assert_eq!(jobs[&dependent_jobs[3]].dependencies, vec![(succeeded, "Terminated".into(), None, DependencyType::Anyway)]);
+
+    // Only the ok dependency on the errored job blocks its job
+    let stats = kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+    assert_eq!(stats.scheduled, 3);
+    let gantt_jobs = Job::get_gantt_jobs(platform.session(), None, None, None, None).unwrap();
+    let mut scheduled = gantt_jobs.iter().map(|job| job.id).collect::<Vec<i64>>();
+    scheduled.sort();
+    assert_eq!(scheduled, dependent_jobs[1..].to_vec());
+}
+
+#[test]
+fn test_dependency_types_without_type_column() {
+    // The OAR 3.0.0 schema has no job_dependency_type column: all dependencies are of type legacy.
+    let (session, mut config) = setup_for_tests(true); // Sqlite
+    session.reset().unwrap();
+    insert_resources_and_queue_for_tests(&session, &mut config);
+    let mut platform = Platform::from_database(session, config);
+    let new_job = |platform: &Platform| {
+        NewJob {
+            user: Some("user1".to_string()),
+            queue_name: "default".to_string(),
+            res: vec![(60, vec![("resource_id=1".to_string(), "".to_string())])],
+            types: vec![],
+        }
+        .insert(platform.session())
+        .expect("insert job")
+    };
+    let errored = new_job(&platform);
+    let succeeded = new_job(&platform);
+    let jobs = Job::get_jobs(platform.session(), None, None, None).unwrap();
+    jobs[&errored].set_state(platform.session(), JobState::Error).unwrap();
+    jobs[&succeeded].set_state(platform.session(), JobState::Terminated).unwrap();
+
+    let dependent_jobs = [errored, succeeded]
+        .iter()
+        .map(|job_id_required| {
+            let job_id = new_job(&platform);
+            NewJobDependency { job_id, job_id_required: *job_id_required, dependency_type: DependencyType::Ok }
+                .insert(platform.session())
+                .expect("insert dependency");
+            job_id
+        })
+        .collect::<Vec<i64>>();
+
+    let jobs = Job::get_jobs(platform.session(), None, None, None).unwrap();
+    assert_eq!(jobs[&dependent_jobs[0]].dependencies, vec![(errored, "Error".into(), None, DependencyType::Legacy)]);
+    assert_eq!(jobs[&dependent_jobs[1]].dependencies, vec![(succeeded, "Terminated".into(), None, DependencyType::Legacy)]);
+
+    // As in OAR 3.0.0, the errored parent does not block its dependent job
+    let stats = kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+    assert_eq!(stats.scheduled, 2);
+    let gantt_jobs = Job::get_gantt_jobs(platform.session(), None, None, None, None).unwrap();
+    let mut scheduled = gantt_jobs.iter().map(|job| job.id).collect::<Vec<i64>>();
+    scheduled.sort();
+    assert_eq!(scheduled, dependent_jobs);
+}
+
+#[test]
+fn test_default_walltime_of_loaded_jobs() {
+    let (session, mut config) = setup_for_tests(true); // Sqlite
+    session.reset().unwrap();
+    insert_resources_and_queue_for_tests(&session, &mut config);
+    // Set after the session creation: the platform gives the session the walltime estimator of its configuration
+    config.default_job_walltime = 3600;
+    config.scheduler_queue_default_walltimes = Some("default:1800".to_string());
+    let mut platform = Platform::from_database(session, config);
+    let new_job = |platform: &Platform, walltime: i64| {
+        NewJob {
+            user: Some("user1".to_string()),
+            queue_name: "default".to_string(),
+            res: vec![(walltime, vec![("resource_id=1".to_string(), "".to_string())])],
+            types: vec![],
+        }
+        .insert(platform.session())
+        .expect("insert job")
+    };
+    let without_walltime = new_job(&platform, 0);
+    let with_walltime = new_job(&platform, 60);
+
+    // The moldables without walltime get the default walltime of their queue
+    let jobs = Job::get_jobs(platform.session(), None, None, None).unwrap();
+    assert_eq!(jobs[&without_walltime].moldables[0].walltime, 1800);
+    assert_eq!(jobs[&with_walltime].moldables[0].walltime, 60);
+
+    kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+    let gantt_jobs = Job::get_gantt_jobs(platform.session(), None, None, None, None).unwrap();
+    let job = gantt_jobs.iter().find(|job| job.id == without_walltime).expect("job without walltime scheduled");
+    let assignment = job.assignment.as_ref().unwrap();
+    assert_eq!(assignment.end - assignment.begin + 1, 1800);
+}
@@ -0,0 +1,138 @@
+use crate::meta_schedule::meta_schedule;
+use oar_scheduler_core::scheduler::kamelot;
+use crate::mock_session::MockSession;
+use crate::platform::Platform;
+use oar_scheduler_core::model::configuration::Configuration;
+use oar_scheduler_core::model::job::{JobAssignment, JobBuilder, Moldable, ProcSet};
+use oar_scheduler_core::platform::{PlatformTrait, ResourceSet};
+use oar_scheduler_core::scheduler::hierarchy::{Hierarchy, HierarchyRequest, HierarchyRequests};
+use std::collections::HashMap;
+
+/// Two nodes of two resources each.
+fn mock_resource_set() -> ResourceSet {
+    let nodes = Box::new([ProcSet::from_iter([0..=1]), ProcSet::from_iter([2..=3])]);
+    ResourceSet {
+        nb_resources_not_dead: 4,
+        nb_resources_default_not_dead: 4,
+        weighted_resources_not_dead: 4,
+        weighted_resources_default_not_dead: 4,
+        default_resources: ProcSet::from_iter([0..=3]),
+        hierarchy: Hierarchy::new()
+            .add_unit_partition("resource_id".into())
+            .add_partition("network_address".into(), nodes),
+        ..ResourceSet::empty()
+    }
+}
+
+fn nodes_moldable(id: i64, walltime: i64, nodes: u32) -> Moldable {
+    let request = HierarchyRequest::new(ProcSet::from_iter([0..=3]), vec![("network_address".into(), nodes)]);
+    Moldable::new(id, walltime, HierarchyRequests::from_requests(vec![request]))
+}
+
+#[test]
+fn test_meta_schedule_with_mock_session() {
+    let now = 1000;
+    let running_job = JobBuilder::new(1)
+        .queue("default".into())
+        .moldable(nodes_moldable(1, 500, 1))
+        .assign(JobAssignment::new(now - 100, now + 399, ProcSet::from_iter([0..=1]), 0))
+        .state("Running".into())
+        .build();
+    let session = MockSession::new(now, mock_resource_set())
+        .queue("default", 2, "kamelot")
+        .job(running_job)
+        .job(JobBuilder::new(2).queue("default".into()).moldable(nodes_moldable(2, 300, 1)).build())
+        .job(JobBuilder::new(3).queue("default".into()).moldable(nodes_moldable(3, 300, 2)).build());
+
+    let mut platform = Platform::from_mock(session, Configuration::default());
+    assert!(platform.is_dry_run());
+    assert_eq!(platform.get_now(), now);
+    let running_jobs = platform.running_jobs_windows(&platform.get_scheduled_jobs());
+    assert_eq!(running_jobs, HashMap::from([(1, (now - 100, now + 399, ProcSet::from_iter([0..=1])))]));
+
+    assert_eq!(meta_schedule(&mut platform), 0);
+
+    let assignments = platform.get_dry_run_assignments();
+    assert_eq!(assignments.len(), 2);
+    // Job 2 starts now on the free node, job 3 needs both nodes and waits for the running job to end.
+    let job_2 = assignments.iter().find(|job| job.id == 2).unwrap().assignment.as_ref().unwrap();
+    assert_eq!(job_2.begin, now);
+    assert_eq!(job_2.resources, ProcSet::from_iter([2..=3]));
+    let job_3 = assignments.iter().find(|job| job.id == 3).unwrap().assignment.as_ref().unwrap();
+    assert!(job_3.begin >= now + 400);
+    assert_eq!(job_3.resources, ProcSet::from_iter([0..=3]));
+}
+
+#[test]
+fn test_jobs_to_launch_with_queue_job_security_time() {
+    let now = 1000;
+    let session = MockSession::new(now, mock_resource_set())
+        .queue("default", 2, "kamelot")
+        .queue("long", 2, "kamelot")
+        .job(JobBuilder::new(1).queue("default".into()).moldable(nodes_moldable(1, 30, 2)).build())
+        .job(JobBuilder::new(2).queue("default".into()).moldable(nodes_moldable(2, 300, 1)).build())
+        .job(JobBuilder::new(3).queue("long".into()).moldable(nodes_moldable(3, 300, 1)).build());
+    // The queue default overrides the global security time of 60 seconds with 10 seconds.
+    let config = Configuration {
+        scheduler_job_security_time: 60,
+        scheduler_besteffort_kill_duration_before_reservation: 0,
+        scheduler_queue_job_security_times: Some("default:10".to_string()),
+        ..Configuration::default()
+    };
+
+    let mut platform = Platform::from_mock(session, config);
+    kamelot::schedule_cycle(&mut platform, &vec!["default".to_string(), "long".to_string()]);
+
+    // Job 1 starts now on both nodes, jobs 2 and 3 start 30 seconds later.
+    let assignments = platform.get_dry_run_assignments();
+    let begin = |job_id: i64| assignments.iter().find(|job| job.id == job_id).unwrap().begin().unwrap();
+    assert_eq!(begin(1), now);
+    assert_eq!(begin(2), now + 30);
+    assert_eq!(begin(3), now + 30);
+
+    // Job 2 begins after the 10 seconds security time of its queue, job 3 before the global 60 seconds.
+    let mut jobs_to_launch = platform.get_gantt_jobs_to_launch_with_security_time().into_iter().map(|job| job.id).collect::<Vec<i64>>();
+    jobs_to_launch.sort_unstable();
+    assert_eq!(jobs_to_launch, vec![1, 3]);
+}
+
+#[test]
+fn test_meta_schedule_besteffort_preemption() {
+    let now = 1000;
+    // Besteffort job running on both nodes beyond the scheduling horizon
+    let besteffort_job = |begin: i64| {
+        JobBuilder::new(1)
+            .queue("besteffort".into())
+            .add_type_key("besteffort".into())
+            .moldable(nodes_moldable(1, i64::MAX / 2, 2))
+            .assign(JobAssignment::new(begin, i64::MAX / 2, ProcSet::from_iter([0..=3]), 0))
+            .state("Running".into())
+            .build()
+    };
+    let session = |besteffort_begin: i64| {
+        MockSession::new(now, mock_resource_set())
+            .queue("default", 2, "kamelot")
+            .queue("besteffort", 0, "kamelot")
+            .job(besteffort_job(besteffort_begin))
+            .job(JobBuilder::new(2).queue("default".into()).moldable(nodes_moldable(2, 300, 1)).build())
+    };
+    let config = || Configuration {
+        scheduler_besteffort_preemption: true,
+        scheduler_besteffort_min_runtime_before_kill: 60,
+        ..Configuration::default()
+    };
+    let job_2_begin = |platform: &Platform| {
+        let assignments = platform.get_dry_run_assignments();
+        assignments.iter().find(|job| job.id == 2).and_then(|job| job.begin())
+    };
+
+    // The besteffort job is preempted: job 2 starts now, and waits for the besteffort job to be killed to be launched.
+    let mut platform = Platform::from_mock(session(now - 100), config());
+    assert_eq!(meta_schedule(&mut platform), 2);
+    assert_eq!(job_2_begin(&platform), Some(now));
+
+    // The besteffort job has been running for less than a minute: it is protected, and job 2 can't be scheduled.
+    let mut platform = Platform::from_mock(session(now - 10), config());
+    assert_eq!(meta_schedule(&mut platform), 0);
+    assert_eq!(job_2_begin(&platform), None);
+}
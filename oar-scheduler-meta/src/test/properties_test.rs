@@ -0,0 +1,133 @@
+use crate::platform::Platform;
+use crate::test::resources_test::create_resources_hierarchy;
+use crate::test::setup_for_tests;
+use oar_scheduler_core::platform::{Job, ProcSet};
+use oar_scheduler_db::model::jobs::{JobDatabaseRequests, NewJob};
+use oar_scheduler_db::model::resources::{NewResource, ResourceLabelValue};
+use oar_scheduler_db::properties;
+use std::collections::HashMap;
+
+fn labels(host: &str, cpu: i64) -> HashMap<Box<str>, ResourceLabelValue> {
+    HashMap::from([
+        ("host".into(), ResourceLabelValue::Varchar(host.to_string())),
+        ("cpu".into(), ResourceLabelValue::Integer(cpu)),
+    ])
+}
+
+#[test]
+fn properties_equality_test() {
+    assert!(properties::matches(&labels("node1", 2), "host = 'node1'"));
+    assert!(!properties::matches(&labels("node2", 2), "host = 'node1'"));
+    assert!(properties::matches(&labels("node1", 2), "cpu=2"));
+    assert!(properties::matches(&labels("node1", 2), "cpu = '2'"));
+    assert!(properties::matches(&labels("node1", 2), "cpu IN (1, 2)"));
+    assert!(!properties::matches(&labels("node1", 3), "cpu in (1, 2)"));
+    // Unknown labels never match
+    assert!(!properties::matches(&labels("node1", 2), "mem = 2"));
+    assert!(properties::matches(&labels("node1", 2), ""));
+}
+
+#[test]
+fn properties_inequality_test() {
+    assert!(properties::matches(&labels("node2", 2), "host != 'node1'"));
+    assert!(!properties::matches(&labels("node1", 2), "host != 'node1'"));
+    assert!(properties::matches(&labels("node1", 3), "cpu <> 2"));
+    assert!(!properties::matches(&labels("node1", 2), "mem != 2"));
+}
+
+#[test]
+fn properties_conjunction_test() {
+    assert!(properties::matches(&labels("node1", 2), "host = 'node1' AND cpu = 2"));
+    assert!(!properties::matches(&labels("node1", 3), "host = 'node1' and cpu = 2"));
+    assert!(properties::matches(&labels("node2", 3), "host = 'node1' AND cpu = 2 OR host = 'node2'"));
+    assert!(!properties::matches(&labels("node2", 3), "host = 'node1' AND (cpu = 2 OR host = 'node2')"));
+    // Malformed or unsupported expressions are ignored, matching all the resources
+    assert!(properties::matches(&labels("node1", 2), "host = 'node1' AND"));
+    assert!(properties::matches(&labels("node2", 2), "host = 'node1"));
+    assert!(properties::matches(&labels("node2", 2), "host IS NULL"));
+}
+
+#[test]
+fn properties_comparison_test() {
+    assert!(properties::matches(&labels("node1", 2), "cpu < 3"));
+    assert!(!properties::matches(&labels("node1", 3), "cpu < 3"));
+    assert!(properties::matches(&labels("node1", 3), "cpu <= 3"));
+    assert!(properties::matches(&labels("node1", 4), "cpu > 3"));
+    assert!(!properties::matches(&labels("node1", 3), "cpu>3"));
+    assert!(properties::matches(&labels("node1", 3), "cpu>=3"));
+    // Integers are compared numerically, even when quoted
+    assert!(properties::matches(&labels("node1", 10), "cpu > '9'"));
+    assert!(properties::matches(&labels("node2", 2), "host > 'node1'"));
+    assert!(!properties::matches(&labels("node1", 2), "mem < 2"));
+}
+
+#[test]
+fn properties_like_and_not_test() {
+    assert!(properties::matches(&labels("node12", 2), "host LIKE 'node%'"));
+    assert!(properties::matches(&labels("node1", 2), "host like 'node_'"));
+    assert!(!properties::matches(&labels("node12", 2), "host LIKE 'node_'"));
+    assert!(!properties::matches(&labels("gpu1", 2), "host LIKE 'node%'"));
+    assert!(properties::matches(&labels("gpu1", 2), "host NOT LIKE 'node%'"));
+    assert!(properties::matches(&labels("node1", 3), "cpu NOT IN (1, 2)"));
+    assert!(!properties::matches(&labels("node1", 2), "NOT cpu = 2"));
+    assert!(properties::matches(&labels("node1", 2), "NOT (cpu = 1 OR host = 'node2') AND host = 'node1'"));
+}
+
+#[test]
+fn properties_filter_moldable_resources_test() {
+    let (session, mut config) = setup_for_tests(true);
+    create_resources_hierarchy(&session, &mut config);
+    for (core, cpu, host) in [(1, 1, "node1"), (2, 2, "node1"), (3, 1, "node2")] {
+        NewResource {
+            network_address: "100.64.0.1".to_string(),
+            r#type: "default".to_string(),
+            state: "Alive".to_string(),
+            labels: indexmap::indexmap! {
+                "switch".to_string() => ResourceLabelValue::Varchar("switch1".to_string()),
+                "core".to_string() => ResourceLabelValue::Integer(core),
+                "cpu".to_string() => ResourceLabelValue::Integer(cpu),
+                "host".to_string() => ResourceLabelValue::Varchar(host.to_string()),
+                "mem".to_string() => ResourceLabelValue::Integer(1),
+            },
+        }
+            .insert(&session)
+            .expect("Failed to insert test resource");
+    }
+    let platform = Platform::from_database(session, config);
+    let session = platform.session();
+
+    // Resource ids allowed by each properties, None for all the resources
+    let groups = [
+        ("", None),
+        ("host = 'node1'", Some(vec![1, 2])),
+        ("cpu = 1 AND host != 'node1'", Some(vec![3])),
+        ("cpu >= 2 OR host LIKE '%2'", Some(vec![2, 3])),
+        // Unsupported operators are evaluated by the database
+        ("host IS NULL", Some(vec![])),
+        ("host IS NOT NULL AND cpu = 1", Some(vec![1, 3])),
+        // Invalid properties allow no resource
+        ("no_such_column = 1", Some(vec![])),
+        ("host = 'node1' AND", Some(vec![])),
+    ];
+    for (property, _resource_ids) in groups.iter() {
+        NewJob {
+            user: Some("user1".to_string()),
+            queue_name: "default".to_string(),
+            res: vec![(60, vec![("resource_id=1".to_string(), property.to_string())])],
+            types: vec![],
+        }
+            .insert(session)
+            .expect("Failed to insert test job");
+    }
+
+    let jobs = Job::get_jobs(session, Some(vec!["default".to_string()]), None, None).unwrap();
+    assert_eq!(jobs.len(), groups.len());
+    for (job, (property, resource_ids)) in jobs.values().zip(groups) {
+        let filter = &job.moldables[0].requests.0[0].filter;
+        let expected = match resource_ids {
+            None => !ProcSet::new(),
+            Some(resource_ids) => resource_ids.into_iter().map(|id| session.resource_id_to_resource_index(id).unwrap()).collect::<ProcSet>(),
+        };
+        assert_eq!(filter, &expected, "properties '{}'", property);
+    }
+}
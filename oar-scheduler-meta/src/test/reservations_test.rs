@@ -0,0 +1,170 @@
+use crate::platform::Platform;
+use crate::queues_schedule::{check_reservation_jobs, ReservationOutcome};
+use crate::test::resources_test::create_resources_hierarchy;
+use crate::test::setup_for_tests;
+use oar_scheduler_core::model::job::{JobAssignment, JobBuilder};
+use oar_scheduler_core::platform::{Job, PlatformTrait};
+use oar_scheduler_core::scheduler::slotset::SlotSet;
+use oar_scheduler_db::model::jobs::{JobDatabaseRequests, JobReservation, NewJob};
+use oar_scheduler_db::model::resources::{NewResource, ResourceLabelValue};
+use oar_scheduler_db::model::SqlEnum;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Creates a platform with a node of two cores.
+fn setup_platform_with_two_cores() -> Platform {
+    let (session, mut config) = setup_for_tests(true);
+    create_resources_hierarchy(&session, &mut config);
+    for core in 1..=2 {
+        NewResource {
+            network_address: "100.64.0.1".to_string(),
+            r#type: "default".to_string(),
+            state: "Alive".to_string(),
+            labels: indexmap::indexmap! {
+                "switch".to_string() => ResourceLabelValue::Varchar("switch1".to_string()),
+                "core".to_string() => ResourceLabelValue::Integer(core),
+                "cpu".to_string() => ResourceLabelValue::Integer(1),
+                "host".to_string() => ResourceLabelValue::Varchar("node1".to_string()),
+                "mem".to_string() => ResourceLabelValue::Integer(1),
+            },
+        }
+            .insert(&session)
+            .expect("Failed to insert test resource");
+    }
+    Platform::from_database(session, config)
+}
+
+#[test]
+fn test_reservation_outcomes() {
+    let mut platform = setup_platform_with_two_cores();
+    let now = platform.get_now();
+
+    // (requested resources, start time): more resources than available, schedulable, and after the horizon
+    let reservations = [("resource_id=3", now + 100), ("resource_id=2", now + 100), ("resource_id=1", now + 2000)];
+    for (resources, _start_time) in reservations {
+        NewJob {
+            user: Some("user1".to_string()),
+            queue_name: "default".to_string(),
+            res: vec![(60, vec![(resources.to_string(), "".to_string())])],
+            types: vec![],
+        }
+            .insert(platform.session())
+            .expect("Failed to insert test job");
+    }
+    let jobs = Job::get_jobs(platform.session(), Some(vec!["default".to_string()]), None, None).unwrap();
+    for (job, (_resources, start_time)) in jobs.values().zip(reservations) {
+        job.set_resa_state(platform.session(), JobReservation::ToSchedule.as_str()).unwrap();
+        job.assign_moldable_and_set_start_time(platform.session(), 0, start_time).unwrap();
+    }
+    let job_ids = jobs.keys().copied().collect::<Vec<i64>>();
+
+    let slot_set = SlotSet::from_platform_config(Rc::clone(platform.get_platform_config()), now, now + 1000);
+    let mut slot_sets = HashMap::from([("default".into(), slot_set)]);
+    let outcomes = check_reservation_jobs(&mut platform, &mut slot_sets, &"default".to_string());
+    assert_eq!(
+        outcomes,
+        vec![
+            ReservationOutcome::InsufficientResources { job_id: job_ids[0], start_time: now + 100 },
+            ReservationOutcome::Scheduled { job_id: job_ids[1] },
+            ReservationOutcome::AfterHorizon { job_id: job_ids[2], horizon: now + 1000 },
+        ]
+    );
+
+    // The reasons are saved as distinct job messages, the reservation after the horizon being left waiting
+    let jobs = Job::get_jobs(platform.session(), Some(vec!["default".to_string()]), None, None).unwrap();
+    let insufficient = &jobs[&job_ids[0]];
+    let last_state = |job: &Job| job.get_state_logs(platform.session()).unwrap().last().map(|log| log.job_state.clone());
+    assert_eq!(last_state(insufficient).as_deref(), Some("toError"));
+    assert_eq!(insufficient.message, format!("This AR cannot run: insufficient resources at {}", now + 100));
+    assert_eq!(outcomes[1].message(), None);
+    let after_horizon = &jobs[&job_ids[2]];
+    assert_ne!(last_state(after_horizon).as_deref(), Some("toError"));
+    assert_eq!(after_horizon.message, outcomes[2].message().unwrap());
+    assert!(after_horizon.message.contains("after scheduling horizon"));
+}
+
+/// Checks a reservation of the two cores at now + 100 with the job type `max_delay=<max_delay>`,
+/// the two cores being busy until now + 699.
+fn check_blocked_reservation(max_delay: i64) -> (Platform, i64, Vec<ReservationOutcome>) {
+    let mut platform = setup_platform_with_two_cores();
+    let now = platform.get_now();
+    let job_id = NewJob {
+        user: Some("user1".to_string()),
+        queue_name: "default".to_string(),
+        res: vec![(60, vec![("resource_id=2".to_string(), "".to_string())])],
+        types: vec![format!("max_delay={}", max_delay)],
+    }
+        .insert(platform.session())
+        .expect("Failed to insert test job");
+    let job = &Job::get_jobs(platform.session(), None, None, None).unwrap()[&job_id];
+    job.set_resa_state(platform.session(), JobReservation::ToSchedule.as_str()).unwrap();
+    job.assign_moldable_and_set_start_time(platform.session(), 0, now + 100).unwrap();
+
+    let mut slot_set = SlotSet::from_platform_config(Rc::clone(platform.get_platform_config()), now, now + 2000);
+    let all_resources = platform.get_platform_config().resource_set.default_resources.clone();
+    let busy_job = JobBuilder::new(0).assign(JobAssignment::new(now, now + 699, all_resources, 0)).build();
+    slot_set.split_slots_for_job_and_update_resources(&busy_job, true, true, None);
+    let mut slot_sets = HashMap::from([("default".into(), slot_set)]);
+    let outcomes = check_reservation_jobs(&mut platform, &mut slot_sets, &"default".to_string());
+    (platform, job_id, outcomes)
+}
+
+#[test]
+fn test_reservation_slides_within_max_delay() {
+    let (platform, job_id, outcomes) = check_blocked_reservation(900);
+    assert_eq!(outcomes, vec![ReservationOutcome::Delayed { job_id, delay: 600 }]);
+
+    let now = platform.get_now();
+    let gantt_jobs = Job::get_gantt_jobs(platform.session(), None, None, None, None).unwrap();
+    let assignment = gantt_jobs[0].assignment.as_ref().unwrap();
+    assert_eq!((assignment.begin, assignment.end), (now + 700, now + 759));
+    let job = &Job::get_jobs(platform.session(), None, None, None).unwrap()[&job_id];
+    assert_eq!(job.message, "This AR is delayed by 600s: the requested window is occupied");
+    // The start time of the job is moved along with its gantt prediction, e.g., for the cycles checking the reservation again
+    job.set_resa_state(platform.session(), JobReservation::ToSchedule.as_str()).unwrap();
+    let job = &Job::get_jobs(platform.session(), None, None, None).unwrap()[&job_id];
+    assert_eq!(job.advance_reservation_begin, Some(now + 700));
+}
+
+#[test]
+fn test_reservation_exceeding_max_delay_is_rejected() {
+    let (platform, job_id, outcomes) = check_blocked_reservation(300);
+    let now = platform.get_now();
+    assert_eq!(outcomes, vec![ReservationOutcome::InsufficientResources { job_id, start_time: now + 100 }]);
+    assert!(Job::get_gantt_jobs(platform.session(), None, None, None, None).unwrap().is_empty());
+}
+
+#[test]
+fn test_reservation_expires_after_its_inclusive_end() {
+    let mut platform = setup_platform_with_two_cores();
+    let now = platform.get_now();
+
+    // Reservations of 120 seconds ending at now - 1 (expired), at now (not expired yet), and at now + 89
+    let start_times = [now - 120, now - 119, now - 30];
+    for _ in start_times {
+        NewJob {
+            user: Some("user1".to_string()),
+            queue_name: "default".to_string(),
+            res: vec![(120, vec![("resource_id=1".to_string(), "".to_string())])],
+            types: vec![],
+        }
+            .insert(platform.session())
+            .expect("Failed to insert test job");
+    }
+    let jobs = Job::get_jobs(platform.session(), Some(vec!["default".to_string()]), None, None).unwrap();
+    for (job, start_time) in jobs.values().zip(start_times) {
+        job.set_resa_state(platform.session(), JobReservation::ToSchedule.as_str()).unwrap();
+        job.assign_moldable_and_set_start_time(platform.session(), 0, start_time).unwrap();
+    }
+    let job_ids = jobs.keys().copied().collect::<Vec<i64>>();
+
+    let slot_set = SlotSet::from_platform_config(Rc::clone(platform.get_platform_config()), now, now + 1000);
+    let mut slot_sets = HashMap::from([("default".into(), slot_set)]);
+    let outcomes = check_reservation_jobs(&mut platform, &mut slot_sets, &"default".to_string());
+    assert_eq!(outcomes[0], ReservationOutcome::Expired { job_id: job_ids[0] });
+    assert!(!matches!(outcomes[1], ReservationOutcome::Expired { .. }));
+    assert_eq!(outcomes[2], ReservationOutcome::Scheduled { job_id: job_ids[2] });
+    // The started reservation is scheduled from now, its walltime being reduced when launched
+    let scheduled = platform.get_scheduled_jobs().into_iter().find(|job| job.id == job_ids[2]).unwrap();
+    assert_eq!(scheduled.begin(), Some(now));
+}
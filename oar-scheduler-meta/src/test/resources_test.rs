@@ -1,10 +1,14 @@
 use crate::platform::Platform;
 use crate::test::setup_for_tests;
 use oar_scheduler_core::model::configuration::Configuration;
-use oar_scheduler_core::platform::{PlatformTrait, ProcSet};
+use oar_scheduler_core::platform::{PlatformTrait, ProcSet, ResourceSet};
 use oar_scheduler_core::scheduler::hierarchy::{HierarchyRequest, HierarchyRequests};
-use oar_scheduler_db::model::resources::{NewResource, NewResourceColumn, ResourceLabelValue};
-use oar_scheduler_db::Session;
+use oar_scheduler_core::scheduler::kamelot;
+use oar_scheduler_core::scheduler::slotset::SlotSet;
+use oar_scheduler_db::model::jobs::NewJob;
+use oar_scheduler_db::model::resources::{NewResource, NewResourceColumn, Resource, ResourceLabelValue};
+use oar_scheduler_db::{DbError, Session};
+use std::rc::Rc;
 
 pub fn create_resources_hierarchy(session: &Session, config: &mut Configuration) {
     NewResourceColumn {
@@ -142,3 +146,309 @@ fn create_resources_test() {
         );
     }
 }
+
+#[test]
+fn all_dead_resources_test() {
+    let (session, mut config) = setup_for_tests(true);
+
+    create_resources_hierarchy(&session, &mut config);
+
+    for core in 1..=2 {
+        NewResource {
+            network_address: "100.64.0.1".to_string(),
+            r#type: "default".to_string(),
+            state: "dead".to_string(),
+            labels: indexmap::indexmap! {
+                "switch".to_string() => ResourceLabelValue::Varchar("switch1".to_string()),
+                "core".to_string() => ResourceLabelValue::Integer(core),
+                "cpu".to_string() => ResourceLabelValue::Integer(1),
+                "host".to_string() => ResourceLabelValue::Varchar("node1".to_string()),
+                "mem".to_string() => ResourceLabelValue::Integer(1),
+            },
+        }
+            .insert(&session)
+            .expect("Failed to insert test resource");
+    }
+
+    NewJob {
+        user: Some("user1".to_string()),
+        queue_name: "default".to_string(),
+        res: vec![(60, vec![("resource_id=1".to_string(), "".to_string())])],
+        types: vec![],
+    }
+        .insert(&session)
+        .expect("Failed to insert test job");
+
+    let mut platform = Platform::from_database(session, config);
+    let resource_set = &platform.get_platform_config().resource_set;
+    assert_eq!(resource_set.nb_resources_not_dead, 0);
+    assert!(resource_set.default_resources.is_empty());
+
+    let stats = kamelot::schedule_cycle(&mut platform, &vec!["default".to_string()]);
+    assert_eq!(stats, kamelot::SchedulingStats::default());
+    assert!(platform.get_scheduled_jobs().is_empty());
+}
+
+#[test]
+fn resource_index_round_trip_test() {
+    let (session, mut config) = setup_for_tests(true);
+
+    create_resources_hierarchy(&session, &mut config);
+
+    for (core, host) in [(1, "node1"), (2, "node1"), (3, "node2")] {
+        NewResource {
+            network_address: "100.64.0.1".to_string(),
+            r#type: "default".to_string(),
+            state: "alive".to_string(),
+            labels: indexmap::indexmap! {
+                "switch".to_string() => ResourceLabelValue::Varchar("switch1".to_string()),
+                "core".to_string() => ResourceLabelValue::Integer(core),
+                "cpu".to_string() => ResourceLabelValue::Integer(1),
+                "host".to_string() => ResourceLabelValue::Varchar(host.to_string()),
+                "mem".to_string() => ResourceLabelValue::Integer(1),
+            },
+        }
+            .insert(&session)
+            .expect("Failed to insert test resource");
+    }
+
+    let platform = Platform::from_database(session, config);
+    let session = platform.session();
+    let default_resources = &platform.get_platform_config().resource_set.default_resources;
+
+    let resource_ids = session.proc_set_to_resource_ids(default_resources).unwrap();
+    let mut sorted_ids = resource_ids.clone();
+    sorted_ids.sort();
+    assert_eq!(sorted_ids, vec![1, 2, 3]);
+    for (resource_index, resource_id) in default_resources.iter().zip(resource_ids) {
+        assert_eq!(session.resource_index_to_resource_id(resource_index), Some(resource_id));
+        assert_eq!(session.resource_id_to_resource_index(resource_id), Some(resource_index));
+    }
+    assert_eq!(session.resource_index_to_resource_id(3), None);
+    assert!(session.proc_set_to_resource_ids(&ProcSet::new()).unwrap().is_empty());
+    // Indexes outside the resource set are an error
+    let error = session.proc_set_to_resource_ids(&ProcSet::from_iter([0..=3])).unwrap_err();
+    assert!(matches!(error, DbError::UnknownResourceIndex(3)), "Unexpected error {:?}", error);
+}
+
+#[test]
+fn resource_set_with_comparator_test() {
+    let (mut session, config) = setup_for_tests(true);
+
+    for node in ["node10", "node2", "node1"] {
+        NewResource {
+            network_address: node.to_string(),
+            r#type: "default".to_string(),
+            state: "alive".to_string(),
+            labels: indexmap::indexmap! {},
+        }
+            .insert(&session)
+            .expect("Failed to insert test resource");
+    }
+
+    // Natural ordering of the node names: node1, node2, node10
+    let node_number = |resource: &Resource| match resource.labels.get("network_address") {
+        Some(ResourceLabelValue::Varchar(name)) => name.trim_start_matches(|c: char| !c.is_ascii_digit()).parse::<u32>().unwrap_or(0),
+        _ => 0,
+    };
+    let resource_set = session.get_resource_set_with_comparator(&config, |a, b| node_number(a).cmp(&node_number(b))).unwrap();
+    assert_eq!(resource_set.default_resources, ProcSet::from_iter([0..=2]));
+    assert_eq!(session.proc_set_to_resource_ids(&resource_set.default_resources).unwrap(), vec![3, 2, 1]);
+    assert_eq!(session.resource_id_to_resource_index(1), Some(2));
+
+    // The SQL ordering is lexicographic: node1, node10, node2
+    session.get_resource_set(&config).unwrap();
+    assert_eq!(session.proc_set_to_resource_ids(&ProcSet::from_iter([0..=2])).unwrap(), vec![3, 1, 2]);
+}
+
+#[test]
+fn resource_weights_test() {
+    let (mut session, mut config) = setup_for_tests(true);
+
+    NewResourceColumn {
+        name: "cpuset_size".to_string(),
+        r#type: "Integer".to_string(),
+    }
+        .insert(&session)
+        .expect("Failed to insert test resource column");
+    for (node, cpuset_size) in [("node1", 4), ("node2", 16)] {
+        NewResource {
+            network_address: node.to_string(),
+            r#type: "default".to_string(),
+            state: "alive".to_string(),
+            labels: indexmap::indexmap! {
+                "cpuset_size".to_string() => ResourceLabelValue::Integer(cpuset_size),
+            },
+        }
+            .insert(&session)
+            .expect("Failed to insert test resource");
+    }
+
+    let resource_set = session.get_resource_set(&config).unwrap();
+    assert!(resource_set.core_weight_prefix_sums.is_empty());
+    assert_eq!(resource_set.weighted_resources_not_dead, 2);
+    assert_eq!(resource_set.weighted_core_count(&resource_set.default_resources), 2);
+
+    config.scheduler_resource_weight_label = Some("cpuset_size".to_string());
+    let resource_set = session.get_resource_set(&config).unwrap();
+    assert_eq!(resource_set.core_weight_prefix_sums, vec![0, 4, 20]);
+    assert_eq!(resource_set.weighted_resources_not_dead, 20);
+    assert_eq!(resource_set.weighted_core_count(&resource_set.default_resources), 20);
+    assert_eq!(resource_set.weighted_core_count(&ProcSet::from_iter([1])), 16);
+    // The weight column is not part of the hierarchy
+    assert!(!resource_set.hierarchy.has_partition(&Box::from("cpuset_size")));
+    assert!(resource_set.hierarchy.has_partition(&Box::from("network_address")));
+}
+
+#[test]
+fn resources_unknown_column_error_test() {
+    let (session, _config) = setup_for_tests(true);
+
+    let labels = vec![Box::from("resource_id")];
+    let error = Resource::get_all_sorted(&session, "no_such_column", &labels).err().expect("Ordering by an unknown column should fail");
+    assert!(matches!(error, DbError::Schema(_)), "Unexpected error {:?}", error);
+    assert!(error.to_string().starts_with("database schema error"));
+
+    let labels = vec![Box::from("resource_id")];
+    assert!(Resource::get_all_sorted(&session, "resource_id", &labels).unwrap().is_empty());
+}
+
+#[test]
+fn absent_resources_test() {
+    let (session, config) = setup_for_tests(true);
+    let now = session.get_now().unwrap();
+
+    // An alive resource, an absent resource coming back in one hour, and an absent resource without return time
+    for (node, state, available_upto) in [("node1", "alive", None), ("node2", "absent", Some(now + 3600)), ("node3", "absent", Some(0))] {
+        NewResource {
+            network_address: node.to_string(),
+            r#type: "default".to_string(),
+            state: state.to_string(),
+            labels: available_upto
+                .map(|time| indexmap::indexmap! { "available_upto".to_string() => ResourceLabelValue::Integer(time) })
+                .unwrap_or_default(),
+        }
+            .insert(&session)
+            .expect("Failed to insert test resource");
+    }
+
+    let platform = Platform::from_database(session, config);
+    let session = platform.session();
+    let resource_set = &platform.get_platform_config().resource_set;
+    let index = |resource_id: i32| session.resource_id_to_resource_index(resource_id).unwrap();
+    let (alive, absent_with_upto, absent_without_upto) = (index(1), index(2), index(3));
+
+    assert_eq!(resource_set.default_resources, ProcSet::from_iter([alive, absent_with_upto]));
+    assert!(!resource_set.default_resources.contains(absent_without_upto));
+    assert_eq!(resource_set.available_from, vec![(now + 3600, ProcSet::from_iter([absent_with_upto]))]);
+
+    // The absent resource is only available in the slots from its return time
+    let slot_set = SlotSet::from_platform_config(Rc::clone(platform.get_platform_config()), now, now + 7200);
+    let slots = slot_set.iter().map(|slot| (slot.begin(), slot.end(), slot.proc_set().clone())).collect::<Vec<_>>();
+    assert_eq!(
+        slots,
+        vec![
+            (now, now + 3599, ProcSet::from_iter([alive])),
+            (now + 3600, now + 7200, ProcSet::from_iter([alive, absent_with_upto])),
+        ]
+    );
+}
+
+#[test]
+fn resource_label_value_ordering_test() {
+    let varchar = |value: &str| ResourceLabelValue::Varchar(value.to_string());
+    // Digit runs are compared numerically, unlike the lexical string ordering
+    assert!(varchar("node2") < varchar("node10"));
+    assert!("node2" > "node10");
+    assert!(varchar("node2-core10") > varchar("node2-core9"));
+    assert!(varchar("node10") < varchar("nodea"));
+    assert!(varchar("node") < varchar("node1"));
+    // Equivalent numbers with leading zeros are ordered lexically
+    assert!(varchar("node02") < varchar("node2"));
+    assert!(ResourceLabelValue::Integer(-5) < ResourceLabelValue::Integer(3));
+    assert!(ResourceLabelValue::Integer(9) < varchar("10"));
+    assert!(ResourceLabelValue::Integer(2) < varchar("2"));
+
+    let mut values = vec![varchar("node10"), varchar("node1"), ResourceLabelValue::Integer(12), varchar("node2"), ResourceLabelValue::Integer(3)];
+    values.sort();
+    assert_eq!(values, vec![ResourceLabelValue::Integer(3), ResourceLabelValue::Integer(12), varchar("node1"), varchar("node2"), varchar("node10")]);
+}
+
+#[test]
+fn reload_resource_set_if_changed_test() {
+    let (mut session, mut config) = setup_for_tests(true);
+    create_resources_hierarchy(&session, &mut config);
+    let insert_resource = |session: &Session, core: i64| {
+        NewResource {
+            network_address: "100.64.0.1".to_string(),
+            r#type: "default".to_string(),
+            state: "alive".to_string(),
+            labels: indexmap::indexmap! {
+                "switch".to_string() => ResourceLabelValue::Varchar("switch1".to_string()),
+                "core".to_string() => ResourceLabelValue::Integer(core),
+                "cpu".to_string() => ResourceLabelValue::Integer(1),
+                "host".to_string() => ResourceLabelValue::Varchar("node1".to_string()),
+                "mem".to_string() => ResourceLabelValue::Integer(1),
+            },
+        }
+            .insert(session)
+            .expect("Failed to insert test resource");
+    };
+    insert_resource(&session, 1);
+    insert_resource(&session, 2);
+
+    // An unchanged resources table does not rebuild the resource set, and the resources are not loaded again
+    let resource_set = session.get_resource_set(&config).unwrap();
+    let query_count = session.query_count();
+    assert!(session.reload_resource_set_if_changed(&resource_set, &config).unwrap().is_none());
+    assert!(session.reload_resource_set_if_changed(&resource_set, &config).unwrap().is_none());
+    assert_eq!(session.query_count(), query_count + 2);
+
+    // Added resource
+    insert_resource(&session, 3);
+    let resource_set = session.reload_resource_set_if_changed(&resource_set, &config).unwrap().expect("A resource was added");
+    assert_eq!(resource_set.default_resources, ProcSet::from_iter(0..=2));
+    assert!(session.reload_resource_set_if_changed(&resource_set, &config).unwrap().is_none());
+
+    // Resource becoming Dead
+    Resource::set_state(&session, 2, "Dead").unwrap();
+    let resource_set = session.reload_resource_set_if_changed(&resource_set, &config).unwrap().expect("A resource state changed");
+    assert_eq!(resource_set.default_resources, ProcSet::from_iter([0, 2]));
+    assert!(session.reload_resource_set_if_changed(&resource_set, &config).unwrap().is_none());
+
+    // A resource set which is not the last one built is rebuilt
+    assert!(session.reload_resource_set_if_changed(&ResourceSet::empty(), &config).unwrap().is_some());
+}
+
+#[test]
+fn resource_partitions_in_label_values_order_test() {
+    let (mut session, mut config) = setup_for_tests(true);
+    create_resources_hierarchy(&session, &mut config);
+    // The resources of node10 get the lowest indexes
+    for (host, core) in [("node10", 1), ("node10", 2), ("node2", 1), ("node2", 2)] {
+        NewResource {
+            network_address: "100.64.0.1".to_string(),
+            r#type: "default".to_string(),
+            state: "Alive".to_string(),
+            labels: indexmap::indexmap! {
+                "switch".to_string() => ResourceLabelValue::Integer(1),
+                "core".to_string() => ResourceLabelValue::Integer(core),
+                "cpu".to_string() => ResourceLabelValue::Integer(1),
+                "host".to_string() => ResourceLabelValue::Varchar(host.to_string()),
+                "mem".to_string() => ResourceLabelValue::Integer(1),
+            },
+        }
+            .insert(&session)
+            .expect("Failed to insert test resource");
+    }
+
+    let resource_set = session.get_resource_set(&config).unwrap();
+    let hierarchy = &resource_set.hierarchy;
+    let (node10, node2) = (ProcSet::from_iter([0..=1]), ProcSet::from_iter([2..=3]));
+    // node2 is the first partition of the host level, and is preferred by the requests
+    assert_eq!(hierarchy.format_proc_set_as_labels(&node2, "host"), "node2");
+    assert_eq!(hierarchy.format_proc_set_as_labels(&node10, "host"), "node10");
+    assert_eq!(hierarchy.format_proc_set_as_labels(&ProcSet::from_iter([0..=2]), "host"), "node2(1/2),node10");
+    let request = HierarchyRequests::new_single(resource_set.default_resources.clone(), vec![("host".into(), 1)]);
+    assert_eq!(hierarchy.request(&resource_set.default_resources, &request), Some(node2));
+}
@@ -0,0 +1,79 @@
+use crate::test::setup_for_tests;
+use oar_scheduler_db::model::queues::Queue;
+use oar_scheduler_db::{DbError, Session};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_sqlite_sessions_concurrent_access() {
+    let (_, mut config) = setup_for_tests(true);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("oar.sqlite");
+    std::fs::File::create(&path).unwrap();
+    config.db_hostname = path.to_str().unwrap().to_string();
+
+    let writer = Session::new(&config);
+    writer.create_schema().unwrap();
+    let reader = Session::new(&config);
+    // The journal mode is set on connect: the write-ahead log file exists once written
+    assert!(dir.path().join("oar.sqlite-wal").exists());
+
+    // A session writes while the other reads, each with its own connection
+    let writer_thread = thread::spawn(move || {
+        for i in 0..50 {
+            Queue {
+                queue_name: format!("queue{}", i),
+                priority: i,
+                scheduler_policy: "kamelot".to_string(),
+                state: "Active".to_string(),
+            }
+            .insert(&writer)
+            .unwrap();
+        }
+    });
+    for _ in 0..50 {
+        Queue::get_all_ordered_by_priority(&reader).unwrap();
+    }
+    writer_thread.join().unwrap();
+    assert_eq!(Queue::get_all_ordered_by_priority(&reader).unwrap().len(), 50);
+}
+
+#[test]
+fn test_session_connect_retry() {
+    let (_, mut config) = setup_for_tests(true);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("oar.sqlite");
+    config.db_hostname = path.to_str().unwrap().to_string();
+    config.db_connect_attempts = 6;
+    config.db_connect_retry_delay = 50;
+
+    // The database file is only created after the first connection attempt failed, as by a delayed database startup
+    let delayed_startup = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        std::fs::File::create(&path).unwrap();
+    });
+    let session = Session::new(&config);
+    delayed_startup.join().unwrap();
+    session.create_schema().unwrap();
+    session.ensure_connected().unwrap();
+    assert!(Queue::get_all_ordered_by_priority(&session).unwrap().is_empty());
+}
+
+#[test]
+#[should_panic(expected = "Failed to connect to the database after 2 attempt(s)")]
+fn test_session_connect_retry_exhausted() {
+    let (_, mut config) = setup_for_tests(true);
+    let dir = tempfile::tempdir().unwrap();
+    config.db_hostname = dir.path().join("missing.sqlite").to_str().unwrap().to_string();
+    config.db_connect_attempts = 2;
+    config.db_connect_retry_delay = 10;
+    Session::new(&config);
+}
+
+#[test]
+fn test_session_errors_are_returned() {
+    let (session, _config) = setup_for_tests(true);
+    // The tables already exist: the error is returned instead of panicking
+    assert!(matches!(session.create_schema(), Err(DbError::Backend(_))));
+    assert!(session.get_now().is_ok());
+}
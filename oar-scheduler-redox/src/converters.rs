@@ -1,5 +1,8 @@
 use oar_scheduler_core::model::configuration::Configuration;
-use oar_scheduler_core::model::job::{Job, JobAssignment, Moldable, PlaceholderType, ProcSet, ProcSetCoresOp, TimeSharingType};
+use oar_scheduler_core::model::job::{
+    DependencyType, Job, JobAssignment, JobBuilder, JobDependency, Moldable, PlaceholderType, ProcSet, ProcSetCoresOp, TimeSharingType,
+};
+use oar_scheduler_core::model::walltime::WalltimeEstimator;
 use oar_scheduler_core::platform;
 use oar_scheduler_core::platform::{PlatformConfig, ResourceSet};
 use oar_scheduler_core::scheduler::hierarchy::{Hierarchy, HierarchyRequest, HierarchyRequests};
@@ -8,14 +11,17 @@ use pyo3::prelude::{PyAnyMethods, PyDictMethods, PyListMethods};
 use pyo3::types::{IntoPyDict, PyDict, PyList, PyTuple};
 use pyo3::{Bound, PyAny, PyResult, Python};
 use std::collections::HashMap;
+use std::rc::Rc;
 
 /// Builds a PlatformConfig Rust struct from a Python resource set.
 pub fn build_platform_config(py_res_set: Bound<PyAny>, config: Configuration) -> PlatformConfig {
     let resource_set = build_resource_set(&py_res_set);
     let quotas_config = platform::build_quotas_config(&config, &resource_set);
+    let queues_quotas_config = platform::build_queues_quotas_config(&config, &resource_set);
 
     PlatformConfig {
         quotas_config,
+        queues_quotas_config,
         resource_set,
         config,
     }
@@ -67,14 +73,18 @@ fn build_resource_set(py_res_set: &Bound<PyAny>) -> ResourceSet {
     ResourceSet {
         nb_resources_not_dead: default_resources.core_count(),
         nb_resources_default_not_dead: default_resources.core_count(),
+        weighted_resources_not_dead: default_resources.core_count() as u64,
+        weighted_resources_default_not_dead: default_resources.core_count() as u64,
         suspendable_resources: ProcSet::new(),
         default_resources,
         available_upto,
+        available_from: vec![], // Absent resources are handled by the Python resource set
         hierarchy: Hierarchy::new_defined(partitions, unit_partitions),
+        core_weight_prefix_sums: vec![], // Resource weights are not provided by the Python resource set
     }
 }
 /// Builds a Rust ProcSet (range-set-blaze lib) from a Python ProcSet (procset lib).
-fn build_proc_set(py_proc_set: &Bound<PyAny>) -> ProcSet {
+pub fn build_proc_set(py_proc_set: &Bound<PyAny>) -> ProcSet {
     py_proc_set
         .py()
         .eval(
@@ -121,7 +131,8 @@ fn build_proc_sets(py_proc_sets: &Bound<PyAny>) -> Box<[ProcSet]> {
         .collect::<Box<[ProcSet]>>()
 }
 /// Transforms a Python job object into a Rust Job struct.
-pub fn build_job(py_job: &Bound<PyAny>) -> Job {
+/// The moldables without walltime get the walltime estimated by `walltime_estimator`.
+pub fn build_job(py_job: &Bound<PyAny>, walltime_estimator: &Rc<dyn WalltimeEstimator>) -> Job {
     let name: Option<String> = py_job.getattr("name").unwrap().extract().unwrap();
     let user: Option<String> = py_job.getattr("user").unwrap().extract().unwrap();
     let project: Option<String> = py_job.getattr("project").unwrap().extract().unwrap();
@@ -185,7 +196,7 @@ pub fn build_job(py_job: &Bound<PyAny>) -> Job {
         let walltime: Option<i64> = py_job.getattr("walltime").unwrap().extract().unwrap();
         if let (Some(begin), Some(walltime)) = (begin, walltime) {
             if walltime > 0 {
-                let end: i64 = begin + walltime - 1;
+                let end: i64 = Job::compute_end(begin, walltime);
 
                 let proc_set: ProcSet = build_proc_set(&py_job.getattr("res_set").unwrap());
 
@@ -213,7 +224,8 @@ pub fn build_job(py_job: &Bound<PyAny>) -> Job {
     }
 
     // Dependencies (scheduled jobs do not have mdl_res_rqts defined)
-    let dependencies: Vec<(i64, Box<str>, Option<i32>)> = if py_job.hasattr("deps").unwrap() {
+    // An optional fourth item is the dependency type, `legacy` by default (see `DependencyType::Legacy`)
+    let dependencies: Vec<JobDependency> = if py_job.hasattr("deps").unwrap() {
         py_job
             .getattr("deps")
             .unwrap()
@@ -225,7 +237,11 @@ pub fn build_job(py_job: &Bound<PyAny>) -> Job {
                 let id: i64 = dep.get_item(0).unwrap().extract().unwrap();
                 let name: String = dep.get_item(1).unwrap().extract().unwrap();
                 let state: Option<i32> = dep.get_item(2).unwrap().extract().unwrap();
-                Ok((id, name.into_boxed_str(), state))
+                let dep_type = match dep.get_item(3) {
+                    Ok(dep_type) => DependencyType::parse(&dep_type.extract::<String>()?),
+                    Err(_) => DependencyType::Legacy,
+                };
+                Ok((id, name.into_boxed_str(), state, dep_type))
             })
             .collect::<PyResult<_>>()
             .unwrap()
@@ -236,28 +252,29 @@ pub fn build_job(py_job: &Bound<PyAny>) -> Job {
     // no_quotas
     let no_quotas: bool = py_job.getattr_opt("no_quotas").unwrap().map(|o| o.extract()).unwrap_or(Ok(false)).unwrap();
 
-    Job {
-        id: py_job.getattr("id").unwrap().extract::<i64>().unwrap(),
-        name: name.map(|n| n.into_boxed_str()),
-        user: user.map(|u| u.into_boxed_str()),
-        project: project.map(|p| p.into_boxed_str()),
-        queue: queue.into_boxed_str(),
-        types,
-        moldables,
-        no_quotas,
-        assignment,
-        quotas_hit_count: 0,
-        time_sharing,
-        placeholder,
-        dependencies,
-        advance_reservation_begin: advance_reservation_start_time,
-        submission_time: py_job.getattr_opt("submission_time").unwrap().map(|v| v.extract::<i64>()).unwrap_or(Ok(0)).unwrap(),
-        qos: py_job.getattr_opt("qos").unwrap().map(|v| v.extract::<f64>()).unwrap_or(Ok(0.0)).unwrap(),
-        nice: py_job.getattr_opt("nice").unwrap().map(|v| v.extract::<f64>()).unwrap_or(Ok(1.0)).unwrap(),
-        karma: 0.0,
-        message: String::new(),
-        state: "".into(), // State is not used in the core
+    let mut job_builder = JobBuilder::new(py_job.getattr("id").unwrap().extract::<i64>().unwrap())
+        .name_opt(name.map(|n| n.into_boxed_str()))
+        .user_opt(user.map(|u| u.into_boxed_str()))
+        .project_opt(project.map(|p| p.into_boxed_str()))
+        .queue(queue.into_boxed_str())
+        .types(types)
+        .moldables(moldables)
+        .assign_opt(assignment)
+        .time_sharing_opt(time_sharing)
+        .placeholder(placeholder)
+        .dependencies(dependencies)
+        .submission_time(py_job.getattr_opt("submission_time").unwrap().map(|v| v.extract::<i64>()).unwrap_or(Ok(0)).unwrap())
+        .array_id_opt(py_job.getattr_opt("array_id").unwrap().map(|v| v.extract::<Option<i64>>()).unwrap_or(Ok(None)).unwrap())
+        .state("".into()) // State is only used in the core to release the resources of suspended jobs, done by the Python platform
+        .walltime_estimator(walltime_estimator.clone());
+    if let Some(advance_reservation_start_time) = advance_reservation_start_time {
+        job_builder = job_builder.set_advance_reservation_start_time(advance_reservation_start_time);
     }
+    let mut job = job_builder.build();
+    job.no_quotas = no_quotas;
+    job.qos = py_job.getattr_opt("qos").unwrap().map(|v| v.extract::<f64>()).unwrap_or(Ok(0.0)).unwrap();
+    job.nice = py_job.getattr_opt("nice").unwrap().map(|v| v.extract::<f64>()).unwrap_or(Ok(1.0)).unwrap();
+    job
 }
 /// Builds a Moldable Rust struct from a Python moldable object.
 fn build_moldable(py_moldable: &Bound<PyAny>) -> Moldable {
@@ -281,16 +298,21 @@ fn build_moldable(py_moldable: &Bound<PyAny>) -> Moldable {
                     let level_nb_tuple = level_nb_tuple.downcast::<PyTuple>().unwrap();
                     let level_name: String = level_nb_tuple.get_item(0).unwrap().extract().unwrap();
                     let level_nb: u32 = level_nb_tuple.get_item(1).unwrap().extract().unwrap();
-                    Ok((level_name.into_boxed_str(), level_nb))
+                    // Optional third item: maximum number of resources, for a range request.
+                    let level_max_nb: u32 = level_nb_tuple.get_item(2).map(|max| max.extract().unwrap()).unwrap_or(level_nb);
+                    Ok((level_name.into_boxed_str(), level_nb, level_max_nb))
                 })
                 .collect::<PyResult<Vec<_>>>()
                 .unwrap();
             let filter = build_proc_set(&req.get_item(1).unwrap());
 
-            Ok(HierarchyRequest::new(filter, level_nbs))
+            Ok(HierarchyRequest::new_range(filter, level_nbs))
         })
         .collect::<PyResult<Vec<_>>>()
         .unwrap();
 
-    Moldable::new(id, walltime, HierarchyRequests::from_requests(requests))
+    // Optional fourth item: resources excluded from the moldable.
+    let excluded_resources = py_moldable.get_item(3).map(|excluded| build_proc_set(&excluded)).unwrap_or_default();
+
+    Moldable::new(id, walltime, HierarchyRequests::from_requests(requests)).with_excluded_resources(excluded_resources)
 }
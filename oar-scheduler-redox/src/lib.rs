@@ -3,14 +3,19 @@ mod platform;
 #[cfg(test)]
 mod test;
 
+use crate::converters::{build_proc_set, proc_set_to_python};
 use crate::platform::Platform;
 use indexmap::IndexMap;
-use log::{warn, LevelFilter};
-use oar_scheduler_core::model::job::{Job, JobAssignment, ProcSetCoresOp};
+use log::{info, warn, LevelFilter};
+use oar_scheduler_core::logging;
+use oar_scheduler_core::model::job::{assignments_to_table, Job, JobAssignment};
 use oar_scheduler_core::platform::PlatformTrait;
-use oar_scheduler_core::scheduler::slotset::SlotSet;
-use oar_scheduler_core::scheduler::{kamelot, quotas};
+use oar_scheduler_core::scheduler::slotset::{SlotSet, SplitError};
+use oar_scheduler_core::scheduler::kamelot::SchedulingStats;
+use oar_scheduler_core::scheduler::kamelot;
+use oar_scheduler_core::scheduler::scheduling::{self, ReservationWindow};
 use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyList};
 use std::cell::RefCell;
 use std::collections::HashMap;
 
@@ -22,8 +27,11 @@ fn oar_scheduler_redox(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(build_redox_slot_sets, m)?)?;
     m.add_function(wrap_pyfunction!(schedule_cycle_internal, m)?)?;
     m.add_function(wrap_pyfunction!(check_reservation_jobs, m)?)?;
+    m.add_function(wrap_pyfunction!(proc_set_to_bitmap, m)?)?;
+    m.add_function(wrap_pyfunction!(proc_set_from_bitmap, m)?)?;
 
-    env_logger::Builder::new().filter(None, LevelFilter::Info).init();
+    // The log level defaults to info and can be set with the OAR_SCHEDULER_LOG_LEVEL environment variable
+    logging::init(logging::level_from_env(LevelFilter::Info), &[]);
 
     // Register plugin hooks from the oar-scheduler-hooks crate into the oar-scheduler-core crate
     if let Some(hooks) = oar_scheduler_hooks::Hooks::new() {
@@ -35,8 +43,15 @@ fn oar_scheduler_redox(m: &Bound<'_, PyModule>) -> PyResult<()> {
 
 /// Schedules the jobs from the platform and saves the assignments back to the platform.
 /// Should be called in external scheduler mode.
+/// Returns the statistics of the cycle as a dict.
 #[pyfunction]
-fn schedule_cycle_external(py_session: Bound<PyAny>, py_config: Bound<PyAny>, py_platform: Bound<PyAny>, py_now: Bound<PyAny>, py_queues: Bound<PyAny>) -> PyResult<()> {
+fn schedule_cycle_external(
+    py_session: Bound<PyAny>,
+    py_config: Bound<PyAny>,
+    py_platform: Bound<PyAny>,
+    py_now: Bound<PyAny>,
+    py_queues: Bound<PyAny>,
+) -> PyResult<SchedulingStats> {
     // Extracting the platform (including the resource set, quotas config, and waiting jobs)
     let mut platform = Platform::from_python(&py_platform, &py_session, &py_config, &py_now, None);
 
@@ -45,9 +60,7 @@ fn schedule_cycle_external(py_session: Bound<PyAny>, py_config: Bound<PyAny>, py
 
     // Scheduling (Platform automatically calls py_platform.save_assigns upon saving scheduled jobs.)
     let queues: Vec<String> = py_queues.extract().unwrap();
-    kamelot::schedule_cycle(&mut platform, &queues);
-
-    Ok(())
+    Ok(kamelot::schedule_cycle(&mut platform, &queues))
 }
 
 /// PlatformHandle is not thread-safe and cannot be sent across threads.
@@ -61,9 +74,15 @@ struct PlatformHandle {
 #[pyclass(unsendable)]
 struct SlotSetsHandle {
     inner: RefCell<HashMap<Box<str>, SlotSet>>,
+    /// Already scheduled besteffort jobs inserted in the slot sets that can still be preempted,
+    /// only with `SCHEDULER_BESTEFFORT_PREEMPTION`.
+    besteffort_jobs: RefCell<Vec<Job>>,
 }
 
+/// Builds a platform handle. With `dry_run=True`, `check_reservation_jobs` does not modify the database
+/// and only prints the would-be assignments.
 #[pyfunction]
+#[pyo3(signature = (py_session, py_config, py_platform, py_now, py_scheduled_jobs, dry_run = false))]
 fn build_redox_platform(
     py: Python,
     py_session: Bound<PyAny>,
@@ -71,8 +90,10 @@ fn build_redox_platform(
     py_platform: Bound<PyAny>,
     py_now: Bound<PyAny>,
     py_scheduled_jobs: Bound<PyAny>,
+    dry_run: bool,
 ) -> PyResult<Py<PlatformHandle>> {
-    let platform = Platform::from_python(&py_platform, &py_session, &py_config, &py_now, Some(&py_scheduled_jobs));
+    let mut platform = Platform::from_python(&py_platform, &py_session, &py_config, &py_now, Some(&py_scheduled_jobs));
+    platform.set_dry_run(dry_run);
     Py::new(
         py,
         PlatformHandle {
@@ -81,24 +102,45 @@ fn build_redox_platform(
     )
 }
 
+/// Builds the slot sets shared by the queues scheduled with `schedule_cycle_internal` in this cycle.
+/// If `py_queues` lists these queues, the slot sets end at their scheduling horizon (see [`kamelot::init_slot_sets_with_horizons`]).
+/// The already scheduled besteffort jobs are only inserted with `SCHEDULER_BESTEFFORT_PREEMPTION`,
+/// `schedule_cycle_internal` inserting them otherwise when scheduling the `besteffort` queue alone.
 #[pyfunction]
-fn build_redox_slot_sets(platform: Bound<PlatformHandle>) -> PyResult<Py<SlotSetsHandle>> {
+#[pyo3(signature = (platform, py_queues = None))]
+fn build_redox_slot_sets(platform: Bound<PlatformHandle>, py_queues: Option<Bound<PyAny>>) -> PyResult<Py<SlotSetsHandle>> {
     let py = platform.py();
     let platform_handle_ref = platform.borrow();
     let platform = platform_handle_ref.inner.borrow();
+    let queues: Vec<String> = py_queues.map(|py_queues| py_queues.extract()).transpose()?.unwrap_or_default();
 
-    let (slot_sets, _besteffort_jobs) = kamelot::init_slot_sets(&*platform, false);
+    let config = &platform.get_platform_config().config;
+    let preemption = config.scheduler_besteffort_preemption;
+    let queue_horizons = config.queue_horizons();
+    let (slot_sets, besteffort_jobs) = kamelot::init_slot_sets_with_horizons(&*platform, preemption, &queues, &queue_horizons);
 
     Py::new(
         py,
         SlotSetsHandle {
             inner: RefCell::new(slot_sets),
+            besteffort_jobs: RefCell::new(besteffort_jobs),
         },
     )
 }
 
+/// Schedules the waiting jobs of `py_queues` on the platform and slot sets handles.
+/// Should be called in internal scheduler mode.
+/// Returns a dict with the statistics of the cycle (`stats`), the newly assigned jobs (`assigned`),
+/// as `{job_id: {begin, end, moldable_id, resources}}`, and the jobs that could not be assigned (`rejected`),
+/// as a list of `(job_id, reason)` tuples. With `SCHEDULER_BESTEFFORT_PREEMPTION`, the ids of the besteffort jobs
+/// preempted by the cycle, which should be killed, are listed in `preempted`.
 #[pyfunction]
-fn schedule_cycle_internal(platform: Bound<PlatformHandle>, slot_sets: Bound<SlotSetsHandle>, py_queues: Bound<PyAny>) -> PyResult<()> {
+fn schedule_cycle_internal<'py>(
+    platform: Bound<'py, PlatformHandle>,
+    slot_sets: Bound<'py, SlotSetsHandle>,
+    py_queues: Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let py = platform.py();
     let platform_handle_ref = platform.borrow_mut();
     let mut platform = platform_handle_ref.inner.borrow_mut();
     let slot_sets_handle_ref = slot_sets.borrow();
@@ -108,13 +150,49 @@ fn schedule_cycle_internal(platform: Bound<PlatformHandle>, slot_sets: Bound<Slo
     // Loading the waiting jobs from the python platform into the rust platform for these specific queues
     platform.load_waiting_jobs(&py_queues, None);
 
-    // Insert scheduled besteffort jobs if py_queues = ['besteffort'].
-    if queues.len() == 1 && queues[0] == "besteffort" {
+    // Insert scheduled besteffort jobs if py_queues = ['besteffort'], unless they are already inserted for the preemption.
+    let preemption = platform.get_platform_config().config.scheduler_besteffort_preemption;
+    if kamelot::is_besteffort_only(&queues) && !preemption {
         kamelot::add_already_scheduled_jobs_to_slot_set(&mut *slot_sets, &mut *platform, true, false);
     }
 
-    kamelot::internal_schedule_cycle(&mut *platform, &mut *slot_sets, &queues);
-    Ok(())
+    // Drop the events of the previous cycles, so that only the decisions of this cycle are returned.
+    platform.take_cycle_events();
+    let mut besteffort_jobs = slot_sets_handle_ref.besteffort_jobs.borrow_mut();
+    let (stats, preempted) = kamelot::internal_schedule_cycle_with_preemption(&mut *platform, &mut slot_sets, &queues, &besteffort_jobs);
+    // The preempted jobs resources are given back to the slot sets, so they can't be preempted again by the next queues.
+    besteffort_jobs.retain(|job| !preempted.contains(&job.id));
+
+    cycle_result_to_python(py, &platform, stats, &preempted)
+}
+
+/// Builds the dict returned by `schedule_cycle_internal` from the scheduling events recorded by the platform.
+fn cycle_result_to_python<'py>(py: Python<'py>, platform: &Platform, stats: SchedulingStats, preempted: &[i64]) -> PyResult<Bound<'py, PyDict>> {
+    let scheduled_jobs: HashMap<i64, &Job> = platform.get_scheduled_jobs_ref().iter().map(|job| (job.id, job)).collect();
+    let py_assigned = PyDict::new(py);
+    let py_rejected = PyList::empty(py);
+    for event in platform.take_cycle_events() {
+        if let Some(reason) = &event.rejection_reason {
+            py_rejected.append((event.job_id, reason.as_ref()))?;
+            continue;
+        }
+        let Some(assignment) = scheduled_jobs.get(&event.job_id).and_then(|job| job.assignment.as_ref()) else {
+            continue;
+        };
+        let py_job = PyDict::new(py);
+        py_job.set_item("begin", assignment.begin)?;
+        py_job.set_item("end", assignment.end)?;
+        py_job.set_item("moldable_id", event.moldable_id)?;
+        py_job.set_item("resources", proc_set_to_python(py, &assignment.resources))?;
+        py_assigned.set_item(event.job_id, py_job)?;
+    }
+
+    let result = PyDict::new(py);
+    result.set_item("stats", stats)?;
+    result.set_item("assigned", py_assigned)?;
+    result.set_item("rejected", py_rejected)?;
+    result.set_item("preempted", PyList::new(py, preempted)?)?;
+    Ok(result)
 }
 
 #[pyfunction]
@@ -124,7 +202,6 @@ fn check_reservation_jobs(platform: Bound<PlatformHandle>, slot_sets: Bound<Slot
     let mut platform = platform_handle_ref.inner.borrow_mut();
 
     let platform_config = platform.get_platform_config();
-    let job_security_time = platform_config.config.scheduler_job_security_time;
     let now = platform.get_now();
     let job_handling = PyModule::import(py, "oar.lib.job_handling").expect("Could not import job_handling");
     let slot_sets_handle_ref = slot_sets.borrow();
@@ -146,8 +223,8 @@ fn check_reservation_jobs(platform: Bound<PlatformHandle>, slot_sets: Bound<Slot
 
         // Check if reservation is too old
         let mut start_time = job.advance_reservation_begin.unwrap();
-        let end_time = start_time + moldable.walltime - 1;
-        if now > start_time + moldable.walltime {
+        let end_time = Job::compute_end(start_time, moldable.walltime);
+        if now > end_time {
             set_job_resa_not_scheduled(&job_handling, &platform, job.id, "Reservation expired and couldn't be started.");
             continue;
         } else if start_time < now {
@@ -157,53 +234,75 @@ fn check_reservation_jobs(platform: Bound<PlatformHandle>, slot_sets: Bound<Slot
         let ss_name = job.slot_set_name();
         let slot_set = slot_sets.get_mut(&*ss_name).expect("SlotSet not found");
 
-        let effective_end = end_time - job_security_time;
-        let (left_slot_id, right_slot_id) = match slot_set.get_encompassing_range(start_time, effective_end, None) {
-            Some((s1, s2)) => (s1.id(), s2.id()),
-            None => {
+        let job_security_time = slot_set.get_platform_config().config.job_security_time(&job.queue);
+        let effective_end = moldable.effective_end(job.advance_reservation_begin.unwrap(), job_security_time);
+        let (delay, proc_set) = match scheduling::find_reservation_window(slot_set, &job, start_time, end_time, effective_end) {
+            ReservationWindow::Found { delay, resources } => (delay, resources),
+            ReservationWindow::AfterHorizon { .. } => {
                 // Skipping, reservation might be after max_time.
                 warn!("Job {} cannot be scheduled: no slots available for the requested time range.", job.id);
                 continue;
             }
-        };
-
-        // Time-sharing and placeholder
-        let empty: Box<str> = "".into();
-        let (ts_user_name, ts_job_name) = job.time_sharing.as_ref().map_or((None, None), |_| {
-            (Some(job.user.as_ref().unwrap_or(&empty)), Some(job.name.as_ref().unwrap_or(&empty)))
-        });
-        let available_resources = slot_set.intersect_slots_intervals(left_slot_id, right_slot_id, ts_user_name, ts_job_name, &job.placeholder);
-
-        let res = slot_set
-            .get_platform_config()
-            .resource_set
-            .hierarchy
-            .request(&available_resources, &moldable.requests);
-
-        if let Some(proc_set) = res {
-            if slot_set.get_platform_config().quotas_config.enabled && !job.no_quotas {
-                let slots = slot_set.iter().between(left_slot_id, right_slot_id);
-                if let Some((_msg, _rule, _limit)) = quotas::check_slots_quotas(slots, &job, start_time, end_time, proc_set.core_count()) {
-                    set_job_resa_scheduled(&job_handling, &platform, job.id, Some("This AR cannot run: quotas exceeded"));
-                    continue;
-                }
+            ReservationWindow::InsufficientResources => {
+                set_job_resa_scheduled(&job_handling, &platform, job.id, Some("This AR cannot run: not enough resources"));
+                continue;
+            }
+            ReservationWindow::QuotasExceeded { .. } => {
+                set_job_resa_scheduled(&job_handling, &platform, job.id, Some("This AR cannot run: quotas exceeded"));
+                continue;
             }
+        };
+        let (start_time, end_time) = (start_time + delay, end_time + delay);
 
-            job.assignment = Some(JobAssignment::new(start_time, end_time, proc_set, 0));
-            slot_set.split_slots_for_job_and_update_resources(&job, true, true, None);
-            set_job_resa_scheduled(&job_handling, &platform, job.id, None);
-            assigned_jobs.insert(job.id, job);
-        } else {
-            set_job_resa_scheduled(&job_handling, &platform, job.id, Some("This AR cannot run: not enough resources"));
+        // Reservation times are untrusted: an invalid split rejects the reservation instead of crashing the cycle
+        // Once the maximum number of slots is reached, the reservation is inserted without splitting the slots
+        if let Err(e) = slot_set.try_split_slots_for_range(start_time, end_time, None)
+            && !matches!(e, SplitError::MaxSlotsReached(_))
+        {
+            warn!("Job {} cannot be scheduled: {}", job.id, e);
+            set_job_resa_scheduled(&job_handling, &platform, job.id, Some("This AR cannot run: invalid reservation time range"));
             continue;
         }
+        job.assignment = Some(JobAssignment::new(start_time, end_time, proc_set, 0));
+        slot_set.split_slots_for_job_and_update_resources(&job, true, true, None);
+        if delay > 0 {
+            let message = format!("This AR is delayed by {}s: the requested window is occupied", delay);
+            set_job_resa_start_time(&job_handling, &platform, &job, start_time);
+            set_job_resa_state(&job_handling, &platform, job.id, "toAckReservation", Some(&message), true);
+        } else {
+            set_job_resa_scheduled(&job_handling, &platform, job.id, None);
+        }
+        assigned_jobs.insert(job.id, job);
     }
-    if !assigned_jobs.is_empty() {
+    if platform.is_dry_run() {
+        info!("Dry run: reservations not saved, would-be assignments:");
+        assignments_to_table(assigned_jobs.values()).printstd();
+    } else if !assigned_jobs.is_empty() {
         platform.save_assignments(assigned_jobs);
     }
 }
 
+/// Exports a Python ProcSet as a dense bit array of `n_resources` bits returned as `bytes`,
+/// e.g., to be loaded with `numpy.unpackbits(bitmap, bitorder="little")`.
+/// See [`oar_scheduler_core::platform::proc_set_to_bitmap`].
+#[pyfunction]
+fn proc_set_to_bitmap<'py>(py_proc_set: Bound<'py, PyAny>, n_resources: u32) -> Bound<'py, PyBytes> {
+    let proc_set = build_proc_set(&py_proc_set);
+    PyBytes::new(py_proc_set.py(), &oar_scheduler_core::platform::proc_set_to_bitmap(&proc_set, n_resources))
+}
+
+/// Parses a Python ProcSet from a dense bit array given as `bytes`, e.g., from `numpy.packbits(array, bitorder="little")`.
+/// See [`oar_scheduler_core::platform::proc_set_from_bitmap`].
+#[pyfunction]
+fn proc_set_from_bitmap<'py>(py: Python<'py>, bitmap: &[u8]) -> Bound<'py, PyAny> {
+    proc_set_to_python(py, &oar_scheduler_core::platform::proc_set_from_bitmap(bitmap))
+}
+
 fn set_job_resa_state(job_handling: &Bound<PyModule>, platform: &Platform, job_id: i64, state: &str, message: Option<&str>, scheduled: bool) {
+    if platform.is_dry_run() {
+        info!("Dry run: reservation job {} would be set to {} ({})", job_id, state, message.unwrap_or("no message"));
+        return;
+    }
     job_handling
         .getattr("set_job_state")
         .unwrap()
@@ -224,6 +323,18 @@ fn set_job_resa_state(job_handling: &Bound<PyModule>, platform: &Platform, job_i
             .unwrap();
     }
 }
+/// Moves the start time of the delayed reservation `job` to its new `start_time`.
+fn set_job_resa_start_time(job_handling: &Bound<PyModule>, platform: &Platform, job: &Job, start_time: i64) {
+    if platform.is_dry_run() {
+        info!("Dry run: reservation job {} would start at {}", job.id, start_time);
+        return;
+    }
+    job_handling
+        .getattr("set_job_start_time_assigned_moldable_id")
+        .unwrap()
+        .call1((platform.get_py_session(), job.id, start_time, job.moldables[0].id))
+        .unwrap();
+}
 fn set_job_resa_scheduled(job_handling: &Bound<PyModule>, platform: &Platform, job_id: i64, error: Option<&str>) {
     if let Some(error) = error {
         set_job_resa_state(job_handling, platform, job_id, "toError", Some(error), true);
@@ -2,10 +2,13 @@ use crate::converters::{build_job, build_platform_config, proc_set_to_python};
 use indexmap::{indexmap, IndexMap};
 use oar_scheduler_core::model::configuration::Configuration;
 use oar_scheduler_core::model::job::Job;
+use oar_scheduler_core::model::walltime::WalltimeEstimator;
 use oar_scheduler_core::platform::{PlatformConfig, PlatformTrait};
+use oar_scheduler_core::scheduler::observer::{SchedulingEvent, SchedulingObserver};
 use pyo3::prelude::{PyAnyMethods, PyDictMethods, PyListMethods};
 use pyo3::types::{PyDict, PyList, PyTuple};
 use pyo3::{Bound, Py, PyAny, PyResult, Python};
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
@@ -22,6 +25,23 @@ pub struct Platform {
     py_session: Py<PyAny>,
     py_config: Py<PyAny>,
     py_res_set: Py<PyAny>,
+
+    /// When true, reservation states and assignments are not saved back to the Python platform (see `check_reservation_jobs`).
+    dry_run: bool,
+    /// Scheduling decisions recorded since the last call to `Platform::take_cycle_events`.
+    cycle_events: CycleEvents,
+}
+
+/// Observer keeping the scheduling events in memory, to be returned to Python at the end of a cycle.
+#[derive(Default)]
+struct CycleEvents {
+    events: RefCell<Vec<SchedulingEvent>>,
+}
+
+impl SchedulingObserver for CycleEvents {
+    fn on_decision(&self, event: &SchedulingEvent) {
+        self.events.borrow_mut().push(event.clone());
+    }
 }
 
 impl PlatformTrait for Platform {
@@ -184,6 +204,10 @@ impl PlatformTrait for Platform {
             (asked, used)
         })
     }
+
+    fn get_scheduling_observer(&self) -> Option<&dyn SchedulingObserver> {
+        Some(&self.cycle_events)
+    }
 }
 
 impl Platform {
@@ -198,7 +222,7 @@ impl Platform {
                         py_job.setattr("start_time", sd.begin).unwrap();
                         py_job.setattr("walltime", sd.end - sd.begin + 1).unwrap();
                         py_job.setattr("end_time", sd.end).unwrap();
-                        py_job.setattr("moldable_id", job.moldables[sd.moldable_index].id).unwrap();
+                        py_job.setattr("moldable_id", sd.moldable_id(&job.moldables)).unwrap();
                         py_job.setattr("res_set", proc_set_to_python(py_job.py(), &sd.resources)).unwrap();
                         py_job.setattr("karma", job.karma).unwrap();
                         py_scheduled_jobs.set_item(py_job_id, py_job).unwrap();
@@ -217,6 +241,7 @@ impl Platform {
 
         let now: i64 = py_now.extract().unwrap();
         let config: Configuration = py_config.extract().unwrap();
+        let walltime_estimator: Rc<dyn WalltimeEstimator> = Rc::new(config.walltime_estimator());
 
         // Get the resource set
         let kwargs = PyDict::new(py_platform.py());
@@ -225,30 +250,50 @@ impl Platform {
         let py_res_set: Bound<PyAny> = py_platform.getattr("resource_set").unwrap().call((), Some(&kwargs)).unwrap();
 
         // Get already scheduled jobs
-        let py_scheduled_jobs = if let Some(py_scheduled_jobs) = py_scheduled_jobs {
-            py_scheduled_jobs.clone()
-        }else {
-            py_platform
-                .getattr("get_scheduled_jobs")
-                .unwrap()
-                .call((py_session, &py_res_set, &config.scheduler_job_security_time, &py_now), None)
+        let scheduled_jobs = if let Some(py_scheduled_jobs) = py_scheduled_jobs {
+            py_scheduled_jobs
+                .downcast::<PyList>()
                 .unwrap()
+                .iter()
+                .map(|py_job| build_job(&py_job, &walltime_estimator))
+                .collect::<Vec<Job>>()
+        } else {
+            // The jobs are fetched once per security time, keeping for each call the jobs of the queues using it.
+            config
+                .distinct_job_security_times()
+                .into_iter()
+                .flat_map(|security_time| {
+                    let py_scheduled_jobs = py_platform
+                        .getattr("get_scheduled_jobs")
+                        .unwrap()
+                        .call((py_session, &py_res_set, &security_time, &py_now), None)
+                        .unwrap();
+                    py_scheduled_jobs
+                        .downcast::<PyList>()
+                        .unwrap()
+                        .iter()
+                        .filter(|py_job| {
+                            let queue: String = py_job.getattr("queue_name").unwrap().extract().unwrap();
+                            config.job_security_time(&queue) == security_time
+                        })
+                        .map(|py_job| build_job(&py_job, &walltime_estimator))
+                        .collect::<Vec<Job>>()
+                })
+                .collect::<Vec<Job>>()
         };
-        let py_scheduled_jobs = py_scheduled_jobs.downcast::<PyList>().unwrap();
 
         Platform {
             now,
             platform_config: Rc::new(build_platform_config(py_res_set.clone(), config)),
-            scheduled_jobs: py_scheduled_jobs
-                .iter()
-                .map(|py_job| build_job(&py_job))
-                .collect::<Vec<Job>>(),
+            scheduled_jobs,
             waiting_jobs: None,
             py_waiting_jobs_map: None,
             py_platform: py_platform.clone().unbind(),
             py_session: py_session.clone().unbind(),
             py_config: py_config.clone().unbind(),
             py_res_set: py_res_set.unbind(),
+            dry_run: false,
+            cycle_events: CycleEvents::default(),
         }
     }
 
@@ -282,21 +327,33 @@ impl Platform {
         }
 
         let py_waiting_jobs_ids = py_waiting_jobs_tuple.downcast::<PyTuple>().unwrap().get_item(1).unwrap();
-        self.py_platform
-            .getattr(py, "get_data_jobs")
-            .unwrap()
-            .call(
-                py,
-                (
-                    &self.py_session,
-                    &py_waiting_jobs_map,
-                    &py_waiting_jobs_ids,
-                    &self.py_res_set,
-                    &self.platform_config.config.scheduler_job_security_time,
-                ),
-                None,
-            )
-            .unwrap();
+
+        // Load the jobs data, calling `get_data_jobs` once per security time with the jobs of the queues using it.
+        let config = &self.platform_config.config;
+        let mut job_ids_by_security_time: IndexMap<i64, Vec<i64>> = IndexMap::new();
+        for py_job_id in py_waiting_jobs_ids.try_iter().unwrap() {
+            let job_id: i64 = py_job_id.unwrap().extract().unwrap();
+            let py_job = py_waiting_jobs_map.get_item(job_id).unwrap().unwrap();
+            let queue: String = py_job.getattr("queue_name").unwrap().extract().unwrap();
+            job_ids_by_security_time.entry(config.job_security_time(&queue)).or_default().push(job_id);
+        }
+        for (security_time, job_ids) in job_ids_by_security_time {
+            self.py_platform
+                .getattr(py, "get_data_jobs")
+                .unwrap()
+                .call(
+                    py,
+                    (
+                        &self.py_session,
+                        &py_waiting_jobs_map,
+                        PyList::new(py, job_ids).unwrap(),
+                        &self.py_res_set,
+                        security_time,
+                    ),
+                    None,
+                )
+                .unwrap();
+        }
 
         // Sort waiting jobs
         let py_sorted_waiting_job_ids = py_waiting_jobs_ids;
@@ -317,6 +374,7 @@ impl Platform {
         //     .unwrap();
 
         // Create Rust IndexMap from Python jobs
+        let walltime_estimator: Rc<dyn WalltimeEstimator> = Rc::new(self.platform_config.config.walltime_estimator());
         self.waiting_jobs = Some(
             py_sorted_waiting_job_ids
                 .downcast::<PyList>()
@@ -325,17 +383,30 @@ impl Platform {
                 .map(|py_id| {
                     let id: i64 = py_id.extract().unwrap();
                     let py_job = py_waiting_jobs_map.get_item(py_id).unwrap().unwrap();
-                    Ok((id, build_job(&py_job)))
+                    Ok((id, build_job(&py_job, &walltime_estimator)))
                 })
                 .collect::<PyResult<IndexMap<i64, Job>>>()
                 .unwrap(),
         );
     }
 
+    pub(crate) fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+    pub(crate) fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
     pub(crate) fn get_py_session(&self) -> &Py<PyAny> {
         &self.py_session
     }
     pub(crate) fn get_py_config(&self) -> &Py<PyAny> {
         &self.py_config
     }
+    pub(crate) fn get_scheduled_jobs_ref(&self) -> &[Job] {
+        &self.scheduled_jobs
+    }
+    /// Returns the scheduling events recorded since the last call, and clears them.
+    pub(crate) fn take_cycle_events(&self) -> Vec<SchedulingEvent> {
+        self.cycle_events.events.take()
+    }
 }